@@ -0,0 +1,118 @@
+use std::sync::{Arc, Mutex};
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use delax::{
+    delay_engine::engine::{DelayEngine, DelayInterpolationMode},
+    filter_pipeline::pipeline::FilterPipeline,
+    filters::simper::{SimperSinSVF, StereoSimperSinSVF},
+    Delax,
+};
+
+const SAMPLE_RATE: f32 = 44100.;
+const BLOCK_SIZE: usize = 512;
+
+/// A block of synthetic input, a sine with a bit of a second harmonic so the filters have something to do
+fn input_block() -> Vec<f32> {
+    (0..BLOCK_SIZE)
+        .map(|i| {
+            let t = i as f32 / SAMPLE_RATE;
+            (t * 440. * std::f32::consts::TAU).sin() * 0.5
+                + (t * 880. * std::f32::consts::TAU).sin() * 0.1
+        })
+        .collect()
+}
+
+fn delay_engine(c: &mut Criterion) {
+    let input = input_block();
+    let mut group = c.benchmark_group("delay_engine");
+    group.throughput(Throughput::Elements(BLOCK_SIZE as u64));
+
+    let mut engine = DelayEngine::new(SAMPLE_RATE as usize, SAMPLE_RATE);
+    engine.set_delay_amount(500.);
+    group.bench_function("write_sample", |b| {
+        b.iter(|| {
+            for sample in &input {
+                engine.write_sample(black_box(*sample));
+            }
+        })
+    });
+
+    group.bench_function("interpolate_sample_nearest", |b| {
+        b.iter(|| {
+            for _ in 0..BLOCK_SIZE {
+                black_box(engine.interpolate_sample(DelayInterpolationMode::Nearest));
+            }
+        })
+    });
+
+    group.finish();
+}
+
+fn svf(c: &mut Criterion) {
+    let input = input_block();
+    let mut group = c.benchmark_group("svf");
+    group.throughput(Throughput::Elements(BLOCK_SIZE as u64));
+
+    let mut filter = SimperSinSVF::new(SAMPLE_RATE);
+    group.bench_function("sin_svf_tick_sample", |b| {
+        b.iter(|| {
+            for sample in &input {
+                black_box(filter.tick_sample(black_box(*sample)));
+            }
+        })
+    });
+
+    group.finish();
+}
+
+fn filter_pipeline(c: &mut Criterion) {
+    let input = input_block();
+    let mut group = c.benchmark_group("filter_pipeline");
+    group.throughput(Throughput::Elements(BLOCK_SIZE as u64));
+
+    let mut pipeline = FilterPipeline::new();
+    pipeline.register_stereo_pair(
+        Arc::new(Mutex::new(SimperSinSVF::new(SAMPLE_RATE))),
+        Arc::new(Mutex::new(SimperSinSVF::new(SAMPLE_RATE))),
+    );
+    group.bench_function("process_stereo_pair", |b| {
+        b.iter(|| {
+            for sample in &input {
+                black_box(pipeline.process_stereo(black_box(*sample), black_box(*sample)));
+            }
+        })
+    });
+
+    let mut pipeline = FilterPipeline::new();
+    pipeline.register_stereo(Arc::new(Mutex::new(StereoSimperSinSVF::new(SAMPLE_RATE))));
+    group.bench_function("process_stereo_simd", |b| {
+        b.iter(|| {
+            for sample in &input {
+                black_box(pipeline.process_stereo(black_box(*sample), black_box(*sample)));
+            }
+        })
+    });
+
+    group.finish();
+}
+
+fn process(c: &mut Criterion) {
+    let input = input_block();
+    let mut group = c.benchmark_group("process");
+    group.throughput(Throughput::Elements(BLOCK_SIZE as u64));
+
+    let mut plugin = Delax::default();
+    plugin.prepare(SAMPLE_RATE, 2);
+    group.bench_function("process_block", |b| {
+        b.iter(|| {
+            for sample in &input {
+                black_box(plugin.process_sample(black_box(*sample), black_box(*sample)));
+            }
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, delay_engine, svf, filter_pipeline, process);
+criterion_main!(benches);
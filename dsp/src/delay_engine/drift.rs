@@ -0,0 +1,117 @@
+use core::f32::consts::TAU;
+
+use crate::sample::Sample;
+
+/// The deepest the stereo drift can move the read positions, in ms to either side
+pub const MAX_DRIFT_MS: f32 = 2.;
+/// How long the offset glides over from where it was when the drift is retriggered, in ms
+pub const RETRIGGER_GLIDE_MS: f32 = 50.;
+
+/// A slow sine LFO that moves the read positions of the left and the right channel in opposite directions.
+///
+/// The channels drift apart by a few ms and back, which widens the repeats like a chorus. Only the reads of the
+/// delay lines move, so the dry signal is never flanged.
+///
+/// Usage:
+/// ```
+/// use delax_dsp::delay_engine::drift::StereoDrift;
+///
+/// let mut drift = StereoDrift::new(1000.);
+/// // A quarter of a 1 Hz cycle in, the left channel is a whole ms later and the right one a ms earlier
+/// let offset = (0..250).map(|_| drift.next(1., 1.)).last().unwrap();
+/// assert!((offset - 1.).abs() < 1e-3);
+/// assert_eq!(StereoDrift::channel_offset(offset, 1), -offset);
+/// ```
+pub struct StereoDrift {
+    sample_rate: f32,
+    /// The phase of the LFO from 0 to 1
+    phase: f32,
+    /// The offset the last call to [StereoDrift::next()] returned
+    offset: f32,
+    /// The offset from before the last retrigger, which is added on top and glides to 0
+    glide_from: f32,
+    /// How much of `glide_from` is left, from 1 to 0
+    glide: f32,
+}
+
+impl StereoDrift {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            sample_rate,
+            phase: 0.,
+            offset: 0.,
+            glide_from: 0.,
+            glide: 0.,
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+    }
+
+    /// The offset of the left channel in ms for the next sample, at a rate in Hz and a depth in ms.
+    /// The right channel moves by the opposite amount, see [StereoDrift::channel_offset()].
+    pub fn next(&mut self, rate: f32, depth: f32) -> f32 {
+        self.phase = Sample::fract(self.phase + rate / self.sample_rate);
+        self.glide = (self.glide - 1000. / (RETRIGGER_GLIDE_MS * self.sample_rate)).max(0.);
+
+        self.offset = Sample::sin(self.phase * TAU) * depth.clamp(0., MAX_DRIFT_MS)
+            + self.glide_from * self.glide;
+        self.offset
+    }
+
+    /// Start the LFO over at the beginning of its cycle, so the drift moves the same way every time, e.g. on every bar.
+    /// The offset glides over from where it was instead of jumping, which would click.
+    pub fn retrigger(&mut self) {
+        self.phase = 0.;
+        self.glide_from = self.offset;
+        self.glide = 1.;
+    }
+
+    /// The offset of a channel. Channels with an even index follow the left channel, the others the right one.
+    pub fn channel_offset(offset: f32, channel: usize) -> f32 {
+        if channel.is_multiple_of(2) {
+            offset
+        } else {
+            -offset
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.phase = 0.;
+        self.offset = 0.;
+        self.glide_from = 0.;
+        self.glide = 0.;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drift_stays_within_the_depth() {
+        let mut drift = StereoDrift::new(1000.);
+        let offsets: Vec<_> = (0..2000).map(|_| drift.next(2., 10.)).collect();
+
+        assert!(offsets.iter().all(|offset| offset.abs() <= MAX_DRIFT_MS));
+        assert!(offsets.iter().any(|offset| *offset > 1.9));
+        assert!(offsets.iter().any(|offset| *offset < -1.9));
+    }
+
+    #[test]
+    fn retriggered_drift_repeats_itself() {
+        let mut drift = StereoDrift::new(1000.);
+        let first: Vec<_> = (0..500).map(|_| drift.next(0.7, 2.)).collect();
+
+        // The glide takes the offset over from where it was without a jump
+        drift.retrigger();
+        let glided: Vec<_> = (0..100).map(|_| drift.next(0.7, 2.)).collect();
+        assert!((glided[0] - first[499]).abs() < 0.1);
+
+        // Once the glide is over, the drift moves like it did after the start
+        for (retriggered, started) in glided[60..].iter().zip(&first[60..100]) {
+            assert!((retriggered - started).abs() < 1e-5);
+        }
+    }
+}
@@ -0,0 +1,1115 @@
+use alloc::{vec, vec::Vec};
+use core::f64::consts::PI;
+
+#[cfg(feature = "glitch")]
+use super::jump_table::{JumpError, JumpTable};
+use crate::{random::XorShift, sample::Sample, sanitize::sanitize};
+
+/// The entry of the delay engine for Delax. It holds the buffers and handles the input and output of samples for specific parameters.
+///
+/// Usage:
+/// ```rs
+/// let mut engine = DelayEngine::new(44100);
+/// engine.write_sample(0.5);
+/// let out = engine.pop_sample();
+/// assert_eq!(out, 0.5);
+/// ```
+///
+/// The samples are held at the precision `T`, the times are always f32 in ms.
+pub struct DelayEngine<T: Sample = f32> {
+    /// The internal mono buffer
+    buffer: Vec<T>,
+    /// The sample rate to be used for internal conversions
+    sample_rate: f32,
+    /// The delay time in ms
+    delay_time: f32,
+    /// The positions at which the read head should jump
+    read_jumps: Vec<Jump>,
+    /// The positions at which the write head should jump
+    write_jumps: Vec<Jump>,
+    /// The current write head position
+    write_head: usize,
+    /// The current read head position
+    read_head: usize,
+    /// The random variation of the repeats
+    humanize: Humanize,
+    /// The offset of the read position in ms that's added by modulation like the stereo drift
+    read_offset: f32,
+    /// What happens to the input while a loop region plays, None while there's no loop
+    #[cfg(feature = "glitch")]
+    loop_input: Option<LoopInput>,
+    /// Plays the buffer back faster or slower than it's written
+    read_speed: ReadSpeed,
+}
+
+/// What happens to the input of a delay line while a loop region plays, see [DelayEngine::start_loop()]
+#[cfg(feature = "glitch")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoopInput {
+    /// The delay line stops taking in samples, only the loop plays
+    Ignore,
+    /// The delay line keeps going around the loop region, and the loop plays on top of the repeats
+    Layer,
+}
+
+impl DelayEngine {
+    /// Initialize the engine using the size.
+    /// The given size is the maximum size of the buffer and describes the maximum amount of data that can be held per bank.
+    ///
+    /// The buffer size can later be changed using [DelayEngine::set_buffer_size()].
+    pub fn new(size: usize, sample_rate: f32) -> Self {
+        Self::with_precision(size, sample_rate)
+    }
+}
+
+impl<T: Sample> DelayEngine<T> {
+    /// Initialize an engine that holds its samples at the precision `T`, see [DelayEngine::new()].
+    ///
+    /// Usage:
+    /// ```
+    /// use delax_dsp::delay_engine::engine::{DelayEngine, DelayInterpolationMode};
+    ///
+    /// let mut engine = DelayEngine::<f64>::with_precision(100, 1000.);
+    /// engine.set_delay_amount(10.);
+    /// engine.write_sample(0.5);
+    /// for _ in 0..9 {
+    ///     engine.write_sample(0.);
+    /// }
+    /// assert_eq!(engine.interpolate_sample(DelayInterpolationMode::Nearest), 0.5);
+    /// ```
+    pub fn with_precision(size: usize, sample_rate: f32) -> Self {
+        Self {
+            buffer: vec![T::ZERO; size],
+            sample_rate,
+            delay_time: 0.,
+            read_jumps: wrap_jumps(size),
+            write_jumps: wrap_jumps(size),
+            write_head: 0,
+            read_head: 0,
+            humanize: Humanize::new(sample_rate),
+            read_offset: 0.,
+            #[cfg(feature = "glitch")]
+            loop_input: None,
+            read_speed: ReadSpeed::new(sample_rate),
+        }
+    }
+
+    /// Outputs a sample and advances the read position in the internal banks.
+    /// Usage:
+    /// ```rs
+    /// let mut engine = DelayEngine::new(44100);
+    /// engine.write_sample(0.5);
+    /// let out = engine.pop_sample();
+    /// assert_eq!(out, 0.5);
+    /// ```
+    #[allow(dead_code)]
+    pub fn pop_sample(&mut self) -> T {
+        let sample = self.buffer[self.read_head];
+        if let Some(jump) = self.check_jumps(self.read_head, &self.read_jumps) {
+            self.read_head = jump.1;
+        } else {
+            self.read_head += 1;
+        }
+
+        sample
+    }
+
+    /// Interpolate the buffer at the current delay time using the method specified as interpolation mode.
+    pub fn interpolate_sample(&self, interpolation_mode: DelayInterpolationMode) -> T {
+        let delay_samples = match interpolation_mode {
+            DelayInterpolationMode::Nearest => {
+                ms_to_samples(self.read_delay_time(), self.sample_rate) as f64
+            }
+            // The delay can't be shorter than a sample, the current sample isn't written yet
+            DelayInterpolationMode::Linear => {
+                (self.read_delay_time() / 1000. * self.sample_rate).max(1.) as f64
+            }
+        };
+
+        let sample = if self.read_speed.active() {
+            // Both taps are read between the samples, they move through the buffer at the read speed
+            let longest = self.buffer.len() as f64 - 2.;
+            self.read_speed
+                .taps()
+                .into_iter()
+                .map(|(offset, weight)| {
+                    let delay = (delay_samples + offset).clamp(1., longest);
+                    self.read_fractional(self.write_head as f64 - delay)
+                        * T::from_f32(weight as f32)
+                })
+                .fold(T::ZERO, |sum, tap| sum + tap)
+        } else {
+            self.read_fractional(self.write_head as f64 - delay_samples)
+        };
+
+        sample * T::from_f32(self.humanize.gain)
+    }
+
+    /// Read the buffer at a fractional position, linearly interpolated between the two samples around it.
+    ///
+    /// The position wraps around the buffer in both directions, so positions behind the write head can be passed
+    /// without wrapping them first. Whole positions read a single sample.
+    ///
+    /// Usage:
+    /// ```
+    /// use delax_dsp::delay_engine::engine::DelayEngine;
+    ///
+    /// let mut engine = DelayEngine::new(4, 1000.);
+    /// for sample in [1., 2., 3., 4.] {
+    ///     engine.write_sample(sample);
+    /// }
+    /// assert_eq!(engine.read_fractional(1.5), 2.5);
+    /// // Between the last and the first sample of the buffer
+    /// assert_eq!(engine.read_fractional(-0.5), 2.5);
+    /// assert_eq!(engine.read_fractional(3.5), 2.5);
+    /// ```
+    pub fn read_fractional(&self, pos: f64) -> T {
+        let len = self.buffer.len() as f64;
+        let wrapped = pos - Sample::floor(pos / len) * len;
+        // Rounding can land a tiny negative position right on the length
+        let index = if wrapped < len { wrapped as usize } else { 0 };
+        let fraction = (wrapped - index as f64) as f32;
+
+        let current = self.buffer[index];
+        if fraction == 0. {
+            return current;
+        }
+        let next = self.buffer[(index + 1) % self.buffer.len()];
+        current * T::from_f32(1. - fraction) + next * T::from_f32(fraction)
+    }
+
+    /// The delay time in ms the delay line is read at, with the variation of the current repeat and the read offset
+    fn read_delay_time(&self) -> f32 {
+        (self.delay_time + self.humanize.time_offset + self.read_offset).max(0.)
+    }
+
+    /// The sample that was written a whole amount of samples ago, 1 is the newest one. This is meant for looking at
+    /// the whole buffer, like drawing it.
+    ///
+    /// Usage:
+    /// ```
+    /// use delax_dsp::delay_engine::engine::DelayEngine;
+    ///
+    /// let mut engine = DelayEngine::new(4, 1000.);
+    /// for sample in [1., 2., 3.] {
+    ///     engine.write_sample(sample);
+    /// }
+    /// assert_eq!(engine.history_sample(1), 3.);
+    /// assert_eq!(engine.history_sample(3), 1.);
+    /// ```
+    pub fn history_sample(&self, age: usize) -> T {
+        self.read_fractional(self.write_head as f64 - age as f64)
+    }
+
+    /// The sample at a delay time in ms other than the current one, without interpolation.
+    ///
+    /// This is used to fade from an old delay time to the current one.
+    pub fn sample_at(&self, delay_time: f32) -> T {
+        self.read_fractional(
+            self.write_head as f64 - ms_to_samples(delay_time, self.sample_rate) as f64,
+        )
+    }
+
+    /// Writes a sample into the internal banks and advances the write position in the internal banks.
+    ///
+    /// NaN and infinite samples are written as silence and tiny values are flushed to zero,
+    /// so a single broken sample can't get stuck in the feedback loop.
+    ///
+    /// At the end of the buffer and at a jump the write head moves straight to the target of the jump, so the next
+    /// sample is written there and no position is skipped.
+    /// Usage:
+    /// ```rs
+    /// let mut engine = DelayEngine::new(44100);
+    /// engine.write_sample(0.5);
+    /// let out = engine.pop_sample();
+    /// assert_eq!(out, 0.5);
+    /// ```
+    pub fn write_sample(&mut self, sample: T) {
+        #[cfg(feature = "glitch")]
+        if self.loop_input == Some(LoopInput::Ignore) {
+            return;
+        }
+
+        self.buffer[self.write_head] = sanitize(sample);
+
+        // The write head lands right on the target of a jump, like the read head does
+        if let Some(jump) = self.check_jumps(self.write_head, &self.write_jumps) {
+            self.write_head = jump.1;
+        } else {
+            self.write_head += 1;
+        }
+
+        let period = self.delay_samples();
+        self.humanize.advance(period);
+        self.read_speed.advance();
+    }
+
+    /// Returns the state of the internal buffer banks as an immutable pointer.
+    #[allow(dead_code)]
+    pub fn get_buffer_ptr(&self) -> &[T] {
+        &self.buffer
+    }
+
+    /// Changes the delay duration in samples.
+    ///
+    /// Input: Delay time in ms
+    ///
+    /// For now this changes the position of the write head relative to the read head.
+    ///
+    /// Values larger than the bank size will simply result in a duration of `samples % bank_size``
+    pub fn set_delay_amount(&mut self, delay_time: f32) {
+        // A loop region has the read head to itself
+        if !self.looping() {
+            let delay_samples = ms_to_samples(delay_time, self.sample_rate);
+            self.read_head = (self.write_head + delay_samples) % self.buffer.len();
+        }
+        self.delay_time = delay_time;
+    }
+
+    /// How much every repeat varies in time and level, from 0 to 1. The variation changes once per delay period.
+    pub fn set_humanize(&mut self, amount: f32) {
+        self.humanize.amount = amount.clamp(0., 1.);
+    }
+
+    /// Move the read position by an offset in ms, without changing the delay time. This is meant for modulation.
+    ///
+    /// Only [DelayEngine::interpolate_sample()] follows the offset.
+    pub fn set_read_offset(&mut self, offset: f32) {
+        self.read_offset = offset;
+    }
+
+    /// Play the buffer back at a multiple of the speed it's written at, which shifts the pitch of the repeats.
+    /// The speed is clamped to [MIN_READ_SPEED] and [MAX_READ_SPEED].
+    ///
+    /// A head that's faster or slower than the write head would run away from the delay time, so the buffer is read
+    /// by two taps that move through a short window around the delay time and crossfade where they wrap, like a
+    /// pitch shifter. Once the speed is back at 1 the taps glide back to the delay time. Only
+    /// [DelayEngine::interpolate_sample()] follows the speed.
+    ///
+    /// Usage:
+    /// ```
+    /// use delax_dsp::delay_engine::engine::{DelayEngine, DelayInterpolationMode};
+    ///
+    /// let mut engine = DelayEngine::new(1000, 1000.);
+    /// engine.set_delay_amount(100.);
+    /// engine.set_read_speed(2.);
+    /// for _ in 0..500 {
+    ///     engine.write_sample(0.5);
+    /// }
+    /// // The taps always add up to the full level
+    /// let read = engine.interpolate_sample(DelayInterpolationMode::Linear);
+    /// assert!((read - 0.5).abs() < 1e-6);
+    /// ```
+    pub fn set_read_speed(&mut self, speed: f32) {
+        self.read_speed.speed = speed.clamp(MIN_READ_SPEED, MAX_READ_SPEED) as f64;
+    }
+
+    /// Seed the random variation, so the same input is varied the same way every time
+    pub fn set_humanize_seed(&mut self, seed: u32) {
+        self.humanize.rng = XorShift::new(seed);
+    }
+
+    /// The current delay duration in ms
+    pub fn delay_time(&self) -> f32 {
+        self.delay_time
+    }
+
+    /// The current delay duration in samples
+    pub fn delay_samples(&self) -> usize {
+        ms_to_samples(self.delay_time, self.sample_rate)
+    }
+
+    #[allow(dead_code)]
+    /// Changes the buffer size.
+    ///
+    /// This resets the whole buffer to zero. The jumps belonged to the old size, so both heads go back to wrapping around the buffer.
+    pub fn set_buffer_size(&mut self, size: usize) {
+        self.buffer = vec![T::ZERO; size];
+        self.write_head %= size;
+        self.read_head %= size;
+        self.read_jumps = wrap_jumps(size);
+        self.write_jumps = wrap_jumps(size);
+        #[cfg(feature = "glitch")]
+        {
+            self.loop_input = None;
+        }
+    }
+
+    /// Check if there is a jump in the current index. If there is a jump, return it.
+    fn check_jumps(&self, index: usize, jumps: &Vec<Jump>) -> Option<Jump> {
+        for j in jumps {
+            if index == j.0 {
+                return Some(*j);
+            }
+        }
+        None
+    }
+
+    /// Set the raw read jump vector. This assumes that the vector of jumps is valid and covers the whole buffer,
+    /// [DelayEngine::set_read_jumps()] checks them instead.
+    #[allow(dead_code)]
+    pub fn set_raw_read_jumps(&mut self, jumps: &[Jump]) {
+        self.read_jumps = jumps.to_vec();
+    }
+
+    #[cfg(feature = "glitch")]
+    /// Let the read head of [DelayEngine::pop_sample()] follow a table of jumps, which has to be built for the size of the buffer
+    pub fn set_read_jumps(&mut self, table: &JumpTable) -> Result<(), JumpError> {
+        self.check_table_size(table)?;
+        self.read_jumps = table.jumps().to_vec();
+        Ok(())
+    }
+
+    #[cfg(feature = "glitch")]
+    /// Let the write head follow a table of jumps. It has to go through the whole buffer, so no position keeps an old sample forever.
+    ///
+    /// The reads at a delay time count back from the write head through the buffer, they don't follow the jumps.
+    pub fn set_write_jumps(&mut self, table: &JumpTable) -> Result<(), JumpError> {
+        self.check_table_size(table)?;
+        table.check_coverage()?;
+        self.write_jumps = table.jumps().to_vec();
+        Ok(())
+    }
+
+    #[cfg(feature = "glitch")]
+    /// Capture the last samples of the buffer and loop them with the read head, like a beat repeat.
+    ///
+    /// The region starts `start` ms back from the write head and is `length` ms long, so it has to lie in the samples
+    /// that are already written. The loop is read with [DelayEngine::loop_output()] until [DelayEngine::stop_loop()].
+    /// With [LoopInput::Layer] the write head skips the region, so the loop stays intact, and reads at delay times that
+    /// reach back past the region hear the loop instead.
+    ///
+    /// This only sets the jumps up and doesn't allocate, so it can be called on the audio thread.
+    ///
+    /// Usage:
+    /// ```
+    /// use delax_dsp::delay_engine::engine::{DelayEngine, LoopInput};
+    ///
+    /// // At 1000 Hz a ms is a sample
+    /// let mut engine = DelayEngine::new(8, 1000.);
+    /// for sample in [1., 2., 3., 4.] {
+    ///     engine.write_sample(sample);
+    /// }
+    /// // Loop the two samples from 3 samples back
+    /// engine.start_loop(3., 2., LoopInput::Ignore).unwrap();
+    /// let looped: Vec<f32> = (0..5).map(|_| engine.loop_output(0.)).collect();
+    /// assert_eq!(looped, [2., 3., 2., 3., 2.]);
+    /// ```
+    pub fn start_loop(
+        &mut self,
+        start: f32,
+        length: f32,
+        input: LoopInput,
+    ) -> Result<(), JumpError> {
+        let size = self.buffer.len();
+        let start = ms_to_samples(start, self.sample_rate);
+        let length = ms_to_samples(length, self.sample_rate).max(1);
+        if start >= size || length > start {
+            return Err(JumpError::InvalidLoop { start, length });
+        }
+
+        let first = (self.write_head + size - start) % size;
+        let last = (first + length - 1) % size;
+        // The jumps are kept within the capacity the lists already have
+        self.read_jumps.clear();
+        self.read_jumps.push(Jump(last, first));
+        if last != size - 1 {
+            self.read_jumps.push(Jump(size - 1, 0));
+        }
+        self.read_head = first;
+
+        self.write_jumps.clear();
+        if input == LoopInput::Layer {
+            let before = if first == 0 { size - 1 } else { first - 1 };
+            self.write_jumps.push(Jump(before, (last + 1) % size));
+            if before != size - 1 {
+                self.write_jumps.push(Jump(size - 1, 0));
+            }
+        } else {
+            self.write_jumps.push(Jump(size - 1, 0));
+        }
+
+        self.loop_input = Some(input);
+        Ok(())
+    }
+
+    #[cfg(feature = "glitch")]
+    /// Go back to the delay after a loop region, the read head picks up the delay time again
+    pub fn stop_loop(&mut self) {
+        let size = self.buffer.len();
+        for jumps in [&mut self.read_jumps, &mut self.write_jumps] {
+            jumps.clear();
+            jumps.push(Jump(size - 1, 0));
+        }
+        self.loop_input = None;
+        self.set_delay_amount(self.delay_time);
+    }
+
+    /// Whether a loop region is playing. Without the `glitch` feature there never is one.
+    pub fn looping(&self) -> bool {
+        #[cfg(feature = "glitch")]
+        {
+            self.loop_input.is_some()
+        }
+        #[cfg(not(feature = "glitch"))]
+        {
+            false
+        }
+    }
+
+    /// Start the patterns of the heads over without touching the samples in the buffer. A loop region plays from its
+    /// first sample again, and the humanize picks the variation of a new repeat right away.
+    ///
+    /// This puts the patterns back in time with the song, for example at the start of every bar.
+    pub fn restart_patterns(&mut self) {
+        if self.looping() {
+            // The jump back to the start of the loop region always comes first, see [DelayEngine::start_loop()]
+            self.read_head = self.read_jumps[0].1;
+        }
+        self.humanize.counter = 0;
+    }
+
+    #[cfg(feature = "glitch")]
+    /// The output of the delay line with the loop region. The loop replaces the delayed sample with [LoopInput::Ignore]
+    /// and is added to it with [LoopInput::Layer]. Without a loop the delayed sample is passed on.
+    ///
+    /// This moves the loop on by a sample, so it needs to be called once per sample while looping.
+    pub fn loop_output(&mut self, delayed: T) -> T {
+        match self.loop_input {
+            None => delayed,
+            Some(LoopInput::Ignore) => self.pop_sample(),
+            Some(LoopInput::Layer) => delayed + self.pop_sample(),
+        }
+    }
+
+    #[cfg(feature = "glitch")]
+    fn check_table_size(&self, table: &JumpTable) -> Result<(), JumpError> {
+        if table.size() == self.buffer.len() {
+            Ok(())
+        } else {
+            Err(JumpError::SizeMismatch {
+                table: table.size(),
+                buffer: self.buffer.len(),
+            })
+        }
+    }
+
+    /// Reset the internal buffers to zero.
+    pub fn reset(&mut self) {
+        self.buffer.iter_mut().for_each(|sample| *sample = T::ZERO);
+        self.humanize.reset();
+        self.read_speed.offset = 0.;
+    }
+}
+
+/// The largest offset of the delay time of a repeat at full humanize, in ms
+const HUMANIZE_TIME_MS: f32 = 3.;
+/// The largest drop of the level of a repeat at full humanize. The level is only ever lowered, so high feedback can't run away.
+const HUMANIZE_LEVEL: f32 = 0.25;
+/// The time the variation glides to the values of the next repeat in ms, so the changes don't click
+const HUMANIZE_GLIDE_MS: f32 = 5.;
+
+/// Random offsets of the delay time and level that are picked once per delay period, so every repeat varies a little.
+struct Humanize {
+    amount: f32,
+    rng: XorShift,
+    /// The samples since the variation of the current repeat was picked, it's picked when this is zero
+    counter: usize,
+    /// The coefficient of the glide towards the variation of the current repeat
+    glide: f32,
+    /// The time offset in ms and the gain of the current repeat, the variation glides towards these
+    target_time_offset: f32,
+    target_gain: f32,
+    time_offset: f32,
+    gain: f32,
+}
+
+impl Humanize {
+    fn new(sample_rate: f32) -> Self {
+        Self {
+            amount: 0.,
+            rng: XorShift::default(),
+            counter: 0,
+            glide: 1. - Sample::exp(-1000. / (HUMANIZE_GLIDE_MS * sample_rate)),
+            target_time_offset: 0.,
+            target_gain: 1.,
+            time_offset: 0.,
+            gain: 1.,
+        }
+    }
+
+    /// Move on by a sample, picking the variation of the next repeat once a whole period passed
+    fn advance(&mut self, period: usize) {
+        if self.counter == 0 {
+            // The random values are drawn even without humanize, so turning it up doesn't change the sequence
+            let time = self.rng.next_bipolar();
+            let level = self.rng.next_unipolar();
+            self.target_time_offset = time * HUMANIZE_TIME_MS * self.amount;
+            self.target_gain = 1. - level * HUMANIZE_LEVEL * self.amount;
+        }
+        self.counter = (self.counter + 1) % period.max(1);
+
+        self.time_offset += (self.target_time_offset - self.time_offset) * self.glide;
+        self.gain += (self.target_gain - self.gain) * self.glide;
+    }
+
+    fn reset(&mut self) {
+        self.counter = 0;
+        self.target_time_offset = 0.;
+        self.target_gain = 1.;
+        self.time_offset = 0.;
+        self.gain = 1.;
+    }
+}
+
+/// The slowest speed the delay lines can be read at, two octaves down
+pub const MIN_READ_SPEED: f32 = 0.25;
+/// The fastest speed the delay lines can be read at, two octaves up
+pub const MAX_READ_SPEED: f32 = 4.;
+/// The length of the window the taps of a read head at another speed move through, in ms.
+/// Longer windows smear the repeats, shorter ones make them buzz.
+const READ_SPEED_WINDOW_MS: f32 = 50.;
+/// How fast the taps glide back to the delay time once the read speed is back at 1, in samples per sample.
+/// This bends the pitch by about a third of a semitone while they do.
+const READ_SPEED_SETTLE: f64 = 0.02;
+
+/// The read head of a delay line that's played back at another speed than it's written at.
+///
+/// It's made of two taps half a window apart. Every sample they move away from the delay time by the difference
+/// between the speeds, and wrap around the window when they reach its edge. A raised cosine fades each tap out
+/// towards the edges, so the jump of the wrap can't be heard and the two taps always add up to the full level.
+struct ReadSpeed {
+    speed: f64,
+    /// The offset of the first tap from the delay time in samples, from minus to plus half a window
+    offset: f64,
+    /// The length of the window in samples
+    window: f64,
+}
+
+impl ReadSpeed {
+    fn new(sample_rate: f32) -> Self {
+        Self {
+            speed: 1.,
+            offset: 0.,
+            window: (READ_SPEED_WINDOW_MS / 1000. * sample_rate).max(2.) as f64,
+        }
+    }
+
+    /// Whether the taps are needed. At the normal speed they're only used until they're back at the delay time.
+    fn active(&self) -> bool {
+        self.speed != 1. || self.offset != 0.
+    }
+
+    /// Move the taps on by a sample
+    fn advance(&mut self) {
+        let step = if self.speed != 1. {
+            // A faster read head catches up with the write head, so its delay gets shorter
+            1. - self.speed
+        } else {
+            -self.offset.clamp(-READ_SPEED_SETTLE, READ_SPEED_SETTLE)
+        };
+
+        let half = self.window / 2.;
+        self.offset += step;
+        if self.offset >= half {
+            self.offset -= self.window;
+        } else if self.offset < -half {
+            self.offset += self.window;
+        }
+    }
+
+    /// The offsets of both taps from the delay time in samples, with their gains
+    fn taps(&self) -> [(f64, f64); 2] {
+        let half = self.window / 2.;
+        let other = if self.offset >= 0. {
+            self.offset - half
+        } else {
+            self.offset + half
+        };
+
+        [self.offset, other].map(|offset| {
+            let gain = Sample::cos(PI * offset / self.window);
+            (offset, gain * gain)
+        })
+    }
+}
+
+/// The longest delay time in ms the delay lines of the plugin can hold.
+/// This is long enough for the longest BPM bound delay down to 30 BPM.
+pub const MAX_DELAY_MS: f32 = 4000.;
+
+/// Delay times below this many ms don't sound like repeats anymore, they comb filter the signal like a flanger.
+/// They are read with linear interpolation, so sweeping them stays smooth.
+pub const FLANGE_ZONE_MS: f32 = 5.;
+
+/// The interpolation that suits a delay time in ms
+pub fn interpolation_for(delay_time: f32) -> DelayInterpolationMode {
+    if delay_time < FLANGE_ZONE_MS {
+        DelayInterpolationMode::Linear
+    } else {
+        DelayInterpolationMode::Nearest
+    }
+}
+
+/// The size of a delay line that can hold [MAX_DELAY_MS] at a sample rate
+pub fn max_delay_buffer_size(sample_rate: f32) -> usize {
+    ms_to_samples(MAX_DELAY_MS, sample_rate) + 1
+}
+
+/// The jumps of a head that wraps from the end of a buffer back to its start. There's room for a second jump,
+/// so a loop region can be set up without allocating.
+fn wrap_jumps(size: usize) -> Vec<Jump> {
+    let mut jumps = Vec::with_capacity(2);
+    jumps.push(Jump(size - 1, 0));
+    jumps
+}
+
+/// A jump inside of the banks. Currently this holds `Jump(from, to)`.
+/// Both are inclusive, so with `Jump(10,100)` the read order will be 8,9,10,100
+///
+/// Jumps are checked by collecting them in a [JumpTable].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Jump(pub usize, pub usize);
+
+pub enum DelayInterpolationMode {
+    Nearest,
+    Linear,
+}
+
+pub fn ms_to_samples(ms: f32, sample_rate: f32) -> usize {
+    Sample::floor((ms / 1000.) * sample_rate) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::{DelayEngine, DelayInterpolationMode, Humanize, Jump};
+    #[cfg(feature = "glitch")]
+    use super::{JumpError, JumpTable, LoopInput};
+    use crate::{random::XorShift, sample::Sample};
+
+    /// The sample that was written a fractional amount of samples ago, linearly interpolated, straight from the history
+    fn reference_read(history: &[f64], delay_samples: f64) -> f64 {
+        let whole = delay_samples.floor() as usize;
+        let fraction = delay_samples - whole as f64;
+        let newer = history[history.len() - whole];
+        let older = history[history.len() - whole - 1];
+        newer + (older - newer) * fraction
+    }
+
+    #[test]
+    fn init() {
+        let mut engine = DelayEngine::new(44100, 44100.);
+
+        assert_eq!(engine.pop_sample(), 0.);
+        assert_eq!(engine.get_buffer_ptr().len(), 44100);
+    }
+
+    #[test]
+    fn check_sample_inout() {
+        let mut engine = DelayEngine::new(5, 44100.);
+
+        engine.write_sample(1.);
+        engine.write_sample(2.);
+        engine.write_sample(3.);
+        engine.write_sample(4.);
+        engine.write_sample(5.);
+
+        assert_eq!(engine.pop_sample(), 1.);
+        assert_eq!(engine.pop_sample(), 2.);
+        assert_eq!(engine.pop_sample(), 3.);
+        assert_eq!(engine.pop_sample(), 4.);
+        assert_eq!(engine.pop_sample(), 5.);
+        assert_eq!(engine.pop_sample(), 1.);
+    }
+
+    #[test]
+    fn internal_buffer() {
+        let mut engine = DelayEngine::new(5, 44100.);
+
+        engine.write_sample(1.);
+        engine.write_sample(2.);
+        engine.write_sample(3.);
+        engine.write_sample(4.);
+        engine.write_sample(5.);
+
+        let buffer = engine.get_buffer_ptr();
+
+        assert_eq!(buffer, [1., 2., 3., 4., 5.])
+    }
+
+    #[test]
+    fn buffer_size() {
+        let mut engine = DelayEngine::new(5, 44100.);
+
+        let buffer = engine.get_buffer_ptr();
+
+        assert_eq!(buffer.len(), 5);
+
+        engine.set_buffer_size(10);
+
+        let buffer = engine.get_buffer_ptr();
+
+        assert_eq!(buffer.len(), 10);
+    }
+
+    #[test]
+    fn read_jumps() {
+        let mut engine = DelayEngine::new(10, 44100.);
+        engine.set_raw_read_jumps(&vec![Jump(9, 0), Jump(2, 5), Jump(7, 3), Jump(4, 8)]);
+
+        engine.write_sample(1.);
+        engine.write_sample(2.);
+        engine.write_sample(3.);
+        engine.write_sample(4.);
+        engine.write_sample(5.);
+        engine.write_sample(6.);
+        engine.write_sample(7.);
+        engine.write_sample(8.);
+        engine.write_sample(9.);
+        engine.write_sample(10.);
+
+        assert_eq!(engine.pop_sample(), 1.);
+        assert_eq!(engine.pop_sample(), 2.);
+        assert_eq!(engine.pop_sample(), 3.);
+        assert_eq!(engine.pop_sample(), 6.);
+        assert_eq!(engine.pop_sample(), 7.);
+        assert_eq!(engine.pop_sample(), 8.);
+        assert_eq!(engine.pop_sample(), 4.);
+        assert_eq!(engine.pop_sample(), 5.);
+        assert_eq!(engine.pop_sample(), 9.);
+        assert_eq!(engine.pop_sample(), 10.);
+
+        assert_eq!(engine.pop_sample(), 1.);
+        assert_eq!(engine.pop_sample(), 2.);
+        assert_eq!(engine.pop_sample(), 3.);
+        assert_eq!(engine.pop_sample(), 6.);
+        assert_eq!(engine.pop_sample(), 7.);
+        assert_eq!(engine.pop_sample(), 8.);
+        assert_eq!(engine.pop_sample(), 4.);
+        assert_eq!(engine.pop_sample(), 5.);
+        assert_eq!(engine.pop_sample(), 9.);
+        assert_eq!(engine.pop_sample(), 10.);
+    }
+
+    #[test]
+    #[cfg(feature = "glitch")]
+    fn jump_tables_are_checked_against_the_buffer() {
+        let mut engine = DelayEngine::new(8, 1000.);
+        assert_eq!(
+            engine.set_read_jumps(&JumpTable::linear(4).unwrap()),
+            Err(JumpError::SizeMismatch {
+                table: 4,
+                buffer: 8
+            })
+        );
+        // The write head can't leave a section behind
+        let looped = JumpTable::loop_section(8, 2, 5).unwrap();
+        assert!(engine.set_read_jumps(&looped).is_ok());
+        assert_eq!(
+            engine.set_write_jumps(&looped),
+            Err(JumpError::Uncovered { position: 0 })
+        );
+
+        // Writing through the slices in reverse and reading straight through reverses the order of the slices
+        let reversed = JumpTable::slices(8, 2, &[3, 2, 1, 0]).unwrap();
+        engine
+            .set_read_jumps(&JumpTable::linear(8).unwrap())
+            .unwrap();
+        engine.set_write_jumps(&reversed).unwrap();
+        for sample in 0..8 {
+            engine.write_sample(sample as f32);
+        }
+        let read: Vec<f32> = (0..8).map(|_| engine.pop_sample()).collect();
+        assert_eq!(read, [0., 1., 6., 7., 4., 5., 2., 3.]);
+    }
+
+    #[test]
+    fn the_write_head_lands_on_the_jump_target() {
+        // Wrapping around the end writes the next sample to the start of the buffer
+        let mut engine = DelayEngine::new(4, 1000.);
+        let mut heads = Vec::new();
+        for sample in 1..=6 {
+            engine.write_sample(sample as f32);
+            heads.push(engine.write_head);
+        }
+        assert_eq!(heads, [1, 2, 3, 0, 1, 2]);
+        assert_eq!(engine.get_buffer_ptr(), [5., 6., 3., 4.]);
+
+        // A jump of the table writes the next sample to its target
+        #[cfg(feature = "glitch")]
+        {
+            let mut engine = DelayEngine::new(6, 1000.);
+            engine
+                .set_write_jumps(&JumpTable::slices(6, 2, &[0, 2, 1]).unwrap())
+                .unwrap();
+            for sample in 1..=6 {
+                engine.write_sample(sample as f32);
+            }
+            assert_eq!(engine.get_buffer_ptr(), [1., 2., 5., 6., 3., 4.]);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "glitch")]
+    fn layered_loops_keep_their_region() {
+        let mut engine = DelayEngine::new(8, 1000.);
+        for sample in 1..=4 {
+            engine.write_sample(sample as f32);
+        }
+        engine.set_delay_amount(2.);
+        engine.start_loop(2., 2., LoopInput::Layer).unwrap();
+        assert!(engine.looping());
+
+        // Writing more than a lap goes around the loop region, which holds on to 3 and 4
+        for sample in 5..=14 {
+            engine.write_sample(sample as f32);
+        }
+        assert_eq!(engine.get_buffer_ptr()[2..4], [3., 4.]);
+        assert_eq!(engine.loop_output(0.5), 3.5);
+        assert_eq!(engine.loop_output(0.5), 4.5);
+        assert_eq!(engine.loop_output(0.5), 3.5);
+
+        // Stopping goes back to reading at the delay time
+        engine.stop_loop();
+        assert!(!engine.looping());
+        assert_eq!(engine.loop_output(0.5), 0.5);
+        assert_eq!(
+            engine.start_loop(1., 2., LoopInput::Ignore),
+            Err(JumpError::InvalidLoop {
+                start: 1,
+                length: 2
+            })
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "glitch")]
+    fn restarting_the_patterns_keeps_the_buffer() {
+        let mut engine = DelayEngine::new(8, 1000.);
+        for sample in 1..=6 {
+            engine.write_sample(sample as f32);
+        }
+        engine.start_loop(4., 3., LoopInput::Ignore).unwrap();
+        assert_eq!(engine.loop_output(0.), 3.);
+        assert_eq!(engine.loop_output(0.), 4.);
+
+        // The loop starts over from its first sample, nothing was cleared
+        engine.restart_patterns();
+        let looped: Vec<f32> = (0..4).map(|_| engine.loop_output(0.)).collect();
+        assert_eq!(looped, [3., 4., 5., 3.]);
+        assert_eq!(engine.get_buffer_ptr()[..6], [1., 2., 3., 4., 5., 6.]);
+    }
+
+    #[test]
+    fn double_speed_doubles_the_pitch() {
+        let sample_rate = 8000.;
+        let mut engine = DelayEngine::new(8000, sample_rate);
+        engine.set_delay_amount(200.);
+        engine.set_read_speed(2.);
+
+        let mut last = 0.;
+        let mut crossings = 0;
+        for i in 0..16000 {
+            let phase = core::f32::consts::TAU * 100. * i as f32 / sample_rate;
+            engine.write_sample(Sample::sin(phase));
+            if i >= 8000 {
+                let read = engine.interpolate_sample(DelayInterpolationMode::Linear);
+                if last < 0. && read >= 0. {
+                    crossings += 1;
+                }
+                last = read;
+            }
+        }
+        // A second of 100 Hz crosses zero upwards 100 times, the crossfades of the taps blur that a little
+        assert!((190..=210).contains(&crossings), "{crossings}");
+    }
+
+    #[test]
+    fn taps_settle_back_at_normal_speed() {
+        let mut engine = DelayEngine::new(1000, 1000.);
+        engine.set_delay_amount(100.);
+        engine.set_read_speed(0.5);
+        for _ in 0..30 {
+            engine.write_sample(0.);
+        }
+        assert!(engine.read_speed.active());
+
+        // The taps are 15 samples away and glide back in 750 samples
+        engine.set_read_speed(1.);
+        for sample in 0..1000 {
+            engine.write_sample(sample as f32);
+        }
+        assert!(!engine.read_speed.active());
+        assert_eq!(
+            engine.interpolate_sample(DelayInterpolationMode::Nearest),
+            900.
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "glitch")]
+    fn ignored_input_freezes_the_buffer() {
+        let mut engine = DelayEngine::new(8, 1000.);
+        for sample in 1..=6 {
+            engine.write_sample(sample as f32);
+        }
+        // A region that wraps around the end of the buffer is looped all the same
+        for sample in 7..=10 {
+            engine.write_sample(sample as f32);
+        }
+        engine.start_loop(4., 4., LoopInput::Ignore).unwrap();
+        engine.write_sample(100.);
+        let looped: Vec<f32> = (0..6).map(|_| engine.loop_output(0.)).collect();
+        assert_eq!(looped, [7., 8., 9., 10., 7., 8.]);
+    }
+
+    #[test]
+    fn linear_interpolation_reads_between_samples() {
+        // At 1000 Hz a ms is a sample
+        let mut engine = DelayEngine::new(10, 1000.);
+        engine.write_sample(1.);
+        engine.write_sample(2.);
+        engine.write_sample(3.);
+
+        engine.set_delay_amount(1.);
+        assert_eq!(
+            engine.interpolate_sample(DelayInterpolationMode::Linear),
+            3.
+        );
+        engine.set_delay_amount(1.5);
+        assert_eq!(
+            engine.interpolate_sample(DelayInterpolationMode::Linear),
+            2.5
+        );
+        engine.set_delay_amount(2.25);
+        assert_eq!(
+            engine.interpolate_sample(DelayInterpolationMode::Linear),
+            1.75
+        );
+
+        // Shorter delays are held at a single sample
+        engine.set_delay_amount(0.);
+        assert_eq!(
+            engine.interpolate_sample(DelayInterpolationMode::Linear),
+            3.
+        );
+    }
+
+    #[test]
+    fn fractional_reads_match_the_history() {
+        let mut rng = XorShift::new(7);
+        for _ in 0..50 {
+            let size = 2 + (rng.next_unipolar() * 30.) as usize;
+            let mut engine = DelayEngine::<f64>::with_precision(size, 1000.);
+
+            // Write a few laps, so the reads wrap around the end of the buffer
+            let mut history = Vec::new();
+            for _ in 0..size * 3 + (rng.next_unipolar() * size as f32) as usize {
+                let sample = rng.next_bipolar() as f64;
+                engine.write_sample(sample);
+                history.push(sample);
+            }
+
+            for _ in 0..20 {
+                // Between the last written sample and the oldest sample that's still in the buffer
+                let delay_samples = 1. + rng.next_unipolar() as f64 * (size - 1) as f64;
+                let read = engine.read_fractional(engine.write_head as f64 - delay_samples);
+                let expected = reference_read(&history, delay_samples);
+                assert!(
+                    (read - expected).abs() < 1e-6,
+                    "size {size}, delay {delay_samples}: {read} != {expected}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn linear_reads_match_the_history() {
+        let mut rng = XorShift::new(11);
+        let mut engine = DelayEngine::<f64>::with_precision(64, 1000.);
+        let mut history = Vec::new();
+        for _ in 0..500 {
+            let sample = rng.next_bipolar() as f64;
+            engine.write_sample(sample);
+            history.push(sample);
+
+            // At 1000 Hz a ms is a sample
+            let delay_time = 1. + rng.next_unipolar() * 60.;
+            engine.set_delay_amount(delay_time);
+            let read = engine.interpolate_sample(DelayInterpolationMode::Linear);
+            if history.len() > 62 {
+                // The delay in samples is rounded to f32 like the engine does it
+                let delay_samples = (delay_time / 1000. * 1000.) as f64;
+                let expected = reference_read(&history, delay_samples);
+                assert!((read - expected).abs() < 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn humanize_varies_once_per_repeat() {
+        let mut humanize = Humanize::new(1000.);
+        humanize.amount = 1.;
+
+        let mut targets = Vec::new();
+        for _ in 0..30 {
+            humanize.advance(10);
+            targets.push((humanize.target_time_offset, humanize.target_gain));
+        }
+
+        // A new variation is picked every 10 samples and held until the next one
+        for repeat in targets.chunks(10) {
+            assert!(repeat.iter().all(|target| *target == repeat[0]));
+        }
+        assert_ne!(targets[0], targets[10]);
+        assert!(targets
+            .iter()
+            .all(|(time_offset, gain)| time_offset.abs() <= 3. && (0.75..=1.).contains(gain)));
+    }
+
+    #[test]
+    fn no_humanize_reads_unchanged() {
+        let mut engine = DelayEngine::new(64, 1000.);
+        engine.set_delay_amount(2.);
+        for sample in 1..=30 {
+            engine.write_sample(sample as f32);
+        }
+
+        assert_eq!(
+            engine.interpolate_sample(DelayInterpolationMode::Nearest),
+            29.
+        );
+    }
+
+    #[test]
+    fn pathological_input() {
+        let mut engine = DelayEngine::new(5, 44100.);
+
+        engine.write_sample(f32::NAN);
+        engine.write_sample(f32::INFINITY);
+        engine.write_sample(f32::NEG_INFINITY);
+        engine.write_sample(1e-40);
+        engine.write_sample(0.5);
+
+        assert_eq!(engine.get_buffer_ptr(), [0., 0., 0., 0., 0.5]);
+    }
+
+    #[test]
+    fn feedback_tail_flushes_to_zero() {
+        let mut engine = DelayEngine::new(10, 44100.);
+        engine.write_sample(1.);
+
+        // Run a very long, quiet feedback tail through the buffer
+        for _ in 0..10000 {
+            let sample = engine.pop_sample();
+            engine.write_sample(sample * 0.5);
+        }
+
+        assert!(engine
+            .get_buffer_ptr()
+            .iter()
+            .all(|sample| *sample == 0. || sample.is_normal()));
+    }
+}
@@ -0,0 +1,205 @@
+use alloc::{vec, vec::Vec};
+
+/// A square matrix that routes the output of every delay line into the feedback of every delay line.
+///
+/// The entry at `(to, from)` is the gain with which the output of channel `from` is fed back into channel `to`.
+/// The identity matrix keeps every channel to itself, which is the classic multi mono delay.
+///
+/// Usage:
+/// ```
+/// use delax_dsp::delay_engine::feedback_matrix::FeedbackMatrix;
+///
+/// let mut matrix = FeedbackMatrix::identity(2);
+/// matrix.set(1, 0, 0.5);
+///
+/// let mut output = [0.; 2];
+/// matrix.apply(&[1., 0.], &mut output);
+/// assert_eq!(output, [1., 0.5]);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeedbackMatrix {
+    /// The amount of channels, the matrix has `size * size` entries
+    size: usize,
+    /// The gains in row major order, so a row holds all the inputs of one channel
+    gains: Vec<f32>,
+}
+
+impl FeedbackMatrix {
+    /// Create a matrix where every channel only feeds back into itself
+    pub fn identity(size: usize) -> Self {
+        let mut matrix = Self {
+            size,
+            gains: vec![0.; size * size],
+        };
+        for channel in 0..size {
+            matrix.set(channel, channel, 1.);
+        }
+        matrix
+    }
+
+    /// Create a matrix where every channel keeps `1 - amount` for itself and sends `amount` to the next channel.
+    /// The last channel sends into the first one, so for stereo this is a ping pong delay.
+    pub fn rotation(size: usize, amount: f32) -> Self {
+        let mut matrix = Self {
+            size,
+            gains: vec![0.; size * size],
+        };
+        for channel in 0..size {
+            let next = (channel + 1) % size;
+            matrix.set(channel, channel, 1. - amount);
+            matrix.set(next, channel, matrix.get(next, channel) + amount);
+        }
+        matrix
+    }
+
+    /// Create a matrix that swaps the channels of every pair, so the repeats bounce between left and right.
+    ///
+    /// Unlike [FeedbackMatrix::rotation()] the pairs stay separate, so every pair of a surround layout is its own ping pong delay.
+    pub fn ping_pong(size: usize) -> Self {
+        let mut matrix = Self {
+            size,
+            gains: vec![0.; size * size],
+        };
+        for pair in 0..size / 2 {
+            let (l, r) = (pair * 2, pair * 2 + 1);
+            matrix.set(l, r, 1.);
+            matrix.set(r, l, 1.);
+        }
+        matrix
+    }
+
+    /// Turn the matrix into the injection of a ping pong delay, in place so it can be done on the audio thread.
+    ///
+    /// Every pair is summed to mono and sent to the channel `start` of the pair, 0 for left and 1 for right.
+    /// `offset` pans the first repeat from the start channel towards the center, at 1 both channels get the same amount.
+    pub fn set_ping_pong_injection(&mut self, start: usize, offset: f32) {
+        debug_assert!(start < 2);
+        let offset = offset.clamp(0., 1.);
+
+        self.gains.fill(0.);
+        for pair in 0..self.size / 2 {
+            let (first, second) = (pair * 2 + start, pair * 2 + 1 - start);
+            // The mono sum is halved, so a centered input keeps its level
+            for from in [pair * 2, pair * 2 + 1] {
+                self.set(first, from, 0.5 * (1. - offset * 0.5));
+                self.set(second, from, 0.5 * offset * 0.5);
+            }
+        }
+    }
+
+    /// The amount of channels the matrix routes
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// The gain from channel `from` into channel `to`
+    pub fn get(&self, to: usize, from: usize) -> f32 {
+        self.gains[to * self.size + from]
+    }
+
+    /// Set the gain from channel `from` into channel `to`
+    pub fn set(&mut self, to: usize, from: usize, gain: f32) {
+        self.gains[to * self.size + from] = gain;
+    }
+
+    /// The most any channel gets out of the matrix for inputs of at most 1, the largest sum of absolute gains of a row.
+    ///
+    /// Usage:
+    /// ```
+    /// use delax_dsp::delay_engine::feedback_matrix::FeedbackMatrix;
+    ///
+    /// assert_eq!(FeedbackMatrix::rotation(2, 0.3).max_row_gain(), 1.);
+    /// ```
+    pub fn max_row_gain(&self) -> f32 {
+        self.gains
+            .chunks_exact(self.size)
+            .map(|row| row.iter().map(|gain| gain.abs()).sum())
+            .fold(0., f32::max)
+    }
+
+    /// Route the delay outputs in `input` through the matrix and write the result into `output`.
+    ///
+    /// Both slices need to hold exactly [FeedbackMatrix::size()] samples.
+    pub fn apply(&self, input: &[f32], output: &mut [f32]) {
+        debug_assert_eq!(input.len(), self.size);
+        debug_assert_eq!(output.len(), self.size);
+
+        for (row, out) in self.gains.chunks_exact(self.size).zip(output.iter_mut()) {
+            *out = row
+                .iter()
+                .zip(input)
+                .map(|(gain, sample)| gain * sample)
+                .sum();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identity_passes_through() {
+        let matrix = FeedbackMatrix::identity(6);
+        let input = [0.1, -0.2, 0.3, -0.4, 0.5, -0.6];
+        let mut output = [0.; 6];
+
+        matrix.apply(&input, &mut output);
+
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn rotation_sends_to_the_next_channel() {
+        let matrix = FeedbackMatrix::rotation(4, 1.);
+        let mut output = [0.; 4];
+
+        matrix.apply(&[1., 2., 3., 4.], &mut output);
+
+        assert_eq!(output, [4., 1., 2., 3.]);
+    }
+
+    #[test]
+    fn ping_pong_swaps_within_pairs() {
+        let matrix = FeedbackMatrix::ping_pong(4);
+        let mut output = [0.; 4];
+
+        matrix.apply(&[1., 2., 3., 4.], &mut output);
+
+        assert_eq!(output, [2., 1., 4., 3.]);
+        assert_eq!(
+            FeedbackMatrix::ping_pong(2),
+            FeedbackMatrix::rotation(2, 1.)
+        );
+    }
+
+    #[test]
+    fn ping_pong_injection_starts_on_one_side() {
+        let mut matrix = FeedbackMatrix::identity(4);
+        let mut output = [0.; 4];
+
+        matrix.set_ping_pong_injection(1, 0.);
+        matrix.apply(&[1., 1., 0.5, 0.5], &mut output);
+        assert_eq!(output, [0., 1., 0., 0.5]);
+
+        matrix.set_ping_pong_injection(0, 0.5);
+        matrix.apply(&[1., 1., 0.5, 0.5], &mut output);
+        assert_eq!(output, [0.75, 0.25, 0.375, 0.125]);
+
+        // All the way to the center both sides get half of the input
+        matrix.set_ping_pong_injection(0, 1.);
+        matrix.apply(&[1., 1., 0., 0.], &mut output);
+        assert_eq!(output, [0.5, 0.5, 0., 0.]);
+    }
+
+    #[test]
+    fn rotation_keeps_the_gain() {
+        // Every column sums up to one, so no channel gets louder or quieter in total
+        let matrix = FeedbackMatrix::rotation(3, 0.3);
+
+        for from in 0..3 {
+            let total: f32 = (0..3).map(|to| matrix.get(to, from)).sum();
+            assert!((total - 1.).abs() < 1e-6);
+        }
+    }
+}
@@ -0,0 +1,355 @@
+use alloc::{vec, vec::Vec};
+use core::fmt;
+
+use super::engine::Jump;
+use crate::random::XorShift;
+
+/// Why a set of jumps can't be used by a delay line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JumpError {
+    /// A buffer without samples has nowhere to jump to
+    EmptyBuffer,
+    /// A jump starts or lands outside of the buffer
+    OutOfRange { from: usize, to: usize },
+    /// There are two jumps from the same position, so it's unclear where the head goes
+    DuplicateJump { from: usize },
+    /// There is no jump at the last position of the buffer, so the head would run off its end
+    RunsOffTheEnd,
+    /// The head never comes back to a position, but it has to cover the whole buffer
+    Uncovered { position: usize },
+    /// The table was built for a buffer of another size
+    SizeMismatch { table: usize, buffer: usize },
+    /// A section doesn't lie inside of the buffer or leaves nothing to play
+    InvalidSection { start: usize, end: usize },
+    /// The slices are empty, or the order doesn't play every slice exactly once
+    InvalidSlices,
+    /// A loop region reaches back further than the buffer, or into samples that aren't written yet. Both are in samples.
+    InvalidLoop { start: usize, length: usize },
+}
+
+impl fmt::Display for JumpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JumpError::EmptyBuffer => write!(f, "the buffer is empty"),
+            JumpError::OutOfRange { from, to } => {
+                write!(f, "the jump from {from} to {to} leaves the buffer")
+            }
+            JumpError::DuplicateJump { from } => write!(f, "there are two jumps from {from}"),
+            JumpError::RunsOffTheEnd => write!(f, "the head runs off the end of the buffer"),
+            JumpError::Uncovered { position } => {
+                write!(f, "the head never comes back to position {position}")
+            }
+            JumpError::SizeMismatch { table, buffer } => write!(
+                f,
+                "the jumps are for a buffer of {table} samples, not {buffer}"
+            ),
+            JumpError::InvalidSection { start, end } => {
+                write!(
+                    f,
+                    "the section from {start} to {end} doesn't fit the buffer"
+                )
+            }
+            JumpError::InvalidSlices => write!(f, "the slices don't cover the buffer once"),
+            JumpError::InvalidLoop { start, length } => write!(
+                f,
+                "a loop of {length} samples starting {start} samples back doesn't fit the buffer"
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for JumpError {}
+
+/// The jumps of a read or write head through a delay buffer, checked to be safe to follow.
+///
+/// The head moves on by one position every sample and follows a jump when it's at the start of one.
+/// A table always has a jump at the last position, so the head can't run off the end of the buffer.
+/// Build one with [JumpTableBuilder] or one of the constructors for common patterns.
+///
+/// Usage:
+/// ```
+/// use delax_dsp::delay_engine::{engine::DelayEngine, jump_table::JumpTable};
+///
+/// let mut engine = DelayEngine::new(6, 1000.);
+/// for sample in [1., 2., 3., 4., 5., 6.] {
+///     engine.write_sample(sample);
+/// }
+/// // Play the start, then repeat the samples from 2 to 3 forever
+/// engine.set_read_jumps(&JumpTable::loop_section(6, 2, 3).unwrap()).unwrap();
+/// let read: Vec<f32> = (0..7).map(|_| engine.pop_sample()).collect();
+/// assert_eq!(read, [1., 2., 3., 4., 3., 4., 3.]);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct JumpTable {
+    size: usize,
+    jumps: Vec<Jump>,
+    /// The first position the head never comes back to, None if it keeps going through the whole buffer
+    uncovered: Option<usize>,
+}
+
+impl JumpTable {
+    /// Check a set of jumps for a buffer of a size, this is where all tables are validated
+    pub fn from_jumps(size: usize, jumps: &[Jump]) -> Result<Self, JumpError> {
+        if size == 0 {
+            return Err(JumpError::EmptyBuffer);
+        }
+
+        let mut targets = vec![None; size];
+        for jump in jumps {
+            if jump.0 >= size || jump.1 >= size {
+                return Err(JumpError::OutOfRange {
+                    from: jump.0,
+                    to: jump.1,
+                });
+            }
+            if targets[jump.0].replace(jump.1).is_some() {
+                return Err(JumpError::DuplicateJump { from: jump.0 });
+            }
+        }
+        if targets[size - 1].is_none() {
+            return Err(JumpError::RunsOffTheEnd);
+        }
+
+        // Follow the head from the start until it comes back to a position. Everything from that position on
+        // repeats forever, whatever came before it is only played once.
+        let mut visited_at = vec![usize::MAX; size];
+        let mut position = 0;
+        let mut step = 0;
+        while visited_at[position] == usize::MAX {
+            visited_at[position] = step;
+            step += 1;
+            position = targets[position].unwrap_or(position + 1);
+        }
+        let cycle_start = visited_at[position];
+        let uncovered = visited_at
+            .iter()
+            .position(|visited_at| *visited_at == usize::MAX || *visited_at < cycle_start);
+
+        Ok(Self {
+            size,
+            jumps: jumps.to_vec(),
+            uncovered,
+        })
+    }
+
+    /// The plain buffer, the head wraps from the end back to the start
+    pub fn linear(size: usize) -> Result<Self, JumpError> {
+        JumpTableBuilder::new(size).build()
+    }
+
+    /// Play up to the end of a section and then repeat the section forever. Both ends are inclusive.
+    pub fn loop_section(size: usize, start: usize, end: usize) -> Result<Self, JumpError> {
+        if start > end || end >= size {
+            return Err(JumpError::InvalidSection { start, end });
+        }
+
+        JumpTableBuilder::new(size).jump(end, start).build()
+    }
+
+    /// Leave out a section of the buffer. Both ends are inclusive and at least one position has to be left.
+    pub fn skip_section(size: usize, start: usize, end: usize) -> Result<Self, JumpError> {
+        if start > end || end >= size || (start == 0 && end == size - 1) {
+            return Err(JumpError::InvalidSection { start, end });
+        }
+
+        let from = if start == 0 { size - 1 } else { start - 1 };
+        JumpTableBuilder::new(size)
+            .jump(from, (end + 1) % size)
+            .build()
+    }
+
+    /// Cut the buffer into slices of the same length and play them in an order, which has to contain every slice once.
+    /// The last slice is shorter if the size isn't a multiple of the length.
+    ///
+    /// Usage:
+    /// ```
+    /// use delax_dsp::delay_engine::jump_table::JumpTable;
+    ///
+    /// // Play the last two of three slices the other way around
+    /// let table = JumpTable::slices(9, 3, &[0, 2, 1]).unwrap();
+    /// assert!(table.covers_whole_buffer());
+    /// assert!(JumpTable::slices(9, 3, &[0, 1, 1]).is_err());
+    /// ```
+    pub fn slices(size: usize, slice_len: usize, order: &[usize]) -> Result<Self, JumpError> {
+        if slice_len == 0 {
+            return Err(JumpError::InvalidSlices);
+        }
+        let num_slices = size.div_ceil(slice_len);
+        let mut played = vec![false; num_slices];
+        for slice in order {
+            if *slice >= num_slices || core::mem::replace(&mut played[*slice], true) {
+                return Err(JumpError::InvalidSlices);
+            }
+        }
+        if played.contains(&false) {
+            return Err(JumpError::InvalidSlices);
+        }
+
+        // The end of every slice jumps to the start of the next one in the order, the last one back to the first
+        let mut builder = JumpTableBuilder::new(size);
+        for (index, slice) in order.iter().enumerate() {
+            let next = order[(index + 1) % order.len()];
+            let end = ((slice + 1) * slice_len).min(size) - 1;
+            builder = builder.jump(end, next * slice_len);
+        }
+        builder.build()
+    }
+
+    /// Play the slices of the buffer in a random order, see [JumpTable::slices()]. The same seed gives the same order.
+    pub fn shuffled_slices(size: usize, slice_len: usize, seed: u32) -> Result<Self, JumpError> {
+        if slice_len == 0 {
+            return Err(JumpError::InvalidSlices);
+        }
+
+        // A Fisher-Yates shuffle
+        let mut rng = XorShift::new(seed);
+        let mut order: Vec<usize> = (0..size.div_ceil(slice_len)).collect();
+        for index in (1..order.len()).rev() {
+            let other = ((rng.next_unipolar() * (index + 1) as f32) as usize).min(index);
+            order.swap(index, other);
+        }
+        Self::slices(size, slice_len, &order)
+    }
+
+    /// The size of the buffer the table was built for
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn jumps(&self) -> &[Jump] {
+        &self.jumps
+    }
+
+    /// Whether the head keeps going through every position of the buffer. Write heads need this,
+    /// otherwise the positions they skip hold on to old samples forever.
+    pub fn covers_whole_buffer(&self) -> bool {
+        self.uncovered.is_none()
+    }
+
+    /// The same as [JumpTable::covers_whole_buffer()], as an error naming the first position that's left out
+    pub fn check_coverage(&self) -> Result<(), JumpError> {
+        match self.uncovered {
+            Some(position) => Err(JumpError::Uncovered { position }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Collects jumps for a [JumpTable]. It starts out with the jump from the end of the buffer back to the start,
+/// which is replaced by adding another jump from the end.
+pub struct JumpTableBuilder {
+    size: usize,
+    jumps: Vec<Jump>,
+}
+
+impl JumpTableBuilder {
+    pub fn new(size: usize) -> Self {
+        Self {
+            size,
+            jumps: size
+                .checked_sub(1)
+                .map(|last| Jump(last, 0))
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    /// Jump from one position to another. A jump from the same position is replaced.
+    pub fn jump(mut self, from: usize, to: usize) -> Self {
+        self.jumps.retain(|jump| jump.0 != from);
+        self.jumps.push(Jump(from, to));
+        self
+    }
+
+    pub fn build(self) -> Result<JumpTable, JumpError> {
+        JumpTable::from_jumps(self.size, &self.jumps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The positions the head goes through, starting at 0
+    fn walk(table: &JumpTable, steps: usize) -> Vec<usize> {
+        let mut position = 0;
+        (0..steps)
+            .map(|_| {
+                let current = position;
+                position = table
+                    .jumps()
+                    .iter()
+                    .find(|jump| jump.0 == current)
+                    .map_or(current + 1, |jump| jump.1);
+                current
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sections_are_looped_and_skipped() {
+        let looped = JumpTable::loop_section(6, 2, 3).unwrap();
+        assert_eq!(walk(&looped, 8), [0, 1, 2, 3, 2, 3, 2, 3]);
+        assert_eq!(
+            looped.check_coverage(),
+            Err(JumpError::Uncovered { position: 0 })
+        );
+
+        let skipped = JumpTable::skip_section(6, 2, 3).unwrap();
+        assert_eq!(walk(&skipped, 6), [0, 1, 4, 5, 0, 1]);
+        assert_eq!(
+            skipped.check_coverage(),
+            Err(JumpError::Uncovered { position: 2 })
+        );
+
+        // Skipping the start moves the wrap at the end
+        let skipped = JumpTable::skip_section(6, 0, 1).unwrap();
+        assert_eq!(walk(&skipped, 6), [0, 1, 2, 3, 4, 5]);
+        assert_eq!(walk(&skipped, 8)[6..], [2, 3]);
+
+        assert_eq!(
+            JumpTable::skip_section(6, 0, 5),
+            Err(JumpError::InvalidSection { start: 0, end: 5 })
+        );
+        assert!(JumpTable::loop_section(6, 4, 6).is_err());
+    }
+
+    #[test]
+    fn slices_cover_the_whole_buffer() {
+        // The order is a loop through the slices, so a rotation of it plays the buffer as it is
+        let table = JumpTable::slices(7, 3, &[2, 0, 1]).unwrap();
+        assert_eq!(walk(&table, 9), [0, 1, 2, 3, 4, 5, 6, 0, 1]);
+        let table = JumpTable::slices(7, 3, &[0, 2, 1]).unwrap();
+        assert_eq!(walk(&table, 8), [0, 1, 2, 6, 3, 4, 5, 0]);
+        assert!(table.covers_whole_buffer());
+
+        let shuffled = JumpTable::shuffled_slices(64, 8, 3).unwrap();
+        assert!(shuffled.covers_whole_buffer());
+        assert_eq!(shuffled, JumpTable::shuffled_slices(64, 8, 3).unwrap());
+        assert_eq!(JumpTable::slices(8, 4, &[0]), Err(JumpError::InvalidSlices));
+    }
+
+    #[test]
+    fn broken_jumps_are_rejected() {
+        assert_eq!(JumpTable::linear(0), Err(JumpError::EmptyBuffer));
+        assert_eq!(
+            JumpTable::from_jumps(4, &[Jump(3, 4)]),
+            Err(JumpError::OutOfRange { from: 3, to: 4 })
+        );
+        assert_eq!(
+            JumpTable::from_jumps(4, &[Jump(3, 0), Jump(3, 1)]),
+            Err(JumpError::DuplicateJump { from: 3 })
+        );
+        assert_eq!(
+            JumpTable::from_jumps(4, &[Jump(2, 0)]),
+            Err(JumpError::RunsOffTheEnd)
+        );
+        // The head gets stuck at 2 and never gets to the end
+        let stuck = JumpTable::from_jumps(4, &[Jump(2, 2), Jump(3, 0)]).unwrap();
+        assert_eq!(
+            stuck.check_coverage(),
+            Err(JumpError::Uncovered { position: 0 })
+        );
+    }
+}
@@ -0,0 +1,7 @@
+pub mod drift;
+pub mod engine;
+pub mod feedback_matrix;
+#[cfg(feature = "glitch")]
+pub mod jump_table;
+pub mod multihead;
+pub mod string;
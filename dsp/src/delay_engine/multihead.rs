@@ -0,0 +1,74 @@
+use super::engine::DelayEngine;
+
+/// The amount of playback heads of the tape echo
+pub const NUM_HEADS: usize = 3;
+
+/// The positions of the playback heads as multiples of the repeat rate.
+/// Like on a tape echo, the heads are spaced evenly along the tape after the record head.
+pub const HEAD_RATIOS: [f32; NUM_HEADS] = [1., 2., 3.];
+
+/// The delay times of all playback heads in ms for a repeat rate in ms
+///
+/// Usage:
+/// ```
+/// use delax_dsp::delay_engine::multihead::head_delays;
+///
+/// assert_eq!(head_delays(100.), [100., 200., 300.]);
+/// ```
+pub fn head_delays(repeat_rate: f32) -> [f32; NUM_HEADS] {
+    HEAD_RATIOS.map(|ratio| repeat_rate * ratio)
+}
+
+/// Read the enabled playback heads of a delay line, the delay time of the line is used as the repeat rate.
+///
+/// The heads are averaged, so the level of the repeats and the amount of feedback stay the same however many heads are on.
+/// With no heads enabled the tape is silent.
+pub fn read_heads(delay_engine: &DelayEngine, heads: [bool; NUM_HEADS]) -> f32 {
+    let enabled = heads.iter().filter(|head| **head).count();
+    if enabled == 0 {
+        return 0.;
+    }
+
+    let sum: f32 = head_delays(delay_engine.delay_time())
+        .into_iter()
+        .zip(heads)
+        .filter(|(_, enabled)| *enabled)
+        .map(|(delay_time, _)| delay_engine.sample_at(delay_time))
+        .sum();
+
+    sum / enabled as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Write an impulse and return at which samples the heads play it back
+    fn impulse_response(heads: [bool; NUM_HEADS]) -> Vec<(usize, f32)> {
+        // At 1000 Hz a ms is a sample
+        let mut engine = DelayEngine::new(64, 1000.);
+        engine.set_delay_amount(10.);
+
+        (0..40)
+            .filter_map(|sample| {
+                let out = read_heads(&engine, heads);
+                engine.write_sample(if sample == 0 { 1. } else { 0. });
+                (out != 0.).then_some((sample, out))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn heads_repeat_at_multiples_of_the_rate() {
+        assert_eq!(
+            impulse_response([true, true, true]),
+            vec![(10, 1. / 3.), (20, 1. / 3.), (30, 1. / 3.)]
+        );
+        assert_eq!(impulse_response([false, true, false]), vec![(20, 1.)]);
+        assert_eq!(
+            impulse_response([true, false, true]),
+            vec![(10, 0.5), (30, 0.5)]
+        );
+        assert_eq!(impulse_response([false; NUM_HEADS]), vec![]);
+    }
+}
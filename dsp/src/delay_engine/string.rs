@@ -0,0 +1,202 @@
+use crate::{random::XorShift, sample::Sample};
+
+/// The feedback gain that lets a string with a period in ms fall by 60 dB over `decay` seconds.
+///
+/// Usage:
+/// ```
+/// use delax_dsp::delay_engine::string::decay_gain;
+///
+/// // After a second worth of 10 ms periods the string is down by 60 dB
+/// let gain = decay_gain(10., 1.);
+/// assert!((gain.powi(100) - 0.001).abs() < 1e-6);
+/// ```
+pub fn decay_gain(period_ms: f32, decay: f32) -> f32 {
+    Sample::powf(10f32, -3. * period_ms / 1000. / decay.max(f32::EPSILON))
+}
+
+/// The ratio between the fast and the slow envelope of the input that counts as a transient
+const TRANSIENT_RATIO: f32 = 2.;
+/// Envelopes below this level are never transients, so noise and tails don't pluck the string
+const TRANSIENT_THRESHOLD: f32 = 0.01;
+/// The time constants of the fast and slow envelope in ms
+const FAST_ENVELOPE_MS: f32 = 1.;
+const SLOW_ENVELOPE_MS: f32 = 50.;
+/// The shortest time between two detected transients in ms
+const TRANSIENT_HOLDOFF_MS: f32 = 50.;
+
+/// Finds the attacks in a signal by comparing a fast and a slow envelope of it
+pub struct TransientDetector {
+    sample_rate: f32,
+    fast: f32,
+    slow: f32,
+    /// The samples until the next transient can be detected
+    holdoff: usize,
+}
+
+impl TransientDetector {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            sample_rate,
+            fast: 0.,
+            slow: 0.,
+            holdoff: 0,
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+    }
+
+    /// Feed a sample, returns the level of the attack if one starts at this sample
+    pub fn process(&mut self, sample: f32) -> Option<f32> {
+        let level = sample.abs();
+        self.fast += (level - self.fast) * envelope_coefficient(FAST_ENVELOPE_MS, self.sample_rate);
+        self.slow += (level - self.slow) * envelope_coefficient(SLOW_ENVELOPE_MS, self.sample_rate);
+
+        if self.holdoff > 0 {
+            self.holdoff -= 1;
+            return None;
+        }
+
+        if self.fast > TRANSIENT_THRESHOLD && self.fast > self.slow * TRANSIENT_RATIO {
+            self.holdoff = (TRANSIENT_HOLDOFF_MS / 1000. * self.sample_rate) as usize;
+            Some(self.fast)
+        } else {
+            None
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.fast = 0.;
+        self.slow = 0.;
+        self.holdoff = 0;
+    }
+}
+
+/// The coefficient of a one pole envelope follower with a time constant in ms
+fn envelope_coefficient(ms: f32, sample_rate: f32) -> f32 {
+    1. - Sample::exp(-1000. / (ms * sample_rate))
+}
+
+/// A short burst of white noise, the classic excitation of a Karplus-Strong string
+#[derive(Default)]
+pub struct NoiseBurst {
+    rng: XorShift,
+    /// The samples left in the current burst
+    remaining: usize,
+    level: f32,
+}
+
+impl NoiseBurst {
+    /// Start a burst of `len` samples at a level
+    pub fn trigger(&mut self, len: usize, level: f32) {
+        self.remaining = len;
+        self.level = level;
+    }
+
+    /// The next sample of the burst, silence once it's over
+    pub fn next_sample(&mut self) -> f32 {
+        if self.remaining == 0 {
+            return 0.;
+        }
+        self.remaining -= 1;
+
+        self.rng.next_bipolar() * self.level
+    }
+
+    pub fn reset(&mut self) {
+        self.remaining = 0;
+    }
+}
+
+/// Everything a delay line needs to act as a plucked string: the excitation from the input and the damping in the loop
+pub struct StringVoice {
+    detector: TransientDetector,
+    burst: NoiseBurst,
+    /// The state of the damping lowpass
+    damped: f32,
+}
+
+impl StringVoice {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            detector: TransientDetector::new(sample_rate),
+            burst: NoiseBurst::default(),
+            damped: 0.,
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.detector.set_sample_rate(sample_rate);
+    }
+
+    /// The noise that plucks the string. Every attack of the input starts a burst that fills one period of the string.
+    pub fn excite(&mut self, input: f32, period_samples: usize) -> f32 {
+        if let Some(level) = self.detector.process(input) {
+            self.burst.trigger(period_samples.max(1), level);
+        }
+
+        self.burst.next_sample()
+    }
+
+    /// Run the feedback through the damping lowpass. At full brightness the feedback passes unchanged,
+    /// darker settings lose the high harmonics faster than the fundamental, like a real string.
+    pub fn damp(&mut self, feedback: f32, brightness: f32) -> f32 {
+        let coefficient = 0.05 + 0.95 * brightness.clamp(0., 1.);
+        self.damped += (feedback - self.damped) * coefficient;
+        self.damped
+    }
+
+    pub fn reset(&mut self) {
+        self.detector.reset();
+        self.burst.reset();
+        self.damped = 0.;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attacks_are_detected_once() {
+        let mut detector = TransientDetector::new(1000.);
+        let transients: Vec<_> = (0..200)
+            .filter_map(|i| {
+                let sample = if (50..150).contains(&i) { 0.5 } else { 0. };
+                detector.process(sample).map(|_| i)
+            })
+            .collect();
+
+        assert_eq!(transients, vec![50]);
+    }
+
+    #[test]
+    fn quiet_input_doesnt_pluck() {
+        let mut detector = TransientDetector::new(1000.);
+        assert!((0..200).all(|_| detector.process(0.001).is_none()));
+    }
+
+    #[test]
+    fn burst_lasts_one_period() {
+        let mut voice = StringVoice::new(1000.);
+        let noise: Vec<_> = (0..20)
+            .map(|i| voice.excite(if i == 0 { 1. } else { 0. }, 8))
+            .collect();
+
+        assert!(noise[..8]
+            .iter()
+            .all(|sample| *sample != 0. && sample.abs() <= 1.));
+        assert!(noise[8..].iter().all(|sample| *sample == 0.));
+    }
+
+    #[test]
+    fn full_brightness_doesnt_damp() {
+        let mut voice = StringVoice::new(1000.);
+        assert_eq!(voice.damp(0.5, 1.), 0.5);
+        assert_eq!(voice.damp(-0.25, 1.), -0.25);
+
+        let mut voice = StringVoice::new(1000.);
+        assert!(voice.damp(0.5, 0.) < 0.5);
+    }
+}
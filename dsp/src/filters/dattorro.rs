@@ -1,8 +1,20 @@
+use alloc::{vec, vec::Vec};
+
+#[cfg(feature = "reverb")]
 use super::StereoFilter;
+#[cfg(feature = "reverb")]
+use crate::sanitize::sanitize;
+use crate::{sample::Sample, sanitize::flush_denormal};
 
-impl StereoFilter for DattorroReverb {
+#[cfg(feature = "reverb")]
+impl<T: Sample> StereoFilter for DattorroReverb<T> {
     fn process_stereo(&mut self, input_l: f32, input_r: f32) -> (f32, f32) {
-        self.process_stereo(input_l, input_r)
+        let (l, r) = self.process_stereo(T::from_f32(input_l), T::from_f32(input_r));
+        (l.to_f32(), r.to_f32())
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.set_sample_rate(sample_rate)
     }
 }
 
@@ -11,45 +23,65 @@ impl StereoFilter for DattorroReverb {
 ///
 /// Usage:
 /// ```
-/// use delax::filters::dattorro::DattorroReverb;
+/// use delax_dsp::filters::dattorro::DattorroReverb;
 ///
 /// let mut reverb = DattorroReverb::new(44100., 0.5);
 /// let (l, r) = reverb.process_stereo(0.5, 0.5);
 ///
 /// ```
+///
+/// The tank runs at the precision `T`, the settings are always f32.
+#[cfg(feature = "reverb")]
 #[derive(Clone)]
-pub struct DattorroReverb {
-    pre_delay: DelayLine,
-    bandwith_damper: Damper,
-    input_diffusor_1: InputDiffusor,
-    input_diffusor_2: InputDiffusor,
-    input_diffusor_3: InputDiffusor,
-    input_diffusor_4: InputDiffusor,
-    decay_diffusor_l: DecayDiffusor,
-    decay_diffusor_r: DecayDiffusor,
-    input_diffusor_l: InputDiffusor,
-    input_diffusor_r: InputDiffusor,
-    damper_l: Damper,
-    damper_r: Damper,
-    delay_line_1_l: DelayLine,
-    delay_line_2_l: DelayLine,
-    delay_line_1_r: DelayLine,
-    delay_line_2_r: DelayLine,
-    recursive_l: f32,
-    recursive_r: f32,
-    decay: f32,
-    tap_l_1: DelayLine,
-    tap_l_2: DelayLine,
-    tap_l_3: DelayLine,
-    tap_r_1: DelayLine,
-    tap_r_2: DelayLine,
-    tap_r_3: DelayLine,
-    gain: f32,
+pub struct DattorroReverb<T: Sample = f32> {
+    pre_delay: DelayLine<T>,
+    bandwith_damper: Damper<T>,
+    input_diffusor_1: InputDiffusor<T>,
+    input_diffusor_2: InputDiffusor<T>,
+    input_diffusor_3: InputDiffusor<T>,
+    input_diffusor_4: InputDiffusor<T>,
+    decay_diffusor_l: DecayDiffusor<T>,
+    decay_diffusor_r: DecayDiffusor<T>,
+    input_diffusor_l: InputDiffusor<T>,
+    input_diffusor_r: InputDiffusor<T>,
+    damper_l: Damper<T>,
+    damper_r: Damper<T>,
+    delay_line_1_l: DelayLine<T>,
+    delay_line_2_l: DelayLine<T>,
+    delay_line_1_r: DelayLine<T>,
+    delay_line_2_r: DelayLine<T>,
+    recursive_l: T,
+    recursive_r: T,
+    decay: T,
+    tap_l_1: DelayLine<T>,
+    tap_l_2: DelayLine<T>,
+    tap_l_3: DelayLine<T>,
+    tap_r_1: DelayLine<T>,
+    tap_r_2: DelayLine<T>,
+    tap_r_3: DelayLine<T>,
+    gain: T,
 }
 
+#[cfg(feature = "reverb")]
 impl DattorroReverb {
     /// Create a new reverb instance with a sample rate and an initial decay factor
     pub fn new(sample_rate: f32, decay: f32) -> Self {
+        Self::with_precision(sample_rate, decay)
+    }
+}
+
+#[cfg(feature = "reverb")]
+impl<T: Sample> DattorroReverb<T> {
+    /// Create a new reverb that runs at the precision `T`, see [DattorroReverb::new()].
+    ///
+    /// Usage:
+    /// ```
+    /// use delax_dsp::filters::dattorro::DattorroReverb;
+    ///
+    /// let mut reverb = DattorroReverb::<f64>::with_precision(44100., 0.5);
+    /// let (l, r) = reverb.process_stereo(0.5, 0.5);
+    /// ```
+    pub fn with_precision(sample_rate: f32, decay: f32) -> Self {
         let mut pre_delay = DelayLine::new(sample_rate as usize);
         pre_delay.set_delay(0);
 
@@ -70,24 +102,24 @@ impl DattorroReverb {
             delay_line_2_l: DelayLine::new(3720),
             delay_line_1_r: DelayLine::new(4217),
             delay_line_2_r: DelayLine::new(3163),
-            recursive_l: 0.,
-            recursive_r: 0.,
-            decay,
+            recursive_l: T::ZERO,
+            recursive_r: T::ZERO,
+            decay: T::from_f32(decay),
             tap_l_1: DelayLine::new(sample_rate as usize / 4),
             tap_l_2: DelayLine::new(sample_rate as usize / 4),
             tap_l_3: DelayLine::new(sample_rate as usize / 4),
             tap_r_1: DelayLine::new(sample_rate as usize / 4),
             tap_r_2: DelayLine::new(sample_rate as usize / 4),
             tap_r_3: DelayLine::new(sample_rate as usize / 4),
-            gain: 1.,
+            gain: T::ONE,
         }
     }
 
     /// Process a stereo signal through the reverb
     ///
     /// It will return the processed signal as a stereo pair.
-    pub fn process_stereo(&mut self, l: f32, r: f32) -> (f32, f32) {
-        let input = (l + r) / 2.;
+    pub fn process_stereo(&mut self, l: T, r: T) -> (T, T) {
+        let input = sanitize((l + r) / T::from_f32(2.));
         let pre_delayed = self.pre_delay.process(input);
         let bandwith_damped = self.bandwith_damper.process(pre_delayed);
 
@@ -106,8 +138,8 @@ impl DattorroReverb {
         self.recursive_r = self.decay_diffusor_r.process(self.recursive_r);
 
         // First taps
-        let left_init_tap: f32 = self.recursive_l;
-        let right_init_tap: f32 = self.recursive_r;
+        let left_init_tap = self.recursive_l;
+        let right_init_tap = self.recursive_r;
 
         self.recursive_l = self.delay_line_1_l.process(self.recursive_l);
         self.recursive_r = self.delay_line_1_r.process(self.recursive_r);
@@ -130,14 +162,15 @@ impl DattorroReverb {
         self.tap_l_3.insert(self.recursive_l);
         self.tap_r_3.insert(self.recursive_r);
 
-        self.recursive_l = self.delay_line_2_l.process(self.recursive_l);
-        self.recursive_r = self.delay_line_2_r.process(self.recursive_r);
+        // Keep the tank free of broken and subnormal values, since it never fully empties
+        self.recursive_l = sanitize(self.delay_line_2_l.process(self.recursive_l));
+        self.recursive_r = sanitize(self.delay_line_2_r.process(self.recursive_r));
 
         self.output(left_init_tap, right_init_tap)
     }
 
     /// Calculate the output from the taps with two inital taps
-    fn output(&self, left_init: f32, right_init: f32) -> (f32, f32) {
+    fn output(&self, left_init: T, right_init: T) -> (T, T) {
         // The delay lengths are all from the Dattorro paper
         let mut y_l =
             left_init + self.tap_r_1.get_with_delay(266) + self.tap_r_1.get_with_delay(2974)
@@ -157,15 +190,55 @@ impl DattorroReverb {
 
         // Double the gain, since the wet signal is very quiet without it
         // TODO: Check if it should be this quiet or if something went wrong
-        y_l *= self.gain * 2.;
-        y_r *= self.gain * 2.;
+        y_l *= self.gain * T::from_f32(2.);
+        y_r *= self.gain * T::from_f32(2.);
 
         (y_l, y_r)
     }
 
     /// Set the decay factor of the reverb
     pub fn set_decay(&mut self, decay: f32) {
-        self.decay = decay;
+        self.decay = T::from_f32(decay);
+    }
+
+    /// Empty the tank and all delay lines, so the tail stops right away
+    pub fn reset(&mut self) {
+        for delay_line in [
+            &mut self.pre_delay,
+            &mut self.delay_line_1_l,
+            &mut self.delay_line_2_l,
+            &mut self.delay_line_1_r,
+            &mut self.delay_line_2_r,
+            &mut self.tap_l_1,
+            &mut self.tap_l_2,
+            &mut self.tap_l_3,
+            &mut self.tap_r_1,
+            &mut self.tap_r_2,
+            &mut self.tap_r_3,
+        ] {
+            delay_line.reset();
+        }
+        for diffusor in [
+            &mut self.input_diffusor_1,
+            &mut self.input_diffusor_2,
+            &mut self.input_diffusor_3,
+            &mut self.input_diffusor_4,
+            &mut self.input_diffusor_l,
+            &mut self.input_diffusor_r,
+        ] {
+            diffusor.delay_line.reset();
+        }
+        self.decay_diffusor_l.delay_line.reset();
+        self.decay_diffusor_r.delay_line.reset();
+        for damper in [
+            &mut self.bandwith_damper,
+            &mut self.damper_l,
+            &mut self.damper_r,
+        ] {
+            damper.last_sample = T::ZERO;
+        }
+        self.recursive_l = T::ZERO;
+        self.recursive_r = T::ZERO;
     }
 
     /// Update the sample rate of everything.
@@ -185,17 +258,19 @@ impl DattorroReverb {
 
 #[derive(Debug, Clone)]
 /// A general purpose delay line that only supports delay lengths as samples
-struct DelayLine {
-    buffer: Vec<f32>,
+struct DelayLine<T: Sample> {
+    buffer: Vec<T>,
     delay: usize,
     write_index: usize,
 }
 
-impl DelayLine {
+// Without the reverb only the input diffusors of the spread use the delay lines
+#[cfg_attr(not(feature = "reverb"), allow(dead_code))]
+impl<T: Sample> DelayLine<T> {
     /// Create a new delay line with a maximum delay length
     fn new(max_delay: usize) -> Self {
         Self {
-            buffer: vec![0.0; (max_delay) as usize],
+            buffer: vec![T::ZERO; max_delay],
             delay: max_delay,
             write_index: 0,
         }
@@ -209,7 +284,7 @@ impl DelayLine {
     /// Process a sample through the delay line
     ///
     /// This is the same as get() and then insert()
-    fn process(&mut self, input: f32) -> f32 {
+    fn process(&mut self, input: T) -> T {
         let delayed_index = (self.write_index as i32 - self.delay as i32)
             .rem_euclid(self.buffer.len() as i32) as usize;
         let delayed = self.buffer[delayed_index];
@@ -223,46 +298,56 @@ impl DelayLine {
     /// Get the delayed sample at the current delay length
     ///
     /// get() and insert() together are the same as process()
-    fn get(&self) -> f32 {
+    fn get(&self) -> T {
         let delayed_index = (self.write_index as i32 - self.delay as i32)
             .rem_euclid(self.buffer.len() as i32) as usize;
         self.buffer[delayed_index]
     }
 
     /// Get the delayed sample at a specific delay length
-    fn get_with_delay(&self, delay: usize) -> f32 {
+    fn get_with_delay(&self, delay: usize) -> T {
         let delayed_index =
             (self.write_index as i32 - delay as i32).rem_euclid(self.buffer.len() as i32) as usize;
         self.buffer[delayed_index]
     }
 
     /// Insert a sample into the delay line
-    fn insert(&mut self, input: f32) {
+    fn insert(&mut self, input: T) {
         self.buffer[self.write_index] = input;
         self.write_index = (self.write_index + 1) % self.buffer.len();
     }
+
+    /// Fill the delay line with silence
+    fn reset(&mut self) {
+        self.buffer.iter_mut().for_each(|sample| *sample = T::ZERO);
+    }
 }
 
 #[derive(Clone)]
 /// An input diffusor with a structure taken from the Dattorro paper. It acts as an all pass filter.
-struct InputDiffusor {
-    delay_line: DelayLine,
-    gain: f32,
+pub(crate) struct InputDiffusor<T: Sample> {
+    delay_line: DelayLine<T>,
+    gain: T,
 }
 
-impl InputDiffusor {
+impl<T: Sample> InputDiffusor<T> {
     /// Create a new input diffusor with a delay length and gain
-    fn new(delay: usize, gain: f32) -> Self {
+    pub(crate) fn new(delay: usize, gain: f32) -> Self {
         Self {
             delay_line: DelayLine::new(delay),
-            gain: gain,
+            gain: T::from_f32(gain),
         }
     }
 
+    /// Change how much the input diffusor diffuses, at 0 it only delays
+    pub(crate) fn set_gain(&mut self, gain: f32) {
+        self.gain = T::from_f32(gain);
+    }
+
     /// Process a sample through the input diffusor
-    fn process(&mut self, input: f32) -> f32 {
+    pub(crate) fn process(&mut self, input: T) -> T {
         let delayed = self.delay_line.get();
-        let in_changed = input + delayed * self.gain * -1.;
+        let in_changed = flush_denormal(input - delayed * self.gain);
 
         self.delay_line.insert(in_changed);
 
@@ -270,17 +355,19 @@ impl InputDiffusor {
     }
 
     /// Tap the delay line at position 0
-    fn tap(&self) -> f32 {
+    #[cfg(feature = "reverb")]
+    fn tap(&self) -> T {
         self.delay_line.get_with_delay(0)
     }
 }
 
+#[cfg(feature = "reverb")]
 #[derive(Clone)]
 /// A diffusor that allows modulation of the delay length and has a slightly different structure from [InputDiffusor]
-struct DecayDiffusor {
-    delay_line: DelayLine,
+struct DecayDiffusor<T: Sample> {
+    delay_line: DelayLine<T>,
     delay: usize,
-    gain: f32,
+    gain: T,
     sample_rate: f32,
     excursion: f32,
     excursion_tick: f32,
@@ -288,13 +375,14 @@ struct DecayDiffusor {
     excursion_depth: f32,
 }
 
-impl DecayDiffusor {
+#[cfg(feature = "reverb")]
+impl<T: Sample> DecayDiffusor<T> {
     /// Create a new decay diffusor with a delay length, gain, and sample rate
     fn new(sample_rate: f32, delay: usize, gain: f32) -> Self {
         Self {
             delay_line: DelayLine::new(delay + 16),
-            delay: delay,
-            gain: gain,
+            delay,
+            gain: T::from_f32(gain),
             excursion: 0.,
             excursion_tick: 0.,
             excursion_rate: 1.,
@@ -304,23 +392,24 @@ impl DecayDiffusor {
     }
 
     /// Process a sample through the decay diffusor
-    fn process(&mut self, input: f32) -> f32 {
+    fn process(&mut self, input: T) -> T {
         // Update excursion and delay length
         self.modulate_excursion();
 
         let delayed = self
             .delay_line
-            .get_with_delay(self.delay + self.excursion.floor() as usize);
-        let in_changed = input + delayed * self.gain;
+            .get_with_delay(self.delay + Sample::floor(self.excursion) as usize);
+        let in_changed = flush_denormal(input + delayed * self.gain);
 
         self.delay_line.insert(in_changed);
 
-        delayed + in_changed * self.gain * -1.
+        delayed - in_changed * self.gain
     }
 
     /// Modulates the excursion for each sample at a specific rate
     fn modulate_excursion(&mut self) {
-        self.excursion = (self.excursion_tick * self.excursion_rate).sin() * self.excursion_depth;
+        self.excursion =
+            Sample::sin(self.excursion_tick * self.excursion_rate) * self.excursion_depth;
         self.excursion_tick += 1. / self.sample_rate;
     }
 
@@ -330,27 +419,29 @@ impl DecayDiffusor {
     }
 }
 
+#[cfg(feature = "reverb")]
 #[derive(Clone)]
 /// A simple damper that smooths the signal using a damping factor.
 ///
 /// Structure is from the Dattorro paper.
-struct Damper {
-    last_sample: f32,
-    damping: f32,
+struct Damper<T: Sample> {
+    last_sample: T,
+    damping: T,
 }
 
-impl Damper {
+#[cfg(feature = "reverb")]
+impl<T: Sample> Damper<T> {
     /// Create a new damper with a damping factor
     fn new(damping: f32) -> Self {
         Self {
-            last_sample: 0.,
-            damping,
+            last_sample: T::ZERO,
+            damping: T::from_f32(damping),
         }
     }
 
     /// Process a sample through the damper
-    fn process(&mut self, input: f32) -> f32 {
-        let out = input * (1. - self.damping) + self.last_sample * self.damping;
+    fn process(&mut self, input: T) -> T {
+        let out = flush_denormal(input * (T::ONE - self.damping) + self.last_sample * self.damping);
         self.last_sample = out;
         out
     }
@@ -373,6 +464,21 @@ mod dattorro_tests {
         assert_eq!(delay_line.process(8.), 6.);
     }
 
+    #[cfg(feature = "reverb")]
+    #[test]
+    fn reset_stops_the_tail() {
+        let mut reverb = DattorroReverb::new(1000., 0.8);
+        reverb.process_stereo(1., 1.);
+        for _ in 0..100 {
+            reverb.process_stereo(0., 0.);
+        }
+
+        reverb.reset();
+        for _ in 0..2000 {
+            assert_eq!(reverb.process_stereo(0., 0.), (0., 0.));
+        }
+    }
+
     #[test]
     fn input_diffusor() {
         let mut input_diffusor = InputDiffusor::new(2, 0.5);
@@ -382,4 +488,18 @@ mod dattorro_tests {
         assert_eq!(input_diffusor.process(2.), 1.);
         assert_eq!(input_diffusor.process(3.), 2.25);
     }
+
+    #[cfg(feature = "reverb")]
+    #[test]
+    fn pathological_input() {
+        let mut reverb = DattorroReverb::new(44100., 0.5);
+
+        reverb.process_stereo(0.5, 0.5);
+        reverb.process_stereo(f32::NAN, f32::INFINITY);
+
+        for _ in 0..44100 {
+            let (l, r) = reverb.process_stereo(0., 0.);
+            assert!(l.is_finite() && r.is_finite());
+        }
+    }
 }
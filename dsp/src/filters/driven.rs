@@ -0,0 +1,157 @@
+use super::{
+    simper::{SimperSinSVF, StereoSimperSinSVF},
+    Filter, StereoFilter,
+};
+use crate::{
+    sample::Sample,
+    simd::{F32x2, X2},
+};
+
+/// A SVF that's driven into saturation: the input is boosted by the drive and the integrators of the filter are
+/// soft clipped, the output is turned back down by the drive.
+///
+/// Quiet signals pass like through the plain filter, loud ones and a ringing resonance are rounded off inside
+/// the filter, which gives the repeats the character of an analog filter. A drive of 1 is the plain filter.
+///
+/// Usage:
+/// ```
+/// use delax_dsp::filters::{driven::DrivenSVF, simper::SimperSinSVF, Filter};
+///
+/// let mut filter = DrivenSVF::new(SimperSinSVF::new(44100.));
+/// filter.filter_mut().set_cutoff(1000.);
+/// filter.set_drive(8.);
+/// let out = filter.process(0.9);
+/// ```
+#[derive(Debug, Clone)]
+pub struct DrivenSVF<F> {
+    filter: F,
+    drive: f32,
+}
+
+impl<F> DrivenSVF<F> {
+    pub fn new(filter: F) -> Self {
+        Self { filter, drive: 1. }
+    }
+
+    /// Set the gain into the filter, from 1 upwards
+    pub fn set_drive(&mut self, drive: f32) {
+        self.drive = drive.max(1.);
+    }
+
+    pub fn drive(&self) -> f32 {
+        self.drive
+    }
+
+    /// The filter that's driven, to change its settings
+    pub fn filter(&self) -> &F {
+        &self.filter
+    }
+
+    pub fn filter_mut(&mut self) -> &mut F {
+        &mut self.filter
+    }
+
+    /// How far the integrators are bent towards their soft clipped value. This starts at 0 for a drive of 1,
+    /// so turning the drive up from there doesn't jump.
+    fn saturation(&self) -> f32 {
+        1. - 1. / self.drive
+    }
+}
+
+impl<T: Sample> Filter for DrivenSVF<SimperSinSVF<T>> {
+    fn process(&mut self, input: f32) -> f32 {
+        if self.drive <= 1. {
+            return self.filter.process(input);
+        }
+
+        let out = self.filter.tick_sample(T::from_f32(input * self.drive));
+        self.filter.saturate_state(T::from_f32(self.saturation()));
+        out.to_f32() / self.drive
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.filter.set_sample_rate(sample_rate)
+    }
+
+    /// The saturation only takes level away, so the peak is the one of the plain filter
+    fn peak_gain(&self) -> f32 {
+        self.filter.peak_gain()
+    }
+}
+
+impl<T: Sample> StereoFilter for DrivenSVF<StereoSimperSinSVF<T>> {
+    fn process_stereo(&mut self, input_l: f32, input_r: f32) -> (f32, f32) {
+        if self.drive <= 1. {
+            return self.filter.process_stereo(input_l, input_r);
+        }
+
+        let input = F32x2::new(input_l, input_r) * self.drive;
+        let out = self.filter.tick_sample(X2::from_f32(input));
+        self.filter.saturate_state(T::from_f32(self.saturation()));
+        (out.to_f32() * (1. / self.drive)).into()
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        StereoFilter::set_sample_rate(&mut self.filter, sample_rate)
+    }
+
+    fn peak_gain(&self) -> (f32, f32) {
+        self.filter.peak_gain()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filters::SVFFilterMode;
+
+    /// The loudest output of a filter for a sine at its cutoff, after it settled
+    fn peak(filter: &mut impl Filter, amplitude: f32) -> f32 {
+        let sine =
+            |i: usize| amplitude * Sample::sin(core::f32::consts::TAU * 1000. * i as f32 / 44100.);
+        (0..44100).for_each(|i| {
+            filter.process(sine(i));
+        });
+        (0..4410).fold(0f32, |peak, i| peak.max(filter.process(sine(i)).abs()))
+    }
+
+    fn resonant_band_pass(drive: f32) -> DrivenSVF<SimperSinSVF> {
+        let mut filter = DrivenSVF::new(SimperSinSVF::new(44100.));
+        filter.filter_mut().set_cutoff_res(1000., 1.);
+        filter.filter_mut().set_mode(SVFFilterMode::Band);
+        filter.set_drive(drive);
+        filter
+    }
+
+    #[test]
+    fn drive_rounds_off_loud_signals() {
+        let plain = peak(&mut resonant_band_pass(1.), 1.);
+        let driven = peak(&mut resonant_band_pass(8.), 1.);
+        assert!(driven < plain * 0.5, "{driven} vs {plain}");
+
+        // Quiet signals barely notice the drive
+        let plain = peak(&mut resonant_band_pass(1.), 0.001);
+        let driven = peak(&mut resonant_band_pass(8.), 0.001);
+        assert!((driven - plain).abs() < plain * 0.05, "{driven} vs {plain}");
+    }
+
+    #[test]
+    fn stereo_matches_mono() {
+        let mut mono = resonant_band_pass(4.);
+        let mut stereo = DrivenSVF::new(StereoSimperSinSVF::new(44100.));
+        stereo
+            .filter_mut()
+            .set_cutoff_res(F32x2::splat(1000.), F32x2::splat(1.));
+        stereo
+            .filter_mut()
+            .set_mode(SVFFilterMode::Band, SVFFilterMode::Band);
+        stereo.set_drive(4.);
+
+        for i in 0..1000 {
+            let input = (i as f32 * 0.05).sin();
+            let (l, r) = stereo.process_stereo(input, input);
+            let out = mono.process(input);
+            assert!((l - out).abs() < 1e-5 && (r - out).abs() < 1e-5);
+        }
+    }
+}
@@ -0,0 +1,45 @@
+pub mod dattorro;
+pub mod driven;
+pub mod simper;
+pub mod spread;
+pub mod stack;
+
+/// The lowest cutoff of the filters in Hz
+pub const MIN_CUTOFF: f32 = 10.;
+/// The highest cutoff of the filters in Hz. The filters themselves also stay below Nyquist.
+pub const MAX_CUTOFF: f32 = 20000.;
+
+/// The output of a SVF
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SVFFilterMode {
+    Low,
+    Band,
+    High,
+    Notch,
+    Peak,
+}
+
+pub trait Filter: Send + Sync {
+    fn process(&mut self, input: f32) -> f32;
+
+    /// Retune the filter to a new sample rate, keeping its settings
+    fn set_sample_rate(&mut self, sample_rate: f32);
+
+    /// The approximate gain of the filter at its loudest frequency with its current settings, so a pipeline can
+    /// make up for it. Filters that barely change the level keep the default of 1.
+    fn peak_gain(&self) -> f32 {
+        1.
+    }
+}
+
+pub trait StereoFilter: Send + Sync {
+    fn process_stereo(&mut self, input_l: f32, input_r: f32) -> (f32, f32);
+
+    /// Retune the filter to a new sample rate, keeping its settings
+    fn set_sample_rate(&mut self, sample_rate: f32);
+
+    /// The approximate gain of the left and the right channel at their loudest frequency, see [Filter::peak_gain()]
+    fn peak_gain(&self) -> (f32, f32) {
+        (1., 1.)
+    }
+}
@@ -0,0 +1,929 @@
+use crate::{
+    sample::Sample,
+    sanitize::{flush_denormal, sanitize},
+    simd::{F32x2, X2},
+};
+
+use super::{Filter, SVFFilterMode, StereoFilter, MIN_CUTOFF};
+
+/// A SVF filter implemented using the paper by Andrew Simper from Cytomic
+/// https://cytomic.com/files/dsp/SvfLinearTrapOptimised2.pdf
+///
+/// The state and the coefficients are kept at the precision `T`, the settings are always f32.
+#[derive(Debug, Clone)]
+pub struct SimperTanSVF<T: Sample = f32> {
+    ic1eq: T,
+    ic2eq: T,
+    cutoff: f32,
+    sample_rate: f32,
+    g: T,
+    res: f32,
+    k: T,
+    a1: T,
+    a2: T,
+    mode: SVFFilterMode,
+}
+
+impl SimperTanSVF {
+    /// Create a new filter given a sample rate. This rate can be updated later on.
+    ///
+    /// Usage:
+    /// ```
+    /// use delax_dsp::filters::simper::SimperTanSVF;
+    ///
+    /// let mut filter = SimperTanSVF::new(44100.);
+    /// let (low, band, high) = filter.tick_sample_full(0.4);
+    /// ```
+    pub fn new(sample_rate: f32) -> Self {
+        Self::with_precision(sample_rate)
+    }
+}
+
+impl<T: Sample> SimperTanSVF<T> {
+    /// Create a new filter that runs at the precision `T`, see [SimperTanSVF::new()].
+    ///
+    /// Usage:
+    /// ```
+    /// use delax_dsp::filters::simper::SimperTanSVF;
+    ///
+    /// let mut filter = SimperTanSVF::<f64>::with_precision(44100.);
+    /// let (low, band, high) = filter.tick_sample_full(0.4);
+    /// ```
+    pub fn with_precision(sample_rate: f32) -> Self {
+        let mut filter = Self {
+            ic1eq: T::ZERO,
+            ic2eq: T::ZERO,
+            cutoff: 1000.,
+            sample_rate,
+            g: T::ZERO,
+            res: 0.2,
+            k: T::ZERO,
+            a1: T::ZERO,
+            a2: T::ZERO,
+            mode: SVFFilterMode::Low,
+        };
+        filter.reinit();
+        filter
+    }
+
+    /// Set the cutoff value. The coefficients are only recalculated if it changed.
+    pub fn set_cutoff(&mut self, cutoff: f32) {
+        if cutoff != self.cutoff {
+            self.cutoff = cutoff;
+            self.reinit();
+        }
+    }
+
+    /// Set the sample rate
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.reinit();
+    }
+
+    /// Set the resonance value. The coefficients are only recalculated if it changed.
+    pub fn set_res(&mut self, res: f32) {
+        if res != self.res {
+            self.res = res;
+            self.reinit();
+        }
+    }
+
+    /// Set the cutoff and resonance at once, so the coefficients are recalculated at most once
+    pub fn set_cutoff_res(&mut self, cutoff: f32, res: f32) {
+        if cutoff != self.cutoff || res != self.res {
+            self.cutoff = cutoff;
+            self.res = res;
+            self.reinit();
+        }
+    }
+
+    pub fn set_mode(&mut self, mode: SVFFilterMode) {
+        self.mode = mode;
+    }
+
+    /// Recalculate all the held values.
+    /// This should be called after a value like the resonance is changed.
+    fn reinit(&mut self) {
+        self.g = (T::PI * T::from_f32(clamp_cutoff(self.cutoff, self.sample_rate))
+            / T::from_f32(self.sample_rate))
+        .tan();
+
+        // The same damping as the sin variant, so both sound the same and stay stable with a res of 1
+        self.k = T::from_f32(2.) - T::from_f32(1.45) * T::from_f32(self.res);
+
+        self.a1 = T::ONE / (T::ONE + self.g * (self.g + self.k));
+        self.a2 = self.g * self.a1;
+    }
+
+    /// Run the filter on a sample.
+    ///
+    /// This returns the values as (low, band, high).
+    /// Other filter types can be calculated based on these as follows:
+    ///
+    /// notch = low + high
+    ///
+    /// peak = low - high
+    ///
+    /// For an all-pass filter use [SimperTanSVF::tick_sample_allpass()]
+    ///
+    /// Usage:
+    /// ```
+    /// use delax_dsp::filters::simper::SimperTanSVF;
+    ///
+    /// let mut filter = SimperTanSVF::new(44100.);
+    /// let (low, band, high) = filter.tick_sample_full(0.4);
+    ///
+    /// let notch = low + high;
+    /// let peak = low - high;
+    /// ```
+    pub fn tick_sample_full(&mut self, sample: T) -> (T, T, T) {
+        let sample = sanitize(sample);
+        let two = T::from_f32(2.);
+
+        let v1 = self.a1 * self.ic1eq + self.a2 * (sample - self.ic2eq);
+        let v2 = self.ic2eq + self.g * v1;
+
+        self.ic1eq = two * v1 - self.ic1eq;
+        self.ic2eq = two * v2 - self.ic2eq;
+        (self.ic1eq, self.ic2eq) = scrub_state(self.ic1eq, self.ic2eq);
+
+        let low = v2;
+        let band = v1;
+        let high = sample - self.k * v1 - v2;
+
+        (low, band, high)
+    }
+
+    /// Run the filter on a sample in allpass mode.
+    ///
+    /// For all the other filter modes use [SimperTanSVF::tick_sample()].
+    /// Usage:
+    /// ```
+    /// use delax_dsp::filters::simper::SimperTanSVF;
+    ///
+    /// let mut filter = SimperTanSVF::new(44100.);
+    /// let all = filter.tick_sample_allpass(0.4);
+    /// ```
+    pub fn tick_sample_allpass(&mut self, sample: T) -> T {
+        let (low, band, high) = self.tick_sample_full(sample);
+        low + high - self.k * band
+    }
+
+    /// Run the filter using the model that is set internally
+    pub fn tick_sample(&mut self, sample: T) -> T {
+        let (low, band, high) = self.tick_sample_full(sample);
+        select_mode(self.mode, low, band, high)
+    }
+}
+
+impl<T: Sample> Filter for SimperTanSVF<T> {
+    fn process(&mut self, input: f32) -> f32 {
+        self.tick_sample(T::from_f32(input)).to_f32()
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.set_sample_rate(sample_rate)
+    }
+
+    fn peak_gain(&self) -> f32 {
+        mode_peak_gain(self.mode, self.k.to_f32())
+    }
+}
+
+/// A SVF filter implemented using the paper by Andrew Simper from Cytomic
+/// https://cytomic.com/files/dsp/SvfLinearTrapezoidalSin.pdf
+///
+/// The state and the coefficients are kept at the precision `T`, the settings are always f32.
+#[derive(Debug, Clone)]
+pub struct SimperSinSVF<T: Sample = f32> {
+    res: f32,
+    cutoff: f32,
+    sample_rate: f32,
+
+    ic1eq: T,
+    ic2eq: T,
+
+    k: T,
+    g0: T,
+    g1: T,
+    g2: T,
+
+    mode: SVFFilterMode,
+}
+
+impl SimperSinSVF {
+    /// Create a new filter given a sample rate. This rate can be updated later on.
+    ///
+    /// Usage:
+    /// ```
+    /// use delax_dsp::filters::simper::SimperSinSVF;
+    ///
+    /// let mut filter = SimperSinSVF::new(44100.);
+    /// let (low, band, high) = filter.tick_sample_full(0.4);
+    /// ```
+    pub fn new(sample_rate: f32) -> Self {
+        Self::with_precision(sample_rate)
+    }
+}
+
+impl<T: Sample> SimperSinSVF<T> {
+    /// Create a new filter that runs at the precision `T`, see [SimperSinSVF::new()].
+    ///
+    /// Usage:
+    /// ```
+    /// use delax_dsp::filters::simper::SimperSinSVF;
+    ///
+    /// let mut filter = SimperSinSVF::<f64>::with_precision(44100.);
+    /// let (low, band, high) = filter.tick_sample_full(0.4);
+    /// ```
+    pub fn with_precision(sample_rate: f32) -> Self {
+        let ic1eq = T::ZERO;
+        let ic2eq = T::ZERO;
+
+        let cutoff = 500.;
+        let w = T::PI * T::from_f32(cutoff) / T::from_f32(sample_rate);
+
+        let res = 0.2;
+
+        // The values for k could be fine-tuned
+        let two = T::from_f32(2.);
+        let k = two - two * T::from_f32(res);
+
+        let s1 = w.sin();
+        let s2 = (two * w).sin();
+
+        let nrm = T::ONE / (two + k * s2);
+
+        let g0 = s2 * nrm;
+        let g1 = (-two * s1 * s1 - k * s2) * nrm;
+        let g2 = (two * s1 * s1) * nrm;
+
+        Self {
+            ic1eq,
+            ic2eq,
+            cutoff,
+            sample_rate,
+            res,
+            k,
+            g0,
+            g1,
+            g2,
+            mode: SVFFilterMode::Low,
+        }
+    }
+
+    /// Set the cutoff value. The coefficients are only recalculated if it changed.
+    pub fn set_cutoff(&mut self, cutoff: f32) {
+        if cutoff != self.cutoff {
+            self.cutoff = cutoff;
+            self.reinit();
+        }
+    }
+
+    /// Set the sample rate
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.reinit();
+    }
+
+    /// Set the resonance value. The coefficients are only recalculated if it changed.
+    pub fn set_res(&mut self, res: f32) {
+        if res != self.res {
+            self.res = res;
+            self.reinit();
+        }
+    }
+
+    /// Set the cutoff and resonance at once, so the coefficients are recalculated at most once
+    pub fn set_cutoff_res(&mut self, cutoff: f32, res: f32) {
+        if cutoff != self.cutoff || res != self.res {
+            self.cutoff = cutoff;
+            self.res = res;
+            self.reinit();
+        }
+    }
+
+    pub fn set_mode(&mut self, mode: SVFFilterMode) {
+        self.mode = mode;
+    }
+
+    /// Recalculate all the held values.
+    /// This should be called after a value like the resonance is changed.
+    fn reinit(&mut self) {
+        let w = T::PI * T::from_f32(clamp_cutoff(self.cutoff, self.sample_rate))
+            / T::from_f32(self.sample_rate);
+        let two = T::from_f32(2.);
+
+        // Note: A res of 1 is very unstable for this delay, so it's limited using the lower. At 1.45 it's just still stable with res = 1.
+        // self.k = 2. - 2. * self.res
+        self.k = two - T::from_f32(1.45) * T::from_f32(self.res);
+
+        // sin(2w) = 2 sin(w) cos(w), so a single sin_cos is enough
+        let (s1, c1) = (w.sin(), w.cos());
+        let s2 = two * s1 * c1;
+
+        let nrm = T::ONE / (two + self.k * s2);
+
+        self.g0 = s2 * nrm;
+        self.g1 = (-two * s1 * s1 - self.k * s2) * nrm;
+        self.g2 = (two * s1 * s1) * nrm;
+    }
+
+    /// Run the filter on a sample.
+    ///
+    /// This returns the values as (low, band, high).
+    /// Other filter types can be calculated based on these as follows:
+    ///
+    /// notch = low + high
+    ///
+    /// peak = low - high
+    ///
+    /// Usage:
+    /// ```
+    /// use delax_dsp::filters::simper::SimperSinSVF;
+    ///
+    /// let mut filter = SimperSinSVF::new(44100.);
+    /// let (low, band, high) = filter.tick_sample_full(0.4);
+    ///
+    /// let notch = low + high;
+    /// let peak = low - high;
+    /// ```
+    pub fn tick_sample_full(&mut self, sample: T) -> (T, T, T) {
+        let sample = sanitize(sample);
+        let two = T::from_f32(2.);
+
+        let t0 = sample - self.ic2eq;
+        let t1 = self.g0 * t0 + self.g1 * self.ic1eq;
+        let t2 = self.g2 * t0 + self.g0 * self.ic1eq;
+        let v1 = t1 + self.ic1eq;
+        let v2 = t2 + self.ic2eq;
+
+        self.ic1eq += two * t1;
+        self.ic2eq += two * t2;
+        (self.ic1eq, self.ic2eq) = scrub_state(self.ic1eq, self.ic2eq);
+
+        let high = sample - self.k * v1 - v2;
+        let band = v1;
+        let low = v2;
+        (low, band, high)
+    }
+
+    /// Run the filter using the model that is set internally
+    pub fn tick_sample(&mut self, sample: T) -> T {
+        let (low, band, high) = self.tick_sample_full(sample);
+        select_mode(self.mode, low, band, high)
+    }
+
+    /// Bend the integrators towards their soft clipped value by `amount`, for a [super::driven::DrivenSVF]
+    pub(crate) fn saturate_state(&mut self, amount: T) {
+        self.ic1eq += (self.ic1eq.tanh() - self.ic1eq) * amount;
+        self.ic2eq += (self.ic2eq.tanh() - self.ic2eq) * amount;
+    }
+}
+
+impl<T: Sample> Filter for SimperSinSVF<T> {
+    fn process(&mut self, input: f32) -> f32 {
+        self.tick_sample(T::from_f32(input)).to_f32()
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.set_sample_rate(sample_rate)
+    }
+
+    fn peak_gain(&self) -> f32 {
+        mode_peak_gain(self.mode, self.k.to_f32())
+    }
+}
+
+/// Two [SimperSinSVF]s running side by side, with the left and right channel packed into the lanes of a [X2].
+///
+/// Both channels share one set of vector operations per sample instead of running the same math twice.
+/// The channels can still have separate cutoff, resonance and mode settings.
+///
+/// Usage:
+/// ```
+/// use delax_dsp::{filters::simper::StereoSimperSinSVF, simd::F32x2};
+///
+/// let mut filter = StereoSimperSinSVF::new(44100.);
+/// filter.set_cutoff(F32x2::new(500., 1000.));
+/// let out = filter.tick_sample(F32x2::new(0.4, 0.4));
+/// ```
+#[derive(Debug, Clone)]
+pub struct StereoSimperSinSVF<T: Sample = f32> {
+    res: F32x2,
+    cutoff: F32x2,
+    sample_rate: f32,
+
+    ic1eq: X2<T>,
+    ic2eq: X2<T>,
+
+    k: X2<T>,
+    g0: X2<T>,
+    g1: X2<T>,
+    g2: X2<T>,
+
+    mode: [SVFFilterMode; 2],
+    /// Whether the output is turned down by the resonant peak, see [StereoSimperSinSVF::set_compensated()]
+    compensated: bool,
+    /// The gain of the output of each channel, below 1 while the resonance is compensated
+    compensation: X2<T>,
+}
+
+impl StereoSimperSinSVF {
+    /// Create a new stereo filter given a sample rate. This rate can be updated later on.
+    pub fn new(sample_rate: f32) -> Self {
+        Self::with_precision(sample_rate)
+    }
+}
+
+impl<T: Sample> StereoSimperSinSVF<T> {
+    /// Create a new stereo filter that runs at the precision `T`, see [StereoSimperSinSVF::new()].
+    pub fn with_precision(sample_rate: f32) -> Self {
+        let mut filter = Self {
+            res: F32x2::splat(0.2),
+            cutoff: F32x2::splat(500.),
+            sample_rate,
+            ic1eq: X2::splat(T::ZERO),
+            ic2eq: X2::splat(T::ZERO),
+            k: X2::splat(T::ZERO),
+            g0: X2::splat(T::ZERO),
+            g1: X2::splat(T::ZERO),
+            g2: X2::splat(T::ZERO),
+            mode: [SVFFilterMode::Low; 2],
+            compensated: false,
+            compensation: X2::splat(T::ONE),
+        };
+        filter.reinit();
+        filter
+    }
+
+    /// Set the cutoff value of both channels. The coefficients are only recalculated if it changed.
+    pub fn set_cutoff(&mut self, cutoff: F32x2) {
+        if cutoff != self.cutoff {
+            self.cutoff = cutoff;
+            self.reinit();
+        }
+    }
+
+    /// Set the sample rate
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.reinit();
+    }
+
+    /// Set the resonance value of both channels. The coefficients are only recalculated if it changed.
+    pub fn set_res(&mut self, res: F32x2) {
+        if res != self.res {
+            self.res = res;
+            self.reinit();
+        }
+    }
+
+    /// Set the cutoff and resonance of both channels at once, so the coefficients are recalculated at most once.
+    /// This is what should be called with smoothed values every sample.
+    pub fn set_cutoff_res(&mut self, cutoff: F32x2, res: F32x2) {
+        if cutoff != self.cutoff || res != self.res {
+            self.cutoff = cutoff;
+            self.res = res;
+            self.reinit();
+        }
+    }
+
+    /// Set the filter mode of the left and right channel
+    pub fn set_mode(&mut self, mode_l: SVFFilterMode, mode_r: SVFFilterMode) {
+        if [mode_l, mode_r] != self.mode {
+            self.mode = [mode_l, mode_r];
+            self.update_compensation();
+        }
+    }
+
+    /// Turn the output down by the resonant peak of the mode, so the loudest frequency stays at the level of the input
+    /// however high the resonance goes. Without resonance nothing changes, the filters are only ever turned down.
+    ///
+    /// Usage:
+    /// ```
+    /// use delax_dsp::filters::{simper::StereoSimperSinSVF, SVFFilterMode, StereoFilter};
+    ///
+    /// let mut filter = StereoSimperSinSVF::new(44100.);
+    /// filter.set_mode(SVFFilterMode::Band, SVFFilterMode::Band);
+    /// filter.set_res([1.; 2].into());
+    /// assert!(filter.peak_gain().0 > 1.);
+    ///
+    /// filter.set_compensated(true);
+    /// assert_eq!(filter.peak_gain(), (1., 1.));
+    /// ```
+    pub fn set_compensated(&mut self, compensated: bool) {
+        if compensated != self.compensated {
+            self.compensated = compensated;
+            self.update_compensation();
+        }
+    }
+
+    /// The filter mode of the left and right channel
+    pub fn mode(&self) -> [SVFFilterMode; 2] {
+        self.mode
+    }
+
+    /// Recalculate all the held values.
+    /// This is the same math as [SimperSinSVF::reinit()], just for both lanes at once.
+    fn reinit(&mut self) {
+        let w = X2::<T>::from_f32(
+            self.cutoff
+                .map(|cutoff| clamp_cutoff(cutoff, self.sample_rate)),
+        ) * (T::PI / T::from_f32(self.sample_rate));
+        let two = T::from_f32(2.);
+
+        self.k = X2::splat(two) - X2::from_f32(self.res) * T::from_f32(1.45);
+
+        let s1 = w.map(T::sin);
+        let s2 = s1 * w.map(T::cos) * two;
+
+        let nrm = (X2::splat(two) + self.k * s2).map(|x| T::ONE / x);
+
+        self.g0 = s2 * nrm;
+        self.g1 = (-(s1 * s1) * two - self.k * s2) * nrm;
+        self.g2 = (s1 * s1) * two * nrm;
+        self.update_compensation();
+    }
+
+    /// The resonance, the modes and the compensation switch decide how far the output is turned down
+    fn update_compensation(&mut self) {
+        self.compensation = if self.compensated {
+            let gain = |mode, k: T| T::from_f32(1. / mode_peak_gain(mode, k.to_f32()).max(1.));
+            X2::new(
+                gain(self.mode[0], self.k.l()),
+                gain(self.mode[1], self.k.r()),
+            )
+        } else {
+            X2::splat(T::ONE)
+        };
+    }
+
+    /// Run the filter on a stereo sample.
+    ///
+    /// This returns the values as (low, band, high), see [SimperSinSVF::tick_sample_full()].
+    pub fn tick_sample_full(&mut self, sample: X2<T>) -> (X2<T>, X2<T>, X2<T>) {
+        let sample = sample.map(sanitize);
+        let two = T::from_f32(2.);
+
+        let t0 = sample - self.ic2eq;
+        let t1 = self.g0 * t0 + self.g1 * self.ic1eq;
+        let t2 = self.g2 * t0 + self.g0 * self.ic1eq;
+        let v1 = t1 + self.ic1eq;
+        let v2 = t2 + self.ic2eq;
+
+        self.ic1eq += t1 * two;
+        self.ic2eq += t2 * two;
+
+        let (ic1eq_l, ic2eq_l) = scrub_state(self.ic1eq.l(), self.ic2eq.l());
+        let (ic1eq_r, ic2eq_r) = scrub_state(self.ic1eq.r(), self.ic2eq.r());
+        self.ic1eq = X2::new(ic1eq_l, ic1eq_r);
+        self.ic2eq = X2::new(ic2eq_l, ic2eq_r);
+
+        let high = sample - self.k * v1 - v2;
+        let band = v1;
+        let low = v2;
+        (low, band, high)
+    }
+
+    /// Run the filter using the modes that are set internally for each channel
+    pub fn tick_sample(&mut self, sample: X2<T>) -> X2<T> {
+        let (low, band, high) = self.tick_sample_full(sample);
+
+        X2::new(
+            select_mode(self.mode[0], low.l(), band.l(), high.l()),
+            select_mode(self.mode[1], low.r(), band.r(), high.r()),
+        ) * self.compensation
+    }
+
+    /// Bend the integrators towards their soft clipped value by `amount`, for a [super::driven::DrivenSVF]
+    pub(crate) fn saturate_state(&mut self, amount: T) {
+        self.ic1eq += (self.ic1eq.map(T::tanh) - self.ic1eq) * amount;
+        self.ic2eq += (self.ic2eq.map(T::tanh) - self.ic2eq) * amount;
+    }
+}
+
+impl<T: Sample> StereoFilter for StereoSimperSinSVF<T> {
+    fn process_stereo(&mut self, input_l: f32, input_r: f32) -> (f32, f32) {
+        self.tick_sample(X2::from_f32(F32x2::new(input_l, input_r)))
+            .to_f32()
+            .into()
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.set_sample_rate(sample_rate)
+    }
+
+    fn peak_gain(&self) -> (f32, f32) {
+        (
+            mode_peak_gain(self.mode[0], self.k.l().to_f32()) * self.compensation.l().to_f32(),
+            mode_peak_gain(self.mode[1], self.k.r().to_f32()) * self.compensation.r().to_f32(),
+        )
+    }
+}
+
+/// The highest cutoff of a SVF relative to the sample rate. Right at Nyquist the coefficients degenerate.
+const MAX_CUTOFF_RATIO: f32 = 0.49;
+
+/// Keep a cutoff in the range the SVF coefficients are well behaved in.
+///
+/// Very low cutoffs make the coefficients vanish and cutoffs at or above Nyquist fold back or blow up.
+/// A NaN cutoff ends up at the lowest cutoff.
+pub(crate) fn clamp_cutoff(cutoff: f32, sample_rate: f32) -> f32 {
+    cutoff.max(MIN_CUTOFF).min(MAX_CUTOFF_RATIO * sample_rate)
+}
+
+/// Combine the outputs of a SVF into the output of a specific filter mode
+fn select_mode<T: Sample>(mode: SVFFilterMode, low: T, band: T, high: T) -> T {
+    match mode {
+        SVFFilterMode::Low => low,
+        SVFFilterMode::Band => band,
+        SVFFilterMode::High => high,
+        SVFFilterMode::Notch => low + high,
+        SVFFilterMode::Peak => low - high,
+    }
+}
+
+/// The gain of a SVF mode at its loudest frequency, for a damping of `k`, which is 1 / Q.
+///
+/// This is the gain of the analog prototype, the digital filters follow it closely below a quarter of the sample rate.
+/// The resonant low and high passes peak a little off the cutoff, the band pass and the peak mode right at it.
+///
+/// Usage:
+/// ```
+/// use delax_dsp::filters::{simper::mode_peak_gain, SVFFilterMode};
+///
+/// // Without resonance the band pass loses half the level, at a Q of 4 it's 4 times as loud
+/// assert_eq!(mode_peak_gain(SVFFilterMode::Band, 2.), 0.5);
+/// assert_eq!(mode_peak_gain(SVFFilterMode::Band, 0.25), 4.);
+/// assert_eq!(mode_peak_gain(SVFFilterMode::Low, 2.), 1.);
+/// ```
+pub fn mode_peak_gain(mode: SVFFilterMode, k: f32) -> f32 {
+    let q = 1. / k.max(f32::EPSILON);
+    match mode {
+        // Below a Q of 1/sqrt(2) there's no resonant peak, the pass band is the loudest part
+        SVFFilterMode::Low | SVFFilterMode::High if q * q > 0.5 => {
+            q / Sample::powf(1. - 1. / (4. * q * q), 0.5)
+        }
+        SVFFilterMode::Low | SVFFilterMode::High | SVFFilterMode::Notch => 1.,
+        SVFFilterMode::Band => q,
+        // The low and the high pass are in opposite phase at the cutoff, so their difference is twice as loud
+        SVFFilterMode::Peak => (2. * q).max(1.),
+    }
+}
+
+/// Clean up the integrator states of a SVF after a tick.
+///
+/// If one of the states blew up, both are reset, since the filter would otherwise output NaN forever.
+/// Otherwise the states are flushed to zero once they decay into the denormal range.
+fn scrub_state<T: Sample>(ic1eq: T, ic2eq: T) -> (T, T) {
+    if !ic1eq.is_finite() || !ic2eq.is_finite() {
+        return (T::ZERO, T::ZERO);
+    }
+
+    (flush_denormal(ic1eq), flush_denormal(ic2eq))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filters::MAX_CUTOFF;
+
+    /// The amplitude of a filter's response to a sine, after it settled
+    fn sine_amplitude(filter: &mut SimperSinSVF, frequency: f32, sample_rate: f32) -> f32 {
+        let sine =
+            |i: usize| Sample::sin(core::f32::consts::TAU * frequency * i as f32 / sample_rate);
+        (0..sample_rate as usize).for_each(|i| {
+            filter.tick_sample(sine(i));
+        });
+        (0..sample_rate as usize / 10)
+            .map(|i| filter.tick_sample(sine(i)).abs())
+            .fold(0., f32::max)
+    }
+
+    #[test]
+    fn peak_gain_matches_the_response() {
+        for (mode, res) in [
+            (SVFFilterMode::Band, 0.),
+            (SVFFilterMode::Band, 1.),
+            (SVFFilterMode::Peak, 0.8),
+        ] {
+            let mut filter = SimperSinSVF::new(48000.);
+            filter.set_cutoff_res(1000., res);
+            filter.set_mode(mode);
+            let measured = sine_amplitude(&mut filter, 1000., 48000.);
+            let estimated = filter.peak_gain();
+            assert!(
+                (measured / estimated - 1.).abs() < 0.05,
+                "{mode:?} at {res}: {measured} vs {estimated}"
+            );
+        }
+
+        // The resonant low pass peaks just below the cutoff, close to the estimate
+        let low_pass = || {
+            let mut filter = SimperSinSVF::new(48000.);
+            filter.set_cutoff_res(1000., 1.);
+            filter
+        };
+        let peak = (900..1000)
+            .step_by(10)
+            .map(|frequency| sine_amplitude(&mut low_pass(), frequency as f32, 48000.))
+            .fold(0., f32::max);
+        assert!((peak / low_pass().peak_gain() - 1.).abs() < 0.05, "{peak}");
+    }
+
+    #[test]
+    fn nan_input_recovers() {
+        let mut filter = SimperSinSVF::new(44100.);
+
+        filter.tick_sample(0.5);
+        assert!(filter.tick_sample(f32::NAN).is_finite());
+        assert!(filter.tick_sample(f32::INFINITY).is_finite());
+        assert!(filter.tick_sample(0.5).is_finite());
+
+        let mut filter = SimperTanSVF::new(44100.);
+
+        filter.tick_sample(0.5);
+        assert!(filter.tick_sample(f32::NAN).is_finite());
+        assert!(filter.tick_sample(f32::NEG_INFINITY).is_finite());
+        assert!(filter.tick_sample(0.5).is_finite());
+    }
+
+    #[test]
+    fn decaying_state_flushes_to_zero() {
+        let mut filter = SimperSinSVF::new(44100.);
+        filter.set_mode(SVFFilterMode::Low);
+
+        filter.tick_sample(1.);
+        let mut out = 1.;
+        for _ in 0..44100 * 5 {
+            out = filter.tick_sample(0.);
+            assert!(out == 0. || out.is_normal());
+        }
+
+        assert_eq!(out, 0.);
+        assert_eq!(filter.ic1eq, 0.);
+        assert_eq!(filter.ic2eq, 0.);
+    }
+
+    #[test]
+    fn stereo_matches_mono() {
+        let mut filter_l = SimperSinSVF::new(44100.);
+        let mut filter_r = SimperSinSVF::new(44100.);
+        let mut stereo = StereoSimperSinSVF::new(44100.);
+
+        filter_l.set_cutoff(300.);
+        filter_r.set_cutoff(2500.);
+        filter_l.set_res(0.7);
+        filter_r.set_res(0.1);
+        filter_l.set_mode(SVFFilterMode::Band);
+        filter_r.set_mode(SVFFilterMode::Notch);
+
+        stereo.set_cutoff(F32x2::new(300., 2500.));
+        stereo.set_res(F32x2::new(0.7, 0.1));
+        stereo.set_mode(SVFFilterMode::Band, SVFFilterMode::Notch);
+
+        for i in 0..1000 {
+            let input = (i as f32 * 0.05).sin();
+            let out = stereo.tick_sample(F32x2::new(input, -input));
+
+            assert!((out.l() - filter_l.tick_sample(input)).abs() < 1e-5);
+            assert!((out.r() - filter_r.tick_sample(-input)).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn compensation_keeps_the_resonant_peak_at_the_input_level() {
+        let sample_rate = 44100.;
+        let amplitude = |compensated: bool, res: f32| {
+            let mut filter = StereoSimperSinSVF::new(sample_rate);
+            filter.set_cutoff(F32x2::splat(1000.));
+            filter.set_res(F32x2::splat(res));
+            filter.set_mode(SVFFilterMode::Band, SVFFilterMode::Peak);
+            filter.set_compensated(compensated);
+            let sine =
+                |i: usize| Sample::sin(core::f32::consts::TAU * 1000. * i as f32 / sample_rate);
+            (0..sample_rate as usize).for_each(|i| {
+                filter.tick_sample(F32x2::splat(sine(i)));
+            });
+            (0..sample_rate as usize / 10)
+                .map(|i| filter.tick_sample(F32x2::splat(sine(i))))
+                .fold(F32x2::splat(0.), |peak, out| {
+                    F32x2::new(peak.l().max(out.l().abs()), peak.r().max(out.r().abs()))
+                })
+        };
+
+        let loud = amplitude(false, 1.);
+        assert!(loud.l() > 1.5 && loud.r() > 1.5);
+        let compensated = amplitude(true, 1.);
+        assert!((compensated.l() - 1.).abs() < 0.05, "{compensated:?}");
+        assert!((compensated.r() - 1.).abs() < 0.05, "{compensated:?}");
+
+        // A band pass without resonance is quieter than the input, it isn't turned up
+        assert_eq!(amplitude(true, 0.), amplitude(false, 0.));
+    }
+
+    #[test]
+    fn cutoff_sweep_stays_finite() {
+        // The low sample rate puts the top of the cutoff range far above Nyquist
+        for sample_rate in [8000., 44100., 192000.] {
+            for res in [0., 0.5, 1.] {
+                let mut filter = SimperSinSVF::new(sample_rate);
+                let mut stereo = StereoSimperSinSVF::new(sample_rate);
+                filter.set_res(res);
+                stereo.set_res(F32x2::splat(res));
+
+                for mode in [SVFFilterMode::Low, SVFFilterMode::High, SVFFilterMode::Peak] {
+                    filter.set_mode(mode);
+                    stereo.set_mode(mode, mode);
+
+                    // Sweep from below the range to above it and back down
+                    let steps = 2000;
+                    for i in 0..steps * 2 {
+                        let position =
+                            if i < steps { i } else { steps * 2 - i } as f32 / steps as f32;
+                        let cutoff = position * (MAX_CUTOFF + 1000.);
+                        filter.set_cutoff(cutoff);
+                        stereo.set_cutoff(F32x2::new(cutoff, MAX_CUTOFF - cutoff));
+
+                        let input = if i % 100 == 0 { 1. } else { 0. };
+                        assert!(filter.tick_sample(input).is_finite());
+                        let out = stereo.tick_sample(F32x2::splat(input));
+                        assert!(out.l().is_finite() && out.r().is_finite());
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn degenerate_cutoffs_are_clamped() {
+        assert_eq!(clamp_cutoff(0., 44100.), MIN_CUTOFF);
+        assert_eq!(clamp_cutoff(f32::NAN, 44100.), MIN_CUTOFF);
+        assert_eq!(clamp_cutoff(30000., 44100.), 0.49 * 44100.);
+        assert_eq!(clamp_cutoff(1000., 44100.), 1000.);
+    }
+
+    #[test]
+    fn tan_matches_sin() {
+        for mode in [
+            SVFFilterMode::Low,
+            SVFFilterMode::Band,
+            SVFFilterMode::High,
+            SVFFilterMode::Notch,
+            SVFFilterMode::Peak,
+        ] {
+            for (cutoff, res) in [(100., 0.), (1000., 0.5), (8000., 1.)] {
+                let mut tan = SimperTanSVF::new(44100.);
+                let mut sin = SimperSinSVF::new(44100.);
+                tan.set_cutoff_res(cutoff, res);
+                sin.set_cutoff_res(cutoff, res);
+                tan.set_mode(mode);
+                sin.set_mode(mode);
+
+                // Both are the same linear trapezoidal filter, so their impulse responses match
+                for i in 0..4410 {
+                    let input = if i == 0 { 1. } else { 0. };
+                    let (out_tan, out_sin) = (tan.process(input), sin.process(input));
+                    assert!(
+                        (out_tan - out_sin).abs() < 1e-4,
+                        "{mode:?} at {cutoff} Hz, res {res}, sample {i}: {out_tan} != {out_sin}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn double_precision_matches_single_precision() {
+        let mut single = StereoSimperSinSVF::new(44100.);
+        let mut double = StereoSimperSinSVF::<f64>::with_precision(44100.);
+        single.set_cutoff_res(F32x2::new(40., 2500.), F32x2::splat(0.9));
+        double.set_cutoff_res(F32x2::new(40., 2500.), F32x2::splat(0.9));
+        single.set_mode(SVFFilterMode::Low, SVFFilterMode::Band);
+        double.set_mode(SVFFilterMode::Low, SVFFilterMode::Band);
+
+        for i in 0..44100 {
+            let input = if i == 0 { 1. } else { 0. };
+            let (single_l, single_r) = single.process_stereo(input, input);
+            let (double_l, double_r) = double.process_stereo(input, input);
+            assert!((single_l - double_l).abs() < 1e-4, "sample {i}");
+            assert!((single_r - double_r).abs() < 1e-4, "sample {i}");
+        }
+    }
+
+    #[test]
+    fn combined_setter_matches_separate_setters() {
+        let mut separate = StereoSimperSinSVF::new(44100.);
+        let mut combined = StereoSimperSinSVF::new(44100.);
+
+        separate.set_cutoff(F32x2::new(300., 2500.));
+        separate.set_res(F32x2::new(0.7, 0.1));
+        combined.set_cutoff_res(F32x2::new(300., 2500.), F32x2::new(0.7, 0.1));
+
+        assert_eq!(separate.g0, combined.g0);
+        assert_eq!(separate.g1, combined.g1);
+        assert_eq!(separate.g2, combined.g2);
+        assert_eq!(separate.k, combined.k);
+    }
+}
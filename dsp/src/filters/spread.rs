@@ -0,0 +1,131 @@
+use super::{dattorro::InputDiffusor, StereoFilter};
+
+/// The lengths of the allpasses of the left chain in ms. They're kept short, so the repeats don't smear.
+const LEFT_MS: [f32; 3] = [0.71, 1.93, 3.07];
+/// The lengths of the right chain in ms. The last one is stretched to the length of the left chain, so both chains
+/// delay by the same amount and the channels stay in time with each other.
+const RIGHT_MS: [f32; 2] = [1.13, 1.61];
+/// The gain of the allpasses at full spread
+const MAX_GAIN: f32 = 0.6;
+
+/// Decorrelates the left and the right channel by running them through different short allpass chains.
+///
+/// Allpasses leave the level of every frequency alone and only shift its phase. The chains shift differently,
+/// so a mono signal comes out wider, without a delay between the channels. Both chains add the same short delay,
+/// which doesn't change with the spread.
+///
+/// Usage:
+/// ```
+/// use delax_dsp::filters::{spread::StereoSpread, StereoFilter};
+///
+/// let mut spread = StereoSpread::new(44100.);
+/// spread.set_spread(1.);
+/// let impulse = |i| if i == 0 { 1. } else { 0. };
+/// let outputs: Vec<(f32, f32)> = (0..1000)
+///     .map(|i| spread.process_stereo(impulse(i), impulse(i)))
+///     .collect();
+/// // The same impulse comes out differently on both sides
+/// assert!(outputs.iter().any(|(l, r)| l != r));
+/// ```
+#[derive(Clone)]
+pub struct StereoSpread {
+    left: [InputDiffusor<f32>; 3],
+    right: [InputDiffusor<f32>; 3],
+    spread: f32,
+}
+
+impl StereoSpread {
+    pub fn new(sample_rate: f32) -> Self {
+        let samples = |ms: f32| ((ms * sample_rate / 1000.) as usize).max(1);
+        let left = LEFT_MS.map(samples);
+        let [first, second] = RIGHT_MS.map(samples);
+        let last = (left.iter().sum::<usize>())
+            .saturating_sub(first + second)
+            .max(1);
+
+        Self {
+            left: left.map(|delay| InputDiffusor::new(delay, 0.)),
+            right: [first, second, last].map(|delay| InputDiffusor::new(delay, 0.)),
+            spread: 0.,
+        }
+    }
+
+    /// How far the channels are pulled apart from 0 to 1. At 0 both chains only delay.
+    pub fn set_spread(&mut self, spread: f32) {
+        let spread = spread.clamp(0., 1.);
+        if spread != self.spread {
+            self.spread = spread;
+            // The left chain shifts the phase one way and the right chain the other way
+            for diffusor in &mut self.left {
+                diffusor.set_gain(spread * MAX_GAIN);
+            }
+            for diffusor in &mut self.right {
+                diffusor.set_gain(-spread * MAX_GAIN);
+            }
+        }
+    }
+
+    pub fn spread(&self) -> f32 {
+        self.spread
+    }
+}
+
+impl StereoFilter for StereoSpread {
+    fn process_stereo(&mut self, input_l: f32, input_r: f32) -> (f32, f32) {
+        let l = self
+            .left
+            .iter_mut()
+            .fold(input_l, |sample, diffusor| diffusor.process(sample));
+        let r = self
+            .right
+            .iter_mut()
+            .fold(input_r, |sample, diffusor| diffusor.process(sample));
+        (l, r)
+    }
+
+    /// Rebuilds the chains, since their lengths depend on the sample rate. This clears them.
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        let spread = self.spread;
+        *self = Self::new(sample_rate);
+        self.set_spread(spread);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The output of both channels for an impulse on both
+    fn impulse_response(spread: f32) -> ([f32; 1024], [f32; 1024]) {
+        let mut stereo_spread = StereoSpread::new(44100.);
+        stereo_spread.set_spread(spread);
+        let mut responses = ([0.; 1024], [0.; 1024]);
+        for i in 0..1024 {
+            let input = if i == 0 { 1. } else { 0. };
+            (responses.0[i], responses.1[i]) = stereo_spread.process_stereo(input, input);
+        }
+        responses
+    }
+
+    #[test]
+    fn no_spread_only_delays() {
+        let (l, r) = impulse_response(0.);
+        assert_eq!(l, r);
+        let delay = l.iter().position(|sample| *sample != 0.).unwrap();
+        // A few ms at most
+        assert!(delay < 44100 * 6 / 1000, "{delay}");
+        assert_eq!(l[delay], 1.);
+    }
+
+    #[test]
+    fn spread_decorrelates_without_changing_the_level() {
+        let (l, r) = impulse_response(1.);
+        let energy = |response: &[f32]| response.iter().map(|sample| sample * sample).sum::<f32>();
+        // Allpasses keep the energy of the impulse, the short tail has nearly died down
+        assert!((energy(&l) - 1.).abs() < 0.01, "{}", energy(&l));
+        assert!((energy(&r) - 1.).abs() < 0.01, "{}", energy(&r));
+
+        let correlation: f32 = l.iter().zip(&r).map(|(l, r)| l * r).sum();
+        assert!(correlation.abs() < 0.5, "{correlation}");
+    }
+}
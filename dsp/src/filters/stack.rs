@@ -0,0 +1,330 @@
+use super::{Filter, StereoFilter};
+
+/// How long a discrete change like a new filter mode is crossfaded, in ms
+pub const CROSSFADE_MS: f32 = 5.;
+
+/// How steeply a [SVFStack] falls off past its cutoff
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Slope {
+    /// A single SVF, 12 dB per octave
+    #[default]
+    Db12,
+    /// Two SVFs in a row, 24 dB per octave
+    Db24,
+}
+
+impl Slope {
+    /// The amount of stages that run for this slope
+    pub fn stages(self) -> usize {
+        match self {
+            Slope::Db12 => 1,
+            Slope::Db24 => 2,
+        }
+    }
+}
+
+/// The stages from before a discrete change, which keep running until they are faded out
+#[derive(Debug, Clone)]
+struct Crossfade<F> {
+    stages: [F; 2],
+    slope: Slope,
+    /// How much of the old stages is still heard, it falls from 1 to 0
+    position: f32,
+}
+
+/// Two filters with the same settings in a row, of which the second one only runs for the steeper [Slope].
+///
+/// The settings go to both stages through [SVFStack::stages_mut()], so a stack is set up like a single filter.
+/// The resonance adds up too, two resonant stages ring twice as loud.
+///
+/// Settings that jump, like the filter mode or the slope, would click at a high resonance. They go through
+/// [SVFStack::crossfade()] instead, which blends from the old settings into the new ones over [CROSSFADE_MS].
+///
+/// Usage:
+/// ```
+/// use delax_dsp::filters::{
+///     simper::SimperSinSVF,
+///     stack::{SVFStack, Slope},
+///     Filter, SVFFilterMode,
+/// };
+///
+/// let mut stack = SVFStack::new(SimperSinSVF::new(44100.), 44100.);
+/// for stage in stack.stages_mut() {
+///     stage.set_cutoff(500.);
+/// }
+/// stack.set_slope(Slope::Db24);
+/// let out = stack.process(0.5);
+/// // The low pass fades into the high pass instead of jumping
+/// stack.crossfade(|stage| stage.set_mode(SVFFilterMode::High));
+/// assert!(stack.is_crossfading());
+/// ```
+#[derive(Debug, Clone)]
+pub struct SVFStack<F> {
+    stages: [F; 2],
+    slope: Slope,
+    fading: Option<Crossfade<F>>,
+    /// How far a crossfade moves per sample
+    fade_step: f32,
+    /// Nothing can click before the first sample, so the stack takes its first settings right away
+    started: bool,
+}
+
+impl<F: Clone> SVFStack<F> {
+    /// Stack a filter with a copy of itself, starting out at 12 dB
+    pub fn new(filter: F, sample_rate: f32) -> Self {
+        Self {
+            stages: [filter.clone(), filter],
+            slope: Slope::Db12,
+            fading: None,
+            fade_step: Self::fade_step(sample_rate),
+            started: false,
+        }
+    }
+
+    /// Switch the slope, crossfaded from the old one.
+    /// The second stage sat idle while it wasn't used, so it starts out as a copy of the first one instead of with
+    /// whatever it held back then.
+    pub fn set_slope(&mut self, slope: Slope) {
+        if slope != self.slope {
+            self.start_crossfade();
+            if slope.stages() > self.slope.stages() {
+                let [first, second] = &mut self.stages;
+                second.clone_from(first);
+            }
+            self.slope = slope;
+        }
+    }
+
+    /// Make a change to both stages that would click if it happened at once, like switching the filter mode.
+    ///
+    /// A copy of the stages keeps running with the old settings and is faded out over [CROSSFADE_MS]. Another change
+    /// during the crossfade starts a new one from where the stages are now. Before the first sample, changes are
+    /// taken right away.
+    pub fn crossfade(&mut self, mut change: impl FnMut(&mut F)) {
+        self.start_crossfade();
+        self.stages.iter_mut().for_each(&mut change);
+    }
+
+    fn start_crossfade(&mut self) {
+        if !self.started {
+            return;
+        }
+        self.fading = Some(Crossfade {
+            stages: self.stages.clone(),
+            slope: self.slope,
+            position: 1.,
+        });
+    }
+}
+
+impl<F> SVFStack<F> {
+    pub fn slope(&self) -> Slope {
+        self.slope
+    }
+
+    /// Both stages, to change their settings. They should always be set the same.
+    ///
+    /// The changes are heard right away, which is fine for the smoothed settings like the cutoff.
+    pub fn stages_mut(&mut self) -> &mut [F; 2] {
+        &mut self.stages
+    }
+
+    /// The first stage, which always runs and has the same settings as the second one
+    pub fn first(&self) -> &F {
+        &self.stages[0]
+    }
+
+    /// Whether the stack is still fading out old settings
+    pub fn is_crossfading(&self) -> bool {
+        self.fading.is_some()
+    }
+
+    fn fade_step(sample_rate: f32) -> f32 {
+        1000. / (CROSSFADE_MS * sample_rate)
+    }
+
+    /// Blend the output of the old stages into the output of the new ones and move the crossfade along
+    fn blend<S>(
+        &mut self,
+        new: S,
+        old: impl FnOnce(&mut Crossfade<F>) -> S,
+        mix: impl Fn(S, S, f32) -> S,
+    ) -> S {
+        self.started = true;
+        let Some(fading) = &mut self.fading else {
+            return new;
+        };
+        let position = fading.position;
+        let blended = mix(new, old(fading), position);
+        fading.position -= self.fade_step;
+        if fading.position <= 0. {
+            self.fading = None;
+        }
+        blended
+    }
+}
+
+impl<F: Filter> Filter for SVFStack<F> {
+    fn process(&mut self, input: f32) -> f32 {
+        let run = |stages: &mut [F], input| {
+            stages
+                .iter_mut()
+                .fold(input, |sample, stage| stage.process(sample))
+        };
+        let new = run(&mut self.stages[..self.slope.stages()], input);
+        self.blend(
+            new,
+            |fading| run(&mut fading.stages[..fading.slope.stages()], input),
+            |new, old, position| new + (old - new) * position,
+        )
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.fade_step = Self::fade_step(sample_rate);
+        self.fading = None;
+        for stage in &mut self.stages {
+            stage.set_sample_rate(sample_rate);
+        }
+    }
+
+    fn peak_gain(&self) -> f32 {
+        self.stages[..self.slope.stages()]
+            .iter()
+            .map(Filter::peak_gain)
+            .product()
+    }
+}
+
+impl<F: StereoFilter> StereoFilter for SVFStack<F> {
+    fn process_stereo(&mut self, input_l: f32, input_r: f32) -> (f32, f32) {
+        let run = |stages: &mut [F]| {
+            stages.iter_mut().fold((input_l, input_r), |(l, r), stage| {
+                stage.process_stereo(l, r)
+            })
+        };
+        let new = run(&mut self.stages[..self.slope.stages()]);
+        self.blend(
+            new,
+            |fading| run(&mut fading.stages[..fading.slope.stages()]),
+            |(new_l, new_r), (old_l, old_r), position| {
+                (
+                    new_l + (old_l - new_l) * position,
+                    new_r + (old_r - new_r) * position,
+                )
+            },
+        )
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.fade_step = Self::fade_step(sample_rate);
+        self.fading = None;
+        for stage in &mut self.stages {
+            stage.set_sample_rate(sample_rate);
+        }
+    }
+
+    fn peak_gain(&self) -> (f32, f32) {
+        self.stages[..self.slope.stages()]
+            .iter()
+            .map(StereoFilter::peak_gain)
+            .fold((1., 1.), |(l, r), (gain_l, gain_r)| {
+                (l * gain_l, r * gain_r)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        filters::{simper::SimperSinSVF, SVFFilterMode},
+        sample::Sample,
+    };
+
+    /// The amplitude of a stack's response to a sine, after it settled
+    fn amplitude(slope: Slope, frequency: f32) -> f32 {
+        let mut stack = SVFStack::new(SimperSinSVF::new(44100.), 44100.);
+        for stage in stack.stages_mut() {
+            stage.set_cutoff_res(1000., 0.);
+            stage.set_mode(SVFFilterMode::Low);
+        }
+        stack.set_slope(slope);
+        let sine = |i: usize| Sample::sin(core::f32::consts::TAU * frequency * i as f32 / 44100.);
+        (0..44100).for_each(|i| {
+            stack.process(sine(i));
+        });
+        (0..4410).fold(0f32, |peak, i| peak.max(stack.process(sine(i)).abs()))
+    }
+
+    #[test]
+    fn the_steeper_slope_falls_twice_as_fast() {
+        // An octave above the cutoff is already past the knee, from there on every octave counts
+        let db = |gain: f32| 20. * gain.log10();
+        let falloff = |slope| db(amplitude(slope, 4000.)) - db(amplitude(slope, 8000.));
+        assert!(
+            (falloff(Slope::Db12) - 12.).abs() < 2.,
+            "{}",
+            falloff(Slope::Db12)
+        );
+        assert!(
+            (falloff(Slope::Db24) - 24.).abs() < 4.,
+            "{}",
+            falloff(Slope::Db24)
+        );
+    }
+
+    #[test]
+    fn peaks_multiply() {
+        let mut stack = SVFStack::new(SimperSinSVF::new(44100.), 44100.);
+        for stage in stack.stages_mut() {
+            stage.set_cutoff_res(1000., 1.);
+            stage.set_mode(SVFFilterMode::Band);
+        }
+        let single = Filter::peak_gain(&stack);
+        stack.set_slope(Slope::Db24);
+        assert_eq!(Filter::peak_gain(&stack), single * single);
+    }
+
+    /// The biggest jump between two samples around a switch from a resonant low pass to a high pass
+    fn largest_step_around_a_mode_switch(crossfaded: bool) -> f32 {
+        let mut stack = SVFStack::new(SimperSinSVF::new(44100.), 44100.);
+        for stage in stack.stages_mut() {
+            stage.set_cutoff_res(200., 0.9);
+            stage.set_mode(SVFFilterMode::Low);
+        }
+        let sine = |i: usize| Sample::sin(core::f32::consts::TAU * 200. * i as f32 / 44100.);
+        let mut last = (0..4410).fold(0., |_, i| stack.process(sine(i)));
+        if crossfaded {
+            stack.crossfade(|stage| stage.set_mode(SVFFilterMode::High));
+        } else {
+            for stage in stack.stages_mut() {
+                stage.set_mode(SVFFilterMode::High);
+            }
+        }
+        (4410..4410 + 441).fold(0f32, |step, i| {
+            let out = stack.process(sine(i));
+            let step = step.max((out - last).abs());
+            last = out;
+            step
+        })
+    }
+
+    #[test]
+    fn mode_switches_are_crossfaded() {
+        let jump = largest_step_around_a_mode_switch(false);
+        let crossfaded = largest_step_around_a_mode_switch(true);
+        assert!(crossfaded * 4. < jump, "{crossfaded} vs {jump}");
+    }
+
+    #[test]
+    fn crossfades_end() {
+        let mut stack = SVFStack::new(SimperSinSVF::new(1000.), 1000.);
+        stack.process(0.);
+        stack.set_slope(Slope::Db24);
+        assert!(stack.is_crossfading());
+        (0..(CROSSFADE_MS as usize + 1)).for_each(|_| {
+            stack.process(0.5);
+        });
+        assert!(!stack.is_crossfading());
+    }
+}
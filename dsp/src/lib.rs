@@ -0,0 +1,22 @@
+//! The DSP core of Delax: the delay engine, the filters, the multiband crossovers and the reverb.
+//!
+//! Nothing in here knows about nih-plug or parameters. The buffers are only allocated when a processor is created
+//! or resized, never while processing, so the crate builds for embedded and WASM targets without `std` by turning
+//! off the default features. The float math then comes from libm.
+//!
+//! The multiband delay and the reverb are behind the `multiband` and `reverb` features, which are on by default.
+//! The band count and the solo logic of the multiband delay are always there, so its parameters don't depend on the features.
+//! The loop region of the delay engine and the jump tables of its heads are behind the `glitch` feature, which is on by
+//! default as well. Without it the heads only wrap around the end of the buffer.
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+pub mod delay_engine;
+pub mod filters;
+pub mod lofi;
+pub mod multiband;
+pub mod random;
+pub mod sample;
+pub mod sanitize;
+pub mod simd;
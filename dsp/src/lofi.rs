@@ -0,0 +1,80 @@
+use crate::sample::Sample;
+
+/// Holds every sample for a while and rounds it to a few bits, for the grainy sound of early digital delays.
+///
+/// The held samples aren't filtered, so everything above half the reduced rate folds back down. That's the point.
+///
+/// Usage:
+/// ```
+/// use delax_dsp::lofi::Decimator;
+///
+/// // A quarter of the sample rate and 4 bits
+/// let mut decimator = Decimator::new(48000., 12000., 4);
+/// let held: Vec<f32> = (0..8).map(|sample| decimator.process(sample as f32 / 8.)).collect();
+/// // Every sample is held for four samples and lands on one of the 16 levels
+/// assert_eq!(held[0], held[3]);
+/// assert_ne!(held[3], held[4]);
+/// assert!(held.iter().all(|sample| (sample * 8.).fract() == 0.));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Decimator {
+    /// How far the current sample is held, a new one is taken at 1
+    phase: f32,
+    held: f32,
+    /// The reduced rate over the sample rate
+    step: f32,
+    rate: f32,
+    /// The amount of levels on either side of zero
+    levels: f32,
+}
+
+impl Decimator {
+    /// Reduce the signal to `rate` Hz and `bits` bits
+    pub fn new(sample_rate: f32, rate: f32, bits: u32) -> Self {
+        Self {
+            // The first sample is taken right away
+            phase: 1.,
+            held: 0.,
+            step: (rate / sample_rate).min(1.),
+            rate,
+            levels: (1u32 << (bits.clamp(1, 24) - 1)) as f32,
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.step = (self.rate / sample_rate).min(1.);
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        if self.phase >= 1. {
+            self.phase -= 1.;
+            self.held = Sample::floor(input * self.levels + 0.5) / self.levels;
+        }
+        self.phase += self.step;
+        self.held
+    }
+
+    pub fn reset(&mut self) {
+        self.phase = 1.;
+        self.held = 0.;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_rates_only_round() {
+        let mut decimator = Decimator::new(48000., 48000., 24);
+        for sample in [0.5, -0.25, 0.125] {
+            assert_eq!(decimator.process(sample), sample);
+        }
+
+        // A single bit leaves the sign and silence
+        let mut decimator = Decimator::new(48000., 48000., 1);
+        assert_eq!(decimator.process(0.7), 1.);
+        assert_eq!(decimator.process(-0.7), -1.);
+        assert_eq!(decimator.process(0.2), 0.);
+    }
+}
@@ -0,0 +1,414 @@
+#[cfg(feature = "multiband")]
+use alloc::vec::Vec;
+#[cfg(feature = "multiband")]
+use core::f32::consts::{PI, SQRT_2};
+
+#[cfg(feature = "multiband")]
+use crate::{
+    delay_engine::engine::{interpolation_for, ms_to_samples, DelayEngine},
+    filters::simper::clamp_cutoff,
+    sample::Sample,
+    sanitize::flush_denormal,
+};
+
+/// The most bands a [BandSplitter] splits a signal into
+pub const MAX_BANDS: usize = 3;
+/// The longest delay time of a band in ms
+pub const MAX_BAND_MS: f32 = 4000.;
+
+/// Which of the first `bands` bands are heard. Soloing a band silences every band that isn't soloed, a muted band
+/// stays silent even while it's soloed. The bands after `bands` are never heard.
+///
+/// Usage:
+/// ```
+/// use delax_dsp::multiband::audible_bands;
+///
+/// assert_eq!(audible_bands([false; 3], [false, true, false], 3), [true, false, true]);
+/// assert_eq!(audible_bands([false, true, false], [false; 3], 3), [false, true, false]);
+/// // A solo on a band that isn't used doesn't silence the others
+/// assert_eq!(audible_bands([false, false, true], [false; 3], 2), [true, true, false]);
+/// ```
+pub fn audible_bands(
+    solo: [bool; MAX_BANDS],
+    mute: [bool; MAX_BANDS],
+    bands: usize,
+) -> [bool; MAX_BANDS] {
+    let any_solo = solo.iter().take(bands).any(|solo| *solo);
+    core::array::from_fn(|band| band < bands && !mute[band] && (!any_solo || solo[band]))
+}
+
+/// A 2nd order Butterworth state variable filter. Two of them in a row make one side of a [Crossover].
+#[cfg(feature = "multiband")]
+#[derive(Debug, Clone, Default)]
+struct ButterworthSvf {
+    ic1eq: f32,
+    ic2eq: f32,
+    a1: f32,
+    a2: f32,
+    a3: f32,
+}
+
+#[cfg(feature = "multiband")]
+impl ButterworthSvf {
+    fn set_cutoff(&mut self, cutoff: f32, sample_rate: f32) {
+        let g = Sample::tan(PI * clamp_cutoff(cutoff, sample_rate) / sample_rate);
+        self.a1 = 1. / (1. + g * (g + SQRT_2));
+        self.a2 = g * self.a1;
+        self.a3 = g * self.a2;
+    }
+
+    /// Filter a sample and return the low, the band and the high pass output
+    fn tick(&mut self, input: f32) -> (f32, f32, f32) {
+        let v3 = input - self.ic2eq;
+        let v1 = self.a1 * self.ic1eq + self.a2 * v3;
+        let v2 = self.ic2eq + self.a2 * self.ic1eq + self.a3 * v3;
+        self.ic1eq = flush_denormal(2. * v1 - self.ic1eq);
+        self.ic2eq = flush_denormal(2. * v2 - self.ic2eq);
+
+        (v2, v1, input - SQRT_2 * v1 - v2)
+    }
+
+    /// The allpass that a [Crossover] at the same cutoff puts on the sum of its bands
+    fn allpass(&mut self, input: f32) -> f32 {
+        let (_, band, _) = self.tick(input);
+        input - 2. * SQRT_2 * band
+    }
+
+    fn reset(&mut self) {
+        self.ic1eq = 0.;
+        self.ic2eq = 0.;
+    }
+}
+
+/// A 4th order Linkwitz-Riley crossover, which splits a signal into a low and a high band.
+///
+/// Both bands fall by 6 dB at the cutoff and add back up to an allpass of the input, so summing them keeps the level flat.
+///
+/// Usage:
+/// ```
+/// use delax_dsp::multiband::Crossover;
+///
+/// let mut crossover = Crossover::new(48000.);
+/// crossover.set_cutoff(1000.);
+/// let (low, high) = crossover.process(0.5);
+/// ```
+#[cfg(feature = "multiband")]
+#[derive(Debug, Clone)]
+pub struct Crossover {
+    /// Splits the input, its low and high outputs are the first half of each band
+    split: ButterworthSvf,
+    low: ButterworthSvf,
+    high: ButterworthSvf,
+    cutoff: f32,
+    sample_rate: f32,
+}
+
+#[cfg(feature = "multiband")]
+impl Crossover {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut crossover = Self {
+            split: ButterworthSvf::default(),
+            low: ButterworthSvf::default(),
+            high: ButterworthSvf::default(),
+            cutoff: 1000.,
+            sample_rate,
+        };
+        crossover.reinit();
+        crossover
+    }
+
+    pub fn set_cutoff(&mut self, cutoff: f32) {
+        if cutoff != self.cutoff {
+            self.cutoff = cutoff;
+            self.reinit();
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.reinit();
+    }
+
+    /// Split a sample into the low and the high band
+    pub fn process(&mut self, input: f32) -> (f32, f32) {
+        let (low, _, high) = self.split.tick(input);
+        let (low, _, _) = self.low.tick(low);
+        let (_, _, high) = self.high.tick(high);
+        (low, high)
+    }
+
+    pub fn reset(&mut self) {
+        for svf in [&mut self.split, &mut self.low, &mut self.high] {
+            svf.reset();
+        }
+    }
+
+    fn reinit(&mut self) {
+        for svf in [&mut self.split, &mut self.low, &mut self.high] {
+            svf.set_cutoff(self.cutoff, self.sample_rate);
+        }
+    }
+}
+
+/// Splits a signal into two or three bands with [Crossover]s, from the lowest band to the highest one.
+///
+/// With three bands the low band goes through the allpass of the upper crossover, so all bands have the same phase
+/// and still add back up to an allpass of the input.
+///
+/// Usage:
+/// ```
+/// use delax_dsp::multiband::BandSplitter;
+///
+/// let mut splitter = BandSplitter::new(48000.);
+/// splitter.set_crossovers(300., 3000.);
+/// let [low, mid, high] = splitter.process(0.5, 3);
+///
+/// // With two bands only the lower crossover is used and the last band stays silent
+/// let [low, high, silent] = splitter.process(0.5, 2);
+/// assert_eq!(silent, 0.);
+/// ```
+#[cfg(feature = "multiband")]
+#[derive(Debug, Clone)]
+pub struct BandSplitter {
+    lower: Crossover,
+    upper: Crossover,
+    /// The allpass of the upper crossover for the low band
+    low_allpass: ButterworthSvf,
+}
+
+#[cfg(feature = "multiband")]
+impl BandSplitter {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut splitter = Self {
+            lower: Crossover::new(sample_rate),
+            upper: Crossover::new(sample_rate),
+            low_allpass: ButterworthSvf::default(),
+        };
+        splitter.set_crossovers(300., 3000.);
+        splitter
+    }
+
+    /// Set the cutoffs of the crossovers. The upper one is only used with three bands and kept above the lower one.
+    pub fn set_crossovers(&mut self, lower: f32, upper: f32) {
+        let upper = upper.max(lower);
+        self.lower.set_cutoff(lower);
+        if upper != self.upper.cutoff {
+            self.upper.set_cutoff(upper);
+            self.low_allpass.set_cutoff(upper, self.upper.sample_rate);
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.lower.set_sample_rate(sample_rate);
+        self.upper.set_sample_rate(sample_rate);
+        self.low_allpass.set_cutoff(self.upper.cutoff, sample_rate);
+    }
+
+    /// Split a sample into `bands` bands, two or three. The bands that aren't used are zero.
+    pub fn process(&mut self, input: f32, bands: usize) -> [f32; MAX_BANDS] {
+        let (low, high) = self.lower.process(input);
+        if bands < MAX_BANDS {
+            return [low, high, 0.];
+        }
+
+        let (mid, high) = self.upper.process(high);
+        [self.low_allpass.allpass(low), mid, high]
+    }
+
+    pub fn reset(&mut self) {
+        self.lower.reset();
+        self.upper.reset();
+        self.low_allpass.reset();
+    }
+}
+
+/// A delay line per band, so every band repeats at its own time and feedback.
+///
+/// The input is split by a [BandSplitter] and every band feeds back into its own delay line, the repeats of the
+/// bands are summed into the output at their levels. Short repeats on the highs and long ones on the lows sound like a dub delay.
+///
+/// The level of a band only changes what's heard, its feedback loop keeps running. So a muted band comes back with
+/// the repeats it had built up.
+///
+/// Usage:
+/// ```
+/// use delax_dsp::multiband::MultibandDelay;
+///
+/// let mut delay = MultibandDelay::new(1000.);
+/// delay.set_times([100., 50., 10.]);
+/// let output = delay.process(1., [0.5; 3], [1.; 3], 3);
+/// // Nothing is repeated yet
+/// assert_eq!(output, 0.);
+/// ```
+#[cfg(feature = "multiband")]
+pub struct MultibandDelay {
+    splitter: BandSplitter,
+    engines: Vec<DelayEngine>,
+}
+
+#[cfg(feature = "multiband")]
+impl MultibandDelay {
+    pub fn new(sample_rate: f32) -> Self {
+        let size = ms_to_samples(MAX_BAND_MS, sample_rate) + 1;
+        Self {
+            splitter: BandSplitter::new(sample_rate),
+            engines: (0..MAX_BANDS)
+                .map(|_| {
+                    let mut engine = DelayEngine::new(size, sample_rate);
+                    engine.set_delay_amount(0.);
+                    engine
+                })
+                .collect(),
+        }
+    }
+
+    /// The band splitter, to set its crossovers
+    pub fn splitter(&mut self) -> &mut BandSplitter {
+        &mut self.splitter
+    }
+
+    /// Set the delay time of every band in ms, up to [MAX_BAND_MS]
+    pub fn set_times(&mut self, times: [f32; MAX_BANDS]) {
+        for (engine, time) in self.engines.iter_mut().zip(times) {
+            engine.set_delay_amount(time.min(MAX_BAND_MS));
+        }
+    }
+
+    /// Run a sample through `bands` bands, two or three, and return the sum of their repeats at their `levels`
+    pub fn process(
+        &mut self,
+        input: f32,
+        feedbacks: [f32; MAX_BANDS],
+        levels: [f32; MAX_BANDS],
+        bands: usize,
+    ) -> f32 {
+        let split = self.splitter.process(input, bands);
+        let mut output = 0.;
+        for (((engine, band), feedback), level) in self
+            .engines
+            .iter_mut()
+            .zip(split)
+            .zip(feedbacks)
+            .zip(levels)
+            .take(bands)
+        {
+            let delayed = engine.interpolate_sample(interpolation_for(engine.delay_time()));
+            engine.write_sample(band + delayed * feedback);
+            output += delayed * level;
+        }
+        output
+    }
+
+    /// Empty the delay lines and the crossovers
+    pub fn reset(&mut self) {
+        self.splitter.reset();
+        self.engines.iter_mut().for_each(DelayEngine::reset);
+    }
+}
+
+#[cfg(all(test, feature = "multiband"))]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48000.;
+
+    /// The RMS of every band and of their sum for a sine, after the filters settled
+    fn band_levels(frequency: f32, bands: usize) -> ([f32; MAX_BANDS], f32) {
+        let mut splitter = BandSplitter::new(SAMPLE_RATE);
+        splitter.set_crossovers(300., 3000.);
+        let mut energy = [0.; MAX_BANDS];
+        let mut sum_energy = 0.;
+        let settle = 4800;
+        let len = 48000;
+        for i in 0..len {
+            let input = Sample::sin(2. * PI * frequency * i as f32 / SAMPLE_RATE);
+            let split = splitter.process(input, bands);
+            if i >= settle {
+                for (energy, band) in energy.iter_mut().zip(split) {
+                    *energy += band * band;
+                }
+                let sum: f32 = split.iter().sum();
+                sum_energy += sum * sum;
+            }
+        }
+        let count = (len - settle) as f32;
+        (
+            energy.map(|energy| (energy / count * 2.).sqrt()),
+            (sum_energy / count * 2.).sqrt(),
+        )
+    }
+
+    #[test]
+    fn the_bands_add_up_to_a_flat_response() {
+        for bands in [2, 3] {
+            for frequency in [50., 300., 1000., 3000., 10000.] {
+                let (_, sum) = band_levels(frequency, bands);
+                assert!(
+                    (sum - 1.).abs() < 0.01,
+                    "{bands} bands at {frequency} Hz: {sum}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn signals_land_in_their_band() {
+        let (low, _) = band_levels(50., 3);
+        assert!(low[0] > 0.99 && low[1] < 0.1 && low[2] < 0.01);
+        let (mid, _) = band_levels(1000., 3);
+        assert!(mid[1] > 0.9 && mid[0] < 0.1 && mid[2] < 0.1);
+        let (high, _) = band_levels(10000., 3);
+        assert!(high[2] > 0.99 && high[0] < 0.01 && high[1] < 0.1);
+
+        // Both crossover bands are 6 dB down at the cutoff
+        let (cutoff, _) = band_levels(300., 2);
+        assert!((cutoff[0] - 0.5).abs() < 0.01 && (cutoff[1] - 0.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn every_band_repeats_at_its_own_time() {
+        let mut delay = MultibandDelay::new(1000.);
+        delay.set_times([100., 50., 10.]);
+        let mut output = Vec::new();
+        for i in 0..150 {
+            let input = if i == 0 { 1. } else { 0. };
+            output.push(delay.process(input, [0.; MAX_BANDS], [1.; MAX_BANDS], 3));
+        }
+
+        // The highest band has the shortest time, so most of the click repeats early
+        let energy = |range: core::ops::Range<usize>| -> f32 {
+            output[range].iter().map(|sample| sample * sample).sum()
+        };
+        assert!(output[..10].iter().all(|sample| *sample == 0.));
+        assert!(energy(10..50) > 0.);
+        assert!(energy(50..100) > 0.);
+        assert!(energy(100..150) > 0.);
+    }
+
+    #[test]
+    fn muted_bands_keep_repeating() {
+        let mut delay = MultibandDelay::new(1000.);
+        delay.set_times([10.; MAX_BANDS]);
+        let mut muted = [1.; MAX_BANDS];
+        muted[0] = 0.;
+        let mut heard = 0.;
+        for i in 0..100 {
+            let input = if i == 0 { 1. } else { 0. };
+            // The low band is muted for the first repeats and comes back with its feedback still ringing
+            let levels = if i < 50 { muted } else { [1.; MAX_BANDS] };
+            let output = delay.process(input, [0.9; MAX_BANDS], levels, 2);
+            if i >= 50 {
+                heard += output.abs();
+            }
+        }
+        assert!(heard > 0.1);
+
+        // Silence while every band is muted
+        delay.reset();
+        let silent = (0..100).all(|i| {
+            let input = if i == 0 { 1. } else { 0. };
+            delay.process(input, [0.9; MAX_BANDS], [0.; MAX_BANDS], 3) == 0.
+        });
+        assert!(silent);
+    }
+}
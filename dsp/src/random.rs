@@ -0,0 +1,53 @@
+/// The seed of generators that aren't seeded explicitly
+const DEFAULT_SEED: u32 = 0x9E37_79B9;
+
+/// A small xorshift random number generator.
+///
+/// It's seedable, so renders and tests can be repeated exactly, and it never allocates or locks, so it's safe to use on the audio thread.
+///
+/// Usage:
+/// ```
+/// use delax_dsp::random::XorShift;
+///
+/// let mut a = XorShift::new(1);
+/// let mut b = XorShift::new(1);
+/// assert_eq!(a.next_bipolar(), b.next_bipolar());
+/// assert!((-1. ..=1.).contains(&a.next_bipolar()));
+/// ```
+#[derive(Debug, Clone)]
+pub struct XorShift {
+    /// The state of the generator, it's never zero
+    state: u32,
+}
+
+impl Default for XorShift {
+    fn default() -> Self {
+        Self::new(DEFAULT_SEED)
+    }
+}
+
+impl XorShift {
+    /// Create a generator with a seed. Xorshift gets stuck at zero, so a zero seed is replaced with the default one.
+    pub fn new(seed: u32) -> Self {
+        Self {
+            state: if seed == 0 { DEFAULT_SEED } else { seed },
+        }
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        self.state
+    }
+
+    /// A random value in the range 0 to 1
+    pub fn next_unipolar(&mut self) -> f32 {
+        self.next_u32() as f32 / u32::MAX as f32
+    }
+
+    /// A random value in the range -1 to 1
+    pub fn next_bipolar(&mut self) -> f32 {
+        self.next_unipolar() * 2. - 1.
+    }
+}
@@ -0,0 +1,150 @@
+use core::{
+    fmt::Debug,
+    ops::{Add, AddAssign, Div, Mul, MulAssign, Neg, Sub, SubAssign},
+};
+
+/// A floating point type the DSP can run at.
+///
+/// The audio going in and out of the plugin is always f32, but recursive structures like the filters can keep their
+/// state at a higher precision. Tiny rounding errors add up over a long tail with a lot of feedback.
+///
+/// The math runs on the standard library with the `std` feature and on libm without it.
+///
+/// Usage:
+/// ```
+/// use delax_dsp::sample::Sample;
+///
+/// fn half<T: Sample>(value: T) -> T {
+///     value * T::from_f32(0.5)
+/// }
+///
+/// assert_eq!(half(1f32), 0.5);
+/// assert_eq!(half(1f64), 0.5);
+/// ```
+pub trait Sample:
+    Copy
+    + Debug
+    + Default
+    + PartialEq
+    + PartialOrd
+    + Send
+    + Sync
+    + 'static
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+    + AddAssign
+    + SubAssign
+    + MulAssign
+{
+    const ZERO: Self;
+    const ONE: Self;
+    const PI: Self;
+
+    fn from_f32(value: f32) -> Self;
+    fn to_f32(self) -> f32;
+
+    fn abs(self) -> Self;
+    fn is_finite(self) -> bool;
+    fn floor(self) -> Self;
+    fn fract(self) -> Self;
+    fn sin(self) -> Self;
+    fn cos(self) -> Self;
+    fn tan(self) -> Self;
+    fn tanh(self) -> Self;
+    fn exp(self) -> Self;
+    fn powf(self, exponent: Self) -> Self;
+}
+
+/// Call a float function of the standard library, or its libm counterpart without it
+#[cfg(feature = "std")]
+macro_rules! float_fn {
+    ($t:ident, $name:ident, $($arg:expr),+) => {
+        $t::$name($($arg),+)
+    };
+}
+/// Call a float function of the standard library, or its libm counterpart without it
+#[cfg(not(feature = "std"))]
+macro_rules! float_fn {
+    ($t:ident, powf, $($arg:expr),+) => {
+        libm::Libm::<$t>::pow($($arg),+)
+    };
+    ($t:ident, $name:ident, $($arg:expr),+) => {
+        libm::Libm::<$t>::$name($($arg),+)
+    };
+}
+
+macro_rules! impl_sample {
+    ($t:ident) => {
+        impl Sample for $t {
+            const ZERO: Self = 0.;
+            const ONE: Self = 1.;
+            const PI: Self = core::$t::consts::PI;
+
+            #[inline]
+            fn from_f32(value: f32) -> Self {
+                value as $t
+            }
+
+            #[inline]
+            fn to_f32(self) -> f32 {
+                self as f32
+            }
+
+            #[inline]
+            fn abs(self) -> Self {
+                $t::abs(self)
+            }
+
+            #[inline]
+            fn is_finite(self) -> bool {
+                $t::is_finite(self)
+            }
+
+            #[inline]
+            fn floor(self) -> Self {
+                float_fn!($t, floor, self)
+            }
+
+            #[inline]
+            fn fract(self) -> Self {
+                self - float_fn!($t, trunc, self)
+            }
+
+            #[inline]
+            fn sin(self) -> Self {
+                float_fn!($t, sin, self)
+            }
+
+            #[inline]
+            fn cos(self) -> Self {
+                float_fn!($t, cos, self)
+            }
+
+            #[inline]
+            fn tan(self) -> Self {
+                float_fn!($t, tan, self)
+            }
+
+            #[inline]
+            fn tanh(self) -> Self {
+                float_fn!($t, tanh, self)
+            }
+
+            #[inline]
+            fn exp(self) -> Self {
+                float_fn!($t, exp, self)
+            }
+
+            #[inline]
+            fn powf(self, exponent: Self) -> Self {
+                float_fn!($t, powf, self, exponent)
+            }
+        }
+    };
+}
+
+impl_sample!(f32);
+impl_sample!(f64);
@@ -0,0 +1,77 @@
+use crate::sample::Sample;
+
+/// Everything below this magnitude is treated as silence.
+///
+/// This is around -300 dB, so it's far below anything audible, but still well above the subnormal range of f32.
+/// Long decaying feedback tails would otherwise slowly crawl into subnormal values, which are very slow on most CPUs.
+pub const DENORMAL_THRESHOLD: f32 = 1e-15;
+
+/// Flush a sample to zero if it's so quiet that it's about to become subnormal.
+///
+/// Usage:
+/// ```
+/// use delax_dsp::sanitize::flush_denormal;
+///
+/// assert_eq!(flush_denormal(1e-20), 0.);
+/// assert_eq!(flush_denormal(0.5), 0.5);
+/// ```
+#[inline]
+pub fn flush_denormal<T: Sample>(sample: T) -> T {
+    if sample.abs() < T::from_f32(DENORMAL_THRESHOLD) {
+        T::ZERO
+    } else {
+        sample
+    }
+}
+
+/// Make a sample safe to be fed back into a recursive structure.
+///
+/// NaN and infinite values are replaced with silence, since once they are inside of a feedback loop they never leave again.
+/// Tiny values are flushed to zero using [flush_denormal()].
+///
+/// Usage:
+/// ```
+/// use delax_dsp::sanitize::sanitize;
+///
+/// assert_eq!(sanitize(f32::NAN), 0.);
+/// assert_eq!(sanitize(f32::INFINITY), 0.);
+/// assert_eq!(sanitize(0.5), 0.5);
+/// ```
+#[inline]
+pub fn sanitize<T: Sample>(sample: T) -> T {
+    if sample.is_finite() {
+        flush_denormal(sample)
+    } else {
+        T::ZERO
+    }
+}
+
+/// The level the safety clipper never exceeds, +0.5 dB
+pub const SAFETY_CEILING: f32 = 1.0593;
+/// Everything up to this level passes the safety clipper untouched, 0 dB
+pub const SAFETY_KNEE: f32 = 1.;
+
+/// The safety clipper on the output. Samples up to 0 dB pass unchanged, louder ones are bent
+/// smoothly towards [SAFETY_CEILING], so runaway feedback can't blow up speakers or ears.
+///
+/// There is no lookahead, so this adds no latency. It's only meant as a safety net, it distorts hard when driven.
+///
+/// Usage:
+/// ```
+/// use delax_dsp::sanitize::{safety_clip, SAFETY_CEILING};
+///
+/// assert_eq!(safety_clip(0.5), 0.5);
+/// assert!(safety_clip(10.) <= SAFETY_CEILING);
+/// assert!(safety_clip(-10.) >= -SAFETY_CEILING);
+/// ```
+#[inline]
+pub fn safety_clip(sample: f32) -> f32 {
+    let level = sample.abs();
+    if level <= SAFETY_KNEE {
+        return sample;
+    }
+
+    let range = SAFETY_CEILING - SAFETY_KNEE;
+    let clipped = SAFETY_KNEE + range * Sample::tanh((level - SAFETY_KNEE) / range);
+    clipped.copysign(sample)
+}
@@ -0,0 +1,146 @@
+use core::ops::{Add, AddAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+
+use crate::sample::Sample;
+
+/// A stereo pair of samples packed into two lanes.
+///
+/// The left channel lives in lane 0 and the right channel in lane 1.
+/// All the operations are done lane-wise on a fixed size, aligned array,
+/// which the compiler lowers to single vector instructions on targets with SSE or NEON.
+///
+/// Usage:
+/// ```
+/// use delax_dsp::simd::F32x2;
+///
+/// let signal = F32x2::new(0.5, 0.25);
+/// let gain = F32x2::splat(2.);
+///
+/// assert_eq!(signal * gain, F32x2::new(1., 0.5));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[repr(C, align(8))]
+pub struct X2<T>(pub [T; 2]);
+
+/// The pair of samples the audio is processed in
+pub type F32x2 = X2<f32>;
+/// A pair of samples at double precision
+pub type F64x2 = X2<f64>;
+
+impl<T: Sample> X2<T> {
+    /// Pack a left and right sample into a vector
+    #[inline]
+    pub const fn new(l: T, r: T) -> Self {
+        Self([l, r])
+    }
+
+    /// Create a vector with the same value in both lanes
+    #[inline]
+    pub const fn splat(value: T) -> Self {
+        Self([value, value])
+    }
+
+    /// Convert a pair of f32 samples to the precision of this vector
+    #[inline]
+    pub fn from_f32(value: F32x2) -> Self {
+        value.map_to(T::from_f32)
+    }
+
+    /// Convert the vector back to a pair of f32 samples
+    #[inline]
+    pub fn to_f32(self) -> F32x2 {
+        self.map_to(T::to_f32)
+    }
+
+    /// The left lane
+    #[inline]
+    pub fn l(self) -> T {
+        self.0[0]
+    }
+
+    /// The right lane
+    #[inline]
+    pub fn r(self) -> T {
+        self.0[1]
+    }
+
+    /// Exchange the left and right lane
+    #[inline]
+    pub fn swap(self) -> Self {
+        Self([self.0[1], self.0[0]])
+    }
+
+    /// Apply a scalar function to both lanes
+    #[inline]
+    pub fn map(self, f: impl Fn(T) -> T) -> Self {
+        Self([f(self.0[0]), f(self.0[1])])
+    }
+
+    /// Apply a scalar function that changes the type to both lanes
+    #[inline]
+    fn map_to<U: Sample>(self, f: impl Fn(T) -> U) -> X2<U> {
+        X2([f(self.0[0]), f(self.0[1])])
+    }
+}
+
+impl<T: Sample> From<[T; 2]> for X2<T> {
+    #[inline]
+    fn from(lanes: [T; 2]) -> Self {
+        Self(lanes)
+    }
+}
+
+impl<T: Sample> From<(T, T)> for X2<T> {
+    #[inline]
+    fn from((l, r): (T, T)) -> Self {
+        Self::new(l, r)
+    }
+}
+
+impl<T: Sample> From<X2<T>> for (T, T) {
+    #[inline]
+    fn from(value: X2<T>) -> Self {
+        (value.0[0], value.0[1])
+    }
+}
+
+macro_rules! impl_lane_op {
+    ($op_trait:ident, $op_fn:ident, $assign_trait:ident, $assign_fn:ident, $op:tt) => {
+        impl<T: Sample> $op_trait for X2<T> {
+            type Output = Self;
+
+            #[inline]
+            fn $op_fn(self, rhs: Self) -> Self {
+                Self([self.0[0] $op rhs.0[0], self.0[1] $op rhs.0[1]])
+            }
+        }
+
+        impl<T: Sample> $op_trait<T> for X2<T> {
+            type Output = Self;
+
+            #[inline]
+            fn $op_fn(self, rhs: T) -> Self {
+                Self([self.0[0] $op rhs, self.0[1] $op rhs])
+            }
+        }
+
+        impl<T: Sample> $assign_trait for X2<T> {
+            #[inline]
+            fn $assign_fn(&mut self, rhs: Self) {
+                *self = *self $op rhs;
+            }
+        }
+    };
+}
+
+impl_lane_op!(Add, add, AddAssign, add_assign, +);
+impl_lane_op!(Sub, sub, SubAssign, sub_assign, -);
+impl_lane_op!(Mul, mul, MulAssign, mul_assign, *);
+
+impl<T: Sample> Neg for X2<T> {
+    type Output = Self;
+
+    #[inline]
+    fn neg(self) -> Self {
+        Self([-self.0[0], -self.0[1]])
+    }
+}
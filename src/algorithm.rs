@@ -0,0 +1,157 @@
+use nih_plug::prelude::Enum;
+
+/// The sample rate the Lo-Fi algorithm reduces the feedback to, in Hz
+pub const LOFI_RATE: f32 = 8000.;
+/// The bit depth the Lo-Fi algorithm reduces the feedback to
+pub const LOFI_BITS: u32 = 8;
+
+/// Ready made signal flows, each of them sets up the stages of the plugin for one kind of delay.
+///
+/// An algorithm doesn't change any parameter. It's a layer between the parameters and the DSP that forces some
+/// stages on or off, so all parameters can still be automated and take over again in [Algorithm::Custom].
+///
+/// Usage:
+/// ```
+/// use delax::algorithm::Algorithm;
+///
+/// // The tape algorithm always runs the tape echo, whatever its switch says
+/// assert!(Algorithm::Tape.topology().tape_echo(false));
+/// // Custom leaves everything to the parameters
+/// assert!(!Algorithm::Custom.topology().tape_echo(false));
+/// ```
+#[derive(Debug, Enum, Default, PartialEq, Eq, Clone, Copy)]
+pub enum Algorithm {
+    /// Every stage follows its own parameters
+    #[default]
+    Custom,
+    /// A plain delay without any coloring in the feedback loop
+    #[name = "Digital Clean"]
+    DigitalClean,
+    /// The tape echo with filtered repeats
+    Tape,
+    /// Filtered repeats that bounce between the channels
+    #[name = "Ping-Pong Dub"]
+    PingPongDub,
+    /// Every repeat is played back an octave higher than the last one
+    Shimmer,
+    /// Filtered repeats at a reduced sample rate and bit depth
+    #[name = "Lo-Fi"]
+    LoFi,
+}
+
+impl Algorithm {
+    /// The stages this algorithm forces on or off
+    pub fn topology(self) -> Topology {
+        let fixed = Topology {
+            tape_echo: Some(false),
+            ping_pong: None,
+            feedback_filter: Some(true),
+            multiband: Some(false),
+            read_speed: None,
+            lofi: false,
+        };
+        match self {
+            Algorithm::Custom => Topology::default(),
+            Algorithm::DigitalClean => Topology {
+                ping_pong: Some(false),
+                feedback_filter: Some(false),
+                read_speed: Some(1.),
+                ..fixed
+            },
+            Algorithm::Tape => Topology {
+                tape_echo: Some(true),
+                ping_pong: Some(false),
+                ..fixed
+            },
+            Algorithm::PingPongDub => Topology {
+                ping_pong: Some(true),
+                ..fixed
+            },
+            Algorithm::Shimmer => Topology {
+                feedback_filter: Some(false),
+                read_speed: Some(2.),
+                ..fixed
+            },
+            Algorithm::LoFi => Topology {
+                lofi: true,
+                ..fixed
+            },
+        }
+    }
+}
+
+/// What an [Algorithm] does to the signal flow. A stage that's `None` follows its parameter.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Topology {
+    pub tape_echo: Option<bool>,
+    pub ping_pong: Option<bool>,
+    /// Whether the feedback runs through the feedback filters
+    pub feedback_filter: Option<bool>,
+    pub multiband: Option<bool>,
+    /// The speed the delay lines are read at, replacing the read speed and its detune
+    pub read_speed: Option<f32>,
+    /// Whether the feedback is reduced to [LOFI_RATE] and [LOFI_BITS]
+    pub lofi: bool,
+}
+
+impl Topology {
+    /// Whether the tape echo runs, given the value of its switch
+    pub fn tape_echo(&self, enabled: bool) -> bool {
+        self.tape_echo.unwrap_or(enabled)
+    }
+
+    /// Whether the repeats bounce between the channels, given the value of the ping pong switch
+    pub fn ping_pong(&self, enabled: bool) -> bool {
+        self.ping_pong.unwrap_or(enabled)
+    }
+
+    /// Whether the feedback filters are bypassed, given the value of their bypass
+    pub fn feedback_filter_bypassed(&self, bypassed: bool) -> bool {
+        self.feedback_filter.map_or(bypassed, |filtered| !filtered)
+    }
+
+    /// Whether the multiband mode runs, given the value of its switch
+    pub fn multiband(&self, enabled: bool) -> bool {
+        self.multiband.unwrap_or(enabled)
+    }
+
+    /// The read speed of the delay lines, given the speed the parameters ask for
+    pub fn read_speed(&self, speed: f32) -> f32 {
+        self.read_speed.unwrap_or(speed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn custom_follows_the_parameters() {
+        let topology = Algorithm::Custom.topology();
+        for value in [false, true] {
+            assert_eq!(topology.tape_echo(value), value);
+            assert_eq!(topology.ping_pong(value), value);
+            assert_eq!(topology.feedback_filter_bypassed(value), value);
+            assert_eq!(topology.multiband(value), value);
+        }
+        assert_eq!(topology.read_speed(0.5), 0.5);
+        assert!(!topology.lofi);
+    }
+
+    #[test]
+    fn algorithms_force_their_stages() {
+        let clean = Algorithm::DigitalClean.topology();
+        assert!(clean.feedback_filter_bypassed(false));
+        assert!(!clean.tape_echo(true) && !clean.ping_pong(true) && !clean.multiband(true));
+        assert_eq!(clean.read_speed(0.5), 1.);
+
+        assert!(Algorithm::PingPongDub.topology().ping_pong(false));
+        assert!(!Algorithm::PingPongDub
+            .topology()
+            .feedback_filter_bypassed(true));
+        assert_eq!(Algorithm::Shimmer.topology().read_speed(1.), 2.);
+        assert!(Algorithm::LoFi.topology().lofi);
+        // Shimmer leaves the ping pong to its switch
+        assert!(Algorithm::Shimmer.topology().ping_pong(true));
+    }
+}
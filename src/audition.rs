@@ -0,0 +1,292 @@
+use std::{
+    f32::consts::TAU,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use crate::wav::WavAudio;
+
+/// The sample rate the built-in loop is generated at
+const BUILTIN_SAMPLE_RATE: f32 = 44100.;
+/// The tempo of the built-in loop, it's one bar long
+const BUILTIN_BPM: f32 = 120.;
+/// The notes of the built-in loop in Hz, one per eighth note. An A minor pentatonic line leaves room for the repeats.
+const BUILTIN_NOTES: [f32; 8] = [220., 261.63, 329.63, 392., 440., 392., 293.66, 261.63];
+/// The level of every pluck of the built-in loop
+const BUILTIN_LEVEL: f32 = 0.3;
+/// How fast a pluck of the built-in loop decays, per second
+const BUILTIN_DECAY: f32 = 12.;
+/// The name shown for the built-in loop
+pub const BUILTIN_NAME: &str = "Built-in loop";
+
+/// A stereo loop at its own sample rate, as it was generated or loaded
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditionLoop {
+    pub sample_rate: f32,
+    pub frames: Vec<[f32; 2]>,
+}
+
+impl AuditionLoop {
+    /// One bar of short plucks on every eighth note, panned from side to side, so the repeats are easy to hear.
+    ///
+    /// Usage:
+    /// ```
+    /// use delax::audition::AuditionLoop;
+    ///
+    /// let builtin = AuditionLoop::builtin();
+    /// // One bar at 120 BPM
+    /// assert_eq!(builtin.frames.len() as f32 / builtin.sample_rate, 2.);
+    /// ```
+    pub fn builtin() -> Self {
+        let note_len = (BUILTIN_SAMPLE_RATE * 30. / BUILTIN_BPM) as usize;
+        let frames = BUILTIN_NOTES
+            .iter()
+            .enumerate()
+            .flat_map(|(index, frequency)| {
+                // Every other note leans to the other side
+                let pan = if index % 2 == 0 { 0.3 } else { 0.7 };
+                (0..note_len).map(move |frame| {
+                    let time = frame as f32 / BUILTIN_SAMPLE_RATE;
+                    let sample = (TAU * frequency * time).sin()
+                        * (-BUILTIN_DECAY * time).exp()
+                        * BUILTIN_LEVEL;
+                    [sample * (1. - pan), sample * pan]
+                })
+            })
+            .collect();
+
+        Self {
+            sample_rate: BUILTIN_SAMPLE_RATE,
+            frames,
+        }
+    }
+
+    /// Take the first two channels of a WAV file, mono files play on both sides
+    pub fn from_wav(audio: WavAudio) -> Result<Self, String> {
+        let left = audio.channels.first().ok_or("The file has no channels")?;
+        let right = audio.channels.get(1).unwrap_or(left);
+        if left.is_empty() {
+            return Err(String::from("The file is empty"));
+        }
+
+        Ok(Self {
+            sample_rate: audio.sample_rate as f32,
+            frames: left.iter().zip(right).map(|(l, r)| [*l, *r]).collect(),
+        })
+    }
+
+    /// The loop at another sample rate, linearly interpolated. It's only meant for listening, not for the output.
+    pub fn resampled(&self, sample_rate: f32) -> Vec<[f32; 2]> {
+        if sample_rate == self.sample_rate {
+            return self.frames.clone();
+        }
+
+        let ratio = self.sample_rate / sample_rate;
+        let len = ((self.frames.len() as f32 / ratio).round() as usize).max(1);
+        (0..len)
+            .map(|frame| {
+                let position = frame as f32 * ratio;
+                let index = position as usize;
+                let fraction = position - index as f32;
+                // The loop wraps around, so the last frame interpolates towards the first one
+                let [l1, r1] = self.frames[index % self.frames.len()];
+                let [l2, r2] = self.frames[(index + 1) % self.frames.len()];
+                [l1 + (l2 - l1) * fraction, r1 + (r2 - r1) * fraction]
+            })
+            .collect()
+    }
+}
+
+/// The audition loop, shared between the editor, the background thread and the audio thread.
+///
+/// The editor turns the audition on and off, the background thread loads new loops and the [AuditionPlayer] on the
+/// audio thread plays them. Like the solos this isn't saved, so a forgotten audition doesn't end up in a project.
+pub struct Audition {
+    enabled: AtomicBool,
+    /// The name of the loop that's playing, to show in the editor
+    name: Mutex<String>,
+    /// The loop at its own sample rate, so the player can resample it when the sample rate changes
+    source: Mutex<AuditionLoop>,
+    /// A new loop resampled for the player, or the loop the player replaced. The player swaps them, so the old
+    /// loop is freed by the next load instead of on the audio thread.
+    pending: Mutex<Vec<[f32; 2]>>,
+    has_pending: AtomicBool,
+}
+
+impl Default for Audition {
+    fn default() -> Self {
+        Self {
+            enabled: AtomicBool::new(false),
+            name: Mutex::new(BUILTIN_NAME.to_string()),
+            source: Mutex::new(AuditionLoop::builtin()),
+            pending: Mutex::new(Vec::new()),
+            has_pending: AtomicBool::new(false),
+        }
+    }
+}
+
+impl Audition {
+    pub fn enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    /// The name of the loop that's playing
+    pub fn name(&self) -> String {
+        self.name.lock().unwrap().clone()
+    }
+
+    /// Hand a new loop to the player. This resamples the loop, so it must not be called from the audio thread.
+    pub fn load(&self, audition_loop: AuditionLoop, name: String, sample_rate: f32) {
+        let frames = audition_loop.resampled(sample_rate);
+        *self.source.lock().unwrap() = audition_loop;
+        *self.pending.lock().unwrap() = frames;
+        self.has_pending.store(true, Ordering::Release);
+        *self.name.lock().unwrap() = name;
+    }
+}
+
+/// Plays the audition loop on the audio thread while the [Audition] is turned on.
+///
+/// Usage:
+/// ```
+/// use std::sync::Arc;
+/// use delax::audition::{Audition, AuditionPlayer};
+///
+/// let audition = Arc::new(Audition::default());
+/// let mut player = AuditionPlayer::new(audition.clone(), 44100.);
+/// assert!(!player.active(false));
+///
+/// audition.set_enabled(true);
+/// assert!(player.active(false));
+/// // The host playing always wins over the audition
+/// assert!(!player.active(true));
+/// ```
+pub struct AuditionPlayer {
+    audition: Arc<Audition>,
+    /// The loop at the sample rate of the plugin
+    frames: Vec<[f32; 2]>,
+    position: usize,
+}
+
+impl AuditionPlayer {
+    pub fn new(audition: Arc<Audition>, sample_rate: f32) -> Self {
+        let frames = audition.source.lock().unwrap().resampled(sample_rate);
+        Self {
+            audition,
+            frames,
+            position: 0,
+        }
+    }
+
+    /// The audition shared with the editor
+    pub fn audition(&self) -> &Arc<Audition> {
+        &self.audition
+    }
+
+    /// Resample the loop for a new sample rate. This allocates, so it's only called while preparing the plugin.
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.frames = self.audition.source.lock().unwrap().resampled(sample_rate);
+        self.audition.has_pending.store(false, Ordering::Release);
+        self.position = 0;
+    }
+
+    /// Whether the loop plays during the next block, while the audition is on and the host isn't playing.
+    /// This also picks up a newly loaded loop, so it's called once per block.
+    pub fn active(&mut self, host_playing: bool) -> bool {
+        if self.audition.has_pending.load(Ordering::Acquire) {
+            // The loop is picked up on a later block if the background thread is still busy with it
+            if let Ok(mut pending) = self.audition.pending.try_lock() {
+                std::mem::swap(&mut self.frames, &mut *pending);
+                self.audition.has_pending.store(false, Ordering::Release);
+                self.position = 0;
+            }
+        }
+
+        let active = self.audition.enabled() && !host_playing;
+        if !active {
+            // Start from the top every time the audition starts again
+            self.position = 0;
+        }
+        active
+    }
+
+    /// The next frame of the loop
+    pub fn next_frame(&mut self) -> [f32; 2] {
+        let Some(frame) = self.frames.get(self.position) else {
+            return [0.; 2];
+        };
+        self.position = (self.position + 1) % self.frames.len();
+        *frame
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loaded_loops_replace_the_playing_one() {
+        let audition = Arc::new(Audition::default());
+        let mut player = AuditionPlayer::new(audition.clone(), 1000.);
+        audition.set_enabled(true);
+        assert!(player.active(false));
+        // The first pluck starts at zero
+        player.next_frame();
+        assert_ne!(player.next_frame(), [0.; 2]);
+
+        let audition_loop = AuditionLoop {
+            sample_rate: 500.,
+            frames: vec![[1., 0.], [0., 1.]],
+        };
+        audition.load(audition_loop, String::from("Drums.wav"), 1000.);
+        assert_eq!(audition.name(), "Drums.wav");
+        assert!(player.active(false));
+        let frames: Vec<_> = (0..5).map(|_| player.next_frame()).collect();
+        assert_eq!(
+            frames,
+            [[1., 0.], [0.5, 0.5], [0., 1.], [0.5, 0.5], [1., 0.]]
+        );
+
+        // A new sample rate resamples the loaded loop
+        player.set_sample_rate(500.);
+        assert!(player.active(false));
+        assert_eq!(player.next_frame(), [1., 0.]);
+        assert_eq!(player.next_frame(), [0., 1.]);
+    }
+
+    #[test]
+    fn the_loop_restarts_when_the_audition_stops() {
+        let audition = Arc::new(Audition::default());
+        let mut player = AuditionPlayer::new(audition.clone(), 44100.);
+        audition.set_enabled(true);
+        player.active(false);
+        let first = player.next_frame();
+        player.next_frame();
+
+        assert!(!player.active(true));
+        assert!(player.active(false));
+        assert_eq!(player.next_frame(), first);
+    }
+
+    #[test]
+    fn mono_files_play_on_both_sides() {
+        let audio = WavAudio {
+            sample_rate: 48000,
+            channels: vec![vec![0.5, -0.5]],
+        };
+        let audition_loop = AuditionLoop::from_wav(audio).unwrap();
+        assert_eq!(audition_loop.frames, [[0.5, 0.5], [-0.5, -0.5]]);
+
+        let empty = WavAudio {
+            sample_rate: 48000,
+            channels: vec![Vec::new(), Vec::new()],
+        };
+        assert!(AuditionLoop::from_wav(empty).is_err());
+    }
+}
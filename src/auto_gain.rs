@@ -0,0 +1,103 @@
+use nih_plug::util;
+
+/// The time constant of the short-term RMS of the input and the output in ms
+const RMS_MS: f32 = 300.;
+/// The time constant the gain follows the level difference with in ms, slow enough to not pump
+const GAIN_MS: f32 = 500.;
+/// The most the auto-gain boosts or cuts, in dB
+pub const MAX_AUTO_GAIN_DB: f32 = 12.;
+/// Inputs below this RMS level count as silence, the gain is held while the input is silent so tails aren't cut
+const SILENCE_RMS: f32 = 1e-3;
+
+/// Keeps the output roughly as loud as the input, so changing the wetness or feedback doesn't bias decisions by level.
+///
+/// The short-term RMS of the input and the output are compared and the output is pulled towards the input level.
+/// The energy of all channels is summed, so the stereo image stays the same.
+///
+/// Usage:
+/// ```
+/// use delax::auto_gain::AutoGain;
+///
+/// let mut auto_gain = AutoGain::new(1000.);
+/// // An output twice as loud as the input is brought back down by 6 dB
+/// let gain = (0..10000).map(|_| auto_gain.next(0.25, 1.)).last().unwrap();
+/// assert!((gain - 0.5).abs() < 1e-3);
+/// ```
+pub struct AutoGain {
+    /// The coefficients of the RMS and the gain smoothers for the current sample rate
+    rms_coefficient: f32,
+    gain_coefficient: f32,
+    /// The short-term mean squares of the input and the output
+    input_ms: f32,
+    output_ms: f32,
+    gain: f32,
+}
+
+impl AutoGain {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            rms_coefficient: one_pole_coefficient(RMS_MS, sample_rate),
+            gain_coefficient: one_pole_coefficient(GAIN_MS, sample_rate),
+            input_ms: 0.,
+            output_ms: 0.,
+            gain: 1.,
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.rms_coefficient = one_pole_coefficient(RMS_MS, sample_rate);
+        self.gain_coefficient = one_pole_coefficient(GAIN_MS, sample_rate);
+    }
+
+    /// Feed the summed squares of the input and the output of a frame, returns the gain for the output of the frame
+    pub fn next(&mut self, input_energy: f32, output_energy: f32) -> f32 {
+        self.input_ms += (input_energy - self.input_ms) * self.rms_coefficient;
+        self.output_ms += (output_energy - self.output_ms) * self.rms_coefficient;
+
+        if self.input_ms.sqrt() > SILENCE_RMS && self.output_ms > 0. {
+            let max_gain = util::db_to_gain(MAX_AUTO_GAIN_DB);
+            let target = (self.input_ms / self.output_ms)
+                .sqrt()
+                .clamp(1. / max_gain, max_gain);
+            self.gain += (target - self.gain) * self.gain_coefficient;
+        }
+
+        self.gain
+    }
+
+    pub fn reset(&mut self) {
+        self.input_ms = 0.;
+        self.output_ms = 0.;
+        self.gain = 1.;
+    }
+}
+
+/// The coefficient of a one pole smoother with a time constant in ms
+fn one_pole_coefficient(ms: f32, sample_rate: f32) -> f32 {
+    1. - (-1000. / (ms * sample_rate)).exp()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gain_is_held_while_the_input_is_silent() {
+        let mut auto_gain = AutoGain::new(1000.);
+        for _ in 0..10000 {
+            auto_gain.next(1., 0.25);
+        }
+        assert!((auto_gain.next(1., 0.25) - 2.).abs() < 1e-2);
+
+        // Once the input died down, a ringing tail keeps the gain it had
+        let tail_gains: Vec<_> = (0..10000).map(|_| auto_gain.next(0., 0.1)).collect();
+        assert_eq!(tail_gains[5000], tail_gains[9999]);
+    }
+
+    #[test]
+    fn gain_is_limited() {
+        let mut auto_gain = AutoGain::new(1000.);
+        let gain = (0..20000).map(|_| auto_gain.next(1., 1e-6)).last().unwrap();
+        assert!(gain <= util::db_to_gain(MAX_AUTO_GAIN_DB) + 1e-3);
+    }
+}
@@ -0,0 +1,138 @@
+use super::{
+    engine::{interpolation_for, ms_to_samples, DelayEngine},
+    params::DualRouting,
+};
+
+/// The longest delay time of the second stage in ms
+pub const MAX_DUAL_MS: f32 = 2000.;
+
+/// How the second stage of a channel is fed and mixed, read from the parameters once per sample
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DualMix {
+    pub routing: DualRouting,
+    pub feedback: f32,
+    /// From only the main delay at 0 to only the second stage at 1
+    pub balance: f32,
+}
+
+/// The second delay line of a channel, which runs after or next to the main delay, like a slapback into a long echo.
+///
+/// It has its own feedback loop, which skips the filters and the routing between the channels.
+///
+/// Usage:
+/// ```
+/// use delax::delay_engine::{
+///     dual::{DualMix, DualStage},
+///     params::DualRouting,
+/// };
+///
+/// let mut stage = DualStage::new(1000.);
+/// stage.set_time(10.);
+/// let mix = DualMix {
+///     routing: DualRouting::Parallel,
+///     feedback: 0.,
+///     balance: 1.,
+/// };
+///
+/// // Only the second stage is heard, it repeats the input 10 ms later
+/// let wet: Vec<f32> = (0..=10)
+///     .map(|sample| stage.process(if sample == 0 { 1. } else { 0. }, 0., mix))
+///     .collect();
+/// assert_eq!(wet[10], 1.);
+/// assert!(wet[..10].iter().all(|sample| *sample == 0.));
+/// ```
+pub struct DualStage {
+    engine: DelayEngine,
+}
+
+impl DualStage {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut engine = DelayEngine::new(ms_to_samples(MAX_DUAL_MS, sample_rate) + 1, sample_rate);
+        engine.set_delay_amount(0.);
+        Self { engine }
+    }
+
+    /// Set the delay time in ms, up to [MAX_DUAL_MS]
+    pub fn set_time(&mut self, time: f32) {
+        self.engine.set_delay_amount(time.min(MAX_DUAL_MS));
+    }
+
+    /// Run one sample. `input` is what the main delay of the channel was fed and `main` what it put out,
+    /// the result is the wet signal of the channel.
+    pub fn process(&mut self, input: f32, main: f32, mix: DualMix) -> f32 {
+        let delayed = self
+            .engine
+            .interpolate_sample(interpolation_for(self.engine.delay_time()));
+        let fed = match mix.routing {
+            DualRouting::Serial => main,
+            DualRouting::Parallel => input,
+        };
+        self.engine.write_sample(fed + delayed * mix.feedback);
+
+        main + (delayed - main) * mix.balance
+    }
+
+    /// Empty the delay line
+    pub fn reset(&mut self) {
+        self.engine.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feed an impulse into the input or the main delay and collect the wet signal
+    fn impulse_response(mix: DualMix, into_main: bool, len: usize) -> Vec<f32> {
+        let mut stage = DualStage::new(1000.);
+        stage.set_time(10.);
+        (0..len)
+            .map(|sample| {
+                let impulse = if sample == 0 { 1. } else { 0. };
+                if into_main {
+                    stage.process(0., impulse, mix)
+                } else {
+                    stage.process(impulse, 0., mix)
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn serial_stages_repeat_the_main_delay() {
+        let mix = DualMix {
+            routing: DualRouting::Serial,
+            feedback: 0.5,
+            balance: 0.5,
+        };
+        let wet = impulse_response(mix, true, 25);
+        // The repeat of the main delay, then the repeats of the second stage at half the level each
+        assert_eq!(wet[0], 0.5);
+        assert_eq!(wet[10], 0.5);
+        assert_eq!(wet[20], 0.25);
+        let heard = wet.iter().filter(|sample| **sample != 0.).count();
+        assert_eq!(heard, 3);
+
+        // The input doesn't reach a serial second stage
+        assert!(impulse_response(mix, false, 25)
+            .iter()
+            .all(|sample| *sample == 0.));
+    }
+
+    #[test]
+    fn parallel_stages_repeat_the_input() {
+        let mix = DualMix {
+            routing: DualRouting::Parallel,
+            feedback: 0.,
+            balance: 0.25,
+        };
+        let wet = impulse_response(mix, false, 25);
+        assert_eq!(wet[10], 0.25);
+        assert_eq!(wet.iter().filter(|sample| **sample != 0.).count(), 1);
+
+        // The main delay passes at the rest of the balance
+        let main = impulse_response(mix, true, 25);
+        assert_eq!(main[0], 0.75);
+        assert_eq!(main.iter().filter(|sample| **sample != 0.).count(), 1);
+    }
+}
@@ -0,0 +1,128 @@
+use super::{
+    engine::{DelayEngine, LoopInput},
+    params::EngineParams,
+    reverse::ReverseReader,
+    tempo_lock::{synced_delay_ms, TempoFrame},
+};
+use crate::{smoothing::Gate, GATE_FADE_MS};
+
+/// The loop region and the reverse button of the plugin, which play back the delay lines of every channel in
+/// another order than they were written in.
+///
+/// The loop region sets up the jumps of the delay lines themselves, the reverse button reads them with a
+/// [ReverseReader] per channel and fades over to it while it's held.
+pub struct GlitchStage {
+    /// Plays the repeats of every channel backwards while the reverse button is held
+    reversers: Vec<ReverseReader>,
+    /// Fades between the repeats and the reversed repeats
+    reverse_gate: Gate,
+    /// The start and the length in ms of the loop region that's playing, to capture it again when the parameters change
+    loop_region: Option<(f32, f32)>,
+}
+
+impl GlitchStage {
+    pub fn new(channels: usize) -> Self {
+        Self {
+            reversers: vec![ReverseReader::default(); channels],
+            reverse_gate: Gate::new(GATE_FADE_MS, false),
+            loop_region: None,
+        }
+    }
+
+    /// Start over with new delay lines, for a new sample rate or amount of channels
+    pub fn prepare(&mut self, channels: usize) {
+        // The new delay lines are empty, so a loop is captured again from scratch
+        self.loop_region = None;
+        self.reversers = vec![ReverseReader::default(); channels];
+    }
+
+    /// Jump to the state of the reverse button without fading
+    pub fn reset(&mut self, params: &EngineParams) {
+        self.reverse_gate.reset(params.perform.reverse.value());
+    }
+
+    /// Let go of the loop region after the delay lines were emptied, so it's captured again from the empty delay lines
+    pub fn clear(&mut self, delay_engines: &mut [DelayEngine]) {
+        if self.loop_region.take().is_some() {
+            delay_engines.iter_mut().for_each(DelayEngine::stop_loop);
+        }
+        self.restart();
+    }
+
+    /// Start the reversed slices at the delay time again
+    pub fn restart(&mut self) {
+        self.reversers.iter_mut().for_each(ReverseReader::reset);
+    }
+
+    /// Follow the loop region of the parameters, once per sample.
+    ///
+    /// The loop region is captured when it's turned on. It reaches back from the newest sample, so it can't be
+    /// longer than its start. While the input is ignored nothing new is written, so the loop can be moved around
+    /// the frozen delay lines and is captured again whenever its times change.
+    pub fn update(
+        &mut self,
+        params: &EngineParams,
+        tempo: &TempoFrame,
+        delay_engines: &mut [DelayEngine],
+    ) {
+        let loop_params = &params.loop_region;
+        let loop_region = loop_params.enabled.value().then(|| {
+            let (start, length) = (loop_params.start.value(), loop_params.length.value());
+            let (start, length) = if loop_params.bpm_bound.value() {
+                (
+                    synced_delay_ms(start, tempo.to),
+                    synced_delay_ms(length, tempo.to),
+                )
+            } else {
+                (start, length)
+            };
+            (start, length.min(start))
+        });
+        let input: LoopInput = loop_params.input.value().into();
+        let recapture = match (self.loop_region, loop_region) {
+            (Some(playing), Some(region)) => input == LoopInput::Ignore && playing != region,
+            (playing, region) => playing.is_some() != region.is_some(),
+        };
+        if recapture {
+            self.loop_region = loop_region;
+            for delay_engine in delay_engines {
+                match loop_region {
+                    Some((start, length)) => {
+                        let result = delay_engine.start_loop(start, length, input);
+                        debug_assert!(result.is_ok(), "{result:?}");
+                    }
+                    None => delay_engine.stop_loop(),
+                }
+            }
+        }
+    }
+
+    /// Play the repeats of every channel backwards while `reverse` is set, and replace them with the loop region or
+    /// play it on top of them. This moves the reversers and the loop on by a sample.
+    pub fn process(
+        &mut self,
+        popped: &mut [f32],
+        delay_engines: &mut [DelayEngine],
+        sample_rate: f32,
+        reverse: bool,
+    ) {
+        // The readers start a new slice every time the button is pressed
+        let reverse_gain = self.reverse_gate.next(sample_rate, reverse);
+        if reverse_gain > 0. {
+            for ((popped, delay_engine), reverser) in popped
+                .iter_mut()
+                .zip(delay_engines.iter())
+                .zip(&mut self.reversers)
+            {
+                let reversed = reverser.next(delay_engine);
+                *popped += (reversed - *popped) * reverse_gain;
+            }
+        } else {
+            self.restart();
+        }
+        // The loop region replaces the repeats or plays on top of them
+        for (popped, delay_engine) in popped.iter_mut().zip(delay_engines) {
+            *popped = delay_engine.loop_output(*popped);
+        }
+    }
+}
@@ -1,2 +1,19 @@
-pub mod engine;
+pub use delax_dsp::delay_engine::{drift, engine, feedback_matrix, multihead, string};
+#[cfg(feature = "plugin")]
+pub mod dual;
+#[cfg(all(feature = "plugin", feature = "glitch"))]
+pub mod glitch;
+#[cfg(feature = "plugin")]
 pub mod params;
+#[cfg(all(feature = "plugin", feature = "glitch"))]
+pub mod reverse;
+#[cfg(feature = "plugin")]
+pub mod snap;
+#[cfg(feature = "plugin")]
+pub mod stability;
+#[cfg(feature = "plugin")]
+pub mod tempo_lock;
+#[cfg(feature = "plugin")]
+pub mod throw;
+#[cfg(feature = "plugin")]
+pub mod transport;
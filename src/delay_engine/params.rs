@@ -1,34 +1,156 @@
+use std::{collections::BTreeMap, ops::Deref};
+
 use nih_plug::prelude::*;
 
+#[cfg(feature = "glitch")]
+use super::engine::LoopInput;
+use super::{
+    drift::MAX_DRIFT_MS,
+    dual::MAX_DUAL_MS,
+    engine::{MAX_DELAY_MS, MAX_READ_SPEED, MIN_READ_SPEED},
+    multihead::NUM_HEADS,
+    string::decay_gain,
+    throw::{MAX_THROW_RAMP_MS, MIN_THROW_RAMP_MS},
+};
+use crate::{
+    midi::poly_mod::PolyModTarget,
+    param_changes::{ParamChanges, ParamTopic},
+    param_snapshot::{bool_param, enum_param, float_param},
+    smoothing::Response,
+};
+
+/// The amount of channels that have their own set of delay parameters
+pub const NUM_CHANNELS: usize = 2;
+
 #[derive(Enum, PartialEq)]
 pub enum DelayMode {
     Mono,
     Stereo,
 }
 
+/// How the feedback of the channels is set
+#[derive(Debug, Enum, PartialEq, Clone, Copy)]
+pub enum FeedbackMode {
+    /// The feedback knob sets the gain of every repeat
+    Ratio,
+    /// The decay time sets how long the repeats take to fall by 60 dB, whatever the delay time is
+    #[name = "Decay Time"]
+    DecayTime,
+}
+
+impl FeedbackMode {
+    /// The gain of every repeat for the feedback and the decay time of a channel with a delay time of `delay_ms`.
+    ///
+    /// Delays below a millisecond count as one, so the shortest delays don't ring forever.
+    ///
+    /// Usage:
+    /// ```
+    /// use delax::delay_engine::params::FeedbackMode;
+    ///
+    /// assert_eq!(FeedbackMode::Ratio.feedback_gain(0.5, 2., 500.), 0.5);
+    /// // Four repeats half a second apart take the two seconds to fall by 60 dB
+    /// let gain = FeedbackMode::DecayTime.feedback_gain(0.5, 2., 500.);
+    /// assert!((gain.powi(4) - 0.001).abs() < 1e-6);
+    /// ```
+    pub fn feedback_gain(self, feedback: f32, decay_time: f32, delay_ms: f32) -> f32 {
+        match self {
+            FeedbackMode::Ratio => feedback,
+            FeedbackMode::DecayTime => decay_gain(delay_ms.max(1.), decay_time),
+        }
+    }
+}
+
+/// What happens to the repeats when the host stops playing
+#[derive(Debug, Enum, PartialEq, Clone, Copy)]
+pub enum StopBehavior {
+    /// The repeats ring out as usual
+    #[name = "Ring Out"]
+    Continue,
+    /// The delay lines are cleared
+    Clear,
+}
+
+/// How the second delay stage is connected to the main delay
+#[derive(Debug, Enum, PartialEq, Clone, Copy)]
+pub enum DualRouting {
+    /// The second stage repeats the repeats of the main delay, like a slapback running into a long echo
+    Serial,
+    /// Both stages are fed the input and repeat side by side
+    Parallel,
+}
+
+/// When the patterns of the heads or the drift start over while the host is playing, so they're in time with the song
+#[derive(Debug, Enum, PartialEq, Clone, Copy)]
+pub enum RetriggerMode {
+    Off,
+    /// When the host starts playing
+    #[name = "On Play"]
+    Play,
+    /// At the start of every bar
+    #[name = "Every Bar"]
+    Bar,
+}
+
+/// How a BPM bound delay moves over to a new host tempo
+#[derive(Debug, Enum, PartialEq, Clone, Copy)]
+pub enum TempoTransition {
+    /// The delay time glides, which bends the pitch of the repeats
+    Repitch,
+    /// The old and the new delay time are crossfaded
+    Crossfade,
+}
+
+/// The side the first repeat of the ping pong delay appears on
+#[derive(Debug, Enum, PartialEq, Clone, Copy)]
+pub enum PingPongStart {
+    Left,
+    Right,
+}
+
+/// What happens to the input while the loop region plays, mirroring [LoopInput] of the DSP crate
+#[derive(Debug, Enum, PartialEq, Clone, Copy)]
+pub enum LoopInputMode {
+    /// Only the loop plays, the delay lines stop taking in the input
+    Ignore,
+    /// The delay keeps going and the loop plays on top of the repeats
+    Layer,
+}
+
+#[cfg(feature = "glitch")]
+impl From<LoopInputMode> for LoopInput {
+    fn from(mode: LoopInputMode) -> Self {
+        match mode {
+            LoopInputMode::Ignore => LoopInput::Ignore,
+            LoopInputMode::Layer => LoopInput::Layer,
+        }
+    }
+}
+
+/// The delay parameters of a single channel
 #[derive(Params)]
-pub struct EngineParams {
-    #[id = "delay_l"]
-    pub delay_len_l: FloatParam,
-    #[id = "delay_r"]
-    pub delay_len_r: FloatParam,
-    #[id = "feedback_l"]
-    pub feedback_l: FloatParam,
-    #[id = "feedback_r"]
-    pub feedback_r: FloatParam,
-    #[id = "stereo"]
-    pub stereo_delay: EnumParam<DelayMode>,
-    #[id = "bpm_bound_l"]
-    pub bpm_bound_l: BoolParam,
-    #[id = "bpm_bound_r"]
-    pub bpm_bound_r: BoolParam,
+pub struct ChannelDelayParams {
+    #[id = "delay"]
+    pub len: FloatParam,
+    #[id = "feedback"]
+    pub feedback: FloatParam,
+    /// The time in seconds the repeats take to fall by 60 dB in [FeedbackMode::DecayTime]
+    #[id = "decay_time"]
+    pub decay_time: FloatParam,
+    #[id = "bpm_bound"]
+    pub bpm_bound: BoolParam,
+    /// Flip the polarity of the wet signal of this channel
+    #[id = "invert"]
+    pub invert: BoolParam,
 }
 
-impl Default for EngineParams {
-    fn default() -> Self {
+impl ChannelDelayParams {
+    /// Create the parameters for the channel with the given index, smoothed with the shared response time
+    pub fn new(channel: usize, response: &Response, changes: &ParamChanges) -> Self {
+        let suffix = channel_name_suffix(channel);
+
         Self {
-            delay_len_l: FloatParam::new(
-                "Delay",
+            len: float_param(
+                format!("Delay{suffix}"),
                 500.,
                 FloatRange::Skewed {
                     min: 0.,
@@ -36,36 +158,823 @@ impl Default for EngineParams {
                     factor: 0.5,
                 },
             )
-            .with_smoother(SmoothingStyle::Linear(50.0))
+            .with_smoother(response.style())
+            .with_poly_modulation_id(PolyModTarget::DelayTime.id(channel))
+            .with_callback(changes.callback(ParamTopic::DelayTimes))
             .with_unit(" ms")
             .with_value_to_string(formatters::v2s_f32_rounded(1)),
+            feedback: float_param(
+                format!("Feedback{suffix}"),
+                0.5,
+                FloatRange::Linear { min: 0., max: 1. },
+            )
+            .with_smoother(response.style())
+            .with_poly_modulation_id(PolyModTarget::Feedback.id(channel))
+            .with_callback(changes.callback(ParamTopic::DelayTimes))
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            decay_time: float_param(
+                format!("Decay Time{suffix}"),
+                2.,
+                FloatRange::Skewed {
+                    min: 0.05,
+                    max: 30.,
+                    factor: FloatRange::skew_factor(-2.),
+                },
+            )
+            .with_smoother(response.logarithmic_style())
+            .with_callback(changes.callback(ParamTopic::DelayTimes))
+            .with_unit(" s")
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+            bpm_bound: bool_param(format!("BPM Bound{suffix}"), false)
+                .with_callback(changes.callback(ParamTopic::DelayTimes)),
+            invert: bool_param(format!("Invert Polarity{suffix}"), false),
+        }
+    }
 
-            delay_len_r: FloatParam::new(
-                "Delay Channel 2",
-                500.,
+    /// Snap all the smoothers to their current values
+    pub fn reset_smoothers(&self) {
+        for param in [&self.len, &self.feedback, &self.decay_time] {
+            param.smoothed.reset(param.value());
+        }
+    }
+}
+
+/// The controls of the tape echo mode, a simpler take on the delay with fixed playback heads like a tape echo
+#[derive(Params)]
+pub struct TapeEchoParams {
+    /// Play the delay lines back through the tape heads instead of at the delay time of the channels
+    #[id = "tape_echo"]
+    pub enabled: BoolParam,
+    /// The delay time of the first head, the other heads follow at multiples of it
+    #[id = "tape_repeat_rate"]
+    pub repeat_rate: FloatParam,
+    #[id = "tape_head1"]
+    pub head_1: BoolParam,
+    #[id = "tape_head2"]
+    pub head_2: BoolParam,
+    #[id = "tape_head3"]
+    pub head_3: BoolParam,
+}
+
+impl TapeEchoParams {
+    pub fn new(response: &Response) -> Self {
+        Self {
+            enabled: bool_param("Tape Echo", false),
+            repeat_rate: float_param(
+                "Repeat Rate",
+                150.,
                 FloatRange::Skewed {
-                    min: 0.,
+                    min: 20.,
                     max: 1000.,
                     factor: 0.5,
                 },
             )
-            .with_smoother(SmoothingStyle::Linear(50.0))
+            .with_smoother(response.style())
             .with_unit(" ms")
             .with_value_to_string(formatters::v2s_f32_rounded(1)),
+            head_1: bool_param("Head 1", true),
+            head_2: bool_param("Head 2", false),
+            head_3: bool_param("Head 3", false),
+        }
+    }
+
+    /// Which of the playback heads are on
+    pub fn heads(&self) -> [bool; NUM_HEADS] {
+        [
+            self.head_1.value(),
+            self.head_2.value(),
+            self.head_3.value(),
+        ]
+    }
+}
+
+/// The controls of the string mode, which turns the delay into a Karplus-Strong string that's plucked by the attacks of the input
+#[derive(Params)]
+pub struct StringParams {
+    /// Tune the delay lines to the pitch and pluck them with noise, note tracking still sets the pitch
+    #[id = "string_mode"]
+    pub enabled: BoolParam,
+    /// The pitch of the string in Hz, note names can be typed in as well
+    #[id = "string_pitch"]
+    pub pitch: FloatParam,
+    /// The time in seconds the string takes to fall by 60 dB
+    #[id = "string_decay"]
+    pub decay: FloatParam,
+    /// How much of the high harmonics the string keeps on every pass
+    #[id = "string_brightness"]
+    pub brightness: FloatParam,
+}
 
-            feedback_l: FloatParam::new("Feedback", 0.5, FloatRange::Linear { min: 0., max: 1. })
-                .with_smoother(SmoothingStyle::Linear(50.0))
-                .with_value_to_string(formatters::v2s_f32_rounded(2)),
-            feedback_r: FloatParam::new(
-                "Feedback Channel 2",
+impl StringParams {
+    pub fn new(response: &Response) -> Self {
+        Self {
+            enabled: bool_param("String Mode", false),
+            pitch: float_param(
+                "String Pitch",
+                110.,
+                FloatRange::Skewed {
+                    min: 20.,
+                    max: 2000.,
+                    factor: FloatRange::skew_factor(-2.),
+                },
+            )
+            .with_smoother(response.logarithmic_style())
+            .with_value_to_string(formatters::v2s_f32_hz_then_khz_with_note_name(1, false))
+            .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
+            decay: float_param(
+                "String Decay",
+                2.,
+                FloatRange::Skewed {
+                    min: 0.05,
+                    max: 20.,
+                    factor: FloatRange::skew_factor(-2.),
+                },
+            )
+            .with_unit(" s")
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+            brightness: float_param(
+                "String Brightness",
                 0.5,
                 FloatRange::Linear { min: 0., max: 1. },
             )
-            .with_smoother(SmoothingStyle::Linear(50.0))
+            .with_smoother(response.style())
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+        }
+    }
+
+    /// Snap all the smoothers to their current values
+    pub fn reset_smoothers(&self) {
+        for param in [&self.pitch, &self.brightness] {
+            param.smoothed.reset(param.value());
+        }
+    }
+}
+
+/// The controls of the second delay stage, which runs after or next to the main delay of every channel
+#[derive(Params)]
+pub struct DualDelayParams {
+    /// Run the second stage, it starts out empty every time it's turned on
+    #[id = "dual_delay"]
+    pub enabled: BoolParam,
+    #[id = "dual_routing"]
+    pub routing: EnumParam<DualRouting>,
+    /// The delay time of the second stage, independent of the delay times of the channels
+    #[id = "dual_time"]
+    pub time: FloatParam,
+    /// The feedback of the second stage, its loop has no filters
+    #[id = "dual_feedback"]
+    pub feedback: FloatParam,
+    /// From only the main delay at 0 to only the second stage at 1
+    #[id = "dual_balance"]
+    pub balance: FloatParam,
+}
+
+impl DualDelayParams {
+    pub fn new(response: &Response) -> Self {
+        Self {
+            enabled: bool_param("Dual Delay", false),
+            routing: enum_param("Dual Routing", DualRouting::Serial),
+            time: float_param(
+                "Dual Time",
+                90.,
+                FloatRange::Skewed {
+                    min: 1.,
+                    max: MAX_DUAL_MS,
+                    factor: FloatRange::skew_factor(-1.5),
+                },
+            )
+            .with_smoother(response.style())
+            .with_unit(" ms")
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
+            feedback: float_param(
+                "Dual Feedback",
+                0.2,
+                FloatRange::Linear { min: 0., max: 1. },
+            )
+            .with_smoother(response.style())
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            balance: float_param("Dual Balance", 0.5, FloatRange::Linear { min: 0., max: 1. })
+                .with_smoother(response.style())
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+        }
+    }
+
+    /// Snap all the smoothers to their current values
+    pub fn reset_smoothers(&self) {
+        for param in [&self.time, &self.feedback, &self.balance] {
+            param.smoothed.reset(param.value());
+        }
+    }
+}
+
+/// The controls of the loop region, which captures a slice of the delay lines and repeats it like a beat repeat
+#[derive(Params)]
+pub struct LoopParams {
+    /// Capture the slice when this is turned on, and go back to the delay when it's turned off
+    #[id = "loop_region"]
+    pub enabled: BoolParam,
+    /// How far back from the newest sample the slice starts, in ms
+    #[id = "loop_start"]
+    pub start: FloatParam,
+    /// How long the slice is, it can't be longer than the start
+    #[id = "loop_length"]
+    pub length: FloatParam,
+    /// Read the start and the length as note lengths at the host tempo, like BPM bound delay times
+    #[id = "loop_bpm_bound"]
+    pub bpm_bound: BoolParam,
+    #[id = "loop_input"]
+    pub input: EnumParam<LoopInputMode>,
+}
+
+impl LoopParams {
+    pub fn new(changes: &ParamChanges) -> Self {
+        let time = |name, default| {
+            float_param(
+                name,
+                default,
+                FloatRange::Skewed {
+                    min: 10.,
+                    max: MAX_DELAY_MS,
+                    factor: 0.5,
+                },
+            )
+            .with_callback(changes.callback(ParamTopic::LoopRegion))
+            .with_unit(" ms")
+            .with_value_to_string(formatters::v2s_f32_rounded(1))
+        };
+
+        Self {
+            enabled: bool_param("Loop", false),
+            start: time("Loop Start", 500.),
+            length: time("Loop Length", 250.),
+            bpm_bound: bool_param("Loop BPM Bound", false)
+                .with_callback(changes.callback(ParamTopic::LoopRegion)),
+            input: enum_param("Loop Input", LoopInputMode::Ignore),
+        }
+    }
+}
+
+/// The controls of the dub throw, which ramps the delay time and the feedback to their own targets while it's held
+#[derive(Params)]
+pub struct ThrowParams {
+    /// Ramp towards the targets while this is on and back once it's off, meant for a button, footswitches and automation
+    #[id = "throw"]
+    pub engaged: BoolParam,
+    /// The delay time the throw ramps to, in ms
+    #[id = "throw_time"]
+    pub time: FloatParam,
+    /// The feedback the throw ramps to
+    #[id = "throw_feedback"]
+    pub feedback: FloatParam,
+    /// How long the ramps towards the targets and back take, in ms
+    #[id = "throw_ramp"]
+    pub ramp: FloatParam,
+}
+
+impl ThrowParams {
+    pub fn new(response: &Response) -> Self {
+        Self {
+            engaged: bool_param("Throw", false),
+            time: float_param(
+                "Throw Time",
+                1000.,
+                FloatRange::Skewed {
+                    min: 1.,
+                    max: MAX_DELAY_MS,
+                    factor: 0.5,
+                },
+            )
+            .with_smoother(response.style())
+            .with_unit(" ms")
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
+            feedback: float_param(
+                "Throw Feedback",
+                0.9,
+                FloatRange::Linear { min: 0., max: 1. },
+            )
+            .with_smoother(response.style())
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            ramp: float_param(
+                "Throw Ramp",
+                500.,
+                FloatRange::Skewed {
+                    min: MIN_THROW_RAMP_MS,
+                    max: MAX_THROW_RAMP_MS,
+                    factor: FloatRange::skew_factor(-1.5),
+                },
+            )
+            .with_unit(" ms")
+            .with_value_to_string(formatters::v2s_f32_rounded(0)),
+        }
+    }
+
+    /// Snap all the smoothers to their current values
+    pub fn reset_smoothers(&self) {
+        for param in [&self.time, &self.feedback] {
+            param.smoothed.reset(param.value());
+        }
+    }
+}
+
+/// The performance buttons, which change how the repeats are played back only while they're held
+#[derive(Params)]
+pub struct PerformParams {
+    /// Play the repeats backwards, a slice of the delay time at a time, see [super::reverse::ReverseReader]
+    #[id = "perform_reverse"]
+    pub reverse: BoolParam,
+    /// Halve the read speed, which drops the repeats by an octave
+    #[id = "perform_half_speed"]
+    pub half_speed: BoolParam,
+}
+
+impl Default for PerformParams {
+    fn default() -> Self {
+        Self {
+            reverse: bool_param("Reverse", false),
+            half_speed: bool_param("Half Speed", false),
+        }
+    }
+}
+
+impl PerformParams {
+    /// The read speed with the half speed button applied
+    pub fn read_speed(&self, speed: f32) -> f32 {
+        if self.half_speed.value() {
+            speed * 0.5
+        } else {
+            speed
+        }
+    }
+}
+
+#[derive(Params)]
+pub struct EngineParams {
+    #[nested]
+    pub channels: Channels<ChannelDelayParams>,
+    #[id = "stereo"]
+    pub stereo_delay: EnumParam<DelayMode>,
+    /// Let incoming MIDI notes set the delay time to their period
+    #[id = "note_tracking"]
+    pub note_tracking: BoolParam,
+    /// How long the delay time takes to glide to a new note
+    #[id = "note_glide"]
+    pub note_glide: FloatParam,
+    /// Momentarily push the feedback of all channels to 1 while it's on, meant for footswitches and automation
+    #[id = "hold"]
+    pub hold: BoolParam,
+    /// Vary the time and level of every repeat a little, like a player or an old machine would
+    #[id = "humanize"]
+    pub humanize: FloatParam,
+    /// How fast the left and the right repeats drift apart and back, in Hz
+    #[id = "drift_rate"]
+    pub drift_rate: FloatParam,
+    /// How far the left and the right repeats drift apart, in ms to either side
+    #[id = "drift_depth"]
+    pub drift_depth: FloatParam,
+    /// When the drift starts its cycle over, so it moves the same way in every render
+    #[id = "drift_retrigger"]
+    pub drift_retrigger: EnumParam<RetriggerMode>,
+    /// How fast the delay lines are played back compared to how fast they're written, which shifts the pitch of the repeats
+    #[id = "read_speed"]
+    pub read_speed: FloatParam,
+    /// Fine tunes the read speed in semitones
+    #[id = "read_detune"]
+    pub read_detune: FloatParam,
+    /// Whether the feedback is set by the feedback knobs or by the decay time of the channels
+    #[id = "feedback_mode"]
+    pub feedback_mode: EnumParam<FeedbackMode>,
+    /// Flip the polarity of the feedback. With delays in the flange zone this moves the comb from the harmonics to the odd harmonics.
+    #[id = "invert_feedback"]
+    pub invert_feedback: BoolParam,
+    /// Scale the feedback back whenever the feedback loop would build up into self oscillation
+    #[id = "stability_trim"]
+    pub stability_trim: BoolParam,
+    /// How much of the feedback comes back from the aux return instead of the internal feedback path.
+    /// The feedback is always sent to the aux send, so external plugins can be inserted into the loop.
+    #[id = "external_return"]
+    pub external_return: FloatParam,
+    /// Exchange the left and right channel of the wet signal
+    #[id = "swap_channels"]
+    pub swap_channels: BoolParam,
+    /// The stereo width of the wet signal. Below 100% every wet channel bleeds into the other one,
+    /// which narrows wide ping-pong results down to mono at 0%.
+    #[id = "wet_width"]
+    pub width: FloatParam,
+    /// How different the allpass chains are that decorrelate the left and the right wet channel
+    #[id = "wet_spread"]
+    pub spread: FloatParam,
+    /// Bounce the repeats between the left and the right channel
+    #[id = "ping_pong"]
+    pub ping_pong: BoolParam,
+    #[id = "ping_pong_start"]
+    pub ping_pong_start: EnumParam<PingPongStart>,
+    /// How far the first repeat is panned from its start side towards the center
+    #[id = "ping_pong_offset"]
+    pub ping_pong_offset: FloatParam,
+    #[id = "stop_behavior"]
+    pub stop_behavior: EnumParam<StopBehavior>,
+    #[id = "retrigger"]
+    pub retrigger: EnumParam<RetriggerMode>,
+    #[id = "tempo_transition"]
+    pub tempo_transition: EnumParam<TempoTransition>,
+    /// Snap free delay times to a grid of 5 ms below 100 ms and 10 ms above, see [super::snap::snap_delay_ms()]
+    #[id = "delay_snap"]
+    pub snap: BoolParam,
+    #[nested(group = "Tape Echo")]
+    pub tape_echo: TapeEchoParams,
+    #[nested(group = "String")]
+    pub string: StringParams,
+    #[nested(group = "Loop")]
+    pub loop_region: LoopParams,
+    #[nested(group = "Dual Delay")]
+    pub dual: DualDelayParams,
+    #[nested(group = "Throw")]
+    pub throw: ThrowParams,
+    #[nested(group = "Perform")]
+    pub perform: PerformParams,
+}
+
+impl EngineParams {
+    pub fn new(response: &Response, changes: &ParamChanges) -> Self {
+        Self {
+            channels: Channels(std::array::from_fn(|channel| {
+                ChannelDelayParams::new(channel, response, changes)
+            })),
+            stereo_delay: enum_param("Seperate Delay", DelayMode::Mono)
+                .with_callback(changes.callback(ParamTopic::DelayTimes)),
+            note_tracking: bool_param("Note Tracking", false),
+            note_glide: float_param(
+                "Note Glide",
+                50.,
+                FloatRange::Skewed {
+                    min: 0.,
+                    max: 2000.,
+                    factor: 0.5,
+                },
+            )
+            .with_unit(" ms")
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
+            hold: bool_param("Hold", false),
+            humanize: float_param("Humanize", 0., FloatRange::Linear { min: 0., max: 1. })
+                .with_smoother(response.style())
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            drift_rate: float_param(
+                "Drift Rate",
+                0.2,
+                FloatRange::Skewed {
+                    min: 0.01,
+                    max: 2.,
+                    factor: FloatRange::skew_factor(-2.),
+                },
+            )
+            .with_unit(" Hz")
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+            drift_depth: float_param(
+                "Drift Depth",
+                0.,
+                FloatRange::Linear {
+                    min: 0.,
+                    max: MAX_DRIFT_MS,
+                },
+            )
+            .with_smoother(response.style())
+            .with_unit(" ms")
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+            drift_retrigger: enum_param("Drift Retrigger", RetriggerMode::Off),
+            read_speed: float_param(
+                "Read Speed",
+                1.,
+                FloatRange::SymmetricalSkewed {
+                    min: MIN_READ_SPEED,
+                    max: MAX_READ_SPEED,
+                    factor: FloatRange::skew_factor(-1.),
+                    center: 1.,
+                },
+            )
+            .with_smoother(response.logarithmic_style())
+            .with_unit("x")
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+            read_detune: float_param(
+                "Read Detune",
+                0.,
+                FloatRange::Linear {
+                    min: -12.,
+                    max: 12.,
+                },
+            )
+            .with_step_size(0.01)
+            .with_smoother(response.style())
+            .with_unit(" st")
             .with_value_to_string(formatters::v2s_f32_rounded(2)),
-            stereo_delay: EnumParam::new("Seperate Delay", DelayMode::Mono),
-            bpm_bound_l: BoolParam::new("BPM Bound", false),
-            bpm_bound_r: BoolParam::new("BPM Bound Channel 2", false),
+            feedback_mode: enum_param("Feedback Mode", FeedbackMode::Ratio)
+                .with_callback(changes.callback(ParamTopic::DelayTimes)),
+            invert_feedback: bool_param("Invert Feedback", false),
+            stability_trim: bool_param("Stability Trim", false),
+            external_return: float_param(
+                "External Return",
+                0.,
+                FloatRange::Linear { min: 0., max: 1. },
+            )
+            .with_smoother(response.style())
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            swap_channels: bool_param("Swap Channels", false),
+            width: float_param("Wet Width", 1., FloatRange::Linear { min: 0., max: 1. })
+                .with_smoother(response.style())
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            spread: float_param("Wet Spread", 0., FloatRange::Linear { min: 0., max: 1. })
+                .with_smoother(response.style())
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            ping_pong: bool_param("Ping Pong", false),
+            ping_pong_start: enum_param("First Repeat", PingPongStart::Left),
+            ping_pong_offset: float_param("Start Pan", 0., FloatRange::Linear { min: 0., max: 1. })
+                .with_smoother(response.style())
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            stop_behavior: enum_param("On Stop", StopBehavior::Continue),
+            retrigger: enum_param("Retrigger", RetriggerMode::Off),
+            tempo_transition: enum_param("Tempo Change", TempoTransition::Crossfade),
+            snap: bool_param("Snap Delay", false),
+            tape_echo: TapeEchoParams::new(response),
+            string: StringParams::new(response),
+            loop_region: LoopParams::new(changes),
+            dual: DualDelayParams::new(response),
+            throw: ThrowParams::new(response),
+            perform: PerformParams::default(),
+        }
+    }
+
+    /// Get the next smoothed value of a parameter for every channel.
+    ///
+    /// `stereo` is how far the switch to [DelayMode::Stereo] is, see [Channels::next_blended()].
+    pub fn next_per_channel(
+        &self,
+        param: impl Fn(&ChannelDelayParams) -> &FloatParam,
+        stereo: f32,
+    ) -> [f32; NUM_CHANNELS] {
+        self.channels.next_blended(param, stereo)
+    }
+
+    /// Whether the delay time of every channel is BPM bound. In mono mode all channels follow the first channel.
+    pub fn synced_per_channel(&self) -> [bool; NUM_CHANNELS] {
+        match self.stereo_delay.value() {
+            DelayMode::Mono => [self.channels[0].bpm_bound.value(); NUM_CHANNELS],
+            DelayMode::Stereo => {
+                std::array::from_fn(|channel| self.channels[channel].bpm_bound.value())
+            }
+        }
+    }
+
+    /// The gains that flip the polarity of the wet signal of the channels that are inverted.
+    /// Unlike the other channel parameters these also apply separately in mono mode.
+    pub fn polarity(&self) -> [f32; NUM_CHANNELS] {
+        std::array::from_fn(|channel| {
+            if self.channels[channel].invert.value() {
+                -1.
+            } else {
+                1.
+            }
+        })
+    }
+
+    /// Snap all the smoothers to their current values
+    pub fn reset_smoothers(&self) {
+        self.channels
+            .iter()
+            .for_each(ChannelDelayParams::reset_smoothers);
+        self.width.smoothed.reset(self.width.value());
+        self.spread.smoothed.reset(self.spread.value());
+        self.humanize.smoothed.reset(self.humanize.value());
+        self.drift_depth.smoothed.reset(self.drift_depth.value());
+        self.read_speed.smoothed.reset(self.read_speed.value());
+        self.read_detune.smoothed.reset(self.read_detune.value());
+        self.external_return
+            .smoothed
+            .reset(self.external_return.value());
+        self.ping_pong_offset
+            .smoothed
+            .reset(self.ping_pong_offset.value());
+        let repeat_rate = &self.tape_echo.repeat_rate;
+        repeat_rate.smoothed.reset(repeat_rate.value());
+        self.string.reset_smoothers();
+        self.dual.reset_smoothers();
+        self.throw.reset_smoothers();
+    }
+}
+
+/// The amount of each wet channel that's mixed into the other one for a width.
+///
+/// Usage:
+/// ```
+/// use delax::delay_engine::params::width_crossfeed;
+///
+/// // Full width keeps the channels apart, no width mixes them down to mono
+/// assert_eq!(width_crossfeed(1.), 0.);
+/// assert_eq!(width_crossfeed(0.), 0.5);
+/// ```
+pub fn width_crossfeed(width: f32) -> f32 {
+    (1. - width.clamp(0., 1.)) * 0.5
+}
+
+/// The names of the channels, which the hosts show as groups of the per channel parameters
+pub const CHANNEL_NAMES: [&str; NUM_CHANNELS] = ["Left", "Right"];
+
+/// The suffix that's added to parameter names to tell the channels apart, for hosts that show a flat list.
+pub fn channel_name_suffix(channel: usize) -> String {
+    format!(" {}", &CHANNEL_NAMES[channel][..1])
+}
+
+/// The parameters of every channel, grouped by the channel name so hosts show them as "Delay / Left / Delay L".
+///
+/// This does what `#[nested(array)]` does, but names the groups after the channels instead of numbering them.
+/// The IDs still end in the channel number starting at 1, so automation and state saved with the numbered
+/// groups keep mapping to the same parameters.
+pub struct Channels<P>(pub [P; NUM_CHANNELS]);
+
+impl<P> Deref for Channels<P> {
+    type Target = [P; NUM_CHANNELS];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<P> Channels<P> {
+    /// Get the next smoothed value of a parameter for every channel, faded between the first channel and the
+    /// channels' own values.
+    ///
+    /// At a `stereo` of 0 all channels follow the first channel, at 1 every channel has its own value. Switching
+    /// between mono and stereo fades over the values in between, instead of jumping to the values of the other channels.
+    ///
+    /// The smoothers of all channels are advanced either way. An unused smoother would otherwise still be on its way
+    /// to the value it had when the mode switched to mono, and catch up in a burst once it's used again.
+    pub fn next_blended(
+        &self,
+        param: impl Fn(&P) -> &FloatParam,
+        stereo: f32,
+    ) -> [f32; NUM_CHANNELS] {
+        let values: [f32; NUM_CHANNELS] =
+            std::array::from_fn(|channel| param(&self.0[channel]).smoothed.next());
+        values.map(|value| values[0] + (value - values[0]) * stereo.clamp(0., 1.))
+    }
+}
+
+/// The ID of a parameter on a channel
+fn channel_id(id: &str, channel: usize) -> String {
+    format!("{id}_{}", channel + 1)
+}
+
+unsafe impl<P: Params> Params for Channels<P> {
+    fn param_map(&self) -> Vec<(String, ParamPtr, String)> {
+        self.0
+            .iter()
+            .zip(CHANNEL_NAMES)
+            .enumerate()
+            .flat_map(|(channel, (params, name))| {
+                params
+                    .param_map()
+                    .into_iter()
+                    .map(move |(id, param, group)| {
+                        let group = if group.is_empty() {
+                            name.to_string()
+                        } else {
+                            format!("{name}/{group}")
+                        };
+                        (channel_id(&id, channel), param, group)
+                    })
+            })
+            .collect()
+    }
+
+    fn serialize_fields(&self) -> BTreeMap<String, String> {
+        self.0
+            .iter()
+            .enumerate()
+            .flat_map(|(channel, params)| {
+                params
+                    .serialize_fields()
+                    .into_iter()
+                    .map(move |(key, value)| (channel_id(&key, channel), value))
+            })
+            .collect()
+    }
+
+    fn deserialize_fields(&self, serialized: &BTreeMap<String, String>) {
+        for (channel, params) in self.0.iter().enumerate() {
+            let suffix = format!("_{}", channel + 1);
+            let fields = serialized
+                .iter()
+                .filter_map(|(key, value)| {
+                    Some((key.strip_suffix(&suffix)?.to_string(), value.clone()))
+                })
+                .collect();
+            params.deserialize_fields(&fields);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct GainParams {
+        gain: FloatParam,
+    }
+
+    unsafe impl Params for GainParams {
+        fn param_map(&self) -> Vec<(String, ParamPtr, String)> {
+            vec![(String::from("gain"), self.gain.as_ptr(), String::new())]
         }
     }
+
+    #[test]
+    fn channels_fade_from_the_first_channel() {
+        let channels = Channels(std::array::from_fn(|_| GainParams {
+            gain: FloatParam::new("Gain", 0., FloatRange::Linear { min: 0., max: 1. }),
+        }));
+        channels[0].gain.smoothed.reset(0.2);
+        channels[1].gain.smoothed.reset(0.6);
+
+        assert_eq!(channels.next_blended(|params| &params.gain, 0.), [0.2, 0.2]);
+        assert_eq!(channels.next_blended(|params| &params.gain, 1.), [0.2, 0.6]);
+        let halfway = channels.next_blended(|params| &params.gain, 0.5);
+        assert_eq!(halfway[0], 0.2);
+        assert!((halfway[1] - 0.4).abs() < 1e-6);
+    }
+
+    #[test]
+    fn unused_channels_keep_smoothing_in_mono() {
+        let channels = Channels(std::array::from_fn(|_| GainParams {
+            gain: FloatParam::new("Gain", 0., FloatRange::Linear { min: 0., max: 1. })
+                .with_smoother(SmoothingStyle::Linear(10.)),
+        }));
+        // The right channel was turned up right before switching to mono
+        channels[1].gain.smoothed.set_target(1000., 1.);
+        for _ in 0..100 {
+            assert_eq!(channels.next_blended(|params| &params.gain, 0.), [0., 0.]);
+        }
+
+        // Back in stereo the right channel is already where it was set, instead of sweeping up from 0
+        assert_eq!(channels.next_blended(|params| &params.gain, 1.), [0., 1.]);
+    }
+
+    #[test]
+    fn decay_times_hold_for_any_delay_time() {
+        for delay_ms in [10., 250., 1000.] {
+            let gain = FeedbackMode::DecayTime.feedback_gain(0.5, 3., delay_ms);
+            let repeats = 3000. / delay_ms;
+            assert!(
+                (20. * gain.powf(repeats).log10() + 60.).abs() < 1e-2,
+                "{delay_ms}"
+            );
+        }
+        // Longer delays fit fewer repeats into the tail, so each of them falls further
+        let gain = |delay_ms| FeedbackMode::DecayTime.feedback_gain(0.5, 3., delay_ms);
+        assert!(gain(400.) < gain(100.));
+        // A zero delay doesn't hold the repeats forever
+        assert!(gain(0.) < 1.);
+    }
+
+    #[test]
+    fn channels_are_grouped_by_name_with_numbered_ids() {
+        let channels = Channels(std::array::from_fn(|channel| GainParams {
+            gain: FloatParam::new(
+                format!("Gain{}", channel_name_suffix(channel)),
+                0.,
+                FloatRange::Linear { min: 0., max: 1. },
+            ),
+        }));
+
+        let map: Vec<_> = channels
+            .param_map()
+            .into_iter()
+            .map(|(id, _, group)| (id, group))
+            .collect();
+        assert_eq!(
+            map,
+            vec![
+                (String::from("gain_1"), String::from("Left")),
+                (String::from("gain_2"), String::from("Right")),
+            ]
+        );
+        assert_eq!(channels[1].gain.name(), "Gain R");
+    }
 }
@@ -0,0 +1,95 @@
+use std::f32::consts::PI;
+
+use super::engine::DelayEngine;
+
+/// Plays the repeats of a delay line backwards, one slice of the delay time at a time.
+///
+/// The slice is read by two taps half a slice apart. Every sample they move a sample further back in time while the
+/// delay line moves a sample forward, so the buffer passes them backwards at the normal speed. A tap jumps back to the
+/// delay time at the end of the slice, where a raised sine has faded it out, and the two taps always add up to the
+/// full level. The delay line itself keeps running forwards, so the normal repeats are there again once it's released.
+///
+/// Usage:
+/// ```
+/// use delax::delay_engine::{engine::DelayEngine, reverse::ReverseReader};
+///
+/// let mut engine = DelayEngine::new(1000, 1000.);
+/// engine.set_delay_amount(100.);
+/// for sample in 0..400 {
+///     engine.write_sample(sample as f32);
+/// }
+///
+/// // The samples come out in the opposite order they were written in
+/// let mut reader = ReverseReader::default();
+/// let first = reader.next(&engine);
+/// engine.write_sample(400.);
+/// assert!(reader.next(&engine) < first);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ReverseReader {
+    /// How far the first tap is into the slice, in samples
+    phase: usize,
+}
+
+impl ReverseReader {
+    /// The next reversed sample of the delay line, moving the taps on by a sample.
+    /// This needs to be called once for every sample that's written to the delay line.
+    pub fn next(&mut self, delay_engine: &DelayEngine) -> f32 {
+        let delay = delay_engine.delay_samples().max(1);
+        // The taps reach back up to two slices behind the delay time, which has to fit into the buffer
+        let longest = delay_engine.get_buffer_ptr().len().saturating_sub(1);
+        let slice = delay.min(longest.saturating_sub(delay) / 2);
+        if slice < 2 {
+            return delay_engine.history_sample(delay);
+        }
+
+        self.phase %= slice;
+        let read = [self.phase, (self.phase + slice / 2) % slice]
+            .into_iter()
+            .map(|phase| {
+                let gain = (PI * phase as f32 / slice as f32).sin();
+                delay_engine.history_sample(delay + phase * 2) * gain * gain
+            })
+            .sum();
+        self.phase += 1;
+
+        read
+    }
+
+    /// Start the next reversed slice at the delay time
+    pub fn reset(&mut self) {
+        self.phase = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn constant_signals_keep_their_level() {
+        let mut engine = DelayEngine::new(1000, 1000.);
+        engine.set_delay_amount(100.);
+        let mut reader = ReverseReader::default();
+        for _ in 0..1000 {
+            engine.write_sample(0.5);
+        }
+
+        for _ in 0..300 {
+            assert!((reader.next(&engine) - 0.5).abs() < 1e-5);
+            engine.write_sample(0.5);
+        }
+    }
+
+    #[test]
+    fn delays_that_leave_no_room_are_read_forwards() {
+        let mut engine = DelayEngine::new(100, 1000.);
+        engine.set_delay_amount(98.);
+        for sample in 0..100 {
+            engine.write_sample(sample as f32);
+        }
+
+        let mut reader = ReverseReader::default();
+        assert_eq!(reader.next(&engine), engine.history_sample(98));
+    }
+}
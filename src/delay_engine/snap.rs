@@ -0,0 +1,105 @@
+use super::params::{EngineParams, NUM_CHANNELS};
+
+/// The grid of free delay times below [COARSE_FROM_MS], in ms
+pub const FINE_STEP_MS: f32 = 5.;
+/// The grid of free delay times from [COARSE_FROM_MS] on, in ms
+pub const COARSE_STEP_MS: f32 = 10.;
+/// The delay time in ms where the grid gets coarser
+pub const COARSE_FROM_MS: f32 = 100.;
+
+/// Snap a free delay time to the closest step of the grid.
+///
+/// Short delays are heard as slapback and doubling, so they get the finer grid.
+///
+/// Usage:
+/// ```
+/// use delax::delay_engine::snap::snap_delay_ms;
+///
+/// assert_eq!(snap_delay_ms(42.), 40.);
+/// assert_eq!(snap_delay_ms(347.), 350.);
+/// ```
+pub fn snap_delay_ms(len_ms: f32) -> f32 {
+    let step = if len_ms < COARSE_FROM_MS {
+        FINE_STEP_MS
+    } else {
+        COARSE_STEP_MS
+    };
+
+    (len_ms / step).round() * step
+}
+
+/// Snaps the free delay times of the channels while the snap parameter is on.
+///
+/// The knobs already snap while they're moved in the editor, this catches automation and everything else that sets
+/// the delay times. Only the smoothers are moved, the parameters keep the values the host sent.
+/// BPM bound channels are skipped, they're snapped to note lengths instead.
+#[derive(Debug, Default)]
+pub struct DelaySnap {
+    /// The parameter value and the snapped delay time every channel's smoother was last moved to, None if it follows the parameter
+    snapped: [Option<(f32, f32)>; NUM_CHANNELS],
+}
+
+impl DelaySnap {
+    /// Move the smoothers of the delay times to their snapped values, once per block
+    pub fn apply(&mut self, params: &EngineParams, sample_rate: f32) {
+        for (channel, channel_params) in params.channels.iter().enumerate() {
+            let len = &channel_params.len;
+            let snapping = params.snap.value() && !channel_params.bpm_bound.value();
+            if let Some(target) = self.target(channel, len.value(), snapping) {
+                len.smoothed.set_target(sample_rate, target);
+            }
+        }
+    }
+
+    /// The value a channel's smoother needs to be moved to for a delay time in ms, None if it's already there
+    fn target(&mut self, channel: usize, len_ms: f32, snapping: bool) -> Option<f32> {
+        let snapped = snapping.then(|| (len_ms, snap_delay_ms(len_ms)));
+        // Setting the same target again would restart the ramp, so only changes are passed on. Every new parameter
+        // value moves the smoother to it, even within the same step of the grid, so then it's snapped again.
+        if snapped == self.snapped[channel] {
+            return None;
+        }
+
+        // Turning the snap off lets the smoother return to the parameter value
+        let was_snapping = self.snapped[channel].is_some();
+        self.snapped[channel] = snapped;
+        match snapped {
+            Some((_, target)) => Some(target),
+            None => was_snapping.then_some(len_ms),
+        }
+    }
+
+    /// Forget the snapped values, after the smoothers were reset to the parameter values
+    pub fn reset(&mut self) {
+        self.snapped = [None; NUM_CHANNELS];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_gets_coarser_above_100_ms() {
+        assert_eq!(snap_delay_ms(0.), 0.);
+        assert_eq!(snap_delay_ms(2.4), 0.);
+        assert_eq!(snap_delay_ms(97.6), 100.);
+        assert_eq!(snap_delay_ms(104.), 100.);
+        assert_eq!(snap_delay_ms(106.), 110.);
+        assert_eq!(snap_delay_ms(995.), 1000.);
+    }
+
+    #[test]
+    fn automation_within_a_step_is_snapped_again() {
+        let mut snap = DelaySnap::default();
+        assert_eq!(snap.target(0, 347., true), Some(350.));
+        assert_eq!(snap.target(0, 347., true), None);
+        // The host moved the smoother to 348 ms, so it's pulled back to the grid
+        assert_eq!(snap.target(0, 348., true), Some(350.));
+        assert_eq!(snap.target(1, 348., false), None);
+
+        // Without the snap the smoother goes back to the parameter and is left to it
+        assert_eq!(snap.target(0, 348., false), Some(348.));
+        assert_eq!(snap.target(0, 349., false), None);
+    }
+}
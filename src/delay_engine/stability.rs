@@ -0,0 +1,138 @@
+/// Loop gains above this grow on every pass through the feedback loop instead of decaying
+pub const UNSTABLE_LOOP_GAIN: f32 = 1.;
+/// The level below which a tail counts as gone, like the -60 dB of the RT60 of a room
+pub const SILENCE_DB: f32 = -60.;
+
+/// How many passes through the feedback loop it takes the repeats to fall [SILENCE_DB] below the first one,
+/// for the gain of one pass. `None` if they never fall that far.
+///
+/// Usage:
+/// ```
+/// use delax::delay_engine::stability::repeats_until_silent;
+///
+/// // Every pass takes 6 dB off, so after ten of them the repeats are 60 dB down
+/// assert_eq!(repeats_until_silent(0.5), Some(10));
+/// assert_eq!(repeats_until_silent(0.), Some(0));
+/// // A held loop rings on forever
+/// assert_eq!(repeats_until_silent(1.), None);
+/// ```
+pub fn repeats_until_silent(loop_gain: f32) -> Option<u32> {
+    let loop_gain = loop_gain.abs();
+    if loop_gain >= UNSTABLE_LOOP_GAIN {
+        None
+    } else if loop_gain <= 0. {
+        Some(0)
+    } else {
+        // A little slack keeps gains that land right on a whole number of passes from rounding up
+        let passes = SILENCE_DB / (20. * loop_gain.log10());
+        Some((passes - 1e-4).ceil() as u32)
+    }
+}
+
+/// Everything that amplifies the repeats of one channel on their way through the feedback loop.
+///
+/// There's no saturation in the loop, so nothing pulls a loop that's louder than 1 back down before the
+/// safety clipper on the output. This is an estimate from the settings, not a measurement.
+///
+/// Usage:
+/// ```
+/// use delax::delay_engine::stability::LoopGain;
+///
+/// let resonant = LoopGain {
+///     feedback: 0.8,
+///     routing: 1.,
+///     filter_peak: 2.,
+///     filter_mix: 1.,
+/// };
+/// assert!(resonant.unstable());
+/// // The trim scales the feedback back until the repeats neither grow nor decay
+/// assert_eq!(resonant.trim(), 1. / 1.6);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoopGain {
+    /// The feedback of the channel, including the hold
+    pub feedback: f32,
+    /// The largest gain the routing between the channels adds
+    pub routing: f32,
+    /// The highest gain of the feedback filters, at their resonance
+    pub filter_peak: f32,
+    /// How much of the feedback goes through the filters
+    pub filter_mix: f32,
+}
+
+impl LoopGain {
+    /// The gain of one pass in the worst case, where the filtered and the unfiltered feedback add up in phase
+    pub fn estimate(&self) -> f32 {
+        let filter_gain = 1. - self.filter_mix + self.filter_mix * self.filter_peak;
+        self.feedback.abs() * self.routing * filter_gain
+    }
+
+    /// Whether the repeats are likely to build up into self oscillation
+    pub fn unstable(&self) -> bool {
+        self.estimate() > UNSTABLE_LOOP_GAIN
+    }
+
+    /// The factor that brings the feedback of an unstable loop back to [UNSTABLE_LOOP_GAIN], 1 for stable loops.
+    ///
+    /// A held loop sits right at that gain, so holding still rings on forever with the trim turned on.
+    pub fn trim(&self) -> f32 {
+        let estimate = self.estimate();
+        if estimate > UNSTABLE_LOOP_GAIN {
+            UNSTABLE_LOOP_GAIN / estimate
+        } else {
+            1.
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unfiltered_loops_follow_the_feedback() {
+        let loop_gain = LoopGain {
+            feedback: 0.7,
+            routing: 1.,
+            filter_peak: 4.,
+            filter_mix: 0.,
+        };
+        assert_eq!(loop_gain.estimate(), 0.7);
+        assert!(!loop_gain.unstable());
+        assert_eq!(loop_gain.trim(), 1.);
+
+        // Holding keeps the repeats at their level
+        let held = LoopGain {
+            feedback: 1.,
+            ..loop_gain
+        };
+        assert!(!held.unstable());
+        assert_eq!(held.trim(), 1.);
+    }
+
+    #[test]
+    fn half_filtered_loops_average_the_gains() {
+        let loop_gain = LoopGain {
+            feedback: -0.8,
+            routing: 1.,
+            filter_peak: 1.6,
+            filter_mix: 0.5,
+        };
+        // Inverted feedback grows just the same
+        assert!((loop_gain.estimate() - 1.04).abs() < 1e-6);
+        assert!(loop_gain.unstable());
+        assert!((loop_gain.trim() * loop_gain.estimate() - 1.).abs() < 1e-6);
+    }
+
+    #[test]
+    fn higher_loop_gains_ring_longer() {
+        let repeats: Vec<u32> = [0.1, 0.5, 0.9, 0.99]
+            .into_iter()
+            .map(|gain| repeats_until_silent(gain).unwrap())
+            .collect();
+        assert_eq!(repeats[0], 3);
+        assert!(repeats.windows(2).all(|pair| pair[0] < pair[1]));
+        // Unstable loops never fall silent either
+        assert_eq!(repeats_until_silent(1.2), None);
+    }
+}
@@ -0,0 +1,201 @@
+use super::{engine::MAX_DELAY_MS, params::TempoTransition};
+
+/// The tempo the delay knob is read at when the delay is BPM bound
+pub const REFERENCE_TEMPO: f32 = 120.;
+/// Tempo changes smaller than this in BPM are treated as jitter and don't re-lock the delay
+pub const RELOCK_THRESHOLD: f32 = 0.05;
+/// How long the transition to a new tempo takes, in ms
+pub const TRANSITION_MS: f32 = 80.;
+
+/// The delay time of a BPM bound delay at a tempo.
+///
+/// The delay knob picks a note length: its time is read at [REFERENCE_TEMPO] and snapped to sixteenth notes,
+/// with a sixteenth as the shortest length. That length is then played at the given tempo,
+/// as long as it fits into the delay lines.
+///
+/// Usage:
+/// ```
+/// use delax::delay_engine::tempo_lock::synced_delay_ms;
+///
+/// // 500 ms is a quarter note at 120 BPM, which is 600 ms at 100 BPM
+/// assert_eq!(synced_delay_ms(500., 100.), 600.);
+/// // 480 ms snaps to the same quarter note
+/// assert_eq!(synced_delay_ms(480., 120.), 500.);
+/// ```
+pub fn synced_delay_ms(len_ms: f32, tempo: f32) -> f32 {
+    let sixteenth_ms = 15000. / REFERENCE_TEMPO;
+    let sixteenths = (len_ms / sixteenth_ms).round().max(1.);
+
+    (sixteenths * 15000. / tempo).min(MAX_DELAY_MS)
+}
+
+/// The delay knob value that gives a BPM bound delay time at a tempo, the inverse of [synced_delay_ms()]
+/// without the snapping.
+pub fn synced_len_ms(delay_ms: f32, tempo: f32) -> f32 {
+    delay_ms * tempo / REFERENCE_TEMPO
+}
+
+/// The tempos to read a BPM bound delay at for one sample, see [TempoLock::next()]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TempoFrame {
+    /// The tempo that's faded out
+    pub from: f32,
+    /// The tempo that's faded in, this is the one the delay is written at
+    pub to: f32,
+    /// How far the fade from `from` to `to` is, 1 once it's done
+    pub mix: f32,
+}
+
+/// Locks BPM bound delays to the host tempo.
+///
+/// The tempo is checked once per block. Small changes are ignored, so jitter of the reported tempo doesn't keep
+/// moving the delay. Bigger changes, like tempo automation, move over to the new tempo smoothly: either by gliding
+/// the delay time, which repitches the repeats, or by crossfading between the old and the new delay time.
+#[derive(Debug)]
+pub struct TempoLock {
+    sample_rate: f32,
+    /// The tempo the delay is locked to
+    locked: f32,
+    /// The tempo the delay was locked to before the current transition
+    previous: f32,
+    /// How far the current transition is, 1 once it's done
+    progress: f32,
+}
+
+impl TempoLock {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            sample_rate,
+            locked: REFERENCE_TEMPO,
+            previous: REFERENCE_TEMPO,
+            progress: 1.,
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+    }
+
+    /// The tempo the delay is locked to
+    pub fn tempo(&self) -> f32 {
+        self.locked
+    }
+
+    /// Check the tempo of the host at the start of a block. Returns true if the delay is re-locked to a new tempo.
+    pub fn update(&mut self, tempo: f32) -> bool {
+        if tempo.is_nan() || tempo <= 0. || (tempo - self.locked).abs() < RELOCK_THRESHOLD {
+            return false;
+        }
+
+        // A transition that's still running continues from where it is right now
+        self.previous = self.previous + (self.locked - self.previous) * self.progress;
+        self.locked = tempo;
+        self.progress = 0.;
+        true
+    }
+
+    /// Advance the transition by one sample and return the tempos to read the delay at
+    pub fn next(&mut self, transition: TempoTransition) -> TempoFrame {
+        if self.progress < 1. {
+            let step = 1000. / (TRANSITION_MS * self.sample_rate);
+            self.progress = (self.progress + step).min(1.);
+        }
+
+        match transition {
+            TempoTransition::Repitch => {
+                let tempo = self.previous + (self.locked - self.previous) * self.progress;
+                TempoFrame {
+                    from: tempo,
+                    to: tempo,
+                    mix: 1.,
+                }
+            }
+            TempoTransition::Crossfade => TempoFrame {
+                from: self.previous,
+                to: self.locked,
+                mix: self.progress,
+            },
+        }
+    }
+
+    /// Jump to the locked tempo, e.g. after the plugin was reset
+    pub fn reset(&mut self) {
+        self.previous = self.locked;
+        self.progress = 1.;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jitter_is_ignored() {
+        let mut lock = TempoLock::new(1000.);
+        assert!(!lock.update(120.01));
+        assert!(!lock.update(119.97));
+        assert_eq!(lock.tempo(), REFERENCE_TEMPO);
+
+        assert!(lock.update(121.));
+        assert_eq!(lock.tempo(), 121.);
+
+        // No tempo from the host keeps the lock
+        assert!(!lock.update(0.));
+        assert_eq!(lock.tempo(), 121.);
+    }
+
+    #[test]
+    fn repitch_glides_to_the_new_tempo() {
+        let mut lock = TempoLock::new(1000.);
+        lock.update(100.);
+
+        let first = lock.next(TempoTransition::Repitch);
+        assert_eq!(first.from, first.to);
+        assert!(first.to < 120. && first.to > 100.);
+
+        let samples = (TRANSITION_MS * 1000. / 1000.) as usize;
+        let mut frame = first;
+        for _ in 0..samples {
+            frame = lock.next(TempoTransition::Repitch);
+        }
+        assert_eq!(frame.to, 100.);
+        assert_eq!(frame.mix, 1.);
+    }
+
+    #[test]
+    fn crossfade_fades_between_the_tempos() {
+        let mut lock = TempoLock::new(1000.);
+        lock.update(140.);
+
+        let first = lock.next(TempoTransition::Crossfade);
+        assert_eq!((first.from, first.to), (120., 140.));
+        assert!(first.mix > 0. && first.mix < 1.);
+
+        for _ in 0..TRANSITION_MS as usize {
+            lock.next(TempoTransition::Crossfade);
+        }
+        assert_eq!(lock.next(TempoTransition::Crossfade).mix, 1.);
+    }
+
+    #[test]
+    fn relock_during_a_transition_continues_from_the_current_tempo() {
+        let mut lock = TempoLock::new(1000.);
+        lock.update(100.);
+        for _ in 0..(TRANSITION_MS / 2.) as usize {
+            lock.next(TempoTransition::Repitch);
+        }
+
+        lock.update(140.);
+        let frame = lock.next(TempoTransition::Repitch);
+        assert!((frame.to - 110.).abs() < 1.);
+    }
+
+    #[test]
+    fn synced_delay_snaps_to_sixteenths() {
+        assert_eq!(synced_delay_ms(0., 120.), 125.);
+        assert_eq!(synced_delay_ms(260., 120.), 250.);
+        assert_eq!(synced_delay_ms(1000., 60.), 2000.);
+        assert_eq!(synced_delay_ms(1000., 10.), MAX_DELAY_MS);
+        assert_eq!(synced_len_ms(synced_delay_ms(750., 90.), 90.), 750.);
+    }
+}
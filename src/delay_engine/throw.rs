@@ -0,0 +1,96 @@
+/// The shortest time in ms the throw takes to ramp in or out
+pub const MIN_THROW_RAMP_MS: f32 = 10.;
+/// The longest time in ms the throw takes to ramp in or out
+pub const MAX_THROW_RAMP_MS: f32 = 5000.;
+
+/// The gesture of a dub throw: while it's engaged the delay time and the feedback ramp towards their throw targets,
+/// once it's released they ramp back to the values of their parameters.
+///
+/// The ramp is linear over the ramp time, so the delay time glides and the repeats bend in pitch like a tape machine
+/// that's sped up or slowed down. It acts on top of the parameters, which keep their values the whole time.
+///
+/// Usage:
+/// ```
+/// use delax::delay_engine::throw::Throw;
+///
+/// let mut throw = Throw::default();
+/// // At 1000 Hz a ramp of 10 ms takes 10 samples
+/// for _ in 0..10 {
+///     throw.next(true, 10., 1000.);
+/// }
+/// assert_eq!(throw.amount(), 1.);
+/// assert_eq!(Throw::apply(throw.amount(), 250., 1000.), 1000.);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Throw {
+    /// How far the values are ramped towards their targets, from 0 to 1
+    amount: f32,
+}
+
+impl Throw {
+    /// The next amount of the throw, ramping towards 1 while it's `engaged` and back to 0 after it's released.
+    /// `ramp_ms` is the time a whole ramp takes.
+    pub fn next(&mut self, engaged: bool, ramp_ms: f32, sample_rate: f32) -> f32 {
+        let step = 1000. / (ramp_ms.max(MIN_THROW_RAMP_MS) * sample_rate);
+        self.amount = if engaged {
+            (self.amount + step).min(1.)
+        } else {
+            (self.amount - step).max(0.)
+        };
+        self.amount
+    }
+
+    /// The amount [Throw::next()] returned last
+    pub fn amount(&self) -> f32 {
+        self.amount
+    }
+
+    /// Whether the throw moves the values at all
+    pub fn active(&self) -> bool {
+        self.amount > 0.
+    }
+
+    /// Jump to being engaged or released without a ramp
+    pub fn reset(&mut self, engaged: bool) {
+        self.amount = if engaged { 1. } else { 0. };
+    }
+
+    /// A value moved towards its throw `target` by an `amount` of the throw
+    pub fn apply(amount: f32, value: f32, target: f32) -> f32 {
+        value + (target - value) * amount
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn throw_ramps_in_and_back_out() {
+        let mut throw = Throw::default();
+        let ramp: Vec<f32> = (0..20).map(|_| throw.next(true, 10., 1000.)).collect();
+        assert!(ramp.windows(2).all(|pair| pair[1] >= pair[0]));
+        assert!((ramp[4] - 0.5).abs() < 1e-6);
+        assert_eq!(ramp[19], 1.);
+
+        // Releasing halfway through the ramp goes back from where it was
+        throw.reset(false);
+        for _ in 0..5 {
+            throw.next(true, 10., 1000.);
+        }
+        let released = throw.next(false, 10., 1000.);
+        assert!((released - 0.4).abs() < 1e-6);
+        for _ in 0..5 {
+            throw.next(false, 10., 1000.);
+        }
+        assert!(!throw.active());
+    }
+
+    #[test]
+    fn short_ramps_are_clamped() {
+        let mut throw = Throw::default();
+        // A ramp of 0 ms would jump, so it takes the shortest ramp instead
+        assert!(throw.next(true, 0., 1000.) < 1.);
+        assert!((Throw::apply(0.5, 0.2, 1.) - 0.6).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,246 @@
+use nih_plug::prelude::Transport;
+
+use super::params::{RetriggerMode, StopBehavior};
+
+/// The state of the host transport at the start of a block, reduced to what the delay needs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TransportInfo {
+    pub playing: bool,
+    /// The amount of samples until the next bar starts, 0 if the block starts right on a bar.
+    /// None if the host doesn't report its position.
+    pub samples_to_next_bar: Option<f64>,
+}
+
+impl TransportInfo {
+    pub fn from_transport(transport: &Transport, sample_rate: f32) -> Self {
+        Self {
+            playing: transport.playing,
+            samples_to_next_bar: Self::next_bar(transport, sample_rate),
+        }
+    }
+
+    fn next_bar(transport: &Transport, sample_rate: f32) -> Option<f64> {
+        let pos_beats = transport.pos_beats()?;
+        let bar_start = transport.bar_start_pos_beats()?;
+        let tempo = transport.tempo?;
+        // The positions are in quarter notes
+        let bar_length =
+            transport.time_sig_numerator? as f64 * 4. / transport.time_sig_denominator? as f64;
+
+        samples_to_next_bar(pos_beats - bar_start, bar_length, tempo, sample_rate)
+    }
+}
+
+/// The amount of samples until the next bar starts.
+///
+/// Usage:
+/// ```
+/// use delax::delay_engine::transport::samples_to_next_bar;
+///
+/// // Half way through a 4/4 bar at 120 BPM there is one second left
+/// assert_eq!(samples_to_next_bar(2., 4., 120., 48000.), Some(48000.));
+/// assert_eq!(samples_to_next_bar(0., 4., 120., 48000.), Some(0.));
+/// ```
+pub fn samples_to_next_bar(
+    beats_into_bar: f64,
+    bar_length: f64,
+    tempo: f64,
+    sample_rate: f32,
+) -> Option<f64> {
+    if bar_length <= 0. || tempo <= 0. {
+        return None;
+    }
+
+    let beats_left = (bar_length - beats_into_bar).rem_euclid(bar_length);
+    Some(beats_left * 60. / tempo * sample_rate as f64)
+}
+
+/// What the delay does in a block to follow the transport, as the samples in the block it's done at
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TransportActions {
+    /// Everything that holds on to the repeats is cleared, because the host stopped
+    pub clear_at: Option<usize>,
+    /// The patterns of the heads start over, so they're in time with the song. The repeats are kept.
+    pub retrigger_at: Option<usize>,
+}
+
+/// Follows the host transport from block to block and decides when the delay lines get cleared or retriggered
+#[derive(Debug, Default)]
+pub struct TransportFollower {
+    was_playing: bool,
+    /// Whether the last block ended between the last sample and the start of a bar, so the next block retriggers first
+    carried_bar: bool,
+}
+
+impl TransportFollower {
+    /// Check the transport at the start of a block for everything that needs to happen in it
+    pub fn follow(
+        &mut self,
+        info: TransportInfo,
+        stop_behavior: StopBehavior,
+        retrigger: RetriggerMode,
+        block_len: usize,
+    ) -> TransportActions {
+        let stopped = !info.playing && self.was_playing;
+
+        TransportActions {
+            retrigger_at: self.retrigger_at(info, retrigger, block_len),
+            clear_at: (stopped && stop_behavior == StopBehavior::Clear).then_some(0),
+        }
+    }
+
+    /// Check the transport at the start of a block, for a retrigger alone.
+    ///
+    /// Returns the sample in the block at which the retrigger is due, if it is.
+    pub fn retrigger_at(
+        &mut self,
+        info: TransportInfo,
+        retrigger: RetriggerMode,
+        block_len: usize,
+    ) -> Option<usize> {
+        let started = info.playing && !self.was_playing;
+        self.was_playing = info.playing;
+        let carried_bar = std::mem::take(&mut self.carried_bar);
+
+        if !info.playing {
+            return None;
+        }
+
+        match retrigger {
+            RetriggerMode::Off => None,
+            RetriggerMode::Play => started.then_some(0),
+            RetriggerMode::Bar => {
+                // Starting playback in the middle of a bar waits for the next one
+                let samples_to_next_bar = info.samples_to_next_bar?;
+                let next_bar = samples_to_next_bar.ceil();
+                if next_bar < block_len as f64 {
+                    return Some(next_bar as usize);
+                }
+
+                // A bar that starts after the last sample of the block is due on the first sample of the next one
+                self.carried_bar = samples_to_next_bar < block_len as f64;
+                carried_bar.then_some(0)
+            }
+        }
+    }
+
+    /// Forget the transport state, e.g. after the plugin was reset
+    pub fn reset(&mut self) {
+        self.was_playing = false;
+        self.carried_bar = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(playing: bool, samples_to_next_bar: Option<f64>) -> TransportInfo {
+        TransportInfo {
+            playing,
+            samples_to_next_bar,
+        }
+    }
+
+    #[test]
+    fn clears_when_the_host_stops() {
+        let mut follower = TransportFollower::default();
+        let clear = |follower: &mut TransportFollower, playing| {
+            follower
+                .follow(
+                    info(playing, None),
+                    StopBehavior::Clear,
+                    RetriggerMode::Off,
+                    64,
+                )
+                .clear_at
+        };
+
+        assert_eq!(clear(&mut follower, false), None);
+        assert_eq!(clear(&mut follower, true), None);
+        assert_eq!(clear(&mut follower, false), Some(0));
+        assert_eq!(clear(&mut follower, false), None);
+
+        // Letting the tails ring on doesn't clear anything
+        follower.follow(
+            info(true, None),
+            StopBehavior::Continue,
+            RetriggerMode::Off,
+            64,
+        );
+        assert_eq!(
+            follower.follow(
+                info(false, None),
+                StopBehavior::Continue,
+                RetriggerMode::Off,
+                64
+            ),
+            TransportActions::default()
+        );
+    }
+
+    #[test]
+    fn retriggers_on_play() {
+        let mut follower = TransportFollower::default();
+        let mut retrigger = |playing| {
+            follower.follow(
+                info(playing, None),
+                StopBehavior::Clear,
+                RetriggerMode::Play,
+                64,
+            )
+        };
+
+        let retriggered = TransportActions {
+            retrigger_at: Some(0),
+            clear_at: None,
+        };
+        assert_eq!(retrigger(true), retriggered);
+        assert_eq!(retrigger(true), TransportActions::default());
+        // Stopping clears, starting again only retriggers
+        assert_eq!(retrigger(false).clear_at, Some(0));
+        assert_eq!(retrigger(true), retriggered);
+    }
+
+    #[test]
+    fn retriggers_on_bars() {
+        let mut follower = TransportFollower::default();
+        let mut retrigger = |samples_to_next_bar| {
+            follower.retrigger_at(
+                info(true, Some(samples_to_next_bar)),
+                RetriggerMode::Bar,
+                64,
+            )
+        };
+
+        assert_eq!(retrigger(0.), Some(0));
+        assert_eq!(retrigger(100.), None);
+        assert_eq!(retrigger(36.), Some(36));
+        // A bar that starts between two samples is retriggered on the sample after it
+        assert_eq!(retrigger(12.5), Some(13));
+        // That's the first sample of the next block for a bar after the last sample of this one
+        assert_eq!(retrigger(63.5), None);
+        assert_eq!(retrigger(1999.5), Some(0));
+        assert_eq!(retrigger(1935.5), None);
+    }
+
+    #[test]
+    fn retriggers_without_clearing_on_stop() {
+        let mut follower = TransportFollower::default();
+        let mut retrigger =
+            |playing| follower.retrigger_at(info(playing, None), RetriggerMode::Play, 64);
+
+        assert_eq!(retrigger(true), Some(0));
+        assert_eq!(retrigger(false), None);
+        assert_eq!(retrigger(true), Some(0));
+    }
+
+    #[test]
+    fn odd_time_signatures() {
+        // A 7/8 bar is three and a half quarter notes long
+        let samples = samples_to_next_bar(3., 3.5, 60., 1000.).unwrap();
+        assert!((samples - 500.).abs() < 1e-9);
+
+        assert_eq!(samples_to_next_bar(1., 4., 0., 1000.), None);
+    }
+}
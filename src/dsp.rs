@@ -0,0 +1,49 @@
+//! The DSP core of Delax as a library, without the plugin around it.
+//!
+//! Nothing in here depends on nih-plug, so it can be reused in other audio projects by depending on Delax with
+//! `default-features = false, features = ["dsp"]`, plus `reverb` for the [DattorroReverb]. Everything is generic
+//! over the [Sample] type and runs at f32 unless asked for another precision.
+//!
+//! Usage:
+//! ```
+//! use delax::dsp::{DelayEngine, DelayInterpolationMode, SVFFilterMode, SimperSinSVF};
+//!
+//! let mut delay = DelayEngine::<f64>::with_precision(4800, 48000.);
+//! delay.set_delay_amount(50.);
+//! let mut filter = SimperSinSVF::<f64>::with_precision(48000.);
+//! filter.set_cutoff(2000.);
+//! filter.set_mode(SVFFilterMode::Low);
+//! #[cfg(feature = "reverb")]
+//! let mut reverb = delax::dsp::DattorroReverb::<f64>::with_precision(48000., 0.5);
+//!
+//! for i in 0..4800 {
+//!     let input = if i == 0 { 1. } else { 0. };
+//!     let repeat = delay.interpolate_sample(DelayInterpolationMode::Nearest);
+//!     delay.write_sample(input + filter.tick_sample(repeat) * 0.5);
+//!     #[cfg(feature = "reverb")]
+//!     {
+//!         let (l, r) = reverb.process_stereo(repeat, repeat);
+//!         assert!(l.is_finite() && r.is_finite());
+//!     }
+//! }
+//! ```
+
+#[cfg(feature = "reverb")]
+pub use crate::filters::dattorro::DattorroReverb;
+pub use crate::{
+    delay_engine::{
+        drift::StereoDrift,
+        engine::{DelayEngine, DelayInterpolationMode},
+        feedback_matrix::FeedbackMatrix,
+    },
+    filter_pipeline::pipeline::{FilterPipeline, MonoMode},
+    filters::{
+        simper::{SimperSinSVF, SimperTanSVF, StereoSimperSinSVF},
+        spread::StereoSpread,
+        Filter, SVFFilterMode, StereoFilter, MAX_CUTOFF, MIN_CUTOFF,
+    },
+    lofi::Decimator,
+    sample::Sample,
+    sanitize::{flush_denormal, sanitize},
+    simd::{F32x2, F64x2, X2},
+};
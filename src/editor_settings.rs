@@ -0,0 +1,272 @@
+use serde::{Deserialize, Serialize};
+
+use crate::strings::{Language, Text};
+
+/// The distances in pixels a knob can be set to need for a drag over its full range, from coarse to fine
+pub const DRAG_PIXELS: [f32; 4] = [500., 1000., 2000., 4000.];
+/// The amounts of scroll steps a knob can be set to need for its full range, from coarse to fine
+pub const SCROLL_STEPS: [f32; 4] = [10., 25., 50., 100.];
+
+/// Which way scrolling moves the knobs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ScrollDirection {
+    /// Follow the convention of the OS. macOS flips the scroll deltas when natural scrolling is on, which is the default there,
+    /// so scrolling is turned back around to keep "up" turning the knobs up.
+    #[default]
+    Auto,
+    Normal,
+    Inverted,
+}
+
+impl ScrollDirection {
+    /// The next direction, so the GUI can cycle through them
+    pub fn next(self) -> Self {
+        match self {
+            ScrollDirection::Auto => ScrollDirection::Normal,
+            ScrollDirection::Normal => ScrollDirection::Inverted,
+            ScrollDirection::Inverted => ScrollDirection::Auto,
+        }
+    }
+
+    /// The name shown in the GUI
+    pub fn text(self) -> Text {
+        match self {
+            ScrollDirection::Auto => Text::Auto,
+            ScrollDirection::Normal => Text::Normal,
+            ScrollDirection::Inverted => Text::Inverted,
+        }
+    }
+
+    /// Whether the scroll deltas are flipped on the current OS
+    fn inverted(self) -> bool {
+        match self {
+            ScrollDirection::Auto => cfg!(target_os = "macos"),
+            ScrollDirection::Normal => false,
+            ScrollDirection::Inverted => true,
+        }
+    }
+}
+
+/// How the knobs of the editor react to dragging and scrolling. These are part of the [EditorPreferences].
+///
+/// Usage:
+/// ```
+/// use delax::editor_settings::{KnobSettings, ScrollDirection};
+///
+/// let settings = KnobSettings {
+///     drag_pixels: 2000.,
+///     scroll_steps: 50.,
+///     scroll_direction: ScrollDirection::Normal,
+/// };
+/// // Dragging 100 pixels moves a quarter as far as with the coarsest setting
+/// assert_eq!(settings.drag_delta(100.), 0.05);
+/// assert_eq!(settings.scroll_delta(1.), 0.02);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KnobSettings {
+    /// How far the mouse needs to be dragged to move a knob over its full range, in pixels
+    pub drag_pixels: f32,
+    /// How many scroll steps move a knob over its full range
+    pub scroll_steps: f32,
+    pub scroll_direction: ScrollDirection,
+}
+
+impl Default for KnobSettings {
+    fn default() -> Self {
+        Self {
+            drag_pixels: 1000.,
+            scroll_steps: 25.,
+            scroll_direction: ScrollDirection::Auto,
+        }
+    }
+}
+
+impl KnobSettings {
+    /// The change of the normalized value for a drag by some pixels
+    pub fn drag_delta(&self, pixels: f32) -> f32 {
+        pixels / self.drag_pixels.max(1.)
+    }
+
+    /// The change of the normalized value for some scroll steps
+    pub fn scroll_delta(&self, steps: f32) -> f32 {
+        let steps = if self.scroll_direction.inverted() {
+            -steps
+        } else {
+            steps
+        };
+        steps / self.scroll_steps.max(1.)
+    }
+
+    /// Switch to the next drag distance, going back to the coarsest after the finest
+    pub fn cycle_drag_pixels(&mut self) {
+        self.drag_pixels = next_option(&DRAG_PIXELS, self.drag_pixels);
+    }
+
+    /// Switch to the next amount of scroll steps, going back to the coarsest after the finest
+    pub fn cycle_scroll_steps(&mut self) {
+        self.scroll_steps = next_option(&SCROLL_STEPS, self.scroll_steps);
+    }
+}
+
+/// The colors of the editor
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Theme {
+    #[default]
+    Dark,
+    Light,
+    /// Black and white with colors that stay apart with color vision deficiencies. The left and the right
+    /// channel also differ by more than their colors.
+    HighContrast,
+}
+
+impl Theme {
+    /// The next theme, so the GUI can cycle through them
+    pub fn next(self) -> Self {
+        match self {
+            Theme::Dark => Theme::Light,
+            Theme::Light => Theme::HighContrast,
+            Theme::HighContrast => Theme::Dark,
+        }
+    }
+
+    /// The name shown in the GUI
+    pub fn text(self) -> Text {
+        match self {
+            Theme::Dark => Text::Dark,
+            Theme::Light => Text::Light,
+            Theme::HighContrast => Text::HighContrast,
+        }
+    }
+}
+
+/// How much the readout bar at the bottom of the editor tells about the parameter under the cursor
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TooltipVerbosity {
+    /// The readout bar stays empty
+    Off,
+    /// The name and the value
+    #[default]
+    Short,
+    /// The name, the value and the default value
+    Full,
+}
+
+impl TooltipVerbosity {
+    /// The next verbosity, so the GUI can cycle through them
+    pub fn next(self) -> Self {
+        match self {
+            TooltipVerbosity::Off => TooltipVerbosity::Short,
+            TooltipVerbosity::Short => TooltipVerbosity::Full,
+            TooltipVerbosity::Full => TooltipVerbosity::Off,
+        }
+    }
+
+    /// The name shown in the GUI
+    pub fn text(self) -> Text {
+        match self {
+            TooltipVerbosity::Off => Text::Off,
+            TooltipVerbosity::Short => Text::Short,
+            TooltipVerbosity::Full => Text::Full,
+        }
+    }
+}
+
+/// The preferences of the editor. None of them change the sound, so they aren't parameters, they're stored
+/// with the editor state instead.
+///
+/// Usage:
+/// ```
+/// use delax::editor_settings::{EditorPreferences, Theme};
+///
+/// // Preferences stored by an older version keep the defaults for everything that's new
+/// let preferences: EditorPreferences = serde_json::from_str(r#"{"theme":"Light"}"#).unwrap();
+/// assert_eq!(preferences.theme, Theme::Light);
+/// assert!(preferences.show_meters);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EditorPreferences {
+    pub theme: Theme,
+    /// Whether the level meters and the stereo scope are shown
+    pub show_meters: bool,
+    pub tooltips: TooltipVerbosity,
+    pub knobs: KnobSettings,
+    /// The language of all texts of the editor
+    pub language: Language,
+}
+
+impl Default for EditorPreferences {
+    fn default() -> Self {
+        Self {
+            theme: Theme::Dark,
+            show_meters: true,
+            tooltips: TooltipVerbosity::Short,
+            knobs: KnobSettings::default(),
+            language: Language::English,
+        }
+    }
+}
+
+/// The option after the one closest to the current value, wrapping around at the end
+fn next_option(options: &[f32], current: f32) -> f32 {
+    let closest = options
+        .iter()
+        .enumerate()
+        .min_by(|(_, a), (_, b)| (*a - current).abs().total_cmp(&(*b - current).abs()))
+        .map_or(0, |(index, _)| index);
+
+    options[(closest + 1) % options.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn options_cycle() {
+        let mut settings = KnobSettings::default();
+        settings.cycle_drag_pixels();
+        assert_eq!(settings.drag_pixels, 2000.);
+        settings.cycle_drag_pixels();
+        settings.cycle_drag_pixels();
+        assert_eq!(settings.drag_pixels, 500.);
+
+        // Values that were stored by hand snap to the closest option first
+        settings.scroll_steps = 60.;
+        settings.cycle_scroll_steps();
+        assert_eq!(settings.scroll_steps, 100.);
+    }
+
+    #[test]
+    fn scroll_direction_can_be_forced() {
+        let mut settings = KnobSettings {
+            scroll_direction: ScrollDirection::Inverted,
+            ..KnobSettings::default()
+        };
+        assert_eq!(settings.scroll_delta(1.), -0.04);
+        settings.scroll_direction = ScrollDirection::Normal;
+        assert_eq!(settings.scroll_delta(1.), 0.04);
+    }
+
+    #[test]
+    fn preferences_round_trip() {
+        let mut preferences = EditorPreferences::default();
+        preferences.theme = preferences.theme.next();
+        preferences.tooltips = preferences.tooltips.next();
+        preferences.knobs.cycle_drag_pixels();
+
+        let json = serde_json::to_string(&preferences).unwrap();
+        let loaded: EditorPreferences = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded, preferences);
+        assert_eq!(loaded.tooltips, TooltipVerbosity::Full);
+        assert_eq!(loaded.knobs.drag_pixels, 2000.);
+    }
+
+    #[test]
+    fn missing_fields_keep_their_defaults() {
+        let settings: KnobSettings = serde_json::from_str(r#"{"drag_pixels":4000.0}"#).unwrap();
+        assert_eq!(settings.drag_pixels, 4000.);
+        assert_eq!(settings.scroll_steps, 25.);
+    }
+}
@@ -0,0 +1,74 @@
+//! A filter for testing the pipeline without any DSP
+
+use std::sync::{Arc, Mutex};
+
+use crate::filters::{Filter, StereoFilter};
+
+/// What a [MockFilter] was asked to do
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Call {
+    Process(&'static str),
+    SampleRate(&'static str, f32),
+}
+
+/// The calls of all the mock filters of a test, in the order they happened
+#[derive(Debug, Clone, Default)]
+pub struct CallLog(Arc<Mutex<Vec<Call>>>);
+
+impl CallLog {
+    /// The calls since the last time the log was taken
+    pub fn take(&self) -> Vec<Call> {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+
+    fn push(&self, call: Call) {
+        self.0.lock().unwrap().push(call);
+    }
+}
+
+/// Applies a fixed gain and records every call, so tests can tell which filter ran when
+pub struct MockFilter {
+    name: &'static str,
+    gain: f32,
+    log: CallLog,
+}
+
+impl MockFilter {
+    pub fn new(name: &'static str, gain: f32, log: &CallLog) -> Self {
+        Self {
+            name,
+            gain,
+            log: log.clone(),
+        }
+    }
+}
+
+impl Filter for MockFilter {
+    fn process(&mut self, input: f32) -> f32 {
+        self.log.push(Call::Process(self.name));
+        input * self.gain
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.log.push(Call::SampleRate(self.name, sample_rate));
+    }
+
+    fn peak_gain(&self) -> f32 {
+        self.gain
+    }
+}
+
+impl StereoFilter for MockFilter {
+    fn process_stereo(&mut self, input_l: f32, input_r: f32) -> (f32, f32) {
+        self.log.push(Call::Process(self.name));
+        (input_l * self.gain, input_r * self.gain)
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.log.push(Call::SampleRate(self.name, sample_rate));
+    }
+
+    fn peak_gain(&self) -> (f32, f32) {
+        (self.gain, self.gain)
+    }
+}
@@ -1,2 +1,7 @@
+#[cfg(test)]
+pub(crate) mod mock;
+#[cfg(feature = "plugin")]
 pub mod params;
 pub mod pipeline;
+#[cfg(feature = "plugin")]
+pub mod stages;
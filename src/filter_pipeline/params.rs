@@ -1 +1,65 @@
+use std::sync::Arc;
 
+use nih_plug::prelude::*;
+
+use super::stages::StageOrder;
+use crate::{
+    param_snapshot::{float_param, int_param},
+    smoothing::Response,
+};
+
+/// The stages of the feedback loop next to the filters, and the order the feedback passes them in
+#[derive(Params)]
+pub struct PipelineParams {
+    /// The order of the stages as the index of its permutation, see [StageOrder]
+    #[id = "stage_order"]
+    pub stage_order: IntParam,
+    /// How hard the feedback is soft clipped, see [super::stages::saturate()]
+    #[id = "saturation"]
+    pub saturation: FloatParam,
+    /// How much of the feedback runs through the reverb. Builds without the `reverb` feature ignore it, but keep the
+    /// parameter so presets and automation move between the builds.
+    #[id = "reverb_mix"]
+    pub reverb_mix: FloatParam,
+}
+
+impl PipelineParams {
+    pub fn new(response: &Response) -> Self {
+        Self {
+            stage_order: int_param(
+                "Stage Order",
+                0,
+                IntRange::Linear {
+                    min: 0,
+                    max: StageOrder::COUNT - 1,
+                },
+            )
+            .with_value_to_string(Arc::new(|index| StageOrder::from_index(index).to_string()))
+            .with_string_to_value(Arc::new(|string| {
+                StageOrder::parse(string).map(StageOrder::index)
+            })),
+            saturation: float_param("Saturation", 0., FloatRange::Linear { min: 0., max: 1. })
+                .with_smoother(response.style())
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            reverb_mix: float_param("Reverb Mix", 0., FloatRange::Linear { min: 0., max: 1. })
+                .with_smoother(response.style())
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+        }
+    }
+
+    /// The order of the stages the parameter asks for
+    pub fn stage_order(&self) -> StageOrder {
+        StageOrder::from_index(self.stage_order.value())
+    }
+
+    /// Snap all the smoothers to their current values
+    pub fn reset_smoothers(&self) {
+        for param in [&self.saturation, &self.reverb_mix] {
+            param.smoothed.reset(param.value());
+        }
+    }
+}
@@ -6,8 +6,12 @@ use crate::filters::{Filter, StereoFilter};
 pub struct FilterPipeline {
     /// This holds the filter instances so that they can be called in order.
     registered_filters: Vec<FilterPipelineElement>,
-    /// The order of the filters to be called.
+    /// The order of the filters to be called. Filters that aren't in it are skipped.
     order: Vec<usize>,
+    /// Whether each of the registered filters is skipped, while keeping its place in the order
+    bypassed: Vec<bool>,
+    /// Whether every filter is followed by the makeup gain for its peak gain, see [FilterPipeline::set_gain_staging()]
+    gain_staging: bool,
 }
 
 impl FilterPipeline {
@@ -16,26 +20,116 @@ impl FilterPipeline {
         FilterPipeline {
             registered_filters: Vec::new(),
             order: Vec::new(),
+            bypassed: Vec::new(),
+            gain_staging: false,
         }
     }
 
-    /// Register a stereo pair of filter instances
+    /// Make up for the level every filter adds or takes away, using the peak gain it reports.
+    /// This keeps a resonant filter from suddenly pushing the feedback loop around when it's switched on or turned up.
+    pub fn set_gain_staging(&mut self, enabled: bool) {
+        self.gain_staging = enabled;
+    }
+
+    /// Register a stereo pair of filter instances. Returns the index of the new element, it's run last.
     pub fn register_stereo_pair(
         &mut self,
         filter_l: Arc<Mutex<dyn Filter>>,
         filter_r: Arc<Mutex<dyn Filter>>,
-    ) {
-        self.registered_filters
-            .push(FilterPipelineElement::StereoMonoFilter(filter_l, filter_r));
-        self.order.push(self.registered_filters.len() - 1);
+    ) -> usize {
+        self.register(FilterPipelineElement::StereoMonoFilter(filter_l, filter_r))
+    }
+
+    /// Register a stereo filter that's combined. Returns the index of the new element, it's run last.
+    pub fn register_stereo(&mut self, filter: Arc<Mutex<dyn StereoFilter>>) -> usize {
+        self.register(FilterPipelineElement::StereoStereoFilter(filter))
+    }
+
+    /// Hand a stereo pair of filters over to the pipeline, for filters whose settings don't need to be changed
+    /// from the outside anymore. See [FilterPipeline::register_stereo_pair()].
+    ///
+    /// Usage:
+    /// ```
+    /// use delax::{filter_pipeline::pipeline::FilterPipeline, filters::simper::SimperSinSVF};
+    ///
+    /// let mut pipeline = FilterPipeline::new();
+    /// let element = pipeline.add_stereo_pair(SimperSinSVF::new(44100.), SimperSinSVF::new(44100.));
+    /// assert_eq!(pipeline.order(), [element]);
+    /// ```
+    pub fn add_stereo_pair(
+        &mut self,
+        filter_l: impl Filter + 'static,
+        filter_r: impl Filter + 'static,
+    ) -> usize {
+        self.register_stereo_pair(
+            Arc::new(Mutex::new(filter_l)),
+            Arc::new(Mutex::new(filter_r)),
+        )
+    }
+
+    /// Hand a stereo filter over to the pipeline, see [FilterPipeline::add_stereo_pair()]
+    pub fn add_stereo(&mut self, filter: impl StereoFilter + 'static) -> usize {
+        self.register_stereo(Arc::new(Mutex::new(filter)))
+    }
+
+    /// Register a single filter for both channels. Returns the index of the new element, it's run last.
+    pub fn register_mono(&mut self, filter: Arc<Mutex<dyn Filter>>, mode: MonoMode) -> usize {
+        self.register(FilterPipelineElement::Mono(filter, mode))
+    }
+
+    /// Hand a single filter for both channels over to the pipeline, see [FilterPipeline::add_stereo_pair()]
+    pub fn add_mono(&mut self, filter: impl Filter + 'static, mode: MonoMode) -> usize {
+        self.register_mono(Arc::new(Mutex::new(filter)), mode)
     }
 
-    /// Register a stereo filter that's combined
-    #[allow(dead_code)]
-    pub fn register_stereo(&mut self, filter: Arc<Mutex<dyn StereoFilter>>) {
-        self.registered_filters
-            .push(FilterPipelineElement::StereoStereoFilter(filter));
+    fn register(&mut self, element: FilterPipelineElement) -> usize {
+        self.registered_filters.push(element);
+        self.bypassed.push(false);
         self.order.push(self.registered_filters.len() - 1);
+        self.registered_filters.len() - 1
+    }
+
+    /// The amount of registered elements
+    pub fn len(&self) -> usize {
+        self.registered_filters.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.registered_filters.is_empty()
+    }
+
+    /// The indices of the elements in the order they're run in
+    pub fn order(&self) -> &[usize] {
+        &self.order
+    }
+
+    /// Run the elements in another order. Elements that are left out are skipped until they're put back in.
+    ///
+    /// This doesn't allocate, so it can be called on the audio thread.
+    ///
+    /// # Panics
+    ///
+    /// If an element doesn't exist or appears twice, running a filter twice per sample would mess up its state.
+    pub fn set_order(&mut self, order: &[usize]) {
+        for (position, element) in order.iter().enumerate() {
+            assert!(
+                *element < self.registered_filters.len(),
+                "There is no pipeline element {element}"
+            );
+            assert!(
+                !order[..position].contains(element),
+                "The pipeline element {element} is in the order twice"
+            );
+        }
+
+        // Every element is in the order at most once, so this fits into the capacity the registered elements left
+        self.order.clear();
+        self.order.extend_from_slice(order);
+    }
+
+    /// Skip an element without taking it out of the order. A bypassed filter isn't run, so its state is kept as it is.
+    pub fn set_bypassed(&mut self, element: usize, bypassed: bool) {
+        self.bypassed[element] = bypassed;
     }
 
     /// Process a stereo signal through the stack of filters
@@ -44,28 +138,377 @@ impl FilterPipeline {
         let mut r = input_r;
 
         for i in &self.order {
-            match &self.registered_filters[*i] {
+            if self.bypassed[*i] {
+                continue;
+            }
+
+            let (gain_l, gain_r) = match &self.registered_filters[*i] {
                 FilterPipelineElement::StereoMonoFilter(filter_l, filter_r) => {
-                    l = filter_l.lock().unwrap().process(l);
-                    r = filter_r.lock().unwrap().process(r);
+                    let mut filter_l = filter_l.lock().unwrap();
+                    let mut filter_r = filter_r.lock().unwrap();
+                    l = filter_l.process(l);
+                    r = filter_r.process(r);
+                    (filter_l.peak_gain(), filter_r.peak_gain())
                 }
                 FilterPipelineElement::StereoStereoFilter(filter) => {
-                    let (new_l, new_r) = filter.lock().unwrap().process_stereo(l, r);
-                    l = new_l;
-                    r = new_r;
+                    let mut filter = filter.lock().unwrap();
+                    (l, r) = filter.process_stereo(l, r);
+                    filter.peak_gain()
+                }
+                FilterPipelineElement::Mono(filter, MonoMode::Mid) => {
+                    let mut filter = filter.lock().unwrap();
+                    let side = (l - r) * 0.5;
+                    let mid = filter.process((l + r) * 0.5);
+                    (l, r) = (mid + side, mid - side);
+                    let gain = filter.peak_gain();
+                    (gain, gain)
                 }
-                FilterPipelineElement::Mono(_) => {}
+                FilterPipelineElement::Mono(filter, MonoMode::Both) => {
+                    let mut filter = filter.lock().unwrap();
+                    l = filter.process((l + r) * 0.5);
+                    r = l;
+                    let gain = filter.peak_gain();
+                    (gain, gain)
+                }
+            };
+            if self.gain_staging {
+                l *= makeup_gain(gain_l);
+                r *= makeup_gain(gain_r);
             }
         }
 
         (l, r)
     }
+
+    /// Process a mono signal through the stack of filters.
+    ///
+    /// Stereo pairs only run their left filter and stereo filters get the signal on both sides, so their state
+    /// stays the same as it would be for a centered stereo signal.
+    pub fn process_mono(&self, input: f32) -> f32 {
+        let mut sample = input;
+
+        for i in &self.order {
+            if self.bypassed[*i] {
+                continue;
+            }
+
+            let gain = match &self.registered_filters[*i] {
+                FilterPipelineElement::StereoMonoFilter(filter_l, _) => {
+                    let mut filter_l = filter_l.lock().unwrap();
+                    sample = filter_l.process(sample);
+                    filter_l.peak_gain()
+                }
+                FilterPipelineElement::StereoStereoFilter(filter) => {
+                    let mut filter = filter.lock().unwrap();
+                    let (l, r) = filter.process_stereo(sample, sample);
+                    sample = (l + r) * 0.5;
+                    let (gain_l, gain_r) = filter.peak_gain();
+                    gain_l.max(gain_r)
+                }
+                FilterPipelineElement::Mono(filter, _) => {
+                    let mut filter = filter.lock().unwrap();
+                    sample = filter.process(sample);
+                    filter.peak_gain()
+                }
+            };
+            if self.gain_staging {
+                sample *= makeup_gain(gain);
+            }
+        }
+
+        sample
+    }
+
+    /// The highest gain of the whole pipeline on each side, the product of the peak gains of the filters that run.
+    /// With gain staging on it's what's left after the makeup gains.
+    pub fn peak_gain(&self) -> (f32, f32) {
+        self.order
+            .iter()
+            .filter(|i| !self.bypassed[**i])
+            .map(|i| match &self.registered_filters[*i] {
+                FilterPipelineElement::StereoMonoFilter(filter_l, filter_r) => (
+                    filter_l.lock().unwrap().peak_gain(),
+                    filter_r.lock().unwrap().peak_gain(),
+                ),
+                FilterPipelineElement::StereoStereoFilter(filter) => {
+                    filter.lock().unwrap().peak_gain()
+                }
+                FilterPipelineElement::Mono(filter, _) => {
+                    let gain = filter.lock().unwrap().peak_gain();
+                    (gain, gain)
+                }
+            })
+            .map(|(gain_l, gain_r)| {
+                if self.gain_staging {
+                    (gain_l * makeup_gain(gain_l), gain_r * makeup_gain(gain_r))
+                } else {
+                    (gain_l, gain_r)
+                }
+            })
+            .fold((1., 1.), |(l, r), (gain_l, gain_r)| {
+                (l * gain_l, r * gain_r)
+            })
+    }
+
+    /// Retune every registered filter to a new sample rate. The filters are updated in place, so everyone holding them sees the change.
+    pub fn set_sample_rate(&self, sample_rate: f32) {
+        for element in &self.registered_filters {
+            match element {
+                FilterPipelineElement::StereoMonoFilter(filter_l, filter_r) => {
+                    filter_l.lock().unwrap().set_sample_rate(sample_rate);
+                    filter_r.lock().unwrap().set_sample_rate(sample_rate);
+                }
+                FilterPipelineElement::StereoStereoFilter(filter) => {
+                    filter.lock().unwrap().set_sample_rate(sample_rate);
+                }
+                FilterPipelineElement::Mono(filter, _) => {
+                    filter.lock().unwrap().set_sample_rate(sample_rate);
+                }
+            }
+        }
+    }
+}
+
+impl Default for FilterPipeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The most a filter is turned up to make up for the level it takes away, so a filter that reports silence can't blow up
+const MAX_MAKEUP_GAIN: f32 = 8.;
+
+/// The gain that makes up for a peak gain
+fn makeup_gain(peak_gain: f32) -> f32 {
+    (1. / peak_gain).min(MAX_MAKEUP_GAIN)
 }
 
-#[allow(dead_code)]
 /// A bundle of filter instances to be used in the pipeline
 pub enum FilterPipelineElement {
     StereoMonoFilter(Arc<Mutex<dyn Filter>>, Arc<Mutex<dyn Filter>>),
     StereoStereoFilter(Arc<Mutex<dyn StereoFilter>>),
-    Mono(Arc<Mutex<dyn Filter>>),
+    /// A single filter in a stereo pipeline, see [MonoMode]
+    Mono(Arc<Mutex<dyn Filter>>, MonoMode),
+}
+
+/// How a mono filter is applied to a stereo signal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MonoMode {
+    /// Only filter the mid and leave the side as it is, so the stereo image is kept
+    #[default]
+    Mid,
+    /// Filter the sum of both channels and send it to both sides
+    Both,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        filter_pipeline::mock::{Call, CallLog, MockFilter},
+        filters::{simper::SimperSinSVF, SVFFilterMode},
+    };
+
+    #[test]
+    fn elements_run_in_their_order() {
+        let log = CallLog::default();
+        let mut pipeline = FilterPipeline::new();
+        let double = pipeline.add_stereo(MockFilter::new("double", 2., &log));
+        let triple = pipeline.add_stereo(MockFilter::new("triple", 3., &log));
+        assert_eq!(pipeline.order(), [double, triple]);
+
+        assert_eq!(pipeline.process_stereo(1., -1.), (6., -6.));
+        assert_eq!(
+            log.take(),
+            [Call::Process("double"), Call::Process("triple")]
+        );
+
+        pipeline.set_order(&[triple, double]);
+        pipeline.process_stereo(1., -1.);
+        assert_eq!(
+            log.take(),
+            [Call::Process("triple"), Call::Process("double")]
+        );
+
+        // Leaving an element out skips it
+        pipeline.set_order(&[triple]);
+        assert_eq!(pipeline.process_stereo(1., -1.), (3., -3.));
+        assert_eq!(log.take(), [Call::Process("triple")]);
+    }
+
+    #[test]
+    #[should_panic(expected = "twice")]
+    fn elements_cant_run_twice() {
+        let mut pipeline = FilterPipeline::new();
+        let element = pipeline.add_stereo(MockFilter::new("mock", 1., &CallLog::default()));
+        pipeline.set_order(&[element, element]);
+    }
+
+    #[test]
+    fn stereo_pairs_filter_each_side_on_its_own() {
+        let log = CallLog::default();
+        let mut pipeline = FilterPipeline::new();
+        pipeline.add_stereo_pair(
+            MockFilter::new("left", 2., &log),
+            MockFilter::new("right", 0.5, &log),
+        );
+        pipeline.add_stereo(MockFilter::new("stereo", -1., &log));
+
+        assert_eq!(pipeline.process_stereo(1., 1.), (-2., -0.5));
+        // The pair runs both of its filters, the stereo filter runs once for both sides
+        assert_eq!(
+            log.take(),
+            [
+                Call::Process("left"),
+                Call::Process("right"),
+                Call::Process("stereo")
+            ]
+        );
+
+        pipeline.set_sample_rate(96000.);
+        assert_eq!(
+            log.take(),
+            [
+                Call::SampleRate("left", 96000.),
+                Call::SampleRate("right", 96000.),
+                Call::SampleRate("stereo", 96000.)
+            ]
+        );
+    }
+
+    #[test]
+    fn mono_elements_filter_the_mid() {
+        let log = CallLog::default();
+        let mut pipeline = FilterPipeline::new();
+        let mid = pipeline.add_mono(MockFilter::new("mid", 2., &log), MonoMode::Mid);
+        let both = pipeline.add_mono(MockFilter::new("both", 0.5, &log), MonoMode::Both);
+
+        // A mid of 0.5 and a side of 0.5, only the mid is doubled
+        pipeline.set_order(&[mid]);
+        assert_eq!(pipeline.process_stereo(1., 0.), (1.5, 0.5));
+        assert_eq!(log.take(), [Call::Process("mid")]);
+
+        pipeline.set_order(&[both]);
+        assert_eq!(pipeline.process_stereo(1., 0.), (0.25, 0.25));
+        assert_eq!(log.take(), [Call::Process("both")]);
+    }
+
+    #[test]
+    fn mono_processing_runs_every_element_once() {
+        let log = CallLog::default();
+        let mut pipeline = FilterPipeline::new();
+        pipeline.add_stereo_pair(
+            MockFilter::new("left", 2., &log),
+            MockFilter::new("right", 5., &log),
+        );
+        pipeline.add_stereo(MockFilter::new("stereo", 3., &log));
+        pipeline.add_mono(MockFilter::new("mono", -1., &log), MonoMode::Mid);
+
+        assert_eq!(pipeline.process_mono(1.), -6.);
+        assert_eq!(
+            log.take(),
+            [
+                Call::Process("left"),
+                Call::Process("stereo"),
+                Call::Process("mono")
+            ]
+        );
+    }
+
+    #[test]
+    fn peak_gains_multiply() {
+        let log = CallLog::default();
+        let mut pipeline = FilterPipeline::new();
+        pipeline.add_stereo_pair(
+            MockFilter::new("left", 2., &log),
+            MockFilter::new("right", 0.5, &log),
+        );
+        let triple = pipeline.add_stereo(MockFilter::new("triple", 3., &log));
+        assert_eq!(pipeline.peak_gain(), (6., 1.5));
+
+        pipeline.set_bypassed(triple, true);
+        assert_eq!(pipeline.peak_gain(), (2., 0.5));
+
+        // Gain staging makes up for every filter
+        pipeline.set_gain_staging(true);
+        assert_eq!(pipeline.peak_gain(), (1., 1.));
+        // Asking for the gains doesn't run the filters
+        assert!(log.take().is_empty());
+    }
+
+    #[test]
+    fn bypassed_elements_pass_the_signal_through() {
+        let log = CallLog::default();
+        let mut pipeline = FilterPipeline::new();
+        let double = pipeline.add_stereo(MockFilter::new("double", 2., &log));
+        pipeline.add_stereo(MockFilter::new("triple", 3., &log));
+
+        pipeline.set_bypassed(double, true);
+        assert_eq!(pipeline.process_stereo(1., 2.), (3., 6.));
+        assert_eq!(log.take(), [Call::Process("triple")]);
+        // Bypassed elements keep their place in the order
+        assert_eq!(pipeline.order(), [0, 1]);
+
+        pipeline.set_bypassed(double, false);
+        assert_eq!(pipeline.process_stereo(1., 2.), (6., 12.));
+    }
+
+    #[test]
+    fn gain_staging_uses_the_reported_gain() {
+        let log = CallLog::default();
+        let mut pipeline = FilterPipeline::new();
+        pipeline.add_stereo(MockFilter::new("double", 2., &log));
+        pipeline.add_stereo(MockFilter::new("quiet", 0.001, &log));
+
+        pipeline.set_gain_staging(true);
+        // The quiet filter is only made up for up to the limit
+        let (l, _) = pipeline.process_stereo(1., 1.);
+        assert!((l - 0.001 * MAX_MAKEUP_GAIN).abs() < 1e-6);
+    }
+
+    #[test]
+    fn sample_rate_changes_reach_the_registered_filters() {
+        let filter_l = Arc::new(Mutex::new(SimperSinSVF::new(44100.)));
+        let filter_r = Arc::new(Mutex::new(SimperSinSVF::new(44100.)));
+        filter_l.lock().unwrap().set_cutoff(1000.);
+        filter_r.lock().unwrap().set_cutoff(1000.);
+        let mut pipeline = FilterPipeline::new();
+        pipeline.register_stereo_pair(filter_l, filter_r);
+
+        pipeline.set_sample_rate(96000.);
+
+        let mut expected = SimperSinSVF::new(96000.);
+        expected.set_cutoff(1000.);
+        for i in 0..64 {
+            let sample = if i == 0 { 1. } else { 0. };
+            let (l, _) = pipeline.process_stereo(sample, sample);
+            assert_eq!(l, expected.tick_sample(sample));
+        }
+    }
+
+    #[test]
+    fn gain_staging_makes_up_for_the_resonance() {
+        let resonant = || {
+            let mut filter = SimperSinSVF::new(48000.);
+            filter.set_cutoff_res(1000., 1.);
+            filter.set_mode(SVFFilterMode::Band);
+            Arc::new(Mutex::new(filter))
+        };
+        let mut pipeline = FilterPipeline::new();
+        pipeline.register_stereo_pair(resonant(), resonant());
+
+        let peak = |pipeline: &FilterPipeline| {
+            (0..48000)
+                .map(|i| {
+                    let sample = (std::f32::consts::TAU * 1000. * i as f32 / 48000.).sin();
+                    pipeline.process_stereo(sample, sample).0.abs()
+                })
+                .skip(24000)
+                .fold(0., f32::max)
+        };
+        // Full resonance makes the band pass almost twice as loud at the cutoff
+        assert!(peak(&pipeline) > 1.7);
+        pipeline.set_gain_staging(true);
+        assert!((peak(&pipeline) - 1.).abs() < 0.05);
+    }
 }
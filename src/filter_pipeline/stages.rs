@@ -0,0 +1,295 @@
+use std::fmt::{self, Display};
+
+use nih_plug::util;
+
+use crate::{smoothing::Gate, GATE_FADE_MS};
+
+/// The amount of stages in the feedback loop that can be reordered
+pub const NUM_STAGES: usize = 4;
+/// How hard the feedback is driven into the saturation at its full amount, in dB
+pub const MAX_SATURATION_DB: f32 = 12.;
+
+/// The stages of the feedback loop that can be put in any order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeedbackStage {
+    /// The feedback filters with their mix
+    Svf,
+    Saturation,
+    /// The Dattorro reverb, it passes the feedback on unchanged in builds without the `reverb` feature
+    Reverb,
+    /// The sample rate and bit depth reduction of the Lo-Fi algorithm
+    Degrade,
+}
+
+impl FeedbackStage {
+    /// All stages in their default order
+    pub const ALL: [FeedbackStage; NUM_STAGES] = [
+        FeedbackStage::Svf,
+        FeedbackStage::Saturation,
+        FeedbackStage::Reverb,
+        FeedbackStage::Degrade,
+    ];
+
+    /// The name of the stage in the value of the order parameter
+    pub fn name(self) -> &'static str {
+        match self {
+            FeedbackStage::Svf => "SVF",
+            FeedbackStage::Saturation => "Saturation",
+            FeedbackStage::Reverb => "Reverb",
+            FeedbackStage::Degrade => "Degrade",
+        }
+    }
+
+    /// The position of the stage in the default order
+    fn position(self) -> usize {
+        FeedbackStage::ALL
+            .iter()
+            .position(|stage| *stage == self)
+            .unwrap()
+    }
+}
+
+/// An order of the [FeedbackStage]s.
+///
+/// A parameter can only hold a number, so an order is stored as the index of its permutation, from 0 for the default
+/// order to [StageOrder::COUNT] - 1 for the reversed one.
+///
+/// Usage:
+/// ```
+/// use delax::filter_pipeline::stages::{FeedbackStage, StageOrder};
+///
+/// // Dragging the SVF behind the saturation
+/// let order = StageOrder::default().moved(0, 1);
+/// assert_eq!(order.stages()[..2], [FeedbackStage::Saturation, FeedbackStage::Svf]);
+/// assert_eq!(StageOrder::from_index(order.index()), order);
+/// assert_eq!(order.to_string(), "Saturation > SVF > Reverb > Degrade");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StageOrder([FeedbackStage; NUM_STAGES]);
+
+impl Default for StageOrder {
+    fn default() -> Self {
+        Self(FeedbackStage::ALL)
+    }
+}
+
+impl StageOrder {
+    /// The amount of different orders
+    pub const COUNT: i32 = 24;
+
+    /// The order with the given index, out of range indices are clamped. This doesn't allocate, so the audio thread
+    /// can read the parameter every block.
+    pub fn from_index(index: i32) -> Self {
+        let mut index = index.clamp(0, Self::COUNT - 1) as usize;
+        let mut remaining = FeedbackStage::ALL;
+        let mut stages = FeedbackStage::ALL;
+        // Every position picks one of the remaining stages, the earlier positions count for more.
+        // The picked stage is rotated behind the remaining ones, so they stay in their default order.
+        for (position, stage) in stages.iter_mut().enumerate() {
+            let weight = factorial(NUM_STAGES - 1 - position);
+            let picked = index / weight;
+            *stage = remaining[picked];
+            remaining[picked..NUM_STAGES - position].rotate_left(1);
+            index %= weight;
+        }
+
+        Self(stages)
+    }
+
+    /// The index of the order, the inverse of [StageOrder::from_index()]
+    pub fn index(self) -> i32 {
+        let index: usize = self
+            .0
+            .iter()
+            .enumerate()
+            .map(|(position, stage)| {
+                let smaller_after = self.0[position + 1..]
+                    .iter()
+                    .filter(|later| later.position() < stage.position())
+                    .count();
+                smaller_after * factorial(NUM_STAGES - 1 - position)
+            })
+            .sum();
+        index as i32
+    }
+
+    /// The stages in the order the feedback passes them
+    pub fn stages(self) -> [FeedbackStage; NUM_STAGES] {
+        self.0
+    }
+
+    /// The order with the stage at `from` taken out and put back in at `to`, like a drag in a list
+    pub fn moved(self, from: usize, to: usize) -> Self {
+        let mut stages = self.0;
+        if from < NUM_STAGES && to < NUM_STAGES {
+            if from < to {
+                stages[from..=to].rotate_left(1);
+            } else {
+                stages[to..=from].rotate_right(1);
+            }
+        }
+
+        Self(stages)
+    }
+
+    /// Parse the names of the stages separated by `>`, as they are shown in the value of the parameter.
+    /// Returns None unless every stage is named exactly once.
+    pub fn parse(string: &str) -> Option<Self> {
+        let mut stages = FeedbackStage::ALL;
+        let mut names = string.split('>').map(str::trim);
+        for stage in &mut stages {
+            let name = names.next()?;
+            *stage = *FeedbackStage::ALL
+                .iter()
+                .find(|stage| stage.name().eq_ignore_ascii_case(name))?;
+        }
+        let order = Self(stages);
+
+        (names.next().is_none() && Self::from_index(order.index()) == order).then_some(order)
+    }
+}
+
+impl Display for StageOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (position, stage) in self.0.iter().enumerate() {
+            if position > 0 {
+                f.write_str(" > ")?;
+            }
+            f.write_str(stage.name())?;
+        }
+        Ok(())
+    }
+}
+
+fn factorial(n: usize) -> usize {
+    (1..=n).product()
+}
+
+/// Takes over a new [StageOrder] without clicking.
+///
+/// Moving a stage in the middle of a repeat jumps between two different signals, so the stages are faded out to the
+/// unprocessed feedback first. The new order is only applied at the start of a block once they're silent, then they
+/// fade back in.
+pub struct StageSwitch {
+    applied: StageOrder,
+    /// The order of the parameter at the start of the current block
+    pending: StageOrder,
+    gate: Gate,
+}
+
+impl StageSwitch {
+    pub fn new(order: StageOrder) -> Self {
+        Self {
+            applied: order,
+            pending: order,
+            gate: Gate::new(GATE_FADE_MS, true),
+        }
+    }
+
+    /// The order the stages run in right now
+    pub fn order(&self) -> StageOrder {
+        self.applied
+    }
+
+    /// Follow the order of the parameter, once at the start of every block
+    pub fn begin_block(&mut self, order: StageOrder) {
+        self.pending = order;
+        if self.applied != order && self.gate.value() == 0. {
+            self.applied = order;
+        }
+    }
+
+    /// How far the stages are faded in for the next sample, from 0 for the unprocessed feedback to 1
+    pub fn next(&mut self, sample_rate: f32) -> f32 {
+        self.gate.next(sample_rate, self.applied == self.pending)
+    }
+
+    /// Jump to an order without fading
+    pub fn reset(&mut self, order: StageOrder) {
+        self.applied = order;
+        self.pending = order;
+        self.gate.reset(true);
+    }
+}
+
+/// Soft clip a sample of the feedback by the saturation `amount` from 0 to 1.
+///
+/// Quiet repeats pass at their level, loud ones are squashed more the higher the amount. At 0 the sample passes unchanged.
+pub fn saturate(input: f32, amount: f32) -> f32 {
+    let drive = util::db_to_gain(amount * MAX_SATURATION_DB);
+    input + ((drive * input).tanh() / drive - input) * amount
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_index_is_a_different_order() {
+        let orders: Vec<StageOrder> = (0..StageOrder::COUNT).map(StageOrder::from_index).collect();
+        for (index, order) in orders.iter().enumerate() {
+            assert_eq!(order.index(), index as i32);
+            assert!(orders[..index].iter().all(|other| other != order));
+            assert_eq!(StageOrder::parse(&order.to_string()), Some(*order));
+        }
+        assert_eq!(StageOrder::from_index(0), StageOrder::default());
+        let mut reversed = FeedbackStage::ALL;
+        reversed.reverse();
+        assert_eq!(
+            StageOrder::from_index(StageOrder::COUNT - 1).stages(),
+            reversed
+        );
+        // Every stage has to be named once
+        assert_eq!(
+            StageOrder::parse("svf > reverb > saturation > degrade").map(StageOrder::index),
+            Some(2)
+        );
+        assert!(StageOrder::parse("SVF > SVF > Reverb > Degrade").is_none());
+        assert!(StageOrder::parse("SVF > Saturation > Reverb").is_none());
+    }
+
+    #[test]
+    fn moving_keeps_the_other_stages_in_order() {
+        use FeedbackStage::*;
+
+        let order = StageOrder::default();
+        assert_eq!(
+            order.moved(3, 0).stages(),
+            [Degrade, Svf, Saturation, Reverb]
+        );
+        assert_eq!(
+            order.moved(0, 2).stages(),
+            [Saturation, Reverb, Svf, Degrade]
+        );
+        assert_eq!(order.moved(1, 1), order);
+        assert_eq!(order.moved(0, 4), order);
+    }
+
+    #[test]
+    fn new_orders_wait_until_the_stages_are_faded_out() {
+        let sample_rate = 1000.;
+        let mut switch = StageSwitch::new(StageOrder::default());
+        let swapped = StageOrder::from_index(5);
+
+        switch.begin_block(swapped);
+        assert_eq!(switch.order(), StageOrder::default());
+        let fade_samples = (GATE_FADE_MS / 1000. * sample_rate) as usize;
+        for _ in 0..fade_samples {
+            assert!(switch.next(sample_rate) < 1.);
+        }
+        assert_eq!(switch.next(sample_rate), 0.);
+        // The order only changes on the next block, then the stages fade back in
+        assert_eq!(switch.order(), StageOrder::default());
+        switch.begin_block(swapped);
+        assert_eq!(switch.order(), swapped);
+        assert!(switch.next(sample_rate) > 0.);
+    }
+
+    #[test]
+    fn saturation_keeps_quiet_repeats() {
+        assert_eq!(saturate(0.8, 0.), 0.8);
+        assert!((saturate(0.01, 1.) - 0.01).abs() < 1e-4);
+        assert!(saturate(1., 1.) < 0.5);
+        assert!(saturate(-1., 0.5) > -1. && saturate(-1., 0.5) < 0.);
+    }
+}
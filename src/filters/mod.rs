@@ -1,10 +1,6 @@
-pub mod dattorro;
+pub use delax_dsp::filters::{
+    dattorro, driven, simper, spread, stack, Filter, SVFFilterMode, StereoFilter, MAX_CUTOFF,
+    MIN_CUTOFF,
+};
+#[cfg(feature = "plugin")]
 pub mod params;
-pub mod simper;
-pub trait Filter: Send + Sync {
-    fn process(&mut self, input: f32) -> f32;
-}
-
-pub trait StereoFilter: Send + Sync {
-    fn process_stereo(&mut self, input_l: f32, input_r: f32) -> (f32, f32);
-}
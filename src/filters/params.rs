@@ -1,7 +1,20 @@
 use nih_plug::prelude::*;
 
-#[derive(Debug, Enum, PartialEq, Clone, Copy)]
-pub enum SVFFilterMode {
+use crate::{
+    delay_engine::params::{channel_name_suffix, Channels, NUM_CHANNELS},
+    midi::poly_mod::PolyModTarget,
+    param_snapshot::{bool_param, enum_param, float_param},
+    smoothing::Response,
+};
+
+pub use super::{stack::Slope, SVFFilterMode, MAX_CUTOFF, MIN_CUTOFF};
+
+/// The most the repeats can be driven into the feedback filters, in dB
+pub const MAX_DRIVE_DB: f32 = 24.;
+
+/// The filter mode parameter, mirroring [SVFFilterMode] of the DSP crate, which knows nothing about parameters
+#[derive(Enum, Debug, PartialEq, Clone, Copy)]
+pub enum FilterMode {
     Low,
     Band,
     High,
@@ -9,80 +22,196 @@ pub enum SVFFilterMode {
     Peak,
 }
 
+impl From<FilterMode> for SVFFilterMode {
+    fn from(mode: FilterMode) -> Self {
+        match mode {
+            FilterMode::Low => SVFFilterMode::Low,
+            FilterMode::Band => SVFFilterMode::Band,
+            FilterMode::High => SVFFilterMode::High,
+            FilterMode::Notch => SVFFilterMode::Notch,
+            FilterMode::Peak => SVFFilterMode::Peak,
+        }
+    }
+}
+
+/// The slope parameter, mirroring [Slope] of the DSP crate
+#[derive(Enum, Debug, PartialEq, Clone, Copy)]
+pub enum FilterSlope {
+    #[name = "12 dB"]
+    Db12,
+    #[name = "24 dB"]
+    Db24,
+}
+
+impl From<FilterSlope> for Slope {
+    fn from(slope: FilterSlope) -> Self {
+        match slope {
+            FilterSlope::Db12 => Slope::Db12,
+            FilterSlope::Db24 => Slope::Db24,
+        }
+    }
+}
+
 #[derive(Enum, PartialEq)]
 pub enum SVFStereoMode {
     Mono,
     Stereo,
 }
 
+/// The filter parameters of a single channel
 #[derive(Params)]
-pub struct FilterParams {
-    #[id = "svf_cutoff_l"]
-    pub svf_cutoff_l: FloatParam,
-    #[id = "svf_cutoff_r"]
-    pub svf_cutoff_r: FloatParam,
-    #[id = "svf_res_l"]
-    pub svf_res_l: FloatParam,
-    #[id = "svf_res_r"]
-    pub svf_res_r: FloatParam,
-    #[id = "svf_filter_mode_l"]
-    pub svf_filter_mode_l: EnumParam<SVFFilterMode>,
-    #[id = "svf_filter_mode_r"]
-    pub svf_filter_mode_r: EnumParam<SVFFilterMode>,
-    #[id = "svf_stereo_mode"]
-    pub svf_stereo_mode: EnumParam<SVFStereoMode>,
-    #[id = "svf_mix_l"]
-    pub svf_mix_l: FloatParam,
-    #[id = "svf_mix_r"]
-    pub svf_mix_r: FloatParam,
+pub struct ChannelFilterParams {
+    #[id = "svf_cutoff"]
+    pub cutoff: FloatParam,
+    #[id = "svf_res"]
+    pub res: FloatParam,
+    #[id = "svf_filter_mode"]
+    pub mode: EnumParam<FilterMode>,
+    #[id = "svf_mix"]
+    pub mix: FloatParam,
+    /// How much the cutoff follows the last MIDI note
+    #[id = "svf_keytrack"]
+    pub keytrack: FloatParam,
 }
 
-impl Default for FilterParams {
-    fn default() -> Self {
+impl ChannelFilterParams {
+    /// Create the parameters for the channel with the given index, smoothed with the shared response time
+    pub fn new(channel: usize, response: &Response) -> Self {
+        let suffix = channel_name_suffix(channel);
+
         Self {
-            svf_cutoff_l: FloatParam::new(
-                "SVF Cutoff",
+            // The cutoff is smoothed logarithmically, so sweeps move evenly through the octaves.
+            // That needs a range that doesn't include zero.
+            cutoff: float_param(
+                format!("SVF Cutoff{suffix}"),
                 500.,
                 FloatRange::Skewed {
-                    min: 0.,
-                    max: 20000.,
-                    factor: 0.5,
-                },
-            )
-            .with_smoother(SmoothingStyle::Linear(50.))
-            .with_value_to_string(formatters::v2s_f32_hz_then_khz(2))
-            .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
-            svf_cutoff_r: FloatParam::new(
-                "SVF Cutoff Channel 2",
-                500.,
-                FloatRange::Skewed {
-                    min: 0.,
-                    max: 20000.,
+                    min: MIN_CUTOFF,
+                    max: MAX_CUTOFF,
                     factor: 0.5,
                 },
             )
-            .with_smoother(SmoothingStyle::Linear(50.))
+            .with_smoother(response.logarithmic_style())
+            .with_poly_modulation_id(PolyModTarget::Cutoff.id(channel))
             .with_value_to_string(formatters::v2s_f32_hz_then_khz(2))
             .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
-            svf_res_l: FloatParam::new("SVF Res", 0.2, FloatRange::Linear { min: 0., max: 1. })
-                .with_smoother(SmoothingStyle::Linear(50.)),
-            svf_res_r: FloatParam::new(
-                "SVF Res Channel 2",
+            res: float_param(
+                format!("SVF Res{suffix}"),
                 0.2,
                 FloatRange::Linear { min: 0., max: 1. },
             )
-            .with_smoother(SmoothingStyle::Linear(50.)),
-            svf_filter_mode_l: EnumParam::new("SVF Filter Mode", SVFFilterMode::Band),
-            svf_filter_mode_r: EnumParam::new("SVF Filter Mode Channel 2", SVFFilterMode::Band),
-            svf_stereo_mode: EnumParam::new("SVF Seperated", SVFStereoMode::Mono),
-            svf_mix_l: FloatParam::new("Mix", 1., FloatRange::Linear { min: 0., max: 1. })
-                .with_smoother(SmoothingStyle::Linear(50.)),
-            svf_mix_r: FloatParam::new(
-                "Mix Channel 2",
+            .with_smoother(response.style())
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            mode: enum_param(format!("SVF Filter Mode{suffix}"), FilterMode::Band),
+            mix: float_param(
+                format!("Mix{suffix}"),
                 1.,
                 FloatRange::Linear { min: 0., max: 1. },
             )
-            .with_smoother(SmoothingStyle::Linear(50.)),
+            .with_smoother(response.style())
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            keytrack: float_param(
+                format!("SVF Keytrack{suffix}"),
+                0.,
+                FloatRange::Linear { min: 0., max: 1. },
+            )
+            .with_smoother(response.style())
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+        }
+    }
+
+    /// Snap all the smoothers to their current values
+    pub fn reset_smoothers(&self) {
+        for param in [&self.cutoff, &self.res, &self.mix, &self.keytrack] {
+            param.smoothed.reset(param.value());
+        }
+    }
+}
+
+#[derive(Params)]
+pub struct FilterParams {
+    #[nested]
+    pub channels: Channels<ChannelFilterParams>,
+    #[id = "svf_stereo_mode"]
+    pub svf_stereo_mode: EnumParam<SVFStereoMode>,
+    /// Skip the filters on the input signal
+    #[id = "input_filter_bypass"]
+    pub input_filter_bypass: BoolParam,
+    /// Skip the filters in the feedback loop
+    #[id = "feedback_filter_bypass"]
+    pub feedback_filter_bypass: BoolParam,
+    /// Make up for the level the filters add or take away, so the resonance doesn't push the feedback loop around
+    #[id = "gain_staging"]
+    pub gain_staging: BoolParam,
+    /// Turn the filters down as the resonance goes up, so the resonant peak stays at the level of the input
+    #[id = "res_compensation"]
+    pub res_compensation: BoolParam,
+    /// How hard the repeats are driven into the feedback filters, which soft clip inside
+    #[id = "svf_drive"]
+    pub drive: FloatParam,
+    /// Whether every filter is a single SVF or two of them in a row
+    #[id = "svf_slope"]
+    pub slope: EnumParam<FilterSlope>,
+}
+
+impl FilterParams {
+    pub fn new(response: &Response) -> Self {
+        Self {
+            channels: Channels(std::array::from_fn(|channel| {
+                ChannelFilterParams::new(channel, response)
+            })),
+            svf_stereo_mode: enum_param("SVF Seperated", SVFStereoMode::Mono),
+            input_filter_bypass: bool_param("Input Filter Bypass", false),
+            feedback_filter_bypass: bool_param("Feedback Filter Bypass", false),
+            gain_staging: bool_param("Gain Staging", false),
+            res_compensation: bool_param("Compensated Resonance", false),
+            drive: float_param(
+                "SVF Drive",
+                0.,
+                FloatRange::Linear {
+                    min: 0.,
+                    max: MAX_DRIVE_DB,
+                },
+            )
+            .with_smoother(response.style())
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
+            slope: enum_param("SVF Slope", FilterSlope::Db12),
         }
     }
+
+    /// Get the next smoothed value of a parameter for every channel.
+    ///
+    /// `stereo` is how far the switch to [SVFStereoMode::Stereo] is, see [Channels::next_blended()].
+    pub fn next_per_channel(
+        &self,
+        param: impl Fn(&ChannelFilterParams) -> &FloatParam,
+        stereo: f32,
+    ) -> [f32; NUM_CHANNELS] {
+        self.channels.next_blended(param, stereo)
+    }
+
+    /// Get the filter mode of every channel. In mono mode all channels use the mode of the first channel.
+    pub fn modes(&self) -> [SVFFilterMode; NUM_CHANNELS] {
+        match self.svf_stereo_mode.value() {
+            SVFStereoMode::Mono => [self.channels[0].mode.value().into(); NUM_CHANNELS],
+            SVFStereoMode::Stereo => {
+                std::array::from_fn(|channel| self.channels[channel].mode.value().into())
+            }
+        }
+    }
+
+    /// Snap all the smoothers to their current values
+    pub fn reset_smoothers(&self) {
+        self.channels
+            .iter()
+            .for_each(ChannelFilterParams::reset_smoothers);
+        self.drive.smoothed.reset(self.drive.value());
+    }
 }
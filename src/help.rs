@@ -0,0 +1,182 @@
+use crate::strings::Language;
+
+/// Defines the hints of the help overlay with one row per parameter: the ID of the parameter, the English hint and
+/// the German hint.
+macro_rules! help_table {
+    ($($id:literal => $english:literal, $german:literal;)*) => {
+        /// The hints of the help overlay by the ID of their parameter.
+        ///
+        /// The per channel and per band parameters share one hint, so they're listed without the number their
+        /// IDs end in.
+        const HINTS: &[(&str, &str, &str)] = &[$(($id, $english, $german),)*];
+    };
+}
+
+help_table! {
+    // The delay
+    "delay" => "Time between the repeats", "Zeit zwischen den Wiederholungen";
+    "feedback" => "How much of every repeat comes back", "Wie viel jeder Wiederholung zurückkommt";
+    "decay_time" => "Time the repeats take to fall by 60 dB", "Zeit, bis die Wiederholungen um 60 dB abfallen";
+    "bpm_bound" => "Set the delay time as a note length at the host tempo",
+        "Verzögerung als Notenlänge im Tempo des Hosts";
+    "invert" => "Flip the polarity of the wet signal", "Polarität des Effektsignals umkehren";
+    "stereo" => "Separate times and feedback for both channels", "Eigene Zeiten und Feedback für beide Kanäle";
+    "feedback_mode" => "Set the feedback directly or by its decay time",
+        "Feedback direkt oder über die Abklingzeit einstellen";
+    "invert_feedback" => "Flip the polarity of every repeat", "Polarität jeder Wiederholung umkehren";
+    "stability_trim" => "Pull the feedback back before it self oscillates",
+        "Feedback zurücknehmen, bevor es sich aufschaukelt";
+    "hold" => "Repeat what's in the delay forever", "Den Inhalt der Verzögerung endlos wiederholen";
+    "humanize" => "Vary the time and level of every repeat", "Zeit und Pegel jeder Wiederholung variieren";
+    "delay_snap" => "Snap the delay knobs to musical times", "Zeitregler auf musikalische Werte rasten";
+    "note_tracking" => "Play the delay time with MIDI notes", "Verzögerung mit MIDI-Noten spielen";
+    "note_glide" => "How long the time glides to a new note", "Wie lange die Zeit zur neuen Note gleitet";
+    "read_speed" => "Play the delay lines faster or slower, which shifts the pitch",
+        "Verzögerung schneller oder langsamer abspielen, das verschiebt die Tonhöhe";
+    "read_detune" => "Fine tune the read speed in semitones", "Abspielgeschwindigkeit in Halbtönen feinstimmen";
+    "drift_rate" => "How fast the channels drift apart", "Wie schnell die Kanäle auseinanderdriften";
+    "drift_depth" => "How far the channels drift apart", "Wie weit die Kanäle auseinanderdriften";
+    "drift_retrigger" => "Start the drift over on play or every bar", "Drift beim Start oder jedem Takt neu beginnen";
+    "external_return" => "Take the feedback from the aux return", "Feedback vom Aux-Return nehmen";
+    "stop_behavior" => "Whether the repeats ring out when the host stops",
+        "Ob die Wiederholungen beim Stopp ausklingen";
+    "retrigger" => "Restart loop and reverse on play or every bar", "Loop und Reverse beim Start oder jedem Takt neu starten";
+    "tempo_transition" => "How BPM bound times follow tempo changes", "Wie Notenlängen Tempowechseln folgen";
+    // The tape echo and the string
+    "tape_echo" => "Play the repeats back through three tape heads", "Wiederholungen über drei Tonköpfe abspielen";
+    "tape_repeat_rate" => "Time of the first head, the others follow", "Zeit des ersten Kopfes, die anderen folgen";
+    "tape_head1" => "Turn the first tape head on", "Ersten Tonkopf einschalten";
+    "tape_head2" => "Turn the second tape head on", "Zweiten Tonkopf einschalten";
+    "tape_head3" => "Turn the third tape head on", "Dritten Tonkopf einschalten";
+    "string_mode" => "Turn the repeats into a plucked string", "Wiederholungen in eine gezupfte Saite verwandeln";
+    "string_pitch" => "The note the string rings at", "Der Ton, auf dem die Saite klingt";
+    "string_decay" => "How long the string rings", "Wie lange die Saite klingt";
+    "string_brightness" => "How bright the string stays", "Wie hell die Saite bleibt";
+    // The second stage and the loop region
+    "dual_delay" => "Add a second delay stage", "Eine zweite Verzögerung hinzufügen";
+    "dual_routing" => "Run the second stage after or next to the main delay",
+        "Zweite Stufe nach oder neben der Hauptverzögerung";
+    "dual_time" => "Time of the second stage", "Zeit der zweiten Stufe";
+    "dual_feedback" => "Feedback of the second stage", "Feedback der zweiten Stufe";
+    "dual_balance" => "From the main delay to the second stage", "Von der Hauptverzögerung zur zweiten Stufe";
+    "loop_region" => "Loop a slice of the delay lines", "Einen Ausschnitt der Verzögerung loopen";
+    "loop_start" => "How far back the slice starts", "Wie weit zurück der Ausschnitt beginnt";
+    "loop_length" => "How long the slice is", "Wie lang der Ausschnitt ist";
+    "loop_bpm_bound" => "Set the slice as note lengths", "Ausschnitt als Notenlängen einstellen";
+    "loop_input" => "Freeze the delay or keep it running under the loop",
+        "Verzögerung einfrieren oder unter dem Loop weiterlaufen lassen";
+    // The throw
+    "throw" => "Ramp the time and the feedback to the throw targets while held",
+        "Zeit und Feedback beim Halten zu den Wurf-Zielen fahren";
+    "throw_time" => "The delay time the throw ramps to", "Die Zeit, zu der der Wurf fährt";
+    "throw_feedback" => "The feedback the throw ramps to", "Das Feedback, zu dem der Wurf fährt";
+    "throw_ramp" => "How long the throw takes to ramp in and back", "Wie lange der Wurf hin und zurück braucht";
+    "perform_reverse" => "Play the repeats backwards while held, shift click to latch",
+        "Wiederholungen beim Halten rückwärts spielen, mit Umschalt einrasten";
+    "perform_half_speed" => "Play the repeats an octave down while held, shift click to latch",
+        "Wiederholungen beim Halten eine Oktave tiefer spielen, mit Umschalt einrasten";
+    // The filters
+    "svf_cutoff" => "Cutoff of the filter in the feedback", "Frequenz des Filters im Feedback";
+    "svf_res" => "Resonance of the filter", "Resonanz des Filters";
+    "svf_filter_mode" => "Which part of the spectrum the filter keeps", "Welchen Teil des Spektrums der Filter behält";
+    "svf_mix" => "How much of the feedback runs through the filter", "Wie viel des Feedbacks durch den Filter läuft";
+    "svf_keytrack" => "How much the cutoff follows MIDI notes", "Wie sehr die Frequenz MIDI-Noten folgt";
+    "svf_stereo_mode" => "Separate filters for both channels", "Eigene Filter für beide Kanäle";
+    "svf_drive" => "Drive the repeats into the filters", "Wiederholungen in die Filter treiben";
+    "svf_slope" => "How steep the filters fall", "Wie steil die Filter abfallen";
+    "input_filter_bypass" => "Skip the filter on the input", "Den Filter am Eingang überspringen";
+    "feedback_filter_bypass" => "Skip the filters in the feedback", "Die Filter im Feedback überspringen";
+    "gain_staging" => "Keep the filters from changing the level", "Verhindern, dass die Filter den Pegel ändern";
+    "res_compensation" => "Turn the filters down as the resonance rises",
+        "Filter mit steigender Resonanz leiser machen";
+    "stage_order" => "The order the repeats pass the stages of the feedback in",
+        "Reihenfolge, in der die Wiederholungen die Stufen im Feedback durchlaufen";
+    "saturation" => "Softly clip the loud repeats", "Laute Wiederholungen weich begrenzen";
+    "reverb_mix" => "How much of the feedback runs through the reverb", "Wie viel des Feedbacks durch den Hall läuft";
+    // The multiband mode
+    "multiband" => "Give every band its own delay", "Jedem Band eine eigene Verzögerung geben";
+    "multiband_bands" => "Split into two or three bands", "In zwei oder drei Bänder aufteilen";
+    "crossover_low" => "Where the low band ends", "Wo das tiefe Band endet";
+    "crossover_high" => "Where the high band starts", "Wo das hohe Band beginnt";
+    "band_time" => "Time of the band", "Zeit des Bands";
+    "band_feedback" => "Feedback of the band", "Feedback des Bands";
+    "band_solo" => "Only hear the soloed bands", "Nur die Solo-Bänder hören";
+    "band_mute" => "Silence the band, its repeats keep going", "Band stummschalten, die Wiederholungen laufen weiter";
+    // The mix
+    "wetness" => "Balance of the dry and the wet signal", "Verhältnis von Trocken- und Effektsignal";
+    "wet_width" => "Stereo width of the repeats", "Stereobreite der Wiederholungen";
+    "wet_spread" => "Decorrelate the channels of the repeats", "Die Kanäle der Wiederholungen dekorrelieren";
+    "swap_channels" => "Swap left and right of the repeats", "Links und rechts der Wiederholungen tauschen";
+    "ping_pong" => "Bounce the repeats between the channels", "Wiederholungen zwischen den Kanälen springen lassen";
+    "ping_pong_start" => "The side of the first repeat", "Die Seite der ersten Wiederholung";
+    "ping_pong_offset" => "Pan the first repeat towards the center", "Erste Wiederholung zur Mitte schwenken";
+    "auto_gain" => "Keep the output as loud as the input", "Ausgang so laut wie den Eingang halten";
+    "safety_clipper" => "Softly clip the output above 0 dB", "Ausgang über 0 dB weich begrenzen";
+    // The input and the plugin
+    "algorithm" => "A ready made signal flow", "Ein fertiger Signalweg";
+    "input_trim" => "Level of the input", "Pegel des Eingangs";
+    "input_mute" => "Stop feeding the delay", "Die Verzögerung nicht mehr speisen";
+    "bypass" => "Pass the dry signal only", "Nur das Trockensignal durchlassen";
+    "bypass_mode" => "Whether the repeats ring out while bypassed", "Ob die Wiederholungen im Bypass ausklingen";
+    "response" => "How fast the parameters follow changes", "Wie schnell die Parameter Änderungen folgen";
+    "quality" => "Trade precision for CPU", "Präzision gegen Rechenleistung tauschen";
+    "macro1" => "Moves every parameter assigned to it", "Bewegt alle zugewiesenen Parameter";
+    "macro2" => "Moves every parameter assigned to it", "Bewegt alle zugewiesenen Parameter";
+    "scene" => "Fade to a stored scene, switches follow while the editor is open", "Zu einer gespeicherten Szene blenden, Schalter folgen bei offenem Editor";
+    "scene_crossfade" => "How long scenes take to fade", "Wie lange Szenen zum Überblenden brauchen";
+}
+
+/// The hint the help overlay shows next to a parameter, or None if it doesn't have one.
+///
+/// Usage:
+/// ```
+/// use delax::{help::param_help, strings::Language};
+///
+/// // Both channels share the hint of their parameter
+/// assert_eq!(param_help("feedback_2", Language::English), param_help("feedback_1", Language::English));
+/// assert!(param_help("macro1", Language::German).is_some());
+/// assert!(param_help("not_a_param", Language::English).is_none());
+/// ```
+pub fn param_help(param_id: &str, language: Language) -> Option<&'static str> {
+    let hint = |id: &str| HINTS.iter().find(|(hint_id, _, _)| *hint_id == id);
+    // The per channel and per band parameters end in their number, see [crate::params::other_channel_param_id()]
+    let (_, english, german) = hint(param_id).or_else(|| {
+        let (base, number) = param_id.rsplit_once('_')?;
+        number.parse::<usize>().ok().and_then(|_| hint(base))
+    })?;
+
+    Some(match language {
+        Language::English => english,
+        Language::German => german,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_hint_is_translated_once() {
+        for (index, (id, english, german)) in HINTS.iter().enumerate() {
+            assert!(!english.is_empty() && !german.is_empty(), "{id}");
+            assert!(
+                HINTS[..index].iter().all(|(other, _, _)| other != id),
+                "{id} has two hints"
+            );
+        }
+    }
+
+    #[test]
+    fn numbered_ids_fall_back_to_their_parameter() {
+        assert_eq!(
+            param_help("band_time_3", Language::German),
+            Some("Zeit des Bands")
+        );
+        // The number of the tape heads is part of their ID
+        assert_ne!(
+            param_help("tape_head1", Language::English),
+            param_help("tape_head2", Language::English)
+        );
+        assert!(param_help("delay_x", Language::English).is_none());
+    }
+}
@@ -0,0 +1,281 @@
+use std::sync::{
+    atomic::{AtomicU8, Ordering},
+    Arc,
+};
+
+use nih_plug::{prelude::AtomicF32, util};
+
+/// How long the input is measured for, in seconds
+pub const ANALYSIS_SECONDS: f32 = 3.;
+/// The RMS level the input trim aims for, in dBFS. This leaves room for the repeats to build up on top of the input.
+pub const TARGET_RMS_DB: f32 = -18.;
+/// The highest the trimmed input peaks may get, in dBFS. Spiky material is trimmed by its peaks instead of its RMS.
+pub const PEAK_CEILING_DB: f32 = -6.;
+/// The most the input trim boosts or cuts, in dB. This is also the range of the parameter.
+pub const MAX_TRIM_DB: f32 = 24.;
+/// Inputs with a lower RMS level count as silence, there's nothing to suggest a trim for
+const SILENCE_DB: f32 = -70.;
+
+/// Where the measurement of the input currently is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnalysisState {
+    /// Nothing has been measured, or the last result was dismissed
+    Idle,
+    /// The editor asked for a measurement, the audio thread starts it on the next sample
+    Requested,
+    Measuring,
+    /// The levels of the last measurement are ready
+    Done,
+}
+
+impl AnalysisState {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => AnalysisState::Requested,
+            2 => AnalysisState::Measuring,
+            3 => AnalysisState::Done,
+            _ => AnalysisState::Idle,
+        }
+    }
+}
+
+/// The levels of the input over a whole measurement
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InputLevels {
+    pub peak_db: f32,
+    pub rms_db: f32,
+}
+
+impl InputLevels {
+    /// The input trim in dB that brings the input to [TARGET_RMS_DB], or None if the input was silent.
+    ///
+    /// The trim is lowered if the peaks would go above [PEAK_CEILING_DB] and is limited to [MAX_TRIM_DB] either way.
+    ///
+    /// Usage:
+    /// ```
+    /// use delax::input_analysis::InputLevels;
+    ///
+    /// // A quiet input is pulled up to the target level
+    /// let levels = InputLevels { peak_db: -20., rms_db: -30. };
+    /// assert_eq!(levels.suggested_trim_db(), Some(12.));
+    /// // Silence has nothing to trim
+    /// let levels = InputLevels { peak_db: -100., rms_db: -100. };
+    /// assert_eq!(levels.suggested_trim_db(), None);
+    /// ```
+    pub fn suggested_trim_db(&self) -> Option<f32> {
+        if self.rms_db < SILENCE_DB {
+            return None;
+        }
+
+        let trim = (TARGET_RMS_DB - self.rms_db).min(PEAK_CEILING_DB - self.peak_db);
+        Some(trim.clamp(-MAX_TRIM_DB, MAX_TRIM_DB))
+    }
+}
+
+/// The measurement of the input level, shared between the editor and the audio thread.
+///
+/// The editor starts a measurement and polls the result, the [InputAnalyzer] on the audio thread does the measuring.
+pub struct InputAnalysis {
+    state: AtomicU8,
+    /// How far the running measurement is, from 0 to 1
+    progress: AtomicF32,
+    peak_db: AtomicF32,
+    rms_db: AtomicF32,
+}
+
+impl Default for InputAnalysis {
+    fn default() -> Self {
+        Self {
+            state: AtomicU8::new(AnalysisState::Idle as u8),
+            progress: AtomicF32::new(0.),
+            peak_db: AtomicF32::new(util::MINUS_INFINITY_DB),
+            rms_db: AtomicF32::new(util::MINUS_INFINITY_DB),
+        }
+    }
+}
+
+impl InputAnalysis {
+    /// Measure the input from the next sample on. A running measurement starts over.
+    pub fn start(&self) {
+        self.progress.store(0., Ordering::Relaxed);
+        self.state
+            .store(AnalysisState::Requested as u8, Ordering::Release);
+    }
+
+    /// Forget the result of the last measurement
+    pub fn dismiss(&self) {
+        let _ = self.state.compare_exchange(
+            AnalysisState::Done as u8,
+            AnalysisState::Idle as u8,
+            Ordering::AcqRel,
+            Ordering::Relaxed,
+        );
+    }
+
+    pub fn state(&self) -> AnalysisState {
+        AnalysisState::from_u8(self.state.load(Ordering::Acquire))
+    }
+
+    /// How far the running measurement is, from 0 to 1
+    pub fn progress(&self) -> f32 {
+        self.progress.load(Ordering::Relaxed)
+    }
+
+    /// The levels of the last measurement, once it's done
+    pub fn levels(&self) -> Option<InputLevels> {
+        (self.state() == AnalysisState::Done).then(|| InputLevels {
+            peak_db: self.peak_db.load(Ordering::Relaxed),
+            rms_db: self.rms_db.load(Ordering::Relaxed),
+        })
+    }
+}
+
+/// Measures the peak and RMS level of the input on the audio thread, whenever the editor asks for it through the [InputAnalysis].
+///
+/// Usage:
+/// ```
+/// use std::sync::Arc;
+/// use delax::input_analysis::{AnalysisState, InputAnalysis, InputAnalyzer};
+///
+/// let analysis = Arc::new(InputAnalysis::default());
+/// let mut analyzer = InputAnalyzer::new(analysis.clone(), 1000.);
+/// analysis.start();
+/// for _ in 0..3000 {
+///     analyzer.process(&[0.5, -0.5]);
+/// }
+///
+/// assert_eq!(analysis.state(), AnalysisState::Done);
+/// let levels = analysis.levels().unwrap();
+/// assert!((levels.rms_db + 6.02).abs() < 0.01);
+/// ```
+pub struct InputAnalyzer {
+    analysis: Arc<InputAnalysis>,
+    /// The length of a measurement in frames
+    length: usize,
+    /// The frames measured so far
+    frames: usize,
+    peak: f32,
+    sum_squares: f64,
+    samples: usize,
+}
+
+impl InputAnalyzer {
+    pub fn new(analysis: Arc<InputAnalysis>, sample_rate: f32) -> Self {
+        Self {
+            analysis,
+            length: measurement_length(sample_rate),
+            frames: 0,
+            peak: 0.,
+            sum_squares: 0.,
+            samples: 0,
+        }
+    }
+
+    /// The measurement shared with the editor
+    pub fn analysis(&self) -> &Arc<InputAnalysis> {
+        &self.analysis
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.length = measurement_length(sample_rate);
+    }
+
+    /// Feed a frame of the input, with one sample per channel
+    pub fn process(&mut self, frame: &[f32]) {
+        match self.analysis.state() {
+            AnalysisState::Requested => {
+                self.reset();
+                self.analysis
+                    .state
+                    .store(AnalysisState::Measuring as u8, Ordering::Release);
+            }
+            AnalysisState::Measuring => (),
+            AnalysisState::Idle | AnalysisState::Done => return,
+        }
+
+        for sample in frame {
+            self.peak = self.peak.max(sample.abs());
+            self.sum_squares += (*sample as f64) * (*sample as f64);
+        }
+        self.samples += frame.len();
+        self.frames += 1;
+        self.analysis
+            .progress
+            .store(self.frames as f32 / self.length as f32, Ordering::Relaxed);
+
+        if self.frames >= self.length {
+            let rms = (self.sum_squares / self.samples.max(1) as f64).sqrt() as f32;
+            self.analysis
+                .peak_db
+                .store(util::gain_to_db(self.peak), Ordering::Relaxed);
+            self.analysis
+                .rms_db
+                .store(util::gain_to_db(rms), Ordering::Relaxed);
+            // A new request in the meantime starts over instead
+            let _ = self.analysis.state.compare_exchange(
+                AnalysisState::Measuring as u8,
+                AnalysisState::Done as u8,
+                Ordering::AcqRel,
+                Ordering::Relaxed,
+            );
+        }
+    }
+
+    /// Forget the levels measured so far. A running measurement continues from scratch.
+    pub fn reset(&mut self) {
+        self.frames = 0;
+        self.peak = 0.;
+        self.sum_squares = 0.;
+        self.samples = 0;
+    }
+}
+
+fn measurement_length(sample_rate: f32) -> usize {
+    ((ANALYSIS_SECONDS * sample_rate) as usize).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nothing_is_measured_until_requested() {
+        let analysis = Arc::new(InputAnalysis::default());
+        let mut analyzer = InputAnalyzer::new(analysis.clone(), 100.);
+        for _ in 0..1000 {
+            analyzer.process(&[1., 1.]);
+        }
+        assert_eq!(analysis.state(), AnalysisState::Idle);
+        assert_eq!(analysis.levels(), None);
+
+        analysis.start();
+        analyzer.process(&[0.25, 0.25]);
+        assert_eq!(analysis.state(), AnalysisState::Measuring);
+        assert!(analysis.progress() > 0.);
+        for _ in 1..300 {
+            analyzer.process(&[0.25, -0.25]);
+        }
+        let levels = analysis.levels().unwrap();
+        assert!((levels.peak_db - util::gain_to_db(0.25)).abs() < 1e-4);
+
+        analysis.dismiss();
+        assert_eq!(analysis.state(), AnalysisState::Idle);
+    }
+
+    #[test]
+    fn peaks_limit_the_trim() {
+        // A very spiky input would clip with the full RMS trim
+        let levels = InputLevels {
+            peak_db: -3.,
+            rms_db: -30.,
+        };
+        assert_eq!(levels.suggested_trim_db(), Some(PEAK_CEILING_DB + 3.));
+
+        // Loud inputs are turned down, but never by more than the range of the trim
+        let levels = InputLevels {
+            peak_db: 30.,
+            rms_db: 20.,
+        };
+        assert_eq!(levels.suggested_trim_db(), Some(-MAX_TRIM_DB));
+    }
+}
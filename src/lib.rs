@@ -1,77 +1,437 @@
+#[cfg(feature = "plugin")]
+use algorithm::{LOFI_BITS, LOFI_RATE};
+#[cfg(feature = "plugin")]
+use audition::{Audition, AuditionPlayer};
+#[cfg(feature = "plugin")]
+use auto_gain::AutoGain;
+#[cfg(all(feature = "plugin", feature = "glitch"))]
+use delay_engine::glitch::GlitchStage;
+#[cfg(feature = "plugin")]
 use delay_engine::{
-    engine::{DelayEngine, DelayInterpolationMode},
-    params::DelayMode,
+    drift::StereoDrift,
+    dual::{DualMix, DualStage},
+    engine::{interpolation_for, max_delay_buffer_size, DelayEngine, DelayInterpolationMode},
+    feedback_matrix::FeedbackMatrix,
+    multihead::read_heads,
+    params::{DelayMode, PingPongStart, NUM_CHANNELS},
+    snap::DelaySnap,
+    stability::LoopGain,
+    string::{decay_gain, StringVoice},
+    tempo_lock::{synced_delay_ms, TempoLock},
+    throw::Throw,
+    transport::{TransportFollower, TransportInfo},
 };
-use filter_pipeline::pipeline::FilterPipeline;
-use filters::{dattorro::DattorroReverb, simper::SimperSinSVF};
+#[cfg(feature = "plugin")]
+use filter_pipeline::{
+    pipeline::FilterPipeline,
+    stages::{saturate, FeedbackStage, StageSwitch},
+};
+#[cfg(all(feature = "plugin", feature = "reverb"))]
+use filters::dattorro::DattorroReverb;
+#[cfg(feature = "plugin")]
+use filters::{
+    driven::DrivenSVF,
+    params::SVFStereoMode,
+    simper::StereoSimperSinSVF,
+    spread::StereoSpread,
+    stack::{SVFStack, Slope},
+    SVFFilterMode, StereoFilter,
+};
+#[cfg(feature = "plugin")]
+use input_analysis::{InputAnalysis, InputAnalyzer};
+#[cfg(feature = "plugin")]
+use lofi::Decimator;
+#[cfg(feature = "plugin")]
+use macros::MacroEngine;
+#[cfg(feature = "plugin")]
+use midi::{
+    cc_learn::{CcEngine, CcRouter},
+    note_tracker::NoteTracker,
+    poly_mod::{PolyModTarget, PolyModulation, MAX_VOICES},
+};
+#[cfg(all(feature = "plugin", feature = "multiband"))]
+use multiband::stage::MultibandStage;
+#[cfg(feature = "plugin")]
 use nih_plug::prelude::*;
-use params::DelaxParams;
-use peak_follower::PeakFollower;
+#[cfg(feature = "plugin")]
+use params::{BypassMode, DelaxParams};
+#[cfg(feature = "plugin")]
+use sample::FeedbackSample;
+#[cfg(feature = "plugin")]
+use sanitize::{safety_clip, SAFETY_KNEE};
+#[cfg(feature = "plugin")]
+use scenes::SceneEngine;
+#[cfg(feature = "plugin")]
+use simd::F32x2;
+#[cfg(feature = "plugin")]
+use smoothing::Gate;
+#[cfg(feature = "plugin")]
 use std::sync::{Arc, Mutex};
-use ui::InputData;
+#[cfg(feature = "plugin")]
+use tasks::{Task, TaskRunner};
+#[cfg(feature = "plugin")]
+use visualization::VisualizationProducer;
 
-mod delay_engine;
-mod filter_pipeline;
+#[cfg(feature = "plugin")]
+pub mod algorithm;
+#[cfg(feature = "plugin")]
+pub mod audition;
+#[cfg(feature = "plugin")]
+pub mod auto_gain;
+pub mod delay_engine;
+#[cfg(feature = "dsp")]
+pub mod dsp;
+#[cfg(feature = "plugin")]
+pub mod editor_settings;
+pub mod filter_pipeline;
 pub mod filters;
+#[cfg(feature = "plugin")]
+pub mod help;
+#[cfg(feature = "plugin")]
+pub mod input_analysis;
+#[cfg(feature = "plugin")]
+pub mod macros;
+#[cfg(feature = "plugin")]
+pub mod midi;
+pub mod multiband;
+#[cfg(feature = "plugin")]
+pub mod offline;
+#[cfg(feature = "plugin")]
+pub mod param_changes;
+#[cfg(feature = "plugin")]
+pub mod param_snapshot;
+#[cfg(feature = "plugin")]
 mod params;
+#[cfg(feature = "plugin")]
 mod peak_follower;
+#[cfg(feature = "plugin")]
+pub mod quality;
+pub mod sample;
+#[cfg(feature = "plugin")]
+pub mod scenes;
+#[cfg(feature = "plugin")]
+pub mod settings_file;
+#[cfg(feature = "plugin")]
+pub mod smoothing;
+#[cfg(feature = "plugin")]
+mod state;
+#[cfg(feature = "plugin")]
+pub mod strings;
+#[cfg(feature = "plugin")]
+pub mod tasks;
+#[cfg(feature = "plugin")]
 mod ui;
+#[cfg(feature = "plugin")]
+pub mod visualization;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "plugin")]
+pub mod wav;
+
+pub use delax_dsp::{lofi, random, sanitize, simd};
+
+/// The time the feedback takes to move between the hold and the normal feedback amount, in ms
+#[cfg(feature = "plugin")]
+const HOLD_RELEASE_MS: f32 = 50.;
 
+/// The time the stability trim takes to follow the estimated loop gain, in ms
+#[cfg(feature = "plugin")]
+const FEEDBACK_TRIM_MS: f32 = 50.;
+
+/// The time the input and the repeats take to fade in and out when they're muted, in ms
+#[cfg(feature = "plugin")]
+const GATE_FADE_MS: f32 = 10.;
+/// The time in ms the per channel parameters fade over when the delay or the filters switch between mono and stereo
+#[cfg(feature = "plugin")]
+const STEREO_FADE_MS: f32 = 20.;
+
+/// The names of the aux ports that insert external plugins into the feedback loop
+#[cfg(feature = "plugin")]
+const FEEDBACK_SEND_NAMES: &[&str] = &["Feedback Send"];
+#[cfg(feature = "plugin")]
+const FEEDBACK_RETURN_NAMES: &[&str] = &["Feedback Return"];
+/// The amount of channels of the 5.1 layout
+#[cfg(feature = "plugin")]
+const SURROUND_CHANNELS: usize = 6;
+/// The LFE channel of the 5.1 layout, which is passed through instead of delayed
+#[cfg(feature = "plugin")]
+const LFE_CHANNEL: usize = 3;
+
+/// Every channel gets its own seed for the humanize variation, so the channels don't vary in lockstep
+#[cfg(feature = "plugin")]
+fn humanize_seed(channel: usize) -> u32 {
+    channel as u32 + 1
+}
+
+/// A filter of a pair of channels that's shared between the plugin and its pipeline
+#[cfg(feature = "plugin")]
+type SharedFilter = Arc<Mutex<SVFStack<DrivenSVF<StereoSimperSinSVF<FeedbackSample>>>>>;
+
+/// A stereo filter for every pair of channels, each registered in its own pipeline.
+/// The filters keep their state at the precision of the feedback path.
+///
+/// The pipelines hold the same instances that are returned, so settings and sample rate changes reach them directly.
+#[cfg(feature = "plugin")]
+fn filter_bank(num_pairs: usize, sample_rate: f32) -> (Vec<SharedFilter>, Vec<FilterPipeline>) {
+    (0..num_pairs)
+        .map(|_| {
+            let filter = Arc::new(Mutex::new(SVFStack::new(
+                DrivenSVF::new(StereoSimperSinSVF::with_precision(sample_rate)),
+                sample_rate,
+            )));
+            let mut pipeline = FilterPipeline::new();
+            pipeline.register_stereo(filter.clone());
+            (filter, pipeline)
+        })
+        .unzip()
+}
+
+/// Apply the resolved filter settings of the left and right channel to every pair of channels
+#[cfg(feature = "plugin")]
+fn apply_filter_settings(
+    filters: &[SharedFilter],
+    cutoff: F32x2,
+    res: F32x2,
+    [mode_l, mode_r]: [SVFFilterMode; 2],
+    compensated: bool,
+    drive: f32,
+    slope: Slope,
+) {
+    for filter in filters {
+        let mut filter = filter.lock().unwrap();
+        filter.set_slope(slope);
+        for stage in filter.stages_mut() {
+            stage.set_drive(drive);
+            let svf = stage.filter_mut();
+            svf.set_cutoff_res(cutoff, res);
+            svf.set_compensated(compensated);
+        }
+        // Switching the mode jumps between the outputs of the SVF, so it's crossfaded to not click
+        if filter.first().filter().mode() != [mode_l, mode_r] {
+            filter.crossfade(|stage| stage.filter_mut().set_mode(mode_l, mode_r));
+        }
+    }
+}
+
+#[cfg(feature = "plugin")]
 pub struct Delax {
     params: Arc<DelaxParams>,
-    left_delay_engine: DelayEngine,
-    right_delay_engine: DelayEngine,
+    /// One delay line per audio channel
+    delay_engines: Vec<DelayEngine>,
+    /// Routes the outputs of the delay lines back into their inputs
+    feedback_matrix: FeedbackMatrix,
+    /// Routes the input signal into the delay lines
+    injection_matrix: FeedbackMatrix,
+    /// The feedback routing of the ping pong mode, which replaces the feedback matrix while it's on
+    ping_pong_matrix: FeedbackMatrix,
+    /// The injection of the ping pong mode, it follows the start parameters
+    ping_pong_injection: FeedbackMatrix,
+    /// Scratch space for the outputs of the delay lines of the current frame
+    popped: Vec<f32>,
+    /// Scratch space for the routed feedback of the current frame
+    feedbacked: Vec<f32>,
+    /// Scratch space for the routed input of the current frame
+    injected: Vec<f32>,
+    /// The feedback of the current frame that goes out of the aux send
+    sent: Vec<f32>,
+    /// The feedback of the current frame that came back in from the aux return
+    returned: Vec<f32>,
+    /// The channel that's passed through without any processing, the LFE of a 5.1 layout
+    passthrough: Option<usize>,
+    /// Scratch space for the samples of the current frame in [Plugin::process()]
+    frame: Vec<f32>,
     sample_rate: f32,
-    sin_svf_l: SimperSinSVF,
-    sin_svf_r: SimperSinSVF,
-    input_sin_svf_l: SimperSinSVF,
-    input_sin_svf_r: SimperSinSVF,
-    filter_pipeline: FilterPipeline,
-    initial_filter_pipeline: FilterPipeline,
-    datorro: DattorroReverb,
+    /// The feedback filters of every pair of channels. These are the instances registered in the pipelines.
+    sin_svfs: Vec<SharedFilter>,
+    /// The input filters of every pair of channels. These are the instances registered in the pipelines.
+    input_sin_svfs: Vec<SharedFilter>,
+    /// The feedback filters, one pipeline per pair of channels
+    filter_pipelines: Vec<FilterPipeline>,
+    /// The input filters, one pipeline per pair of channels
+    initial_filter_pipelines: Vec<FilterPipeline>,
+    /// The reverbs in the feedback loop, one per pair of channels
+    #[cfg(feature = "reverb")]
+    reverbs: Vec<DattorroReverb>,
+    #[cfg(feature = "reverb")]
     initial_dattorro: DattorroReverb,
-    input_data: Arc<InputData>,
-    peak_follower_in_l: PeakFollower,
-    peak_follower_in_r: PeakFollower,
-    peak_follower_out_l: PeakFollower,
-    peak_follower_out_r: PeakFollower,
+    /// Moves the stages of the feedback loop to the order of the parameter without clicking
+    stage_switch: StageSwitch,
+    /// Collects the meter levels, waveform and spectrum for the editor
+    visualization: VisualizationProducer,
+    /// Measures the input level when the editor asks for a trim suggestion
+    input_analyzer: InputAnalyzer,
+    /// Plays the audition loop into the input while the host is stopped
+    audition_player: AuditionPlayer,
+    /// Passes the incoming MIDI CCs on to the GUI, which sets the mapped parameters
+    cc_router: Arc<CcRouter>,
+    /// Moves the smoothers of the parameters mapped to MIDI CCs
+    cc_engine: CcEngine,
+    /// Runs the file IO for the editor in the background and holds on to the results
+    tasks: Arc<TaskRunner>,
+    /// Morphs the parameters assigned to the macro knobs
+    macro_engine: MacroEngine,
+    /// Crossfades the parameters to the active scene
+    scene_engine: SceneEngine,
+    /// Snaps the free delay times to a grid while the snap parameter is on
+    delay_snap: DelaySnap,
+    /// Turns the incoming notes into a delay time when note tracking is on
+    note_tracker: NoteTracker,
+    /// The per voice modulation of the host, summed into a single offset per parameter
+    poly_mod: PolyModulation,
+    /// How far the feedback is pushed towards 1 by the hold parameter
+    hold: Smoother<f32>,
+    /// The last value of the hold parameter, so the smoother only gets a new target when it changes
+    held: bool,
+    /// Ramps the delay time and the feedback towards their targets while the throw is engaged
+    throw: Throw,
+    /// Scales the feedback back while the stability trim is on and the loop would build up, see [LoopGain::trim()]
+    feedback_trim: Smoother<f32>,
+    /// Fades the input of the delay lines out while bypassed or muted
+    input_gate: Gate,
+    /// Fades the repeats out while bypassed or muted in [BypassMode::Cut]
+    repeat_gate: Gate,
+    /// Fades the dry signal up to full level while bypassed
+    bypass_gate: Gate,
+    /// Fades the delay parameters between the first channel and every channel's own when the delay mode changes
+    delay_stereo: Gate,
+    /// Fades the filter parameters between the first channel and every channel's own when the filter mode changes
+    filter_stereo: Gate,
+    /// Clears the delay lines when the host stops or a retrigger is due
+    transport_follower: TransportFollower,
+    /// Starts the drift over when its retrigger is due
+    drift_follower: TransportFollower,
+    /// The excitation and damping of every delay line in string mode
+    strings: Vec<StringVoice>,
+    /// The second delay line of every channel, for the dual delay
+    dual_stages: Vec<DualStage>,
+    /// Fades the second delay stages in and out with their switch
+    dual_gate: Gate,
+    /// The loop region and the reverse button of every channel
+    #[cfg(feature = "glitch")]
+    glitch: GlitchStage,
+    /// Crush the feedback of every channel in the Lo-Fi algorithm
+    decimators: Vec<Decimator>,
+    /// Whether the dual delay was on at the last sample, so its delay lines are emptied when it's turned on again
+    dual_enabled: bool,
+    /// The band delays of every channel, which replace the main delay in multiband mode
+    #[cfg(feature = "multiband")]
+    multiband: MultibandStage,
+    /// Decorrelates the wet signal of every pair of channels
+    spreads: Vec<StereoSpread>,
+    /// Fades the spread in and out, so the short delay of its allpasses is only there while it's used
+    spread_gate: Gate,
+    /// Locks the BPM bound delay times to the host tempo
+    tempo_lock: TempoLock,
+    /// Moves the reads of the left and the right channels apart
+    drift: StereoDrift,
+    /// Keeps the output as loud as the input while it's on
+    auto_gain: AutoGain,
+    /// The delay times in ms that are faded out while the tempo lock crossfades to a new tempo
+    fade_from: Vec<f32>,
+    /// The feedback gain of every channel for the current frame, resolved from the feedback mode by update_params()
+    feedbacks: Vec<f32>,
+    /// How far the crossfade to the current delay times is, 1 if there is none
+    fade_mix: f32,
+    /// The samples since the filters were last retuned, see [quality::Quality::control_interval()]
+    control_counter: usize,
 }
 
+#[cfg(feature = "plugin")]
 impl Default for Delax {
     fn default() -> Self {
-        let mut left_delay_engine = DelayEngine::new(44100, 44100.);
-        left_delay_engine.set_delay_amount(0.);
-        let mut right_delay_engine = DelayEngine::new(44100, 44100.);
-        right_delay_engine.set_delay_amount(0.);
-
-        let input_sin_svf_l = SimperSinSVF::new(44100.);
-        let input_sin_svf_r = SimperSinSVF::new(44100.);
+        let delay_engines = (0..NUM_CHANNELS)
+            .map(|channel| {
+                let mut delay_engine = DelayEngine::new(max_delay_buffer_size(44100.), 44100.);
+                delay_engine.set_delay_amount(0.);
+                delay_engine.set_humanize_seed(humanize_seed(channel));
+                delay_engine
+            })
+            .collect();
 
-        let sin_svf_l = SimperSinSVF::new(44100.);
-        let sin_svf_r = SimperSinSVF::new(44100.);
+        let (sin_svfs, filter_pipelines) = filter_bank(NUM_CHANNELS / 2, 44100.);
+        let (input_sin_svfs, initial_filter_pipelines) = filter_bank(NUM_CHANNELS / 2, 44100.);
 
         Self {
             params: Arc::new(DelaxParams::default()),
-            left_delay_engine,
-            right_delay_engine,
+            delay_engines,
+            feedback_matrix: FeedbackMatrix::identity(NUM_CHANNELS),
+            injection_matrix: FeedbackMatrix::identity(NUM_CHANNELS),
+            ping_pong_matrix: FeedbackMatrix::ping_pong(NUM_CHANNELS),
+            ping_pong_injection: FeedbackMatrix::identity(NUM_CHANNELS),
+            popped: vec![0.; NUM_CHANNELS],
+            feedbacked: vec![0.; NUM_CHANNELS],
+            injected: vec![0.; NUM_CHANNELS],
+            sent: vec![0.; NUM_CHANNELS],
+            returned: vec![0.; NUM_CHANNELS],
+            passthrough: None,
+            frame: vec![0.; NUM_CHANNELS],
             sample_rate: 44100.,
-            sin_svf_l,
-            sin_svf_r,
-            input_sin_svf_l,
-            input_sin_svf_r,
-            filter_pipeline: FilterPipeline::new(),
-            initial_filter_pipeline: FilterPipeline::new(),
-            datorro: DattorroReverb::new(44100., 0.5),
+            sin_svfs,
+            input_sin_svfs,
+            filter_pipelines,
+            initial_filter_pipelines,
+            #[cfg(feature = "reverb")]
+            reverbs: (0..NUM_CHANNELS / 2)
+                .map(|_| DattorroReverb::new(44100., 0.5))
+                .collect(),
+            #[cfg(feature = "reverb")]
             initial_dattorro: DattorroReverb::new(44100., 0.5),
-            input_data: Arc::new(InputData::default()),
-            peak_follower_in_l: PeakFollower::new(2., 0.2, 44100., 10),
-            peak_follower_in_r: PeakFollower::new(2., 0.2, 44100., 10),
-            peak_follower_out_l: PeakFollower::new(2., 0.2, 44100., 10),
-            peak_follower_out_r: PeakFollower::new(2., 0.2, 44100., 10),
+            stage_switch: StageSwitch::new(Default::default()),
+            visualization: VisualizationProducer::new(44100.),
+            input_analyzer: InputAnalyzer::new(Arc::new(InputAnalysis::default()), 44100.),
+            audition_player: AuditionPlayer::new(Arc::new(Audition::default()), 44100.),
+            cc_router: Arc::new(CcRouter::default()),
+            cc_engine: CcEngine::default(),
+            tasks: Arc::new(TaskRunner::default()),
+            macro_engine: MacroEngine::default(),
+            scene_engine: SceneEngine::default(),
+            delay_snap: DelaySnap::default(),
+            note_tracker: NoteTracker::new(44100.),
+            poly_mod: PolyModulation::default(),
+            hold: Smoother::new(SmoothingStyle::Linear(HOLD_RELEASE_MS)),
+            held: false,
+            throw: Throw::default(),
+            feedback_trim: {
+                let trim = Smoother::new(SmoothingStyle::Linear(FEEDBACK_TRIM_MS));
+                trim.reset(1.);
+                trim
+            },
+            input_gate: Gate::new(GATE_FADE_MS, true),
+            repeat_gate: Gate::new(GATE_FADE_MS, true),
+            bypass_gate: Gate::new(GATE_FADE_MS, false),
+            delay_stereo: Gate::new(STEREO_FADE_MS, false),
+            filter_stereo: Gate::new(STEREO_FADE_MS, false),
+            transport_follower: TransportFollower::default(),
+            drift_follower: TransportFollower::default(),
+            strings: (0..NUM_CHANNELS)
+                .map(|_| StringVoice::new(44100.))
+                .collect(),
+            dual_stages: (0..NUM_CHANNELS).map(|_| DualStage::new(44100.)).collect(),
+            #[cfg(feature = "glitch")]
+            glitch: GlitchStage::new(NUM_CHANNELS),
+            decimators: (0..NUM_CHANNELS)
+                .map(|_| Decimator::new(44100., LOFI_RATE, LOFI_BITS))
+                .collect(),
+            dual_gate: Gate::new(GATE_FADE_MS, false),
+            dual_enabled: false,
+            #[cfg(feature = "multiband")]
+            multiband: MultibandStage::new(44100., NUM_CHANNELS),
+            spreads: (0..NUM_CHANNELS / 2)
+                .map(|_| StereoSpread::new(44100.))
+                .collect(),
+            spread_gate: Gate::new(GATE_FADE_MS, false),
+            tempo_lock: TempoLock::new(44100.),
+            drift: StereoDrift::new(44100.),
+            auto_gain: AutoGain::new(44100.),
+            fade_from: vec![0.; NUM_CHANNELS],
+            feedbacks: vec![0.; NUM_CHANNELS],
+            fade_mix: 1.,
+            control_counter: 0,
         }
     }
 }
 
+#[cfg(feature = "plugin")]
 impl Plugin for Delax {
     const NAME: &'static str = "Delax";
     const VENDOR: &'static str = "Ava Wallenfang";
@@ -82,20 +442,56 @@ impl Plugin for Delax {
 
     // The first audio IO layout is used as the default. The other layouts may be selected either
     // explicitly or automatically by the host or the user depending on the plugin API/backend.
-    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[AudioIOLayout {
-        main_input_channels: NonZeroU32::new(2),
-        main_output_channels: NonZeroU32::new(2),
+    // Every layout needs an even amount of channels, since the filters process the channels in pairs.
+    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(2),
+            main_output_channels: NonZeroU32::new(2),
+
+            aux_input_ports: &[new_nonzero_u32(2)],
+            aux_output_ports: &[new_nonzero_u32(2)],
+
+            // Individual ports and the layout as a whole can be named here. By default these names
+            // are generated as needed. This layout will be called 'Stereo', while a layout with
+            // only one input and output channel would be called 'Mono'.
+            names: PortNames {
+                aux_inputs: FEEDBACK_RETURN_NAMES,
+                aux_outputs: FEEDBACK_SEND_NAMES,
+                ..PortNames::const_default()
+            },
+        },
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(4),
+            main_output_channels: NonZeroU32::new(4),
 
-        aux_input_ports: &[],
-        aux_output_ports: &[],
+            aux_input_ports: &[new_nonzero_u32(4)],
+            aux_output_ports: &[new_nonzero_u32(4)],
 
-        // Individual ports and the layout as a whole can be named here. By default these names
-        // are generated as needed. This layout will be called 'Stereo', while a layout with
-        // only one input and output channel would be called 'Mono'.
-        names: PortNames::const_default(),
-    }];
+            names: PortNames {
+                layout: Some("Quad"),
+                aux_inputs: FEEDBACK_RETURN_NAMES,
+                aux_outputs: FEEDBACK_SEND_NAMES,
+                ..PortNames::const_default()
+            },
+        },
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(6),
+            main_output_channels: NonZeroU32::new(6),
 
-    const MIDI_INPUT: MidiConfig = MidiConfig::None;
+            aux_input_ports: &[new_nonzero_u32(6)],
+            aux_output_ports: &[new_nonzero_u32(6)],
+
+            names: PortNames {
+                layout: Some("5.1"),
+                aux_inputs: FEEDBACK_RETURN_NAMES,
+                aux_outputs: FEEDBACK_SEND_NAMES,
+                ..PortNames::const_default()
+            },
+        },
+    ];
+
+    // CCs are needed for MIDI learn, notes for note tracking
+    const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
     const MIDI_OUTPUT: MidiConfig = MidiConfig::None;
 
     const SAMPLE_ACCURATE_AUTOMATION: bool = true;
@@ -104,66 +500,54 @@ impl Plugin for Delax {
     // messages here. The type implements the `SysExMessage` trait, which allows conversion to and
     // from plain byte buffers.
     type SysExMessage = ();
-    // More advanced plugins can use this to run expensive background tasks. See the field's
-    // documentation for more information. `()` means that the plugin does not have any background
-    // tasks.
-    type BackgroundTask = ();
+    // Everything that touches the disk runs as a background task, so neither the audio thread nor the GUI stall
+    type BackgroundTask = Task;
 
     fn params(&self) -> Arc<dyn Params> {
         self.params.clone()
     }
 
-    fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
+    fn filter_state(state: &mut PluginState) {
+        state::migrate(state);
+    }
+
+    fn task_executor(&mut self) -> TaskExecutor<Self> {
+        let tasks = self.tasks.clone();
+        Box::new(move |task| tasks.run(task))
+    }
+
+    fn editor(&mut self, async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
         ui::create(
             self.params.clone(),
             self.params.editor_state.clone(),
-            self.input_data.clone(),
+            self.visualization.bus().clone(),
+            self.cc_router.clone(),
+            self.input_analyzer.analysis().clone(),
+            self.audition_player.audition().clone(),
+            self.tasks.clone(),
+            async_executor,
         )
     }
 
     fn initialize(
         &mut self,
-        _audio_io_layout: &AudioIOLayout,
+        audio_io_layout: &AudioIOLayout,
         buffer_config: &BufferConfig,
         _context: &mut impl InitContext<Self>,
     ) -> bool {
         // Resize buffers and perform other potentially expensive initialization operations here.
         // The `reset()` function is always called right after this function. You can remove this
         // function if you do not need it.
-        self.sample_rate = buffer_config.sample_rate;
-
-        let mut left_delay_engine = DelayEngine::new(self.sample_rate as usize, self.sample_rate);
-        left_delay_engine.set_delay_amount(0.);
-        let mut right_delay_engine = DelayEngine::new(self.sample_rate as usize, self.sample_rate);
-        right_delay_engine.set_delay_amount(0.);
-
-        self.left_delay_engine = left_delay_engine;
-        self.right_delay_engine = right_delay_engine;
-
-        self.sin_svf_l.set_sample_rate(self.sample_rate);
-        self.sin_svf_r.set_sample_rate(self.sample_rate);
-        self.input_sin_svf_l.set_sample_rate(self.sample_rate);
-        self.input_sin_svf_r.set_sample_rate(self.sample_rate);
-
-        self.datorro.set_sample_rate(self.sample_rate);
-        self.initial_dattorro.set_sample_rate(self.sample_rate);
-
-        self.filter_pipeline.register_stereo_pair(
-            Arc::new(Mutex::new(self.sin_svf_l.clone())),
-            Arc::new(Mutex::new(self.sin_svf_r.clone())),
-        );
-        self.initial_filter_pipeline.register_stereo_pair(
-            Arc::new(Mutex::new(self.input_sin_svf_l.clone())),
-            Arc::new(Mutex::new(self.input_sin_svf_r.clone())),
-        );
-
-        self.peak_follower_in_l.set_sample_rate(self.sample_rate);
-        self.peak_follower_in_r.set_sample_rate(self.sample_rate);
-        self.peak_follower_out_l.set_sample_rate(self.sample_rate);
-        self.peak_follower_out_r.set_sample_rate(self.sample_rate);
-
-        // self.filter_pipeline.register_stereo(Arc::new(Mutex::new(self.datorro.clone())));
-        // self.initial_filter_pipeline.register_stereo(Arc::new(Mutex::new(self.initial_dattorro.clone())));
+        let num_channels = audio_io_layout
+            .main_output_channels
+            .map(NonZeroU32::get)
+            .unwrap_or(2) as usize;
+        // The channels are processed in pairs, a layout with an odd amount of channels can't be handled
+        if !num_channels.is_multiple_of(2) {
+            return false;
+        }
+        self.prepare(buffer_config.sample_rate, num_channels);
+        self.tasks.set_sample_rate(buffer_config.sample_rate);
 
         true
     }
@@ -171,213 +555,1092 @@ impl Plugin for Delax {
     fn reset(&mut self) {
         // Reset buffers and envelopes here. This can be called from the audio thread and may not
         // allocate. You can remove this function if you do not need it.
-        self.left_delay_engine.reset();
-        self.right_delay_engine.reset();
+        self.clear_repeats();
+        self.note_tracker.reset();
+        self.transport_follower.reset();
+        self.drift_follower.reset();
+        self.tempo_lock.reset();
+        self.drift.reset();
+        self.auto_gain.reset();
+        self.input_analyzer.reset();
+        self.decimators.iter_mut().for_each(Decimator::reset);
+        #[cfg(feature = "glitch")]
+        self.glitch.reset(&self.params.delay_params);
+        self.dual_enabled = self.params.delay_params.dual.enabled.value();
+        self.dual_gate.reset(self.dual_enabled);
+        self.spread_gate
+            .reset(self.params.delay_params.spread.value() > 0.);
+        // The smoothers were reset to the parameter values, so the macros need to set them again.
+        // The scene those values belong to is taken as it is.
+        self.macro_engine.reset();
+        self.scene_engine.reset();
+        self.delay_snap.reset();
+        self.poly_mod.reset();
+        self.stage_switch
+            .reset(self.params.pipeline_params.stage_order());
+
+        self.held = self.params.delay_params.hold.value();
+        self.hold.reset(if self.held { 1. } else { 0. });
+        self.throw
+            .reset(self.params.delay_params.throw.engaged.value());
+        self.feedback_trim.reset(1.);
+
+        let (input_open, repeats_open, bypassed) = self.gates_open();
+        self.input_gate.reset(input_open);
+        self.repeat_gate.reset(repeats_open);
+        self.bypass_gate.reset(bypassed);
+        let (delay_stereo, filter_stereo) = self.stereo_modes();
+        self.delay_stereo.reset(delay_stereo);
+        self.filter_stereo.reset(filter_stereo);
     }
 
     fn process(
         &mut self,
         buffer: &mut Buffer,
-        _aux: &mut AuxiliaryBuffers,
-        _context: &mut impl ProcessContext<Self>,
+        aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
     ) -> ProcessStatus {
-        for channel_samples in buffer.iter_samples() {
-            // Update all the elements to the current params
-            self.update_params();
+        // The frame is taken out of self for the loop, so it can be processed while self is borrowed mutably
+        let mut frame = std::mem::take(&mut self.frame);
 
-            // ########## Input ###########
-            // Read the values sample by sample for now
-            let mut channel_iter = channel_samples.into_iter();
-
-            // If for some reason the iterator is empty something went very wrong and ig a panic is in order
-            let left_sample = channel_iter.next().unwrap();
-            let right_sample = channel_iter.next().unwrap();
-
-            self.input_ui_send(*left_sample, *right_sample);
-
-            // The output of the banks
-            let pop_left = self
-                .left_delay_engine
-                .interpolate_sample(DelayInterpolationMode::Nearest);
-            let pop_right = self
-                .right_delay_engine
-                .interpolate_sample(DelayInterpolationMode::Nearest);
-
-            // ####### Feedback loop #########
-            // The feedback values, used for the feedback loop.
-            let feedbacked_left;
-            let feedbacked_right;
-
-            match self.params.delay_params.stereo_delay.value() {
-                DelayMode::Mono => {
-                    let feedback_l = self.params.delay_params.feedback_l.smoothed.next();
-                    feedbacked_left = feedback_l * pop_left;
-                    feedbacked_right = feedback_l * pop_right;
-                }
-                DelayMode::Stereo => {
-                    let feedback_l = self.params.delay_params.feedback_l.smoothed.next();
-                    let feedback_r = self.params.delay_params.feedback_r.smoothed.next();
-                    feedbacked_left = feedback_l * pop_left;
-                    feedbacked_right = feedback_r * pop_right;
-                }
-            }
+        // Parameter changes are already handled by nih-plug, since with sample accurate automation it splits the
+        // buffer at every change and the smoothers pick up the new value on the first sample of the new block.
+        // Everything else comes in as events, which need to be handled right before the sample they're timed on.
+        let mut next_event = context.next_event();
 
-            // ############ Filtering ###############
+        if let Some(tempo) = context.transport().tempo {
+            self.visualization.set_tempo(tempo as f32);
+            self.tempo_lock.update(tempo as f32);
+        }
 
-            // Run the signal through the filters
-            let (filtered_output_l, filtered_output_r) =
-                self.run_filters(feedbacked_left, feedbacked_right);
+        let transport = TransportInfo::from_transport(context.transport(), self.sample_rate);
+        let transport_actions = self.transport_follower.follow(
+            transport,
+            self.params.delay_params.stop_behavior.value(),
+            self.params.delay_params.retrigger.value(),
+            buffer.samples(),
+        );
+        let drift_retrigger_at = self.drift_follower.retrigger_at(
+            transport,
+            self.params.delay_params.drift_retrigger.value(),
+            buffer.samples(),
+        );
 
-            // ########### Mixing #######
-            // Get the mix amount
-            let mix_left;
-            let mix_right;
-            match self.params.filter_params.svf_stereo_mode.value() {
-                filters::params::SVFStereoMode::Mono => {
-                    let mix = self.params.filter_params.svf_mix_l.smoothed.next();
-                    mix_left = mix;
-                    mix_right = mix;
+        // The host can only pass the send back in through the return on a later block,
+        // so an external loop makes the repeats at least a block longer
+        let return_latency_ms = if self.params.delay_params.external_return.value() > 0. {
+            buffer.samples() as f32 / self.sample_rate * 1000.
+        } else {
+            0.
+        };
+        self.visualization.report_return_latency(return_latency_ms);
+        self.visualization
+            .set_publish_divider(self.params.quality.value().publish_divider());
+        let auditioning = self.audition_player.active(transport.playing);
+        // The scenes, the delay snap and the macros move the smoothers once per block, update_params() then reads
+        // them sample by sample. The macros come last, so a moving macro wins over a scene change.
+        self.apply_scenes(buffer.samples());
+        self.delay_snap
+            .apply(&self.params.delay_params, self.sample_rate);
+        self.apply_macros();
+        self.apply_stage_order();
+        self.analyze_feedback_loop();
+
+        let aux_return = aux.inputs.first().map(|port| port.as_slice_immutable());
+        let mut aux_send = aux.outputs.first_mut().map(|port| port.as_slice());
+
+        for (sample_id, mut channel_samples) in buffer.iter_samples().enumerate() {
+            while let Some(event) = next_event {
+                if event.timing() > sample_id as u32 {
+                    break;
                 }
-                filters::params::SVFStereoMode::Stereo => {
-                    mix_left = self.params.filter_params.svf_mix_l.smoothed.next();
-                    mix_right = self.params.filter_params.svf_mix_r.smoothed.next();
+
+                if let Some(reply) = self.handle_event(event) {
+                    context.send_event(reply);
                 }
+                next_event = context.next_event();
             }
 
-            // Mix the feedback and filtered signal together
-            // Make the filtered output more stable by using the feedback param as well
-            let (input_left, input_right) = self.run_input_filters(*left_sample, *right_sample);
-            self.left_delay_engine.write_sample(
-                input_left + (feedbacked_left * (1. - mix_left) + filtered_output_l * mix_left),
-            );
-            self.right_delay_engine.write_sample(
-                input_right + (feedbacked_right * (1. - mix_right) + filtered_output_r * mix_right),
-            );
+            if transport_actions.clear_at == Some(sample_id) {
+                self.clear_repeats();
+            }
+            if transport_actions.retrigger_at == Some(sample_id) {
+                self.restart_patterns();
+            }
+            if drift_retrigger_at == Some(sample_id) {
+                self.drift.retrigger();
+            }
 
-            // ########### Output ##########
-            let wetness = self.params.wetness.smoothed.next();
+            // Read the values sample by sample for now
+            for (value, sample) in frame.iter_mut().zip(channel_samples.iter_mut()) {
+                *value = *sample;
+            }
+            // The audition loop is mixed into the input, so it goes through everything the input would
+            if auditioning {
+                let [left, right] = self.audition_player.next_frame();
+                for pair in frame.chunks_exact_mut(2) {
+                    pair[0] += left;
+                    pair[1] += right;
+                }
+            }
+
+            if let Some(aux_return) = &aux_return {
+                for (returned, channel) in self.returned.iter_mut().zip(aux_return.iter()) {
+                    *returned = channel[sample_id];
+                }
+            }
 
-            *left_sample = *left_sample * (1. - wetness) + pop_left * wetness;
-            *right_sample = *right_sample * (1. - wetness) + pop_right * wetness;
+            self.process_frame(&mut frame);
 
-            self.output_ui_send(*left_sample, *right_sample);
+            for (value, sample) in frame.iter().zip(channel_samples.iter_mut()) {
+                *sample = *value;
+            }
+            if let Some(aux_send) = &mut aux_send {
+                for (sent, channel) in self.sent.iter().zip(aux_send.iter_mut()) {
+                    channel[sample_id] = *sent;
+                }
+            }
         }
 
+        self.frame = frame;
+        self.publish_snapshot();
+
         ProcessStatus::Normal
     }
 }
 
+#[cfg(feature = "plugin")]
 impl Delax {
-    fn update_params(&mut self) {
-        match self.params.delay_params.stereo_delay.value() {
-            DelayMode::Mono => {
-                let delay_amt = self.params.delay_params.delay_len_l.smoothed.next();
+    /// Set up all the DSP for a sample rate and an amount of channels.
+    ///
+    /// This is what [Plugin::initialize()] does, but it doesn't need a host, so the DSP can also be driven from benchmarks and tests.
+    /// The amount of channels has to be even, since the filters work on pairs of channels, [Plugin::initialize()] turns
+    /// other layouts down. With six channels the fourth one is the LFE of 5.1, which is passed through.
+    pub fn prepare(&mut self, sample_rate: f32, num_channels: usize) {
+        assert!(
+            num_channels > 0 && num_channels.is_multiple_of(2),
+            "Delax needs an even amount of channels"
+        );
+        self.sample_rate = sample_rate;
+
+        self.delay_engines = (0..num_channels)
+            .map(|channel| {
+                let mut delay_engine =
+                    DelayEngine::new(max_delay_buffer_size(self.sample_rate), self.sample_rate);
+                delay_engine.set_delay_amount(0.);
+                delay_engine.set_humanize_seed(humanize_seed(channel));
+                delay_engine
+            })
+            .collect();
+        self.feedback_matrix = FeedbackMatrix::identity(num_channels);
+        self.injection_matrix = FeedbackMatrix::identity(num_channels);
+        self.ping_pong_matrix = FeedbackMatrix::ping_pong(num_channels);
+        self.ping_pong_injection = FeedbackMatrix::identity(num_channels);
+        self.popped = vec![0.; num_channels];
+        self.feedbacked = vec![0.; num_channels];
+        self.injected = vec![0.; num_channels];
+        self.sent = vec![0.; num_channels];
+        self.returned = vec![0.; num_channels];
+        self.passthrough = (num_channels == SURROUND_CHANNELS).then_some(LFE_CHANNEL);
+        self.frame = vec![0.; num_channels];
+        self.fade_from = vec![0.; num_channels];
+        self.feedbacks = vec![0.; num_channels];
+        self.strings = (0..num_channels)
+            .map(|_| StringVoice::new(self.sample_rate))
+            .collect();
+        self.dual_stages = (0..num_channels)
+            .map(|_| DualStage::new(self.sample_rate))
+            .collect();
+        #[cfg(feature = "glitch")]
+        self.glitch.prepare(num_channels);
+        self.decimators = (0..num_channels)
+            .map(|_| Decimator::new(self.sample_rate, LOFI_RATE, LOFI_BITS))
+            .collect();
+        #[cfg(feature = "multiband")]
+        self.multiband.prepare(self.sample_rate, num_channels);
+        self.spreads = (0..num_channels / 2)
+            .map(|_| StereoSpread::new(self.sample_rate))
+            .collect();
+
+        self.note_tracker.set_sample_rate(self.sample_rate);
+        self.tempo_lock.set_sample_rate(self.sample_rate);
+        self.drift.set_sample_rate(self.sample_rate);
+        self.auto_gain.set_sample_rate(self.sample_rate);
+        self.input_analyzer.set_sample_rate(self.sample_rate);
+        self.audition_player.set_sample_rate(self.sample_rate);
+
+        #[cfg(feature = "reverb")]
+        {
+            self.reverbs = (0..num_channels / 2)
+                .map(|_| DattorroReverb::new(self.sample_rate, 0.5))
+                .collect();
+            self.initial_dattorro.set_sample_rate(self.sample_rate);
+        }
+
+        // The filters are only rebuilt when the channel layout changes, otherwise they're retuned in place and keep their settings
+        let num_pairs = num_channels / 2;
+        if self.filter_pipelines.len() != num_pairs {
+            (self.sin_svfs, self.filter_pipelines) = filter_bank(num_pairs, self.sample_rate);
+            (self.input_sin_svfs, self.initial_filter_pipelines) =
+                filter_bank(num_pairs, self.sample_rate);
+        }
+        for pipeline in self
+            .filter_pipelines
+            .iter()
+            .chain(&self.initial_filter_pipelines)
+        {
+            pipeline.set_sample_rate(self.sample_rate);
+        }
+
+        self.visualization.set_sample_rate(self.sample_rate);
+        self.cc_engine.prepare(self.params.param_map());
+        self.macro_engine.prepare(self.params.param_map());
+        self.scene_engine.prepare(self.params.param_map());
+
+        // self.initial_filter_pipeline.register_stereo(Arc::new(Mutex::new(self.initial_dattorro.clone())));
+    }
 
-                self.left_delay_engine.set_delay_amount(delay_amt);
-                self.right_delay_engine.set_delay_amount(delay_amt);
+    /// Replace the routing of the feedback between the channels.
+    ///
+    /// The matrix needs to have the same size as the amount of channels passed to [Delax::prepare()].
+    pub fn set_feedback_matrix(&mut self, feedback_matrix: FeedbackMatrix) {
+        assert_eq!(feedback_matrix.size(), self.delay_engines.len());
+        self.feedback_matrix = feedback_matrix;
+    }
+
+    /// Replace the routing of the input signal into the delay lines, which decides where the first repeat appears.
+    ///
+    /// The matrix needs to have the same size as the amount of channels passed to [Delax::prepare()].
+    pub fn set_injection_matrix(&mut self, injection_matrix: FeedbackMatrix) {
+        assert_eq!(injection_matrix.size(), self.delay_engines.len());
+        self.injection_matrix = injection_matrix;
+    }
+
+    /// Empty everything that holds on to the repeats, so none of them are heard anymore. This is what happens when the
+    /// host stops with the stop behavior set to clear, and when the plugin is reset.
+    ///
+    /// A loop region is let go and captured again from the empty delay lines.
+    pub fn clear_repeats(&mut self) {
+        self.delay_engines.iter_mut().for_each(DelayEngine::reset);
+        #[cfg(feature = "glitch")]
+        self.glitch.clear(&mut self.delay_engines);
+        self.dual_stages.iter_mut().for_each(DualStage::reset);
+        self.strings.iter_mut().for_each(StringVoice::reset);
+        #[cfg(feature = "reverb")]
+        self.reverbs.iter_mut().for_each(DattorroReverb::reset);
+        #[cfg(feature = "multiband")]
+        self.multiband.reset(
+            &self.params.multiband_params,
+            self.params
+                .topology()
+                .multiband(self.params.multiband_params.enabled.value()),
+        );
+    }
+
+    /// Start the patterns of the heads over, so they're in time with the song again. The loop region plays from its
+    /// start, the reversed slices start at the delay time and the humanize picks a new repeat. The repeats are kept.
+    ///
+    /// This is what the retrigger does when playback starts or at the start of every bar.
+    pub fn restart_patterns(&mut self) {
+        self.delay_engines
+            .iter_mut()
+            .for_each(DelayEngine::restart_patterns);
+        #[cfg(feature = "glitch")]
+        self.glitch.restart();
+    }
+
+    /// Fade the continuous parameters towards the active scene, for a block of `block_len` samples.
+    ///
+    /// This is what [Plugin::process()] does at the start of every block, before the macros are applied.
+    pub fn apply_scenes(&mut self, block_len: usize) {
+        self.scene_engine.apply(
+            &self.params.scenes,
+            self.params.scene_index(),
+            self.params.scene_crossfade.value(),
+            self.sample_rate,
+            block_len,
+        );
+    }
+
+    /// Move the parameters assigned to the macro knobs to their morphed values.
+    ///
+    /// This is what [Plugin::process()] does at the start of every block, so the macros also work when the DSP is driven without a host.
+    pub fn apply_macros(&mut self) {
+        self.macro_engine.apply(
+            &self.params.macro_targets,
+            self.params.macro_amounts(),
+            self.sample_rate,
+        );
+    }
+
+    /// Follow the stage order parameter. A new order is faded in over the next blocks, see [StageSwitch].
+    ///
+    /// This is what [Plugin::process()] does at the start of every block.
+    pub fn apply_stage_order(&mut self) {
+        self.stage_switch
+            .begin_block(self.params.pipeline_params.stage_order());
+    }
+
+    /// Estimate how much the repeats of every channel grow on each pass through the feedback loop and report the
+    /// loudest loop to the editor. With the stability trim on the feedback is scaled back until that loop stops growing.
+    ///
+    /// This is what [Plugin::process()] does at the start of every block. The filters are read as the last block left them.
+    pub fn analyze_feedback_loop(&mut self) {
+        let delay_params = &self.params.delay_params;
+        let filter_params = &self.params.filter_params;
+        // A string decays by its decay time, whatever the feedback is
+        if delay_params.string.enabled.value() {
+            self.visualization.report_loop_gain(0.);
+            self.feedback_trim.set_target(self.sample_rate, 1.);
+            return;
+        }
+
+        let topology = self.params.topology();
+        let routing = if topology.ping_pong(delay_params.ping_pong.value()) {
+            &self.ping_pong_matrix
+        } else {
+            &self.feedback_matrix
+        }
+        .max_row_gain();
+        let filtered =
+            !topology.feedback_filter_bypassed(filter_params.feedback_filter_bypass.value());
+        let held = delay_params.hold.value();
+        let loudest = (0..self.delay_engines.len())
+            .map(|channel| {
+                let delay_channel = match delay_params.stereo_delay.value() {
+                    DelayMode::Mono => 0,
+                    DelayMode::Stereo => channel % NUM_CHANNELS,
+                };
+                let filter_channel = match filter_params.svf_stereo_mode.value() {
+                    SVFStereoMode::Mono => 0,
+                    SVFStereoMode::Stereo => channel % NUM_CHANNELS,
+                };
+                // A channel without a partner isn't filtered
+                let filter_peak = self.filter_pipelines.get(channel / 2).map(|pipeline| {
+                    let (peak_l, peak_r) = pipeline.peak_gain();
+                    if channel % 2 == 0 {
+                        peak_l
+                    } else {
+                        peak_r
+                    }
+                });
+
+                LoopGain {
+                    feedback: if held {
+                        1.
+                    } else {
+                        let channel_params = &delay_params.channels[delay_channel];
+                        delay_params.feedback_mode.value().feedback_gain(
+                            channel_params.feedback.smoothed.previous_value(),
+                            channel_params.decay_time.smoothed.previous_value(),
+                            self.delay_engines[channel].delay_time(),
+                        )
+                    },
+                    routing,
+                    filter_peak: filter_peak.unwrap_or(1.),
+                    filter_mix: if filtered && filter_peak.is_some() {
+                        filter_params.channels[filter_channel]
+                            .mix
+                            .smoothed
+                            .previous_value()
+                    } else {
+                        0.
+                    },
+                }
+            })
+            .max_by(|a, b| a.estimate().total_cmp(&b.estimate()));
+
+        let Some(loudest) = loudest else {
+            return;
+        };
+        self.visualization.report_loop_gain(loudest.estimate());
+        let trim = if delay_params.stability_trim.value() {
+            loudest.trim()
+        } else {
+            1.
+        };
+        self.feedback_trim.set_target(self.sample_rate, trim);
+    }
+
+    /// Run the whole processing chain on a single stereo sample and return the output sample.
+    ///
+    /// This only works if the plugin was prepared for two channels. See [Delax::process_frame()] for all other layouts.
+    pub fn process_sample(&mut self, left_sample: f32, right_sample: f32) -> (f32, f32) {
+        let mut frame = [left_sample, right_sample];
+        self.process_frame(&mut frame);
+
+        (frame[0], frame[1])
+    }
+
+    /// Run the whole processing chain on a frame with one sample per channel, the output is written back into the frame.
+    ///
+    /// This is what [Plugin::process()] calls for every sample in the buffer.
+    /// Channels with an even index use the parameters of the left channel, channels with an odd index the ones of the right channel.
+    pub fn process_frame(&mut self, frame: &mut [f32]) {
+        debug_assert_eq!(frame.len(), self.delay_engines.len());
+
+        // Update all the elements to the current params
+        self.update_params();
+
+        // The LFE is taken out of the frame, so its delay line stays silent, and put back in as it came
+        let passthrough = self
+            .passthrough
+            .map(|channel| (channel, std::mem::take(&mut frame[channel])));
+
+        // ########## Input ###########
+        // The analysis measures the input before the trim, so its suggestion is the trim itself
+        self.input_analyzer.process(frame);
+        let input_trim = self.params.input_trim.smoothed.next();
+        frame.iter_mut().for_each(|sample| *sample *= input_trim);
+        self.visualization.process_input(frame[0], frame[1]);
+        let input_energy: f32 = frame.iter().map(|sample| sample * sample).sum();
+
+        // The output of the banks, in tape echo mode every enabled head adds a repeat.
+        // The string mode takes precedence over the tape echo.
+        // The algorithm decides which stages run, over their own switches
+        let topology = self.params.topology();
+        let tape_echo = &self.params.delay_params.tape_echo;
+        let string_mode = self.params.delay_params.string.enabled.value();
+        if topology.tape_echo(tape_echo.enabled.value()) && !string_mode {
+            let heads = tape_echo.heads();
+            for (popped, delay_engine) in self.popped.iter_mut().zip(&self.delay_engines) {
+                *popped = read_heads(delay_engine, heads);
             }
-            DelayMode::Stereo => {
-                let delay_amt_l = self.params.delay_params.delay_len_l.smoothed.next();
-                let delay_amt_r = self.params.delay_params.delay_len_r.smoothed.next();
-                self.left_delay_engine.set_delay_amount(delay_amt_l);
-                self.right_delay_engine.set_delay_amount(delay_amt_r);
+        } else {
+            let drift_depth = &self.params.delay_params.drift_depth;
+            let drifting = drift_depth.value() > 0. || drift_depth.smoothed.is_smoothing();
+            let interpolate_all = self.params.quality.value().interpolates_every_read();
+            let throwing = self.throw.active();
+            for (popped, delay_engine) in self.popped.iter_mut().zip(&self.delay_engines) {
+                // Short delays are combs and strings need to be in tune, they are read between the samples.
+                // The drift and the throw move the reads slowly, which would step audibly between whole samples.
+                let interpolation = if string_mode || drifting || throwing || interpolate_all {
+                    DelayInterpolationMode::Linear
+                } else {
+                    interpolation_for(delay_engine.delay_time())
+                };
+                *popped = delay_engine.interpolate_sample(interpolation);
             }
         }
+        // Fade out the old delay times after the tempo changed
+        if self.fade_mix < 1. {
+            let mix = self.fade_mix;
+            for ((popped, delay_engine), from) in self
+                .popped
+                .iter_mut()
+                .zip(&self.delay_engines)
+                .zip(&self.fade_from)
+            {
+                *popped = delay_engine.sample_at(*from) * (1. - mix) + *popped * mix;
+            }
+        }
+        // The reverse button fades over to the repeats played backwards. The string has to stay in tune, it's never reversed.
+        #[cfg(feature = "glitch")]
+        self.glitch.process(
+            &mut self.popped,
+            &mut self.delay_engines,
+            self.sample_rate,
+            self.params.delay_params.perform.reverse.value() && !string_mode,
+        );
+        self.visualization.process_repeats(
+            [self.popped[0], self.popped[1]],
+            [
+                self.delay_engines[0].delay_samples(),
+                self.delay_engines[1].delay_samples(),
+            ],
+        );
 
-        // Update the filter params
-        match self.params.filter_params.svf_stereo_mode.value() {
-            // For mono params it's important to just call the params function once. Otherwise the smoothing is out of sync
-            filters::params::SVFStereoMode::Mono => {
-                let res = self.params.filter_params.svf_res_l.smoothed.next();
-                self.sin_svf_l.set_res(res);
-                self.sin_svf_r.set_res(res);
-                self.input_sin_svf_l.set_res(res);
-                self.input_sin_svf_r.set_res(res);
-
-                let cutoff = self.params.filter_params.svf_cutoff_l.smoothed.next();
-                self.sin_svf_l.set_cutoff(cutoff);
-                self.sin_svf_r.set_cutoff(cutoff);
-                self.input_sin_svf_l.set_cutoff(cutoff);
-                self.input_sin_svf_r.set_cutoff(cutoff);
-
-                let mode = self.params.filter_params.svf_filter_mode_l.value();
-                self.sin_svf_l.set_mode(mode);
-                self.sin_svf_r.set_mode(mode);
+        // ####### Feedback loop #########
+        // Route the outputs between the channels, then apply the feedback values of each channel.
+        // Holding moves the feedback towards 1, so the repeats ring on until it's released.
+        let hold = self.next_hold(self.params.delay_params.hold.value());
+        let feedback_trim = self.feedback_trim.next();
+        // Bypassing or muting gates the write path, so the repeats that are already there ring out.
+        // Only in cut mode the repeats and their feedback are faded out as well.
+        let (input_open, repeats_open, bypassed) = self.gates_open();
+        let input_gain = self.input_gate.next(self.sample_rate, input_open);
+        let repeat_gain = self.repeat_gate.next(self.sample_rate, repeats_open);
+        let bypass_gain = self.bypass_gate.next(self.sample_rate, bypassed);
+        let feedback_polarity = if self.params.delay_params.invert_feedback.value() {
+            -1.
+        } else {
+            1.
+        };
+        // Ping pong replaces both the routing of the feedback and the routing of the input
+        let (injection_matrix, feedback_matrix) =
+            if topology.ping_pong(self.params.delay_params.ping_pong.value()) {
+                (&self.ping_pong_injection, &self.ping_pong_matrix)
+            } else {
+                (&self.injection_matrix, &self.feedback_matrix)
+            };
+        feedback_matrix.apply(&self.popped, &mut self.feedbacked);
+        injection_matrix.apply(frame, &mut self.injected);
+        // A string decays by its decay time instead of the feedback and loses its high harmonics on every pass
+        let string = &self.params.delay_params.string;
+        let brightness = string.brightness.smoothed.next();
+        let decay = string.decay.value();
+        for (channel, feedbacked) in self.feedbacked.iter_mut().enumerate() {
+            let feedback = if string_mode {
+                *feedbacked = self.strings[channel].damp(*feedbacked, brightness);
+                decay_gain(self.delay_engines[channel].delay_time(), decay)
+            } else {
+                self.feedbacks[channel]
+            };
+            *feedbacked *= (feedback + (1. - feedback) * hold)
+                * feedback_trim
+                * repeat_gain
+                * feedback_polarity;
+        }
+        // The attacks of the input pluck the strings with a burst of noise
+        if string_mode {
+            for (channel, injected) in self.injected.iter_mut().enumerate() {
+                let period_samples = self.delay_engines[channel].delay_samples();
+                *injected += self.strings[channel].excite(frame[channel], period_samples);
             }
-            filters::params::SVFStereoMode::Stereo => {
-                let res_l = self.params.filter_params.svf_res_l.smoothed.next();
-                let res_r = self.params.filter_params.svf_res_r.smoothed.next();
-
-                self.sin_svf_l.set_res(res_l);
-                self.sin_svf_r.set_res(res_r);
-
-                let cutoff_l = self.params.filter_params.svf_cutoff_l.smoothed.next();
-                let cutoff_r = self.params.filter_params.svf_cutoff_r.smoothed.next();
-                self.sin_svf_l.set_cutoff(cutoff_l);
-                self.sin_svf_r.set_cutoff(cutoff_r);
-
-                let mode_l = self.params.filter_params.svf_filter_mode_l.value();
-                let mode_r = self.params.filter_params.svf_filter_mode_r.value();
-                self.sin_svf_l.set_mode(mode_l);
-                self.sin_svf_r.set_mode(mode_r);
+        }
+
+        // Get the mix and wet amount, these are shared between all the pairs
+        let mix = F32x2::from(
+            self.params
+                .filter_params
+                .next_per_channel(|channel| &channel.mix, self.filter_stereo.value()),
+        );
+        // A solo of the editor overrides the wetness of its channel
+        let wetness = self.params.wetness.smoothed.next();
+        let (dry_l, wet_l) = self.params.solo(0).gains(wetness);
+        let (dry_r, wet_r) = self.params.solo(1).gains(wetness);
+        let (dry_gain, wet_gain) = (F32x2::new(dry_l, dry_r), F32x2::new(wet_l, wet_r));
+        // The bypass lets the dry signal through at full level
+        let dry_gain = dry_gain + (F32x2::splat(1.) - dry_gain) * bypass_gain;
+        let wet_gain = wet_gain * repeat_gain;
+
+        // The utility stages of the wet signal, they fix phasey results of the cross feedback
+        let swap_channels = self.params.delay_params.swap_channels.value();
+        let crossfeed =
+            delay_engine::params::width_crossfeed(self.params.delay_params.width.smoothed.next());
+        let polarity = F32x2::from(self.params.delay_params.polarity());
+        // The spread keeps running while it's off, so it has the latest repeats when it fades back in
+        let spread = &self.params.delay_params.spread;
+        let spread_amount = spread.smoothed.next();
+        let spread_gain = self.spread_gate.next(self.sample_rate, spread.value() > 0.);
+        for stereo_spread in &mut self.spreads {
+            stereo_spread.set_spread(spread_amount);
+        }
+        let external_return = self.params.delay_params.external_return.smoothed.next();
+        // The second stages are only run while they're on or fading out. Their feedback stops like the main one.
+        let dual = &self.params.delay_params.dual;
+        let dual_gain = self.dual_gate.next(self.sample_rate, self.dual_enabled);
+        let dual_mix = DualMix {
+            routing: dual.routing.value(),
+            feedback: dual.feedback.smoothed.next() * repeat_gain,
+            balance: dual.balance.smoothed.next() * dual_gain,
+        };
+        let dual_running = self.dual_enabled || dual_gain > 0.;
+        // The band delays fade in over the main delay, which keeps running underneath
+        #[cfg(feature = "multiband")]
+        let band_mix =
+            self.multiband
+                .next_mix(&self.params.multiband_params, self.sample_rate, repeat_gain);
+        let feedback_filter_bypassed = topology
+            .feedback_filter_bypassed(self.params.filter_params.feedback_filter_bypass.value());
+        // The stages of the feedback loop run in the order of the parameter, they're faded out while it changes
+        let stage_order = self.stage_switch.order();
+        let stage_fade = self.stage_switch.next(self.sample_rate);
+        let saturation = self.params.pipeline_params.saturation.smoothed.next();
+        #[cfg(feature = "reverb")]
+        let reverb_mix = self.params.pipeline_params.reverb_mix.smoothed.next();
+
+        // From here on the channels are processed in pairs, left and right are packed together
+        for pair in 0..frame.len() / 2 {
+            let (l, r) = (pair * 2, pair * 2 + 1);
+            let popped = F32x2::new(self.popped[l], self.popped[r]);
+            let feedbacked = F32x2::new(self.feedbacked[l], self.feedbacked[r]);
+
+            // ############ Feedback stages ###############
+
+            let mut staged = feedbacked;
+            for stage in stage_order.stages() {
+                staged = match stage {
+                    // Run the signal through the filters, a bypassed filter passes the feedback on unchanged.
+                    // Strings have their own damping, so they skip the filters.
+                    // Mix the feedback and filtered signal together
+                    FeedbackStage::Svf if string_mode || feedback_filter_bypassed => staged,
+                    FeedbackStage::Svf => {
+                        staged * (F32x2::splat(1.) - mix) + self.run_filters(pair, staged) * mix
+                    }
+                    FeedbackStage::Saturation => staged.map(|sample| saturate(sample, saturation)),
+                    // The reverb keeps running while it's turned down, so it fades back in with its tail
+                    #[cfg(feature = "reverb")]
+                    FeedbackStage::Reverb => {
+                        let reverbed =
+                            F32x2::from(self.reverbs[pair].process_stereo(staged.l(), staged.r()));
+                        staged + (reverbed - staged) * reverb_mix
+                    }
+                    #[cfg(not(feature = "reverb"))]
+                    FeedbackStage::Reverb => staged,
+                    // The Lo-Fi algorithm crushes the feedback, so every repeat gets grainier than the one before
+                    FeedbackStage::Degrade if topology.lofi => F32x2::new(
+                        self.decimators[l].process(staged.l()),
+                        self.decimators[r].process(staged.r()),
+                    ),
+                    FeedbackStage::Degrade => staged,
+                };
             }
+            let feedback = feedbacked + (staged - feedbacked) * stage_fade;
+
+            // ########### Mixing #######
+            let dry = F32x2::new(frame[l], frame[r]);
+            let injected = F32x2::new(self.injected[l], self.injected[r]);
+            let input = if self.params.filter_params.input_filter_bypass.value() {
+                injected
+            } else {
+                self.run_input_filters(pair, injected)
+            };
+            // The feedback goes out of the aux send, the return replaces it by the external return amount
+            [self.sent[l], self.sent[r]] = feedback.0;
+            let returned = F32x2::new(self.returned[l], self.returned[r]);
+            let written = input * input_gain + feedback + (returned - feedback) * external_return;
+            self.delay_engines[l].write_sample(written.l());
+            self.delay_engines[r].write_sample(written.r());
+
+            // ########### Multiband ##########
+            // The bands get the same gated input as the main delay
+            let gated = input * input_gain;
+            #[cfg(feature = "multiband")]
+            let popped = match &band_mix {
+                Some(band_mix) => self.multiband.process(pair, gated, popped, band_mix),
+                None => popped,
+            };
+
+            // ########### Dual delay ##########
+            // The second stage gets the same gated input as the main delay, the balance decides what's heard
+            let wet = if dual_running {
+                F32x2::new(
+                    self.dual_stages[l].process(gated.l(), popped.l(), dual_mix),
+                    self.dual_stages[r].process(gated.r(), popped.r(), dual_mix),
+                )
+            } else {
+                popped
+            };
+
+            // ########### Output ##########
+            // The spread decorrelates the channels before they are swapped, crossfed and flipped
+            let (spread_l, spread_r) = self.spreads[pair].process_stereo(wet.l(), wet.r());
+            let wet = wet + (F32x2::new(spread_l, spread_r) - wet) * spread_gain;
+            // The channels are swapped and crossfed first, so the polarity is flipped on the output channel
+            let wet = if swap_channels { wet.swap() } else { wet };
+            let wet = (wet * (1. - crossfeed) + wet.swap() * crossfeed) * polarity;
+            let output = dry * dry_gain + wet * wet_gain;
+            [frame[l], frame[r]] = output.0;
         }
+
+        // ########### Auto gain ##########
+        // Level matching the output to the input, so louder settings don't just sound better for being louder
+        if self.params.auto_gain.value() {
+            let output_energy: f32 = frame.iter().map(|sample| sample * sample).sum();
+            let gain = self.auto_gain.next(input_energy, output_energy);
+            frame.iter_mut().for_each(|sample| *sample *= gain);
+            if gain < 1. {
+                self.visualization
+                    .report_gain_reduction(-util::gain_to_db(gain));
+            }
+        } else {
+            self.auto_gain.reset();
+        }
+
+        // ########### Safety ##########
+        // The last stage catches runaway feedback and resonance before it leaves the plugin
+        if self.params.safety_clipper.value() {
+            let peak = frame
+                .iter()
+                .fold(0f32, |peak, sample| peak.max(sample.abs()));
+            frame
+                .iter_mut()
+                .for_each(|sample| *sample = safety_clip(*sample));
+            if peak > SAFETY_KNEE {
+                let gain_reduction_db = util::gain_to_db(peak / safety_clip(peak));
+                self.visualization.report_safety_clip(gain_reduction_db);
+                self.visualization.report_gain_reduction(gain_reduction_db);
+            }
+        }
+
+        if let Some((channel, sample)) = passthrough {
+            frame[channel] = sample;
+            self.sent[channel] = 0.;
+        }
+
+        self.visualization.process_output(frame[0], frame[1]);
     }
 
-    /// Run the current filter chain. Input is the stereo signal, output is the resulting stereo signal.
-    fn run_filters(&mut self, input_l: f32, input_r: f32) -> (f32, f32) {
-        self.filter_pipeline.process_stereo(input_l, input_r)
+    /// React to an event from the host. This is called right before the sample the event is timed on is processed.
+    ///
+    /// Returns the event to send back to the host, if there is one.
+    fn handle_event(&mut self, event: PluginNoteEvent<Self>) -> Option<PluginNoteEvent<Self>> {
+        match event {
+            NoteEvent::NoteOn { note, .. } => self.note_tracker.note_on(note),
+            NoteEvent::NoteOff {
+                timing,
+                voice_id: Some(voice_id),
+                channel,
+                note,
+                ..
+            }
+            | NoteEvent::Choke {
+                timing,
+                voice_id: Some(voice_id),
+                channel,
+                note,
+            } => {
+                // Only voices that were modulated are tracked, the host doesn't expect to hear about the others
+                let ended = self.poly_mod.end_voice(voice_id)?;
+                for id in ended {
+                    self.apply_poly_mod(id, None);
+                }
+                // The voice ends with its note, the host can free its modulation
+                return Some(NoteEvent::VoiceTerminated {
+                    timing,
+                    voice_id: Some(voice_id),
+                    channel,
+                    note,
+                });
+            }
+            NoteEvent::PolyModulation {
+                voice_id,
+                poly_modulation_id,
+                normalized_offset,
+                ..
+            } => {
+                self.poly_mod
+                    .set(voice_id, poly_modulation_id, normalized_offset);
+                self.apply_poly_mod(poly_modulation_id, None);
+            }
+            // The host moved the parameter itself, which also moved its smoother to the new value without the offset
+            NoteEvent::MonoAutomation {
+                poly_modulation_id,
+                normalized_value,
+                ..
+            } => self.apply_poly_mod(poly_modulation_id, Some(normalized_value)),
+            NoteEvent::MidiCC {
+                channel, cc, value, ..
+            } => self.cc_router.route(
+                &self.params.cc_mappings,
+                &self.cc_engine,
+                channel,
+                cc,
+                value,
+                self.sample_rate,
+            ),
+            _ => (),
+        }
+
+        None
     }
 
-    /// Run the filter chain on the input signal. This can probably be refactored out down the line. But for now it doesn't work correctly without
-    fn run_input_filters(&mut self, input_l: f32, input_r: f32) -> (f32, f32) {
-        self.initial_filter_pipeline
-            .process_stereo(input_l, input_r)
+    /// The parameter a polyphonic modulation ID belongs to
+    fn poly_mod_param(&self, id: u32) -> Option<&FloatParam> {
+        let (target, channel) = PolyModTarget::from_id(id)?;
+        Some(match target {
+            PolyModTarget::DelayTime => &self.params.delay_params.channels[channel].len,
+            PolyModTarget::Feedback => &self.params.delay_params.channels[channel].feedback,
+            PolyModTarget::Cutoff => &self.params.filter_params.channels[channel].cutoff,
+        })
     }
 
-    fn input_ui_send(&mut self, l: f32, r: f32) {
-        let l = 1. + util::gain_to_db(l) / 100.;
-        let r = 1. + util::gain_to_db(r) / 100.;
+    /// Move the smoother of a polyphonically modulated parameter to its value plus the offsets of all voices.
+    ///
+    /// Like the macros this only drives the smoother, the parameter itself keeps its value. The normalized value
+    /// of the parameter is read from the parameter, unless the host just sent a new one.
+    fn apply_poly_mod(&self, id: u32, normalized_value: Option<f32>) {
+        let Some(param) = self.poly_mod_param(id) else {
+            return;
+        };
+
+        let normalized_value =
+            normalized_value.unwrap_or_else(|| param.modulated_normalized_value());
+        let offset = self.poly_mod.offset(id);
+        param.smoothed.set_target(
+            self.sample_rate,
+            param.preview_plain((normalized_value + offset).clamp(0., 1.)),
+        );
+    }
 
-        let l = self.peak_follower_in_l.process(l);
-        let r = self.peak_follower_in_r.process(r);
+    /// Hand the editor a snapshot of the delay lines of the first pair of channels if it asked for one
+    fn publish_snapshot(&self) {
+        if !self.visualization.snapshot_requested() {
+            return;
+        }
 
-        self.input_data
-            .in_l
-            .store(l, std::sync::atomic::Ordering::Relaxed);
-        self.input_data
-            .in_r
-            .store(r, std::sync::atomic::Ordering::Relaxed);
+        let [left, right] = [&self.delay_engines[0], &self.delay_engines[1]];
+        // The last sample of the buffer is the one that's about to be overwritten
+        let len = left.get_buffer_ptr().len() - 1;
+        self.visualization
+            .publish_snapshot(len, self.sample_rate, |index| {
+                (left.history_sample(len - index) + right.history_sample(len - index)) / 2.
+            });
     }
 
-    fn output_ui_send(&mut self, l: f32, r: f32) {
-        let l = 1. + util::gain_to_db_fast(l) / 100.;
-        let r = 1. + util::gain_to_db_fast(r) / 100.;
+    fn update_params(&mut self) {
+        // The mode switches fade here, every per channel parameter of this frame is read at the same point of the fade
+        let (delay_stereo, filter_stereo) = self.stereo_modes();
+        let delay_stereo = self.delay_stereo.next(self.sample_rate, delay_stereo);
+        let filter_stereo = self.filter_stereo.next(self.sample_rate, filter_stereo);
 
-        let l = self.peak_follower_out_l.process(l);
-        let r = self.peak_follower_out_r.process(r);
+        let delay_lens = self
+            .params
+            .delay_params
+            .next_per_channel(|channel| &channel.len, delay_stereo);
+        // A tracked note overrides the delay time of all the channels
+        let tracked_delay_len = if self.params.delay_params.note_tracking.value() {
+            self.note_tracker
+                .next(self.params.delay_params.note_glide.value())
+        } else {
+            None
+        };
+        // BPM bound delays follow the tempo lock, which may be fading between two tempos
+        let synced = self.params.delay_params.synced_per_channel();
+        let tempo = self
+            .tempo_lock
+            .next(self.params.delay_params.tempo_transition.value());
+        // The string and the tape echo set a single delay time for all channels. The string is tuned to the
+        // tracked note if there is one, the repeat rate of the tape echo sets the spacing of its heads.
+        let topology = self.params.topology();
+        let tape_echo = &self.params.delay_params.tape_echo;
+        let repeat_rate = tape_echo.repeat_rate.smoothed.next();
+        let string = &self.params.delay_params.string;
+        let string_period = 1000. / string.pitch.smoothed.next();
+        let humanize = self.params.delay_params.humanize.smoothed.next();
+        // The drift moves the left and the right reads in opposite directions
+        let drift = self.drift.next(
+            self.params.delay_params.drift_rate.value(),
+            self.params.delay_params.drift_depth.smoothed.next(),
+        );
+        let fixed_delay_len = if string.enabled.value() {
+            Some(tracked_delay_len.unwrap_or(string_period))
+        } else if topology.tape_echo(tape_echo.enabled.value()) {
+            Some(repeat_rate)
+        } else {
+            None
+        };
+        // The string has to stay in tune and the tape heads are read at the normal speed.
+        // The half speed button drops whatever speed the algorithm or the parameters set by an octave.
+        let read_speed = self
+            .params
+            .delay_params
+            .perform
+            .read_speed(topology.read_speed(
+                self.params.delay_params.read_speed.smoothed.next()
+                    * 2f32.powf(self.params.delay_params.read_detune.smoothed.next() / 12.),
+            ));
+        let read_speed = if fixed_delay_len.is_some() {
+            1.
+        } else {
+            read_speed
+        };
+        // The throw ramps the delay times and the feedback over their parameters, only the string stays in tune
+        let throw = &self.params.delay_params.throw;
+        let throw_amount = self.throw.next(
+            throw.engaged.value() && !string.enabled.value(),
+            throw.ramp.value(),
+            self.sample_rate,
+        );
+        let throw_time = throw.time.smoothed.next();
+        let throw_feedback = throw.feedback.smoothed.next();
+        for (channel, delay_engine) in self.delay_engines.iter_mut().enumerate() {
+            let len = delay_lens[channel % NUM_CHANNELS];
+            let (from, to) = match (fixed_delay_len, tracked_delay_len) {
+                (Some(fixed), _) => (fixed, fixed),
+                (None, Some(tracked)) => (tracked, tracked),
+                (None, None) if synced[channel % NUM_CHANNELS] => (
+                    synced_delay_ms(len, tempo.from),
+                    synced_delay_ms(len, tempo.to),
+                ),
+                (None, None) => (len, len),
+            };
+            delay_engine.set_delay_amount(Throw::apply(throw_amount, to, throw_time));
+            delay_engine.set_humanize(humanize);
+            delay_engine.set_read_offset(StereoDrift::channel_offset(drift, channel));
+            delay_engine.set_read_speed(read_speed);
+            self.fade_from[channel] = Throw::apply(throw_amount, from, throw_time);
+        }
+        // A decay time turns into the feedback gain for the delay time each channel has right now,
+        // so the tail keeps its length while the delay time moves
+        let channel_params = &self.params.delay_params;
+        let feedback_mode = channel_params.feedback_mode.value();
+        let feedback = channel_params.next_per_channel(|channel| &channel.feedback, delay_stereo);
+        let decay_time =
+            channel_params.next_per_channel(|channel| &channel.decay_time, delay_stereo);
+        for (channel, (gain, delay_engine)) in self
+            .feedbacks
+            .iter_mut()
+            .zip(&self.delay_engines)
+            .enumerate()
+        {
+            let gain_of_mode = feedback_mode.feedback_gain(
+                feedback[channel % NUM_CHANNELS],
+                decay_time[channel % NUM_CHANNELS],
+                delay_engine.delay_time(),
+            );
+            *gain = Throw::apply(throw_amount, gain_of_mode, throw_feedback);
+        }
+        // The second stages start out empty whenever the dual delay is turned on
+        let dual = &self.params.delay_params.dual;
+        let dual_enabled = dual.enabled.value();
+        if dual_enabled && !self.dual_enabled {
+            self.dual_stages.iter_mut().for_each(DualStage::reset);
+        }
+        self.dual_enabled = dual_enabled;
+        let dual_time = dual.time.smoothed.next();
+        for stage in &mut self.dual_stages {
+            stage.set_time(dual_time);
+        }
+        // The same for the band delays, which keep their crossovers and times up to date while they're off
+        #[cfg(feature = "multiband")]
+        {
+            let multiband = &self.params.multiband_params;
+            self.multiband
+                .update(multiband, topology.multiband(multiband.enabled.value()));
+        }
+        #[cfg(feature = "glitch")]
+        self.glitch
+            .update(&self.params.delay_params, &tempo, &mut self.delay_engines);
 
-        self.input_data
-            .out_l
-            .store(l, std::sync::atomic::Ordering::Relaxed);
-        self.input_data
-            .out_r
-            .store(r, std::sync::atomic::Ordering::Relaxed);
+        // The string and the tape heads don't follow the tempo, so there's nothing to fade
+        self.fade_mix = if fixed_delay_len.is_some() {
+            1.
+        } else {
+            tempo.mix
+        };
+
+        // The ping pong injection follows its smoothed parameters
+        let delay_params = &self.params.delay_params;
+        let offset = delay_params.ping_pong_offset.smoothed.next();
+        if topology.ping_pong(delay_params.ping_pong.value()) {
+            let start = match delay_params.ping_pong_start.value() {
+                PingPongStart::Left => 0,
+                PingPongStart::Right => 1,
+            };
+            self.ping_pong_injection
+                .set_ping_pong_injection(start, offset);
+        }
+
+        // Update the filter params
+        let filter_params = &self.params.filter_params;
+        let res =
+            F32x2::from(filter_params.next_per_channel(|channel| &channel.res, filter_stereo));
+        let cutoff = filter_params.next_per_channel(|channel| &channel.cutoff, filter_stereo);
+        let keytrack = filter_params.next_per_channel(|channel| &channel.keytrack, filter_stereo);
+        // The last note shifts the cutoff of every channel by its own keytrack amount
+        let cutoff = F32x2::from(match self.note_tracker.note() {
+            Some(note) => std::array::from_fn(|channel| {
+                midi::note_tracker::keytrack_cutoff(cutoff[channel], note, keytrack[channel])
+            }),
+            None => cutoff,
+        });
+        let modes = filter_params.modes();
+        let drive = util::db_to_gain(filter_params.drive.smoothed.next());
+
+        // The mono mode is already resolved above, so the input and the feedback filters always get the same settings.
+        // Retuning the filters is the expensive part, the smoothers above still move every sample.
+        self.control_counter += 1;
+        if self.control_counter >= self.params.quality.value().control_interval() {
+            self.control_counter = 0;
+            let compensated = filter_params.res_compensation.value();
+            let slope = filter_params.slope.value().into();
+            // Only the repeats are driven, the input filters stay clean
+            apply_filter_settings(
+                &self.sin_svfs,
+                cutoff,
+                res,
+                modes,
+                compensated,
+                drive,
+                slope,
+            );
+            apply_filter_settings(
+                &self.input_sin_svfs,
+                cutoff,
+                res,
+                modes,
+                compensated,
+                1.,
+                slope,
+            );
+        }
+        let gain_staging = filter_params.gain_staging.value();
+        for pipeline in self
+            .filter_pipelines
+            .iter_mut()
+            .chain(&mut self.initial_filter_pipelines)
+        {
+            pipeline.set_gain_staging(gain_staging);
+        }
+    }
+
+    /// Whether the delay and the filters are in stereo mode
+    fn stereo_modes(&self) -> (bool, bool) {
+        (
+            self.params.delay_params.stereo_delay.value() == DelayMode::Stereo,
+            self.params.filter_params.svf_stereo_mode.value() == SVFStereoMode::Stereo,
+        )
+    }
+
+    /// Whether the input and the repeats are open, and whether the plugin is bypassed
+    fn gates_open(&self) -> (bool, bool, bool) {
+        let bypassed = self.params.bypass.value();
+        let silenced = bypassed || self.params.input_mute.value();
+        let cut = silenced && self.params.bypass_mode.value() == BypassMode::Cut;
+
+        (!silenced, !cut, bypassed)
+    }
+
+    /// The next value of the hold smoother. 1 while the hold parameter is on, gliding back to 0 after it's released.
+    fn next_hold(&mut self, held: bool) -> f32 {
+        if held != self.held {
+            self.held = held;
+            self.hold
+                .set_target(self.sample_rate, if held { 1. } else { 0. });
+        }
+
+        self.hold.next()
+    }
+
+    /// Run the current filter chain of a pair of channels. Input is the stereo signal, output is the resulting stereo signal.
+    fn run_filters(&mut self, pair: usize, input: F32x2) -> F32x2 {
+        self.filter_pipelines[pair]
+            .process_stereo(input.l(), input.r())
+            .into()
+    }
+
+    /// Run the filter chain on the input signal. This can probably be refactored out down the line. But for now it doesn't work correctly without
+    fn run_input_filters(&mut self, pair: usize, input: F32x2) -> F32x2 {
+        self.initial_filter_pipelines[pair]
+            .process_stereo(input.l(), input.r())
+            .into()
     }
 }
 
+#[cfg(feature = "plugin")]
 impl ClapPlugin for Delax {
     const CLAP_ID: &'static str = "com.ritzin-dev.delax";
-    const CLAP_DESCRIPTION: Option<&'static str> = Some("A short description of your plugin");
+    const CLAP_DESCRIPTION: Option<&'static str> = Some(
+        "A stereo delay with filters in the feedback loop, tape echo, string and multiband modes",
+    );
     const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
-    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_SUPPORT_URL: Option<&'static str> =
+        Some("https://github.com/awallenfang/delax/issues");
+    // Delay time, feedback and cutoff can be modulated per voice, the offsets of all voices are summed
+    const CLAP_POLY_MODULATION_CONFIG: Option<PolyModulationConfig> = Some(PolyModulationConfig {
+        max_voice_capacity: MAX_VOICES as u32,
+        supports_overlapping_voices: true,
+    });
 
-    // Don't forget to change these features
+    // The filters sit in the feedback loop, so the plugin is listed as a delay first
     const CLAP_FEATURES: &'static [ClapFeature] = &[
         ClapFeature::AudioEffect,
-        ClapFeature::Stereo,
         ClapFeature::Delay,
-        ClapFeature::Distortion,
+        ClapFeature::Filter,
+        ClapFeature::Stereo,
+        ClapFeature::Surround,
     ];
 }
 
@@ -392,5 +1655,307 @@ impl ClapPlugin for Delax {
 //     ];
 // }
 
+#[cfg(feature = "plugin")]
 nih_export_clap!(Delax);
 // nih_export_vst3!(Delax);
+
+#[cfg(all(test, feature = "plugin"))]
+mod tests {
+    use super::*;
+
+    fn prepared(num_channels: usize) -> Delax {
+        let mut plugin = Delax::default();
+        plugin.prepare(8000., num_channels);
+        plugin.params.reset_smoothers();
+        plugin.reset();
+        plugin
+    }
+
+    #[test]
+    fn surround_pairs_match_stereo() {
+        let mut stereo = prepared(2);
+        let mut quad = prepared(4);
+        let mut surround = prepared(6);
+
+        for i in 0..8000 {
+            let l = if i % 1000 == 0 { 1. } else { 0. };
+            let r = if i % 1500 == 0 { -0.5 } else { 0. };
+
+            let expected = stereo.process_sample(l, r);
+            let mut frame = [l, r, l, r];
+            quad.process_frame(&mut frame);
+            for pair in frame.chunks_exact(2) {
+                assert_eq!((pair[0], pair[1]), expected);
+            }
+
+            // The front and the surround pairs of 5.1 are delayed like stereo, the LFE is passed through
+            let mut frame = [l, r, l, r, l, r];
+            surround.process_frame(&mut frame);
+            assert_eq!((frame[0], frame[1]), expected);
+            assert_eq!((frame[4], frame[5]), expected);
+            assert_eq!(frame[LFE_CHANNEL], r);
+        }
+    }
+
+    #[test]
+    fn clearing_drops_the_repeats() {
+        let mut cleared = prepared(2);
+        let mut kept = prepared(2);
+        cleared.process_sample(1., 0.);
+        kept.process_sample(1., 0.);
+
+        // The first repeat comes after half a second
+        let mut energy = [0.; 2];
+        for i in 1..5000 {
+            if i == 3000 {
+                cleared.clear_repeats();
+            }
+            for (energy, plugin) in energy.iter_mut().zip([&mut cleared, &mut kept]) {
+                let (l, r) = plugin.process_sample(0., 0.);
+                *energy += l * l + r * r;
+            }
+        }
+        assert!(energy[0] < energy[1] * 1e-6, "{energy:?}");
+    }
+
+    #[test]
+    fn ending_a_voice_terminates_it() {
+        let mut plugin = prepared(2);
+        let id = PolyModTarget::Feedback.id(0);
+        plugin.handle_event(NoteEvent::PolyModulation {
+            timing: 0,
+            voice_id: 7,
+            poly_modulation_id: id,
+            normalized_offset: 0.2,
+        });
+        assert!((plugin.poly_mod.offset(id) - 0.2).abs() < 1e-6);
+
+        let reply = plugin.handle_event(NoteEvent::NoteOff {
+            timing: 12,
+            voice_id: Some(7),
+            channel: 1,
+            note: 60,
+            velocity: 0.,
+        });
+        assert_eq!(
+            reply,
+            Some(NoteEvent::VoiceTerminated {
+                timing: 12,
+                voice_id: Some(7),
+                channel: 1,
+                note: 60,
+            })
+        );
+        assert_eq!(plugin.poly_mod.offset(id), 0.);
+
+        // The voice already ended, and a voice that was never modulated isn't tracked
+        for voice_id in [7, 8] {
+            let reply = plugin.handle_event(NoteEvent::NoteOff {
+                timing: 0,
+                voice_id: Some(voice_id),
+                channel: 0,
+                note: 62,
+                velocity: 0.,
+            });
+            assert_eq!(reply, None);
+        }
+
+        // Notes without a voice ID don't belong to a voice
+        let reply = plugin.handle_event(NoteEvent::Choke {
+            timing: 0,
+            voice_id: None,
+            channel: 0,
+            note: 60,
+        });
+        assert_eq!(reply, None);
+    }
+
+    #[test]
+    fn injection_matrix_picks_the_side_of_the_first_repeat() {
+        let mut plugin = prepared(2);
+        let mut injection_matrix = FeedbackMatrix::identity(2);
+        injection_matrix.set_ping_pong_injection(1, 0.);
+        plugin.set_injection_matrix(injection_matrix);
+        plugin.set_feedback_matrix(FeedbackMatrix::ping_pong(2));
+
+        // An impulse on the left channel only
+        plugin.process_sample(1., 0.);
+
+        // The repeats come every half second, starting on the right and then bouncing to the left.
+        // The filters smear them a little, so the sides are compared around every repeat.
+        let mut energy = [[0.; 2]; 3];
+        for i in 1..14000 {
+            let (l, r) = plugin.process_sample(0., 0.);
+            if i >= 2000 {
+                let repeat = (i - 2000) / 4000;
+                energy[repeat][0] += l * l;
+                energy[repeat][1] += r * r;
+            }
+        }
+        assert!(energy[0][0] == 0. && energy[0][1] > 0.);
+        assert!(energy[1][0] > 0. && energy[1][1] == 0.);
+        assert!(energy[2][0] == 0. && energy[2][1] > 0.);
+    }
+
+    #[test]
+    fn feedback_goes_out_of_the_send() {
+        let mut plugin = prepared(2);
+        plugin.process_sample(1., 0.);
+
+        // Nothing is sent until the first repeat comes around, then the left channel feeds it back
+        let mut sent = Vec::new();
+        for _ in 1..4500 {
+            plugin.process_sample(0., 0.);
+            sent.push((plugin.sent[0], plugin.sent[1]));
+        }
+        assert!(sent[..3990].iter().all(|sent| *sent == (0., 0.)));
+        assert!(sent.iter().any(|(l, _)| l.abs() > 1e-6));
+        assert!(sent.iter().all(|(_, r)| *r == 0.));
+    }
+
+    #[test]
+    fn input_is_analyzed_before_the_trim() {
+        let mut plugin = prepared(2);
+        plugin.params.input_trim.smoothed.reset(0.5);
+        let analysis = plugin.input_analyzer.analysis().clone();
+        analysis.start();
+
+        // The wetness is at half, so before the first repeat the dry signal comes out at a quarter with the trim
+        let (l, _) = plugin.process_sample(0.4, 0.4);
+        assert!((l - 0.1).abs() < 1e-6);
+        for _ in 1..(input_analysis::ANALYSIS_SECONDS * 8000.) as usize {
+            plugin.process_sample(0.4, 0.4);
+        }
+        let levels = analysis.levels().unwrap();
+        assert!((levels.peak_db - util::gain_to_db(0.4)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn safety_clipper_holds_the_output_below_the_ceiling() {
+        let mut plugin = prepared(2);
+        let output = plugin.process_sample(8., -8.);
+
+        assert!(output.0 <= sanitize::SAFETY_CEILING && output.0 > 1.);
+        assert!(output.1 >= -sanitize::SAFETY_CEILING && output.1 < -1.);
+        assert!(plugin.visualization.bus().take_safety_clip_db() > 0.);
+    }
+
+    #[test]
+    fn rotation_matrix_moves_feedback_between_channels() {
+        let mut plugin = prepared(4);
+        plugin.set_feedback_matrix(FeedbackMatrix::rotation(4, 1.));
+
+        // An impulse on the first channel only
+        let mut frame = [1., 0., 0., 0.];
+        plugin.process_frame(&mut frame);
+
+        // Every hop through the delay takes half a second, so after 2.5 seconds the impulse went around once
+        let mut reached = [false; 4];
+        for _ in 0..20000 {
+            let mut frame = [0.; 4];
+            plugin.process_frame(&mut frame);
+            for (reached, sample) in reached.iter_mut().zip(frame) {
+                *reached |= sample.abs() > 1e-6;
+            }
+        }
+
+        assert_eq!(reached, [true; 4]);
+    }
+
+    #[test]
+    fn loop_gain_follows_the_routing() {
+        let mut plugin = prepared(2);
+        plugin.analyze_feedback_loop();
+        let loop_gain = plugin
+            .visualization
+            .bus()
+            .loop_gain
+            .load(std::sync::atomic::Ordering::Relaxed);
+        assert!(loop_gain > 0.);
+
+        let mut matrix = FeedbackMatrix::identity(2);
+        matrix.set(1, 1, 4.);
+        plugin.set_feedback_matrix(matrix);
+        plugin.analyze_feedback_loop();
+        let boosted = plugin
+            .visualization
+            .bus()
+            .loop_gain
+            .load(std::sync::atomic::Ordering::Relaxed);
+        assert!((boosted - loop_gain * 4.).abs() < 1e-5);
+    }
+
+    #[test]
+    fn hold_fades_in_and_out() {
+        let mut plugin = prepared(2);
+        assert_eq!(plugin.next_hold(false), 0.);
+
+        let first = plugin.next_hold(true);
+        assert!(first > 0. && first < 1.);
+        for _ in 0..1000 {
+            plugin.next_hold(true);
+        }
+        assert_eq!(plugin.next_hold(true), 1.);
+
+        // Releasing doesn't cut the feedback off, it falls back over the release time
+        let released = plugin.next_hold(false);
+        assert!(released > 0. && released < 1.);
+        for _ in 0..1000 {
+            plugin.next_hold(false);
+        }
+        assert_eq!(plugin.next_hold(false), 0.);
+    }
+
+    #[test]
+    fn solos_split_the_channels() {
+        let mut plugin = prepared(2);
+        plugin.params.set_solo(0, params::Solo::Dry);
+        plugin.params.set_solo(1, params::Solo::Wet);
+
+        // Nothing has been delayed yet, so the wet channel is silent and the dry channel passes the input
+        assert_eq!(plugin.process_sample(0.5, 0.5), (0.5, 0.));
+    }
+
+    #[test]
+    fn input_filters_follow_the_filter_mode() {
+        let mut plugin = prepared(4);
+        plugin.process_frame(&mut [0.; 4]);
+
+        // The filters start out as low passes, the parameters default to band passes
+        let band = [SVFFilterMode::Band; 2];
+        for filters in [&plugin.sin_svfs, &plugin.input_sin_svfs] {
+            for filter in filters {
+                assert_eq!(filter.lock().unwrap().first().filter().mode(), band);
+            }
+        }
+    }
+
+    #[test]
+    fn poly_modulation_is_summed_into_the_smoothers() {
+        let mut plugin = prepared(2);
+        let id = PolyModTarget::Feedback.id(0);
+        let settle = |param: &FloatParam| (0..10000).map(|_| param.smoothed.next()).last().unwrap();
+
+        for voice_id in [1, 2] {
+            plugin.handle_event(NoteEvent::PolyModulation {
+                timing: 0,
+                voice_id,
+                poly_modulation_id: id,
+                normalized_offset: 0.1,
+            });
+        }
+        let param = &plugin.params.delay_params.channels[0].feedback;
+        assert!((settle(param) - 0.7).abs() < 1e-6);
+
+        // Ending a voice takes its offset away, the other voice keeps modulating
+        plugin.handle_event(NoteEvent::NoteOff {
+            timing: 0,
+            voice_id: Some(1),
+            channel: 0,
+            note: 60,
+            velocity: 0.,
+        });
+        let param = &plugin.params.delay_params.channels[0].feedback;
+        assert!((settle(param) - 0.6).abs() < 1e-6);
+    }
+}
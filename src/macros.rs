@@ -0,0 +1,207 @@
+use std::{collections::HashMap, sync::RwLock};
+
+use nih_plug::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::smoothing::smoothed_params;
+
+/// The amount of macro knobs
+pub const NUM_MACROS: usize = 2;
+
+/// A parameter that follows a macro knob.
+///
+/// The values are normalized parameter values. At the bottom of the macro the parameter is at `a`,
+/// at the top it's at `b`, in between it morphs linearly.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MacroTarget {
+    /// The macro the parameter follows, starting at 0
+    pub macro_index: usize,
+    /// The ID of the parameter
+    pub param_id: String,
+    pub a: f32,
+    pub b: f32,
+}
+
+impl MacroTarget {
+    /// A target that starts at the current value of the parameter on both ends, so assigning it changes nothing yet
+    pub fn new(macro_index: usize, param_id: &str, value: f32) -> Self {
+        Self {
+            macro_index,
+            param_id: param_id.to_string(),
+            a: value,
+            b: value,
+        }
+    }
+
+    /// The normalized value of the parameter for a macro amount in the range 0 to 1
+    pub fn morph(&self, amount: f32) -> f32 {
+        self.a + (self.b - self.a) * amount.clamp(0., 1.)
+    }
+}
+
+/// Assign a parameter to a macro, replacing its previous assignment if it had one. A parameter follows at most one macro.
+pub fn assign(targets: &mut Vec<MacroTarget>, macro_index: usize, param_id: &str, value: f32) {
+    forget(targets, param_id);
+    targets.push(MacroTarget::new(macro_index, param_id, value));
+}
+
+/// Remove the assignment of a parameter, if there is one
+pub fn forget(targets: &mut Vec<MacroTarget>, param_id: &str) {
+    targets.retain(|target| target.param_id != param_id);
+}
+
+/// Drives the smoothers of the assigned parameters from the audio thread.
+///
+/// nih-plug only allows setting parameters through a GUI context, so the macros can't change the parameters themselves.
+/// Instead they move the smoothers, which is what the DSP reads. Only parameters that are read through their
+/// smoother can be morphed, all others are skipped. A host or GUI change of a morphed parameter takes over
+/// until the macro moves again, the last change wins.
+#[derive(Default)]
+pub struct MacroEngine {
+    /// The smoothed float parameters by ID, these are the only ones that can be morphed
+    params: HashMap<String, ParamPtr>,
+    /// The parameters the macros drove in the last block and the plain value they were set to
+    driven: Vec<(ParamPtr, f32)>,
+    /// Scratch space for the parameters driven in the current block
+    driving: Vec<(ParamPtr, f32)>,
+}
+
+// SAFETY: The pointers point into the parameters of the plugin, which outlive the engine and are only read through atomics
+unsafe impl Send for MacroEngine {}
+
+impl MacroEngine {
+    /// Collect the parameters that can be morphed from the parameter map of the plugin.
+    ///
+    /// The pointers need to stay valid for as long as the engine is used. This allocates, so it can't be called from the audio thread.
+    pub fn prepare(&mut self, param_map: Vec<(String, ParamPtr, String)>) {
+        self.params = smoothed_params(param_map);
+        self.driven = Vec::with_capacity(self.params.len());
+        self.driving = Vec::with_capacity(self.params.len());
+    }
+
+    /// Move the smoothers of all assigned parameters to the morphed values of the macro amounts. This is called once per block.
+    ///
+    /// Parameters that were unassigned since the last block go back to their own value. If the GUI is currently
+    /// changing the assignments, nothing changes until the next block.
+    pub fn apply(
+        &mut self,
+        targets: &RwLock<Vec<MacroTarget>>,
+        amounts: [f32; NUM_MACROS],
+        sample_rate: f32,
+    ) {
+        let Ok(targets) = targets.try_read() else {
+            return;
+        };
+
+        self.driving.clear();
+        for target in targets.iter() {
+            let (Some(ParamPtr::FloatParam(param)), Some(amount)) = (
+                self.params.get(&target.param_id),
+                amounts.get(target.macro_index),
+            ) else {
+                continue;
+            };
+            // SAFETY: The pointers are valid for as long as the engine is used, see [MacroEngine::prepare()]
+            let param = unsafe { &**param };
+            let value = param.preview_plain(target.morph(*amount));
+
+            // Setting the same target again would restart the ramp, so only changes are passed on
+            let param_ptr = ParamPtr::FloatParam(param);
+            if !self.driven.contains(&(param_ptr, value)) {
+                param.smoothed.set_target(sample_rate, value);
+            }
+            self.driving.push((param_ptr, value));
+        }
+
+        for (param_ptr, _) in &self.driven {
+            if self.driving.iter().any(|(driving, _)| driving == param_ptr) {
+                continue;
+            }
+            if let ParamPtr::FloatParam(param) = param_ptr {
+                // SAFETY: See above
+                let param = unsafe { &**param };
+                param.smoothed.set_target(sample_rate, param.value());
+            }
+        }
+
+        std::mem::swap(&mut self.driven, &mut self.driving);
+    }
+
+    /// Forget what the macros drove, so every assigned parameter is set again on the next block.
+    ///
+    /// This is needed whenever the smoothers are reset, for example when a state is loaded.
+    pub fn reset(&mut self) {
+        self.driven.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn smoothed_param() -> FloatParam {
+        FloatParam::new("Param", 0., FloatRange::Linear { min: 0., max: 100. })
+            .with_smoother(SmoothingStyle::Linear(10.))
+    }
+
+    #[test]
+    fn morph_between_a_and_b() {
+        let target = MacroTarget {
+            macro_index: 0,
+            param_id: String::from("wetness"),
+            a: 0.25,
+            b: 0.75,
+        };
+        assert_eq!(target.morph(0.), 0.25);
+        assert_eq!(target.morph(0.5), 0.5);
+        assert_eq!(target.morph(2.), 0.75);
+    }
+
+    #[test]
+    fn a_parameter_follows_one_macro() {
+        let mut targets = Vec::new();
+        assign(&mut targets, 0, "wetness", 0.5);
+        assign(&mut targets, 1, "wetness", 0.25);
+        assert_eq!(targets, vec![MacroTarget::new(1, "wetness", 0.25)]);
+
+        forget(&mut targets, "wetness");
+        assert!(targets.is_empty());
+    }
+
+    #[test]
+    fn macros_drive_the_smoothers() {
+        let param = smoothed_param();
+        let unsmoothed = FloatParam::new("Other", 0., FloatRange::Linear { min: 0., max: 1. });
+        let mut engine = MacroEngine::default();
+        engine.prepare(vec![
+            (String::from("param"), param.as_ptr(), String::new()),
+            (String::from("other"), unsmoothed.as_ptr(), String::new()),
+        ]);
+
+        let targets = RwLock::new(vec![
+            MacroTarget {
+                macro_index: 1,
+                param_id: String::from("param"),
+                a: 0.,
+                b: 1.,
+            },
+            MacroTarget {
+                macro_index: 1,
+                param_id: String::from("other"),
+                a: 0.,
+                b: 1.,
+            },
+        ]);
+        engine.apply(&targets, [0., 0.5], 1000.);
+        // The smoother reaches the morphed value after its ramp
+        let value = (0..20).map(|_| param.smoothed.next()).last().unwrap();
+        assert_eq!(value, 50.);
+        assert_eq!(unsmoothed.smoothed.next(), 0.);
+
+        // An unassigned parameter goes back to its own value
+        targets.write().unwrap().clear();
+        engine.apply(&targets, [0., 0.5], 1000.);
+        let value = (0..20).map(|_| param.smoothed.next()).last().unwrap();
+        assert_eq!(value, 0.);
+    }
+}
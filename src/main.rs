@@ -1,7 +1,13 @@
 use delax::Delax;
 use nih_plug::prelude::*;
 
+/// Run Delax as a standalone application, without a host.
+///
+/// The command line is handled by nih-plug's standalone wrapper, run with `--help` for all the options.
+/// The most useful ones are `--backend` (`jack`, `alsa`, `core-audio`, `wasapi`, ...),
+/// `--sample-rate` and `--period-size` for the buffer size.
 pub fn main() {
-    nih_export_standalone::<Delax>();
-    println!("Currently no standalone active!")
+    if !nih_export_standalone::<Delax>() {
+        std::process::exit(1);
+    }
 }
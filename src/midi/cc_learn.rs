@@ -0,0 +1,331 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        RwLock,
+    },
+};
+
+use nih_plug::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::smoothing::smoothed_params;
+
+/// The maximum amount of CC mappings, every mapping needs its own slot in the [CcRouter]
+pub const MAX_CC_MAPPINGS: usize = 32;
+
+/// How the incoming CC value is shaped before it's mapped onto the parameter range
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum CcCurve {
+    #[default]
+    Linear,
+    /// Finer control at the bottom of the range
+    Exponential,
+    /// Finer control at the top of the range
+    Logarithmic,
+}
+
+impl CcCurve {
+    /// Shape a value in the range 0 to 1
+    pub fn apply(self, value: f32) -> f32 {
+        match self {
+            CcCurve::Linear => value,
+            CcCurve::Exponential => value * value,
+            CcCurve::Logarithmic => value.sqrt(),
+        }
+    }
+
+    /// The next curve, so the GUI can cycle through them
+    pub fn next(self) -> Self {
+        match self {
+            CcCurve::Linear => CcCurve::Exponential,
+            CcCurve::Exponential => CcCurve::Logarithmic,
+            CcCurve::Logarithmic => CcCurve::Linear,
+        }
+    }
+
+    /// The name shown in the GUI
+    pub fn name(self) -> &'static str {
+        match self {
+            CcCurve::Linear => "Linear",
+            CcCurve::Exponential => "Exponential",
+            CcCurve::Logarithmic => "Logarithmic",
+        }
+    }
+}
+
+/// A mapping from a MIDI CC to a parameter.
+///
+/// The range is given as normalized parameter values. `min` may be larger than `max` to invert the control.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CcMapping {
+    /// The ID of the mapped parameter
+    pub param_id: String,
+    /// The MIDI channel, starting at 0
+    pub channel: u8,
+    pub cc: u8,
+    /// The normalized parameter value for a CC value of 0
+    pub min: f32,
+    /// The normalized parameter value for a CC value of 127
+    pub max: f32,
+    pub curve: CcCurve,
+}
+
+impl CcMapping {
+    /// Map a CC to a parameter using the full range of the parameter
+    pub fn new(param_id: impl Into<String>, channel: u8, cc: u8) -> Self {
+        Self {
+            param_id: param_id.into(),
+            channel,
+            cc,
+            min: 0.,
+            max: 1.,
+            curve: CcCurve::Linear,
+        }
+    }
+
+    /// Whether the mapping listens to this CC
+    pub fn matches(&self, channel: u8, cc: u8) -> bool {
+        self.channel == channel && self.cc == cc
+    }
+
+    /// Turn a CC value in the range 0 to 1 into a normalized parameter value
+    pub fn map_value(&self, cc_value: f32) -> f32 {
+        let shaped = self.curve.apply(cc_value.clamp(0., 1.));
+        self.min + (self.max - self.min) * shaped
+    }
+}
+
+/// Map the next CC to a parameter, replacing the previous mapping of that parameter.
+///
+/// Returns false if there is no free slot left for the mapping.
+pub fn learn(mappings: &mut Vec<CcMapping>, param_id: &str, channel: u8, cc: u8) -> bool {
+    mappings.retain(|mapping| mapping.param_id != param_id);
+    if mappings.len() >= MAX_CC_MAPPINGS {
+        return false;
+    }
+
+    mappings.push(CcMapping::new(param_id, channel, cc));
+    true
+}
+
+/// Remove the mapping of a parameter, if there is one
+pub fn forget(mappings: &mut Vec<CcMapping>, param_id: &str) {
+    mappings.retain(|mapping| mapping.param_id != param_id);
+}
+
+/// The routing layer between the incoming CCs on the audio thread and the parameter setters in the GUI.
+///
+/// nih-plug only allows setting parameters through a GUI context, so the audio thread can't do it itself.
+/// It moves the smoothers of the mapped parameters through a [CcEngine] right away, and stores the mapped values in
+/// one slot per mapping, which the editor then takes and sets on the parameters so they stick.
+/// Only the latest value of every mapping is kept, which is all that matters for a parameter.
+///
+/// Nothing here allocates or blocks, so it's safe to use from the audio thread.
+pub struct CcRouter {
+    /// The last received CC, packed as `sequence << 16 | channel << 8 | cc`. Zero if there hasn't been one yet.
+    last_cc: AtomicU32,
+    /// The pending normalized value of every mapping, NaN if there is none
+    values: [AtomicF32; MAX_CC_MAPPINGS],
+}
+
+impl Default for CcRouter {
+    fn default() -> Self {
+        Self {
+            last_cc: AtomicU32::new(0),
+            values: std::array::from_fn(|_| AtomicF32::new(f32::NAN)),
+        }
+    }
+}
+
+impl CcRouter {
+    /// Route an incoming CC to all the mappings listening to it. This is called from the audio thread.
+    ///
+    /// The smoothers of the mapped parameters are moved by the engine. If the GUI is currently changing the mappings
+    /// the CC is dropped instead of waiting for it.
+    pub fn route(
+        &self,
+        mappings: &RwLock<Vec<CcMapping>>,
+        engine: &CcEngine,
+        channel: u8,
+        cc: u8,
+        value: f32,
+        sample_rate: f32,
+    ) {
+        self.record(channel, cc);
+
+        let Ok(mappings) = mappings.try_read() else {
+            return;
+        };
+        for (slot, mapping) in self.values.iter().zip(mappings.iter()) {
+            if mapping.matches(channel, cc) {
+                let value = mapping.map_value(value);
+                engine.apply(&mapping.param_id, value, sample_rate);
+                slot.store(value, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Take the pending value of the mapping at an index, if there is one
+    pub fn take_value(&self, index: usize) -> Option<f32> {
+        let value = self.values[index].swap(f32::NAN, Ordering::Relaxed);
+        (!value.is_nan()).then_some(value)
+    }
+
+    /// Drop all the pending values. This needs to be done whenever the mappings change, since the slots follow their order.
+    pub fn clear_values(&self) {
+        for slot in &self.values {
+            slot.store(f32::NAN, Ordering::Relaxed);
+        }
+    }
+
+    /// The last received CC as `(sequence, channel, cc)`.
+    ///
+    /// The sequence number changes with every CC, so it can be used to check whether a new one arrived.
+    pub fn last_cc(&self) -> Option<(u16, u8, u8)> {
+        let packed = self.last_cc.load(Ordering::Relaxed);
+        (packed != 0).then_some(((packed >> 16) as u16, (packed >> 8) as u8, packed as u8))
+    }
+
+    fn record(&self, channel: u8, cc: u8) {
+        let sequence = (self.last_cc.load(Ordering::Relaxed) >> 16) as u16;
+        // Skip zero so a recorded CC can never look like no CC at all
+        let sequence = sequence.checked_add(1).unwrap_or(1);
+        let packed = (sequence as u32) << 16 | (channel as u32) << 8 | cc as u32;
+        self.last_cc.store(packed, Ordering::Relaxed);
+    }
+}
+
+/// Drives the smoothers of the mapped parameters from the audio thread.
+///
+/// Like the macros this moves the smoothers, which is what the DSP reads, so a CC takes effect without the editor.
+/// Only parameters that are read through their smoother can be driven like this, all others are only set once the
+/// editor takes the value from the [CcRouter].
+#[derive(Default)]
+pub struct CcEngine {
+    /// The smoothed float parameters by ID, these are the only ones that can be driven
+    params: HashMap<String, ParamPtr>,
+}
+
+// SAFETY: The pointers point into the parameters of the plugin, which outlive the engine and are only read through atomics
+unsafe impl Send for CcEngine {}
+
+impl CcEngine {
+    /// Collect the parameters that can be driven from the parameter map of the plugin.
+    ///
+    /// The pointers need to stay valid for as long as the engine is used. This allocates, so it can't be called from the audio thread.
+    pub fn prepare(&mut self, param_map: Vec<(String, ParamPtr, String)>) {
+        self.params = smoothed_params(param_map);
+    }
+
+    /// Move the smoother of a parameter to a normalized value, if it's one that can be driven
+    pub fn apply(&self, param_id: &str, value: f32, sample_rate: f32) {
+        if let Some(ParamPtr::FloatParam(param)) = self.params.get(param_id) {
+            // SAFETY: The pointers are valid for as long as the engine is used, see [CcEngine::prepare()]
+            let param = unsafe { &**param };
+            param
+                .smoothed
+                .set_target(sample_rate, param.preview_plain(value));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mapping_range_and_curve() {
+        let mut mapping = CcMapping::new("wetness", 0, 1);
+        assert_eq!(mapping.map_value(0.5), 0.5);
+
+        mapping.min = 1.;
+        mapping.max = 0.;
+        assert_eq!(mapping.map_value(0.25), 0.75);
+
+        mapping.min = 0.;
+        mapping.max = 1.;
+        mapping.curve = CcCurve::Exponential;
+        assert_eq!(mapping.map_value(0.5), 0.25);
+        assert_eq!(mapping.map_value(2.), 1.);
+    }
+
+    #[test]
+    fn learning_replaces_the_old_mapping() {
+        let mut mappings = Vec::new();
+        assert!(learn(&mut mappings, "wetness", 0, 1));
+        assert!(learn(&mut mappings, "delay_1", 0, 2));
+        assert!(learn(&mut mappings, "wetness", 3, 7));
+
+        assert_eq!(
+            mappings,
+            vec![
+                CcMapping::new("delay_1", 0, 2),
+                CcMapping::new("wetness", 3, 7)
+            ]
+        );
+
+        forget(&mut mappings, "delay_1");
+        assert_eq!(mappings, vec![CcMapping::new("wetness", 3, 7)]);
+    }
+
+    #[test]
+    fn learning_is_limited() {
+        let mut mappings = Vec::new();
+        for i in 0..MAX_CC_MAPPINGS {
+            assert!(learn(&mut mappings, &format!("param_{i}"), 0, i as u8));
+        }
+
+        assert!(!learn(&mut mappings, "one_too_many", 0, 100));
+        assert_eq!(mappings.len(), MAX_CC_MAPPINGS);
+    }
+
+    #[test]
+    fn router_routes_to_matching_mappings() {
+        let router = CcRouter::default();
+        let mappings = RwLock::new(vec![
+            CcMapping::new("wetness", 0, 1),
+            CcMapping::new("delay_1", 0, 2),
+            CcMapping::new("delay_2", 0, 1),
+        ]);
+
+        assert_eq!(router.last_cc(), None);
+        router.route(&mappings, &CcEngine::default(), 0, 1, 0.5, 1000.);
+
+        assert_eq!(router.take_value(0), Some(0.5));
+        assert_eq!(router.take_value(1), None);
+        assert_eq!(router.take_value(2), Some(0.5));
+        // Taking a value consumes it
+        assert_eq!(router.take_value(0), None);
+    }
+
+    #[test]
+    fn router_tracks_new_ccs() {
+        let router = CcRouter::default();
+        let mappings = RwLock::new(Vec::new());
+
+        router.route(&mappings, &CcEngine::default(), 2, 74, 0., 1000.);
+        let (first, channel, cc) = router.last_cc().unwrap();
+        assert_eq!((channel, cc), (2, 74));
+
+        router.route(&mappings, &CcEngine::default(), 2, 74, 1., 1000.);
+        let (second, _, _) = router.last_cc().unwrap();
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn router_drives_the_smoothers() {
+        let param = FloatParam::new("Param", 0., FloatRange::Linear { min: 0., max: 100. })
+            .with_smoother(SmoothingStyle::Linear(10.));
+        let mut engine = CcEngine::default();
+        engine.prepare(vec![(String::from("param"), param.as_ptr(), String::new())]);
+        let router = CcRouter::default();
+        let mappings = RwLock::new(vec![CcMapping::new("param", 0, 1)]);
+
+        router.route(&mappings, &engine, 0, 1, 0.25, 1000.);
+        // The smoother reaches the mapped value after its ramp, without the editor taking the value
+        let value = (0..20).map(|_| param.smoothed.next()).last().unwrap();
+        assert_eq!(value, 25.);
+        assert_eq!(router.take_value(0), Some(0.25));
+    }
+}
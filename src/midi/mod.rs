@@ -0,0 +1,3 @@
+pub mod cc_learn;
+pub mod note_tracker;
+pub mod poly_mod;
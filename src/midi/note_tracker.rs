@@ -0,0 +1,163 @@
+use nih_plug::util;
+
+use crate::filters::params::{MAX_CUTOFF, MIN_CUTOFF};
+
+/// The period of a MIDI note in ms. Used as a delay time, the feedback loop resonates at the note's pitch.
+///
+/// Usage:
+/// ```
+/// use delax::midi::note_tracker::note_period_ms;
+///
+/// // A4 is at 440 Hz
+/// assert!((note_period_ms(69) - 1000. / 440.).abs() < 1e-6);
+/// ```
+pub fn note_period_ms(note: u8) -> f32 {
+    1000. / util::midi_note_to_freq(note)
+}
+
+/// The note at which keytracking leaves the cutoff untouched, C4
+pub const KEYTRACK_CENTER_NOTE: u8 = 60;
+
+/// Offset a filter cutoff by the distance of a note to [KEYTRACK_CENTER_NOTE].
+///
+/// With an amount of 1 the cutoff follows the note exactly, so an octave up doubles the cutoff.
+/// The result is limited to the range of the cutoff parameter.
+///
+/// Usage:
+/// ```
+/// use delax::midi::note_tracker::keytrack_cutoff;
+///
+/// assert_eq!(keytrack_cutoff(500., 72, 1.), 1000.);
+/// assert_eq!(keytrack_cutoff(500., 72, 0.), 500.);
+/// ```
+pub fn keytrack_cutoff(cutoff: f32, note: u8, amount: f32) -> f32 {
+    let octaves = (note as f32 - KEYTRACK_CENTER_NOTE as f32) / 12.;
+    (cutoff * (octaves * amount).exp2()).clamp(MIN_CUTOFF, MAX_CUTOFF)
+}
+
+/// Follows the incoming MIDI notes and turns them into a delay time that glides from note to note.
+/// It also remembers the last note for keytracking.
+///
+/// The last played note wins. The delay time stays at the note after it's released, so the resonance can ring out.
+pub struct NoteTracker {
+    sample_rate: f32,
+    /// The last played note since the last reset
+    note: Option<u8>,
+    /// The delay time of the last note in ms
+    target: f32,
+    /// The current, possibly still gliding, delay time in ms
+    current: f32,
+}
+
+impl NoteTracker {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            sample_rate,
+            note: None,
+            target: 0.,
+            current: 0.,
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+    }
+
+    /// Start gliding to a new note. The first note after a reset is jumped to directly.
+    pub fn note_on(&mut self, note: u8) {
+        self.target = note_period_ms(note);
+        if self.note.is_none() {
+            self.current = self.target;
+        }
+        self.note = Some(note);
+    }
+
+    /// The last played note, if there has been one since the last reset
+    pub fn note(&self) -> Option<u8> {
+        self.note
+    }
+
+    /// Advance the glide by one sample and return the delay time in ms, or None if no note has been played yet.
+    ///
+    /// The glide time is the time in ms it takes to get most of the way to the new note.
+    pub fn next(&mut self, glide_ms: f32) -> Option<f32> {
+        self.note?;
+
+        let glide_samples = glide_ms / 1000. * self.sample_rate;
+        if glide_samples < 1. {
+            self.current = self.target;
+        } else {
+            let coefficient = (-1. / glide_samples).exp();
+            self.current = self.target + (self.current - self.target) * coefficient;
+        }
+
+        Some(self.current)
+    }
+
+    /// Forget the played notes
+    pub fn reset(&mut self) {
+        self.note = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inactive_until_a_note_arrives() {
+        let mut tracker = NoteTracker::new(48000.);
+        assert_eq!(tracker.next(10.), None);
+
+        tracker.note_on(69);
+        assert_eq!(tracker.next(10.), Some(note_period_ms(69)));
+
+        tracker.reset();
+        assert_eq!(tracker.next(10.), None);
+    }
+
+    #[test]
+    fn octave_halves_the_delay_time() {
+        assert!((note_period_ms(57) - 2. * note_period_ms(69)).abs() < 1e-4);
+    }
+
+    #[test]
+    fn glides_to_the_next_note() {
+        let mut tracker = NoteTracker::new(1000.);
+        tracker.note_on(60);
+        tracker.note_on(72);
+
+        let start = note_period_ms(60);
+        let target = note_period_ms(72);
+
+        // After one glide time the delay should be most of the way there, but not all
+        let mut time = 0.;
+        for _ in 0..100 {
+            time = tracker.next(100.).unwrap();
+        }
+        assert!(time < start && time > target);
+        assert!((time - target) / (start - target) < 0.4);
+
+        for _ in 0..2000 {
+            time = tracker.next(100.).unwrap();
+        }
+        assert!((time - target).abs() < 1e-4);
+    }
+
+    #[test]
+    fn keytracking_follows_the_note() {
+        assert_eq!(keytrack_cutoff(1000., KEYTRACK_CENTER_NOTE, 1.), 1000.);
+        assert_eq!(keytrack_cutoff(1000., 48, 1.), 500.);
+        assert!((keytrack_cutoff(1000., 72, 0.5) - 1000. * 2f32.sqrt()).abs() < 1e-3);
+        assert_eq!(keytrack_cutoff(15000., 127, 1.), 20000.);
+    }
+
+    #[test]
+    fn no_glide_jumps() {
+        let mut tracker = NoteTracker::new(48000.);
+        tracker.note_on(60);
+        tracker.note_on(72);
+
+        assert_eq!(tracker.next(0.), Some(note_period_ms(72)));
+    }
+}
@@ -0,0 +1,166 @@
+use crate::delay_engine::params::NUM_CHANNELS;
+
+/// The most voices the host can modulate at once. Modulation of further voices is ignored until a voice ends.
+pub const MAX_VOICES: usize = 16;
+
+/// The parameters of a channel that the host can modulate per voice
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PolyModTarget {
+    DelayTime,
+    Feedback,
+    Cutoff,
+}
+
+impl PolyModTarget {
+    const ALL: [PolyModTarget; 3] = [
+        PolyModTarget::DelayTime,
+        PolyModTarget::Feedback,
+        PolyModTarget::Cutoff,
+    ];
+
+    /// The polyphonic modulation ID of the target on a channel, unique across all parameters.
+    ///
+    /// Usage:
+    /// ```
+    /// use delax::midi::poly_mod::PolyModTarget;
+    ///
+    /// let id = PolyModTarget::Cutoff.id(1);
+    /// assert_eq!(PolyModTarget::from_id(id), Some((PolyModTarget::Cutoff, 1)));
+    /// ```
+    pub const fn id(self, channel: usize) -> u32 {
+        (self as usize * NUM_CHANNELS + channel) as u32
+    }
+
+    /// The target and channel a polyphonic modulation ID belongs to
+    pub fn from_id(id: u32) -> Option<(PolyModTarget, usize)> {
+        let id = id as usize;
+        let target = *Self::ALL.get(id / NUM_CHANNELS)?;
+        Some((target, id % NUM_CHANNELS))
+    }
+}
+
+/// The amount of polyphonic modulation IDs
+const NUM_IDS: usize = PolyModTarget::ALL.len() * NUM_CHANNELS;
+
+/// The index of a modulation ID in the offsets of a voice, if it's one of Delax' IDs
+fn slot(id: u32) -> Option<usize> {
+    Some(id as usize).filter(|slot| *slot < NUM_IDS)
+}
+
+/// The modulation offsets of a single voice
+struct Voice {
+    voice_id: i32,
+    offsets: [f32; NUM_IDS],
+}
+
+/// Collects the polyphonic modulation of the host and sums it into a single offset per parameter.
+///
+/// Delax has no voices of its own, every delay line is shared by all notes. So instead of modulating voices,
+/// the offsets of all voices that are currently playing are added up. This is the fallback CLAP asks for
+/// when a plugin can't modulate per voice.
+pub struct PolyModulation {
+    voices: Vec<Voice>,
+}
+
+impl Default for PolyModulation {
+    fn default() -> Self {
+        Self {
+            voices: Vec::with_capacity(MAX_VOICES),
+        }
+    }
+}
+
+impl PolyModulation {
+    /// Set the normalized offset of a voice for a modulation ID
+    pub fn set(&mut self, voice_id: i32, id: u32, offset: f32) {
+        let Some(slot) = slot(id) else {
+            return;
+        };
+
+        if let Some(voice) = self
+            .voices
+            .iter_mut()
+            .find(|voice| voice.voice_id == voice_id)
+        {
+            voice.offsets[slot] = offset;
+        } else if self.voices.len() < MAX_VOICES {
+            let mut offsets = [0.; NUM_IDS];
+            offsets[slot] = offset;
+            self.voices.push(Voice { voice_id, offsets });
+        }
+    }
+
+    /// Forget the offsets of a voice that ended. Returns the IDs the voice modulated, so they can be updated, or
+    /// `None` if the voice never modulated anything.
+    pub fn end_voice(&mut self, voice_id: i32) -> Option<impl Iterator<Item = u32>> {
+        let index = self
+            .voices
+            .iter()
+            .position(|voice| voice.voice_id == voice_id)?;
+        let offsets = self.voices.swap_remove(index).offsets;
+
+        Some((0..NUM_IDS as u32).filter(move |id| offsets[*id as usize] != 0.))
+    }
+
+    /// The summed normalized offset of all voices for a modulation ID
+    pub fn offset(&self, id: u32) -> f32 {
+        let Some(slot) = slot(id) else {
+            return 0.;
+        };
+
+        self.voices.iter().map(|voice| voice.offsets[slot]).sum()
+    }
+
+    pub fn reset(&mut self) {
+        self.voices.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_are_unique() {
+        let ids: Vec<_> = PolyModTarget::ALL
+            .iter()
+            .flat_map(|target| (0..NUM_CHANNELS).map(|channel| target.id(channel)))
+            .collect();
+        for (i, id) in ids.iter().enumerate() {
+            assert!(!ids[i + 1..].contains(id));
+            assert_eq!(*id as usize, i);
+        }
+        assert_eq!(PolyModTarget::from_id(NUM_IDS as u32), None);
+    }
+
+    #[test]
+    fn voices_are_summed() {
+        let mut modulation = PolyModulation::default();
+        let id = PolyModTarget::Feedback.id(0);
+        modulation.set(1, id, 0.25);
+        modulation.set(2, id, -0.1);
+        modulation.set(1, id, 0.3);
+        assert!((modulation.offset(id) - 0.2).abs() < 1e-6);
+        assert_eq!(modulation.offset(PolyModTarget::Feedback.id(1)), 0.);
+
+        assert_eq!(
+            modulation.end_voice(1).unwrap().collect::<Vec<_>>(),
+            vec![id]
+        );
+        assert!(modulation.end_voice(1).is_none());
+        assert!((modulation.offset(id) + 0.1).abs() < 1e-6);
+    }
+
+    #[test]
+    fn voices_beyond_the_capacity_are_ignored() {
+        let mut modulation = PolyModulation::default();
+        let id = PolyModTarget::Cutoff.id(0);
+        for voice_id in 0..MAX_VOICES as i32 + 4 {
+            modulation.set(voice_id, id, 0.01);
+        }
+        assert!((modulation.offset(id) - MAX_VOICES as f32 * 0.01).abs() < 1e-6);
+        // Unknown IDs don't modulate anything
+        modulation.set(0, 1000, 1.);
+        assert_eq!(modulation.offset(1000), 0.);
+    }
+}
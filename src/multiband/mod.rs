@@ -0,0 +1,7 @@
+pub use delax_dsp::multiband::{audible_bands, MAX_BANDS, MAX_BAND_MS};
+#[cfg(feature = "multiband")]
+pub use delax_dsp::multiband::{BandSplitter, Crossover, MultibandDelay};
+#[cfg(feature = "plugin")]
+pub mod params;
+#[cfg(all(feature = "plugin", feature = "multiband"))]
+pub mod stage;
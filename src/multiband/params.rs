@@ -0,0 +1,146 @@
+use nih_plug::prelude::*;
+
+use super::{audible_bands, MAX_BANDS, MAX_BAND_MS};
+use crate::{
+    param_snapshot::{bool_param, float_param, int_param},
+    smoothing::Response,
+};
+
+/// The default delay times of the bands from the lowest to the highest, long repeats on the lows like a dub delay
+const DEFAULT_TIMES: [f32; MAX_BANDS] = [600., 300., 120.];
+
+/// The delay time, the feedback and the solo and mute of one band of the multiband delay
+#[derive(Params)]
+pub struct BandParams {
+    #[id = "band_time"]
+    pub time: FloatParam,
+    #[id = "band_feedback"]
+    pub feedback: FloatParam,
+    #[id = "band_solo"]
+    pub solo: BoolParam,
+    #[id = "band_mute"]
+    pub mute: BoolParam,
+}
+
+impl BandParams {
+    pub fn new(band: usize, response: &Response) -> Self {
+        let number = band + 1;
+        Self {
+            time: float_param(
+                format!("Band {number} Time"),
+                DEFAULT_TIMES[band],
+                FloatRange::Skewed {
+                    min: 1.,
+                    max: MAX_BAND_MS,
+                    factor: FloatRange::skew_factor(-1.5),
+                },
+            )
+            .with_smoother(response.style())
+            .with_unit(" ms")
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
+            feedback: float_param(
+                format!("Band {number} Feedback"),
+                0.4,
+                FloatRange::Linear { min: 0., max: 1. },
+            )
+            .with_smoother(response.style())
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            solo: bool_param(format!("Band {number} Solo"), false),
+            mute: bool_param(format!("Band {number} Mute"), false),
+        }
+    }
+}
+
+/// The controls of the multiband mode, which splits the input into bands that each repeat at their own time and feedback
+#[derive(Params)]
+pub struct MultibandParams {
+    /// Replace the main delay with the delays of the bands
+    #[id = "multiband"]
+    pub enabled: BoolParam,
+    /// Split into two or three bands, two bands only use the low crossover
+    #[id = "multiband_bands"]
+    pub bands: IntParam,
+    #[id = "crossover_low"]
+    pub crossover_low: FloatParam,
+    #[id = "crossover_high"]
+    pub crossover_high: FloatParam,
+    /// From the lowest band to the highest one
+    #[nested(array, group = "Band")]
+    pub band: [BandParams; MAX_BANDS],
+}
+
+impl MultibandParams {
+    pub fn new(response: &Response) -> Self {
+        let crossover = |name: &str, default: f32, min: f32, max: f32| {
+            float_param(
+                name,
+                default,
+                FloatRange::Skewed {
+                    min,
+                    max,
+                    factor: FloatRange::skew_factor(-2.),
+                },
+            )
+            .with_smoother(response.logarithmic_style())
+            .with_value_to_string(formatters::v2s_f32_hz_then_khz(0))
+            .with_string_to_value(formatters::s2v_f32_hz_then_khz())
+        };
+
+        Self {
+            enabled: bool_param("Multiband", false),
+            bands: int_param(
+                "Bands",
+                2,
+                IntRange::Linear {
+                    min: 2,
+                    max: MAX_BANDS as i32,
+                },
+            ),
+            crossover_low: crossover("Low Crossover", 300., 40., 2000.),
+            crossover_high: crossover("High Crossover", 3000., 500., 16000.),
+            band: std::array::from_fn(|band| BandParams::new(band, response)),
+        }
+    }
+
+    /// The amount of bands the input is split into
+    pub fn band_count(&self) -> usize {
+        self.bands.value() as usize
+    }
+
+    /// Which bands are heard with the solos and mutes of the bands
+    pub fn audible(&self) -> [bool; MAX_BANDS] {
+        audible_bands(
+            self.band.each_ref().map(|band| band.solo.value()),
+            self.band.each_ref().map(|band| band.mute.value()),
+            self.band_count(),
+        )
+    }
+
+    /// Get the next smoothed delay time of every band
+    pub fn next_times(&self) -> [f32; MAX_BANDS] {
+        self.band.each_ref().map(|band| band.time.smoothed.next())
+    }
+
+    /// Get the next smoothed feedback of every band
+    pub fn next_feedbacks(&self) -> [f32; MAX_BANDS] {
+        self.band
+            .each_ref()
+            .map(|band| band.feedback.smoothed.next())
+    }
+
+    /// Snap all the smoothers to their current values
+    pub fn reset_smoothers(&self) {
+        let bands = self
+            .band
+            .iter()
+            .flat_map(|band| [&band.time, &band.feedback]);
+        for param in [&self.crossover_low, &self.crossover_high]
+            .into_iter()
+            .chain(bands)
+        {
+            param.smoothed.reset(param.value());
+        }
+    }
+}
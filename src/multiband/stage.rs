@@ -0,0 +1,111 @@
+use super::{params::MultibandParams, MultibandDelay, MAX_BANDS};
+use crate::{simd::F32x2, smoothing::Gate, GATE_FADE_MS};
+
+/// How the band delays are heard for one sample, read from the parameters by [MultibandStage::next_mix()]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BandMix {
+    /// How far the band delays replace the main delay, from 0 to 1
+    pub gain: f32,
+    pub feedbacks: [f32; MAX_BANDS],
+    /// The faded levels of the bands, for their solos and mutes
+    pub levels: [f32; MAX_BANDS],
+    pub bands: usize,
+}
+
+/// The multiband mode of the plugin, with the band delays of every channel.
+///
+/// The band delays fade in over the main delay, which keeps running underneath, and every band fades in and out
+/// when it's soloed or muted. While the mode is off the delays follow their crossovers and times but aren't run.
+pub struct MultibandStage {
+    delays: Vec<MultibandDelay>,
+    /// Fades between the main delay and the band delays when the multiband mode is switched
+    gate: Gate,
+    band_gates: [Gate; MAX_BANDS],
+    /// Whether the multiband mode was on at the last sample, so its delay lines are emptied when it's turned on again
+    enabled: bool,
+}
+
+impl MultibandStage {
+    pub fn new(sample_rate: f32, channels: usize) -> Self {
+        Self {
+            delays: (0..channels)
+                .map(|_| MultibandDelay::new(sample_rate))
+                .collect(),
+            gate: Gate::new(GATE_FADE_MS, false),
+            band_gates: std::array::from_fn(|_| Gate::new(GATE_FADE_MS, true)),
+            enabled: false,
+        }
+    }
+
+    /// Rebuild the band delays for a new sample rate or amount of channels
+    pub fn prepare(&mut self, sample_rate: f32, channels: usize) {
+        self.delays = (0..channels)
+            .map(|_| MultibandDelay::new(sample_rate))
+            .collect();
+    }
+
+    /// Empty the band delays and jump to the state of the parameters without fading. `enabled` is whether the
+    /// multiband mode runs after the algorithm had its say.
+    pub fn reset(&mut self, params: &MultibandParams, enabled: bool) {
+        self.delays.iter_mut().for_each(MultibandDelay::reset);
+        self.enabled = enabled;
+        self.gate.reset(enabled);
+        for (gate, audible) in self.band_gates.iter_mut().zip(params.audible()) {
+            gate.reset(audible);
+        }
+    }
+
+    /// Follow the crossovers and times of the parameters, once per sample
+    pub fn update(&mut self, params: &MultibandParams, enabled: bool) {
+        if enabled && !self.enabled {
+            self.delays.iter_mut().for_each(MultibandDelay::reset);
+        }
+        self.enabled = enabled;
+        let (crossover_low, crossover_high) = (
+            params.crossover_low.smoothed.next(),
+            params.crossover_high.smoothed.next(),
+        );
+        let times = params.next_times();
+        for delay in &mut self.delays {
+            delay
+                .splitter()
+                .set_crossovers(crossover_low, crossover_high);
+            delay.set_times(times);
+        }
+    }
+
+    /// Advance the fades and the feedback smoothers by a sample. Returns `None` while the band delays are off and
+    /// faded out, so they don't need to run.
+    pub fn next_mix(
+        &mut self,
+        params: &MultibandParams,
+        sample_rate: f32,
+        repeat_gain: f32,
+    ) -> Option<BandMix> {
+        let gain = self.gate.next(sample_rate, self.enabled);
+        let feedbacks = params
+            .next_feedbacks()
+            .map(|feedback| feedback * repeat_gain);
+        let audible = params.audible();
+        let levels =
+            std::array::from_fn(|band| self.band_gates[band].next(sample_rate, audible[band]));
+
+        (self.enabled || gain > 0.).then_some(BandMix {
+            gain,
+            feedbacks,
+            levels,
+            bands: params.band_count(),
+        })
+    }
+
+    /// Run the gated input of a pair of channels through their band delays and fade them in over what the main
+    /// delays of the pair put out
+    pub fn process(&mut self, pair: usize, gated: F32x2, popped: F32x2, mix: &BandMix) -> F32x2 {
+        let (l, r) = (pair * 2, pair * 2 + 1);
+        let bands = F32x2::new(
+            self.delays[l].process(gated.l(), mix.feedbacks, mix.levels, mix.bands),
+            self.delays[r].process(gated.r(), mix.feedbacks, mix.levels, mix.bands),
+        );
+        popped + (bands - popped) * mix.gain
+    }
+}
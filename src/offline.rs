@@ -0,0 +1,151 @@
+use std::sync::Arc;
+
+use nih_plug::prelude::{ParamPtr, Params, Plugin};
+
+use crate::{
+    param_snapshot::ParamSnapshot, params::DelaxParams, smoothing::smoothed_params, Delax,
+};
+
+/// Runs the complete Delax DSP chain on plain sample vectors, without a host and without nih-plug's buffers.
+///
+/// This is used for regression tests of the sound, but can also be used to render audio offline.
+///
+/// Usage:
+/// ```
+/// use delax::offline::{impulse, OfflineRenderer};
+///
+/// let mut renderer = OfflineRenderer::new(44100.);
+/// let input = impulse(1000);
+/// let (left, right) = renderer.render(&input, &input);
+/// assert_eq!(left.len(), 1000);
+/// ```
+pub struct OfflineRenderer {
+    plugin: Delax,
+}
+
+impl OfflineRenderer {
+    /// Create a new renderer at a sample rate. All parameters are at their default values.
+    pub fn new(sample_rate: f32) -> Self {
+        let mut plugin = Delax::default();
+        plugin.prepare(sample_rate, 2);
+        plugin.params.reset_smoothers();
+        plugin.reset();
+
+        Self { plugin }
+    }
+
+    /// Create a renderer from the parameters of a running plugin, to render what it sounds like right now.
+    ///
+    /// The renderer works on a copy of the parameters, so it never steps the smoothers of the running plugin.
+    /// The copy is built at the current value of every parameter, see [ParamSnapshot], and takes over the
+    /// persistent fields. The smoothers start out at their values without a ramp.
+    pub fn with_params(sample_rate: f32, params: &DelaxParams) -> Self {
+        let copy = ParamSnapshot::capture(params).restore(DelaxParams::default);
+        copy.deserialize_fields(&params.serialize_fields());
+
+        for param_ptr in smoothed_params(copy.param_map()).into_values() {
+            if let ParamPtr::FloatParam(param) = param_ptr {
+                // SAFETY: The pointer points into `copy`, which outlives the loop
+                unsafe { (*param).smoothed.reset((*param).value()) };
+            }
+        }
+
+        let mut plugin = Delax {
+            params: Arc::new(copy),
+            ..Delax::default()
+        };
+        plugin.prepare(sample_rate, 2);
+        plugin.reset();
+
+        Self { plugin }
+    }
+
+    /// Initialize the plugin again at another sample rate, like a host that changes its sample rate.
+    ///
+    /// The parameters keep their values, the delay lines start out empty.
+    pub fn reinitialize(&mut self, sample_rate: f32) {
+        self.plugin.prepare(sample_rate, 2);
+        self.plugin.reset();
+    }
+
+    /// Render a stereo signal through the plugin. The output continues from the state of the previous call.
+    ///
+    /// Both channels need to have the same length.
+    pub fn render(&mut self, left: &[f32], right: &[f32]) -> (Vec<f32>, Vec<f32>) {
+        assert_eq!(left.len(), right.len(), "Channels need the same length");
+
+        left.iter()
+            .zip(right)
+            .map(|(l, r)| self.plugin.process_sample(*l, *r))
+            .unzip()
+    }
+
+    /// Render an interleaved stereo signal through the plugin and return the interleaved output
+    pub fn render_interleaved(&mut self, input: &[f32]) -> Vec<f32> {
+        input
+            .chunks_exact(2)
+            .flat_map(|frame| {
+                let (l, r) = self.plugin.process_sample(frame[0], frame[1]);
+                [l, r]
+            })
+            .collect()
+    }
+}
+
+/// A unit impulse followed by silence
+pub fn impulse(length: usize) -> Vec<f32> {
+    let mut signal = vec![0.; length];
+    if let Some(first) = signal.first_mut() {
+        *first = 1.;
+    }
+    signal
+}
+
+/// An exponential sine sweep from `start_hz` to `end_hz` with an amplitude of 0.5
+pub fn sine_sweep(start_hz: f32, end_hz: f32, length: usize, sample_rate: f32) -> Vec<f32> {
+    let duration = length as f32 / sample_rate;
+    let rate = (end_hz / start_hz).ln();
+
+    (0..length)
+        .map(|i| {
+            let t = i as f32 / sample_rate;
+            let phase = std::f32::consts::TAU * start_hz * duration / rate
+                * ((t / duration * rate).exp() - 1.);
+            phase.sin() * 0.5
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rendering_leaves_the_live_smoothers_alone() {
+        let params = DelaxParams::default();
+        params.wetness.smoothed.reset(0.);
+        params.wetness.smoothed.set_target(1000., 1.);
+        let steps_left = params.wetness.smoothed.steps_left();
+
+        let mut renderer = OfflineRenderer::with_params(1000., &params);
+        let input = impulse(100);
+        renderer.render(&input, &input);
+
+        assert_eq!(params.wetness.smoothed.steps_left(), steps_left);
+    }
+
+    #[test]
+    fn rendering_takes_over_the_switches() {
+        let input = impulse(4000);
+        let default =
+            OfflineRenderer::with_params(1000., &DelaxParams::default()).render(&input, &input);
+
+        let mut snapshot = ParamSnapshot::capture(&DelaxParams::default());
+        snapshot.set("Invert Feedback", 1.);
+        let params = snapshot.restore(DelaxParams::default);
+        let inverted = OfflineRenderer::with_params(1000., &params).render(&input, &input);
+
+        assert!(params.delay_params.invert_feedback.value());
+        assert_ne!(inverted, default);
+    }
+}
@@ -0,0 +1,85 @@
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc,
+};
+
+/// A group of parameters that a part of the editor is drawn from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParamTopic {
+    /// The delay times, the feedback and everything else the timeline draws the repeats with
+    DelayTimes,
+    /// The start and the length of the loop region
+    LoopRegion,
+}
+
+const NUM_TOPICS: usize = 2;
+
+/// Counts the changes of the parameters of every [ParamTopic], so the editor only has to look at the
+/// parameters again when one of them actually changed.
+///
+/// The parameters bump their topic from their callbacks, wherever the change came from: the editor, host
+/// automation or a loaded state. The editor compares the [ParamGenerations] on its timer.
+///
+/// Usage:
+/// ```
+/// use delax::param_changes::{ParamChanges, ParamTopic};
+///
+/// let changes = ParamChanges::default();
+/// let seen = changes.generations();
+///
+/// changes.notify(ParamTopic::LoopRegion);
+/// let now = changes.generations();
+/// assert_ne!(now.get(ParamTopic::LoopRegion), seen.get(ParamTopic::LoopRegion));
+/// assert_eq!(now.get(ParamTopic::DelayTimes), seen.get(ParamTopic::DelayTimes));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ParamChanges(Arc<[AtomicU32; NUM_TOPICS]>);
+
+impl ParamChanges {
+    /// Count a change of a parameter of the topic
+    pub fn notify(&self, topic: ParamTopic) {
+        self.0[topic as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A parameter callback that counts every change of the parameter for the topic
+    pub fn callback<T>(&self, topic: ParamTopic) -> Arc<dyn Fn(T) + Send + Sync> {
+        let changes = self.clone();
+        Arc::new(move |_| changes.notify(topic))
+    }
+
+    /// The current counts of all topics
+    pub fn generations(&self) -> ParamGenerations {
+        ParamGenerations(self.0.each_ref().map(|count| count.load(Ordering::Relaxed)))
+    }
+}
+
+/// The counts of a [ParamChanges] at one point in time. Only whether a count differs matters, they wrap around.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ParamGenerations([u32; NUM_TOPICS]);
+
+impl ParamGenerations {
+    pub fn get(&self, topic: ParamTopic) -> u32 {
+        self.0[topic as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn callbacks_count_for_their_topic() {
+        let changes = ParamChanges::default();
+        let callback = changes.callback::<f32>(ParamTopic::DelayTimes);
+
+        callback(250.);
+        callback(500.);
+        let generations = changes.generations();
+        assert_eq!(generations.get(ParamTopic::DelayTimes), 2);
+        assert_eq!(generations.get(ParamTopic::LoopRegion), 0);
+
+        // Clones count into the same generations
+        changes.clone().notify(ParamTopic::LoopRegion);
+        assert_eq!(changes.generations().get(ParamTopic::LoopRegion), 1);
+    }
+}
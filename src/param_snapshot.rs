@@ -0,0 +1,137 @@
+use std::{cell::RefCell, collections::HashMap};
+
+use nih_plug::prelude::*;
+
+thread_local! {
+    /// The snapshot that the parameters built on this thread right now start out at
+    static RESTORING: RefCell<Option<HashMap<String, f32>>> = const { RefCell::new(None) };
+}
+
+/// The plain values of all parameters, to build another set of parameters that starts out at them.
+///
+/// nih-plug only lets the host and the editor set a parameter, so a copy of the parameters can't be set after it's
+/// built. Instead it's built at the values of the snapshot: while [ParamSnapshot::restore()] runs, the parameter
+/// constructors of this module take the value the snapshot has for their name instead of their default.
+/// The parameters are told apart by their names, which are unique within Delax.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParamSnapshot {
+    /// The plain value of every parameter by its name
+    values: HashMap<String, f32>,
+}
+
+impl ParamSnapshot {
+    /// Take the current values of all parameters, including the modulation of the host
+    pub fn capture(params: &impl Params) -> Self {
+        let values = params
+            .param_map()
+            .into_iter()
+            // SAFETY: The pointers point into `params`, which is borrowed for the whole call
+            .map(|(_, param_ptr, _)| unsafe {
+                (
+                    param_ptr.name().to_string(),
+                    param_ptr.modulated_plain_value(),
+                )
+            })
+            .collect();
+
+        Self { values }
+    }
+
+    /// Set the plain value of a parameter by its name. Bools are 0 or 1, enums and ints are their index.
+    pub fn set(&mut self, name: &str, plain_value: f32) {
+        self.values.insert(name.to_string(), plain_value);
+    }
+
+    /// The plain value of a parameter by its name
+    pub fn get(&self, name: &str) -> Option<f32> {
+        self.values.get(name).copied()
+    }
+
+    /// Build parameters that start out at the values of this snapshot.
+    ///
+    /// Parameters that aren't in the snapshot start out at their default value.
+    pub fn restore<P>(&self, build: impl FnOnce() -> P) -> P {
+        let previous = RESTORING.with(|restoring| restoring.replace(Some(self.values.clone())));
+        let params = build();
+        RESTORING.with(|restoring| restoring.replace(previous));
+
+        params
+    }
+}
+
+/// The value of a parameter in the snapshot that's being restored right now
+fn restored(name: &str) -> Option<f32> {
+    RESTORING.with(|restoring| restoring.borrow().as_ref()?.get(name).copied())
+}
+
+/// A [FloatParam] that starts out at the snapshot that's being restored
+pub fn float_param(name: impl Into<String>, default: f32, range: FloatRange) -> FloatParam {
+    let name = name.into();
+    let value = restored(&name).unwrap_or(default);
+    FloatParam::new(name, value, range)
+}
+
+/// An [IntParam] that starts out at the snapshot that's being restored
+pub fn int_param(name: impl Into<String>, default: i32, range: IntRange) -> IntParam {
+    let name = name.into();
+    let value = restored(&name).map_or(default, |value| value.round() as i32);
+    IntParam::new(name, value, range)
+}
+
+/// A [BoolParam] that starts out at the snapshot that's being restored
+pub fn bool_param(name: impl Into<String>, default: bool) -> BoolParam {
+    let name = name.into();
+    let value = restored(&name).map_or(default, |value| value > 0.5);
+    BoolParam::new(name, value)
+}
+
+/// An [EnumParam] that starts out at the snapshot that's being restored
+pub fn enum_param<T: Enum + PartialEq + 'static>(
+    name: impl Into<String>,
+    default: T,
+) -> EnumParam<T> {
+    let name = name.into();
+    let value = restored(&name).map_or(default, |value| {
+        let last = T::variants().len().saturating_sub(1);
+        T::from_index((value.round().max(0.) as usize).min(last))
+    });
+    EnumParam::new(name, value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::DelaxParams;
+
+    #[test]
+    fn parameter_names_are_unique() {
+        let params = DelaxParams::default();
+        let param_map = params.param_map();
+        let snapshot = ParamSnapshot::capture(&params);
+
+        assert_eq!(snapshot.values.len(), param_map.len());
+    }
+
+    #[test]
+    fn restored_params_start_out_at_the_snapshot() {
+        let mut snapshot = ParamSnapshot::capture(&DelaxParams::default());
+        snapshot.set("Invert Feedback", 1.);
+        snapshot.set("Wetness", 0.25);
+
+        let params = snapshot.restore(DelaxParams::default);
+
+        assert!(params.delay_params.invert_feedback.value());
+        assert_eq!(params.wetness.value(), 0.25);
+        assert_eq!(ParamSnapshot::capture(&params), snapshot);
+    }
+
+    #[test]
+    fn params_outside_of_a_restore_start_out_at_their_default() {
+        let snapshot = ParamSnapshot::capture(&DelaxParams::default());
+        let mut changed = snapshot.clone();
+        changed.set("Invert Feedback", 1.);
+        changed.restore(|| ());
+
+        assert_eq!(ParamSnapshot::capture(&DelaxParams::default()), snapshot);
+    }
+}
@@ -1,31 +1,328 @@
-use std::sync::Arc;
+use std::sync::{
+    atomic::{AtomicBool, AtomicU32, AtomicU8, Ordering},
+    Arc, RwLock,
+};
 
 use nih_plug::prelude::*;
 use nih_plug_vizia::ViziaState;
 
-use crate::{delay_engine::params::EngineParams, filters::params::FilterParams, ui};
+use crate::{
+    algorithm::{Algorithm, Topology},
+    delay_engine::params::{EngineParams, NUM_CHANNELS},
+    editor_settings::EditorPreferences,
+    filter_pipeline::params::PipelineParams,
+    filters::params::FilterParams,
+    input_analysis::MAX_TRIM_DB,
+    macros::{MacroTarget, NUM_MACROS},
+    midi::cc_learn::CcMapping,
+    multiband::params::MultibandParams,
+    param_changes::ParamChanges,
+    param_snapshot::{bool_param, enum_param, float_param, int_param},
+    quality::Quality,
+    scenes::{default_scenes, Scene, NUM_SCENES},
+    smoothing::Response,
+    state::STATE_VERSION,
+    ui,
+};
 
 #[derive(Params)]
 pub struct DelaxParams {
-    #[nested(group = "Delay Parameters")]
+    /// A ready made signal flow over the switches of the stages, see [Algorithm]
+    #[id = "algorithm"]
+    pub algorithm: EnumParam<Algorithm>,
+    #[nested(group = "Delay")]
     pub delay_params: EngineParams,
-    #[nested(group = "Filter Parameters")]
+    #[nested(group = "Filter")]
     pub filter_params: FilterParams,
+    #[nested(group = "Pipeline")]
+    pub pipeline_params: PipelineParams,
+    // The parameters stay in builds without the multiband mode, so presets and automation keep working
+    #[nested(group = "Multiband")]
+    pub multiband_params: MultibandParams,
     #[id = "wetness"]
     pub wetness: FloatParam,
+    /// The gain of the input before anything else, the input analysis of the editor suggests a value for it
+    #[id = "input_trim"]
+    pub input_trim: FloatParam,
+    /// How long the smoothed parameters take to reach a new value, from snappy automation to slow morphs
+    #[id = "response"]
+    pub response: FloatParam,
+    /// The bypass of the host, the dry signal passes and the delay stops taking in new input
+    #[id = "bypass"]
+    pub bypass: BoolParam,
+    /// Stop feeding the delay lines without bypassing the plugin
+    #[id = "input_mute"]
+    pub input_mute: BoolParam,
+    /// Whether the repeats ring out or are cut off while bypassed or muted
+    #[id = "bypass_mode"]
+    pub bypass_mode: EnumParam<BypassMode>,
+    /// Keep the output about as loud as the input, for comparing settings without being fooled by level
+    #[id = "auto_gain"]
+    pub auto_gain: BoolParam,
+    /// Softly clip the output above 0 dB, so runaway feedback can't get too loud
+    #[id = "safety_clipper"]
+    pub safety_clipper: BoolParam,
+    /// The macro knobs, each morphs its assigned parameters between their A and B values
+    #[id = "macro1"]
+    pub macro1: FloatParam,
+    #[id = "macro2"]
+    pub macro2: FloatParam,
+    /// The active scene, starting at 1. Switching it fades the parameters to the stored snapshot.
+    #[id = "scene"]
+    pub scene: IntParam,
+    /// How long the continuous parameters take to fade to a new scene
+    #[id = "scene_crossfade"]
+    pub scene_crossfade: FloatParam,
+    /// How much processing is spent on details, see [Quality]
+    #[id = "quality"]
+    pub quality: EnumParam<Quality>,
 
     #[persist = "editor-state"]
     pub editor_state: Arc<ViziaState>,
+    /// Whether the advanced section of the editor is expanded. [ViziaState] only holds the window size,
+    /// so this is stored right next to it.
+    #[persist = "editor-advanced-open"]
+    pub editor_advanced_open: AtomicBool,
+    /// The theme, the meters, the tooltips and the knob settings of the editor
+    #[persist = "editor-preferences"]
+    pub editor_preferences: RwLock<EditorPreferences>,
+    /// The learned MIDI CC mappings, these are changed by the GUI and read by the audio thread
+    #[persist = "cc-mappings"]
+    pub cc_mappings: RwLock<Vec<CcMapping>>,
+    /// The parameters assigned to the macro knobs, these are changed by the GUI and read by the audio thread
+    #[persist = "macro-targets"]
+    pub macro_targets: RwLock<Vec<MacroTarget>>,
+    /// The stored scenes, these are changed by the GUI and read by the audio thread
+    #[persist = "scenes"]
+    pub scenes: RwLock<Vec<Scene>>,
+    /// The version of the saved state, older state is migrated in [crate::state::migrate] before it's loaded
+    #[persist = "state-version"]
+    pub state_version: AtomicU32,
+    /// The solo of every channel, set by the editor for auditioning. This isn't saved, so a
+    /// forgotten solo doesn't end up in a project.
+    solos: [AtomicU8; NUM_CHANNELS],
+    /// Counts the changes of the parameters the editor draws, so it only redraws when they change
+    pub param_changes: ParamChanges,
+}
+
+/// What happens to the repeats that are still in the delay lines when the plugin is bypassed or its input is muted
+#[derive(Debug, Enum, PartialEq, Clone, Copy)]
+pub enum BypassMode {
+    /// The repeats stop right away and the delay lines empty themselves
+    Cut,
+    /// The repeats keep decaying and playing out
+    Trails,
+}
+
+/// Lets a channel play only the wet or only the dry signal, to make dialing in the delay easier
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Solo {
+    /// The wetness parameter decides the mix
+    Off,
+    /// Only the delayed signal
+    Wet,
+    /// Only the input signal
+    Dry,
+}
+
+impl Solo {
+    /// The gains of the dry and the wet signal for a wetness amount
+    pub fn gains(self, wetness: f32) -> (f32, f32) {
+        match self {
+            Solo::Off => (1. - wetness, wetness),
+            Solo::Wet => (0., 1.),
+            Solo::Dry => (1., 0.),
+        }
+    }
+
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => Solo::Wet,
+            2 => Solo::Dry,
+            _ => Solo::Off,
+        }
+    }
 }
 
 impl Default for DelaxParams {
     fn default() -> Self {
+        // All smoothers share the response time, the response parameter sets it whenever it changes
+        let response = Response::default();
+        let param_changes = ParamChanges::default();
+
         Self {
-            delay_params: EngineParams::default(),
-            filter_params: FilterParams::default(),
-            wetness: FloatParam::new("Wetness", 0.5, FloatRange::Linear { min: 0., max: 1. })
-                .with_smoother(SmoothingStyle::Linear(50.)),
+            algorithm: enum_param("Algorithm", Algorithm::Custom),
+            delay_params: EngineParams::new(&response, &param_changes),
+            filter_params: FilterParams::new(&response),
+            pipeline_params: PipelineParams::new(&response),
+            multiband_params: MultibandParams::new(&response),
+            wetness: float_param("Wetness", 0.5, FloatRange::Linear { min: 0., max: 1. })
+                .with_smoother(response.style())
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            input_trim: float_param(
+                "Input Trim",
+                util::db_to_gain(0.),
+                FloatRange::Skewed {
+                    min: util::db_to_gain(-MAX_TRIM_DB),
+                    max: util::db_to_gain(MAX_TRIM_DB),
+                    factor: FloatRange::gain_skew_factor(-MAX_TRIM_DB, MAX_TRIM_DB),
+                },
+            )
+            .with_smoother(response.logarithmic_style())
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(1))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+            response: float_param(
+                "Response",
+                Response::DEFAULT_MS,
+                FloatRange::Skewed {
+                    min: Response::MIN_MS,
+                    max: Response::MAX_MS,
+                    factor: FloatRange::skew_factor(-1.),
+                },
+            )
+            .with_unit(" ms")
+            .with_value_to_string(formatters::v2s_f32_rounded(0))
+            .with_callback(Arc::new(move |ms| response.set_ms(ms))),
+            bypass: bool_param("Bypass", false).make_bypass(),
+            input_mute: bool_param("Mute Input", false),
+            bypass_mode: enum_param("Bypass Mode", BypassMode::Trails),
+            auto_gain: bool_param("Auto Gain", false),
+            safety_clipper: bool_param("Safety Clipper", true),
+            // The macros aren't smoothed, the parameters they drive already are
+            macro1: float_param("Macro 1", 0., FloatRange::Linear { min: 0., max: 1. })
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            macro2: float_param("Macro 2", 0., FloatRange::Linear { min: 0., max: 1. })
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            scene: int_param(
+                "Scene",
+                1,
+                IntRange::Linear {
+                    min: 1,
+                    max: NUM_SCENES as i32,
+                },
+            ),
+            scene_crossfade: float_param(
+                "Scene Crossfade",
+                500.,
+                FloatRange::Skewed {
+                    min: 0.,
+                    max: 10000.,
+                    factor: FloatRange::skew_factor(-2.),
+                },
+            )
+            .with_unit(" ms")
+            .with_value_to_string(formatters::v2s_f32_rounded(0)),
+            // A machine setting, not a part of the sound that should be automated
+            quality: enum_param("Quality", Quality::Normal).non_automatable(),
             editor_state: ui::default_state(),
+            editor_advanced_open: AtomicBool::new(false),
+            editor_preferences: RwLock::new(EditorPreferences::default()),
+            cc_mappings: RwLock::new(Vec::new()),
+            macro_targets: RwLock::new(Vec::new()),
+            scenes: RwLock::new(default_scenes()),
+            state_version: AtomicU32::new(STATE_VERSION),
+            solos: Default::default(),
+            param_changes,
         }
     }
 }
+
+impl DelaxParams {
+    /// Snap all the smoothers to their current values.
+    ///
+    /// The host wrapper normally does this, so this is only needed when the DSP is driven without one.
+    pub(crate) fn reset_smoothers(&self) {
+        self.delay_params.reset_smoothers();
+        self.filter_params.reset_smoothers();
+        self.pipeline_params.reset_smoothers();
+        self.multiband_params.reset_smoothers();
+        self.wetness.smoothed.reset(self.wetness.value());
+        self.input_trim.smoothed.reset(self.input_trim.value());
+    }
+
+    /// The stages the current algorithm forces on or off
+    pub fn topology(&self) -> Topology {
+        self.algorithm.value().topology()
+    }
+
+    /// The current amount of every macro knob
+    pub fn macro_amounts(&self) -> [f32; NUM_MACROS] {
+        [self.macro1.value(), self.macro2.value()]
+    }
+
+    /// The index of the active scene, starting at 0
+    pub fn scene_index(&self) -> usize {
+        (self.scene.value() - 1) as usize
+    }
+
+    /// The solo of a channel
+    pub fn solo(&self, channel: usize) -> Solo {
+        Solo::from_u8(self.solos[channel].load(Ordering::Relaxed))
+    }
+
+    /// Change the solo of a channel. This can be called from any thread.
+    pub fn set_solo(&self, channel: usize, solo: Solo) {
+        self.solos[channel].store(solo as u8, Ordering::Relaxed);
+    }
+}
+
+/// The ID of the same parameter on the next channel, or None if the parameter isn't per channel.
+///
+/// The IDs of the per channel parameters end in the channel number starting at 1, see [crate::delay_engine::params::Channels].
+pub(crate) fn other_channel_param_id(param_id: &str) -> Option<String> {
+    let (base, channel) = param_id.rsplit_once('_')?;
+    let channel: usize = channel.parse().ok()?;
+    if !(1..=NUM_CHANNELS).contains(&channel) {
+        return None;
+    }
+
+    Some(format!("{base}_{}", channel % NUM_CHANNELS + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn solos_round_trip() {
+        let params = DelaxParams::default();
+        assert_eq!(params.solo(0), Solo::Off);
+
+        params.set_solo(0, Solo::Dry);
+        params.set_solo(1, Solo::Wet);
+        assert_eq!(params.solo(0), Solo::Dry);
+        assert_eq!(params.solo(1), Solo::Wet);
+
+        params.set_solo(0, Solo::Off);
+        assert_eq!(params.solo(0), Solo::Off);
+    }
+
+    #[test]
+    fn solo_overrides_the_wetness() {
+        assert_eq!(Solo::Off.gains(0.25), (0.75, 0.25));
+        assert_eq!(Solo::Wet.gains(0.25), (0., 1.));
+        assert_eq!(Solo::Dry.gains(0.25), (1., 0.));
+    }
+
+    #[test]
+    fn other_channel_of_per_channel_params() {
+        assert_eq!(
+            other_channel_param_id("delay_1").as_deref(),
+            Some("delay_2")
+        );
+        assert_eq!(
+            other_channel_param_id("svf_cutoff_2").as_deref(),
+            Some("svf_cutoff_1")
+        );
+        assert_eq!(other_channel_param_id("wetness"), None);
+        assert_eq!(other_channel_param_id("svf_stereo_mode"), None);
+        assert_eq!(other_channel_param_id("delay_3"), None);
+    }
+}
@@ -1,58 +1,183 @@
-pub struct PeakFollower {
-    pub release: f32,
+use nih_plug::util;
+
+/// How a [PeakFollower] moves, the same at every sample rate
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ballistics {
+    /// How long the peak takes to rise most of the way to a louder signal
+    pub attack_ms: f32,
+    /// How fast the peak falls back once the signal got quieter
+    pub release_db_per_s: f32,
+    /// How long the held peak stays up before it falls back like the peak
+    pub hold_ms: f32,
+    /// The time the RMS level is averaged over
+    pub rms_ms: f32,
+}
+
+impl Default for Ballistics {
+    fn default() -> Self {
+        Self {
+            attack_ms: 0.1,
+            release_db_per_s: 20.,
+            hold_ms: 1000.,
+            rms_ms: 300.,
+        }
+    }
+}
+
+/// The levels of a [PeakFollower] as gains, ready to be drawn
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MeterLevels {
     pub peak: f32,
+    /// The highest recent peak, for the peak hold line
     pub hold: f32,
-    pub hold_counter: f32,
+    pub rms: f32,
+}
+
+/// Follows the level of a signal for a meter.
+///
+/// This is fed every sample on the audio thread, so the meter moves the same no matter how often the editor
+/// draws it. The editor only draws the [MeterLevels] that were published last.
+pub struct PeakFollower {
+    ballistics: Ballistics,
     sample_rate: f32,
-    peak_smoother: PeakSmoother,
+    /// The amount of the distance to a louder signal the peak rises every sample
+    attack: f32,
+    /// The factor the peak falls by every sample, a constant factor falls at a constant rate in dB
+    release: f32,
+    /// The amount of samples the held peak stays up
+    hold_samples: usize,
+    /// The amount of the distance to the current square the mean square moves every sample
+    rms: f32,
+    levels: MeterLevels,
+    mean_square: f32,
+    hold_counter: usize,
 }
 
 impl PeakFollower {
-    pub fn new(release: f32, hold: f32, sample_rate: f32, smoothing: usize) -> Self {
-        Self {
-            release,
-            peak: 0.,
-            hold,
-            hold_counter: 0.,
+    pub fn new(ballistics: Ballistics, sample_rate: f32) -> Self {
+        let mut follower = Self {
+            ballistics,
             sample_rate,
-            peak_smoother: PeakSmoother::new(smoothing),
-        }
+            attack: 1.,
+            release: 1.,
+            hold_samples: 0,
+            rms: 1.,
+            levels: MeterLevels::default(),
+            mean_square: 0.,
+            hold_counter: 0,
+        };
+        follower.set_sample_rate(sample_rate);
+        follower
     }
 
-    pub fn process(&mut self, input: f32) -> f32 {
-        let input = self.peak_smoother.process(input.abs());
-        if input.abs() > self.peak {
-            self.peak = input;
-            self.hold_counter = self.hold;
+    pub fn process(&mut self, input: f32) {
+        let input = input.abs();
+        let levels = &mut self.levels;
+
+        if input > levels.peak {
+            levels.peak += (input - levels.peak) * self.attack;
+        } else {
+            levels.peak = (levels.peak * self.release).max(input);
+        }
+
+        if levels.peak >= levels.hold {
+            levels.hold = levels.peak;
+            self.hold_counter = self.hold_samples;
+        } else if self.hold_counter > 0 {
+            self.hold_counter -= 1;
         } else {
-            self.hold_counter -= 1. / self.sample_rate;
-            if self.hold_counter < 0. {
-                self.peak -= self.release / self.sample_rate;
-            }
+            levels.hold = (levels.hold * self.release).max(levels.peak);
         }
 
-        self.peak
+        self.mean_square += (input * input - self.mean_square) * self.rms;
+        levels.rms = self.mean_square.sqrt();
+    }
+
+    /// The current levels
+    pub fn levels(&self) -> MeterLevels {
+        self.levels
     }
 
     pub fn set_sample_rate(&mut self, sample_rate: f32) {
         self.sample_rate = sample_rate;
+        let Ballistics {
+            attack_ms,
+            release_db_per_s,
+            hold_ms,
+            rms_ms,
+        } = self.ballistics;
+
+        self.attack = one_pole_coefficient(attack_ms, sample_rate);
+        self.release = util::db_to_gain(-release_db_per_s / sample_rate);
+        self.hold_samples = (hold_ms / 1000. * sample_rate).round() as usize;
+        self.rms = one_pole_coefficient(rms_ms, sample_rate);
     }
 }
 
-struct PeakSmoother {
-    buffer: Vec<f32>,
+/// The coefficient of a one pole filter that gets most of the way to its target in `time_ms`
+fn one_pole_coefficient(time_ms: f32, sample_rate: f32) -> f32 {
+    let samples = time_ms / 1000. * sample_rate;
+    if samples <= 1. {
+        1.
+    } else {
+        1. - (-1. / samples).exp()
+    }
 }
 
-impl PeakSmoother {
-    pub fn new(size: usize) -> Self {
-        Self {
-            buffer: vec![0.; size],
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feed a follower a constant level for a while
+    fn feed(follower: &mut PeakFollower, level: f32, seconds: f32) {
+        for _ in 0..(seconds * follower.sample_rate) as usize {
+            follower.process(level);
         }
     }
 
-    pub fn process(&mut self, input: f32) -> f32 {
-        self.buffer.remove(0);
-        self.buffer.push(input);
-        self.buffer.iter().sum::<f32>() / self.buffer.len() as f32
+    #[test]
+    fn release_is_the_same_at_every_sample_rate() {
+        let fall_off = |sample_rate| {
+            let mut follower = PeakFollower::new(Ballistics::default(), sample_rate);
+            feed(&mut follower, 1., 0.1);
+            feed(&mut follower, 0., 0.5);
+            follower.levels()
+        };
+
+        let slow = fall_off(22050.);
+        let fast = fall_off(96000.);
+        // 20 dB per second for half a second
+        assert!((util::gain_to_db(slow.peak) + 10.).abs() < 0.1);
+        assert!((util::gain_to_db(slow.peak) - util::gain_to_db(fast.peak)).abs() < 0.1);
+        // The held peak is still up
+        assert!((slow.hold - 1.).abs() < 1e-3);
+        assert!((fast.hold - 1.).abs() < 1e-3);
+    }
+
+    #[test]
+    fn the_held_peak_falls_after_the_hold_time() {
+        let ballistics = Ballistics {
+            hold_ms: 100.,
+            ..Default::default()
+        };
+        let mut follower = PeakFollower::new(ballistics, 1000.);
+        feed(&mut follower, -0.5, 0.1);
+        feed(&mut follower, 0., 0.1);
+        assert_eq!(follower.levels().hold, 0.5);
+
+        feed(&mut follower, 0., 0.5);
+        let levels = follower.levels();
+        assert!(levels.hold < 0.5);
+        assert!(levels.hold >= levels.peak);
+    }
+
+    #[test]
+    fn rms_of_a_square_wave() {
+        let mut follower = PeakFollower::new(Ballistics::default(), 1000.);
+        for sample in 0..5000 {
+            follower.process(if sample % 2 == 0 { 0.5 } else { -0.5 });
+        }
+
+        assert!((follower.levels().rms - 0.5).abs() < 1e-3);
     }
 }
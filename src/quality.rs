@@ -0,0 +1,68 @@
+use nih_plug::prelude::Enum;
+
+/// How much processing the plugin spends on details, so many instances fit on a slow machine.
+///
+/// There's no oversampling in the signal path, so the quality trades the precision of the delay reads,
+/// how often the filters follow their modulation and how often the editor gets new frames instead.
+///
+/// Usage:
+/// ```
+/// use delax::quality::Quality;
+///
+/// // Eco retunes the filters less often and sends the editor fewer frames
+/// assert!(Quality::Eco.control_interval() > Quality::Normal.control_interval());
+/// assert!(Quality::Eco.publish_divider() > Quality::Normal.publish_divider());
+/// // High reads every delay between the samples
+/// assert!(Quality::High.interpolates_every_read());
+/// ```
+#[derive(Debug, Enum, Default, PartialEq, Eq, Clone, Copy)]
+pub enum Quality {
+    /// Less precise modulation and fewer meter updates, for running many instances on a laptop
+    Eco,
+    #[default]
+    Normal,
+    /// Reads every delay between the samples, not only the short ones that need it
+    High,
+}
+
+impl Quality {
+    /// The amount of samples between two updates of the filter coefficients. The smoothers still move every
+    /// sample, the filters just catch up with them less often.
+    pub fn control_interval(self) -> usize {
+        match self {
+            Quality::Eco => 16,
+            Quality::Normal | Quality::High => 1,
+        }
+    }
+
+    /// The factor the intervals of the meters, the scope and the spectrum are stretched by
+    pub fn publish_divider(self) -> usize {
+        match self {
+            Quality::Eco => 4,
+            Quality::Normal | Quality::High => 1,
+        }
+    }
+
+    /// Whether all delays are read between the samples. Otherwise only short delays are, since rounding a long
+    /// delay to the nearest sample can't be heard unless it moves.
+    pub fn interpolates_every_read(self) -> bool {
+        self == Quality::High
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normal_keeps_the_full_rates() {
+        assert_eq!(Quality::default(), Quality::Normal);
+        assert_eq!(Quality::Normal.control_interval(), 1);
+        assert_eq!(Quality::Normal.publish_divider(), 1);
+        assert!(!Quality::Normal.interpolates_every_read());
+
+        // High only adds precision on top of normal
+        assert_eq!(Quality::High.control_interval(), 1);
+        assert_eq!(Quality::High.publish_divider(), 1);
+    }
+}
@@ -0,0 +1,8 @@
+pub use delax_dsp::sample::Sample;
+
+/// The precision of the state in the feedback path, f64 with the `f64-feedback` feature and f32 otherwise
+#[cfg(feature = "f64-feedback")]
+pub type FeedbackSample = f64;
+/// The precision of the state in the feedback path, f64 with the `f64-feedback` feature and f32 otherwise
+#[cfg(not(feature = "f64-feedback"))]
+pub type FeedbackSample = f32;
@@ -0,0 +1,244 @@
+use std::{collections::HashMap, sync::RwLock};
+
+use nih_plug::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::smoothing::{is_smoothed, smoothed_params};
+
+/// The amount of scenes
+pub const NUM_SCENES: usize = 4;
+
+/// Parameters that aren't part of a scene. Switching scenes mustn't switch the scene again or bypass the plugin.
+const SCENE_EXCLUDED_PARAM_IDS: &[&str] = &["scene", "bypass"];
+
+/// A snapshot of all parameters, as normalized values by parameter ID.
+///
+/// The audio thread fades the smoothed parameters, see [SceneEngine]. Switches, modes and other discrete parameters
+/// can't be faded, the editor sets them as soon as it sees the new scene, see [Scene::discrete_values()].
+///
+/// A scene that was never stored is empty, switching to it leaves the parameters alone.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Scene {
+    pub values: HashMap<String, f32>,
+}
+
+impl Scene {
+    /// Take a snapshot of the current values of the parameters. The pointers need to be valid.
+    pub fn capture(params: &[(String, ParamPtr)]) -> Self {
+        Self {
+            values: params
+                .iter()
+                .filter(|(param_id, _)| !SCENE_EXCLUDED_PARAM_IDS.contains(&param_id.as_str()))
+                // SAFETY: The caller guarantees the pointers are valid
+                .map(|(param_id, param_ptr)| {
+                    (param_id.clone(), unsafe {
+                        param_ptr.unmodulated_normalized_value()
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    /// The values of the parameters the audio thread can't fade, which the editor sets when the scene changes.
+    /// The pointers need to be valid.
+    pub fn discrete_values<'a>(
+        &'a self,
+        params: &'a [(String, ParamPtr)],
+    ) -> impl Iterator<Item = (ParamPtr, f32)> + 'a {
+        params
+            .iter()
+            .filter(|(_, param_ptr)| !is_smoothed(*param_ptr))
+            .filter_map(|(param_id, param_ptr)| Some((*param_ptr, *self.values.get(param_id)?)))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+/// The stored scenes, one per slot
+pub fn default_scenes() -> Vec<Scene> {
+    vec![Scene::default(); NUM_SCENES]
+}
+
+/// Crossfades the continuous parameters to the scene that's switched to, from the audio thread.
+///
+/// Like the macros, this moves the smoothers of the parameters since the audio thread can't set them. The fade is
+/// stepped once per block and the smoothers of the parameters even out the steps. Switches, modes and other
+/// discrete parameters can't be faded, the editor sets them as soon as it sees the new scene.
+#[derive(Default)]
+pub struct SceneEngine {
+    /// The smoothed float parameters by ID, these are the only ones that can be faded
+    params: HashMap<String, ParamPtr>,
+    /// The scene the parameters were last faded to, None until the first block after a reset
+    scene: Option<usize>,
+    /// The faded parameters with the plain values they fade from and to
+    fading: Vec<(ParamPtr, f32, f32)>,
+    /// The samples since the fade started
+    elapsed: usize,
+}
+
+// SAFETY: The pointers point into the parameters of the plugin, which outlive the engine and are only read through atomics
+unsafe impl Send for SceneEngine {}
+
+impl SceneEngine {
+    /// Collect the parameters that can be faded from the parameter map of the plugin.
+    ///
+    /// The pointers need to stay valid for as long as the engine is used. This allocates, so it can't be called from the audio thread.
+    pub fn prepare(&mut self, param_map: Vec<(String, ParamPtr, String)>) {
+        self.params = smoothed_params(param_map);
+        self.fading = Vec::with_capacity(self.params.len());
+        self.reset();
+    }
+
+    /// Fade towards the scene with an index, starting a new fade if it changed. This is called once per block.
+    ///
+    /// The first call after a reset only takes note of the scene, since the parameters were just loaded or set by the host.
+    /// If the GUI is currently storing a scene, the switch waits for the next block.
+    pub fn apply(
+        &mut self,
+        scenes: &RwLock<Vec<Scene>>,
+        scene: usize,
+        crossfade_ms: f32,
+        sample_rate: f32,
+        block_len: usize,
+    ) {
+        if self.scene.is_none() {
+            self.scene = Some(scene);
+        } else if self.scene != Some(scene) {
+            let Ok(scenes) = scenes.try_read() else {
+                return;
+            };
+            self.scene = Some(scene);
+            self.fading.clear();
+            self.elapsed = 0;
+
+            let Some(values) = scenes.get(scene).map(|scene| &scene.values) else {
+                return;
+            };
+            for (param_id, param_ptr) in &self.params {
+                let (Some(value), ParamPtr::FloatParam(param)) = (values.get(param_id), param_ptr)
+                else {
+                    continue;
+                };
+                // SAFETY: The pointers are valid for as long as the engine is used, see [SceneEngine::prepare()]
+                let param = unsafe { &**param };
+                self.fading.push((
+                    *param_ptr,
+                    param.smoothed.previous_value(),
+                    param.preview_plain(*value),
+                ));
+            }
+        }
+
+        if self.fading.is_empty() {
+            return;
+        }
+
+        self.elapsed += block_len;
+        let fade_len = crossfade_ms / 1000. * sample_rate;
+        let mix = if fade_len > 0. {
+            (self.elapsed as f32 / fade_len).min(1.)
+        } else {
+            1.
+        };
+        for (param_ptr, from, to) in &self.fading {
+            if let ParamPtr::FloatParam(param) = param_ptr {
+                // SAFETY: See above
+                let param = unsafe { &**param };
+                param
+                    .smoothed
+                    .set_target(sample_rate, from + (to - from) * mix);
+            }
+        }
+
+        if mix >= 1. {
+            self.fading.clear();
+        }
+    }
+
+    /// Stop fading and take the next scene as the current one without fading to it
+    pub fn reset(&mut self) {
+        self.scene = None;
+        self.fading.clear();
+        self.elapsed = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scenes_leave_out_the_scene_switch() {
+        let scene = FloatParam::new("Scene", 0., FloatRange::Linear { min: 0., max: 1. })
+            .with_smoother(SmoothingStyle::Linear(1.));
+        let param = FloatParam::new("Param", 0.25, FloatRange::Linear { min: 0., max: 1. })
+            .with_smoother(SmoothingStyle::Linear(1.));
+        let snapshot = Scene::capture(&[
+            (String::from("scene"), scene.as_ptr()),
+            (String::from("param"), param.as_ptr()),
+        ]);
+
+        assert_eq!(
+            snapshot.values,
+            HashMap::from([(String::from("param"), 0.25)])
+        );
+    }
+
+    #[test]
+    fn the_editor_sets_the_discrete_parameters() {
+        let switch = BoolParam::new("Switch", true);
+        let unsmoothed =
+            FloatParam::new("Unsmoothed", 0.5, FloatRange::Linear { min: 0., max: 1. });
+        let smoothed = FloatParam::new("Smoothed", 0.5, FloatRange::Linear { min: 0., max: 1. })
+            .with_smoother(SmoothingStyle::Linear(1.));
+        let params = [
+            (String::from("switch"), switch.as_ptr()),
+            (String::from("unsmoothed"), unsmoothed.as_ptr()),
+            (String::from("smoothed"), smoothed.as_ptr()),
+        ];
+        let snapshot = Scene::capture(&params);
+        assert_eq!(snapshot.values.len(), 3);
+
+        let discrete: Vec<_> = snapshot.discrete_values(&params).collect();
+        assert_eq!(
+            discrete,
+            vec![(switch.as_ptr(), 1.), (unsmoothed.as_ptr(), 0.5)]
+        );
+    }
+
+    #[test]
+    fn switching_scenes_crossfades() {
+        let param = FloatParam::new("Param", 0., FloatRange::Linear { min: 0., max: 100. })
+            .with_smoother(SmoothingStyle::Linear(1.));
+        let mut engine = SceneEngine::default();
+        engine.prepare(vec![(String::from("param"), param.as_ptr(), String::new())]);
+
+        let mut scenes = default_scenes();
+        scenes[1].values.insert(String::from("param"), 1.);
+        let scenes = RwLock::new(scenes);
+
+        // Blocks of 10 samples at 1000 Hz with a crossfade of 40 ms
+        let mut values = Vec::new();
+        for scene in [0, 1, 1, 1, 1, 1] {
+            engine.apply(&scenes, scene, 40., 1000., 10);
+            values.push((0..10).map(|_| param.smoothed.next()).last().unwrap());
+        }
+
+        assert_eq!(values, vec![0., 25., 50., 75., 100., 100.]);
+    }
+
+    #[test]
+    fn empty_scenes_change_nothing() {
+        let param = FloatParam::new("Param", 0., FloatRange::Linear { min: 0., max: 100. })
+            .with_smoother(SmoothingStyle::Linear(1.));
+        let mut engine = SceneEngine::default();
+        engine.prepare(vec![(String::from("param"), param.as_ptr(), String::new())]);
+        let scenes = RwLock::new(default_scenes());
+
+        engine.apply(&scenes, 0, 0., 1000., 10);
+        engine.apply(&scenes, 2, 0., 1000., 10);
+        assert!(engine.fading.is_empty());
+    }
+}
@@ -0,0 +1,134 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use nih_plug::{
+    prelude::*,
+    wrapper::state::{ParamValue, PluginState},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::state::{self, STATE_VERSION, STATE_VERSION_FIELD};
+
+/// The file extension of exported settings
+pub const SETTINGS_EXTENSION: &str = "json";
+
+/// Parameters that aren't exported. Loading a delay setup mustn't bypass the plugin.
+const EXCLUDED_PARAM_IDS: &[&str] = &["bypass"];
+
+/// All parameters of the plugin as normalized values by parameter ID, as they are stored in an exported file.
+///
+/// The file is plain JSON, so it can be shared and edited outside of a host. Settings that were exported by an older
+/// version are migrated the same way as the saved state of the plugin.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SettingsFile {
+    /// The version of the plugin that exported the settings, for reference only
+    pub plugin_version: String,
+    /// The version of the saved state the parameter IDs belong to
+    pub state_version: u32,
+    pub values: BTreeMap<String, f32>,
+}
+
+impl SettingsFile {
+    /// Take a snapshot of the current values of the parameters. The pointers need to be valid.
+    pub fn capture(params: &[(String, ParamPtr)]) -> Self {
+        Self {
+            plugin_version: env!("CARGO_PKG_VERSION").to_string(),
+            state_version: STATE_VERSION,
+            values: params
+                .iter()
+                .filter(|(param_id, _)| !EXCLUDED_PARAM_IDS.contains(&param_id.as_str()))
+                // SAFETY: The caller guarantees the pointers are valid
+                .map(|(param_id, param_ptr)| {
+                    (param_id.clone(), unsafe {
+                        param_ptr.unmodulated_normalized_value()
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    /// Bring the parameter IDs of settings exported by an older version up to date
+    fn migrate(&mut self) {
+        if self.state_version >= STATE_VERSION {
+            return;
+        }
+
+        let mut plugin_state = PluginState {
+            version: self.plugin_version.clone(),
+            params: std::mem::take(&mut self.values)
+                .into_iter()
+                .map(|(param_id, value)| (param_id, ParamValue::F32(value)))
+                .collect(),
+            fields: [(
+                STATE_VERSION_FIELD.to_string(),
+                self.state_version.to_string(),
+            )]
+            .into_iter()
+            .collect(),
+        };
+        state::migrate(&mut plugin_state);
+
+        self.values = plugin_state
+            .params
+            .into_iter()
+            .filter_map(|(param_id, value)| match value {
+                ParamValue::F32(value) => Some((param_id, value)),
+                _ => None,
+            })
+            .collect();
+        self.state_version = STATE_VERSION;
+    }
+
+    /// Write the settings to a file
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|error| error.to_string())?;
+        fs::write(path, json).map_err(|error| error.to_string())
+    }
+
+    /// Read settings from a file, migrated to the current parameter IDs
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let json = fs::read_to_string(path).map_err(|error| error.to_string())?;
+        let mut settings: Self = serde_json::from_str(&json).map_err(|error| error.to_string())?;
+        settings.migrate();
+        Ok(settings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_round_trip_through_a_file() {
+        let bypass = BoolParam::new("Bypass", true);
+        let param = FloatParam::new("Param", 0.25, FloatRange::Linear { min: 0., max: 1. });
+        let settings = SettingsFile::capture(&[
+            (String::from("bypass"), bypass.as_ptr()),
+            (String::from("param"), param.as_ptr()),
+        ]);
+        assert_eq!(
+            settings.values,
+            BTreeMap::from([(String::from("param"), 0.25)])
+        );
+
+        let path = std::env::temp_dir().join("delax-settings-round-trip.json");
+        settings.save(&path).unwrap();
+        assert_eq!(SettingsFile::load(&path), Ok(settings));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn old_settings_are_migrated() {
+        let mut settings = SettingsFile {
+            plugin_version: String::from("0.1.0"),
+            state_version: 1,
+            values: BTreeMap::from([(String::from("delay_l"), 0.5), (String::from("stereo"), 1.)]),
+        };
+        settings.migrate();
+
+        assert_eq!(settings.state_version, STATE_VERSION);
+        assert_eq!(
+            settings.values,
+            BTreeMap::from([(String::from("delay_1"), 0.5), (String::from("stereo"), 1.)])
+        );
+    }
+}
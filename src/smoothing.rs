@@ -0,0 +1,152 @@
+use std::{
+    collections::HashMap,
+    sync::{atomic::Ordering, Arc},
+};
+
+use nih_plug::prelude::{AtomicF32, ParamPtr, Smoother, SmoothingStyle};
+
+/// The response time of the parameter smoothers, in ms, shared by all smoothed parameters.
+///
+/// nih-plug fixes the style of a smoother when the parameter is created. The oversampling aware style
+/// scales the time of another style by a shared factor though, which is used here to change the time of all
+/// smoothers at once. The curve can't be changed this way, so every parameter keeps the one it was created with.
+///
+/// Usage:
+/// ```
+/// use delax::smoothing::Response;
+///
+/// let response = Response::default();
+/// assert_eq!(response.ms(), 50.);
+///
+/// // Values outside of the range are clamped
+/// response.set_ms(1000.);
+/// assert_eq!(response.ms(), Response::MAX_MS);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Response(Arc<AtomicF32>);
+
+impl Response {
+    pub const MIN_MS: f32 = 5.;
+    pub const MAX_MS: f32 = 500.;
+    pub const DEFAULT_MS: f32 = 50.;
+
+    /// The style to create the smoothers of the parameters with
+    pub fn style(&self) -> SmoothingStyle {
+        SmoothingStyle::OversamplingAware(self.0.clone(), &SmoothingStyle::Linear(1.))
+    }
+
+    /// A logarithmic version of [Response::style()], for frequencies and other parameters that are heard
+    /// logarithmically. The parameter's range must not include zero.
+    pub fn logarithmic_style(&self) -> SmoothingStyle {
+        SmoothingStyle::OversamplingAware(self.0.clone(), &SmoothingStyle::Logarithmic(1.))
+    }
+
+    pub fn ms(&self) -> f32 {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Change the time of all smoothers. This takes effect the next time their parameters change.
+    pub fn set_ms(&self, ms: f32) {
+        self.0
+            .store(ms.clamp(Self::MIN_MS, Self::MAX_MS), Ordering::Relaxed);
+    }
+}
+
+impl Default for Response {
+    fn default() -> Self {
+        Self(Arc::new(AtomicF32::new(Self::DEFAULT_MS)))
+    }
+}
+
+/// A gain that fades between 0 and 1 whenever it's opened or closed, so switching a signal on and off doesn't click.
+///
+/// Usage:
+/// ```
+/// use delax::smoothing::Gate;
+///
+/// let mut gate = Gate::new(1., true);
+/// assert_eq!(gate.next(1000., true), 1.);
+///
+/// // At 1000 Hz the gate is closed one sample after closing it
+/// assert_eq!(gate.next(1000., false), 0.);
+/// ```
+pub struct Gate {
+    smoother: Smoother<f32>,
+    /// Whether the gate was last opened or closed, so the smoother only gets a new target when that changes
+    open: bool,
+}
+
+impl Gate {
+    /// Create a gate that fades in and out over `fade_ms`
+    pub fn new(fade_ms: f32, open: bool) -> Self {
+        let mut gate = Self {
+            smoother: Smoother::new(SmoothingStyle::Linear(fade_ms)),
+            open,
+        };
+        gate.reset(open);
+        gate
+    }
+
+    /// The next gain of the gate, fading towards 1 while it's open and towards 0 while it's closed
+    pub fn next(&mut self, sample_rate: f32, open: bool) -> f32 {
+        if open != self.open {
+            self.open = open;
+            self.smoother
+                .set_target(sample_rate, if open { 1. } else { 0. });
+        }
+
+        self.smoother.next()
+    }
+
+    /// The gain [Gate::next()] returned last
+    pub fn value(&self) -> f32 {
+        self.smoother.previous_value()
+    }
+
+    /// Jump to being open or closed without a fade
+    pub fn reset(&mut self, open: bool) {
+        self.open = open;
+        self.smoother.reset(if open { 1. } else { 0. });
+    }
+}
+
+/// The float parameters that are read through their smoothers, by ID.
+///
+/// The audio thread can't set parameters, but it can move their smoothers, so these are the only parameters
+/// it can change itself. The pointers of the parameter map need to be valid.
+pub(crate) fn smoothed_params(
+    param_map: Vec<(String, ParamPtr, String)>,
+) -> HashMap<String, ParamPtr> {
+    param_map
+        .into_iter()
+        .filter(|(_, param_ptr, _)| is_smoothed(*param_ptr))
+        .map(|(param_id, param_ptr, _)| (param_id, param_ptr))
+        .collect()
+}
+
+/// Whether a parameter is a float parameter with a smoother. The pointer needs to be valid.
+pub(crate) fn is_smoothed(param_ptr: ParamPtr) -> bool {
+    match param_ptr {
+        // SAFETY: The caller guarantees the pointer is valid
+        ParamPtr::FloatParam(param) => {
+            !matches!(unsafe { &*param }.smoothed.style, SmoothingStyle::None)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn response_scales_the_smoothing_time() {
+        let response = Response::default();
+        let style = response.style();
+        assert_eq!(style.num_steps(1000.), 50);
+
+        response.set_ms(200.);
+        assert_eq!(style.num_steps(1000.), 200);
+        assert_eq!(response.logarithmic_style().num_steps(1000.), 200);
+    }
+}
@@ -0,0 +1,221 @@
+use nih_plug::wrapper::state::PluginState;
+
+/// The version of the saved state. Bump this and add a migration to [MIGRATIONS] whenever parameters are
+/// renamed or a persisted field changes its shape.
+pub(crate) const STATE_VERSION: u32 = 3;
+/// The persisted field the version of the saved state is stored in
+pub(crate) const STATE_VERSION_FIELD: &str = "state-version";
+
+/// Brings saved state from the previous version up to `version`
+struct Migration {
+    version: u32,
+    migrate: fn(&mut PluginState),
+}
+
+/// All migrations in order. State that was saved before the version was stored is version 1.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 2,
+        migrate: migrate_param_ids,
+    },
+    Migration {
+        version: 3,
+        migrate: migrate_knob_settings,
+    },
+];
+
+/// The version a state was saved with
+pub(crate) fn state_version(state: &PluginState) -> u32 {
+    state
+        .fields
+        .get(STATE_VERSION_FIELD)
+        .and_then(|version| version.trim().parse().ok())
+        .unwrap_or(1)
+}
+
+/// Run all the migrations a saved state needs to load as the current version.
+///
+/// State saved by a newer version is left alone, there is no way to know what changed.
+pub(crate) fn migrate(state: &mut PluginState) {
+    let version = state_version(state);
+    if version >= STATE_VERSION {
+        return;
+    }
+
+    for migration in MIGRATIONS
+        .iter()
+        .filter(|migration| migration.version > version)
+    {
+        (migration.migrate)(state);
+    }
+    state
+        .fields
+        .insert(STATE_VERSION_FIELD.to_string(), STATE_VERSION.to_string());
+}
+
+/// Parameter IDs that have been renamed, as `(old, new)` pairs.
+///
+/// The per channel parameters used to be flat fields with `_l` and `_r` suffixes.
+/// They are now nested arrays, which nih-plug suffixes with the channel number.
+const RENAMED_PARAM_IDS: &[(&str, &str)] = &[
+    ("delay_l", "delay_1"),
+    ("delay_r", "delay_2"),
+    ("feedback_l", "feedback_1"),
+    ("feedback_r", "feedback_2"),
+    ("bpm_bound_l", "bpm_bound_1"),
+    ("bpm_bound_r", "bpm_bound_2"),
+    ("svf_cutoff_l", "svf_cutoff_1"),
+    ("svf_cutoff_r", "svf_cutoff_2"),
+    ("svf_res_l", "svf_res_1"),
+    ("svf_res_r", "svf_res_2"),
+    ("svf_filter_mode_l", "svf_filter_mode_1"),
+    ("svf_filter_mode_r", "svf_filter_mode_2"),
+    ("svf_mix_l", "svf_mix_1"),
+    ("svf_mix_r", "svf_mix_2"),
+];
+
+/// Version 2: Rename the parameters of state saved before the channels became nested arrays
+fn migrate_param_ids(state: &mut PluginState) {
+    for (old, new) in RENAMED_PARAM_IDS {
+        if let Some(value) = state.params.remove(*old) {
+            state.params.insert(new.to_string(), value);
+        }
+    }
+}
+
+/// The persisted field the knob settings were stored in before version 3
+const KNOB_SETTINGS_FIELD: &str = "editor-knob-settings";
+/// The persisted field of the editor preferences, see [crate::editor_settings::EditorPreferences]
+const EDITOR_PREFERENCES_FIELD: &str = "editor-preferences";
+
+/// Version 3: Move the knob settings into the editor preferences, which hold them since then
+fn migrate_knob_settings(state: &mut PluginState) {
+    if let Some(knob_settings) = state.fields.remove(KNOB_SETTINGS_FIELD) {
+        state.fields.insert(
+            EDITOR_PREFERENCES_FIELD.to_string(),
+            format!(r#"{{"knobs":{knob_settings}}}"#),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nih_plug::wrapper::state::ParamValue;
+
+    use super::*;
+    use crate::{
+        editor_settings::{EditorPreferences, KnobSettings},
+        midi::cc_learn::{CcCurve, CcMapping},
+    };
+
+    fn old_state() -> PluginState {
+        PluginState {
+            version: String::from("0.1.0"),
+            params: [
+                (String::from("delay_l"), ParamValue::F32(250.)),
+                (String::from("feedback_r"), ParamValue::F32(0.75)),
+                (String::from("svf_filter_mode_r"), ParamValue::I32(2)),
+                (String::from("stereo"), ParamValue::I32(1)),
+            ]
+            .into_iter()
+            .collect(),
+            fields: Default::default(),
+        }
+    }
+
+    #[test]
+    fn old_param_ids_are_migrated() {
+        let mut state = old_state();
+        assert_eq!(state_version(&state), 1);
+        migrate(&mut state);
+
+        assert_eq!(state.params.get("delay_1"), Some(&ParamValue::F32(250.)));
+        assert_eq!(state.params.get("feedback_2"), Some(&ParamValue::F32(0.75)));
+        assert_eq!(
+            state.params.get("svf_filter_mode_2"),
+            Some(&ParamValue::I32(2))
+        );
+        assert_eq!(state.params.get("stereo"), Some(&ParamValue::I32(1)));
+        assert!(!state.params.contains_key("delay_l"));
+        assert_eq!(state.params.len(), 4);
+        assert_eq!(state_version(&state), STATE_VERSION);
+    }
+
+    #[test]
+    fn knob_settings_move_into_the_preferences() {
+        let knob_settings = KnobSettings {
+            drag_pixels: 4000.,
+            ..KnobSettings::default()
+        };
+        let mut state = old_state();
+        state.fields.insert(
+            KNOB_SETTINGS_FIELD.to_string(),
+            serde_json::to_string(&knob_settings).unwrap(),
+        );
+        migrate(&mut state);
+
+        assert!(!state.fields.contains_key(KNOB_SETTINGS_FIELD));
+        let preferences: EditorPreferences =
+            serde_json::from_str(&state.fields[EDITOR_PREFERENCES_FIELD]).unwrap();
+        assert_eq!(preferences.knobs, knob_settings);
+        assert_eq!(preferences.theme, EditorPreferences::default().theme);
+    }
+
+    #[test]
+    fn current_state_is_left_alone() {
+        let mut state = old_state();
+        state
+            .fields
+            .insert(STATE_VERSION_FIELD.to_string(), STATE_VERSION.to_string());
+        let saved = state.clone();
+
+        migrate(&mut state);
+        assert_eq!(state, saved);
+
+        // The same goes for state from the future
+        state.fields.insert(
+            STATE_VERSION_FIELD.to_string(),
+            (STATE_VERSION + 1).to_string(),
+        );
+        let saved = state.clone();
+        migrate(&mut state);
+        assert_eq!(state, saved);
+    }
+
+    #[test]
+    fn serialized_state_round_trips() {
+        let mappings = vec![CcMapping {
+            param_id: String::from("delay_l"),
+            channel: 0,
+            cc: 74,
+            curve: CcCurve::Linear,
+            min: 0.,
+            max: 1.,
+        }];
+        let mut state = old_state();
+        state.fields.insert(
+            String::from("cc-mappings"),
+            serde_json::to_string(&mappings).unwrap(),
+        );
+
+        // Save and load the old state like a host would, then migrate it
+        let json = serde_json::to_string(&state).unwrap();
+        let mut loaded: PluginState = serde_json::from_str(&json).unwrap();
+        migrate(&mut loaded);
+        assert_eq!(state_version(&loaded), STATE_VERSION);
+        assert_eq!(loaded.params.get("delay_1"), Some(&ParamValue::F32(250.)));
+
+        let loaded_mappings: Vec<CcMapping> =
+            serde_json::from_str(&loaded.fields["cc-mappings"]).unwrap();
+        assert_eq!(loaded_mappings, mappings);
+
+        // Migrated state saves and loads the same again
+        let json = serde_json::to_string(&loaded).unwrap();
+        let reloaded: PluginState = serde_json::from_str(&json).unwrap();
+        assert_eq!(reloaded.fields, loaded.fields);
+        assert_eq!(
+            reloaded.params.keys().collect::<Vec<_>>(),
+            loaded.params.keys().collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,224 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+/// The languages the editor can be shown in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Language {
+    #[default]
+    English,
+    German,
+}
+
+impl Language {
+    /// The next language, so the GUI can cycle through them
+    pub fn next(self) -> Self {
+        match self {
+            Language::English => Language::German,
+            Language::German => Language::English,
+        }
+    }
+
+    /// The name of the language in the language itself, so it can be found without reading the current one
+    pub fn name(self) -> &'static str {
+        match self {
+            Language::English => "English",
+            Language::German => "Deutsch",
+        }
+    }
+}
+
+/// Defines [Text] with one row per text: the variant, the English text and the German text.
+/// A `{}` in a text is filled in by [Text::format()].
+macro_rules! string_table {
+    ($($text:ident => $english:literal, $german:literal;)*) => {
+        /// Every text the editor shows. The parameter names come from the parameters themselves and
+        /// aren't translated, so they match the automation lanes of the host.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub enum Text {
+            $($text,)*
+        }
+
+        impl Text {
+            /// All texts, in the order of the table
+            pub const ALL: &'static [Text] = &[$(Text::$text,)*];
+
+            /// The text in a language
+            pub fn get(self, language: Language) -> &'static str {
+                match language {
+                    Language::English => match self {
+                        $(Text::$text => $english,)*
+                    },
+                    Language::German => match self {
+                        $(Text::$text => $german,)*
+                    },
+                }
+            }
+        }
+    };
+}
+
+string_table! {
+    // The main page
+    Mono => "Mono", "Mono";
+    Stereo => "Stereo", "Stereo";
+    NoteTracking => "Note Tracking", "Notenverfolgung";
+    TapeEcho => "Tape Echo", "Bandecho";
+    String => "String", "Saite";
+    Delay => "Delay", "Zeit";
+    Feedback => "Feedback", "Feedback";
+    RepeatsUntilSilent => "{} repeats to -60 dB", "{} Wiederholungen bis -60 dB";
+    RepeatsForever => "Repeats forever", "Endlose Wiederholungen";
+    Pitch => "Pitch", "Tonhöhe";
+    Decay => "Decay", "Abklingen";
+    Bright => "Bright", "Hell";
+    Filter => "Filter", "Filter";
+    Cutoff => "Cutoff", "Frequenz";
+    Res => "Res", "Reso";
+    Mix => "Mix", "Mix";
+    Key => "Key", "Key";
+    Rate => "Rate", "Rate";
+    Intensity => "Intensity", "Intensität";
+    Wet => "Wet", "Nass";
+    Dry => "Dry", "Trocken";
+    GainReduction => "GR", "GR";
+    Safety => "SAFETY", "SCHUTZ";
+    Unstable => "UNSTABLE", "INSTABIL";
+    Glide => "Glide", "Gleiten";
+    CutoffRes => "Cutoff / Res", "Frequenz / Reso";
+    DelayFeedback => "Delay / Feedback", "Zeit / Feedback";
+    Throw => "Throw", "Wurf";
+    Reverse => "Rev", "Rück";
+    HalfSpeed => "1/2", "1/2";
+    // The advanced section
+    Advanced => "Advanced", "Erweitert";
+    Routing => "Routing", "Signalweg";
+    Input => "Input", "Eingang";
+    InputFilter => "Input Filter", "Eingangsfilter";
+    FeedbackFilter => "Feedback Filter", "Feedbackfilter";
+    Bands => "Bands", "Bänder";
+    FeedbackStages => "Feedback stages", "Feedbackstufen";
+    Svf => "SVF", "SVF";
+    Saturation => "Saturation", "Sättigung";
+    Reverb => "Reverb", "Hall";
+    Degrade => "Degrade", "Verfall";
+    ReturnLatency => "The external return adds at least {} ms to the repeats",
+        "Der externe Return verzögert die Wiederholungen um mindestens {} ms";
+    AnalyzeInput => "Analyze input", "Eingang analysieren";
+    Measuring => "Measuring...", "Messe...";
+    SuggestedTrim => "Suggested trim: {} dB", "Empfohlene Anpassung: {} dB";
+    InputSilent => "The input was silent", "Der Eingang war stumm";
+    Apply => "Apply", "Übernehmen";
+    Dismiss => "Dismiss", "Verwerfen";
+    Macros => "Macros", "Makros";
+    MacroAssignments => "Macro {}: {} parameters", "Makro {}: {} Parameter";
+    Scenes => "Scenes", "Szenen";
+    Crossfade => "Crossfade", "Blende";
+    StoreScene => "Store {}", "{} speichern";
+    Audition => "Audition", "Vorhören";
+    Play => "Play", "Abspielen";
+    LoadLoop => "Load loop…", "Loop laden…";
+    BuiltinLoop => "Built-in loop", "Eingebauter Loop";
+    Settings => "Settings", "Einstellungen";
+    ExportSettings => "Export settings…", "Einstellungen exportieren…";
+    ImportSettings => "Import settings…", "Einstellungen importieren…";
+    Presets => "Presets…", "Presets…";
+    SaveState => "Save state…", "Zustand speichern…";
+    ExportImpulse => "Export IR…", "IR exportieren…";
+    // The bar at the bottom
+    Back => "Back", "Zurück";
+    AllParameters => "All parameters", "Alle Parameter";
+    DefaultValue => "default {}", "Standard {}";
+    Reset => "Reset", "Zurücksetzen";
+    // The settings popover
+    ThemeEntry => "Theme: {}", "Farben: {}";
+    Dark => "Dark", "Dunkel";
+    Light => "Light", "Hell";
+    HighContrast => "High contrast", "Hoher Kontrast";
+    MetersEntry => "Meters: {}", "Anzeigen: {}";
+    On => "On", "An";
+    Off => "Off", "Aus";
+    TooltipsEntry => "Tooltips: {}", "Hinweise: {}";
+    Short => "Short", "Kurz";
+    Full => "Full", "Ausführlich";
+    DragEntry => "Drag: {} px", "Ziehen: {} px";
+    ScrollEntry => "Scroll: {} steps", "Scrollen: {} Schritte";
+    ScrollDirectionEntry => "Scroll direction: {}", "Scrollrichtung: {}";
+    Auto => "Auto", "Automatisch";
+    Normal => "Normal", "Normal";
+    Inverted => "Inverted", "Umgekehrt";
+    LanguageEntry => "Language: {}", "Sprache: {}";
+    // The menu of the knobs
+    ResetToDefault => "Reset to default", "Auf Standard zurücksetzen";
+    EnterValue => "Enter value...", "Wert eingeben...";
+    ShowAutomation => "Show automation", "Automation zeigen";
+    CopyToOtherChannel => "Copy to other channel", "Auf anderen Kanal kopieren";
+    AssignToMacro => "Assign to Macro {}", "Makro {} zuweisen";
+    SetMacroA => "Set macro A", "Makro-Wert A setzen";
+    SetMacroB => "Set macro B", "Makro-Wert B setzen";
+    RemoveFromMacro => "Remove from macro", "Aus Makro entfernen";
+    LearnCc => "Learn CC", "CC lernen";
+    ForgetCc => "Forget CC", "CC vergessen";
+    CycleCcCurve => "Cycle CC curve", "CC-Kurve wechseln";
+    InvertCcRange => "Invert CC range", "CC-Bereich umkehren";
+}
+
+impl Text {
+    /// The text in a language with every `{}` filled in by the next argument.
+    ///
+    /// Usage:
+    /// ```
+    /// use delax::strings::{Language, Text};
+    ///
+    /// assert_eq!(Text::StoreScene.format(Language::English, &[&2]), "Store 2");
+    /// // The arguments go wherever the translation puts them
+    /// assert_eq!(Text::StoreScene.format(Language::German, &[&2]), "2 speichern");
+    /// ```
+    pub fn format(self, language: Language, arguments: &[&dyn Display]) -> String {
+        let mut parts = self.get(language).split("{}");
+        let mut arguments = arguments.iter();
+        let mut formatted = parts.next().unwrap_or_default().to_string();
+        for part in parts {
+            // A missing argument leaves its placeholder in place instead of dropping the rest of the text
+            match arguments.next() {
+                Some(argument) => formatted.push_str(&argument.to_string()),
+                None => formatted.push_str("{}"),
+            }
+            formatted.push_str(part);
+        }
+        formatted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translations_fill_in_the_same_arguments() {
+        for text in Text::ALL {
+            let english = text.get(Language::English);
+            let german = text.get(Language::German);
+            assert!(!english.is_empty() && !german.is_empty(), "{text:?}");
+            assert_eq!(
+                english.matches("{}").count(),
+                german.matches("{}").count(),
+                "{text:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn arguments_are_filled_in_order() {
+        assert_eq!(
+            Text::MacroAssignments.format(Language::German, &[&1, &3]),
+            "Makro 1: 3 Parameter"
+        );
+        assert_eq!(
+            Text::ReturnLatency.format(Language::English, &[&format!("{:.1}", 2.5)]),
+            "The external return adds at least 2.5 ms to the repeats"
+        );
+        // Texts without placeholders come out unchanged
+        assert_eq!(Text::Back.format(Language::German, &[&1]), "Zurück");
+    }
+}
@@ -0,0 +1,372 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{atomic::Ordering, Arc, Mutex},
+};
+
+use nih_plug::{prelude::AtomicF32, wrapper::state::PluginState};
+
+use crate::{
+    audition::{Audition, AuditionLoop, BUILTIN_NAME},
+    offline::{impulse, OfflineRenderer},
+    params::DelaxParams,
+    settings_file::{SettingsFile, SETTINGS_EXTENSION},
+    wav::{read_wav, write_wav},
+};
+
+/// The length of exported impulse responses in seconds, long enough for the tails of long delays with a lot of feedback
+pub const IMPULSE_SECONDS: f32 = 10.;
+/// The amount of pieces an impulse response is rendered in, the progress is updated after every piece
+const IMPULSE_RENDER_STEPS: usize = 50;
+
+/// Work that touches the disk. These run on the background thread of the plugin, never on the audio or GUI thread.
+pub enum Task {
+    /// Write all parameters to a settings file
+    ExportSettings(PathBuf, SettingsFile),
+    /// Read the parameters from a settings file, the editor applies them once they're loaded
+    ImportSettings(PathBuf),
+    /// Look for settings files in a folder, to list them as presets
+    ScanPresets(PathBuf),
+    /// Write the complete state of the plugin to a file, with everything that's stored besides the parameters
+    SaveState(PathBuf, Box<PluginState>),
+    /// Render the impulse response of the plugin with the current values of its parameters and write it to a WAV file
+    ExportImpulse(PathBuf, Arc<DelaxParams>),
+    /// Load a WAV file as the audition loop, or the built-in loop without a file
+    LoadAudition(Option<PathBuf>, Arc<Audition>),
+}
+
+impl Task {
+    /// What the task is doing, shown in the editor while it runs
+    pub fn description(&self) -> &'static str {
+        match self {
+            Task::ExportSettings(..) => "Exporting settings",
+            Task::ImportSettings(..) => "Importing settings",
+            Task::ScanPresets(..) => "Scanning presets",
+            Task::SaveState(..) => "Saving state",
+            Task::ExportImpulse(..) => "Exporting impulse response",
+            Task::LoadAudition(..) => "Loading audition loop",
+        }
+    }
+}
+
+/// A settings file found by a preset scan
+#[derive(Debug, Clone, PartialEq)]
+pub struct Preset {
+    /// The name of the file without its extension
+    pub name: String,
+    pub path: PathBuf,
+}
+
+/// A rendered impulse response
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImpulseResponse {
+    pub sample_rate: u32,
+    /// The samples of every channel, all of the same length
+    pub channels: Vec<Vec<f32>>,
+}
+
+/// Runs the tasks and holds on to their results, shared between the background thread and the editor.
+///
+/// The background thread can't set parameters or touch the GUI, so results wait here until the editor polls them.
+pub struct TaskRunner {
+    /// The sample rate the plugin runs at, impulse responses are rendered at it
+    sample_rate: AtomicF32,
+    /// The description of the task that's currently running
+    running: Mutex<Option<&'static str>>,
+    /// How far the running task is, from 0 to 1
+    progress: AtomicF32,
+    imported: Mutex<Option<SettingsFile>>,
+    presets: Mutex<Option<Vec<Preset>>>,
+    /// What happened to the last task, to show in the editor
+    status: Mutex<Option<String>>,
+}
+
+impl Default for TaskRunner {
+    fn default() -> Self {
+        Self {
+            sample_rate: AtomicF32::new(44100.),
+            running: Mutex::new(None),
+            progress: AtomicF32::new(0.),
+            imported: Mutex::new(None),
+            presets: Mutex::new(None),
+            status: Mutex::new(None),
+        }
+    }
+}
+
+impl TaskRunner {
+    pub fn set_sample_rate(&self, sample_rate: f32) {
+        self.sample_rate.store(sample_rate, Ordering::Relaxed);
+    }
+
+    /// Run a task. This does file IO, so it must not be called from the audio thread.
+    pub fn run(&self, task: Task) {
+        *self.running.lock().unwrap() = Some(task.description());
+        self.progress.store(0., Ordering::Relaxed);
+
+        let status = match task {
+            Task::ExportSettings(path, settings) => match settings.save(&path) {
+                Ok(()) => format!("Exported {}", file_name(&path)),
+                Err(error) => format!("Couldn't export {}: {error}", file_name(&path)),
+            },
+            Task::ImportSettings(path) => match SettingsFile::load(&path) {
+                Ok(settings) => {
+                    *self.imported.lock().unwrap() = Some(settings);
+                    format!("Imported {}", file_name(&path))
+                }
+                Err(error) => format!("Couldn't import {}: {error}", file_name(&path)),
+            },
+            Task::ScanPresets(folder) => match self.scan_presets(&folder) {
+                Ok(presets) => {
+                    let status =
+                        format!("Found {} presets in {}", presets.len(), file_name(&folder));
+                    *self.presets.lock().unwrap() = Some(presets);
+                    status
+                }
+                Err(error) => format!("Couldn't scan {}: {error}", file_name(&folder)),
+            },
+            Task::SaveState(path, state) => match save_state(&path, &state) {
+                Ok(()) => format!("Saved {}", file_name(&path)),
+                Err(error) => format!("Couldn't save {}: {error}", file_name(&path)),
+            },
+            Task::ExportImpulse(path, params) => {
+                let impulse = self.render_impulse(params);
+                match write_wav(&path, impulse.sample_rate, &impulse.channels) {
+                    Ok(()) => format!("Exported {}", file_name(&path)),
+                    Err(error) => format!("Couldn't export {}: {error}", file_name(&path)),
+                }
+            }
+            Task::LoadAudition(None, audition) => {
+                audition.load(
+                    AuditionLoop::builtin(),
+                    BUILTIN_NAME.to_string(),
+                    self.sample_rate.load(Ordering::Relaxed),
+                );
+                String::from("Loaded the built-in loop")
+            }
+            Task::LoadAudition(Some(path), audition) => {
+                match read_wav(&path).and_then(AuditionLoop::from_wav) {
+                    Ok(audition_loop) => {
+                        audition.load(
+                            audition_loop,
+                            file_name(&path),
+                            self.sample_rate.load(Ordering::Relaxed),
+                        );
+                        format!("Loaded {}", file_name(&path))
+                    }
+                    Err(error) => format!("Couldn't load {}: {error}", file_name(&path)),
+                }
+            }
+        };
+
+        *self.status.lock().unwrap() = Some(status);
+        *self.running.lock().unwrap() = None;
+    }
+
+    /// The description and progress of the task that's currently running
+    pub fn running(&self) -> Option<(&'static str, f32)> {
+        let running = *self.running.lock().unwrap();
+        running.map(|description| (description, self.progress.load(Ordering::Relaxed)))
+    }
+
+    /// The settings of the last import, if they haven't been applied yet
+    pub fn take_imported(&self) -> Option<SettingsFile> {
+        self.imported.lock().unwrap().take()
+    }
+
+    /// The presets found by the last scan, if they haven't been picked up yet
+    pub fn take_presets(&self) -> Option<Vec<Preset>> {
+        self.presets.lock().unwrap().take()
+    }
+
+    /// The status of the last task, if it hasn't been shown yet
+    pub fn take_status(&self) -> Option<String> {
+        self.status.lock().unwrap().take()
+    }
+
+    /// The settings files in a folder that can be loaded, sorted by name
+    fn scan_presets(&self, folder: &Path) -> Result<Vec<Preset>, String> {
+        let mut paths: Vec<PathBuf> = fs::read_dir(folder)
+            .map_err(|error| error.to_string())?
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| extension == SETTINGS_EXTENSION)
+            })
+            .collect();
+        paths.sort();
+
+        let num_paths = paths.len();
+        let mut presets = Vec::with_capacity(num_paths);
+        for (index, path) in paths.into_iter().enumerate() {
+            // Other JSON files can live in the same folder, only files that load are presets
+            if SettingsFile::load(&path).is_ok() {
+                presets.push(Preset {
+                    name: path
+                        .file_stem()
+                        .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned()),
+                    path,
+                });
+            }
+            self.progress
+                .store((index + 1) as f32 / num_paths as f32, Ordering::Relaxed);
+        }
+
+        Ok(presets)
+    }
+
+    /// Render [IMPULSE_SECONDS] of the output of the plugin for a unit impulse, at the current values of the parameters.
+    /// The dry signal is part of the output at the current mix, just like it would be in a host.
+    fn render_impulse(&self, params: Arc<DelaxParams>) -> ImpulseResponse {
+        let sample_rate = self.sample_rate.load(Ordering::Relaxed);
+        let mut renderer = OfflineRenderer::with_params(sample_rate, &params);
+        let input = impulse((IMPULSE_SECONDS * sample_rate) as usize);
+
+        let mut channels: Vec<Vec<f32>> = (0..2).map(|_| Vec::with_capacity(input.len())).collect();
+        let step_len = input.len().div_ceil(IMPULSE_RENDER_STEPS).max(1);
+        for (step, piece) in input.chunks(step_len).enumerate() {
+            let (left, right) = renderer.render(piece, piece);
+            channels[0].extend(left);
+            channels[1].extend(right);
+            self.progress.store(
+                (step + 1) as f32 / IMPULSE_RENDER_STEPS as f32,
+                Ordering::Relaxed,
+            );
+        }
+
+        ImpulseResponse {
+            sample_rate: sample_rate.round() as u32,
+            channels,
+        }
+    }
+}
+
+fn save_state(path: &Path, state: &PluginState) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(state).map_err(|error| error.to_string())?;
+    fs::write(path, json).map_err(|error| error.to_string())
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    /// An empty folder in the temp folder, unique to a test
+    fn temp_folder(name: &str) -> PathBuf {
+        let folder = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        folder
+    }
+
+    fn settings(value: f32) -> SettingsFile {
+        SettingsFile {
+            plugin_version: String::from("0.1.0"),
+            state_version: crate::state::STATE_VERSION,
+            values: BTreeMap::from([(String::from("feedback_1"), value)]),
+        }
+    }
+
+    #[test]
+    fn imported_settings_wait_for_the_editor() {
+        let folder = temp_folder("delax-tasks-import");
+        let path = folder.join("Dub.json");
+        let runner = TaskRunner::default();
+
+        runner.run(Task::ExportSettings(path.clone(), settings(0.75)));
+        assert_eq!(runner.take_status().as_deref(), Some("Exported Dub.json"));
+        assert_eq!(runner.take_imported(), None);
+        assert_eq!(runner.running(), None);
+
+        runner.run(Task::ImportSettings(path));
+        assert_eq!(runner.take_imported(), Some(settings(0.75)));
+        assert_eq!(runner.take_imported(), None);
+        fs::remove_dir_all(folder).unwrap();
+    }
+
+    #[test]
+    fn scans_only_list_settings_files() {
+        let folder = temp_folder("delax-tasks-scan");
+        settings(0.5).save(&folder.join("Slap.json")).unwrap();
+        settings(0.9).save(&folder.join("Ambient.json")).unwrap();
+        fs::write(folder.join("notes.txt"), "Not a preset").unwrap();
+        fs::write(folder.join("other.json"), "{}").unwrap();
+
+        let runner = TaskRunner::default();
+        runner.run(Task::ScanPresets(folder.clone()));
+        let names: Vec<_> = runner
+            .take_presets()
+            .unwrap()
+            .into_iter()
+            .map(|preset| preset.name)
+            .collect();
+        assert_eq!(names, ["Ambient", "Slap"]);
+        assert_eq!(
+            runner.take_status().as_deref(),
+            Some("Found 2 presets in delax-tasks-scan")
+        );
+        fs::remove_dir_all(folder).unwrap();
+    }
+
+    #[test]
+    fn impulse_responses_are_written_as_wav() {
+        let folder = temp_folder("delax-tasks-impulse");
+        let path = folder.join("Delax IR.wav");
+        let runner = TaskRunner::default();
+        runner.set_sample_rate(1000.);
+
+        // The smoothers of a running plugin have been reset to the values of the parameters
+        let params = Arc::new(DelaxParams::default());
+        params.reset_smoothers();
+
+        runner.run(Task::ExportImpulse(path.clone(), params));
+        assert_eq!(
+            runner.take_status().as_deref(),
+            Some("Exported Delax IR.wav")
+        );
+
+        // The header is followed by ten seconds of stereo float samples, which start with the dry impulse
+        let bytes = fs::read(&path).unwrap();
+        assert_eq!(bytes.len(), 58 + 10000 * 2 * 4);
+        assert!(f32::from_le_bytes(bytes[58..62].try_into().unwrap()) > 0.);
+        fs::remove_dir_all(folder).unwrap();
+    }
+
+    #[test]
+    fn audition_loops_are_loaded_from_wav() {
+        let folder = temp_folder("delax-tasks-audition");
+        let path = folder.join("Drums.wav");
+        crate::wav::write_wav(&path, 500, &[vec![1., 0.]]).unwrap();
+        let runner = TaskRunner::default();
+        runner.set_sample_rate(1000.);
+        let audition = Arc::new(Audition::default());
+
+        runner.run(Task::LoadAudition(Some(path), audition.clone()));
+        assert_eq!(runner.take_status().as_deref(), Some("Loaded Drums.wav"));
+        assert_eq!(audition.name(), "Drums.wav");
+
+        runner.run(Task::LoadAudition(None, audition.clone()));
+        assert_eq!(audition.name(), BUILTIN_NAME);
+        fs::remove_dir_all(folder).unwrap();
+    }
+
+    #[test]
+    fn failures_are_reported() {
+        let runner = TaskRunner::default();
+        runner.run(Task::ImportSettings(PathBuf::from(
+            "/nonexistent/delax-settings.json",
+        )));
+        assert_eq!(runner.take_imported(), None);
+        assert!(runner
+            .take_status()
+            .is_some_and(|status| status.starts_with("Couldn't import delax-settings.json")));
+    }
+}
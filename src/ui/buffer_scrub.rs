@@ -0,0 +1,247 @@
+use std::sync::{atomic::Ordering, Arc};
+
+use nih_plug::prelude::Param;
+use nih_plug_vizia::{
+    vizia::{
+        prelude::*,
+        vg::{Paint, Path},
+    },
+    widgets::param_base::ParamWidgetBase,
+};
+
+use crate::{
+    delay_engine::tempo_lock::{synced_delay_ms, synced_len_ms},
+    param_changes::{ParamGenerations, ParamTopic},
+    params::DelaxParams,
+    visualization::{BufferSnapshot, VisualizationBus},
+};
+
+/// A snapshot of the delay lines, as the scrub view draws it
+#[derive(Debug, Clone, Default, PartialEq, Data)]
+pub struct ScrubWaveform {
+    /// The lowest and the highest sample of every column, from the oldest to the newest
+    pub columns: Vec<[f32; 2]>,
+    /// How far back the oldest column reaches, in ms
+    pub length_ms: f32,
+}
+
+impl From<BufferSnapshot> for ScrubWaveform {
+    fn from(snapshot: BufferSnapshot) -> Self {
+        Self {
+            columns: snapshot
+                .frames
+                .iter()
+                .map(|frame| [frame.min, frame.max])
+                .collect(),
+            length_ms: snapshot.length_ms,
+        }
+    }
+}
+
+/// Everything the scrub view needs to know about the loop region parameters
+#[derive(Debug, Clone, Copy, PartialEq, Data)]
+struct LoopState {
+    start_ms: f32,
+    length_ms: f32,
+    synced: bool,
+}
+
+impl LoopState {
+    fn from_params(params: &Arc<DelaxParams>) -> Self {
+        let loop_region = &params.delay_params.loop_region;
+        Self {
+            start_ms: loop_region.start.value(),
+            length_ms: loop_region.length.value(),
+            synced: loop_region.bpm_bound.value(),
+        }
+    }
+}
+
+enum BufferScrubEvent {
+    SetWaveform(ScrubWaveform),
+    SetLoop(LoopState),
+}
+
+/// Shows the frozen delay lines as a waveform, with the newest sample on the right.
+///
+/// The loop region is drawn over the waveform. Clicking or dragging anywhere moves the start of the loop there,
+/// so the slice that repeats can be picked by ear while scrubbing through the captured audio.
+pub struct BufferScrub {
+    start_param: ParamWidgetBase,
+    visualization: Arc<VisualizationBus>,
+    waveform: ScrubWaveform,
+    loop_state: LoopState,
+    dragging: bool,
+}
+
+impl BufferScrub {
+    pub fn new<L, G, W>(
+        cx: &mut Context,
+        params: L,
+        generations: G,
+        waveform: W,
+        visualization: Arc<VisualizationBus>,
+    ) -> Handle<Self>
+    where
+        L: Lens<Target = Arc<DelaxParams>> + Clone,
+        G: Lens<Target = ParamGenerations>,
+        W: Lens<Target = ScrubWaveform>,
+    {
+        Self {
+            start_param: ParamWidgetBase::new(cx, params.clone(), |params| {
+                &params.delay_params.loop_region.start
+            }),
+            visualization,
+            waveform: ScrubWaveform::default(),
+            loop_state: LoopState::from_params(&params.get(cx)),
+            dragging: false,
+        }
+        .build(cx, move |cx| {
+            let entity = cx.current();
+            Binding::new(cx, waveform, move |cx, waveform| {
+                let waveform = waveform.get(cx);
+                cx.emit_to(entity, BufferScrubEvent::SetWaveform(waveform));
+            });
+            // The loop parameters are only read again when one of them changed
+            Binding::new(
+                cx,
+                generations.map(|generations| generations.get(ParamTopic::LoopRegion)),
+                move |cx, _| {
+                    let state = LoopState::from_params(&params.get(cx));
+                    cx.emit_to(entity, BufferScrubEvent::SetLoop(state));
+                },
+            );
+        })
+    }
+
+    /// The tempo of the host, if the loop is synced and the host reports one
+    fn synced_tempo(&self) -> Option<f32> {
+        let tempo = self.visualization.tempo.load(Ordering::Relaxed);
+        (self.loop_state.synced && tempo > 0.).then_some(tempo)
+    }
+
+    /// The start and the length of the loop in ms, snapped to note lengths when the loop is synced
+    fn loop_ms(&self) -> (f32, f32) {
+        let LoopState {
+            start_ms,
+            length_ms,
+            ..
+        } = self.loop_state;
+        let (start_ms, length_ms) = match self.synced_tempo() {
+            Some(tempo) => (
+                synced_delay_ms(start_ms, tempo),
+                synced_delay_ms(length_ms, tempo),
+            ),
+            None => (start_ms, length_ms),
+        };
+        (start_ms, length_ms.min(start_ms))
+    }
+
+    /// The horizontal position of a time in ms before the newest sample
+    fn x_for_ms(&self, bounds: BoundingBox, ms: f32) -> f32 {
+        let span_ms = self.waveform.length_ms.max(f32::EPSILON);
+        bounds.x + (1. - ms / span_ms).clamp(0., 1.) * bounds.w
+    }
+
+    fn set_start_from_x(&self, cx: &mut EventContext, x: f32) {
+        let bounds = cx.bounds();
+        let start_ms = (1. - (x - bounds.x) / bounds.w).clamp(0., 1.) * self.waveform.length_ms;
+        // A synced loop is read at a reference tempo, like the delay knob
+        let start = match self.synced_tempo() {
+            Some(tempo) => synced_len_ms(start_ms, tempo),
+            None => start_ms,
+        };
+
+        self.start_param
+            .set_normalized_value(cx, self.start_param.preview_normalized(start));
+    }
+}
+
+impl View for BufferScrub {
+    fn element(&self) -> Option<&'static str> {
+        Some("buffer-scrub")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|scrub_event, _| match scrub_event {
+            BufferScrubEvent::SetWaveform(waveform) => {
+                self.waveform = waveform.clone();
+                cx.needs_redraw();
+            }
+            BufferScrubEvent::SetLoop(state) => {
+                self.loop_state = *state;
+                cx.needs_redraw();
+            }
+        });
+
+        event.map(|window_event, event_meta| match window_event {
+            WindowEvent::MouseDown(MouseButton::Left) => {
+                self.dragging = true;
+                cx.capture();
+                cx.set_active(true);
+                self.start_param.begin_set_parameter(cx);
+                let x = cx.mouse().cursorx;
+                self.set_start_from_x(cx, x);
+                event_meta.consume();
+            }
+            WindowEvent::MouseMove(x, _) => {
+                if self.dragging {
+                    self.set_start_from_x(cx, *x);
+                    event_meta.consume();
+                }
+            }
+            WindowEvent::MouseUp(MouseButton::Left) => {
+                if self.dragging {
+                    self.dragging = false;
+                    self.start_param.end_set_parameter(cx);
+                    cx.release();
+                    cx.set_active(false);
+                    event_meta.consume();
+                }
+            }
+            _ => (),
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let bounds = cx.bounds();
+        if bounds.w <= f32::EPSILON || bounds.h <= f32::EPSILON {
+            return;
+        }
+
+        let line_color = cx.border_color();
+        let region_color = cx.caret_color();
+        let center = bounds.y + bounds.h / 2.;
+
+        let mut waveform = Path::new();
+        let columns = self.waveform.columns.len() as f32;
+        for (column, [min, max]) in self.waveform.columns.iter().enumerate() {
+            let x = bounds.x + (column as f32 + 0.5) / columns * bounds.w;
+            waveform.move_to(x, center - max.clamp(-1., 1.) * bounds.h / 2.);
+            waveform.line_to(x, center - min.clamp(-1., 1.) * bounds.h / 2.);
+        }
+        let mut waveform_paint = Paint::color(line_color.into());
+        waveform_paint.set_line_width(1.);
+        canvas.stroke_path(&waveform, &waveform_paint);
+
+        // The loop reaches from its start towards the newest sample
+        let (start_ms, length_ms) = self.loop_ms();
+        let left = self.x_for_ms(bounds, start_ms);
+        let right = self.x_for_ms(bounds, start_ms - length_ms);
+        let mut region = Path::new();
+        region.rect(left, bounds.y, (right - left).max(1.), bounds.h);
+        canvas.fill_path(
+            &region,
+            &Paint::color(
+                Color::rgba(region_color.r(), region_color.g(), region_color.b(), 60).into(),
+            ),
+        );
+
+        let mut marker = Path::new();
+        marker.move_to(left, bounds.y);
+        marker.line_to(left, bounds.y + bounds.h);
+        let mut marker_paint = Paint::color(region_color.into());
+        marker_paint.set_line_width(2.);
+        canvas.stroke_path(&marker, &marker_paint);
+    }
+}
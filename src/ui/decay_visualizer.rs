@@ -1,46 +1,103 @@
-use nih_plug::params::Param;
-use nih_plug_vizia::{
-    vizia::{
-        binding::Lens,
-        context::{Context, DrawContext},
-        vg::{Color, LineCap, Paint, Path},
-        view::{Canvas, Handle, View},
-    },
-    widgets::param_base::ParamWidgetBase,
+use nih_plug::util;
+use nih_plug_vizia::vizia::{
+    prelude::*,
+    vg::{Paint, Path},
 };
-pub struct DecayVisualizer<L: Lens<Target = f32>> {
-    delay_l_lens: Option<L>,
-    delay_r_lens: Option<L>,
-    feedback_l_lens: Option<L>,
-    feedback_r_lens: Option<L>,
-    // param_base: ParamWidgetBase
+
+use crate::delay_engine::params::NUM_CHANNELS;
+
+/// The amount of measured repeats that are shown per channel
+pub const REPEAT_HISTORY: usize = 16;
+/// The level of the bottom of the visualizer in dB
+const FLOOR_DB: f32 = -60.;
+
+/// The RMS levels of the last delay periods of every channel, the newest one is at the end
+#[derive(Debug, Clone, Copy, Default, PartialEq, Data)]
+pub struct RepeatLevels {
+    pub levels: [[f32; REPEAT_HISTORY]; NUM_CHANNELS],
+}
+
+impl RepeatLevels {
+    /// Add a newly measured period of a channel and drop the oldest one
+    pub fn push(&mut self, channel: usize, rms: f32) {
+        let levels = &mut self.levels[channel];
+        levels.rotate_left(1);
+        levels[REPEAT_HISTORY - 1] = rms;
+    }
 }
 
-impl<L: Lens<Target = f32>> DecayVisualizer<L> {
-    pub fn new(cx: &mut Context) -> Handle<Self> {
+enum DecayVisualizerEvent {
+    SetLevels(RepeatLevels),
+}
+
+/// Shows the measured level of the delay output over the last delay periods as bars.
+///
+/// Every period holds one repeat, so after the input stops the bars show how the repeats actually decay,
+/// including what the filters in the feedback loop do to them. The channels are drawn next to each other.
+pub struct DecayVisualizer {
+    levels: RepeatLevels,
+}
+
+impl DecayVisualizer {
+    pub fn new<L>(cx: &mut Context, levels: L) -> Handle<Self>
+    where
+        L: Lens<Target = RepeatLevels>,
+    {
         Self {
-            delay_l_lens: None,
-            delay_r_lens: None,
-            feedback_l_lens: None,
-            feedback_r_lens: None,
+            levels: RepeatLevels::default(),
         }
-        .build(cx, |_| {})
+        .build(cx, move |cx| {
+            let entity = cx.current();
+            Binding::new(cx, levels, move |cx, levels| {
+                let levels = levels.get(cx);
+                cx.emit_to(entity, DecayVisualizerEvent::SetLevels(levels));
+            });
+        })
     }
 }
-impl<L> View for DecayVisualizer<L>
-where
-    L: Lens<Target = f32> + Clone,
-{
+
+impl View for DecayVisualizer {
+    fn element(&self) -> Option<&'static str> {
+        Some("decay-visualizer")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|visualizer_event, _| match visualizer_event {
+            DecayVisualizerEvent::SetLevels(levels) => {
+                self.levels = *levels;
+                cx.needs_redraw();
+            }
+        });
+    }
+
     fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
-        let mut path = Path::new();
-        path.move_to(0.0, 0.0);
-        path.line_to(0.0, 1.0);
-        path.line_to(1.0, 1.0);
-        path.line_to(1.0, 0.0);
-        path.close();
-        let mut paint = Paint::color(Color::white());
-        paint.set_line_cap(LineCap::Round);
-        paint.set_line_width(0.1);
-        canvas.stroke_path(&path, &paint);
+        let bounds = cx.bounds();
+        if bounds.w <= f32::EPSILON || bounds.h <= f32::EPSILON {
+            return;
+        }
+
+        let bar_color = cx.caret_color();
+        let slot_width = bounds.w / REPEAT_HISTORY as f32;
+        let bar_width = slot_width / NUM_CHANNELS as f32 * 0.8;
+
+        for (channel, levels) in self.levels.levels.iter().enumerate() {
+            // The second channel is drawn a bit fainter, so the channels can be told apart
+            let alpha = if channel == 0 { 255 } else { 150 };
+            let paint = Paint::color(
+                Color::rgba(bar_color.r(), bar_color.g(), bar_color.b(), alpha).into(),
+            );
+
+            let mut path = Path::new();
+            for (slot, level) in levels.iter().enumerate() {
+                let height = (1. - util::gain_to_db(*level) / FLOOR_DB).clamp(0., 1.) * bounds.h;
+                if height <= 0. {
+                    continue;
+                }
+
+                let x = bounds.x + slot as f32 * slot_width + channel as f32 * bar_width;
+                path.rect(x, bounds.y + bounds.h - height, bar_width, height);
+            }
+            canvas.fill_path(&path, &paint);
+        }
     }
 }
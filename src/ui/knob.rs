@@ -1,6 +1,6 @@
 use std::f32::consts::PI;
 
-use nih_plug::prelude::Param;
+use nih_plug::prelude::{Param, ParamPtr};
 use nih_plug_vizia::{
     vizia::{
         prelude::*,
@@ -9,6 +9,16 @@ use nih_plug_vizia::{
     widgets::param_base::ParamWidgetBase,
 };
 
+use super::{
+    help_hint, text, text_format,
+    value_entry::{ValueEntry, ValueEntryEvent},
+    Data, MacroEvent, MidiLearnEvent, ParamMenuEvent, ReadoutEvent,
+};
+use crate::{editor_settings::KnobSettings, macros::NUM_MACROS, strings::Text};
+
+/// How far Page Up and Page Down move the knob, as a normalized value
+const PAGE_STEP: f32 = 0.1;
+
 #[allow(dead_code)]
 pub struct DragState {
     start_val: f32,
@@ -20,14 +30,39 @@ pub struct DragState {
 #[derive(Lens)]
 pub struct ParamKnob {
     param_base: ParamWidgetBase,
+    /// The parameter, to tell the readout bar which one is used
+    param_ptr: ParamPtr,
     drag_active: bool,
     default_val: f32,
     drag_status: Option<DragState>,
     active: bool,
+    /// Whether the right click menu is open
+    menu_open: bool,
+    /// Whether the value is being typed in
+    text_input_active: bool,
+    /// Maps every normalized value the knob sets onto the values it may take, see [ParamKnobExt::quantize()]
+    quantize: Option<Box<dyn Fn(f32) -> f32>>,
 }
 
+/// The label below a [ParamKnob]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KnobLabel {
+    /// The name of the parameter, the same the host shows
+    Param,
+    /// A shorter name from the string table, in the language of the editor
+    Text(Text),
+    /// No label, for rows that show the name somewhere else
+    Hidden,
+}
+
+#[derive(Clone)]
 pub enum ParamKnobEvent {
     SetActive(bool),
+    SetMenuOpen(bool),
+    ResetToDefault,
+    /// Touch the parameter without changing it, so hosts that follow the last touched parameter show its automation
+    TouchForAutomation,
+    BeginTextInput,
 }
 
 impl ParamKnob {
@@ -39,14 +74,14 @@ impl ParamKnob {
     /// - Lens to all params
     /// - Function mapping all params to a param
     /// - Default value
-    /// - Option for custom label
+    /// - The label below the knob
     /// - Lens to whether or not it is active. Can just be a lens on true
     pub fn new<L, La, Params, P, FMap>(
         cx: &mut Context,
         params: L,
         params_to_param: FMap,
         default_val: f32,
-        custom_label: Option<String>,
+        label: KnobLabel,
         active_lens: La,
     ) -> Handle<Self>
     where
@@ -56,12 +91,22 @@ impl ParamKnob {
         P: Param + 'static,
         FMap: Fn(&Params) -> &P + Copy + 'static,
     {
+        let param_ptr = params
+            .clone()
+            .map(move |params| params_to_param(params).as_ptr())
+            .get(cx);
+        let entry_params = params.clone();
+
         Self {
-            param_base: ParamWidgetBase::new(cx, params, params_to_param),
+            param_base: ParamWidgetBase::new(cx, params.clone(), params_to_param),
+            param_ptr,
             drag_active: false,
             default_val,
             drag_status: None,
             active: true,
+            menu_open: false,
+            text_input_active: false,
+            quantize: None,
         }
         .build(
             cx,
@@ -81,30 +126,118 @@ impl ParamKnob {
                     KnobVisual::new(cx, default_val)
                         .value(param_lens)
                         .class("knob-visual")
-                        .tooltip(|cx| {
-                            Binding::new(cx, param_lens, move |cx, val| {
-                                Label::new(
-                                    cx,
-                                    &format!(
-                                        "{}",
-                                        param_data
-                                            .param()
-                                            .normalized_value_to_string(val.get(cx), true)
-                                    ),
-                                )
-                                .class("knob-tooltip");
-                            });
-                        })
                         .active(active_lens);
 
-                    if let Some(text) = custom_label {
-                        Label::new(cx, &text).class("knob-label");
-                    } else {
-                        Label::new(cx, *(&param_data.param().name())).class("knob-label");
+                    match label {
+                        KnobLabel::Param => {
+                            Label::new(cx, *(&param_data.param().name())).class("knob-label");
+                        }
+                        KnobLabel::Text(label) => {
+                            Label::new(cx, text(label)).class("knob-label");
+                        }
+                        // An empty label keeps the knob where it would be with a label
+                        KnobLabel::Hidden => {
+                            Label::new(cx, "").class("knob-label");
+                        }
+                    }
+                });
+                let param_ptr = param_data.param().as_ptr();
+                help_hint(cx, param_ptr);
+
+                // The right click menu
+                Popup::new(cx, ParamKnob::menu_open, true, move |cx| {
+                    for (entry, event) in [
+                        (Text::ResetToDefault, ParamKnobEvent::ResetToDefault),
+                        (Text::EnterValue, ParamKnobEvent::BeginTextInput),
+                        (Text::ShowAutomation, ParamKnobEvent::TouchForAutomation),
+                    ] {
+                        Label::new(cx, text(entry))
+                            .class("menu-entry")
+                            .on_press(move |cx| {
+                                cx.emit(event.clone());
+                                cx.emit(ParamKnobEvent::SetMenuOpen(false));
+                            });
+                    }
+                    Label::new(cx, text(Text::CopyToOtherChannel))
+                        .class("menu-entry")
+                        .on_press(move |cx| {
+                            cx.emit(ParamMenuEvent::CopyToOtherChannel(param_ptr));
+                            cx.emit(ParamKnobEvent::SetMenuOpen(false));
+                        });
+
+                    for macro_index in 0..NUM_MACROS {
+                        Label::new(cx, text_format(Text::AssignToMacro, macro_index + 1))
+                            .class("menu-entry")
+                            .on_press(move |cx| {
+                                cx.emit(MacroEvent::Assign(macro_index, param_ptr));
+                                cx.emit(ParamKnobEvent::SetMenuOpen(false));
+                            });
+                    }
+                    for (entry, event) in [
+                        (Text::SetMacroA, MacroEvent::SetA as fn(_) -> _),
+                        (Text::SetMacroB, MacroEvent::SetB),
+                        (Text::RemoveFromMacro, MacroEvent::Remove),
+                    ] {
+                        Label::new(cx, text(entry))
+                            .class("menu-entry")
+                            .on_press(move |cx| {
+                                cx.emit(event(param_ptr));
+                                cx.emit(ParamKnobEvent::SetMenuOpen(false));
+                            });
+                    }
+
+                    for (entry, event) in [
+                        (Text::LearnCc, MidiLearnEvent::Learn as fn(_) -> _),
+                        (Text::ForgetCc, MidiLearnEvent::Forget),
+                        (Text::CycleCcCurve, MidiLearnEvent::CycleCurve),
+                        (Text::InvertCcRange, MidiLearnEvent::InvertRange),
+                    ] {
+                        Label::new(cx, text(entry))
+                            .class("menu-entry")
+                            .on_press(move |cx| {
+                                cx.emit(event(param_ptr));
+                                cx.emit(ParamKnobEvent::SetMenuOpen(false));
+                            });
+                    }
+                })
+                .on_blur(|cx| cx.emit(ParamKnobEvent::SetMenuOpen(false)))
+                .class("param-menu");
+
+                // Typing in a value, this replaces the label while it's active
+                Binding::new(cx, ParamKnob::text_input_active, move |cx, active| {
+                    if active.get(cx) {
+                        ValueEntry::new(cx, entry_params.clone(), params_to_param);
                     }
                 });
             }),
         )
+        // Reachable with tab, so it can be used without a mouse
+        .navigable(true)
+        .role(Role::Slider)
+    }
+
+    /// Set the parameter to a new normalized value as a single gesture
+    fn set_value(&self, cx: &mut EventContext, value: f32) {
+        self.param_base.begin_set_parameter(cx);
+        self.param_base
+            .set_normalized_value(cx, self.quantized(value));
+        self.param_base.end_set_parameter(cx);
+    }
+
+    /// The drag and scroll settings of the editor
+    fn settings(cx: &EventContext) -> KnobSettings {
+        cx.data::<Data>()
+            .map(|data| data.preferences.knobs)
+            .unwrap_or_default()
+    }
+
+    /// Clamp a normalized value and quantize it, if the knob quantizes its values
+    fn quantized(&self, value: f32) -> f32 {
+        let value = value.clamp(0., 1.);
+        match &self.quantize {
+            Some(quantize) => quantize(value),
+            None => value,
+        }
     }
 }
 
@@ -113,6 +246,17 @@ impl View for ParamKnob {
         Some("param-knob")
     }
 
+    fn accessibility(&self, _cx: &mut AccessContext, node: &mut AccessNode) {
+        let value = self.param_base.unmodulated_normalized_value();
+
+        node.set_name(self.param_base.name());
+        node.set_value(self.param_base.normalized_value_to_string(value, true));
+        node.set_numeric_value(value as f64);
+        node.set_min_numeric_value(0.);
+        node.set_max_numeric_value(1.);
+        node.set_numeric_value_jump(PAGE_STEP as f64);
+    }
+
     fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
         // Internal events
         event.map(|param_knob_event, _| match param_knob_event {
@@ -120,6 +264,32 @@ impl View for ParamKnob {
                 self.active = *active;
                 cx.needs_redraw();
             }
+            ParamKnobEvent::SetMenuOpen(open) => {
+                self.menu_open = *open;
+            }
+            ParamKnobEvent::ResetToDefault => {
+                self.set_value(cx, self.param_base.default_normalized_value());
+            }
+            ParamKnobEvent::TouchForAutomation => {
+                // nih-plug can't ask the host to create an automation lane, but most hosts
+                // select the lane of the last touched parameter
+                self.set_value(cx, self.param_base.unmodulated_normalized_value());
+            }
+            ParamKnobEvent::BeginTextInput => {
+                self.text_input_active = true;
+                cx.set_active(true);
+            }
+        });
+
+        // The typed in value, the entry closes either way
+        event.map(|value_entry_event, meta| {
+            if let ValueEntryEvent::Commit(normalized_value) = value_entry_event {
+                self.set_value(cx, *normalized_value);
+            }
+
+            self.text_input_active = false;
+            cx.set_active(false);
+            meta.consume();
         });
 
         // External events
@@ -134,6 +304,7 @@ impl View for ParamKnob {
                     cx.set_active(true);
 
                     self.param_base.begin_set_parameter(cx);
+                    cx.emit(ReadoutEvent::BeginDrag(self.param_ptr));
                 }
             }
             WindowEvent::MouseUp(MouseButton::Left) => {
@@ -145,23 +316,49 @@ impl View for ParamKnob {
                     cx.set_active(false);
 
                     self.param_base.end_set_parameter(cx);
+                    cx.emit(ReadoutEvent::EndDrag(self.param_ptr));
 
                     self.drag_status = None;
 
                     event_meta.consume();
                 }
             }
+            WindowEvent::MouseDown(MouseButton::Right) => {
+                self.menu_open = !self.menu_open;
+                event_meta.consume();
+            }
             WindowEvent::MouseDoubleClick(_) => {
                 if self.active {
                     // Reset to default
-                    self.param_base.begin_set_parameter(cx);
-                    self.param_base
-                        .set_normalized_value(cx, self.param_base.default_normalized_value());
-                    self.param_base.end_set_parameter(cx);
+                    self.set_value(cx, self.param_base.default_normalized_value());
 
                     event_meta.consume();
                 }
             }
+            WindowEvent::KeyDown(code, _) => {
+                if self.active {
+                    let value = self.param_base.unmodulated_normalized_value();
+                    let finer = cx.modifiers().shift();
+
+                    let new_value = match code {
+                        Code::ArrowUp | Code::ArrowRight => {
+                            Some(self.param_base.next_normalized_step(value, finer))
+                        }
+                        Code::ArrowDown | Code::ArrowLeft => {
+                            Some(self.param_base.previous_normalized_step(value, finer))
+                        }
+                        Code::PageUp => Some(value + PAGE_STEP),
+                        Code::PageDown => Some(value - PAGE_STEP),
+                        Code::Home => Some(self.param_base.default_normalized_value()),
+                        _ => None,
+                    };
+
+                    if let Some(new_value) = new_value {
+                        self.set_value(cx, new_value);
+                        event_meta.consume();
+                    }
+                }
+            }
             WindowEvent::MouseMove(x, y) => {
                 if self.drag_active {
                     let drag_status = self.drag_status.get_or_insert_with(|| DragState {
@@ -171,21 +368,19 @@ impl View for ParamKnob {
                     });
 
                     let delta_y = *y - drag_status.start_y;
+                    let value = drag_status.start_val - Self::settings(cx).drag_delta(delta_y);
 
                     self.param_base
-                        .set_normalized_value(cx, drag_status.start_val - delta_y / 1000.);
+                        .set_normalized_value(cx, self.quantized(value));
                     event_meta.consume();
                 }
             }
+            WindowEvent::MouseEnter => cx.emit(ReadoutEvent::Hover(self.param_ptr)),
+            WindowEvent::MouseLeave => cx.emit(ReadoutEvent::Unhover(self.param_ptr)),
             WindowEvent::MouseScroll(_x, y) => {
                 if self.active {
-                    let delta = *y as f32 / 25.;
-                    self.param_base.begin_set_parameter(cx);
-                    self.param_base.set_normalized_value(
-                        cx,
-                        self.param_base.unmodulated_normalized_value() + delta,
-                    );
-                    self.param_base.end_set_parameter(cx);
+                    let delta = Self::settings(cx).scroll_delta(*y as f32);
+                    self.set_value(cx, self.param_base.unmodulated_normalized_value() + delta);
                     event_meta.consume();
                 }
             }
@@ -334,3 +529,14 @@ impl KnobVisualExt for Handle<'_, KnobVisual> {
         self
     }
 }
+
+pub trait ParamKnobExt {
+    /// Quantize the values set with the knob. The function maps a normalized value to the normalized value that's set.
+    fn quantize(self, quantize: impl Fn(f32) -> f32 + 'static) -> Self;
+}
+
+impl ParamKnobExt for Handle<'_, ParamKnob> {
+    fn quantize(self, quantize: impl Fn(f32) -> f32 + 'static) -> Self {
+        self.modify(|knob| knob.quantize = Some(Box::new(quantize)))
+    }
+}
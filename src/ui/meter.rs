@@ -1,6 +1,6 @@
 use std::{f32::consts::PI, sync::Arc};
 
-use nih_plug::{nih_dbg, prelude::Param};
+use nih_plug::{nih_dbg, prelude::Param, util};
 use nih_plug_vizia::{
     vizia::{
         prelude::*,
@@ -9,17 +9,170 @@ use nih_plug_vizia::{
     widgets::param_base::ParamWidgetBase,
 };
 
-#[derive(Lens)]
-pub struct PeakMeter {}
+use super::{MeterReadout, ReadoutEvent};
+use crate::{editor_settings::Theme, visualization::MeterLevels};
+
+/// The gap between the stripes of a hatched meter bar, in pixels
+const HATCH_SPACING: f32 = 3.;
+
+/// The colors of the zones of a [PeakMeter]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MeterPalette {
+    /// Green, yellow and red
+    #[default]
+    Standard,
+    /// Blue, yellow and vermilion, these stay apart with all common color vision deficiencies
+    ColorblindSafe,
+}
+
+impl MeterPalette {
+    /// The palette that goes with a theme of the editor
+    pub fn for_theme(theme: Theme) -> Self {
+        match theme {
+            Theme::Dark | Theme::Light => MeterPalette::Standard,
+            Theme::HighContrast => MeterPalette::ColorblindSafe,
+        }
+    }
+
+    /// Whether the right channel is hatched, so the channels can be told apart without their colors
+    pub fn hatches_right(self) -> bool {
+        self == MeterPalette::ColorblindSafe
+    }
+
+    /// The colors of the safe, the warning and the danger zone
+    fn colors(self) -> [Color; 3] {
+        match self {
+            MeterPalette::Standard => [
+                Color::rgb(0x4C, 0xC9, 0x5B),
+                Color::rgb(0xE8, 0xC5, 0x47),
+                Color::rgb(0xFF, 0x3B, 0x3B),
+            ],
+            MeterPalette::ColorblindSafe => [
+                Color::rgb(0x56, 0xB4, 0xE9),
+                Color::rgb(0xF0, 0xE4, 0x42),
+                Color::rgb(0xD5, 0x5E, 0x00),
+            ],
+        }
+    }
+}
+
+/// The dB scale of a [PeakMeter]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeterScale {
+    /// The level at the bottom of the meter, anything quieter is shown as silence
+    pub floor_db: f32,
+    /// The level at the top of the meter
+    pub ceiling_db: f32,
+    /// The distance between two tick marks, counted down from the ceiling
+    pub tick_db: f32,
+    /// Where the bar turns from green to yellow
+    pub warning_db: f32,
+    /// Where the bar turns from yellow to red
+    pub danger_db: f32,
+    pub palette: MeterPalette,
+}
+
+impl Default for MeterScale {
+    fn default() -> Self {
+        Self {
+            floor_db: -60.,
+            ceiling_db: 0.,
+            tick_db: 12.,
+            warning_db: -18.,
+            danger_db: -6.,
+            palette: MeterPalette::Standard,
+        }
+    }
+}
+
+impl MeterScale {
+    /// How far up the meter a level in dB is drawn, from 0 at the floor to 1 at the ceiling
+    fn position_db(&self, db: f32) -> f32 {
+        ((db - self.floor_db) / (self.ceiling_db - self.floor_db)).clamp(0., 1.)
+    }
+
+    /// How far up the meter a gain is drawn, silence is at the bottom
+    fn position(&self, gain: f32) -> f32 {
+        if gain <= 0. {
+            return 0.;
+        }
+        self.position_db(util::gain_to_db(gain))
+    }
+
+    /// The levels of the tick marks in dB, from the ceiling down to the floor
+    fn ticks(&self) -> impl Iterator<Item = f32> {
+        let scale = *self;
+        (0..)
+            .map(move |tick| scale.ceiling_db - tick as f32 * scale.tick_db.max(1.))
+            .take_while(move |db| *db >= scale.floor_db)
+    }
+
+    /// A gain as a label, silence and everything below the floor is shown as -inf
+    fn label(&self, gain: f32) -> String {
+        if gain > util::db_to_gain(self.floor_db) {
+            format!("{:.1}", util::gain_to_db(gain))
+        } else {
+            String::from("-inf")
+        }
+    }
+
+    /// The colored zones of the bar as their lower and upper positions
+    fn zones(&self) -> [(f32, f32, Color); 3] {
+        let warning = self.position_db(self.warning_db);
+        let danger = self.position_db(self.danger_db);
+        let [safe_color, warning_color, danger_color] = self.palette.colors();
+        [
+            (0., warning, safe_color),
+            (warning, danger, warning_color),
+            (danger, 1., danger_color),
+        ]
+    }
+}
+
+impl Data for MeterLevels {
+    fn same(&self, other: &Self) -> bool {
+        self == other
+    }
+}
+
+/// A level meter in dB with tick marks next to it and the peak below it.
+///
+/// The ballistics are applied on the audio thread, this only draws the last published [MeterLevels].
+/// A hatched meter has gaps in its bar, to tell it apart from a meter next to it without relying on color.
+pub struct PeakMeter {
+    /// What the readout bar shows while the meter is hovered
+    readout: MeterReadout,
+}
 
 impl PeakMeter {
-    pub fn new<L>(cx: &mut Context, val: L) -> Handle<Self>
+    pub fn new<L>(
+        cx: &mut Context,
+        levels: L,
+        scale: MeterScale,
+        hatched: bool,
+        readout: MeterReadout,
+    ) -> Handle<Self>
     where
-        L: Lens<Target = f32> + Clone,
+        L: Lens<Target = MeterLevels> + Clone,
     {
-        Self {}.build(cx, |cx| {
-            PeakMeterBar::new(cx, val.clone());
-            Label::new(cx, val.get(cx)).overflow(Overflow::Visible);
+        Self { readout }.build(cx, |cx| {
+            HStack::new(cx, |cx| {
+                PeakMeterBar::new(cx, levels.clone(), scale, hatched);
+                // The labels of the tick marks, placed at their height next to the bar
+                VStack::new(cx, |cx| {
+                    for db in scale.ticks() {
+                        Label::new(cx, &format!("{db:.0}"))
+                            .class("meter-tick")
+                            .position_type(PositionType::SelfDirected)
+                            .top(Percentage((1. - scale.position_db(db)) * 100.));
+                    }
+                })
+                .class("meter-scale");
+            })
+            .class("meter-body");
+            Label::new(cx, levels.map(move |levels| scale.label(levels.peak)))
+                .class("max-peak")
+                .overflow(Overflow::Visible);
         })
     }
 }
@@ -28,48 +181,270 @@ impl View for PeakMeter {
     fn element(&self) -> Option<&'static str> {
         Some("peak-meter")
     }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|window_event, _| match window_event {
+            WindowEvent::MouseEnter => cx.emit(ReadoutEvent::HoverMeter(self.readout)),
+            WindowEvent::MouseLeave => cx.emit(ReadoutEvent::UnhoverMeter(self.readout)),
+            _ => (),
+        });
+    }
+}
+
+enum PeakMeterBarEvent {
+    SetLevels(MeterLevels),
 }
 
-struct PeakMeterBar<L>
-where
-    L: Lens<Target = f32> + Clone,
-{
-    val: L,
+/// The bar of a [PeakMeter]. The RMS level is filled in, the peak is drawn fainter above it and the held peak is a line.
+/// All of them are colored by the zone they reach into.
+struct PeakMeterBar {
+    levels: MeterLevels,
+    scale: MeterScale,
+    hatched: bool,
 }
 
-impl<L> PeakMeterBar<L>
-where
-    L: Lens<Target = f32> + Clone,
-{
-    pub fn new(cx: &mut Context, val: L) -> Handle<Self> {
-        Self { val }.build(cx, |cx| {})
+impl PeakMeterBar {
+    pub fn new<L>(cx: &mut Context, levels: L, scale: MeterScale, hatched: bool) -> Handle<Self>
+    where
+        L: Lens<Target = MeterLevels>,
+    {
+        Self {
+            levels: MeterLevels::default(),
+            scale,
+            hatched,
+        }
+        .build(cx, move |cx| {
+            let entity = cx.current();
+            Binding::new(cx, levels, move |cx, levels| {
+                let levels = levels.get(cx);
+                cx.emit_to(entity, PeakMeterBarEvent::SetLevels(levels));
+            });
+        })
+    }
+
+    /// Fill the bar up to a position with the colors of the zones
+    fn fill(&self, canvas: &mut Canvas, bounds: BoundingBox, position: f32, alpha: u8) {
+        let bottom = bounds.y + bounds.h;
+        for (lower, upper, color) in self.scale.zones() {
+            let top = upper.min(position);
+            if top <= lower {
+                continue;
+            }
+
+            let mut path = Path::new();
+            path.rect(
+                bounds.x,
+                bottom - top * bounds.h,
+                bounds.w,
+                (top - lower) * bounds.h,
+            );
+            canvas.fill_path(
+                &path,
+                &Paint::color(Color::rgba(color.r(), color.g(), color.b(), alpha).into()),
+            );
+        }
     }
 }
 
-impl<L> View for PeakMeterBar<L>
-where
-    L: Lens<Target = f32> + Clone,
-{
+impl View for PeakMeterBar {
     fn element(&self) -> Option<&'static str> {
         Some("peak-meter-bar")
     }
 
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|meter_event, _| match meter_event {
+            PeakMeterBarEvent::SetLevels(levels) => {
+                self.levels = *levels;
+                cx.needs_redraw();
+            }
+        });
+    }
+
     fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
-        let val = self.val.get(cx);
         let bounds = cx.bounds();
-
         if bounds.w <= f32::EPSILON || bounds.h <= f32::EPSILON {
             return;
         }
 
-        let width = bounds.w;
-        let height = bounds.h;
+        let mut background = Path::new();
+        background.rect(bounds.x, bounds.y, bounds.w, bounds.h);
+        canvas.fill_path(&background, &Paint::color(cx.background_color().into()));
+
+        // The bars grow from the bottom
+        let bottom = bounds.y + bounds.h;
+        self.fill(canvas, bounds, self.scale.position(self.levels.peak), 100);
+        self.fill(canvas, bounds, self.scale.position(self.levels.rms), 255);
+        if self.hatched {
+            // Cut gaps into the bars in the color of the background
+            let top = bottom - self.scale.position(self.levels.peak) * bounds.h;
+            let mut hatch = Path::new();
+            let mut y = bottom - HATCH_SPACING;
+            while y > top {
+                hatch.move_to(bounds.x, y);
+                hatch.line_to(bounds.x + bounds.w, y);
+                y -= HATCH_SPACING;
+            }
+            let mut hatch_paint = Paint::color(cx.background_color().into());
+            hatch_paint.set_line_width(1.);
+            canvas.stroke_path(&hatch, &hatch_paint);
+        }
+
+        let hold = self.scale.position(self.levels.hold);
+        if hold > 0. {
+            let color = self
+                .scale
+                .zones()
+                .into_iter()
+                .find(|(_, upper, _)| hold <= *upper)
+                .map_or(Color::white(), |(_, _, color)| color);
+            let mut path = Path::new();
+            path.move_to(bounds.x, bottom - hold * bounds.h);
+            path.line_to(bounds.x + bounds.w, bottom - hold * bounds.h);
+            let mut paint = Paint::color(color.into());
+            paint.set_line_width(1.);
+            canvas.stroke_path(&path, &paint);
+        }
+
+        // The tick marks reach across the bar, so the levels can be read off the bars
+        let mut ticks = Path::new();
+        for db in self.scale.ticks() {
+            let y = bottom - self.scale.position_db(db) * bounds.h;
+            ticks.move_to(bounds.x, y);
+            ticks.line_to(bounds.x + bounds.w, y);
+        }
+        let mut tick_paint = Paint::color(cx.border_color().into());
+        tick_paint.set_line_width(1.);
+        canvas.stroke_path(&ticks, &tick_paint);
+    }
+}
+
+/// Events sent by the meters to the editor
+pub enum MeterEvent {
+    /// Forget the output peaks and turn the clip LEDs off
+    ResetMaxPeaks,
+}
+
+/// A clip LED with the highest peak below it.
+///
+/// The LED lights up once the peak goes above 0 dBFS and stays on until it's clicked.
+pub struct ClipIndicator {}
 
-        let mut paint = Paint::color(Color::red().into());
+impl ClipIndicator {
+    /// Create an indicator for a lens to the highest peak as a gain
+    pub fn new<L>(cx: &mut Context, max_peak: L) -> Handle<Self>
+    where
+        L: Lens<Target = f32> + Clone,
+    {
+        Self {}.build(cx, |cx| {
+            Element::new(cx)
+                .class("clip-led")
+                .toggle_class("clipped", max_peak.clone().map(|peak| *peak > 1.));
+            Label::new(
+                cx,
+                max_peak.map(|peak| {
+                    if *peak > 0. {
+                        format!("{:.1}", util::gain_to_db(*peak))
+                    } else {
+                        String::from("-inf")
+                    }
+                }),
+            )
+            .class("max-peak");
+        })
+    }
+}
+
+impl View for ClipIndicator {
+    fn element(&self) -> Option<&'static str> {
+        Some("clip-indicator")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|window_event, event_meta| match window_event {
+            WindowEvent::MouseDown(MouseButton::Left) => {
+                cx.emit(MeterEvent::ResetMaxPeaks);
+                event_meta.consume();
+            }
+            _ => (),
+        });
+    }
+}
+
+/// The gain reduction at the bottom of the [GainReductionMeter] in dB
+const MAX_GAIN_REDUCTION_DB: f32 = 24.;
+
+enum GainReductionMeterEvent {
+    SetGainReduction(f32),
+}
+
+/// A meter showing how far the output is pulled down by the auto gain and the safety clipper.
+///
+/// The bar grows from the top, the bottom is at [MAX_GAIN_REDUCTION_DB].
+pub struct GainReductionMeter {
+    gain_reduction_db: f32,
+}
+
+impl GainReductionMeter {
+    /// Create a meter for a lens to the gain reduction as a positive amount of dB
+    pub fn new<L>(cx: &mut Context, gain_reduction_db: L) -> Handle<Self>
+    where
+        L: Lens<Target = f32>,
+    {
+        Self {
+            gain_reduction_db: 0.,
+        }
+        .build(cx, move |cx| {
+            let entity = cx.current();
+            Binding::new(cx, gain_reduction_db, move |cx, gain_reduction_db| {
+                let gain_reduction_db = gain_reduction_db.get(cx);
+                cx.emit_to(
+                    entity,
+                    GainReductionMeterEvent::SetGainReduction(gain_reduction_db),
+                );
+            });
+        })
+    }
+}
+
+impl View for GainReductionMeter {
+    fn element(&self) -> Option<&'static str> {
+        Some("gain-reduction-meter")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|meter_event, _| match meter_event {
+            GainReductionMeterEvent::SetGainReduction(gain_reduction_db) => {
+                self.gain_reduction_db = *gain_reduction_db;
+                cx.needs_redraw();
+            }
+        });
+
+        event.map(|window_event, _| match window_event {
+            WindowEvent::MouseEnter => {
+                cx.emit(ReadoutEvent::HoverMeter(MeterReadout::GainReduction))
+            }
+            WindowEvent::MouseLeave => {
+                cx.emit(ReadoutEvent::UnhoverMeter(MeterReadout::GainReduction))
+            }
+            _ => (),
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let bounds = cx.bounds();
+        if bounds.w <= f32::EPSILON || bounds.h <= f32::EPSILON {
+            return;
+        }
 
         let mut path = Path::new();
-        path.rect(bounds.x, bounds.y, width, height * val);
+        path.rect(bounds.x, bounds.y, bounds.w, bounds.h);
+        canvas.fill_path(&path, &Paint::color(cx.background_color().into()));
 
-        canvas.fill_path(&path, &paint);
+        let amount = (self.gain_reduction_db / MAX_GAIN_REDUCTION_DB).clamp(0., 1.);
+        if amount > 0. {
+            let mut path = Path::new();
+            path.rect(bounds.x, bounds.y, bounds.w, bounds.h * amount);
+            canvas.fill_path(&path, &Paint::color(cx.caret_color().into()));
+        }
     }
 }
@@ -1,249 +1,1941 @@
-use std::sync::{atomic::Ordering, Arc};
+use std::{
+    collections::VecDeque,
+    fmt::Display,
+    path::PathBuf,
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
+};
 
-use crate::{delay_engine::params::DelayMode, filters::params::SVFStereoMode, params::DelaxParams};
-use decay_visualizer::DecayVisualizer;
+use crate::{
+    audition::Audition,
+    delay_engine::{
+        params::{DelayMode, FeedbackMode, LoopInputMode, NUM_CHANNELS},
+        snap::snap_delay_ms,
+        stability::{repeats_until_silent, UNSTABLE_LOOP_GAIN},
+    },
+    editor_settings::{EditorPreferences, Theme, TooltipVerbosity},
+    filters::params::SVFStereoMode,
+    help::param_help,
+    input_analysis::{AnalysisState, InputAnalysis},
+    macros::{self, MacroTarget, NUM_MACROS},
+    midi::cc_learn::{self, CcRouter},
+    param_changes::ParamGenerations,
+    params::{other_channel_param_id, DelaxParams, Solo},
+    scenes::{Scene, NUM_SCENES},
+    settings_file::{SettingsFile, SETTINGS_EXTENSION},
+    strings::{Language, Text},
+    tasks::{Task, TaskRunner},
+    visualization::{PeakFrame, SpectrumFrame, VisualizationBus, WaveformFrame},
+    Delax,
+};
+use buffer_scrub::{BufferScrub, ScrubWaveform};
+use decay_visualizer::{DecayVisualizer, RepeatLevels};
+use momentary::MomentaryButton;
 use nih_plug::{editor::Editor, params::Param, prelude::*};
 use nih_plug_vizia::{
     assets, create_vizia_editor,
     vizia::prelude::*,
-    widgets::{ParamButton, ResizeHandle},
+    widgets::{ParamButton, RawParamEvent, ResizeHandle},
     ViziaState,
 };
+use param_list::{ParamList, ParamListEvent};
+use routing::{stage_controls, RoutingDiagram, RoutingEvent, RoutingStage};
+#[cfg(feature = "multiband")]
+use spectrum_view::SpectrumView;
+use stage_list::StageList;
+use stereo_scope::{StereoScope, StereoTrace};
 use switch::ParamSwitch;
+use tape_echo::TapeEchoPanel;
+use timeline::DelayTimeline;
+use xy_pad::{XYPad, XYPadBinding};
 
-use self::{knob::ParamKnob, meter::PeakMeter};
+use self::{
+    knob::{KnobLabel, ParamKnob, ParamKnobExt},
+    meter::{ClipIndicator, GainReductionMeter, MeterEvent, MeterPalette, MeterScale, PeakMeter},
+};
 
+mod buffer_scrub;
 mod decay_visualizer;
 mod knob;
 mod meter;
+mod momentary;
+mod param_list;
+mod routing;
+#[cfg(feature = "multiband")]
+mod spectrum_view;
+mod stage_list;
+mod stereo_scope;
 mod switch;
+mod tape_echo;
+mod timeline;
+mod value_entry;
+mod xy_pad;
+
+/// The amount of waveform frames the editor keeps around for drawing
+const WAVEFORM_HISTORY: usize = 512;
+/// How far the gain reduction meter falls back every tick, in dB
+const GAIN_REDUCTION_FALL_DB: f32 = 1.;
+
+#[derive(Lens)]
+struct Data {
+    params: Arc<DelaxParams>,
+    visualization: Arc<VisualizationBus>,
+    cc_router: Arc<CcRouter>,
+    input_analysis: Arc<InputAnalysis>,
+    audition: Arc<Audition>,
+    tasks: Arc<TaskRunner>,
+    /// Runs the tasks that touch the disk on the background thread
+    async_executor: AsyncExecutor<Delax>,
+    /// For the complete state of the plugin, which is saved along with the parameters
+    gui_context: Arc<dyn GuiContext>,
+    /// The running background task with its progress, empty while nothing runs
+    task_progress: String,
+    /// What happened to the last background task
+    task_status: String,
+    /// The names and files of the presets found by the last scan
+    preset_names: Vec<String>,
+    preset_paths: Vec<PathBuf>,
+    /// Whether the audition loop plays while the host is stopped, this is only stored in the audition for the audio thread
+    auditioning: bool,
+    /// The name of the audition loop
+    audition_name: String,
+    /// The IDs of all the parameters, to find the parameters of the CC mappings
+    param_ids: Vec<(String, ParamPtr)>,
+    /// The parameter that gets mapped to the next incoming CC
+    learning: Option<String>,
+    /// The sequence number of the last CC when learning started, so only a newer CC gets mapped
+    learn_sequence: Option<u16>,
+    /// The parameters controlled by the XY pad
+    xy_pad_binding: XYPadBinding,
+    /// The parameters under the cursor
+    hovered_params: Vec<ParamPtr>,
+    /// The parameters that are being dragged, these are shown over the hovered parameters
+    dragged_params: Vec<ParamPtr>,
+    /// The meter under the cursor, this is shown when there's no parameter to show
+    hovered_meter: Option<MeterReadout>,
+    /// The names and values of the shown parameters or meter, for the readout bar at the bottom
+    readout: String,
+    /// The latest meter levels from the audio thread
+    peaks: PeakFrame,
+    /// The latest waveform frames from the audio thread, the newest one is at the back
+    waveform: VecDeque<WaveformFrame>,
+    /// The latest spectrum from the audio thread
+    spectrum: SpectrumFrame,
+    /// The measured levels of the last repeats
+    repeat_levels: RepeatLevels,
+    /// The latest output points and the phase correlation, for the stereo scope
+    stereo_trace: StereoTrace,
+    /// Whether the loop region plays with the input ignored, which freezes the delay lines
+    frozen: bool,
+    /// The frozen delay lines, taken from the audio thread when they were frozen
+    buffer_waveform: ScrubWaveform,
+    /// The change counts of the parameters the views draw, they only look at the parameters again when these change
+    param_generations: ParamGenerations,
+    /// The highest output peaks since they were last reset, for the clip LEDs
+    output_max_peaks: [f32; 2],
+    /// The shown gain reduction of the wet signal in dB, this falls back slowly
+    gain_reduction_db: f32,
+    /// The shown gain reduction of the safety clipper in dB, this falls back slowly like the GR meter
+    safety_clip_db: f32,
+    /// The least time the aux send and return add to the repeats in ms, zero if the return isn't used
+    return_latency_ms: f32,
+    /// The estimated gain of the loudest pass through the feedback loop
+    loop_gain: f32,
+    /// The state of the input analysis, with its progress from 0 to 1 while it's measuring
+    analysis_state: AnalysisState,
+    analysis_progress: f32,
+    /// The input trim in dB the last analysis suggests, None if the input was silent
+    suggested_trim_db: Option<f32>,
+    /// The amount of parameters assigned to every macro knob
+    macro_assignments: [usize; NUM_MACROS],
+    /// The scene the discrete parameters were last set to, starting at 0
+    scene: usize,
+    /// Which of the scenes have been stored
+    stored_scenes: [bool; NUM_SCENES],
+    /// The stage of the routing diagram whose controls are shown
+    selected_stage: RoutingStage,
+    /// Whether the advanced section is expanded
+    advanced_open: bool,
+    /// The theme, the meters, the tooltips and the knob settings, this is a copy of the preferences stored in the params
+    preferences: EditorPreferences,
+    /// Whether the settings popover is open
+    settings_open: bool,
+    /// Whether the hints of the help overlay are shown, and which of them were dismissed
+    help: HelpOverlay,
+    /// Whether the list of all parameters is shown instead of the main page
+    param_list_open: bool,
+    /// The text the parameter list is filtered by
+    param_filter: String,
+    /// The solo of every channel, these are only stored in the params for the audio thread
+    solos: [Solo; NUM_CHANNELS],
+}
+
+/// Which controls of the delay the main page shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DelayView {
+    Knobs,
+    TapeEcho,
+    String,
+}
+
+/// The string mode takes precedence over the tape echo, like it does in the DSP. The algorithm can force the tape echo on.
+fn delay_view(params: &DelaxParams) -> DelayView {
+    if params.delay_params.string.enabled.value() {
+        DelayView::String
+    } else if params
+        .topology()
+        .tape_echo(params.delay_params.tape_echo.enabled.value())
+    {
+        DelayView::TapeEcho
+    } else {
+        DelayView::Knobs
+    }
+}
+
+/// The state of the help overlay, which shows a short hint next to every control
+#[derive(Debug, Clone, Default, PartialEq)]
+struct HelpOverlay {
+    open: bool,
+    /// The parameters whose hints were clicked away, they come back when the overlay is opened again
+    dismissed: Vec<ParamPtr>,
+}
+
+impl HelpOverlay {
+    /// Whether the hint of a parameter is shown
+    fn shows(&self, param_ptr: ParamPtr) -> bool {
+        self.open && !self.dismissed.contains(&param_ptr)
+    }
+}
+
+/// Whether the feedback is set by the decay time instead of the feedback knobs
+fn decay_feedback(params: &Arc<DelaxParams>) -> bool {
+    params.delay_params.feedback_mode.value() == FeedbackMode::DecayTime
+}
+
+/// Events that change the state of the editor itself
+pub enum EditorEvent {
+    /// Let the XY pad control the next pair of parameters
+    CycleXYPadBinding,
+    /// Expand or collapse the advanced section
+    ToggleAdvanced,
+    /// Switch between the main page and the list of all parameters
+    ToggleParamList,
+    /// Solo the wet or dry signal of a channel, or turn the solo off again if it's already active
+    ToggleSolo(usize, Solo),
+    /// Measure the input level for a trim suggestion
+    AnalyzeInput,
+    /// Set the input trim to the suggestion of the last analysis
+    ApplySuggestedTrim,
+    /// Forget the suggestion of the last analysis without applying it
+    DismissSuggestedTrim,
+    /// Open or close the settings popover
+    ToggleSettings,
+    CloseSettings,
+    /// Show or hide the hints of the help overlay
+    ToggleHelp,
+    /// Hide the hint of a parameter until the help overlay is opened again
+    DismissHint(ParamPtr),
+    /// Change the preferences of the editor
+    CycleLanguage,
+    CycleTheme,
+    ToggleMeters,
+    CycleTooltips,
+    /// Change how the knobs react to dragging and scrolling
+    CycleDragSensitivity,
+    CycleScrollStep,
+    CycleScrollDirection,
+    /// Pick a file and write all parameters to it
+    ExportSettings,
+    /// Pick a file and load the parameters from it
+    ImportSettings,
+    /// Pick a folder and list the settings files in it as presets
+    ScanPresets,
+    /// Load the preset with an index in the list of the last scan
+    LoadPreset(usize),
+    /// Pick a file and write the complete state of the plugin to it
+    SaveState,
+    /// Pick a file and render the impulse response of the current settings to it
+    ExportImpulse,
+    /// Play the audition loop while the host is stopped, or stop it again
+    ToggleAudition,
+    /// Pick a WAV file as the audition loop
+    LoadAudition,
+    /// Go back to the built-in audition loop
+    BuiltinAudition,
+    /// Sent regularly by a timer, for everything that needs to be polled
+    Tick,
+}
+
+/// Events sent by the parameter widgets and the meters, so the readout bar can show what's currently used.
+///
+/// A widget controlling several parameters sends the events for all of them, and the readout shows them side by side.
+pub enum ReadoutEvent {
+    Hover(ParamPtr),
+    Unhover(ParamPtr),
+    BeginDrag(ParamPtr),
+    EndDrag(ParamPtr),
+    HoverMeter(MeterReadout),
+    UnhoverMeter(MeterReadout),
+}
+
+/// The meters the readout bar can show the level of
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeterReadout {
+    /// The input level of a channel
+    Input(usize),
+    /// The gain reduction of the wet signal
+    GainReduction,
+}
+
+/// Events from the right click menu of the parameters that need to know about other parameters
+pub enum ParamMenuEvent {
+    /// Set the same parameter on the other channel to the value of this one
+    CopyToOtherChannel(ParamPtr),
+}
+
+/// Events for the MIDI CC mappings of parameters
+pub enum MidiLearnEvent {
+    /// Map the next incoming CC to the parameter
+    Learn(ParamPtr),
+    /// Remove the mapping of the parameter
+    Forget(ParamPtr),
+    /// Switch the mapping of the parameter to the next curve
+    CycleCurve(ParamPtr),
+    /// Swap the range of the mapping of the parameter
+    InvertRange(ParamPtr),
+}
+
+/// Events for storing the scenes
+pub enum SceneEvent {
+    /// Store the current parameter values in a scene
+    Store(usize),
+}
 
-pub struct InputData {
-    pub in_l: AtomicF32,
-    pub in_r: AtomicF32,
-    pub out_l: AtomicF32,
-    pub out_r: AtomicF32,
+/// Events for the assignments of parameters to the macro knobs
+pub enum MacroEvent {
+    /// Let the parameter follow a macro, both ends start at its current value
+    Assign(usize, ParamPtr),
+    /// Store the current value of the parameter as the value at the bottom of its macro
+    SetA(ParamPtr),
+    /// Store the current value of the parameter as the value at the top of its macro
+    SetB(ParamPtr),
+    /// Stop the parameter from following its macro
+    Remove(ParamPtr),
 }
 
-impl Default for InputData {
-    fn default() -> Self {
-        Self {
-            in_l: AtomicF32::new(0.),
-            in_r: AtomicF32::new(0.),
-            out_l: AtomicF32::new(0.),
-            out_r: AtomicF32::new(0.),
+impl nih_plug_vizia::vizia::prelude::Data for ParamGenerations {
+    fn same(&self, other: &Self) -> bool {
+        self == other
+    }
+}
+
+impl nih_plug_vizia::vizia::prelude::Data for Theme {
+    fn same(&self, other: &Self) -> bool {
+        self == other
+    }
+}
+
+impl nih_plug_vizia::vizia::prelude::Data for Language {
+    fn same(&self, other: &Self) -> bool {
+        self == other
+    }
+}
+
+impl Data {
+    fn param_id(&self, param_ptr: ParamPtr) -> Option<&str> {
+        self.param_ids
+            .iter()
+            .find(|(_, ptr)| *ptr == param_ptr)
+            .map(|(id, _)| id.as_str())
+    }
+
+    fn param_ptr(&self, param_id: &str) -> Option<ParamPtr> {
+        self.param_ids
+            .iter()
+            .find(|(id, _)| id == param_id)
+            .map(|(_, ptr)| *ptr)
+    }
+
+    /// Change the mapping of a parameter, if it has one
+    fn update_mapping(&self, param_ptr: ParamPtr, f: impl FnOnce(&mut cc_learn::CcMapping)) {
+        let Some(param_id) = self.param_id(param_ptr) else {
+            return;
+        };
+        let mut mappings = self.params.cc_mappings.write().unwrap();
+        if let Some(mapping) = mappings
+            .iter_mut()
+            .find(|mapping| mapping.param_id == param_id)
+        {
+            f(mapping);
+        }
+    }
+
+    /// Change the macro assignment of a parameter, if it has one
+    fn update_macro_target(&mut self, param_ptr: ParamPtr, f: impl FnOnce(&mut MacroTarget)) {
+        let Some(param_id) = self.param_id(param_ptr) else {
+            return;
+        };
+        let mut targets = self.params.macro_targets.write().unwrap();
+        if let Some(target) = targets
+            .iter_mut()
+            .find(|target| target.param_id == param_id)
+        {
+            f(target);
+        }
+    }
+
+    /// Change the preferences and store them with the editor state
+    fn update_preferences(&mut self, f: impl FnOnce(&mut EditorPreferences)) {
+        f(&mut self.preferences);
+        *self.params.editor_preferences.write().unwrap() = self.preferences;
+    }
+
+    /// Count the parameters assigned to every macro knob
+    fn update_macro_assignments(&mut self) {
+        self.macro_assignments = macro_assignments(&self.params);
+    }
+
+    /// Set the discrete parameters when the active scene changes, the audio thread fades the continuous ones
+    fn follow_scene(&mut self, cx: &mut EventContext) {
+        let scene = self.params.scene_index();
+        if scene == self.scene {
+            return;
+        }
+        self.scene = scene;
+
+        let scenes = self.params.scenes.read().unwrap();
+        let Some(scene) = scenes.get(scene) else {
+            return;
+        };
+        for (param_ptr, value) in scene.discrete_values(&self.param_ids) {
+            cx.emit(RawParamEvent::BeginSetParameter(param_ptr));
+            cx.emit(RawParamEvent::SetParameterNormalized(param_ptr, value));
+            cx.emit(RawParamEvent::EndSetParameter(param_ptr));
+        }
+    }
+
+    /// Show the name and value of the dragged or hovered parameters in the readout bar, as much as the tooltip
+    /// verbosity asks for. Without a parameter the level of the hovered meter is shown.
+    fn update_readout(&mut self) {
+        let readout = match self.preferences.tooltips {
+            TooltipVerbosity::Off => String::new(),
+            TooltipVerbosity::Short | TooltipVerbosity::Full => {
+                let shown = if self.dragged_params.is_empty() {
+                    &self.hovered_params
+                } else {
+                    &self.dragged_params
+                };
+                match (shown.is_empty(), self.hovered_meter) {
+                    (true, Some(meter)) => self.meter_readout(meter),
+                    _ => shown
+                        .iter()
+                        .map(|param_ptr| self.param_readout(*param_ptr))
+                        .collect::<Vec<_>>()
+                        .join("  ·  "),
+                }
+            }
+        };
+
+        // Only touch the lens if something changed, so the label isn't rebuilt on every tick
+        if readout != self.readout {
+            self.readout = readout;
+        }
+    }
+
+    /// The name and value of a parameter for the readout bar
+    fn param_readout(&self, param_ptr: ParamPtr) -> String {
+        // SAFETY: The parameters are owned by `self.params`, so they live as long as the pointers
+        unsafe {
+            let readout = format!(
+                "{}: {}",
+                param_ptr.name(),
+                param_ptr
+                    .normalized_value_to_string(param_ptr.unmodulated_normalized_value(), true)
+            );
+            if self.preferences.tooltips == TooltipVerbosity::Full {
+                let default = param_ptr
+                    .normalized_value_to_string(param_ptr.default_normalized_value(), true);
+                let default = Text::DefaultValue.format(self.preferences.language, &[&default]);
+                format!("{readout} ({default})")
+            } else {
+                readout
+            }
+        }
+    }
+
+    /// The name and current level of a meter for the readout bar
+    fn meter_readout(&self, meter: MeterReadout) -> String {
+        let language = self.preferences.language;
+        match meter {
+            MeterReadout::Input(channel) => {
+                let peak = self.peaks.input[channel].peak;
+                let level = if peak > 0. {
+                    format!("{:.1} dB", util::gain_to_db(peak))
+                } else {
+                    String::from("-inf dB")
+                };
+                let side = if channel == 0 { "L" } else { "R" };
+                format!("{} {side}: {level}", Text::Input.get(language))
+            }
+            MeterReadout::GainReduction => format!(
+                "{}: {:.1} dB",
+                Text::GainReduction.get(language),
+                self.gain_reduction_db
+            ),
+        }
+    }
+
+    /// Take the frames the audio thread pushed since the last tick
+    fn drain_visualization(&mut self) {
+        if let Some(peaks) = self.visualization.peaks.drain().last() {
+            self.peaks = peaks;
+            self.stereo_trace.correlation = peaks.correlation;
+        }
+        self.stereo_trace.extend(self.visualization.scope.drain());
+
+        for frame in self.visualization.waveform.drain() {
+            if self.waveform.len() == WAVEFORM_HISTORY {
+                self.waveform.pop_front();
+            }
+            self.waveform.push_back(frame);
+        }
+
+        if let Some(spectrum) = self.visualization.spectrum.drain().last() {
+            self.spectrum = spectrum;
+        }
+
+        for repeat in self.visualization.repeats.drain() {
+            self.repeat_levels.push(repeat.channel, repeat.rms);
+        }
+
+        // Nothing is written to frozen delay lines, so a single snapshot is enough to scrub through them.
+        // Without the glitch feature the delay lines are never frozen.
+        let loop_region = &self.params.delay_params.loop_region;
+        let frozen = cfg!(feature = "glitch")
+            && loop_region.enabled.value()
+            && loop_region.input.value() == LoopInputMode::Ignore;
+        if frozen != self.frozen {
+            self.frozen = frozen;
+            if frozen {
+                self.visualization.request_snapshot();
+            }
+        }
+        if let Some(snapshot) = self.visualization.take_snapshot() {
+            self.buffer_waveform = snapshot.into();
+        }
+
+        self.output_max_peaks = self.visualization.output_max_peaks();
+
+        // Hold on to the gain reduction for a bit, so short peaks stay visible
+        self.gain_reduction_db = self
+            .visualization
+            .take_gain_reduction_db()
+            .max(self.gain_reduction_db - GAIN_REDUCTION_FALL_DB);
+        self.safety_clip_db = self
+            .visualization
+            .take_safety_clip_db()
+            .max(self.safety_clip_db - GAIN_REDUCTION_FALL_DB);
+        self.return_latency_ms = self.visualization.return_latency_ms.load(Ordering::Relaxed);
+        self.loop_gain = self.visualization.loop_gain.load(Ordering::Relaxed);
+    }
+
+    /// Follow the input analysis on the audio thread
+    fn poll_input_analysis(&mut self) {
+        let state = self.input_analysis.state();
+        if state != self.analysis_state {
+            self.analysis_state = state;
+            self.suggested_trim_db = self
+                .input_analysis
+                .levels()
+                .and_then(|levels| levels.suggested_trim_db());
+        }
+
+        let progress = self.input_analysis.progress();
+        if state == AnalysisState::Measuring && progress != self.analysis_progress {
+            self.analysis_progress = progress;
+        }
+    }
+
+    /// Follow the background tasks and apply the settings they imported, the background thread can't set parameters itself
+    fn poll_tasks(&mut self, cx: &mut EventContext) {
+        let task_progress = match self.tasks.running() {
+            Some((description, progress)) if progress > 0. => {
+                format!("{description}… {:.0}%", progress * 100.)
+            }
+            Some((description, _)) => format!("{description}…"),
+            None => String::new(),
+        };
+        // Only touch the lens if something changed, so the label isn't rebuilt on every tick
+        if task_progress != self.task_progress {
+            self.task_progress = task_progress;
+        }
+
+        if let Some(settings) = self.tasks.take_imported() {
+            for (param_id, value) in &settings.values {
+                let Some(param_ptr) = self.param_ptr(param_id) else {
+                    continue;
+                };
+                cx.emit(RawParamEvent::BeginSetParameter(param_ptr));
+                cx.emit(RawParamEvent::SetParameterNormalized(param_ptr, *value));
+                cx.emit(RawParamEvent::EndSetParameter(param_ptr));
+            }
+        }
+
+        if let Some(presets) = self.tasks.take_presets() {
+            (self.preset_names, self.preset_paths) = presets
+                .into_iter()
+                .map(|preset| (preset.name, preset.path))
+                .unzip();
+        }
+
+        if let Some(status) = self.tasks.take_status() {
+            self.task_status = status;
+        }
+
+        let audition_name = self.audition.name();
+        if audition_name != self.audition_name {
+            self.audition_name = audition_name;
+        }
+    }
+
+    fn poll_midi(&mut self, cx: &mut EventContext) {
+        // Finish learning once a new CC came in
+        if let Some((sequence, channel, cc)) = self.cc_router.last_cc() {
+            if Some(sequence) != self.learn_sequence {
+                if let Some(param_id) = self.learning.take() {
+                    let mut mappings = self.params.cc_mappings.write().unwrap();
+                    cc_learn::learn(&mut mappings, &param_id, channel, cc);
+                    self.cc_router.clear_values();
+                }
+            }
+        }
+
+        let mappings = self.params.cc_mappings.read().unwrap();
+        for (index, mapping) in mappings.iter().enumerate() {
+            let Some(value) = self.cc_router.take_value(index) else {
+                continue;
+            };
+            if let Some(param_ptr) = self.param_ptr(&mapping.param_id) {
+                cx.emit(RawParamEvent::BeginSetParameter(param_ptr));
+                cx.emit(RawParamEvent::SetParameterNormalized(param_ptr, value));
+                cx.emit(RawParamEvent::EndSetParameter(param_ptr));
+            }
         }
     }
 }
 
-#[derive(Lens)]
-struct Data {
-    params: Arc<DelaxParams>,
-    input_data: Arc<InputData>,
+impl Model for Data {
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|midi_learn_event, _| match midi_learn_event {
+            MidiLearnEvent::Learn(param_ptr) => {
+                self.learning = self.param_id(*param_ptr).map(String::from);
+                self.learn_sequence = self.cc_router.last_cc().map(|(sequence, _, _)| sequence);
+            }
+            MidiLearnEvent::Forget(param_ptr) => {
+                if let Some(param_id) = self.param_id(*param_ptr) {
+                    cc_learn::forget(&mut self.params.cc_mappings.write().unwrap(), param_id);
+                    self.cc_router.clear_values();
+                }
+            }
+            MidiLearnEvent::CycleCurve(param_ptr) => {
+                self.update_mapping(*param_ptr, |mapping| mapping.curve = mapping.curve.next());
+            }
+            MidiLearnEvent::InvertRange(param_ptr) => {
+                self.update_mapping(*param_ptr, |mapping| {
+                    std::mem::swap(&mut mapping.min, &mut mapping.max)
+                });
+            }
+        });
+
+        event.map(|scene_event, _| match scene_event {
+            SceneEvent::Store(scene) => {
+                let mut scenes = self.params.scenes.write().unwrap();
+                if let Some(stored) = scenes.get_mut(*scene) {
+                    *stored = Scene::capture(&self.param_ids);
+                    self.stored_scenes[*scene] = true;
+                }
+            }
+        });
+
+        event.map(|macro_event, _| {
+            match macro_event {
+                // Only smoothed float parameters can be morphed, the menu is the same for all knobs though
+                MacroEvent::Assign(macro_index, param_ptr @ ParamPtr::FloatParam(_)) => {
+                    if let Some(param_id) = self.param_id(*param_ptr) {
+                        // SAFETY: The parameters are owned by `self.params`, so they live as long as the pointers
+                        let value = unsafe { param_ptr.unmodulated_normalized_value() };
+                        let mut targets = self.params.macro_targets.write().unwrap();
+                        macros::assign(&mut targets, *macro_index, param_id, value);
+                    }
+                }
+                MacroEvent::Assign(..) => (),
+                MacroEvent::SetA(param_ptr) => {
+                    // SAFETY: See above
+                    let value = unsafe { param_ptr.unmodulated_normalized_value() };
+                    self.update_macro_target(*param_ptr, |target| target.a = value);
+                }
+                MacroEvent::SetB(param_ptr) => {
+                    // SAFETY: See above
+                    let value = unsafe { param_ptr.unmodulated_normalized_value() };
+                    self.update_macro_target(*param_ptr, |target| target.b = value);
+                }
+                MacroEvent::Remove(param_ptr) => {
+                    if let Some(param_id) = self.param_id(*param_ptr) {
+                        macros::forget(&mut self.params.macro_targets.write().unwrap(), param_id);
+                    }
+                }
+            }
+            self.update_macro_assignments();
+        });
+
+        event.map(|param_menu_event, _| match param_menu_event {
+            ParamMenuEvent::CopyToOtherChannel(param_ptr) => {
+                let other_ptr = self
+                    .param_id(*param_ptr)
+                    .and_then(other_channel_param_id)
+                    .and_then(|other_id| self.param_ptr(&other_id));
+                if let Some(other_ptr) = other_ptr {
+                    // SAFETY: The parameters are owned by `self.params`, so they live as long as the pointers
+                    let value = unsafe { param_ptr.unmodulated_normalized_value() };
+                    cx.emit(RawParamEvent::BeginSetParameter(other_ptr));
+                    cx.emit(RawParamEvent::SetParameterNormalized(other_ptr, value));
+                    cx.emit(RawParamEvent::EndSetParameter(other_ptr));
+                }
+            }
+        });
+
+        event.map(|meter_event, _| match meter_event {
+            MeterEvent::ResetMaxPeaks => {
+                self.visualization.reset_output_max_peaks();
+                self.output_max_peaks = [0.; 2];
+            }
+        });
+
+        event.map(|param_list_event, _| match param_list_event {
+            ParamListEvent::SetFilter(filter) => self.param_filter = filter.clone(),
+        });
+
+        event.map(|routing_event, _| match routing_event {
+            RoutingEvent::Select(stage) => self.selected_stage = *stage,
+        });
+
+        event.map(|editor_event, _| match editor_event {
+            EditorEvent::CycleXYPadBinding => {
+                self.xy_pad_binding = self.xy_pad_binding.next();
+            }
+            EditorEvent::ToggleParamList => {
+                self.param_list_open = !self.param_list_open;
+            }
+            EditorEvent::ToggleSolo(channel, solo) => {
+                let solo = if self.solos[*channel] == *solo {
+                    Solo::Off
+                } else {
+                    *solo
+                };
+                self.solos[*channel] = solo;
+                self.params.set_solo(*channel, solo);
+            }
+            EditorEvent::AnalyzeInput => self.input_analysis.start(),
+            EditorEvent::ApplySuggestedTrim => {
+                if let Some(trim_db) = self.suggested_trim_db {
+                    let param = &self.params.input_trim;
+                    let param_ptr = param.as_ptr();
+                    let value = param.preview_normalized(util::db_to_gain(trim_db));
+                    cx.emit(RawParamEvent::BeginSetParameter(param_ptr));
+                    cx.emit(RawParamEvent::SetParameterNormalized(param_ptr, value));
+                    cx.emit(RawParamEvent::EndSetParameter(param_ptr));
+                }
+                self.input_analysis.dismiss();
+            }
+            EditorEvent::DismissSuggestedTrim => self.input_analysis.dismiss(),
+            EditorEvent::ToggleSettings => self.settings_open = !self.settings_open,
+            EditorEvent::CloseSettings => self.settings_open = false,
+            EditorEvent::ToggleHelp => {
+                self.help.open = !self.help.open;
+                self.help.dismissed.clear();
+            }
+            EditorEvent::DismissHint(param_ptr) => self.help.dismissed.push(*param_ptr),
+            EditorEvent::CycleLanguage => {
+                self.update_preferences(|preferences| {
+                    preferences.language = preferences.language.next()
+                });
+                self.update_readout();
+            }
+            EditorEvent::CycleTheme => {
+                self.update_preferences(|preferences| preferences.theme = preferences.theme.next())
+            }
+            EditorEvent::ToggleMeters => self.update_preferences(|preferences| {
+                preferences.show_meters = !preferences.show_meters
+            }),
+            EditorEvent::CycleTooltips => {
+                self.update_preferences(|preferences| {
+                    preferences.tooltips = preferences.tooltips.next()
+                });
+                self.update_readout();
+            }
+            EditorEvent::CycleDragSensitivity => {
+                self.update_preferences(|preferences| preferences.knobs.cycle_drag_pixels());
+            }
+            EditorEvent::CycleScrollStep => {
+                self.update_preferences(|preferences| preferences.knobs.cycle_scroll_steps());
+            }
+            EditorEvent::CycleScrollDirection => self.update_preferences(|preferences| {
+                preferences.knobs.scroll_direction = preferences.knobs.scroll_direction.next()
+            }),
+            // The dialogs block the GUI while they're open, this is where they need to run on macOS anyway
+            EditorEvent::ExportSettings => {
+                if let Some(path) = settings_dialog()
+                    .set_file_name(format!("Delax.{SETTINGS_EXTENSION}"))
+                    .save_file()
+                {
+                    let settings = SettingsFile::capture(&self.param_ids);
+                    self.async_executor
+                        .execute_background(Task::ExportSettings(path, settings));
+                }
+            }
+            EditorEvent::ImportSettings => {
+                if let Some(path) = settings_dialog().pick_file() {
+                    self.async_executor
+                        .execute_background(Task::ImportSettings(path));
+                }
+            }
+            EditorEvent::ScanPresets => {
+                if let Some(folder) = rfd::FileDialog::new().pick_folder() {
+                    self.async_executor
+                        .execute_background(Task::ScanPresets(folder));
+                }
+            }
+            EditorEvent::LoadPreset(index) => {
+                if let Some(path) = self.preset_paths.get(*index) {
+                    self.async_executor
+                        .execute_background(Task::ImportSettings(path.clone()));
+                }
+            }
+            EditorEvent::SaveState => {
+                if let Some(path) = rfd::FileDialog::new()
+                    .add_filter("Delax state", &["json"])
+                    .set_file_name("Delax state.json")
+                    .save_file()
+                {
+                    let state = Box::new(self.gui_context.get_state());
+                    self.async_executor
+                        .execute_background(Task::SaveState(path, state));
+                }
+            }
+            EditorEvent::ExportImpulse => {
+                if let Some(path) = rfd::FileDialog::new()
+                    .add_filter("WAV", &["wav"])
+                    .set_file_name("Delax IR.wav")
+                    .save_file()
+                {
+                    self.async_executor
+                        .execute_background(Task::ExportImpulse(path, self.params.clone()));
+                }
+            }
+            EditorEvent::ToggleAudition => {
+                self.auditioning = !self.auditioning;
+                self.audition.set_enabled(self.auditioning);
+            }
+            EditorEvent::LoadAudition => {
+                if let Some(path) = rfd::FileDialog::new()
+                    .add_filter("WAV", &["wav"])
+                    .pick_file()
+                {
+                    self.async_executor
+                        .execute_background(Task::LoadAudition(Some(path), self.audition.clone()));
+                }
+            }
+            EditorEvent::BuiltinAudition => self
+                .async_executor
+                .execute_background(Task::LoadAudition(None, self.audition.clone())),
+            EditorEvent::ToggleAdvanced => {
+                self.advanced_open = !self.advanced_open;
+                self.params
+                    .editor_advanced_open
+                    .store(self.advanced_open, Ordering::Relaxed);
+            }
+            EditorEvent::Tick => {
+                self.param_generations = self.params.param_changes.generations();
+                self.poll_midi(cx);
+                self.follow_scene(cx);
+                self.drain_visualization();
+                self.poll_input_analysis();
+                self.poll_tasks(cx);
+                self.update_readout();
+            }
+        });
+
+        event.map(|readout_event, _| {
+            match readout_event {
+                ReadoutEvent::Hover(param_ptr) => add_readout(&mut self.hovered_params, *param_ptr),
+                ReadoutEvent::Unhover(param_ptr) => {
+                    self.hovered_params.retain(|hovered| hovered != param_ptr)
+                }
+                ReadoutEvent::BeginDrag(param_ptr) => {
+                    add_readout(&mut self.dragged_params, *param_ptr)
+                }
+                ReadoutEvent::EndDrag(param_ptr) => {
+                    self.dragged_params.retain(|dragged| dragged != param_ptr)
+                }
+                ReadoutEvent::HoverMeter(meter) => self.hovered_meter = Some(*meter),
+                ReadoutEvent::UnhoverMeter(meter) => {
+                    if self.hovered_meter == Some(*meter) {
+                        self.hovered_meter = None;
+                    }
+                }
+            }
+            self.update_readout();
+        });
+    }
+}
+
+/// Add a parameter to the hovered or dragged ones, a parameter is only shown once
+fn add_readout(params: &mut Vec<ParamPtr>, param_ptr: ParamPtr) {
+    if !params.contains(&param_ptr) {
+        params.push(param_ptr);
+    }
+}
+
+/// Snaps the values of the delay knob of a channel while the delay snap is on and the channel isn't BPM bound
+fn delay_snap(params: &Arc<DelaxParams>, channel: usize) -> impl Fn(f32) -> f32 {
+    let params = params.clone();
+    move |normalized| {
+        let delay_params = &params.delay_params;
+        let channel_params = &delay_params.channels[channel];
+        if delay_params.snap.value() && !channel_params.bpm_bound.value() {
+            let len = &channel_params.len;
+            len.preview_normalized(snap_delay_ms(len.preview_plain(normalized)))
+        } else {
+            normalized
+        }
+    }
+}
+
+/// A file dialog for exported settings
+fn settings_dialog() -> rfd::FileDialog {
+    rfd::FileDialog::new().add_filter("Delax settings", &[SETTINGS_EXTENSION])
+}
+
+/// Which of the scenes have been stored
+fn stored_scenes(params: &DelaxParams) -> [bool; NUM_SCENES] {
+    let scenes = params.scenes.read().unwrap();
+    std::array::from_fn(|scene| scenes.get(scene).is_some_and(|scene| !scene.is_empty()))
 }
 
-impl Model for Data {}
+/// The amount of parameters assigned to every macro knob
+fn macro_assignments(params: &DelaxParams) -> [usize; NUM_MACROS] {
+    let targets = params.macro_targets.read().unwrap();
+    std::array::from_fn(|macro_index| {
+        targets
+            .iter()
+            .filter(|target| target.macro_index == macro_index)
+            .count()
+    })
+}
 
 pub(crate) fn default_state() -> Arc<ViziaState> {
-    ViziaState::new(|| (500, 275))
+    ViziaState::new(|| (620, 420))
 }
 
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn create(
     params: Arc<DelaxParams>,
     editor_state: Arc<ViziaState>,
-    input_data: Arc<InputData>,
+    visualization: Arc<VisualizationBus>,
+    cc_router: Arc<CcRouter>,
+    input_analysis: Arc<InputAnalysis>,
+    audition: Arc<Audition>,
+    tasks: Arc<TaskRunner>,
+    async_executor: AsyncExecutor<Delax>,
 ) -> Option<Box<dyn Editor>> {
     create_vizia_editor(
         editor_state,
         nih_plug_vizia::ViziaTheming::Custom,
-        move |cx, _| {
+        move |cx, gui_context| {
             assets::register_noto_sans_light(cx);
             assets::register_noto_sans_thin(cx);
             let _ = cx.add_stylesheet(include_style!("src/ui/style.css"));
 
             Data {
                 params: params.clone(),
-                input_data: input_data.clone(),
+                visualization: visualization.clone(),
+                cc_router: cc_router.clone(),
+                input_analysis: input_analysis.clone(),
+                audition: audition.clone(),
+                tasks: tasks.clone(),
+                async_executor: async_executor.clone(),
+                gui_context,
+                task_progress: String::new(),
+                task_status: String::new(),
+                preset_names: Vec::new(),
+                preset_paths: Vec::new(),
+                auditioning: audition.enabled(),
+                audition_name: audition.name(),
+                param_ids: params
+                    .param_map()
+                    .into_iter()
+                    .map(|(id, param_ptr, _)| (id, param_ptr))
+                    .collect(),
+                learning: None,
+                learn_sequence: None,
+                xy_pad_binding: XYPadBinding::CutoffRes,
+                hovered_params: Vec::new(),
+                dragged_params: Vec::new(),
+                hovered_meter: None,
+                readout: String::new(),
+                peaks: PeakFrame::default(),
+                waveform: VecDeque::with_capacity(WAVEFORM_HISTORY),
+                spectrum: SpectrumFrame::default(),
+                repeat_levels: RepeatLevels::default(),
+                stereo_trace: StereoTrace::default(),
+                frozen: false,
+                buffer_waveform: ScrubWaveform::default(),
+                param_generations: params.param_changes.generations(),
+                output_max_peaks: [0.; 2],
+                gain_reduction_db: 0.,
+                safety_clip_db: 0.,
+                return_latency_ms: 0.,
+                loop_gain: 0.,
+                analysis_state: AnalysisState::Idle,
+                analysis_progress: 0.,
+                suggested_trim_db: None,
+                macro_assignments: macro_assignments(&params),
+                scene: params.scene_index(),
+                stored_scenes: stored_scenes(&params),
+                selected_stage: RoutingStage::Delay,
+                advanced_open: params.editor_advanced_open.load(Ordering::Relaxed),
+                preferences: *params.editor_preferences.read().unwrap(),
+                settings_open: false,
+                help: HelpOverlay::default(),
+                param_list_open: false,
+                param_filter: String::new(),
+                solos: std::array::from_fn(|channel| params.solo(channel)),
             }
             .build(cx);
-            VStack::new(cx, |cx| {
-                HStack::new(cx, |cx| {
-                    // Box for the input meters
-                    VStack::new(cx, |cx| {
-                        // PeakMeter::new(
-                        //     cx,
-                        //     Data::input_data
-                        //         .map(|d| d.in_l.load(Ordering::Relaxed)),
-                        // )
-                        // .width(Pixels(50.))
-                        // .height(Pixels(200.));
-                        // Label::new(cx, Data::input_data.map(|d| d.in_l.load(Ordering::Relaxed)));
-                    })
-                    .class("meter-box");
 
-                    // Box for most of the parameter controls
-                    VStack::new(cx, |cx| {
-                        Label::new(cx, "Delax").class("centered");
+            // The audio thread can't set parameters, so the GUI regularly applies the CCs it received.
+            // This also drains the visualization frames and keeps the readout up to date while the host
+            // automates the shown parameter.
+            let timer = cx.add_timer(Duration::from_millis(20), None, |cx, action| {
+                if let TimerAction::Tick(_) = action {
+                    cx.emit(EditorEvent::Tick);
+                }
+            });
+            cx.start_timer(timer);
+            VStack::new(cx, |cx| {
+                // The main page
+                VStack::new(cx, |cx| {
+                    HStack::new(cx, |cx| {
+                        // Box for the input meters
                         HStack::new(cx, |cx| {
-                            // TODO: Toggle button
-                            Label::new(cx, "Mono").left(Stretch(1.));
-                            ParamSwitch::new(
+                            // The palette and the hatching follow the theme
+                            Binding::new(
                                 cx,
-                                Data::params,
-                                |params| &params.delay_params.stereo_delay,
-                                false,
+                                Data::preferences.map(|preferences| preferences.theme),
+                                |cx, theme| {
+                                    let palette = MeterPalette::for_theme(theme.get(cx));
+                                    for channel in 0..2 {
+                                        PeakMeter::new(
+                                            cx,
+                                            Data::peaks.map(move |peaks| peaks.input[channel]),
+                                            MeterScale {
+                                                palette,
+                                                ..MeterScale::default()
+                                            },
+                                            channel == 1 && palette.hatches_right(),
+                                            MeterReadout::Input(channel),
+                                        );
+                                    }
+                                },
                             );
-                            Label::new(cx, "Stereo").right(Stretch(1.));
                         })
-                        .col_between(Pixels(20.));
-                        // TODO: Delay visualizer
-                        // DecayVisualizer::new(cx);
+                        .class("meter-box")
+                        .display(Data::preferences.map(|preferences| preferences.show_meters));
 
-                        // All the delay knobs
-                        HStack::new(cx, |cx| {
-                            // The mono knobs
-                            ParamKnob::new(
+                        // Box for most of the parameter controls
+                        VStack::new(cx, |cx| {
+                            Label::new(cx, "Delax").class("centered");
+                            HStack::new(cx, |cx| {
+                                // TODO: Toggle button
+                                Label::new(cx, text(Text::Mono)).left(Stretch(1.));
+                                ParamSwitch::new(
+                                    cx,
+                                    Data::params,
+                                    |params| &params.delay_params.stereo_delay,
+                                    false,
+                                );
+                                Label::new(cx, text(Text::Stereo)).right(Stretch(1.));
+                            })
+                            .col_between(Pixels(20.));
+                            HStack::new(cx, |cx| {
+                                Label::new(cx, text(Text::NoteTracking)).left(Stretch(1.));
+                                ParamSwitch::new(
+                                    cx,
+                                    Data::params,
+                                    |params| &params.delay_params.note_tracking,
+                                    false,
+                                )
+                                .right(Stretch(1.));
+                            })
+                            .col_between(Pixels(20.));
+                            HStack::new(cx, |cx| {
+                                Label::new(cx, text(Text::TapeEcho)).left(Stretch(1.));
+                                ParamSwitch::new(
+                                    cx,
+                                    Data::params,
+                                    |params| &params.delay_params.tape_echo.enabled,
+                                    false,
+                                )
+                                .right(Stretch(1.));
+                            })
+                            .col_between(Pixels(20.));
+                            HStack::new(cx, |cx| {
+                                Label::new(cx, text(Text::String)).left(Stretch(1.));
+                                ParamSwitch::new(
+                                    cx,
+                                    Data::params,
+                                    |params| &params.delay_params.string.enabled,
+                                    false,
+                                )
+                                .right(Stretch(1.));
+                            })
+                            .col_between(Pixels(20.));
+                            DecayVisualizer::new(cx, Data::repeat_levels);
+                            // The timeline edits the delay times of the channels, which the tape heads and the string don't use
+                            DelayTimeline::new(
                                 cx,
                                 Data::params,
-                                |params| &params.delay_params.delay_len_l,
-                                params.delay_params.delay_len_l.default_normalized_value(),
-                                None,
-                                Data::params.map(|p| true),
-                            );
-                            ParamKnob::new(
+                                Data::param_generations,
+                                visualization.clone(),
+                            )
+                            .display(Data::params.map(|p| delay_view(p) == DelayView::Knobs));
+                            // The frozen delay lines replace nothing, they're only shown while they're frozen
+                            BufferScrub::new(
                                 cx,
                                 Data::params,
-                                |params| &params.delay_params.feedback_l,
-                                params.delay_params.feedback_l.default_normalized_value(),
-                                None,
-                                Data::params.map(|p| true),
+                                Data::param_generations,
+                                Data::buffer_waveform,
+                                visualization.clone(),
+                            )
+                            .display(Data::frozen);
+
+                            // All the delay knobs
+                            HStack::new(cx, |cx| {
+                                // The mono knobs
+                                ParamKnob::new(
+                                    cx,
+                                    Data::params,
+                                    |params| &params.delay_params.channels[0].len,
+                                    params.delay_params.channels[0]
+                                        .len
+                                        .default_normalized_value(),
+                                    KnobLabel::Param,
+                                    Data::params.map(|p| true),
+                                )
+                                .class("channel-left")
+                                .quantize(delay_snap(&params, 0));
+                                VStack::new(cx, |cx| {
+                                    // The decay time replaces the feedback knob while the feedback is set by it
+                                    ParamKnob::new(
+                                        cx,
+                                        Data::params,
+                                        |params| &params.delay_params.channels[0].feedback,
+                                        params.delay_params.channels[0]
+                                            .feedback
+                                            .default_normalized_value(),
+                                        KnobLabel::Param,
+                                        Data::params.map(|p| true),
+                                    )
+                                    .class("channel-left")
+                                    .display(Data::params.map(|p| !decay_feedback(p)));
+                                    ParamKnob::new(
+                                        cx,
+                                        Data::params,
+                                        |params| &params.delay_params.channels[0].decay_time,
+                                        params.delay_params.channels[0]
+                                            .decay_time
+                                            .default_normalized_value(),
+                                        KnobLabel::Param,
+                                        Data::params.map(|p| true),
+                                    )
+                                    .class("channel-left")
+                                    .display(Data::params.map(decay_feedback));
+                                    // How long the tail rings, from the loudest loop including the filters
+                                    localized(cx, |cx, language| {
+                                        Label::new(
+                                            cx,
+                                            Data::loop_gain.map(move |gain| {
+                                                match repeats_until_silent(*gain) {
+                                                    Some(repeats) => Text::RepeatsUntilSilent
+                                                        .format(language, &[&repeats]),
+                                                    None => Text::RepeatsForever
+                                                        .get(language)
+                                                        .to_string(),
+                                                }
+                                            }),
+                                        )
+                                        .class("repeat-readout");
+                                    });
+                                })
+                                .size(Auto);
+
+                                // Only show the stereo delay knobs if the whole delay is stereo
+
+                                ParamKnob::new(
+                                    cx,
+                                    Data::params,
+                                    |params| &params.delay_params.channels[1].len,
+                                    params.delay_params.channels[1]
+                                        .len
+                                        .default_normalized_value(),
+                                    KnobLabel::Text(Text::Delay),
+                                    Data::params.map(|p| {
+                                        p.delay_params.stereo_delay.value() == DelayMode::Stereo
+                                    }),
+                                )
+                                .class("channel-right")
+                                .quantize(delay_snap(&params, 1));
+                                ParamKnob::new(
+                                    cx,
+                                    Data::params,
+                                    |params| &params.delay_params.channels[1].feedback,
+                                    params.delay_params.channels[1]
+                                        .feedback
+                                        .default_normalized_value(),
+                                    KnobLabel::Text(Text::Feedback),
+                                    Data::params.map(|p| {
+                                        p.delay_params.stereo_delay.value() == DelayMode::Stereo
+                                    }),
+                                )
+                                .class("channel-right")
+                                .display(Data::params.map(|p| !decay_feedback(p)));
+                                ParamKnob::new(
+                                    cx,
+                                    Data::params,
+                                    |params| &params.delay_params.channels[1].decay_time,
+                                    params.delay_params.channels[1]
+                                        .decay_time
+                                        .default_normalized_value(),
+                                    KnobLabel::Text(Text::Decay),
+                                    Data::params.map(|p| {
+                                        p.delay_params.stereo_delay.value() == DelayMode::Stereo
+                                    }),
+                                )
+                                .class("channel-right")
+                                .display(Data::params.map(decay_feedback));
+                            })
+                            .col_between(Stretch(1.))
+                            .display(Data::params.map(|p| delay_view(p) == DelayView::Knobs));
+                            // The tape echo and the string replace the delay knobs while they're on
+                            TapeEchoPanel::new(cx, Data::params).display(
+                                Data::params.map(|p| delay_view(p) == DelayView::TapeEcho),
                             );
+                            HStack::new(cx, |cx| {
+                                ParamKnob::new(
+                                    cx,
+                                    Data::params,
+                                    |params| &params.delay_params.string.pitch,
+                                    params.delay_params.string.pitch.default_normalized_value(),
+                                    KnobLabel::Text(Text::Pitch),
+                                    Data::params.map(|p| true),
+                                );
+                                ParamKnob::new(
+                                    cx,
+                                    Data::params,
+                                    |params| &params.delay_params.string.decay,
+                                    params.delay_params.string.decay.default_normalized_value(),
+                                    KnobLabel::Text(Text::Decay),
+                                    Data::params.map(|p| true),
+                                );
+                                ParamKnob::new(
+                                    cx,
+                                    Data::params,
+                                    |params| &params.delay_params.string.brightness,
+                                    params
+                                        .delay_params
+                                        .string
+                                        .brightness
+                                        .default_normalized_value(),
+                                    KnobLabel::Text(Text::Bright),
+                                    Data::params.map(|p| true),
+                                );
+                            })
+                            .col_between(Stretch(1.))
+                            .display(Data::params.map(|p| delay_view(p) == DelayView::String));
+                            Label::new(cx, text(Text::Filter)).class("centered");
+                            HStack::new(cx, |cx| {
+                                // TODO: Toggle Button
+                                Label::new(cx, text(Text::Mono)).left(Stretch(1.));
+                                ParamSwitch::new(
+                                    cx,
+                                    Data::params,
+                                    |params| &params.filter_params.svf_stereo_mode,
+                                    false,
+                                );
+                                Label::new(cx, text(Text::Stereo)).right(Stretch(1.));
+                            })
+                            .col_between(Pixels(20.));
 
-                            // Only show the stereo delay knobs if the whole delay is stereo
+                            // All the filter knobs
+                            HStack::new(cx, |cx| {
+                                // The mono knobs
+                                ParamKnob::new(
+                                    cx,
+                                    Data::params,
+                                    |params| &params.filter_params.channels[0].cutoff,
+                                    params.filter_params.channels[0]
+                                        .cutoff
+                                        .default_normalized_value(),
+                                    KnobLabel::Text(Text::Cutoff),
+                                    Data::params.map(|p| true),
+                                )
+                                .class("channel-left");
+                                ParamKnob::new(
+                                    cx,
+                                    Data::params,
+                                    |params| &params.filter_params.channels[0].res,
+                                    params.filter_params.channels[0]
+                                        .res
+                                        .default_normalized_value(),
+                                    KnobLabel::Text(Text::Res),
+                                    Data::params.map(|p| true),
+                                )
+                                .class("channel-left");
+                                ParamKnob::new(
+                                    cx,
+                                    Data::params,
+                                    |params| &params.filter_params.channels[0].mix,
+                                    params.filter_params.channels[0]
+                                        .mix
+                                        .default_normalized_value(),
+                                    KnobLabel::Text(Text::Mix),
+                                    Data::params.map(|p| true),
+                                )
+                                .class("channel-left");
+                                ParamKnob::new(
+                                    cx,
+                                    Data::params,
+                                    |params| &params.filter_params.channels[0].keytrack,
+                                    params.filter_params.channels[0]
+                                        .keytrack
+                                        .default_normalized_value(),
+                                    KnobLabel::Text(Text::Key),
+                                    Data::params.map(|p| true),
+                                )
+                                .class("channel-left");
 
+                                // Only show the stereo filter knobs if the whole filter is stereo
+                                ParamKnob::new(
+                                    cx,
+                                    Data::params,
+                                    |params| &params.filter_params.channels[1].cutoff,
+                                    params.filter_params.channels[1]
+                                        .cutoff
+                                        .default_normalized_value(),
+                                    KnobLabel::Text(Text::Cutoff),
+                                    Data::params.map(|p| {
+                                        p.filter_params.svf_stereo_mode.value()
+                                            == SVFStereoMode::Stereo
+                                    }),
+                                )
+                                .class("channel-right");
+                                ParamKnob::new(
+                                    cx,
+                                    Data::params,
+                                    |params| &params.filter_params.channels[1].res,
+                                    params.filter_params.channels[1]
+                                        .res
+                                        .default_normalized_value(),
+                                    KnobLabel::Text(Text::Res),
+                                    Data::params.map(|p| {
+                                        p.filter_params.svf_stereo_mode.value()
+                                            == SVFStereoMode::Stereo
+                                    }),
+                                )
+                                .class("channel-right");
+                                ParamKnob::new(
+                                    cx,
+                                    Data::params,
+                                    |params| &params.filter_params.channels[1].mix,
+                                    params.filter_params.channels[1]
+                                        .mix
+                                        .default_normalized_value(),
+                                    KnobLabel::Text(Text::Mix),
+                                    Data::params.map(|p| {
+                                        p.filter_params.svf_stereo_mode.value()
+                                            == SVFStereoMode::Stereo
+                                    }),
+                                )
+                                .class("channel-right");
+                                ParamKnob::new(
+                                    cx,
+                                    Data::params,
+                                    |params| &params.filter_params.channels[1].keytrack,
+                                    params.filter_params.channels[1]
+                                        .keytrack
+                                        .default_normalized_value(),
+                                    KnobLabel::Text(Text::Key),
+                                    Data::params.map(|p| {
+                                        p.filter_params.svf_stereo_mode.value()
+                                            == SVFStereoMode::Stereo
+                                    }),
+                                )
+                                .class("channel-right");
+                            })
+                            .col_between(Stretch(1.));
+                        })
+                        .class("main-box");
+
+                        // The XY pad, the label below it switches the parameters
+                        VStack::new(cx, |cx| {
+                            Binding::new(cx, Data::xy_pad_binding, |cx, binding| {
+                                match binding.get(cx) {
+                                    XYPadBinding::CutoffRes => {
+                                        XYPad::new(
+                                            cx,
+                                            Data::params,
+                                            |params| &params.filter_params.channels[0].cutoff,
+                                            |params| &params.filter_params.channels[0].res,
+                                        );
+                                    }
+                                    XYPadBinding::DelayFeedback => {
+                                        XYPad::new(
+                                            cx,
+                                            Data::params,
+                                            |params| &params.delay_params.channels[0].len,
+                                            |params| &params.delay_params.channels[0].feedback,
+                                        );
+                                    }
+                                }
+                            });
+                            localized(cx, |cx, language| {
+                                Label::new(
+                                    cx,
+                                    Data::xy_pad_binding.map(move |binding| {
+                                        binding.text().get(language).to_string()
+                                    }),
+                                )
+                                .class("xy-pad-label")
+                                .on_press(|cx| cx.emit(EditorEvent::CycleXYPadBinding));
+                            });
+                        })
+                        .class("xy-pad-box");
+                        VStack::new(cx, |cx| {
+                            // Element::new(cx)
+                            //     .width(Pixels(50.))
+                            //     .height(Stretch(1.))
+                            //     .background_color(Color::black());
+                            // The clip LEDs of the output, clicking one resets both
+                            HStack::new(cx, |cx| {
+                                for channel in 0..2 {
+                                    ClipIndicator::new(
+                                        cx,
+                                        Data::output_max_peaks.map(move |peaks| peaks[channel]),
+                                    );
+                                }
+                            })
+                            .class("clip-box");
+                            // Wet and dry solos of every channel, for auditioning
+                            for (channel, name) in ["L", "R"].into_iter().enumerate() {
+                                HStack::new(cx, |cx| {
+                                    Label::new(cx, name).class("solo-channel");
+                                    for (solo, label) in
+                                        [(Solo::Wet, Text::Wet), (Solo::Dry, Text::Dry)]
+                                    {
+                                        Label::new(cx, text(label))
+                                            .class("solo-button")
+                                            .toggle_class(
+                                                "active",
+                                                Data::solos
+                                                    .map(move |solos| solos[channel] == solo),
+                                            )
+                                            .on_press(move |cx| {
+                                                cx.emit(EditorEvent::ToggleSolo(channel, solo))
+                                            });
+                                    }
+                                })
+                                .class("solo-box");
+                            }
+                            // The performance buttons are only on while they're held
+                            HStack::new(cx, |cx| {
+                                MomentaryButton::new(
+                                    cx,
+                                    Data::params,
+                                    |params| &params.delay_params.throw.engaged,
+                                    Text::Throw,
+                                );
+                                #[cfg(feature = "glitch")]
+                                MomentaryButton::new(
+                                    cx,
+                                    Data::params,
+                                    |params| &params.delay_params.perform.reverse,
+                                    Text::Reverse,
+                                );
+                                MomentaryButton::new(
+                                    cx,
+                                    Data::params,
+                                    |params| &params.delay_params.perform.half_speed,
+                                    Text::HalfSpeed,
+                                );
+                            })
+                            .class("perform-box");
+                            GainReductionMeter::new(cx, Data::gain_reduction_db);
+                            Label::new(cx, text(Text::GainReduction)).class("centered");
+                            // Shows when the repeats start to cancel out in mono
+                            StereoScope::new(cx, Data::stereo_trace).display(
+                                Data::preferences.map(|preferences| preferences.show_meters),
+                            );
+                            // Lights up while the safety clipper on the output is working
+                            Label::new(cx, text(Text::Safety))
+                                .class("safety-indicator")
+                                .toggle_class("engaged", Data::safety_clip_db.map(|db| *db > 0.));
+                            // Lights up while the feedback loop would build up into self oscillation
+                            Label::new(cx, text(Text::Unstable))
+                                .class("safety-indicator")
+                                .toggle_class(
+                                    "engaged",
+                                    Data::loop_gain.map(|gain| *gain > UNSTABLE_LOOP_GAIN),
+                                );
+                            // The glide only matters if the delay follows the notes
                             ParamKnob::new(
                                 cx,
                                 Data::params,
-                                |params| &params.delay_params.delay_len_r,
-                                params.delay_params.delay_len_r.default_normalized_value(),
-                                Some("Delay".to_string()),
-                                Data::params.map(|p| {
-                                    p.delay_params.stereo_delay.value() == DelayMode::Stereo
-                                }),
+                                |params| &params.delay_params.note_glide,
+                                params.delay_params.note_glide.default_normalized_value(),
+                                KnobLabel::Text(Text::Glide),
+                                Data::params.map(|p| p.delay_params.note_tracking.value()),
                             );
                             ParamKnob::new(
                                 cx,
                                 Data::params,
-                                |params| &params.delay_params.feedback_r,
-                                params.delay_params.feedback_r.default_normalized_value(),
-                                Some("Feedback".to_string()),
-                                Data::params.map(|p| {
-                                    p.delay_params.stereo_delay.value() == DelayMode::Stereo
-                                }),
-                            );
+                                |params| &params.wetness,
+                                params.wetness.default_normalized_value(),
+                                KnobLabel::Param,
+                                Data::params.map(|p| true),
+                            )
+                            .top(Stretch(1.));
+                        })
+                        .class("meter-box");
+                    })
+                    .id("main-hstack");
+                    // The advanced section, collapsed by default to keep the main view compact
+                    localized(cx, |cx, language| {
+                        Label::new(
+                            cx,
+                            Data::advanced_open.map(move |open| {
+                                let arrow = if *open { '\u{25BE}' } else { '\u{25B8}' };
+                                format!("{} {arrow}", Text::Advanced.get(language))
+                            }),
+                        )
+                        .class("drawer-toggle")
+                        .on_press(|cx| cx.emit(EditorEvent::ToggleAdvanced));
+                    });
+                    VStack::new(cx, |cx| {
+                        Label::new(cx, text(Text::Routing)).class("drawer-section");
+                        // The signal flow, with the controls of the selected stage next to it
+                        HStack::new(cx, |cx| {
+                            RoutingDiagram::new(cx, Data::params, Data::selected_stage);
+                            Binding::new(cx, Data::selected_stage, |cx, stage| {
+                                let stage = stage.get(cx);
+                                HStack::new(cx, |cx| stage_controls(cx, Data::params, stage))
+                                    .class("stage-controls");
+                            });
                         })
-                        .col_between(Stretch(1.));
-                        Label::new(cx, "Filter").class("centered");
+                        .id("routing-box");
+                        // The crossovers of the multiband mode can be dragged right on the spectrum of the output
+                        #[cfg(feature = "multiband")]
+                        SpectrumView::new(
+                            cx,
+                            Data::params,
+                            Data::spectrum.map(|spectrum| spectrum.magnitudes),
+                        )
+                        .display(Data::selected_stage.map(|stage| *stage == RoutingStage::Bands));
+                        // The stages of the feedback loop are put in order by dragging them around
                         HStack::new(cx, |cx| {
-                            // TODO: Toggle Button
-                            Label::new(cx, "Mono").left(Stretch(1.));
-                            ParamSwitch::new(
+                            Label::new(cx, text(Text::FeedbackStages)).class("drawer-section");
+                            StageList::new(cx, Data::params);
+                        })
+                        .class("stage-list-box")
+                        .display(
+                            Data::selected_stage
+                                .map(|stage| *stage == RoutingStage::FeedbackFilter),
+                        );
+                        // The external return can't be faster than the host, warn about the extra delay
+                        localized(cx, |cx, language| {
+                            Label::new(
                                 cx,
-                                Data::params,
-                                |params| &params.filter_params.svf_stereo_mode,
-                                false,
-                            );
-                            Label::new(cx, "Stereo").right(Stretch(1.));
+                                Data::return_latency_ms.map(move |ms| {
+                                    Text::ReturnLatency.format(language, &[&format!("{ms:.1}")])
+                                }),
+                            )
+                            .class("latency-warning")
+                            .display(Data::return_latency_ms.map(|ms| *ms > 0.));
+                        });
+                        // Measures the input for a few seconds and suggests a trim, which is only set once it's confirmed
+                        HStack::new(cx, |cx| {
+                            Label::new(cx, text(Text::AnalyzeInput))
+                                .class("analysis-button")
+                                .on_press(|cx| cx.emit(EditorEvent::AnalyzeInput));
+                            Label::new(cx, text(Text::Measuring))
+                                .class("analysis-status")
+                                .display(Data::analysis_state.map(|state| {
+                                    matches!(
+                                        state,
+                                        AnalysisState::Requested | AnalysisState::Measuring
+                                    )
+                                }));
+                            Element::new(cx)
+                                .class("analysis-progress")
+                                .width(
+                                    Data::analysis_progress
+                                        .map(|progress| Pixels(progress.clamp(0., 1.) * 80.)),
+                                )
+                                .display(
+                                    Data::analysis_state
+                                        .map(|state| *state == AnalysisState::Measuring),
+                                );
+                            localized(cx, |cx, language| {
+                                Label::new(
+                                    cx,
+                                    Data::suggested_trim_db.map(move |trim_db| match trim_db {
+                                        Some(trim_db) => Text::SuggestedTrim
+                                            .format(language, &[&format!("{trim_db:+.1}")]),
+                                        None => Text::InputSilent.get(language).to_string(),
+                                    }),
+                                )
+                                .class("analysis-status")
+                                .display(
+                                    Data::analysis_state.map(|state| *state == AnalysisState::Done),
+                                );
+                            });
+                            Label::new(cx, text(Text::Apply))
+                                .class("analysis-button")
+                                .on_press(|cx| cx.emit(EditorEvent::ApplySuggestedTrim))
+                                .display(Data::suggested_trim_db.map(Option::is_some));
+                            Label::new(cx, text(Text::Dismiss))
+                                .class("analysis-button")
+                                .on_press(|cx| cx.emit(EditorEvent::DismissSuggestedTrim))
+                                .display(
+                                    Data::analysis_state.map(|state| *state == AnalysisState::Done),
+                                );
                         })
-                        .col_between(Pixels(20.));
-
-                        // All the filter knobs
+                        .id("analysis-box");
+                        // The macros morph their parameters, which are assigned through the knob menus
+                        Label::new(cx, text(Text::Macros)).class("drawer-section");
                         HStack::new(cx, |cx| {
-                            // The mono knobs
-                            ParamKnob::new(
-                                cx,
-                                Data::params,
-                                |params| &params.filter_params.svf_cutoff_l,
-                                params.filter_params.svf_cutoff_l.default_normalized_value(),
-                                Some("Cutoff".to_string()),
-                                Data::params.map(|p| true),
-                            );
                             ParamKnob::new(
                                 cx,
                                 Data::params,
-                                |params| &params.filter_params.svf_res_l,
-                                params.filter_params.svf_res_l.default_normalized_value(),
-                                Some("Res".to_string()),
-                                Data::params.map(|p| true),
-                            );
-                            ParamKnob::new(
-                                cx,
-                                Data::params,
-                                |params| &params.filter_params.svf_mix_l,
-                                params.filter_params.svf_mix_l.default_normalized_value(),
-                                Some("Mix".to_string()),
-                                Data::params.map(|p| true),
+                                |params| &params.macro1,
+                                params.macro1.default_normalized_value(),
+                                KnobLabel::Param,
+                                Data::params.map(|_| true),
                             );
-
-                            // Only show the stereo filter knobs if the whole filter is stereo
                             ParamKnob::new(
                                 cx,
                                 Data::params,
-                                |params| &params.filter_params.svf_cutoff_r,
-                                params.filter_params.svf_cutoff_r.default_normalized_value(),
-                                Some("Cutoff".to_string()),
-                                Data::params.map(|p| {
-                                    p.filter_params.svf_stereo_mode.value() == SVFStereoMode::Stereo
-                                }),
+                                |params| &params.macro2,
+                                params.macro2.default_normalized_value(),
+                                KnobLabel::Param,
+                                Data::params.map(|_| true),
                             );
+                            VStack::new(cx, |cx| {
+                                localized(cx, |cx, language| {
+                                    for macro_index in 0..NUM_MACROS {
+                                        Label::new(
+                                            cx,
+                                            Data::macro_assignments.map(move |assignments| {
+                                                Text::MacroAssignments.format(
+                                                    language,
+                                                    &[
+                                                        &(macro_index + 1),
+                                                        &assignments[macro_index],
+                                                    ],
+                                                )
+                                            }),
+                                        )
+                                        .class("macro-assignments");
+                                    }
+                                });
+                            });
+                        })
+                        .id("macro-box");
+                        // Switching the scene fades to the stored snapshot, the buttons store the current values
+                        Label::new(cx, text(Text::Scenes)).class("drawer-section");
+                        HStack::new(cx, |cx| {
                             ParamKnob::new(
                                 cx,
                                 Data::params,
-                                |params| &params.filter_params.svf_res_r,
-                                params.filter_params.svf_res_r.default_normalized_value(),
-                                Some("Res".to_string()),
-                                Data::params.map(|p| {
-                                    p.filter_params.svf_stereo_mode.value() == SVFStereoMode::Stereo
-                                }),
+                                |params| &params.scene,
+                                params.scene.default_normalized_value(),
+                                KnobLabel::Param,
+                                Data::params.map(|_| true),
                             );
                             ParamKnob::new(
                                 cx,
                                 Data::params,
-                                |params| &params.filter_params.svf_mix_r,
-                                params.filter_params.svf_mix_r.default_normalized_value(),
-                                Some("Mix".to_string()),
-                                Data::params.map(|p| {
-                                    p.filter_params.svf_stereo_mode.value() == SVFStereoMode::Stereo
-                                }),
+                                |params| &params.scene_crossfade,
+                                params.scene_crossfade.default_normalized_value(),
+                                KnobLabel::Text(Text::Crossfade),
+                                Data::params.map(|_| true),
                             );
+                            for scene in 0..NUM_SCENES {
+                                Label::new(cx, text_format(Text::StoreScene, scene + 1))
+                                    .class("scene-store")
+                                    .toggle_class(
+                                        "stored",
+                                        Data::stored_scenes.map(move |stored| stored[scene]),
+                                    )
+                                    .on_press(move |cx| cx.emit(SceneEvent::Store(scene)));
+                            }
                         })
-                        .col_between(Stretch(1.));
-                    })
-                    .class("main-box");
-                    VStack::new(cx, |cx| {
-                        // Element::new(cx)
-                        //     .width(Pixels(50.))
-                        //     .height(Stretch(1.))
-                        //     .background_color(Color::black());
-                        ParamKnob::new(
-                            cx,
-                            Data::params,
-                            |params| &params.wetness,
-                            params.wetness.default_normalized_value(),
-                            None,
-                            Data::params.map(|p| true),
-                        )
-                        .top(Stretch(1.));
+                        .id("scene-box");
+                        // A dry loop to play through the plugin while the host is stopped
+                        Label::new(cx, text(Text::Audition)).class("drawer-section");
+                        HStack::new(cx, |cx| {
+                            Label::new(cx, text(Text::Play))
+                                .class("settings-file-button")
+                                .toggle_class("active", Data::auditioning)
+                                .on_press(|cx| cx.emit(EditorEvent::ToggleAudition));
+                            Label::new(cx, text(Text::LoadLoop))
+                                .class("settings-file-button")
+                                .on_press(|cx| cx.emit(EditorEvent::LoadAudition));
+                            Label::new(cx, text(Text::BuiltinLoop))
+                                .class("settings-file-button")
+                                .on_press(|cx| cx.emit(EditorEvent::BuiltinAudition));
+                            Label::new(cx, Data::audition_name).class("audition-name");
+                        })
+                        .id("audition-box");
+                        // Settings files to share a delay setup outside of the presets of the host
+                        Label::new(cx, text(Text::Settings)).class("drawer-section");
+                        HStack::new(cx, |cx| {
+                            Label::new(cx, text(Text::ExportSettings))
+                                .class("settings-file-button")
+                                .on_press(|cx| cx.emit(EditorEvent::ExportSettings));
+                            Label::new(cx, text(Text::ImportSettings))
+                                .class("settings-file-button")
+                                .on_press(|cx| cx.emit(EditorEvent::ImportSettings));
+                            Label::new(cx, text(Text::Presets))
+                                .class("settings-file-button")
+                                .on_press(|cx| cx.emit(EditorEvent::ScanPresets));
+                            Label::new(cx, text(Text::SaveState))
+                                .class("settings-file-button")
+                                .on_press(|cx| cx.emit(EditorEvent::SaveState));
+                            Label::new(cx, text(Text::ExportImpulse))
+                                .class("settings-file-button")
+                                .on_press(|cx| cx.emit(EditorEvent::ExportImpulse));
+                        })
+                        .id("settings-file-box");
+                        // The presets of the last scan, clicking one loads it
+                        Binding::new(cx, Data::preset_names, |cx, names| {
+                            let names = names.get(cx);
+                            if names.is_empty() {
+                                return;
+                            }
+                            HStack::new(cx, |cx| {
+                                for (index, name) in names.iter().enumerate() {
+                                    Label::new(cx, name).class("settings-file-button").on_press(
+                                        move |cx| cx.emit(EditorEvent::LoadPreset(index)),
+                                    );
+                                }
+                            })
+                            .id("preset-box");
+                        });
+                        // Whatever the background thread is working on, or else how the last task went
+                        Label::new(cx, Data::task_progress)
+                            .class("task-progress")
+                            .display(Data::task_progress.map(|progress| !progress.is_empty()));
+                        Label::new(cx, Data::task_status)
+                            .class("task-status")
+                            .display(Data::task_progress.map(|progress| progress.is_empty()));
                     })
-                    .class("meter-box");
+                    .id("advanced-drawer")
+                    .display(Data::advanced_open);
                 })
-                .id("main-hstack");
+                .id("main-page")
+                .display(Data::param_list_open.map(|open| !open));
+
+                // The list of all parameters, it's only built when it's opened
+                Binding::new(cx, Data::param_list_open, |cx, open| {
+                    if open.get(cx) {
+                        let param_ids = Data::param_ids.get(cx);
+                        ParamList::new(cx, Data::params, &param_ids, Data::param_filter);
+                    }
+                });
                 HStack::new(cx, |cx| {
+                    Label::new(cx, Data::readout).class("readout");
+                    Label::new(cx, "?")
+                        .class("page-toggle")
+                        .toggle_class("active", Data::help.map(|help| help.open))
+                        .on_press(|cx| cx.emit(EditorEvent::ToggleHelp));
+                    Label::new(cx, text(Text::Settings))
+                        .class("page-toggle")
+                        .toggle_class("active", Data::settings_open)
+                        .on_press(|cx| cx.emit(EditorEvent::ToggleSettings));
+                    localized(cx, |cx, language| {
+                        Label::new(
+                            cx,
+                            Data::param_list_open.map(move |open| {
+                                let page = if *open {
+                                    Text::Back
+                                } else {
+                                    Text::AllParameters
+                                };
+                                page.get(language).to_string()
+                            }),
+                        )
+                        .class("page-toggle")
+                        .on_press(|cx| cx.emit(EditorEvent::ToggleParamList));
+                    });
                     ResizeHandle::new(cx);
                 })
                 .id("resize-handle-box");
+
+                // The preferences of the editor, these are stored with the editor state
+                Popup::new(cx, Data::settings_open, true, settings_popover)
+                    .on_blur(|cx| cx.emit(EditorEvent::CloseSettings))
+                    .class("settings-popover");
             })
-            .id("main");
+            .id("main")
+            .toggle_class(
+                "light",
+                Data::preferences.map(|preferences| preferences.theme == Theme::Light),
+            )
+            .toggle_class(
+                "high-contrast",
+                Data::preferences.map(|preferences| preferences.theme == Theme::HighContrast),
+            );
         },
     )
 }
+
+/// The entries of the settings popover, clicking one switches to its next option
+fn settings_popover(cx: &mut Context) {
+    fn entry(
+        cx: &mut Context,
+        label: impl Fn(&EditorPreferences) -> String + 'static,
+        event: fn() -> EditorEvent,
+    ) {
+        Label::new(cx, Data::preferences.map(label))
+            .class("menu-entry")
+            .on_press(move |cx| cx.emit(event()));
+    }
+
+    entry(
+        cx,
+        |preferences| {
+            let language = preferences.language;
+            Text::LanguageEntry.format(language, &[&language.name()])
+        },
+        || EditorEvent::CycleLanguage,
+    );
+    entry(
+        cx,
+        |preferences| {
+            let language = preferences.language;
+            let theme = preferences.theme.text().get(language);
+            Text::ThemeEntry.format(language, &[&theme])
+        },
+        || EditorEvent::CycleTheme,
+    );
+    entry(
+        cx,
+        |preferences| {
+            let language = preferences.language;
+            let shown = if preferences.show_meters {
+                Text::On
+            } else {
+                Text::Off
+            };
+            Text::MetersEntry.format(language, &[&shown.get(language)])
+        },
+        || EditorEvent::ToggleMeters,
+    );
+    entry(
+        cx,
+        |preferences| {
+            let language = preferences.language;
+            let tooltips = preferences.tooltips.text().get(language);
+            Text::TooltipsEntry.format(language, &[&tooltips])
+        },
+        || EditorEvent::CycleTooltips,
+    );
+
+    // How the knobs react to the mouse
+    entry(
+        cx,
+        |preferences| {
+            let pixels = format!("{:.0}", preferences.knobs.drag_pixels);
+            Text::DragEntry.format(preferences.language, &[&pixels])
+        },
+        || EditorEvent::CycleDragSensitivity,
+    );
+    entry(
+        cx,
+        |preferences| {
+            let steps = format!("{:.0}", preferences.knobs.scroll_steps);
+            Text::ScrollEntry.format(preferences.language, &[&steps])
+        },
+        || EditorEvent::CycleScrollStep,
+    );
+    entry(
+        cx,
+        |preferences| {
+            let language = preferences.language;
+            let direction = preferences.knobs.scroll_direction.text().get(language);
+            Text::ScrollDirectionEntry.format(language, &[&direction])
+        },
+        || EditorEvent::CycleScrollDirection,
+    );
+}
+
+/// A text from the string table in the language of the editor, it follows when the language is switched
+fn text(text: Text) -> impl Lens<Target = String> {
+    Data::preferences.map(move |preferences| text.get(preferences.language).to_string())
+}
+
+/// Like [text()], with the `{}` of the text filled in by the argument
+fn text_format<T>(text: Text, argument: T) -> impl Lens<Target = String>
+where
+    T: Display + 'static,
+{
+    Data::preferences.map(move |preferences| text.format(preferences.language, &[&argument]))
+}
+
+/// The hint of the help overlay next to the control of a parameter, if the parameter has one. Clicking the hint
+/// dismisses it.
+fn help_hint(cx: &mut Context, param_ptr: ParamPtr) {
+    let param_id = Data::param_ids
+        .map(move |param_ids| {
+            param_ids
+                .iter()
+                .find(|(_, ptr)| *ptr == param_ptr)
+                .map(|(id, _)| id.clone())
+        })
+        .get(cx);
+    let Some(param_id) = param_id else {
+        return;
+    };
+
+    localized(cx, move |cx, language| {
+        if let Some(hint) = param_help(&param_id, language) {
+            Label::new(cx, hint)
+                .class("help-hint")
+                .display(Data::help.map(move |help| help.shows(param_ptr)))
+                .on_press(move |cx| cx.emit(EditorEvent::DismissHint(param_ptr)));
+        }
+    });
+}
+
+/// Build views again whenever the language of the editor is switched, for texts that are mixed with other
+/// state in a lens
+fn localized(cx: &mut Context, content: impl Fn(&mut Context, Language) + 'static) {
+    Binding::new(
+        cx,
+        Data::preferences.map(|preferences| preferences.language),
+        move |cx, language| {
+            let language = language.get(cx);
+            content(cx, language);
+        },
+    );
+}
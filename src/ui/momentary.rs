@@ -0,0 +1,138 @@
+use nih_plug::params::{Param, ParamPtr};
+use nih_plug_vizia::{vizia::prelude::*, widgets::param_base::ParamWidgetBase};
+
+use super::{help_hint, text, ReadoutEvent};
+use crate::strings::Text;
+
+/// A button for a boolean nih-plug parameter that's only on while the button is held down, like a footswitch.
+///
+/// The press and the release are a single gesture for the host, so they're recorded as automation.
+/// Shift clicking latches the button, it then stays on until it's clicked again.
+pub struct MomentaryButton {
+    param_base: ParamWidgetBase,
+    /// The parameter, to tell the readout bar which one is used
+    param_ptr: ParamPtr,
+    /// Whether the button is held down right now
+    pressed: bool,
+    /// Whether the button stays on after it's let go
+    latched: bool,
+}
+
+impl MomentaryButton {
+    pub fn new<L, Params, P, FMap>(
+        cx: &mut Context,
+        params: L,
+        params_to_param: FMap,
+        label: Text,
+    ) -> Handle<Self>
+    where
+        L: Lens<Target = Params> + Clone,
+        Params: 'static,
+        P: Param + 'static,
+        FMap: Fn(&Params) -> &P + Copy + 'static,
+    {
+        let param_ptr = params
+            .clone()
+            .map(move |params| params_to_param(params).as_ptr())
+            .get(cx);
+
+        Self {
+            param_base: ParamWidgetBase::new(cx, params.clone(), params_to_param),
+            param_ptr,
+            pressed: false,
+            latched: false,
+        }
+        .build(
+            cx,
+            ParamWidgetBase::build_view(params, params_to_param, move |cx, param_data| {
+                // Lights up while the parameter is on, also when the host or a footswitch turns it on
+                let engaged =
+                    param_data.make_lens(|param| param.unmodulated_normalized_value() > 0.5);
+                Label::new(cx, text(label))
+                    .class("momentary-label")
+                    .toggle_class("active", engaged)
+                    .hoverable(false);
+                help_hint(cx, param_data.param().as_ptr());
+            }),
+        )
+        .navigable(true)
+        .role(Role::Button)
+    }
+
+    fn press(&mut self, cx: &mut EventContext) {
+        if self.pressed {
+            return;
+        }
+        self.pressed = true;
+        self.param_base.begin_set_parameter(cx);
+        self.param_base.set_normalized_value(cx, 1.);
+    }
+
+    fn release(&mut self, cx: &mut EventContext) {
+        if !self.pressed {
+            return;
+        }
+        self.pressed = false;
+        // A latched button ends the gesture but stays on
+        if !self.latched {
+            self.param_base.set_normalized_value(cx, 0.);
+        }
+        self.param_base.end_set_parameter(cx);
+    }
+
+    /// Let go of a latched button
+    fn unlatch(&mut self, cx: &mut EventContext) {
+        self.latched = false;
+        self.param_base.begin_set_parameter(cx);
+        self.param_base.set_normalized_value(cx, 0.);
+        self.param_base.end_set_parameter(cx);
+    }
+}
+
+impl View for MomentaryButton {
+    fn element(&self) -> Option<&'static str> {
+        Some("momentary-button")
+    }
+
+    fn accessibility(&self, _cx: &mut AccessContext, node: &mut AccessNode) {
+        let value = self.param_base.unmodulated_normalized_value();
+
+        node.set_name(self.param_base.name());
+        node.set_value(self.param_base.normalized_value_to_string(value, true));
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|input_event, event_meta| match input_event {
+            WindowEvent::MouseDown(MouseButton::Left) => {
+                cx.focus();
+                if self.latched {
+                    self.unlatch(cx);
+                } else {
+                    cx.capture();
+                    self.latched = cx.modifiers().shift();
+                    self.press(cx);
+                }
+                event_meta.consume();
+            }
+            WindowEvent::MouseUp(MouseButton::Left) => {
+                if self.pressed {
+                    cx.release();
+                    self.release(cx);
+                    event_meta.consume();
+                }
+            }
+            // The keyboard works like the mouse, the parameter is on while the key is held
+            WindowEvent::KeyDown(Code::Space | Code::Enter, _) => {
+                self.press(cx);
+                event_meta.consume();
+            }
+            WindowEvent::KeyUp(Code::Space | Code::Enter, _) => {
+                self.release(cx);
+                event_meta.consume();
+            }
+            WindowEvent::MouseEnter => cx.emit(ReadoutEvent::Hover(self.param_ptr)),
+            WindowEvent::MouseLeave => cx.emit(ReadoutEvent::Unhover(self.param_ptr)),
+            _ => (),
+        })
+    }
+}
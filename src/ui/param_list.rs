@@ -0,0 +1,120 @@
+use std::sync::Arc;
+
+use nih_plug::prelude::{Param, ParamPtr};
+use nih_plug_vizia::{vizia::prelude::*, widgets::RawParamEvent};
+
+use super::{
+    knob::{KnobLabel, ParamKnob},
+    text,
+};
+use crate::{params::DelaxParams, strings::Text};
+
+/// Events sent by the parameter list to the editor
+pub enum ParamListEvent {
+    /// Only show the parameters whose name contains this text
+    SetFilter(String),
+}
+
+/// A generated list of every parameter with its name, value, a reset button and a knob.
+///
+/// This covers parameters that don't have their own widget yet and helps with debugging.
+/// The filter only shows the parameters whose name contains it, ignoring the case.
+pub struct ParamList {}
+
+impl ParamList {
+    pub fn new<L, LF>(
+        cx: &mut Context,
+        params: L,
+        param_ids: &[(String, ParamPtr)],
+        filter: LF,
+    ) -> Handle<Self>
+    where
+        L: Lens<Target = Arc<DelaxParams>> + Clone,
+        LF: Lens<Target = String> + Clone,
+    {
+        Self {}.build(cx, |cx| {
+            Textbox::new(cx, filter.clone())
+                .on_edit(|cx, text| cx.emit(ParamListEvent::SetFilter(text)))
+                .class("param-filter");
+
+            ScrollView::new(cx, 0., 0., false, true, |cx| {
+                for (_, param_ptr) in param_ids {
+                    let param_ptr = *param_ptr;
+                    // SAFETY: The parameters are owned by the params object, which lives as long as the editor
+                    let name = unsafe { param_ptr.name() }.to_lowercase();
+
+                    HStack::new(cx, |cx| {
+                        Self::row(cx, params.clone(), param_ptr);
+                    })
+                    .class("param-row")
+                    .display(
+                        filter
+                            .clone()
+                            .map(move |filter| name.contains(&filter.to_lowercase())),
+                    );
+                }
+            })
+            .class("param-scroll");
+        })
+    }
+
+    fn row<L>(cx: &mut Context, params: L, param_ptr: ParamPtr)
+    where
+        L: Lens<Target = Arc<DelaxParams>> + Clone,
+    {
+        // SAFETY: The parameters are owned by the params object, which lives as long as the editor
+        unsafe {
+            Label::new(cx, param_ptr.name()).class("param-row-name");
+            Label::new(
+                cx,
+                params.clone().map(move |_| {
+                    param_ptr
+                        .normalized_value_to_string(param_ptr.unmodulated_normalized_value(), true)
+                }),
+            )
+            .class("param-row-value");
+        }
+
+        Label::new(cx, text(Text::Reset))
+            .class("param-row-reset")
+            .on_press(move |cx| {
+                // SAFETY: See above
+                let default = unsafe { param_ptr.default_normalized_value() };
+                cx.emit(RawParamEvent::BeginSetParameter(param_ptr));
+                cx.emit(RawParamEvent::SetParameterNormalized(param_ptr, default));
+                cx.emit(RawParamEvent::EndSetParameter(param_ptr));
+            });
+
+        // The knob needs the concrete parameter type. This is the same trick nih-plug's generic UI uses.
+        match param_ptr {
+            ParamPtr::FloatParam(p) => Self::knob(cx, params, move |_| unsafe { &*p }),
+            ParamPtr::IntParam(p) => Self::knob(cx, params, move |_| unsafe { &*p }),
+            ParamPtr::BoolParam(p) => Self::knob(cx, params, move |_| unsafe { &*p }),
+            ParamPtr::EnumParam(p) => Self::knob(cx, params, move |_| unsafe { &*p }),
+        }
+    }
+
+    fn knob<L, P, FMap>(cx: &mut Context, params: L, params_to_param: FMap)
+    where
+        L: Lens<Target = Arc<DelaxParams>> + Clone,
+        P: Param + 'static,
+        FMap: Fn(&Arc<DelaxParams>) -> &P + Copy + 'static,
+    {
+        let default_val = params_to_param(&params.get(cx)).default_normalized_value();
+        ParamKnob::new(
+            cx,
+            params.clone(),
+            params_to_param,
+            default_val,
+            KnobLabel::Hidden,
+            params.map(|_| true),
+        )
+        .class("param-row-knob");
+    }
+}
+
+impl View for ParamList {
+    fn element(&self) -> Option<&'static str> {
+        Some("param-list")
+    }
+}
@@ -0,0 +1,370 @@
+use std::sync::Arc;
+
+use nih_plug::prelude::{BoolParam, Param};
+use nih_plug_vizia::vizia::prelude::*;
+
+use super::{
+    delay_snap,
+    knob::{KnobLabel, ParamKnob, ParamKnobExt},
+    switch::ParamSwitch,
+    text,
+};
+use crate::{delay_engine::params::FeedbackMode, params::DelaxParams, strings::Text};
+
+/// The blocks of the signal flow, in the order the signal passes them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Data)]
+pub enum RoutingStage {
+    Input,
+    InputFilter,
+    Delay,
+    Bands,
+    FeedbackFilter,
+    Mix,
+}
+
+impl RoutingStage {
+    pub const ALL: [RoutingStage; 6] = [
+        RoutingStage::Input,
+        RoutingStage::InputFilter,
+        RoutingStage::Delay,
+        RoutingStage::Bands,
+        RoutingStage::FeedbackFilter,
+        RoutingStage::Mix,
+    ];
+
+    /// The name shown in the GUI
+    pub fn text(self) -> Text {
+        match self {
+            RoutingStage::Input => Text::Input,
+            RoutingStage::InputFilter => Text::InputFilter,
+            RoutingStage::Delay => Text::Delay,
+            RoutingStage::Bands => Text::Bands,
+            RoutingStage::FeedbackFilter => Text::FeedbackFilter,
+            RoutingStage::Mix => Text::Mix,
+        }
+    }
+
+    /// Whether the stage is shown with the current algorithm. Stages the algorithm turns off are hidden.
+    pub fn shown(self, params: &DelaxParams) -> bool {
+        let topology = params.topology();
+        match self {
+            // Builds without the multiband mode don't have its stage
+            RoutingStage::Bands => cfg!(feature = "multiband") && topology.multiband != Some(false),
+            RoutingStage::FeedbackFilter => topology.feedback_filter != Some(false),
+            RoutingStage::Input
+            | RoutingStage::InputFilter
+            | RoutingStage::Delay
+            | RoutingStage::Mix => true,
+        }
+    }
+
+    /// The bypass parameter of the stage, if it can be bypassed
+    pub fn bypass(self) -> Option<fn(&Arc<DelaxParams>) -> &BoolParam> {
+        match self {
+            RoutingStage::InputFilter => Some(|params| &params.filter_params.input_filter_bypass),
+            RoutingStage::FeedbackFilter => {
+                Some(|params| &params.filter_params.feedback_filter_bypass)
+            }
+            RoutingStage::Input | RoutingStage::Delay | RoutingStage::Bands | RoutingStage::Mix => {
+                None
+            }
+        }
+    }
+}
+
+/// Events sent by the routing diagram to the editor
+pub enum RoutingEvent {
+    /// Show the controls of a stage
+    Select(RoutingStage),
+}
+
+/// A diagram of the signal flow through the plugin.
+///
+/// Every stage is a block with a bypass switch if it can be bypassed. Clicking a block selects it,
+/// which shows its controls through [stage_controls()]. The stages the algorithm turns off are left out.
+pub struct RoutingDiagram {}
+
+impl RoutingDiagram {
+    pub fn new<L, LS>(cx: &mut Context, params: L, selected: LS) -> Handle<Self>
+    where
+        L: Lens<Target = Arc<DelaxParams>> + Clone,
+        LS: Lens<Target = RoutingStage> + Clone,
+    {
+        Self {}.build(cx, |cx| {
+            for (index, stage) in RoutingStage::ALL.into_iter().enumerate() {
+                let shown = params.clone().map(move |params| stage.shown(params));
+                if index > 0 {
+                    Label::new(cx, "\u{2192}")
+                        .class("routing-arrow")
+                        .display(shown.clone());
+                }
+
+                VStack::new(cx, |cx| {
+                    Label::new(cx, text(stage.text())).class("routing-name");
+                    if let Some(bypass) = stage.bypass() {
+                        ParamSwitch::new(cx, params.clone(), bypass, false).class("routing-bypass");
+                    }
+                })
+                .class("routing-block")
+                .toggle_class(
+                    "selected",
+                    selected.clone().map(move |selected| *selected == stage),
+                )
+                .toggle_class(
+                    "bypassed",
+                    params.clone().map(move |params| {
+                        stage.bypass().is_some_and(|bypass| bypass(params).value())
+                    }),
+                )
+                .display(shown)
+                .on_press(move |cx| cx.emit(RoutingEvent::Select(stage)));
+            }
+        })
+    }
+}
+
+impl View for RoutingDiagram {
+    fn element(&self) -> Option<&'static str> {
+        Some("routing-diagram")
+    }
+}
+
+/// Build the controls of a stage of the [RoutingDiagram]
+pub fn stage_controls<L>(cx: &mut Context, params: L, stage: RoutingStage)
+where
+    L: Lens<Target = Arc<DelaxParams>> + Clone,
+{
+    fn knob<'a, L, P>(
+        cx: &'a mut Context,
+        params: L,
+        params_to_param: fn(&Arc<DelaxParams>) -> &P,
+    ) -> Handle<'a, ParamKnob>
+    where
+        L: Lens<Target = Arc<DelaxParams>> + Clone,
+        P: Param + 'static,
+    {
+        let default_val = params_to_param(&params.get(cx)).default_normalized_value();
+        ParamKnob::new(
+            cx,
+            params.clone(),
+            params_to_param,
+            default_val,
+            KnobLabel::Param,
+            params.map(|_| true),
+        )
+    }
+
+    match stage {
+        // The trim sets the input level, muting the input stops feeding the delay and the bypass mode decides whether the repeats ring out
+        // The algorithm comes first, it decides which of the other stages are used at all
+        RoutingStage::Input => {
+            knob(cx, params.clone(), |params| &params.algorithm);
+            knob(cx, params.clone(), |params| &params.input_trim);
+            knob(cx, params.clone(), |params| &params.input_mute);
+            knob(cx, params.clone(), |params| &params.bypass_mode);
+            knob(cx, params, |params| &params.bypass);
+        }
+        // The input filter follows the settings of the first channel
+        RoutingStage::InputFilter => {
+            knob(cx, params.clone(), |params| {
+                &params.filter_params.channels[0].cutoff
+            });
+            knob(cx, params, |params| &params.filter_params.channels[0].res);
+        }
+        RoutingStage::Delay => {
+            let snap = delay_snap(&params.get(cx), 0);
+            knob(cx, params.clone(), |params| {
+                &params.delay_params.channels[0].len
+            })
+            .quantize(snap);
+            knob(cx, params.clone(), |params| &params.delay_params.snap);
+            knob(cx, params.clone(), |params| {
+                &params.delay_params.feedback_mode
+            });
+            let decay_feedback = params
+                .clone()
+                .map(|params| params.delay_params.feedback_mode.value() == FeedbackMode::DecayTime);
+            knob(cx, params.clone(), |params| {
+                &params.delay_params.channels[0].feedback
+            })
+            .display(decay_feedback.clone().map(|decay| !decay));
+            knob(cx, params.clone(), |params| {
+                &params.delay_params.channels[0].decay_time
+            })
+            .display(decay_feedback);
+            knob(cx, params.clone(), |params| &params.delay_params.humanize);
+            knob(cx, params.clone(), |params| &params.delay_params.drift_rate);
+            knob(cx, params.clone(), |params| {
+                &params.delay_params.drift_depth
+            });
+            // The controls of the stages the algorithm takes over are hidden
+            let speed_free = params
+                .clone()
+                .map(|params| params.topology().read_speed.is_none());
+            knob(cx, params.clone(), |params| &params.delay_params.read_speed)
+                .display(speed_free.clone());
+            knob(cx, params.clone(), |params| {
+                &params.delay_params.read_detune
+            })
+            .display(speed_free);
+            knob(cx, params.clone(), |params| {
+                &params.delay_params.invert_feedback
+            });
+            knob(cx, params.clone(), |params| {
+                &params.delay_params.stability_trim
+            });
+            knob(cx, params.clone(), |params| &params.delay_params.ping_pong).display(
+                params
+                    .clone()
+                    .map(|params| params.topology().ping_pong.is_none()),
+            );
+            let ping_pong = params.clone().map(|params| {
+                params
+                    .topology()
+                    .ping_pong(params.delay_params.ping_pong.value())
+            });
+            knob(cx, params.clone(), |params| {
+                &params.delay_params.ping_pong_start
+            })
+            .display(ping_pong.clone());
+            knob(cx, params.clone(), |params| {
+                &params.delay_params.ping_pong_offset
+            })
+            .display(ping_pong);
+            // Builds without the glitch feature keep the loop parameters, but there's no loop to set up
+            #[cfg(feature = "glitch")]
+            {
+                knob(cx, params.clone(), |params| {
+                    &params.delay_params.loop_region.enabled
+                });
+                knob(cx, params.clone(), |params| {
+                    &params.delay_params.loop_region.start
+                });
+                knob(cx, params.clone(), |params| {
+                    &params.delay_params.loop_region.length
+                });
+                knob(cx, params.clone(), |params| {
+                    &params.delay_params.loop_region.bpm_bound
+                });
+                knob(cx, params.clone(), |params| {
+                    &params.delay_params.loop_region.input
+                });
+            }
+            knob(cx, params.clone(), |params| {
+                &params.delay_params.dual.enabled
+            });
+            knob(cx, params.clone(), |params| {
+                &params.delay_params.dual.routing
+            });
+            knob(cx, params.clone(), |params| &params.delay_params.dual.time);
+            knob(cx, params.clone(), |params| {
+                &params.delay_params.dual.feedback
+            });
+            knob(cx, params.clone(), |params| {
+                &params.delay_params.dual.balance
+            });
+            knob(cx, params.clone(), |params| &params.delay_params.throw.time);
+            knob(cx, params.clone(), |params| {
+                &params.delay_params.throw.feedback
+            });
+            knob(cx, params, |params| &params.delay_params.throw.ramp);
+        }
+        // The multiband mode replaces the main delay, every band has its own time and feedback and can be soloed or muted
+        #[cfg(feature = "multiband")]
+        RoutingStage::Bands => {
+            knob(cx, params.clone(), |params| {
+                &params.multiband_params.enabled
+            });
+            knob(cx, params.clone(), |params| &params.multiband_params.bands);
+            knob(cx, params.clone(), |params| {
+                &params.multiband_params.crossover_low
+            });
+            knob(cx, params.clone(), |params| {
+                &params.multiband_params.crossover_high
+            });
+            knob(cx, params.clone(), |params| {
+                &params.multiband_params.band[0].time
+            });
+            knob(cx, params.clone(), |params| {
+                &params.multiband_params.band[0].feedback
+            });
+            knob(cx, params.clone(), |params| {
+                &params.multiband_params.band[0].solo
+            });
+            knob(cx, params.clone(), |params| {
+                &params.multiband_params.band[0].mute
+            });
+            knob(cx, params.clone(), |params| {
+                &params.multiband_params.band[1].time
+            });
+            knob(cx, params.clone(), |params| {
+                &params.multiband_params.band[1].feedback
+            });
+            knob(cx, params.clone(), |params| {
+                &params.multiband_params.band[1].solo
+            });
+            knob(cx, params.clone(), |params| {
+                &params.multiband_params.band[1].mute
+            });
+            knob(cx, params.clone(), |params| {
+                &params.multiband_params.band[2].time
+            });
+            knob(cx, params.clone(), |params| {
+                &params.multiband_params.band[2].feedback
+            });
+            knob(cx, params.clone(), |params| {
+                &params.multiband_params.band[2].solo
+            });
+            knob(cx, params, |params| &params.multiband_params.band[2].mute);
+        }
+        #[cfg(not(feature = "multiband"))]
+        RoutingStage::Bands => (),
+        RoutingStage::FeedbackFilter => {
+            knob(cx, params.clone(), |params| {
+                &params.filter_params.channels[0].cutoff
+            });
+            knob(cx, params.clone(), |params| {
+                &params.filter_params.channels[0].res
+            });
+            knob(cx, params.clone(), |params| {
+                &params.filter_params.channels[0].mix
+            });
+            knob(cx, params.clone(), |params| {
+                &params.filter_params.gain_staging
+            });
+            knob(cx, params.clone(), |params| {
+                &params.filter_params.res_compensation
+            });
+            knob(cx, params.clone(), |params| &params.filter_params.drive);
+            knob(cx, params.clone(), |params| &params.filter_params.slope);
+            knob(cx, params.clone(), |params| {
+                &params.pipeline_params.saturation
+            });
+            // Builds without the reverb keep its parameter, but there's nothing to turn up
+            #[cfg(feature = "reverb")]
+            knob(cx, params.clone(), |params| {
+                &params.pipeline_params.reverb_mix
+            });
+            knob(cx, params, |params| &params.delay_params.external_return);
+        }
+        // The utility stages of the wet signal sit right before the mix, the safety clipper right after it.
+        // The quality goes with them, since it's the last thing to reach for.
+        RoutingStage::Mix => {
+            knob(cx, params.clone(), |params| &params.wetness);
+            knob(cx, params.clone(), |params| {
+                &params.delay_params.swap_channels
+            });
+            knob(cx, params.clone(), |params| &params.delay_params.width);
+            knob(cx, params.clone(), |params| &params.delay_params.spread);
+            knob(cx, params.clone(), |params| {
+                &params.delay_params.channels[0].invert
+            });
+            knob(cx, params.clone(), |params| {
+                &params.delay_params.channels[1].invert
+            });
+            knob(cx, params.clone(), |params| &params.auto_gain);
+            knob(cx, params.clone(), |params| &params.safety_clipper);
+            knob(cx, params, |params| &params.quality);
+        }
+    }
+}
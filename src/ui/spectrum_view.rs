@@ -0,0 +1,249 @@
+use std::sync::Arc;
+
+use nih_plug::{prelude::Param, util};
+use nih_plug_vizia::{
+    vizia::{
+        prelude::*,
+        vg::{Paint, Path},
+    },
+    widgets::param_base::ParamWidgetBase,
+};
+
+use crate::{
+    multiband::MAX_BANDS,
+    params::DelaxParams,
+    visualization::spectrum::{band_frequency, SPECTRUM_BANDS},
+};
+
+/// The level of the bottom of the spectrum in dB
+const FLOOR_DB: f32 = -60.;
+/// How close to a handle in pixels a click has to be to grab it
+const GRAB_DISTANCE: f32 = 8.;
+
+enum SpectrumViewEvent {
+    SetMagnitudes([f32; SPECTRUM_BANDS]),
+    SetCrossover(usize, f32),
+    SetBands(usize),
+}
+
+/// Shows the spectrum of the output with a handle on every crossover of the multiband mode.
+///
+/// Dragging a handle moves its crossover, double clicking it resets the crossover. The frequency axis is
+/// logarithmic like the bands of the spectrum, so a crossover sits right between the bands it splits.
+pub struct SpectrumView {
+    /// The low and the high crossover
+    crossovers: [ParamWidgetBase; MAX_BANDS - 1],
+    /// The frequencies of the crossovers in Hz
+    frequencies: [f32; MAX_BANDS - 1],
+    /// Only the crossovers between the used bands have handles
+    bands: usize,
+    magnitudes: [f32; SPECTRUM_BANDS],
+    /// The crossover that's being dragged
+    dragged: Option<usize>,
+}
+
+impl SpectrumView {
+    pub fn new<L, M>(cx: &mut Context, params: L, magnitudes: M) -> Handle<Self>
+    where
+        L: Lens<Target = Arc<DelaxParams>> + Clone,
+        M: Lens<Target = [f32; SPECTRUM_BANDS]>,
+    {
+        let crossovers = [
+            ParamWidgetBase::new(cx, params.clone(), |params| {
+                &params.multiband_params.crossover_low
+            }),
+            ParamWidgetBase::new(cx, params.clone(), |params| {
+                &params.multiband_params.crossover_high
+            }),
+        ];
+
+        Self {
+            frequencies: crossovers
+                .each_ref()
+                .map(|param| param.unmodulated_plain_value()),
+            crossovers,
+            bands: params.get(cx).multiband_params.band_count(),
+            magnitudes: [0.; SPECTRUM_BANDS],
+            dragged: None,
+        }
+        .build(cx, move |cx| {
+            // Follow the parameters, no matter if they are changed here, by the host or by another widget
+            let entity = cx.current();
+            Binding::new(cx, magnitudes, move |cx, magnitudes| {
+                let magnitudes = magnitudes.get(cx);
+                cx.emit_to(entity, SpectrumViewEvent::SetMagnitudes(magnitudes));
+            });
+            Binding::new(
+                cx,
+                ParamWidgetBase::make_lens(
+                    params.clone(),
+                    |params| &params.multiband_params.crossover_low,
+                    |param| param.unmodulated_plain_value(),
+                ),
+                move |cx, frequency| {
+                    let frequency = frequency.get(cx);
+                    cx.emit_to(entity, SpectrumViewEvent::SetCrossover(0, frequency));
+                },
+            );
+            Binding::new(
+                cx,
+                ParamWidgetBase::make_lens(
+                    params.clone(),
+                    |params| &params.multiband_params.crossover_high,
+                    |param| param.unmodulated_plain_value(),
+                ),
+                move |cx, frequency| {
+                    let frequency = frequency.get(cx);
+                    cx.emit_to(entity, SpectrumViewEvent::SetCrossover(1, frequency));
+                },
+            );
+            Binding::new(
+                cx,
+                ParamWidgetBase::make_lens(
+                    params,
+                    |params| &params.multiband_params.bands,
+                    |param| param.unmodulated_plain_value() as usize,
+                ),
+                move |cx, bands| {
+                    let bands = bands.get(cx);
+                    cx.emit_to(entity, SpectrumViewEvent::SetBands(bands));
+                },
+            );
+        })
+    }
+
+    /// The horizontal position of a frequency in Hz
+    fn x_for_frequency(bounds: BoundingBox, frequency: f32) -> f32 {
+        let (lowest, highest) = (band_frequency(0), band_frequency(SPECTRUM_BANDS - 1));
+        let position = (frequency / lowest).ln() / (highest / lowest).ln();
+        bounds.x + position.clamp(0., 1.) * bounds.w
+    }
+
+    /// The frequency in Hz at a horizontal position
+    fn frequency_for_x(bounds: BoundingBox, x: f32) -> f32 {
+        let (lowest, highest) = (band_frequency(0), band_frequency(SPECTRUM_BANDS - 1));
+        let position = ((x - bounds.x) / bounds.w).clamp(0., 1.);
+        lowest * (highest / lowest).powf(position)
+    }
+
+    /// The handle closest to a horizontal position, if it's close enough to grab
+    fn handle_at(&self, bounds: BoundingBox, x: f32) -> Option<usize> {
+        self.frequencies
+            .iter()
+            .take(self.bands - 1)
+            .map(|frequency| (Self::x_for_frequency(bounds, *frequency) - x).abs())
+            .enumerate()
+            .filter(|(_, distance)| *distance <= GRAB_DISTANCE)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(handle, _)| handle)
+    }
+
+    fn set_from_x(&self, cx: &mut EventContext, handle: usize, x: f32) {
+        let param = &self.crossovers[handle];
+        let frequency = Self::frequency_for_x(cx.bounds(), x);
+        param.set_normalized_value(cx, param.preview_normalized(frequency));
+    }
+}
+
+impl View for SpectrumView {
+    fn element(&self) -> Option<&'static str> {
+        Some("spectrum-view")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|spectrum_event, _| {
+            match spectrum_event {
+                SpectrumViewEvent::SetMagnitudes(magnitudes) => self.magnitudes = *magnitudes,
+                SpectrumViewEvent::SetCrossover(handle, frequency) => {
+                    self.frequencies[*handle] = *frequency
+                }
+                SpectrumViewEvent::SetBands(bands) => self.bands = (*bands).clamp(2, MAX_BANDS),
+            }
+            cx.needs_redraw();
+        });
+
+        event.map(|window_event, event_meta| match window_event {
+            WindowEvent::MouseDown(MouseButton::Left) => {
+                let x = cx.mouse().cursorx;
+                if let Some(handle) = self.handle_at(cx.bounds(), x) {
+                    self.dragged = Some(handle);
+                    cx.capture();
+                    cx.set_active(true);
+                    self.crossovers[handle].begin_set_parameter(cx);
+                    self.set_from_x(cx, handle, x);
+                    event_meta.consume();
+                }
+            }
+            WindowEvent::MouseMove(x, _) => {
+                if let Some(handle) = self.dragged {
+                    self.set_from_x(cx, handle, *x);
+                    event_meta.consume();
+                }
+            }
+            WindowEvent::MouseUp(MouseButton::Left) => {
+                if let Some(handle) = self.dragged.take() {
+                    self.crossovers[handle].end_set_parameter(cx);
+                    cx.release();
+                    cx.set_active(false);
+                    event_meta.consume();
+                }
+            }
+            WindowEvent::MouseDoubleClick(MouseButton::Left) => {
+                // Reset to default
+                if let Some(handle) = self.handle_at(cx.bounds(), cx.mouse().cursorx) {
+                    let param = &self.crossovers[handle];
+                    param.begin_set_parameter(cx);
+                    param.set_normalized_value(cx, param.default_normalized_value());
+                    param.end_set_parameter(cx);
+                    event_meta.consume();
+                }
+            }
+            _ => (),
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let bounds = cx.bounds();
+        if bounds.w <= f32::EPSILON || bounds.h <= f32::EPSILON {
+            return;
+        }
+
+        let mut background = Path::new();
+        background.rect(bounds.x, bounds.y, bounds.w, bounds.h);
+        canvas.fill_path(&background, &Paint::color(cx.background_color().into()));
+
+        // Every band of the spectrum is a bar centered on its frequency
+        let bar_width = bounds.w / SPECTRUM_BANDS as f32;
+        let mut bars = Path::new();
+        for (band, magnitude) in self.magnitudes.iter().enumerate() {
+            let height = (1. - util::gain_to_db(*magnitude) / FLOOR_DB).clamp(0., 1.) * bounds.h;
+            if height <= 0. {
+                continue;
+            }
+
+            let x = Self::x_for_frequency(bounds, band_frequency(band));
+            bars.rect(
+                x - bar_width * 0.4,
+                bounds.y + bounds.h - height,
+                bar_width * 0.8,
+                height,
+            );
+        }
+        canvas.fill_path(&bars, &Paint::color(cx.border_color().into()));
+
+        // The handles reach over the whole height, with a grip at the top
+        let handle_color = cx.caret_color();
+        let mut handles = Path::new();
+        let mut grips = Path::new();
+        for frequency in self.frequencies.iter().take(self.bands - 1) {
+            let x = Self::x_for_frequency(bounds, *frequency);
+            handles.move_to(x, bounds.y);
+            handles.line_to(x, bounds.y + bounds.h);
+            grips.circle(x, bounds.y + 4., 4.);
+        }
+        let mut handle_paint = Paint::color(handle_color.into());
+        handle_paint.set_line_width(2.);
+        canvas.stroke_path(&handles, &handle_paint);
+        canvas.fill_path(&grips, &Paint::color(handle_color.into()));
+    }
+}
@@ -0,0 +1,147 @@
+use std::sync::Arc;
+
+use nih_plug::params::{Param, ParamPtr};
+use nih_plug_vizia::{vizia::prelude::*, widgets::param_base::ParamWidgetBase};
+
+use super::{help_hint, text, ReadoutEvent};
+use crate::{
+    filter_pipeline::stages::{FeedbackStage, StageOrder, NUM_STAGES},
+    params::DelaxParams,
+    strings::Text,
+};
+
+/// The name of a stage in the GUI
+fn stage_text(stage: FeedbackStage) -> Text {
+    match stage {
+        FeedbackStage::Svf => Text::Svf,
+        FeedbackStage::Saturation => Text::Saturation,
+        FeedbackStage::Reverb => Text::Reverb,
+        FeedbackStage::Degrade => Text::Degrade,
+    }
+}
+
+enum StageListEvent {
+    SetOrder(StageOrder),
+}
+
+/// The stages of the feedback loop as a list, from the first stage the feedback passes at the top to the last one.
+///
+/// Dragging a stage up or down moves it in the order. Every row the stage passes sets the order parameter, so the
+/// new order can already be heard while dragging. Double clicking resets the order.
+pub struct StageList {
+    order_param: ParamWidgetBase,
+    /// The parameter, to tell the readout bar which one is used
+    param_ptr: ParamPtr,
+    /// The order of the parameter, as the rows show it
+    order: StageOrder,
+    /// The row of the stage that's being dragged
+    dragged: Option<usize>,
+}
+
+impl StageList {
+    pub fn new<L>(cx: &mut Context, params: L) -> Handle<Self>
+    where
+        L: Lens<Target = Arc<DelaxParams>> + Clone,
+    {
+        let order_param = ParamWidgetBase::new(cx, params.clone(), |params| {
+            &params.pipeline_params.stage_order
+        });
+        let param_ptr = params
+            .clone()
+            .map(|params| params.pipeline_params.stage_order.as_ptr())
+            .get(cx);
+
+        Self {
+            order: StageOrder::from_index(order_param.unmodulated_plain_value() as i32),
+            order_param,
+            param_ptr,
+            dragged: None,
+        }
+        .build(cx, move |cx| {
+            // Follow the parameter, no matter if it's changed here, by the host or by another widget
+            let entity = cx.current();
+            Binding::new(
+                cx,
+                ParamWidgetBase::make_lens(
+                    params,
+                    |params| &params.pipeline_params.stage_order,
+                    |param| param.unmodulated_plain_value(),
+                ),
+                move |cx, index| {
+                    let order = StageOrder::from_index(index.get(cx));
+                    cx.emit_to(entity, StageListEvent::SetOrder(order));
+                    for (position, stage) in order.stages().into_iter().enumerate() {
+                        let name = text(stage_text(stage))
+                            .map(move |name| format!("{}. {name}", position + 1));
+                        Label::new(cx, name).class("stage-row").hoverable(false);
+                    }
+                },
+            );
+            help_hint(cx, param_ptr);
+        })
+    }
+
+    /// The row at a vertical position, the positions above and below the list go to the first and the last row
+    fn row_at(bounds: BoundingBox, y: f32) -> usize {
+        let row = ((y - bounds.y) / bounds.h * NUM_STAGES as f32).floor();
+        row.clamp(0., (NUM_STAGES - 1) as f32) as usize
+    }
+
+    fn set_order(&mut self, cx: &mut EventContext, order: StageOrder) {
+        self.order = order;
+        let param = &self.order_param;
+        param.set_normalized_value(cx, param.preview_normalized(order.index() as f32));
+    }
+}
+
+impl View for StageList {
+    fn element(&self) -> Option<&'static str> {
+        Some("stage-list")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|stage_event, _| match stage_event {
+            StageListEvent::SetOrder(order) => self.order = *order,
+        });
+
+        event.map(|window_event, event_meta| match window_event {
+            WindowEvent::MouseDown(MouseButton::Left) => {
+                self.dragged = Some(Self::row_at(cx.bounds(), cx.mouse().cursory));
+                cx.capture();
+                cx.set_active(true);
+                cx.emit(ReadoutEvent::BeginDrag(self.param_ptr));
+                self.order_param.begin_set_parameter(cx);
+                event_meta.consume();
+            }
+            WindowEvent::MouseMove(_, y) => {
+                if let Some(from) = self.dragged {
+                    let to = Self::row_at(cx.bounds(), *y);
+                    if to != from {
+                        self.set_order(cx, self.order.moved(from, to));
+                        self.dragged = Some(to);
+                    }
+                    event_meta.consume();
+                }
+            }
+            WindowEvent::MouseUp(MouseButton::Left) => {
+                if self.dragged.take().is_some() {
+                    self.order_param.end_set_parameter(cx);
+                    cx.emit(ReadoutEvent::EndDrag(self.param_ptr));
+                    cx.release();
+                    cx.set_active(false);
+                    event_meta.consume();
+                }
+            }
+            WindowEvent::MouseDoubleClick(MouseButton::Left) => {
+                // Reset to default
+                self.order_param.begin_set_parameter(cx);
+                self.set_order(cx, StageOrder::default());
+                self.order_param.end_set_parameter(cx);
+                event_meta.consume();
+            }
+            WindowEvent::MouseEnter => cx.emit(ReadoutEvent::Hover(self.param_ptr)),
+            WindowEvent::MouseLeave => cx.emit(ReadoutEvent::Unhover(self.param_ptr)),
+            _ => (),
+        });
+    }
+}
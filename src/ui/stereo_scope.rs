@@ -0,0 +1,136 @@
+use std::f32::consts::FRAC_1_SQRT_2;
+
+use nih_plug_vizia::vizia::{
+    prelude::*,
+    vg::{Paint, Path},
+};
+
+use crate::visualization::ScopePoint;
+
+/// The amount of points the vectorscope keeps around
+const SCOPE_HISTORY: usize = 512;
+/// Below this correlation the sides start to cancel out when summed to mono
+const MONO_WARNING_CORRELATION: f32 = 0.;
+
+/// The latest output of the plugin, as the stereo scope draws it
+#[derive(Debug, Clone, Default, PartialEq, Data)]
+pub struct StereoTrace {
+    /// The newest points are at the end
+    pub points: Vec<[f32; 2]>,
+    pub correlation: f32,
+}
+
+impl StereoTrace {
+    /// Add the points the audio thread pushed since the last tick and drop the oldest ones
+    pub fn extend(&mut self, points: impl Iterator<Item = ScopePoint>) {
+        self.points.extend(points.map(|ScopePoint { l, r }| [l, r]));
+        if self.points.len() > SCOPE_HISTORY {
+            self.points.drain(..self.points.len() - SCOPE_HISTORY);
+        }
+    }
+}
+
+enum StereoScopeEvent {
+    SetTrace(StereoTrace),
+}
+
+/// A vectorscope of the output with a phase correlation meter below it.
+///
+/// Mono signals are a vertical line in the scope, the wider the stereo image the more it spreads out sideways.
+/// A trace that leans towards horizontal and a correlation below zero mean the sides cancel out when summed to mono,
+/// which cross feedback and a wide ping pong spread can cause. The correlation bar turns red then.
+pub struct StereoScope {
+    trace: StereoTrace,
+}
+
+impl StereoScope {
+    pub fn new<L>(cx: &mut Context, trace: L) -> Handle<Self>
+    where
+        L: Lens<Target = StereoTrace>,
+    {
+        Self {
+            trace: StereoTrace::default(),
+        }
+        .build(cx, move |cx| {
+            let entity = cx.current();
+            Binding::new(cx, trace, move |cx, trace| {
+                let trace = trace.get(cx);
+                cx.emit_to(entity, StereoScopeEvent::SetTrace(trace));
+            });
+        })
+    }
+}
+
+impl View for StereoScope {
+    fn element(&self) -> Option<&'static str> {
+        Some("stereo-scope")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|scope_event, _| match scope_event {
+            StereoScopeEvent::SetTrace(trace) => {
+                self.trace = trace.clone();
+                cx.needs_redraw();
+            }
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let bounds = cx.bounds();
+        if bounds.w <= f32::EPSILON || bounds.h <= f32::EPSILON {
+            return;
+        }
+
+        // The scope is a square on top, the correlation bar fills the rest
+        let bar_height = (bounds.h - bounds.w).max(4.);
+        let size = bounds.h - bar_height;
+        let center_x = bounds.x + bounds.w / 2.;
+        let center_y = bounds.y + size / 2.;
+        let line_color = cx.border_color();
+        let trace_color = cx.caret_color();
+
+        let mut background = Path::new();
+        background.rect(bounds.x, bounds.y, bounds.w, bounds.h);
+        canvas.fill_path(&background, &Paint::color(cx.background_color().into()));
+
+        // The mono axis and the axis where the sides cancel out
+        let mut axes = Path::new();
+        axes.move_to(center_x, bounds.y);
+        axes.line_to(center_x, bounds.y + size);
+        axes.move_to(bounds.x, center_y);
+        axes.line_to(bounds.x + bounds.w, center_y);
+        let mut axes_paint = Paint::color(line_color.into());
+        axes_paint.set_line_width(1.);
+        canvas.stroke_path(&axes, &axes_paint);
+
+        // Rotated by 45 degrees, so the mid points up and the side points sideways
+        let scale = size.min(bounds.w) / 2.;
+        let mut trace = Path::new();
+        for [l, r] in &self.trace.points {
+            let side = ((r - l) * FRAC_1_SQRT_2).clamp(-1., 1.);
+            let mid = ((l + r) * FRAC_1_SQRT_2).clamp(-1., 1.);
+            let x = center_x + side * scale;
+            let y = center_y - mid * scale;
+            trace.rect(x - 0.5, y - 0.5, 1., 1.);
+        }
+        canvas.fill_path(&trace, &Paint::color(trace_color.into()));
+
+        // The correlation bar grows from the center towards -1 on the left and 1 on the right
+        let correlation = self.trace.correlation.clamp(-1., 1.);
+        let bar_y = bounds.y + size;
+        let end_x = center_x + correlation * bounds.w / 2.;
+        let mut bar = Path::new();
+        bar.rect(
+            center_x.min(end_x),
+            bar_y,
+            (end_x - center_x).abs().max(1.),
+            bar_height,
+        );
+        let bar_color = if correlation < MONO_WARNING_CORRELATION {
+            Color::rgb(0xFF, 0x3B, 0x3B)
+        } else {
+            trace_color
+        };
+        canvas.fill_path(&bar, &Paint::color(bar_color.into()));
+    }
+}
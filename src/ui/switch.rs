@@ -1,4 +1,4 @@
-use nih_plug::params::Param;
+use nih_plug::params::{Param, ParamPtr};
 use nih_plug_vizia::{
     vizia::{
         prelude::*,
@@ -7,9 +7,13 @@ use nih_plug_vizia::{
     widgets::param_base::ParamWidgetBase,
 };
 
+use super::{help_hint, ReadoutEvent};
+
 /// A switch to control a boolean nih-plug parameter
 pub struct ParamSwitch {
     param_base: ParamWidgetBase,
+    /// The parameter, to tell the readout bar which one is used
+    param_ptr: ParamPtr,
 }
 
 impl ParamSwitch {
@@ -25,8 +29,14 @@ impl ParamSwitch {
         P: Param + 'static,
         FMap: Fn(&Params) -> &P + Copy + 'static,
     {
+        let param_ptr = params
+            .clone()
+            .map(move |params| params_to_param(params).as_ptr())
+            .get(cx);
+
         Self {
-            param_base: ParamWidgetBase::new(cx, params, params_to_param),
+            param_base: ParamWidgetBase::new(cx, params.clone(), params_to_param),
+            param_ptr,
         }
         .build(
             cx,
@@ -39,8 +49,12 @@ impl ParamSwitch {
                     .class("switch-visual")
                     .height(Stretch(1.))
                     .width(Stretch(1.));
+                help_hint(cx, param_data.param().as_ptr());
             }),
         )
+        // Reachable with tab, so it can be used without a mouse
+        .navigable(true)
+        .role(Role::Switch)
     }
 
     /// Toggles the value of the parameter
@@ -49,8 +63,12 @@ impl ParamSwitch {
 
         let new_val = if current > 0.5 { 0. } else { 1. };
 
+        self.set_value(cx, new_val);
+    }
+
+    fn set_value(&self, cx: &mut EventContext, value: f32) {
         self.param_base.begin_set_parameter(cx);
-        self.param_base.set_normalized_value(cx, new_val);
+        self.param_base.set_normalized_value(cx, value);
         self.param_base.end_set_parameter(cx);
     }
 }
@@ -60,11 +78,37 @@ impl View for ParamSwitch {
         Some("param-switch")
     }
 
+    fn accessibility(&self, _cx: &mut AccessContext, node: &mut AccessNode) {
+        let value = self.param_base.unmodulated_normalized_value();
+
+        node.set_name(self.param_base.name());
+        node.set_value(self.param_base.normalized_value_to_string(value, true));
+    }
+
     fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
-        event.map(|input_event, _| match input_event {
+        event.map(|input_event, event_meta| match input_event {
             WindowEvent::MouseDown(MouseButton::Left) => {
+                cx.focus();
+                self.toggle(cx);
+            }
+            WindowEvent::KeyDown(Code::Space | Code::Enter, _) => {
                 self.toggle(cx);
+                event_meta.consume();
+            }
+            WindowEvent::KeyDown(Code::ArrowRight | Code::ArrowUp, _) => {
+                self.set_value(cx, 1.);
+                event_meta.consume();
+            }
+            WindowEvent::KeyDown(Code::ArrowLeft | Code::ArrowDown, _) => {
+                self.set_value(cx, 0.);
+                event_meta.consume();
+            }
+            WindowEvent::KeyDown(Code::Home, _) => {
+                self.set_value(cx, self.param_base.default_normalized_value());
+                event_meta.consume();
             }
+            WindowEvent::MouseEnter => cx.emit(ReadoutEvent::Hover(self.param_ptr)),
+            WindowEvent::MouseLeave => cx.emit(ReadoutEvent::Unhover(self.param_ptr)),
             _ => (),
         })
     }
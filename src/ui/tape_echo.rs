@@ -0,0 +1,74 @@
+use std::sync::Arc;
+
+use nih_plug::prelude::{BoolParam, Param};
+use nih_plug_vizia::vizia::prelude::*;
+
+use super::{
+    knob::{KnobLabel, ParamKnob},
+    switch::ParamSwitch,
+};
+use crate::{delay_engine::multihead::NUM_HEADS, params::DelaxParams, strings::Text};
+
+/// The controls of the tape echo mode. It replaces the delay knobs while the mode is on,
+/// so there's only the repeat rate, the intensity and a switch for every playback head.
+pub struct TapeEchoPanel {}
+
+impl TapeEchoPanel {
+    pub fn new<L>(cx: &mut Context, params: L) -> Handle<Self>
+    where
+        L: Lens<Target = Arc<DelaxParams>> + Clone,
+    {
+        Self {}.build(cx, |cx| {
+            let default_rate = params
+                .get(cx)
+                .delay_params
+                .tape_echo
+                .repeat_rate
+                .default_normalized_value();
+            ParamKnob::new(
+                cx,
+                params.clone(),
+                |params| &params.delay_params.tape_echo.repeat_rate,
+                default_rate,
+                KnobLabel::Text(Text::Rate),
+                params.clone().map(|_| true),
+            );
+            // The feedback of the first channel, it's what the intensity knob of a tape echo does
+            let default_feedback = params.get(cx).delay_params.channels[0]
+                .feedback
+                .default_normalized_value();
+            ParamKnob::new(
+                cx,
+                params.clone(),
+                |params| &params.delay_params.channels[0].feedback,
+                default_feedback,
+                KnobLabel::Text(Text::Intensity),
+                params.clone().map(|_| true),
+            );
+
+            // A switch for every playback head, numbered by their distance from the record head
+            HStack::new(cx, |cx| {
+                let heads: [fn(&Arc<DelaxParams>) -> &BoolParam; NUM_HEADS] = [
+                    |params| &params.delay_params.tape_echo.head_1,
+                    |params| &params.delay_params.tape_echo.head_2,
+                    |params| &params.delay_params.tape_echo.head_3,
+                ];
+                for (index, head) in heads.into_iter().enumerate() {
+                    let default_val = head(&params.get(cx)).default_plain_value();
+                    VStack::new(cx, |cx| {
+                        Label::new(cx, &(index + 1).to_string()).class("tape-head-name");
+                        ParamSwitch::new(cx, params.clone(), head, default_val);
+                    })
+                    .class("tape-head");
+                }
+            })
+            .class("tape-heads");
+        })
+    }
+}
+
+impl View for TapeEchoPanel {
+    fn element(&self) -> Option<&'static str> {
+        Some("tape-echo-panel")
+    }
+}
@@ -0,0 +1,362 @@
+use std::sync::{atomic::Ordering, Arc};
+
+use nih_plug::prelude::{FloatParam, Param, ParamPtr};
+use nih_plug_vizia::{
+    vizia::{
+        prelude::*,
+        vg::{Paint, Path},
+    },
+    widgets::param_base::ParamWidgetBase,
+};
+
+use super::{
+    value_entry::{ValueEntry, ValueEntryEvent},
+    ReadoutEvent,
+};
+use crate::{
+    delay_engine::{
+        params::{DelayMode, FeedbackMode, NUM_CHANNELS},
+        tempo_lock::{synced_delay_ms, synced_len_ms},
+    },
+    param_changes::{ParamGenerations, ParamTopic},
+    params::DelaxParams,
+    visualization::VisualizationBus,
+};
+
+/// The length of the timeline when the delay isn't synced, in ms
+const FREE_SPAN_MS: f32 = 1000.;
+/// Repeats quieter than this aren't drawn anymore
+const MIN_REPEAT_GAIN: f32 = 0.05;
+/// The maximum amount of drawn repeats per channel, so very short delays don't fill the whole timeline
+const MAX_REPEATS: usize = 32;
+/// How close the cursor needs to be to a marker to grab it, in pixels
+const GRAB_DISTANCE: f32 = 10.;
+
+/// Everything the timeline needs to know about the parameters to draw the repeats
+#[derive(Debug, Clone, Copy, PartialEq, Data)]
+struct TimelineState {
+    delay_ms: [f32; NUM_CHANNELS],
+    feedback: [f32; NUM_CHANNELS],
+    /// The decay times in seconds, which set the feedback in [FeedbackMode::DecayTime]
+    decay_time: [f32; NUM_CHANNELS],
+    decay_mode: bool,
+    stereo: bool,
+    synced: bool,
+}
+
+impl TimelineState {
+    fn from_params(params: &Arc<DelaxParams>) -> Self {
+        let delay_params = &params.delay_params;
+        Self {
+            delay_ms: std::array::from_fn(|channel| delay_params.channels[channel].len.value()),
+            feedback: std::array::from_fn(|channel| {
+                delay_params.channels[channel].feedback.value()
+            }),
+            decay_time: std::array::from_fn(|channel| {
+                delay_params.channels[channel].decay_time.value()
+            }),
+            decay_mode: delay_params.feedback_mode.value() == FeedbackMode::DecayTime,
+            stereo: delay_params.stereo_delay.value() == DelayMode::Stereo,
+            synced: delay_params.channels[0].bpm_bound.value(),
+        }
+    }
+
+    /// The channels that have their own delay time. In mono mode everything follows the first channel.
+    fn channels(&self) -> usize {
+        if self.stereo {
+            NUM_CHANNELS
+        } else {
+            1
+        }
+    }
+}
+
+enum DelayTimelineEvent {
+    SetState(TimelineState),
+}
+
+/// The delay time parameters of the channels, for the value entry
+const DELAY_PARAMS: [fn(&Arc<DelaxParams>) -> &FloatParam; NUM_CHANNELS] = [
+    |params| &params.delay_params.channels[0].len,
+    |params| &params.delay_params.channels[1].len,
+];
+
+/// A timeline showing the repeats of the delay as dots.
+///
+/// The timeline spans one bar of 4/4 when the delay is synced and one second otherwise.
+/// The first dot of every channel is the delay time itself and can be dragged to change it,
+/// double clicking it opens a [ValueEntry] to type in the delay time. The following repeats fade out with the feedback.
+#[derive(Lens)]
+pub struct DelayTimeline {
+    delay_params: [ParamWidgetBase; NUM_CHANNELS],
+    /// The delay time parameters, to tell the readout bar which one is used
+    param_ptrs: [ParamPtr; NUM_CHANNELS],
+    visualization: Arc<VisualizationBus>,
+    state: TimelineState,
+    /// The channel whose marker is under the cursor
+    hovered: Option<usize>,
+    /// The channel whose marker is currently dragged
+    dragging: Option<usize>,
+    /// The channel whose delay time is being typed in
+    entry_channel: Option<usize>,
+}
+
+impl DelayTimeline {
+    pub fn new<L, G>(
+        cx: &mut Context,
+        params: L,
+        generations: G,
+        visualization: Arc<VisualizationBus>,
+    ) -> Handle<Self>
+    where
+        L: Lens<Target = Arc<DelaxParams>> + Clone,
+        G: Lens<Target = ParamGenerations>,
+    {
+        let current_params = params.get(cx);
+        Self {
+            delay_params: DELAY_PARAMS
+                .map(|delay_param| ParamWidgetBase::new(cx, params.clone(), delay_param)),
+            param_ptrs: DELAY_PARAMS.map(|delay_param| delay_param(&current_params).as_ptr()),
+            visualization,
+            state: TimelineState::from_params(&current_params),
+            hovered: None,
+            dragging: None,
+            entry_channel: None,
+        }
+        .build(cx, move |cx| {
+            // Redraw whenever one of the parameters changes
+            let entity = cx.current();
+            let state_params = params.clone();
+            Binding::new(
+                cx,
+                generations.map(|generations| generations.get(ParamTopic::DelayTimes)),
+                move |cx, _| {
+                    let state = TimelineState::from_params(&state_params.get(cx));
+                    cx.emit_to(entity, DelayTimelineEvent::SetState(state));
+                },
+            );
+
+            Binding::new(cx, DelayTimeline::entry_channel, move |cx, channel| {
+                if let Some(channel) = channel.get(cx) {
+                    ValueEntry::new(cx, params.clone(), DELAY_PARAMS[channel]);
+                }
+            });
+        })
+    }
+
+    /// The channel whose delay time marker is under the cursor, if there's one close enough
+    fn marker_at_cursor(&self, cx: &EventContext) -> Option<usize> {
+        let bounds = cx.bounds();
+        let (cursor_x, cursor_y) = (cx.mouse().cursorx, cx.mouse().cursory);
+        let span_ms = self.span_ms();
+
+        (0..self.state.channels())
+            .map(|channel| {
+                let x = bounds.x + self.delay_ms(channel) / span_ms * bounds.w;
+                let y = self.row_y(bounds, channel);
+                (channel, (x - cursor_x).hypot(y - cursor_y))
+            })
+            .filter(|(_, distance)| *distance <= GRAB_DISTANCE)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(channel, _)| channel)
+    }
+
+    /// The tempo of the host, if the delay is synced and the host reports one
+    fn synced_tempo(&self) -> Option<f32> {
+        let tempo = self.visualization.tempo.load(Ordering::Relaxed);
+        (self.state.synced && tempo > 0.).then_some(tempo)
+    }
+
+    /// The length of the timeline in ms
+    fn span_ms(&self) -> f32 {
+        match self.synced_tempo() {
+            Some(tempo) => 4. * 60000. / tempo,
+            None => FREE_SPAN_MS,
+        }
+    }
+
+    /// The delay time of a channel in ms, snapped to the note length when the delay is synced
+    fn delay_ms(&self, channel: usize) -> f32 {
+        let len = self.state.delay_ms[channel];
+        match self.synced_tempo() {
+            Some(tempo) => synced_delay_ms(len, tempo),
+            None => len,
+        }
+    }
+
+    /// The times in ms and the gains of all the visible repeats of a channel
+    fn repeats(&self, channel: usize) -> impl Iterator<Item = (f32, f32)> {
+        let delay_ms = self.delay_ms(channel);
+        let feedback_mode = if self.state.decay_mode {
+            FeedbackMode::DecayTime
+        } else {
+            FeedbackMode::Ratio
+        };
+        let feedback = feedback_mode.feedback_gain(
+            self.state.feedback[channel],
+            self.state.decay_time[channel],
+            delay_ms,
+        );
+        let span_ms = self.span_ms();
+
+        (0..MAX_REPEATS)
+            .map(move |repeat| (delay_ms * (repeat + 1) as f32, feedback.powi(repeat as i32)))
+            .take_while(move |(time, gain)| *time <= span_ms && *gain >= MIN_REPEAT_GAIN)
+    }
+
+    /// The vertical center of the row of a channel
+    fn row_y(&self, bounds: BoundingBox, channel: usize) -> f32 {
+        let rows = self.state.channels() as f32;
+        bounds.y + bounds.h * (channel as f32 + 0.5) / rows
+    }
+
+    /// Tell the readout bar about the marker under the cursor, if it changed
+    fn set_hovered(&mut self, cx: &mut EventContext, hovered: Option<usize>) {
+        if hovered == self.hovered {
+            return;
+        }
+        if let Some(channel) = self.hovered {
+            cx.emit(ReadoutEvent::Unhover(self.param_ptrs[channel]));
+        }
+        if let Some(channel) = hovered {
+            cx.emit(ReadoutEvent::Hover(self.param_ptrs[channel]));
+        }
+        self.hovered = hovered;
+    }
+
+    fn set_delay_from_x(&self, cx: &mut EventContext, channel: usize, x: f32) {
+        let bounds = cx.bounds();
+        let delay_ms = ((x - bounds.x) / bounds.w).clamp(0., 1.) * self.span_ms();
+        // A synced delay knob is read at a reference tempo
+        let len = match self.synced_tempo() {
+            Some(tempo) => synced_len_ms(delay_ms, tempo),
+            None => delay_ms,
+        };
+
+        let param = &self.delay_params[channel];
+        param.set_normalized_value(cx, param.preview_normalized(len));
+    }
+}
+
+impl View for DelayTimeline {
+    fn element(&self) -> Option<&'static str> {
+        Some("delay-timeline")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|timeline_event, _| match timeline_event {
+            DelayTimelineEvent::SetState(state) => {
+                self.state = *state;
+                cx.needs_redraw();
+            }
+        });
+
+        // The typed in delay time, the entry closes either way
+        event.map(|value_entry_event, meta| {
+            if let (ValueEntryEvent::Commit(normalized_value), Some(channel)) =
+                (value_entry_event, self.entry_channel)
+            {
+                let param = &self.delay_params[channel];
+                param.begin_set_parameter(cx);
+                param.set_normalized_value(cx, *normalized_value);
+                param.end_set_parameter(cx);
+            }
+
+            self.entry_channel = None;
+            meta.consume();
+        });
+
+        event.map(|window_event, event_meta| match window_event {
+            WindowEvent::MouseDown(MouseButton::Left) => {
+                // Grab the closest delay time marker
+                self.dragging = self.marker_at_cursor(cx);
+
+                if let Some(channel) = self.dragging {
+                    cx.capture();
+                    cx.set_active(true);
+                    self.delay_params[channel].begin_set_parameter(cx);
+                    cx.emit(ReadoutEvent::BeginDrag(self.param_ptrs[channel]));
+                    event_meta.consume();
+                }
+            }
+            WindowEvent::MouseDoubleClick(MouseButton::Left) => {
+                if let Some(channel) = self.marker_at_cursor(cx) {
+                    self.entry_channel = Some(channel);
+                    event_meta.consume();
+                }
+            }
+            WindowEvent::MouseMove(x, _) => {
+                if let Some(channel) = self.dragging {
+                    self.set_delay_from_x(cx, channel, *x);
+                    event_meta.consume();
+                } else {
+                    let hovered = self.marker_at_cursor(cx);
+                    self.set_hovered(cx, hovered);
+                }
+            }
+            WindowEvent::MouseLeave => self.set_hovered(cx, None),
+            WindowEvent::MouseUp(MouseButton::Left) => {
+                if let Some(channel) = self.dragging.take() {
+                    self.delay_params[channel].end_set_parameter(cx);
+                    cx.emit(ReadoutEvent::EndDrag(self.param_ptrs[channel]));
+                    cx.release();
+                    cx.set_active(false);
+                    event_meta.consume();
+                }
+            }
+            _ => (),
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let bounds = cx.bounds();
+        if bounds.w <= f32::EPSILON || bounds.h <= f32::EPSILON {
+            return;
+        }
+
+        let line_color = cx.border_color();
+        let dot_color = cx.caret_color();
+        let span_ms = self.span_ms();
+
+        // The beats of the bar, or tenths of a second when the delay is free
+        let divisions = if self.state.synced { 4 } else { 10 };
+        let mut grid = Path::new();
+        for division in 0..=divisions {
+            let x = bounds.x + bounds.w * division as f32 / divisions as f32;
+            grid.move_to(x, bounds.y);
+            grid.line_to(x, bounds.y + bounds.h);
+        }
+        let mut grid_paint =
+            Paint::color(Color::rgba(line_color.r(), line_color.g(), line_color.b(), 80).into());
+        grid_paint.set_line_width(1.);
+        canvas.stroke_path(&grid, &grid_paint);
+
+        let radius = (bounds.h / (2. * self.state.channels() as f32)).min(6.) * 0.8;
+        for channel in 0..self.state.channels() {
+            let y = self.row_y(bounds, channel);
+
+            let mut row = Path::new();
+            row.move_to(bounds.x, y);
+            row.line_to(bounds.x + bounds.w, y);
+            let mut row_paint = Paint::color(line_color.into());
+            row_paint.set_line_width(1.);
+            canvas.stroke_path(&row, &row_paint);
+
+            for (repeat, (time, gain)) in self.repeats(channel).enumerate() {
+                let x = bounds.x + time / span_ms * bounds.w;
+                let alpha = (gain.clamp(0., 1.) * 255.) as u8;
+
+                // The first dot is the one that can be dragged, so it's drawn a bit larger
+                let dot_radius = if repeat == 0 { radius } else { radius * 0.6 };
+                let mut dot = Path::new();
+                dot.circle(x, y, dot_radius);
+                canvas.fill_path(
+                    &dot,
+                    &Paint::color(
+                        Color::rgba(dot_color.r(), dot_color.g(), dot_color.b(), alpha).into(),
+                    ),
+                );
+            }
+        }
+    }
+}
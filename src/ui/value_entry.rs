@@ -0,0 +1,89 @@
+use nih_plug::prelude::Param;
+use nih_plug_vizia::{vizia::prelude::*, widgets::param_base::ParamWidgetBase};
+
+/// Sent by a [ValueEntry] to the control that opened it. The control sets the value itself, so it can
+/// apply its own adjustments like quantizing, and closes the entry on both events.
+#[derive(Debug, Clone, Copy)]
+pub enum ValueEntryEvent {
+    /// A valid value was entered, as a normalized value
+    Commit(f32),
+    Cancel,
+}
+
+/// The text box inside of the entry is done
+enum TextboxEvent {
+    Submit(String),
+    Cancel,
+}
+
+/// A text box to type in the value of a parameter, it's opened by controls like the knobs and the timeline.
+///
+/// The text is parsed with the string to value conversion of the parameter, so units and note names work
+/// wherever the parameter understands them. Text that can't be parsed is marked as invalid and stays open
+/// for another try, Escape cancels the entry.
+pub struct ValueEntry {
+    param_base: ParamWidgetBase,
+}
+
+impl ValueEntry {
+    pub fn new<L, Params, P, FMap>(
+        cx: &mut Context,
+        params: L,
+        params_to_param: FMap,
+    ) -> Handle<Self>
+    where
+        L: Lens<Target = Params> + Clone,
+        Params: 'static,
+        P: Param + 'static,
+        FMap: Fn(&Params) -> &P + Copy + 'static,
+    {
+        Self {
+            param_base: ParamWidgetBase::new(cx, params.clone(), params_to_param),
+        }
+        .build(
+            cx,
+            ParamWidgetBase::build_view(params, params_to_param, |cx, param_data| {
+                Textbox::new(cx, param_data.make_lens(|param| param.to_string()))
+                    .on_submit(|cx, string, success| {
+                        if success {
+                            cx.emit(TextboxEvent::Submit(string))
+                        } else {
+                            cx.emit(TextboxEvent::Cancel)
+                        }
+                    })
+                    .on_build(|cx| {
+                        cx.emit(TextEvent::StartEdit);
+                        cx.emit(TextEvent::SelectAll);
+                    });
+            }),
+        )
+    }
+}
+
+impl View for ValueEntry {
+    fn element(&self) -> Option<&'static str> {
+        Some("value-entry")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|textbox_event, meta| {
+            match textbox_event {
+                TextboxEvent::Submit(string) => {
+                    match self.param_base.string_to_normalized_value(string) {
+                        Some(normalized_value) => {
+                            cx.emit(ValueEntryEvent::Commit(normalized_value))
+                        }
+                        None => {
+                            // Keep the text, so a typo can be fixed
+                            cx.toggle_class("invalid", true);
+                            cx.emit_to(meta.origin, TextEvent::StartEdit);
+                            cx.emit_to(meta.origin, TextEvent::SelectAll);
+                        }
+                    }
+                }
+                TextboxEvent::Cancel => cx.emit(ValueEntryEvent::Cancel),
+            }
+            meta.consume();
+        });
+    }
+}
@@ -0,0 +1,278 @@
+use std::collections::VecDeque;
+
+use nih_plug::prelude::{Param, ParamPtr};
+use nih_plug_vizia::{
+    vizia::{
+        prelude::*,
+        vg::{Paint, Path},
+    },
+    widgets::param_base::ParamWidgetBase,
+};
+
+use super::ReadoutEvent;
+use crate::strings::Text;
+
+/// The amount of previous positions that are drawn as a fading trail
+const TRAIL_LENGTH: usize = 24;
+
+/// The pairs of parameters the XY pad can control
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Data)]
+pub enum XYPadBinding {
+    /// Filter cutoff on the X axis and resonance on the Y axis
+    CutoffRes,
+    /// Delay time on the X axis and feedback on the Y axis
+    DelayFeedback,
+}
+
+impl XYPadBinding {
+    /// The next binding, so the GUI can cycle through them
+    pub fn next(self) -> Self {
+        match self {
+            XYPadBinding::CutoffRes => XYPadBinding::DelayFeedback,
+            XYPadBinding::DelayFeedback => XYPadBinding::CutoffRes,
+        }
+    }
+
+    /// The name shown in the GUI
+    pub fn text(self) -> Text {
+        match self {
+            XYPadBinding::CutoffRes => Text::CutoffRes,
+            XYPadBinding::DelayFeedback => Text::DelayFeedback,
+        }
+    }
+}
+
+enum XYPadEvent {
+    SetX(f32),
+    SetY(f32),
+}
+
+/// A pad that controls two parameters at once, one on every axis.
+///
+/// Dragging moves both parameters, double clicking resets both to their defaults.
+/// The last positions stay visible as a fading trail, which also shows automation.
+pub struct XYPad {
+    x_param: ParamWidgetBase,
+    y_param: ParamWidgetBase,
+    /// The parameters of the X and the Y axis, to tell the readout bar which ones are used
+    param_ptrs: [ParamPtr; 2],
+    /// The current normalized position
+    position: (f32, f32),
+    /// The previous positions, the newest one is at the back
+    trail: VecDeque<(f32, f32)>,
+    drag_active: bool,
+}
+
+impl XYPad {
+    pub fn new<L, Params, PX, PY, FMapX, FMapY>(
+        cx: &mut Context,
+        params: L,
+        params_to_x_param: FMapX,
+        params_to_y_param: FMapY,
+    ) -> Handle<Self>
+    where
+        L: Lens<Target = Params> + Clone,
+        Params: 'static,
+        PX: Param + 'static,
+        PY: Param + 'static,
+        FMapX: Fn(&Params) -> &PX + Copy + 'static,
+        FMapY: Fn(&Params) -> &PY + Copy + 'static,
+    {
+        let param_ptrs = [
+            params
+                .clone()
+                .map(move |params| params_to_x_param(params).as_ptr())
+                .get(cx),
+            params
+                .clone()
+                .map(move |params| params_to_y_param(params).as_ptr())
+                .get(cx),
+        ];
+        let x_param = ParamWidgetBase::new(cx, params.clone(), params_to_x_param);
+        let y_param = ParamWidgetBase::new(cx, params.clone(), params_to_y_param);
+        let position = (
+            x_param.unmodulated_normalized_value(),
+            y_param.unmodulated_normalized_value(),
+        );
+
+        Self {
+            x_param,
+            y_param,
+            param_ptrs,
+            position,
+            trail: VecDeque::with_capacity(TRAIL_LENGTH),
+            drag_active: false,
+        }
+        .build(cx, move |cx| {
+            // Follow the parameters, no matter if they are changed here, by the host or by another widget
+            let entity = cx.current();
+            Binding::new(
+                cx,
+                ParamWidgetBase::make_lens(params.clone(), params_to_x_param, |param| {
+                    param.unmodulated_normalized_value()
+                }),
+                move |cx, x| {
+                    let x = x.get(cx);
+                    cx.emit_to(entity, XYPadEvent::SetX(x));
+                },
+            );
+            Binding::new(
+                cx,
+                ParamWidgetBase::make_lens(params, params_to_y_param, |param| {
+                    param.unmodulated_normalized_value()
+                }),
+                move |cx, y| {
+                    let y = y.get(cx);
+                    cx.emit_to(entity, XYPadEvent::SetY(y));
+                },
+            );
+        })
+    }
+
+    /// Move to a new position and remember the old one for the trail
+    fn move_to(&mut self, position: (f32, f32)) {
+        if self.trail.len() == TRAIL_LENGTH {
+            self.trail.pop_front();
+        }
+        self.trail.push_back(self.position);
+        self.position = position;
+    }
+
+    /// Set both parameters to the position of the cursor
+    fn set_from_cursor(&self, cx: &mut EventContext, x: f32, y: f32) {
+        let bounds = cx.bounds();
+        let normalized_x = ((x - bounds.x) / bounds.w).clamp(0., 1.);
+        // The Y axis goes up
+        let normalized_y = 1. - ((y - bounds.y) / bounds.h).clamp(0., 1.);
+
+        self.x_param.set_normalized_value(cx, normalized_x);
+        self.y_param.set_normalized_value(cx, normalized_y);
+    }
+}
+
+impl View for XYPad {
+    fn element(&self) -> Option<&'static str> {
+        Some("xy-pad")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|xy_pad_event, _| {
+            match xy_pad_event {
+                XYPadEvent::SetX(x) => self.move_to((*x, self.position.1)),
+                XYPadEvent::SetY(y) => self.move_to((self.position.0, *y)),
+            }
+            cx.needs_redraw();
+        });
+
+        event.map(|window_event, event_meta| match window_event {
+            WindowEvent::MouseDown(MouseButton::Left) => {
+                self.drag_active = true;
+                cx.capture();
+                cx.set_active(true);
+
+                self.x_param.begin_set_parameter(cx);
+                self.y_param.begin_set_parameter(cx);
+                for param_ptr in self.param_ptrs {
+                    cx.emit(ReadoutEvent::BeginDrag(param_ptr));
+                }
+                let (x, y) = (cx.mouse().cursorx, cx.mouse().cursory);
+                self.set_from_cursor(cx, x, y);
+
+                event_meta.consume();
+            }
+            WindowEvent::MouseMove(x, y) => {
+                if self.drag_active {
+                    self.set_from_cursor(cx, *x, *y);
+                    event_meta.consume();
+                }
+            }
+            WindowEvent::MouseUp(MouseButton::Left) => {
+                if self.drag_active {
+                    self.drag_active = false;
+                    cx.release();
+                    cx.set_active(false);
+
+                    self.x_param.end_set_parameter(cx);
+                    self.y_param.end_set_parameter(cx);
+                    for param_ptr in self.param_ptrs {
+                        cx.emit(ReadoutEvent::EndDrag(param_ptr));
+                    }
+
+                    event_meta.consume();
+                }
+            }
+            WindowEvent::MouseDoubleClick(MouseButton::Left) => {
+                // Reset to default
+                for param in [&self.x_param, &self.y_param] {
+                    param.begin_set_parameter(cx);
+                    param.set_normalized_value(cx, param.default_normalized_value());
+                    param.end_set_parameter(cx);
+                }
+
+                event_meta.consume();
+            }
+            WindowEvent::MouseEnter => {
+                for param_ptr in self.param_ptrs {
+                    cx.emit(ReadoutEvent::Hover(param_ptr));
+                }
+            }
+            WindowEvent::MouseLeave => {
+                for param_ptr in self.param_ptrs {
+                    cx.emit(ReadoutEvent::Unhover(param_ptr));
+                }
+            }
+            _ => (),
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let bounds = cx.bounds();
+        if bounds.w <= f32::EPSILON || bounds.h <= f32::EPSILON {
+            return;
+        }
+
+        let to_screen =
+            |(x, y): (f32, f32)| (bounds.x + x * bounds.w, bounds.y + (1. - y) * bounds.h);
+
+        // Background and outline
+        let mut path = Path::new();
+        path.rect(bounds.x, bounds.y, bounds.w, bounds.h);
+        canvas.fill_path(&path, &Paint::color(cx.background_color().into()));
+        let mut outline_paint = Paint::color(cx.border_color().into());
+        outline_paint.set_line_width(1.);
+        canvas.stroke_path(&path, &outline_paint);
+
+        // The trail fades out towards the older positions
+        let dot_color = cx.caret_color();
+        let radius = bounds.w.min(bounds.h) * 0.04;
+        for (age, position) in self.trail.iter().rev().enumerate() {
+            let (x, y) = to_screen(*position);
+            let alpha = 120. * (1. - (age + 1) as f32 / (TRAIL_LENGTH + 1) as f32);
+
+            let mut ghost = Path::new();
+            ghost.circle(x, y, radius * 0.7);
+            canvas.fill_path(
+                &ghost,
+                &Paint::color(
+                    Color::rgba(dot_color.r(), dot_color.g(), dot_color.b(), alpha as u8).into(),
+                ),
+            );
+        }
+
+        // Crosshair and the current position
+        let (x, y) = to_screen(self.position);
+        let mut crosshair = Path::new();
+        crosshair.move_to(x, bounds.y);
+        crosshair.line_to(x, bounds.y + bounds.h);
+        crosshair.move_to(bounds.x, y);
+        crosshair.line_to(bounds.x + bounds.w, y);
+        let mut crosshair_paint =
+            Paint::color(Color::rgba(dot_color.r(), dot_color.g(), dot_color.b(), 60).into());
+        crosshair_paint.set_line_width(1.);
+        canvas.stroke_path(&crosshair, &crosshair_paint);
+
+        let mut dot = Path::new();
+        dot.circle(x, y, radius);
+        canvas.fill_path(&dot, &Paint::color(dot_color.into()));
+    }
+}
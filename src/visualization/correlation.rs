@@ -0,0 +1,88 @@
+/// The time the correlation is averaged over in ms
+const CORRELATION_MS: f32 = 300.;
+/// Below this energy per channel the signal counts as silent and the correlation is shown as zero
+const SILENCE_ENERGY: f32 = 1e-10;
+
+/// Measures the phase correlation between the left and the right channel.
+///
+/// The result goes from 1 for a signal that's the same on both sides over 0 for unrelated sides down to -1
+/// for sides that cancel each other out when summed to mono.
+///
+/// Usage:
+/// ```
+/// use delax::visualization::correlation::CorrelationMeter;
+///
+/// let mut meter = CorrelationMeter::new(1000.);
+/// for sample in 0..5000 {
+///     let sample = (sample as f32 * 0.1).sin();
+///     meter.process(sample, -sample);
+/// }
+/// assert!((meter.correlation() + 1.).abs() < 1e-3);
+/// ```
+pub struct CorrelationMeter {
+    /// The amount of the distance to the current products the averages move every sample
+    smoothing: f32,
+    /// The averaged left and right energies and their product
+    left: f32,
+    right: f32,
+    product: f32,
+}
+
+impl CorrelationMeter {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut meter = Self {
+            smoothing: 1.,
+            left: 0.,
+            right: 0.,
+            product: 0.,
+        };
+        meter.set_sample_rate(sample_rate);
+        meter
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.smoothing = 1. - (-1000. / (CORRELATION_MS * sample_rate)).exp();
+    }
+
+    pub fn process(&mut self, l: f32, r: f32) {
+        self.left += (l * l - self.left) * self.smoothing;
+        self.right += (r * r - self.right) * self.smoothing;
+        self.product += (l * r - self.product) * self.smoothing;
+    }
+
+    /// The current correlation between -1 and 1, silence is 0
+    pub fn correlation(&self) -> f32 {
+        let energy = self.left * self.right;
+        if self.left < SILENCE_ENERGY || self.right < SILENCE_ENERGY {
+            return 0.;
+        }
+        (self.product / energy.sqrt()).clamp(-1., 1.)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn measure(signal: impl Fn(f32) -> (f32, f32)) -> f32 {
+        let mut meter = CorrelationMeter::new(48000.);
+        for sample in 0..48000 {
+            let (l, r) = signal(sample as f32 / 48000.);
+            meter.process(l, r);
+        }
+        meter.correlation()
+    }
+
+    #[test]
+    fn correlation_of_typical_signals() {
+        let sine = |frequency: f32, time: f32| (std::f32::consts::TAU * frequency * time).sin();
+
+        assert!((measure(|time| (sine(440., time), sine(440., time) * 0.5)) - 1.).abs() < 1e-3);
+        assert!((measure(|time| (sine(440., time), -sine(440., time))) + 1.).abs() < 1e-3);
+        // Unrelated sides
+        assert!(measure(|time| (sine(440., time), sine(1234., time))).abs() < 0.05);
+        // A quarter period apart, like a very wide stereo spread
+        assert!(measure(|time| (sine(440., time), sine(440., time + 0.25 / 440.))).abs() < 0.05);
+        assert_eq!(measure(|_| (0., 0.)), 0.);
+    }
+}
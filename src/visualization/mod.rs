@@ -0,0 +1,545 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+
+use nih_plug::prelude::AtomicF32;
+
+pub use crate::peak_follower::MeterLevels;
+use crate::peak_follower::{Ballistics, PeakFollower};
+use correlation::CorrelationMeter;
+use ring_buffer::RingBuffer;
+use spectrum::{SpectrumAnalyzer, SPECTRUM_BANDS, SPECTRUM_WINDOW};
+
+pub mod correlation;
+pub mod ring_buffer;
+pub mod spectrum;
+
+/// The amount of samples between two peak frames
+const PEAK_INTERVAL: usize = 256;
+/// The amount of samples that are summarized by one waveform frame
+const WAVEFORM_INTERVAL: usize = 64;
+/// The amount of samples between two points of the vectorscope
+const SCOPE_INTERVAL: usize = 8;
+/// The shortest period the repeat level is measured over. Shorter delays measure several repeats at once,
+/// so very short delays don't flood the bus.
+const MIN_REPEAT_PERIOD: usize = 256;
+/// The amount of waveform frames a [BufferSnapshot] is summarized into
+pub const SNAPSHOT_COLUMNS: usize = 512;
+
+/// The levels of the meters of the first pair of channels, with the ballistics already applied
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PeakFrame {
+    pub input: [MeterLevels; 2],
+    pub output: [MeterLevels; 2],
+    /// The phase correlation of the output between -1 and 1, see [CorrelationMeter]
+    pub correlation: f32,
+}
+
+/// One output sample of the first pair of channels, for the vectorscope
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ScopePoint {
+    pub l: f32,
+    pub r: f32,
+}
+
+/// The range of the output signal over [WAVEFORM_INTERVAL] samples
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct WaveformFrame {
+    pub min: f32,
+    pub max: f32,
+}
+
+/// The whole delay line of the first pair of channels, summarized into [SNAPSHOT_COLUMNS] waveform frames
+/// from the oldest sample to the newest one
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BufferSnapshot {
+    pub frames: Vec<WaveformFrame>,
+    /// How far back the oldest sample was written, in ms
+    pub length_ms: f32,
+}
+
+/// The amplitudes of the output in the bands of the [SpectrumAnalyzer]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SpectrumFrame {
+    pub magnitudes: [f32; SPECTRUM_BANDS],
+}
+
+/// The RMS level of the delay output of one channel over one delay period.
+///
+/// Every period holds one repeat, so consecutive frames show how the repeats really decay,
+/// including everything the filters in the feedback loop do to them.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RepeatFrame {
+    pub channel: usize,
+    pub rms: f32,
+}
+
+/// Everything the audio thread shares with the editor for drawing.
+///
+/// The audio thread pushes frames into the ring buffers and the editor drains them on a timer.
+/// Neither side ever waits for the other one. The [VisualizationProducer] is the only producer, the editor the only consumer.
+pub struct VisualizationBus {
+    pub peaks: RingBuffer<PeakFrame>,
+    pub waveform: RingBuffer<WaveformFrame>,
+    pub spectrum: RingBuffer<SpectrumFrame>,
+    pub repeats: RingBuffer<RepeatFrame>,
+    pub scope: RingBuffer<ScopePoint>,
+    /// The highest absolute output sample of the first pair of channels since the last reset.
+    /// Anything above 1 clipped. The editor resets this when the clip LEDs are clicked.
+    pub output_max_peaks: [AtomicF32; 2],
+    /// The highest gain reduction of the auto gain and the safety clipper in dB since the editor last took it
+    pub gain_reduction_db: AtomicF32,
+    /// The highest gain reduction of the safety clipper on the output in dB since the editor last took it
+    pub safety_clip_db: AtomicF32,
+    /// The tempo of the host in BPM, zero if it's unknown
+    pub tempo: AtomicF32,
+    /// The least time in ms the round trip through the aux send and return adds to the repeats, zero if it isn't used
+    pub return_latency_ms: AtomicF32,
+    /// The estimated gain of the loudest pass through the feedback loop, above 1 the repeats build up
+    pub loop_gain: AtomicF32,
+    /// Set by the editor to ask for a [BufferSnapshot], the audio thread clears it once it took one
+    snapshot_requested: AtomicBool,
+    /// The last snapshot, it's only locked by the audio thread while it takes one and by the editor while it copies it
+    snapshot: Mutex<BufferSnapshot>,
+    snapshot_ready: AtomicBool,
+}
+
+impl Default for VisualizationBus {
+    fn default() -> Self {
+        Self {
+            peaks: RingBuffer::new(64),
+            waveform: RingBuffer::new(1024),
+            spectrum: RingBuffer::new(8),
+            repeats: RingBuffer::new(256),
+            scope: RingBuffer::new(1024),
+            output_max_peaks: std::array::from_fn(|_| AtomicF32::new(0.)),
+            gain_reduction_db: AtomicF32::new(0.),
+            safety_clip_db: AtomicF32::new(0.),
+            tempo: AtomicF32::new(0.),
+            return_latency_ms: AtomicF32::new(0.),
+            loop_gain: AtomicF32::new(0.),
+            snapshot_requested: AtomicBool::new(false),
+            // The audio thread fills the frames in place, so they must never grow
+            snapshot: Mutex::new(BufferSnapshot {
+                frames: Vec::with_capacity(SNAPSHOT_COLUMNS),
+                length_ms: 0.,
+            }),
+            snapshot_ready: AtomicBool::new(false),
+        }
+    }
+}
+
+impl VisualizationBus {
+    /// The highest output peak of every channel since the last reset
+    pub fn output_max_peaks(&self) -> [f32; 2] {
+        self.output_max_peaks
+            .each_ref()
+            .map(|peak| peak.load(Ordering::Relaxed))
+    }
+
+    /// Take the highest gain reduction in dB since the last call, for the GR meter
+    pub fn take_gain_reduction_db(&self) -> f32 {
+        self.gain_reduction_db.swap(0., Ordering::Relaxed)
+    }
+
+    /// Take the highest gain reduction of the safety clipper in dB since the last call, for the safety indicator
+    pub fn take_safety_clip_db(&self) -> f32 {
+        self.safety_clip_db.swap(0., Ordering::Relaxed)
+    }
+
+    /// Ask the audio thread for a snapshot of the whole delay line. Reading all of it takes a while,
+    /// so it's only done on demand, for example when the delay lines are frozen.
+    pub fn request_snapshot(&self) {
+        self.snapshot_requested.store(true, Ordering::Relaxed);
+    }
+
+    /// Take the snapshot the audio thread took since the last call, if there's one
+    pub fn take_snapshot(&self) -> Option<BufferSnapshot> {
+        if !self.snapshot_ready.swap(false, Ordering::Acquire) {
+            return None;
+        }
+        Some(self.snapshot.lock().unwrap().clone())
+    }
+
+    /// Forget the output peaks, this turns the clip LEDs off again
+    pub fn reset_output_max_peaks(&self) {
+        for peak in &self.output_max_peaks {
+            peak.store(0., Ordering::Relaxed);
+        }
+    }
+}
+
+/// The audio thread side of the [VisualizationBus].
+///
+/// It's fed every processed sample and collects them into frames, which are pushed once they are complete.
+pub struct VisualizationProducer {
+    bus: Arc<VisualizationBus>,
+    peak_followers_in: [PeakFollower; 2],
+    peak_followers_out: [PeakFollower; 2],
+    /// The samples since the last peak frame
+    peak_counter: usize,
+    /// The highest absolute output samples since the last peak frame, these are published together with it
+    output_max_peaks: [f32; 2],
+    /// The waveform frame that's currently collected
+    waveform: WaveformFrame,
+    /// The samples in the current waveform frame
+    waveform_counter: usize,
+    spectrum_analyzer: SpectrumAnalyzer,
+    correlation_meter: CorrelationMeter,
+    /// The samples since the last point of the vectorscope
+    scope_counter: usize,
+    /// The factor the peak and the scope intervals are stretched by, see [VisualizationProducer::set_publish_divider()]
+    publish_divider: usize,
+    /// The samples that are left out of the spectrum before its next window starts
+    spectrum_skip: usize,
+    /// The sum of the squared delay outputs in the current period of every channel
+    repeat_energy: [f32; 2],
+    /// The samples in the current period of every channel
+    repeat_counter: [usize; 2],
+}
+
+impl VisualizationProducer {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            bus: Arc::new(VisualizationBus::default()),
+            peak_followers_in: std::array::from_fn(|_| {
+                PeakFollower::new(Ballistics::default(), sample_rate)
+            }),
+            peak_followers_out: std::array::from_fn(|_| {
+                PeakFollower::new(Ballistics::default(), sample_rate)
+            }),
+            peak_counter: 0,
+            output_max_peaks: [0.; 2],
+            waveform: WaveformFrame::default(),
+            waveform_counter: 0,
+            spectrum_analyzer: SpectrumAnalyzer::new(sample_rate),
+            correlation_meter: CorrelationMeter::new(sample_rate),
+            scope_counter: 0,
+            publish_divider: 1,
+            spectrum_skip: 0,
+            repeat_energy: [0.; 2],
+            repeat_counter: [0; 2],
+        }
+    }
+
+    /// The bus the frames are pushed into, for the editor
+    pub fn bus(&self) -> &Arc<VisualizationBus> {
+        &self.bus
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        for peak_follower in self
+            .peak_followers_in
+            .iter_mut()
+            .chain(&mut self.peak_followers_out)
+        {
+            peak_follower.set_sample_rate(sample_rate);
+        }
+        self.spectrum_analyzer.set_sample_rate(sample_rate);
+        self.correlation_meter.set_sample_rate(sample_rate);
+    }
+
+    /// Push the peak frames and the scope points only every `divider` times as often, and analyze only every
+    /// `divider`th window of the spectrum. The waveform keeps its rate, since the timeline measures time in its frames.
+    pub fn set_publish_divider(&mut self, divider: usize) {
+        self.publish_divider = divider.max(1);
+    }
+
+    pub fn set_tempo(&self, tempo: f32) {
+        self.bus.tempo.store(tempo, Ordering::Relaxed);
+    }
+
+    /// Report how much the round trip through the aux send and return delays the external feedback
+    pub fn report_return_latency(&self, return_latency_ms: f32) {
+        self.bus
+            .return_latency_ms
+            .store(return_latency_ms, Ordering::Relaxed);
+    }
+
+    /// Report the estimated gain of the loudest pass through the feedback loop, see [crate::delay_engine::stability::LoopGain]
+    pub fn report_loop_gain(&self, loop_gain: f32) {
+        self.bus.loop_gain.store(loop_gain, Ordering::Relaxed);
+    }
+
+    /// Report how far the auto gain or the safety clipper currently pull the output down, as a positive amount of dB.
+    /// The highest report since the editor last took it is kept.
+    pub fn report_gain_reduction(&self, gain_reduction_db: f32) {
+        self.bus
+            .gain_reduction_db
+            .fetch_max(gain_reduction_db, Ordering::Relaxed);
+    }
+
+    /// Report how far the safety clipper pulls the output down, as a positive amount of dB
+    pub fn report_safety_clip(&self, gain_reduction_db: f32) {
+        self.bus
+            .safety_clip_db
+            .fetch_max(gain_reduction_db, Ordering::Relaxed);
+    }
+
+    /// Whether the editor asked for a snapshot of the delay line, see [VisualizationProducer::publish_snapshot()]
+    pub fn snapshot_requested(&self) -> bool {
+        self.bus.snapshot_requested.load(Ordering::Relaxed)
+    }
+
+    /// Summarize a delay line of `len` samples for the editor. `sample` returns the sample at an index,
+    /// from the oldest one at 0 to the newest one.
+    ///
+    /// This doesn't allocate, but it reads every sample, so it's only called when the editor asked for it.
+    /// If the editor is copying the last snapshot right now the request stays and this is tried again later.
+    pub fn publish_snapshot(&self, len: usize, sample_rate: f32, sample: impl Fn(usize) -> f32) {
+        let Ok(mut snapshot) = self.bus.snapshot.try_lock() else {
+            return;
+        };
+
+        snapshot.frames.clear();
+        for column in 0..SNAPSHOT_COLUMNS {
+            let start = column * len / SNAPSHOT_COLUMNS;
+            let end = ((column + 1) * len / SNAPSHOT_COLUMNS)
+                .max(start + 1)
+                .min(len);
+            let frame = (start..end).map(&sample).fold(None, |frame, sample| {
+                Some(match frame {
+                    None => WaveformFrame {
+                        min: sample,
+                        max: sample,
+                    },
+                    Some(WaveformFrame { min, max }) => WaveformFrame {
+                        min: sample.min(min),
+                        max: sample.max(max),
+                    },
+                })
+            });
+            snapshot.frames.push(frame.unwrap_or_default());
+        }
+        snapshot.length_ms = len as f32 / sample_rate * 1000.;
+        drop(snapshot);
+
+        self.bus.snapshot_requested.store(false, Ordering::Relaxed);
+        self.bus.snapshot_ready.store(true, Ordering::Release);
+    }
+
+    /// Feed the input of the first pair of channels
+    pub fn process_input(&mut self, l: f32, r: f32) {
+        for (peak_follower, sample) in self.peak_followers_in.iter_mut().zip([l, r]) {
+            peak_follower.process(sample);
+        }
+    }
+
+    /// Feed the output of the delay lines of the first pair of channels, together with their current delay in samples
+    pub fn process_repeats(&mut self, delay_outputs: [f32; 2], delay_samples: [usize; 2]) {
+        for channel in 0..2 {
+            self.repeat_energy[channel] += delay_outputs[channel] * delay_outputs[channel];
+            self.repeat_counter[channel] += 1;
+
+            let period = delay_samples[channel].max(MIN_REPEAT_PERIOD);
+            if self.repeat_counter[channel] >= period {
+                let rms =
+                    (self.repeat_energy[channel] / self.repeat_counter[channel] as f32).sqrt();
+                self.bus.repeats.push(RepeatFrame { channel, rms });
+
+                self.repeat_energy[channel] = 0.;
+                self.repeat_counter[channel] = 0;
+            }
+        }
+    }
+
+    /// Feed the output of the first pair of channels. This is where the frames are pushed.
+    pub fn process_output(&mut self, l: f32, r: f32) {
+        for (peak_follower, sample) in self.peak_followers_out.iter_mut().zip([l, r]) {
+            peak_follower.process(sample);
+        }
+        for (max_peak, sample) in self.output_max_peaks.iter_mut().zip([l, r]) {
+            *max_peak = max_peak.max(sample.abs());
+        }
+        self.correlation_meter.process(l, r);
+
+        self.scope_counter += 1;
+        if self.scope_counter >= SCOPE_INTERVAL * self.publish_divider {
+            self.scope_counter = 0;
+            self.bus.scope.push(ScopePoint { l, r });
+        }
+
+        self.peak_counter += 1;
+        if self.peak_counter >= PEAK_INTERVAL * self.publish_divider {
+            self.peak_counter = 0;
+            for (shared, max_peak) in self
+                .bus
+                .output_max_peaks
+                .iter()
+                .zip(&mut self.output_max_peaks)
+            {
+                shared.fetch_max(*max_peak, Ordering::Relaxed);
+                *max_peak = 0.;
+            }
+            self.bus.peaks.push(PeakFrame {
+                input: self.peak_followers_in.each_ref().map(PeakFollower::levels),
+                output: self.peak_followers_out.each_ref().map(PeakFollower::levels),
+                correlation: self.correlation_meter.correlation(),
+            });
+        }
+
+        let mono = (l + r) / 2.;
+
+        if self.waveform_counter == 0 {
+            self.waveform = WaveformFrame {
+                min: mono,
+                max: mono,
+            };
+        } else {
+            self.waveform.min = self.waveform.min.min(mono);
+            self.waveform.max = self.waveform.max.max(mono);
+        }
+        self.waveform_counter += 1;
+        if self.waveform_counter >= WAVEFORM_INTERVAL {
+            self.waveform_counter = 0;
+            self.bus.waveform.push(self.waveform);
+        }
+
+        if self.spectrum_skip > 0 {
+            self.spectrum_skip -= 1;
+        } else if let Some(magnitudes) = self.spectrum_analyzer.process(mono) {
+            self.bus.spectrum.push(SpectrumFrame { magnitudes });
+            self.spectrum_skip = (self.publish_divider - 1) * SPECTRUM_WINDOW;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_are_pushed_in_intervals() {
+        let mut producer = VisualizationProducer::new(48000.);
+        for i in 0..PEAK_INTERVAL * 4 {
+            let sample = if i % 2 == 0 { 0.5 } else { -0.5 };
+            producer.process_input(sample, sample);
+            producer.process_output(sample, sample);
+        }
+
+        let bus = producer.bus();
+        let peaks: Vec<_> = bus.peaks.drain().collect();
+        assert_eq!(peaks.len(), 4);
+        // The meters publish plain gains
+        let last = peaks[3];
+        assert!((last.input[0].peak - 0.5).abs() < 1e-3);
+        assert!((last.output[1].hold - 0.5).abs() < 1e-3);
+        // The same signal on both sides
+        assert!(last.correlation > 0.99);
+        assert_eq!(
+            bus.scope.drain().count(),
+            PEAK_INTERVAL * 4 / SCOPE_INTERVAL
+        );
+        let waveform: Vec<_> = bus.waveform.drain().collect();
+        assert_eq!(waveform.len(), PEAK_INTERVAL * 4 / WAVEFORM_INTERVAL);
+        assert!(waveform.iter().all(|frame| *frame
+            == WaveformFrame {
+                min: -0.5,
+                max: 0.5
+            }));
+        assert_eq!(bus.spectrum.drain().count(), 1);
+    }
+
+    #[test]
+    fn the_divider_thins_out_the_frames() {
+        let mut producer = VisualizationProducer::new(48000.);
+        producer.set_publish_divider(4);
+        for _ in 0..SPECTRUM_WINDOW * 8 {
+            producer.process_output(0.5, -0.5);
+        }
+
+        let bus = producer.bus();
+        assert_eq!(
+            bus.peaks.drain().count(),
+            SPECTRUM_WINDOW * 8 / (PEAK_INTERVAL * 4)
+        );
+        assert_eq!(
+            bus.scope.drain().count(),
+            SPECTRUM_WINDOW * 8 / (SCOPE_INTERVAL * 4)
+        );
+        assert_eq!(bus.spectrum.drain().count(), 2);
+        // The timeline needs the waveform at its full rate
+        assert_eq!(
+            bus.waveform.drain().count(),
+            SPECTRUM_WINDOW * 8 / WAVEFORM_INTERVAL
+        );
+    }
+
+    #[test]
+    fn repeats_are_measured_per_period() {
+        let mut producer = VisualizationProducer::new(48000.);
+        // A constant output of 0.5 for three periods of the first channel
+        for _ in 0..3000 {
+            producer.process_repeats([0.5, 0.], [1000, 100]);
+        }
+
+        let repeats: Vec<_> = producer.bus().repeats.drain().collect();
+        let first: Vec<_> = repeats.iter().filter(|frame| frame.channel == 0).collect();
+        assert_eq!(first.len(), 3);
+        assert!(first.iter().all(|frame| (frame.rms - 0.5).abs() < 1e-4));
+
+        // The second channel is measured over the minimum period instead of its own short one
+        let second = repeats.iter().filter(|frame| frame.channel == 1).count();
+        assert_eq!(second, 3000 / MIN_REPEAT_PERIOD);
+    }
+
+    #[test]
+    fn clipping_latches_until_reset() {
+        let mut producer = VisualizationProducer::new(48000.);
+        producer.process_output(1.5, -0.5);
+        for _ in 0..PEAK_INTERVAL * 2 {
+            producer.process_output(0.1, 0.1);
+        }
+        assert_eq!(producer.bus().output_max_peaks(), [1.5, 0.5]);
+
+        producer.bus().reset_output_max_peaks();
+        for _ in 0..PEAK_INTERVAL {
+            producer.process_output(0.1, -0.2);
+        }
+        assert_eq!(producer.bus().output_max_peaks(), [0.1, 0.2]);
+    }
+
+    #[test]
+    fn gain_reduction_holds_the_highest_value() {
+        let producer = VisualizationProducer::new(48000.);
+        producer.report_gain_reduction(3.);
+        producer.report_gain_reduction(6.);
+        producer.report_gain_reduction(1.);
+
+        assert_eq!(producer.bus().take_gain_reduction_db(), 6.);
+        assert_eq!(producer.bus().take_gain_reduction_db(), 0.);
+    }
+
+    #[test]
+    fn snapshots_are_only_taken_on_request() {
+        let producer = VisualizationProducer::new(1000.);
+        assert!(!producer.snapshot_requested());
+        assert_eq!(producer.bus().take_snapshot(), None);
+
+        producer.bus().request_snapshot();
+        assert!(producer.snapshot_requested());
+        // A ramp from -1 to 1 over two seconds
+        let len = SNAPSHOT_COLUMNS * 4;
+        producer.publish_snapshot(len, 1000., |index| index as f32 / len as f32 * 2. - 1.);
+        assert!(!producer.snapshot_requested());
+
+        let snapshot = producer.bus().take_snapshot().unwrap();
+        assert_eq!(snapshot.length_ms, len as f32);
+        assert_eq!(snapshot.frames.len(), SNAPSHOT_COLUMNS);
+        assert_eq!(snapshot.frames[0].min, -1.);
+        assert!(snapshot.frames[SNAPSHOT_COLUMNS - 1].max > 0.99);
+        assert_eq!(producer.bus().take_snapshot(), None);
+    }
+
+    #[test]
+    fn full_buffers_drop_frames() {
+        let mut producer = VisualizationProducer::new(48000.);
+        let capacity = producer.bus().peaks.capacity();
+        for _ in 0..PEAK_INTERVAL * (capacity + 10) {
+            producer.process_output(0., 0.);
+        }
+
+        assert_eq!(producer.bus().peaks.drain().count(), capacity);
+    }
+}
@@ -0,0 +1,135 @@
+use std::{
+    cell::UnsafeCell,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// A fixed size, lock-free ring buffer with a single producer and a single consumer.
+///
+/// The audio thread is the producer and the editor the consumer. Pushing never blocks or allocates,
+/// if the buffer is full the new value is dropped. This happens while the editor is closed, nothing drains the buffer then.
+///
+/// Only one thread may push and only one thread may pop at the same time, otherwise values can get lost or torn.
+///
+/// Usage:
+/// ```
+/// use delax::visualization::ring_buffer::RingBuffer;
+///
+/// let buffer = RingBuffer::new(2);
+/// assert!(buffer.push(1.));
+/// assert!(buffer.push(2.));
+/// assert!(!buffer.push(3.));
+///
+/// assert_eq!(buffer.pop(), Some(1.));
+/// assert_eq!(buffer.pop(), Some(2.));
+/// assert_eq!(buffer.pop(), None);
+/// ```
+pub struct RingBuffer<T> {
+    slots: Box<[UnsafeCell<T>]>,
+    /// The amount of popped values, the next value is read from `read % capacity`
+    read: AtomicUsize,
+    /// The amount of pushed values, the next value is written to `write % capacity`
+    write: AtomicUsize,
+}
+
+// SAFETY: A slot is only ever accessed by either the producer or the consumer, the indices hand it over between them.
+// The producer only writes slots the consumer has already read and the consumer only reads slots the producer has
+// finished writing. This relies on there being a single producer and a single consumer.
+unsafe impl<T: Send> Sync for RingBuffer<T> {}
+
+impl<T: Copy + Default> RingBuffer<T> {
+    /// Create a buffer that can hold `capacity` values that haven't been popped yet
+    pub fn new(capacity: usize) -> Self {
+        assert!(
+            capacity > 0,
+            "The ring buffer needs space for at least one value"
+        );
+
+        Self {
+            slots: (0..capacity)
+                .map(|_| UnsafeCell::new(T::default()))
+                .collect(),
+            read: AtomicUsize::new(0),
+            write: AtomicUsize::new(0),
+        }
+    }
+
+    /// The amount of values the buffer can hold
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Add a value to the buffer. Returns false and drops the value if the buffer is full.
+    pub fn push(&self, value: T) -> bool {
+        let write = self.write.load(Ordering::Relaxed);
+        let read = self.read.load(Ordering::Acquire);
+        if write.wrapping_sub(read) >= self.capacity() {
+            return false;
+        }
+
+        // SAFETY: The slot has been read by the consumer already, see the `Sync` implementation
+        unsafe { *self.slots[write % self.capacity()].get() = value };
+        self.write.store(write.wrapping_add(1), Ordering::Release);
+        true
+    }
+
+    /// Take the oldest value out of the buffer
+    pub fn pop(&self) -> Option<T> {
+        let read = self.read.load(Ordering::Relaxed);
+        let write = self.write.load(Ordering::Acquire);
+        if read == write {
+            return None;
+        }
+
+        // SAFETY: The slot has been written by the producer already, see the `Sync` implementation
+        let value = unsafe { *self.slots[read % self.capacity()].get() };
+        self.read.store(read.wrapping_add(1), Ordering::Release);
+        Some(value)
+    }
+
+    /// Take all the values out of the buffer, oldest first
+    pub fn drain(&self) -> impl Iterator<Item = T> + '_ {
+        std::iter::from_fn(|| self.pop())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn wraps_around() {
+        let buffer = RingBuffer::new(3);
+        for value in 0..10 {
+            assert!(buffer.push(value));
+            assert_eq!(buffer.pop(), Some(value));
+        }
+        assert_eq!(buffer.pop(), None);
+    }
+
+    #[test]
+    fn keeps_the_order_across_threads() {
+        let buffer = Arc::new(RingBuffer::new(16));
+
+        let producer = {
+            let buffer = buffer.clone();
+            std::thread::spawn(move || {
+                for value in 0..10000u32 {
+                    while !buffer.push(value) {
+                        std::thread::yield_now();
+                    }
+                }
+            })
+        };
+
+        let mut expected = 0;
+        while expected < 10000 {
+            if let Some(value) = buffer.pop() {
+                assert_eq!(value, expected);
+                expected += 1;
+            }
+        }
+        producer.join().unwrap();
+    }
+}
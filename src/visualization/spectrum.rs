@@ -0,0 +1,135 @@
+use std::f32::consts::TAU;
+
+/// The amount of frequency bands in a spectrum frame
+pub const SPECTRUM_BANDS: usize = 32;
+/// The amount of samples that are analyzed for every spectrum frame
+pub const SPECTRUM_WINDOW: usize = 1024;
+/// The center frequency of the lowest band in Hz
+const LOWEST_FREQUENCY: f32 = 40.;
+/// The center frequency of the highest band in Hz
+const HIGHEST_FREQUENCY: f32 = 16000.;
+
+/// The center frequency of a band in Hz. The bands are spaced logarithmically.
+///
+/// Usage:
+/// ```
+/// use delax::visualization::spectrum::{band_frequency, SPECTRUM_BANDS};
+///
+/// assert_eq!(band_frequency(0), 40.);
+/// assert!((band_frequency(SPECTRUM_BANDS - 1) - 16000.).abs() < 0.1);
+/// ```
+pub fn band_frequency(band: usize) -> f32 {
+    let position = band as f32 / (SPECTRUM_BANDS - 1) as f32;
+    LOWEST_FREQUENCY * (HIGHEST_FREQUENCY / LOWEST_FREQUENCY).powf(position)
+}
+
+/// A coarse spectrum analyzer that measures the level of a few logarithmically spaced bands.
+///
+/// Every band is a Goertzel filter that runs over a Hann windowed block of [SPECTRUM_WINDOW] samples.
+/// This works sample by sample without a buffer, so it fits into the per sample processing of the plugin.
+pub struct SpectrumAnalyzer {
+    /// The Goertzel coefficient of every band, `2 * cos(omega)`
+    coefficients: [f32; SPECTRUM_BANDS],
+    /// The last two filter states of every band
+    states: [(f32, f32); SPECTRUM_BANDS],
+    /// The position in the current window
+    position: usize,
+}
+
+impl SpectrumAnalyzer {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut analyzer = Self {
+            coefficients: [0.; SPECTRUM_BANDS],
+            states: [(0., 0.); SPECTRUM_BANDS],
+            position: 0,
+        };
+        analyzer.set_sample_rate(sample_rate);
+        analyzer
+    }
+
+    /// Recalculate the filters for a new sample rate, this also starts a new window
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        for (band, coefficient) in self.coefficients.iter_mut().enumerate() {
+            let frequency = band_frequency(band).min(sample_rate / 2.);
+            *coefficient = 2. * (TAU * frequency / sample_rate).cos();
+        }
+        self.reset();
+    }
+
+    /// Drop the current window
+    pub fn reset(&mut self) {
+        self.states = [(0., 0.); SPECTRUM_BANDS];
+        self.position = 0;
+    }
+
+    /// Feed the next sample into the analyzer.
+    ///
+    /// Once a window is complete this returns the amplitude of every band, a sine with an amplitude of 1 shows up as 1.
+    pub fn process(&mut self, sample: f32) -> Option<[f32; SPECTRUM_BANDS]> {
+        let window = 0.5 - 0.5 * (TAU * self.position as f32 / SPECTRUM_WINDOW as f32).cos();
+        let input = sample * window;
+        for ((s1, s2), coefficient) in self.states.iter_mut().zip(self.coefficients) {
+            let s = input + coefficient * *s1 - *s2;
+            *s2 = *s1;
+            *s1 = s;
+        }
+
+        self.position += 1;
+        if self.position < SPECTRUM_WINDOW {
+            return None;
+        }
+
+        // The Hann window halves the amplitude on average, the factor 2 accounts for the negative frequencies
+        let scale = 2. / (SPECTRUM_WINDOW as f32 * 0.5);
+        let mut magnitudes = [0.; SPECTRUM_BANDS];
+        for ((magnitude, (s1, s2)), coefficient) in magnitudes
+            .iter_mut()
+            .zip(self.states)
+            .zip(self.coefficients)
+        {
+            let power = s1 * s1 + s2 * s2 - coefficient * s1 * s2;
+            *magnitude = power.max(0.).sqrt() * scale;
+        }
+
+        self.reset();
+        Some(magnitudes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sine_shows_up_in_its_band() {
+        let sample_rate = 48000.;
+        let band = 20;
+        let frequency = band_frequency(band);
+        let mut analyzer = SpectrumAnalyzer::new(sample_rate);
+
+        let mut magnitudes = None;
+        for i in 0..SPECTRUM_WINDOW {
+            let sample = (TAU * frequency * i as f32 / sample_rate).sin();
+            magnitudes = analyzer.process(sample).or(magnitudes);
+        }
+        let magnitudes = magnitudes.expect("A full window should produce a frame");
+
+        assert!((magnitudes[band] - 1.).abs() < 0.05);
+        let loudest = (0..SPECTRUM_BANDS)
+            .max_by(|a, b| magnitudes[*a].total_cmp(&magnitudes[*b]))
+            .unwrap();
+        assert_eq!(loudest, band);
+        // Bands far away stay quiet
+        assert!(magnitudes[band - 8] < 0.01);
+    }
+
+    #[test]
+    fn silence_is_silent() {
+        let mut analyzer = SpectrumAnalyzer::new(44100.);
+        let magnitudes = (0..SPECTRUM_WINDOW)
+            .find_map(|_| analyzer.process(0.))
+            .unwrap();
+
+        assert!(magnitudes.iter().all(|magnitude| *magnitude == 0.));
+    }
+}
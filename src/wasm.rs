@@ -0,0 +1,158 @@
+//! Bindings to run the delay and its feedback filter in the browser.
+//!
+//! This is a stripped down stereo Delax for demo pages: a delay line per channel with a low pass in the feedback loop.
+//! Build it with [wasm-pack](https://rustwasm.github.io/wasm-pack/):
+//!
+//! ```shell
+//! wasm-pack build --target web --no-default-features --features wasm
+//! ```
+//!
+//! The page then processes its audio in blocks:
+//!
+//! ```js
+//! import init, { WebDelax } from "./pkg/delax.js";
+//!
+//! await init();
+//! const delax = new WebDelax(audioContext.sampleRate);
+//! delax.set_delay(350);
+//! delax.process_block(left, right);
+//! ```
+
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    delay_engine::engine::{interpolation_for, max_delay_buffer_size, DelayEngine, MAX_DELAY_MS},
+    filters::{simper::StereoSimperSinSVF, SVFFilterMode, MAX_CUTOFF, MIN_CUTOFF},
+    simd::F32x2,
+};
+
+/// A stereo delay with a filtered feedback loop that processes blocks of samples handed over from JavaScript
+#[wasm_bindgen]
+pub struct WebDelax {
+    delay_engines: [DelayEngine; 2],
+    filter: StereoSimperSinSVF,
+    feedback: f32,
+    wetness: f32,
+}
+
+#[wasm_bindgen]
+impl WebDelax {
+    /// Create a delay for the sample rate of the audio context, with a 500 ms delay and half of the repeats fed back
+    #[wasm_bindgen(constructor)]
+    pub fn new(sample_rate: f32) -> WebDelax {
+        let size = max_delay_buffer_size(sample_rate);
+        let mut delay_engines = [
+            DelayEngine::new(size, sample_rate),
+            DelayEngine::new(size, sample_rate),
+        ];
+        for delay_engine in &mut delay_engines {
+            delay_engine.set_delay_amount(500.);
+        }
+
+        let mut filter = StereoSimperSinSVF::new(sample_rate);
+        filter.set_mode(SVFFilterMode::Low, SVFFilterMode::Low);
+        filter.set_cutoff(F32x2::splat(4000.));
+
+        Self {
+            delay_engines,
+            filter,
+            feedback: 0.5,
+            wetness: 0.5,
+        }
+    }
+
+    /// Set the delay time of both channels in ms
+    pub fn set_delay(&mut self, delay_time: f32) {
+        for delay_engine in &mut self.delay_engines {
+            delay_engine.set_delay_amount(delay_time.clamp(0., MAX_DELAY_MS));
+        }
+    }
+
+    /// Set how much of the repeats are fed back, from 0 to 1
+    pub fn set_feedback(&mut self, feedback: f32) {
+        self.feedback = feedback.clamp(0., 1.);
+    }
+
+    /// Set the cutoff of the low pass in the feedback loop in Hz
+    pub fn set_cutoff(&mut self, cutoff: f32) {
+        self.filter
+            .set_cutoff(F32x2::splat(cutoff.clamp(MIN_CUTOFF, MAX_CUTOFF)));
+    }
+
+    /// Set the resonance of the low pass in the feedback loop, from 0 to 1
+    pub fn set_res(&mut self, res: f32) {
+        self.filter.set_res(F32x2::splat(res.clamp(0., 1.)));
+    }
+
+    /// Set the balance between the dry signal and the repeats, from 0 to 1
+    pub fn set_wetness(&mut self, wetness: f32) {
+        self.wetness = wetness.clamp(0., 1.);
+    }
+
+    /// Process a block of stereo audio in place. Both channels need to have the same length.
+    pub fn process_block(&mut self, left: &mut [f32], right: &mut [f32]) {
+        for (l, r) in left.iter_mut().zip(right.iter_mut()) {
+            let dry = F32x2::new(*l, *r);
+            let popped =
+                F32x2::from(self.delay_engines.each_ref().map(|engine| {
+                    engine.interpolate_sample(interpolation_for(engine.delay_time()))
+                }));
+
+            let written = dry + self.filter.tick_sample(popped) * self.feedback;
+            self.delay_engines[0].write_sample(written.l());
+            self.delay_engines[1].write_sample(written.r());
+
+            let output = dry * (1. - self.wetness) + popped * self.wetness;
+            (*l, *r) = output.into();
+        }
+    }
+
+    /// Clear the delay lines, so no old repeats are heard anymore
+    pub fn reset(&mut self) {
+        self.delay_engines.iter_mut().for_each(DelayEngine::reset);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WebDelax;
+
+    #[test]
+    fn blocks_repeat_the_input_after_the_delay_time() {
+        let mut delax = WebDelax::new(1000.);
+        delax.set_delay(10.);
+        delax.set_feedback(0.);
+        delax.set_wetness(1.);
+
+        let mut left = [0.; 32];
+        let mut right = [0.; 32];
+        left[0] = 1.;
+        right[0] = -1.;
+        // Two blocks, so the state carries over between them
+        let (first_l, second_l) = left.split_at_mut(16);
+        let (first_r, second_r) = right.split_at_mut(16);
+        delax.process_block(first_l, first_r);
+        delax.process_block(second_l, second_r);
+
+        assert_eq!(left[10], 1.);
+        assert_eq!(right[10], -1.);
+        assert!(left.iter().enumerate().all(|(i, s)| i == 10 || *s == 0.));
+    }
+
+    #[test]
+    fn reset_clears_the_repeats() {
+        let mut delax = WebDelax::new(1000.);
+        delax.set_delay(10.);
+        delax.set_wetness(1.);
+
+        let mut left = vec![1.; 8];
+        let mut right = vec![1.; 8];
+        delax.process_block(&mut left, &mut right);
+        delax.reset();
+
+        let mut left = vec![0.; 32];
+        let mut right = vec![0.; 32];
+        delax.process_block(&mut left, &mut right);
+        assert!(left.iter().chain(&right).all(|s| *s == 0.));
+    }
+}
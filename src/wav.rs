@@ -0,0 +1,213 @@
+use std::{fs, io, path::Path};
+
+/// Encode audio as a 32 bit float WAV file. The channels are interleaved in the file, they all need the same length.
+///
+/// Usage:
+/// ```
+/// use delax::wav::encode_wav;
+///
+/// let bytes = encode_wav(48000, &[vec![0.5, -0.5], vec![0.25, -0.25]]);
+/// assert_eq!(&bytes[..4], b"RIFF");
+/// // The header is followed by 2 frames of 2 channels of 4 bytes each
+/// assert_eq!(bytes.len(), 58 + 16);
+/// ```
+pub fn encode_wav(sample_rate: u32, channels: &[Vec<f32>]) -> Vec<u8> {
+    const FORMAT_IEEE_FLOAT: u16 = 3;
+    const BYTES_PER_SAMPLE: u16 = 4;
+
+    let num_channels = channels.len() as u16;
+    let num_frames = channels.first().map_or(0, Vec::len);
+    assert!(
+        channels.iter().all(|channel| channel.len() == num_frames),
+        "Channels need the same length"
+    );
+
+    let block_align = num_channels * BYTES_PER_SAMPLE;
+    let data_size = (num_frames * block_align as usize) as u32;
+
+    let mut bytes = Vec::with_capacity(58 + data_size as usize);
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(50 + data_size).to_le_bytes());
+    bytes.extend_from_slice(b"WAVE");
+
+    // Float formats need the extended format chunk with an empty extension
+    bytes.extend_from_slice(b"fmt ");
+    bytes.extend_from_slice(&18u32.to_le_bytes());
+    bytes.extend_from_slice(&FORMAT_IEEE_FLOAT.to_le_bytes());
+    bytes.extend_from_slice(&num_channels.to_le_bytes());
+    bytes.extend_from_slice(&sample_rate.to_le_bytes());
+    bytes.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+    bytes.extend_from_slice(&block_align.to_le_bytes());
+    bytes.extend_from_slice(&(BYTES_PER_SAMPLE * 8).to_le_bytes());
+    bytes.extend_from_slice(&0u16.to_le_bytes());
+
+    // Float formats also need a fact chunk with the amount of frames
+    bytes.extend_from_slice(b"fact");
+    bytes.extend_from_slice(&4u32.to_le_bytes());
+    bytes.extend_from_slice(&(num_frames as u32).to_le_bytes());
+
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_size.to_le_bytes());
+    for frame in 0..num_frames {
+        for channel in channels {
+            bytes.extend_from_slice(&channel[frame].to_le_bytes());
+        }
+    }
+
+    bytes
+}
+
+/// Write audio to a 32 bit float WAV file, see [encode_wav()]
+pub fn write_wav(path: &Path, sample_rate: u32, channels: &[Vec<f32>]) -> io::Result<()> {
+    fs::write(path, encode_wav(sample_rate, channels))
+}
+
+/// Decoded audio from a WAV file, with the samples of every channel converted to f32
+#[derive(Debug, Clone, PartialEq)]
+pub struct WavAudio {
+    pub sample_rate: u32,
+    pub channels: Vec<Vec<f32>>,
+}
+
+/// Decode a WAV file with 16, 24 or 32 bit integer samples or 32 bit float samples.
+///
+/// Usage:
+/// ```
+/// use delax::wav::{decode_wav, encode_wav};
+///
+/// let audio = decode_wav(&encode_wav(44100, &[vec![0.5, -0.25]])).unwrap();
+/// assert_eq!(audio.sample_rate, 44100);
+/// assert_eq!(audio.channels, vec![vec![0.5, -0.25]]);
+/// ```
+pub fn decode_wav(bytes: &[u8]) -> Result<WavAudio, String> {
+    const FORMAT_PCM: u16 = 1;
+    const FORMAT_IEEE_FLOAT: u16 = 3;
+    const FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+    if bytes.len() < 12 || &bytes[..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return Err(String::from("Not a WAV file"));
+    }
+
+    let u16_at = |index: usize| u16::from_le_bytes([bytes[index], bytes[index + 1]]);
+    let u32_at = |index: usize| u32::from_le_bytes(bytes[index..index + 4].try_into().unwrap());
+
+    // The format and the samples are the only chunks that matter, everything else is skipped
+    let mut format = None;
+    let mut data = None;
+    let mut offset = 12;
+    while offset + 8 <= bytes.len() {
+        let size = u32_at(offset + 4) as usize;
+        let body = offset + 8..(offset + 8 + size).min(bytes.len());
+        match &bytes[offset..offset + 4] {
+            b"fmt " if body.len() >= 16 => {
+                let mut tag = u16_at(body.start);
+                if tag == FORMAT_EXTENSIBLE && body.len() >= 26 {
+                    tag = u16_at(body.start + 24);
+                }
+                format = Some((
+                    tag,
+                    u16_at(body.start + 2) as usize,
+                    u32_at(body.start + 4),
+                    u16_at(body.start + 14),
+                ));
+            }
+            b"data" => data = Some(&bytes[body]),
+            _ => (),
+        }
+        // Chunks are padded to an even size
+        offset += 8 + size + size % 2;
+    }
+
+    let (tag, num_channels, sample_rate, bits) = format.ok_or("The format is missing")?;
+    let data = data.ok_or("The samples are missing")?;
+    if num_channels == 0 {
+        return Err(String::from("The file has no channels"));
+    }
+    let decode: fn(&[u8]) -> f32 = match (tag, bits) {
+        (FORMAT_PCM, 16) => |b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.,
+        (FORMAT_PCM, 24) => |b| i32::from_le_bytes([0, b[0], b[1], b[2]]) as f32 / 2147483648.,
+        (FORMAT_PCM, 32) => |b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32 / 2147483648.,
+        (FORMAT_IEEE_FLOAT, 32) => |b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+        _ => {
+            return Err(format!(
+                "{bits} bit samples of format {tag} aren't supported"
+            ))
+        }
+    };
+
+    let bytes_per_sample = bits as usize / 8;
+    let mut channels = vec![Vec::new(); num_channels];
+    for frame in data.chunks_exact(bytes_per_sample * num_channels) {
+        for (channel, sample) in channels
+            .iter_mut()
+            .zip(frame.chunks_exact(bytes_per_sample))
+        {
+            channel.push(decode(sample));
+        }
+    }
+
+    Ok(WavAudio {
+        sample_rate,
+        channels,
+    })
+}
+
+/// Read a WAV file, see [decode_wav()]
+pub fn read_wav(path: &Path) -> Result<WavAudio, String> {
+    let bytes = fs::read(path).map_err(|error| error.to_string())?;
+    decode_wav(&bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_describes_the_samples() {
+        let bytes = encode_wav(44100, &[vec![1., 0., -1.]]);
+        let u16_at = |index: usize| u16::from_le_bytes([bytes[index], bytes[index + 1]]);
+        let u32_at = |index: usize| u32::from_le_bytes(bytes[index..index + 4].try_into().unwrap());
+
+        assert_eq!(u32_at(4) as usize, bytes.len() - 8);
+        assert_eq!(&bytes[8..16], b"WAVEfmt ");
+        assert_eq!(u16_at(20), 3);
+        assert_eq!(u16_at(22), 1);
+        assert_eq!(u32_at(24), 44100);
+        assert_eq!(u32_at(28), 44100 * 4);
+        assert_eq!(u16_at(34), 32);
+        assert_eq!(u32_at(46), 3);
+        assert_eq!(&bytes[50..54], b"data");
+        assert_eq!(u32_at(54), 12);
+        assert_eq!(f32::from_le_bytes(bytes[62..66].try_into().unwrap()), 0.);
+        assert_eq!(f32::from_le_bytes(bytes[66..70].try_into().unwrap()), -1.);
+    }
+
+    #[test]
+    fn integer_samples_are_decoded() {
+        // A hand written 24 bit stereo file with a LIST chunk in front of the samples
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"RIFF\0\0\0\0WAVEfmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        for value in [1u16, 2] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.extend_from_slice(&48000u32.to_le_bytes());
+        bytes.extend_from_slice(&(48000u32 * 6).to_le_bytes());
+        for value in [6u16, 24] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.extend_from_slice(b"LIST");
+        bytes.extend_from_slice(&3u32.to_le_bytes());
+        bytes.extend_from_slice(b"abc\0");
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&6u32.to_le_bytes());
+        bytes.extend_from_slice(&[0x00, 0x00, 0x40, 0x00, 0x00, 0xC0]);
+
+        let audio = decode_wav(&bytes).unwrap();
+        assert_eq!(audio.sample_rate, 48000);
+        assert_eq!(audio.channels, vec![vec![0.5], vec![-0.5]]);
+
+        assert!(decode_wav(b"RIFF\0\0\0\0WAVE").is_err());
+        assert!(decode_wav(b"not a wav file").is_err());
+    }
+}
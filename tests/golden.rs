@@ -0,0 +1,67 @@
+//! Golden file tests of the complete DSP chain at the default settings.
+//!
+//! The fixtures are raw little endian f32 files with the stereo output interleaved.
+//! When the sound is changed on purpose, regenerate them by running the tests with `DELAX_BLESS=1`.
+
+use std::path::PathBuf;
+
+use delax::offline::{impulse, sine_sweep, OfflineRenderer};
+
+const SAMPLE_RATE: f32 = 8000.;
+const TOLERANCE: f32 = 1e-4;
+
+fn fixture_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join(format!("{name}.f32"))
+}
+
+fn check_golden(name: &str, input: &[f32]) {
+    let mut renderer = OfflineRenderer::new(SAMPLE_RATE);
+    let (left, right) = renderer.render(input, input);
+    let output: Vec<f32> = left
+        .iter()
+        .zip(&right)
+        .flat_map(|(l, r)| [*l, *r])
+        .collect();
+
+    let path = fixture_path(name);
+    if std::env::var_os("DELAX_BLESS").is_some() {
+        let bytes: Vec<u8> = output.iter().flat_map(|s| s.to_le_bytes()).collect();
+        std::fs::write(&path, bytes).expect("Couldn't write the fixture");
+        return;
+    }
+
+    let bytes = std::fs::read(&path).unwrap_or_else(|_| {
+        panic!("Missing fixture {path:?}, run the tests with DELAX_BLESS=1 to create it")
+    });
+    let expected: Vec<f32> = bytes
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect();
+
+    assert_eq!(output.len(), expected.len(), "{name}: length changed");
+    for (i, (out, exp)) in output.iter().zip(&expected).enumerate() {
+        assert!(
+            (out - exp).abs() <= TOLERANCE,
+            "{name}: sample {} of channel {} differs, expected {exp} got {out}",
+            i / 2,
+            i % 2
+        );
+    }
+}
+
+#[test]
+fn impulse_response() {
+    // Long enough for a few repeats at the default delay time
+    check_golden("impulse_response", &impulse((SAMPLE_RATE * 1.75) as usize));
+}
+
+#[test]
+fn sine_sweep_response() {
+    check_golden(
+        "sine_sweep",
+        &sine_sweep(20., SAMPLE_RATE / 2., SAMPLE_RATE as usize, SAMPLE_RATE),
+    );
+}
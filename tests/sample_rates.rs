@@ -0,0 +1,86 @@
+//! Tests of the filters across sample rates.
+//!
+//! A host can initialize the plugin again at another sample rate at any time. The filters need to
+//! keep their tuning in Hz, no matter which sample rate the plugin started at.
+
+use delax::offline::OfflineRenderer;
+
+const SAMPLE_RATES: [f32; 4] = [44100., 48000., 96000., 192000.];
+/// The level differences that are allowed between sample rates, in dB
+const TOLERANCE_DB: f32 = 0.5;
+
+fn sine(freq: f32, length: usize, sample_rate: f32) -> Vec<f32> {
+    (0..length)
+        .map(|i| (std::f32::consts::TAU * freq * i as f32 / sample_rate).sin() * 0.5)
+        .collect()
+}
+
+/// The level of the first repeat of a sine in dB.
+///
+/// The dry signal is taken out of the output, and only the time after the first repeat settled and before the
+/// second one starts is measured, so only the input filter shapes the level.
+fn first_repeat_db(renderer: &mut OfflineRenderer, freq: f32, sample_rate: f32) -> f32 {
+    let length = sample_rate as usize;
+    let input = sine(freq, length, sample_rate);
+    let (left, _) = renderer.render(&input, &input);
+
+    let start = length * 3 / 4;
+    let energy = left[start..]
+        .iter()
+        .zip(&input[start..])
+        .map(|(out, dry)| (out - dry * 0.5).powi(2))
+        .sum::<f32>()
+        / (length - start) as f32;
+    10. * energy.log10()
+}
+
+/// Levels around the cutoff, so a filter tuned to the wrong sample rate stands out
+fn response(renderer: &mut OfflineRenderer, sample_rate: f32) -> Vec<f32> {
+    [250., 500., 2000.]
+        .into_iter()
+        .map(|freq| first_repeat_db(renderer, freq, sample_rate))
+        .collect()
+}
+
+fn assert_same_response(actual: &[f32], expected: &[f32], context: &str) {
+    for (actual, expected) in actual.iter().zip(expected) {
+        assert!(
+            (actual - expected).abs() <= TOLERANCE_DB,
+            "{context}: expected {expected} dB got {actual} dB"
+        );
+    }
+}
+
+#[test]
+fn reinitializing_keeps_the_tuning() {
+    let reference = response(&mut OfflineRenderer::new(44100.), 44100.);
+
+    let mut renderer = OfflineRenderer::new(44100.);
+    for sample_rate in SAMPLE_RATES {
+        renderer.reinitialize(sample_rate);
+        let reinitialized = response(&mut renderer, sample_rate);
+        let fresh = response(&mut OfflineRenderer::new(sample_rate), sample_rate);
+
+        assert_same_response(
+            &reinitialized,
+            &fresh,
+            &format!("reinitialized at {sample_rate} Hz"),
+        );
+        assert_same_response(&fresh, &reference, &format!("fresh at {sample_rate} Hz"));
+    }
+}
+
+#[test]
+fn reinitializing_back_and_forth() {
+    let reference = response(&mut OfflineRenderer::new(48000.), 48000.);
+
+    let mut renderer = OfflineRenderer::new(48000.);
+    for sample_rate in [192000., 44100., 48000.] {
+        renderer.reinitialize(sample_rate);
+    }
+    assert_same_response(
+        &response(&mut renderer, 48000.),
+        &reference,
+        "back at 48000 Hz",
+    );
+}